@@ -134,6 +134,12 @@ impl UplinkFrame {
                     context: rx_info.context.clone(),
                     metadata: rx_info.metadata.clone(),
                     crc_status: rx_info.crc_status,
+                    encrypted_fine_timestamp: match &rx_info.fine_timestamp {
+                        Some(uplink_rx_info_legacy::FineTimestamp::EncryptedFineTimestamp(v)) => {
+                            Some(v.clone())
+                        }
+                        _ => None,
+                    },
                 });
             }
         }
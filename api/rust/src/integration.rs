@@ -36,3 +36,16 @@ impl Into<String> for LogCode {
         .to_string()
     }
 }
+
+#[allow(clippy::from_over_into)]
+impl Into<String> for DownlinkState {
+    fn into(self) -> String {
+        match self {
+            DownlinkState::Enqueued => "ENQUEUED",
+            DownlinkState::Rejected => "REJECTED",
+            DownlinkState::Expired => "EXPIRED",
+            DownlinkState::Dropped => "DROPPED",
+        }
+        .to_string()
+    }
+}
@@ -0,0 +1,5 @@
+alter table device_profile
+  add column lr_fhss_rx_window smallint not null default 0;
+
+alter table device_profile
+  alter column lr_fhss_rx_window drop default;
@@ -0,0 +1,2 @@
+alter table gateway
+  add column fine_timestamp_key bytea null;
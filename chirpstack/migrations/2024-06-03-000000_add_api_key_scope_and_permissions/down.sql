@@ -0,0 +1,12 @@
+drop index idx_api_key_device_profile_id;
+drop index idx_api_key_application_id;
+
+alter table api_key
+  drop column last_used_at,
+  drop column expires_at,
+  drop column can_read_keys,
+  drop column can_enqueue,
+  drop column can_write,
+  drop column can_read,
+  drop column device_profile_id,
+  drop column application_id;
@@ -0,0 +1,18 @@
+alter table api_key
+  add column application_id uuid null references application on delete cascade,
+  add column device_profile_id uuid null references device_profile on delete cascade,
+  add column can_read boolean not null default true,
+  add column can_write boolean not null default true,
+  add column can_enqueue boolean not null default true,
+  add column can_read_keys boolean not null default true,
+  add column expires_at timestamp with time zone null,
+  add column last_used_at timestamp with time zone null;
+
+alter table api_key
+  alter column can_read drop default,
+  alter column can_write drop default,
+  alter column can_enqueue drop default,
+  alter column can_read_keys drop default;
+
+create index idx_api_key_application_id on api_key (application_id);
+create index idx_api_key_device_profile_id on api_key (device_profile_id);
@@ -0,0 +1,7 @@
+drop index idx_audit_log_api_key_id;
+drop index idx_audit_log_user_id;
+drop index idx_audit_log_dev_eui;
+drop index idx_audit_log_application_id;
+drop index idx_audit_log_tenant_id;
+drop index idx_audit_log_created_at;
+drop table audit_log;
@@ -0,0 +1,21 @@
+create table audit_log (
+  id uuid primary key,
+  created_at timestamp with time zone not null,
+  user_id uuid null,
+  api_key_id uuid null,
+  service varchar(100) not null,
+  method varchar(100) not null,
+  status_code varchar(20) not null,
+  tenant_id uuid null,
+  application_id uuid null,
+  dev_eui bytea null,
+  metadata jsonb not null,
+  changes jsonb not null
+);
+
+create index idx_audit_log_created_at on audit_log (created_at);
+create index idx_audit_log_tenant_id on audit_log (tenant_id);
+create index idx_audit_log_application_id on audit_log (application_id);
+create index idx_audit_log_dev_eui on audit_log (dev_eui);
+create index idx_audit_log_user_id on audit_log (user_id);
+create index idx_audit_log_api_key_id on audit_log (api_key_id);
@@ -0,0 +1,8 @@
+drop index idx_tenant_user_role_user_id_role_id_application_id;
+drop index idx_tenant_user_role_application_id;
+drop index idx_tenant_user_role_role_id;
+drop index idx_tenant_user_role_tenant_id_user_id;
+drop table tenant_user_role;
+
+drop index idx_tenant_role_tenant_id_name;
+drop table tenant_role;
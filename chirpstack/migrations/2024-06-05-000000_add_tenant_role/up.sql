@@ -0,0 +1,36 @@
+create table tenant_role (
+  id uuid primary key,
+  tenant_id uuid not null references tenant on delete cascade,
+  created_at timestamp with time zone not null,
+  updated_at timestamp with time zone not null,
+  name varchar(100) not null,
+  description text not null,
+  can_write_applications boolean not null,
+  can_write_integrations boolean not null,
+  can_write_devices boolean not null,
+  can_enqueue boolean not null,
+  can_write_device_profiles boolean not null,
+  can_write_gateways boolean not null,
+  can_write_multicast_groups boolean not null
+);
+
+create unique index idx_tenant_role_tenant_id_name on tenant_role (tenant_id, name);
+
+create table tenant_user_role (
+  id uuid primary key,
+  tenant_id uuid not null,
+  user_id uuid not null references "user" on delete cascade,
+  role_id uuid not null references tenant_role on delete cascade,
+  application_id uuid null references application on delete cascade,
+  created_at timestamp with time zone not null,
+  foreign key (tenant_id, user_id) references tenant_user on delete cascade
+);
+
+create index idx_tenant_user_role_tenant_id_user_id on tenant_user_role (tenant_id, user_id);
+create index idx_tenant_user_role_role_id on tenant_user_role (role_id);
+create index idx_tenant_user_role_application_id on tenant_user_role (application_id);
+create unique index idx_tenant_user_role_user_id_role_id_application_id on tenant_user_role (
+  user_id,
+  role_id,
+  coalesce(application_id, '00000000-0000-0000-0000-000000000000')
+);
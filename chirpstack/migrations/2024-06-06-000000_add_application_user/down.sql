@@ -0,0 +1,3 @@
+drop index idx_application_user_tenant_id_user_id;
+drop index idx_application_user_user_id;
+drop table application_user;
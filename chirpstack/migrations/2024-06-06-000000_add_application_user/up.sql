@@ -0,0 +1,14 @@
+create table application_user (
+  application_id uuid not null references application on delete cascade,
+  user_id uuid not null references "user" on delete cascade,
+  tenant_id uuid not null,
+  created_at timestamp with time zone not null,
+  updated_at timestamp with time zone not null,
+  is_admin boolean not null,
+  is_device_admin boolean not null,
+  primary key (application_id, user_id),
+  foreign key (tenant_id, user_id) references tenant_user on delete cascade
+);
+
+create index idx_application_user_user_id on application_user (user_id);
+create index idx_application_user_tenant_id_user_id on application_user (tenant_id, user_id);
@@ -0,0 +1,14 @@
+drop index idx_user_recovery_code_user_id;
+drop table user_recovery_code;
+
+drop index idx_user_password_history_user_id;
+drop table user_password_history;
+
+alter table "user"
+  drop column sessions_revoked_at,
+  drop column locked_until,
+  drop column failed_login_count,
+  drop column totp_last_counter,
+  drop column totp_enabled,
+  drop column totp_kek_label,
+  drop column totp_secret;
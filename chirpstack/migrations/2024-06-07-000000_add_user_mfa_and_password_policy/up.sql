@@ -0,0 +1,32 @@
+alter table "user"
+  add column totp_secret bytea null,
+  add column totp_kek_label varchar(100) not null default '',
+  add column totp_enabled boolean not null default false,
+  add column totp_last_counter bigint not null default 0,
+  add column failed_login_count integer not null default 0,
+  add column locked_until timestamp with time zone null,
+  add column sessions_revoked_at timestamp with time zone null;
+
+alter table "user"
+  alter column totp_kek_label drop default,
+  alter column totp_enabled drop default,
+  alter column totp_last_counter drop default,
+  alter column failed_login_count drop default;
+
+create table user_password_history (
+  id uuid primary key,
+  user_id uuid not null references "user" on delete cascade,
+  created_at timestamp with time zone not null,
+  password_hash varchar(200) not null
+);
+
+create index idx_user_password_history_user_id on user_password_history (user_id);
+
+create table user_recovery_code (
+  id uuid primary key,
+  user_id uuid not null references "user" on delete cascade,
+  created_at timestamp with time zone not null,
+  code_hash varchar(64) not null
+);
+
+create index idx_user_recovery_code_user_id on user_recovery_code (user_id);
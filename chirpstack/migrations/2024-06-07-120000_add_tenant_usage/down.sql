@@ -0,0 +1 @@
+drop table tenant_usage;
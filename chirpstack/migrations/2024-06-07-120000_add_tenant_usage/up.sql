@@ -0,0 +1,14 @@
+create table tenant_usage (
+  tenant_id uuid not null references tenant on delete cascade,
+  aggregation varchar(10) not null,
+  time timestamp with time zone not null,
+  uplinks bigint not null,
+  uplink_bytes bigint not null,
+  downlinks bigint not null,
+  downlink_bytes bigint not null,
+  active_devices bigint not null,
+  gateway_airtime_ms bigint not null,
+  roaming_uplinks bigint not null,
+
+  primary key (tenant_id, aggregation, time)
+);
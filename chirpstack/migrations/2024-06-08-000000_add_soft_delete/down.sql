@@ -0,0 +1,16 @@
+drop index idx_gateway_deleted_at;
+drop index idx_device_deleted_at;
+drop index idx_application_deleted_at;
+drop index idx_tenant_deleted_at;
+
+alter table gateway
+  drop column deleted_at;
+
+alter table device
+  drop column deleted_at;
+
+alter table application
+  drop column deleted_at;
+
+alter table tenant
+  drop column deleted_at;
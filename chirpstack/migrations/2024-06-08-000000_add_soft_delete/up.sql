@@ -0,0 +1,16 @@
+alter table tenant
+  add column deleted_at timestamp with time zone null;
+
+alter table application
+  add column deleted_at timestamp with time zone null;
+
+alter table device
+  add column deleted_at timestamp with time zone null;
+
+alter table gateway
+  add column deleted_at timestamp with time zone null;
+
+create index idx_tenant_deleted_at on tenant (deleted_at);
+create index idx_application_deleted_at on application (deleted_at);
+create index idx_device_deleted_at on device (deleted_at);
+create index idx_gateway_deleted_at on gateway (deleted_at);
@@ -0,0 +1,2 @@
+alter table tenant_user
+  drop column scim_group;
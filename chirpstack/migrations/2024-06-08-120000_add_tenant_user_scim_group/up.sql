@@ -0,0 +1,2 @@
+alter table tenant_user
+  add column scim_group varchar(100) null;
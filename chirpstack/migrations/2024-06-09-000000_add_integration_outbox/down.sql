@@ -0,0 +1,4 @@
+drop index idx_integration_outbox_dead_at;
+drop index idx_integration_outbox_next_attempt_at;
+drop index idx_integration_outbox_application_id_kind;
+drop table integration_outbox;
@@ -0,0 +1,17 @@
+create table integration_outbox (
+  id uuid primary key,
+  created_at timestamp with time zone not null,
+  application_id uuid not null references application on delete cascade,
+  kind varchar(20) not null,
+  event varchar(20) not null,
+  vars jsonb not null,
+  payload bytea not null,
+  attempts integer not null default 0,
+  next_attempt_at timestamp with time zone not null,
+  last_error text not null default '',
+  dead_at timestamp with time zone null
+);
+
+create index idx_integration_outbox_application_id_kind on integration_outbox (application_id, kind);
+create index idx_integration_outbox_next_attempt_at on integration_outbox (next_attempt_at);
+create index idx_integration_outbox_dead_at on integration_outbox (dead_at);
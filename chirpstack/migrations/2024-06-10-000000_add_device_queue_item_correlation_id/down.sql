@@ -0,0 +1,2 @@
+alter table device_queue_item
+  drop column correlation_id;
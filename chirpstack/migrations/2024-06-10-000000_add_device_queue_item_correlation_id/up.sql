@@ -0,0 +1,2 @@
+alter table device_queue_item
+  add column correlation_id varchar(100) not null default '';
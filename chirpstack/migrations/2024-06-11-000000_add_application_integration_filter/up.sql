@@ -0,0 +1,2 @@
+alter table application_integration
+  add column filter jsonb not null default '{}';
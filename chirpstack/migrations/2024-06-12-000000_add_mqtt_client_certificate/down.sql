@@ -0,0 +1,3 @@
+drop index idx_mqtt_client_certificate_revoked_at;
+drop index idx_mqtt_client_certificate_application_id;
+drop table mqtt_client_certificate;
@@ -0,0 +1,10 @@
+create table mqtt_client_certificate (
+  serial_number varchar(40) primary key,
+  created_at timestamp with time zone not null,
+  application_id uuid not null references application on delete cascade,
+  expires_at timestamp with time zone not null,
+  revoked_at timestamp with time zone null
+);
+
+create index idx_mqtt_client_certificate_application_id on mqtt_client_certificate (application_id);
+create index idx_mqtt_client_certificate_revoked_at on mqtt_client_certificate (revoked_at);
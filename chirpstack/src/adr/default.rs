@@ -3,6 +3,7 @@ use async_trait::async_trait;
 
 use super::{Handler, Request, Response};
 use crate::region;
+use lrwn::region::airtime;
 
 pub struct Algorithm {}
 
@@ -77,6 +78,36 @@ impl Algorithm {
         max_snr
     }
 
+    // Returns the typical (max.) PHYPayload size of the uplink history.
+    fn get_typical_phy_payload_size(&self, req: &Request) -> usize {
+        req.uplink_history
+            .iter()
+            .map(|uh| uh.phy_payload_size as usize)
+            .max()
+            .unwrap_or(0)
+    }
+
+    // Returns the min. DR (up to max_dr) at which the given payload-size can be sent within
+    // the 400ms dwell-time. If none of the data-rates qualify, max_dr is returned.
+    fn get_dwell_time_min_dr(
+        &self,
+        region_conf: &(dyn lrwn::region::Region + Sync + Send),
+        phy_payload_size: usize,
+        dr: u8,
+        max_dr: u8,
+    ) -> Result<u8> {
+        for dr in dr..=max_dr {
+            let modulation = region_conf.get_data_rate(dr)?;
+            if airtime::get_time_on_air(&modulation, phy_payload_size, true)?
+                <= airtime::DWELL_TIME_400MS
+            {
+                return Ok(dr);
+            }
+        }
+
+        Ok(max_dr)
+    }
+
     fn get_nb_trans(&self, current_nb_trans: u8, pkt_loss_rate: f32) -> u8 {
         let pkt_loss_table: [[u8; 3]; 4] = [[1, 1, 2], [1, 2, 3], [2, 3, 3], [3, 3, 3]];
 
@@ -175,6 +206,14 @@ impl Handler for Algorithm {
             resp.dr = max_dr;
         }
 
+        // Raise the DR if the typical payload-size of the device can not be sent within the
+        // 400ms dwell-time at the current DR.
+        let phy_payload_size = self.get_typical_phy_payload_size(req);
+        if req.uplink_dwell_time_400ms && phy_payload_size != 0 && resp.dr < max_dr {
+            resp.dr =
+                self.get_dwell_time_min_dr(&**region_conf, phy_payload_size, resp.dr, max_dr)?;
+        }
+
         // Set the new nb_trans;
         resp.nb_trans = self.get_nb_trans(req.nb_trans, self.get_packet_loss_percentage(req));
 
@@ -236,6 +275,7 @@ mod test {
             installation_margin: 0.0,
             min_dr: 0,
             max_dr: 0,
+            uplink_dwell_time_400ms: false,
             uplink_history: vec![],
             skip_f_cnt_check: false,
             device_variables: Default::default(),
@@ -460,6 +500,7 @@ mod test {
             installation_margin: 0.0,
             min_dr: 0,
             max_dr: 0,
+            uplink_dwell_time_400ms: false,
             uplink_history: vec![],
             skip_f_cnt_check: false,
             device_variables: Default::default(),
@@ -500,6 +541,7 @@ mod test {
             installation_margin: 0.0,
             min_dr: 0,
             max_dr: 0,
+            uplink_dwell_time_400ms: false,
             uplink_history: vec![],
             skip_f_cnt_check: false,
             device_variables: Default::default(),
@@ -575,6 +617,31 @@ mod test {
                     nb_trans: 1,
                 },
             },
+            Test {
+                name: "dwell-time exceeded, increase dr".into(),
+                request: Request {
+                    region_config_id: "eu868".into(),
+                    adr: true,
+                    dr: 0,
+                    tx_power_index: 0,
+                    nb_trans: 1,
+                    max_dr: 5,
+                    max_tx_power_index: 5,
+                    required_snr_for_dr: -20.0,
+                    uplink_dwell_time_400ms: true,
+                    uplink_history: vec![internal::UplinkAdrHistory {
+                        max_snr: -20.0,
+                        phy_payload_size: 51,
+                        ..Default::default()
+                    }],
+                    ..req_template.clone()
+                },
+                response: Response {
+                    dr: 3,
+                    tx_power_index: 0,
+                    nb_trans: 1,
+                },
+            },
         ];
 
         for tst in &tests {
@@ -206,6 +206,7 @@ pub mod test {
             installation_margin: 0.0,
             min_dr: 0,
             max_dr: 0,
+            uplink_dwell_time_400ms: false,
             uplink_history: vec![],
             skip_f_cnt_check: false,
             device_variables: Default::default(),
@@ -108,6 +108,7 @@ pub struct Request {
     pub installation_margin: f32,
     pub min_dr: u8,
     pub max_dr: u8,
+    pub uplink_dwell_time_400ms: bool,
     pub uplink_history: Vec<internal::UplinkAdrHistory>,
     pub skip_f_cnt_check: bool,
     pub device_variables: HashMap<String, String>,
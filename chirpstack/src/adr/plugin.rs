@@ -78,6 +78,7 @@ impl Handler for Plugin {
             input.set("installationMargin", req.installation_margin)?;
             input.set("minDr", req.min_dr)?;
             input.set("maxDr", req.max_dr)?;
+            input.set("uplinkDwellTime400ms", req.uplink_dwell_time_400ms)?;
             input.set("deviceVariables", device_variables)?;
 
             let mut uplink_history: Vec<rquickjs::Object> = Vec::new();
@@ -89,6 +90,7 @@ impl Handler for Plugin {
                 obj.set("maxRssi", uh.max_rssi)?;
                 obj.set("txPowerIndex", uh.tx_power_index)?;
                 obj.set("gatewayCount", uh.gateway_count)?;
+                obj.set("phyPayloadSize", uh.phy_payload_size)?;
                 uplink_history.push(obj);
             }
 
@@ -134,6 +136,7 @@ pub mod test {
             installation_margin: 10.0,
             min_dr: 0,
             max_dr: 5,
+            uplink_dwell_time_400ms: false,
             uplink_history: vec![],
             skip_f_cnt_check: false,
             device_variables: Default::default(),
@@ -6,11 +6,15 @@ use uuid::Uuid;
 use chirpstack_api::api;
 use chirpstack_api::api::application_service_server::ApplicationService;
 
-use super::auth::validator;
+use super::audit;
+use super::auth::{validator, AuthID};
 use super::error::ToStatus;
-use super::helpers;
+use super::helpers::{self, FromProto, ToProto};
 use crate::certificate;
-use crate::storage::{application, fields};
+use crate::integration;
+use crate::storage::{
+    application, fields, integration_outbox, mqtt_client_certificate, tenant, user,
+};
 
 pub struct Application {
     validator: validator::RequestValidator,
@@ -58,6 +62,8 @@ impl ApplicationService for Application {
         });
         resp.metadata_mut()
             .insert("x-log-application_id", a.id.to_string().parse().unwrap());
+        resp.extensions_mut()
+            .insert(audit::Changes::new(None, Some(&a)));
 
         Ok(resp)
     }
@@ -118,7 +124,9 @@ impl ApplicationService for Application {
             )
             .await?;
 
-        let _ = application::update(application::Application {
+        let a_before = application::get(&app_id).await.map_err(|e| e.status())?;
+
+        let a = application::update(application::Application {
             id: app_id,
             name: req_app.name.to_string(),
             description: req_app.description.to_string(),
@@ -131,6 +139,8 @@ impl ApplicationService for Application {
         let mut resp = Response::new(());
         resp.metadata_mut()
             .insert("x-log-application_id", req_app.id.parse().unwrap());
+        resp.extensions_mut()
+            .insert(audit::Changes::new(Some(&a_before), Some(&a)));
 
         Ok(resp)
     }
@@ -149,11 +159,44 @@ impl ApplicationService for Application {
             )
             .await?;
 
+        let a = application::get(&app_id).await.map_err(|e| e.status())?;
         application::delete(&app_id).await.map_err(|e| e.status())?;
+        integration::invalidate_cache(&app_id).await;
+
+        let mut resp = Response::new(());
+        resp.metadata_mut()
+            .insert("x-log-application_id", req.id.parse().unwrap());
+        resp.extensions_mut()
+            .insert(audit::Changes::new(Some(&a), None));
+
+        Ok(resp)
+    }
+
+    async fn restore(
+        &self,
+        request: Request<api::RestoreApplicationRequest>,
+    ) -> Result<Response<()>, Status> {
+        let req = request.get_ref();
+        let app_id = Uuid::from_str(&req.id).map_err(|e| e.status())?;
+
+        self.validator
+            .validate(
+                request.extensions(),
+                validator::ValidateApplicationAccess::new(validator::Flag::Delete, app_id),
+            )
+            .await?;
+
+        application::restore(&app_id)
+            .await
+            .map_err(|e| e.status())?;
+        integration::invalidate_cache(&app_id).await;
+        let a = application::get(&app_id).await.map_err(|e| e.status())?;
 
         let mut resp = Response::new(());
         resp.metadata_mut()
             .insert("x-log-application_id", req.id.parse().unwrap());
+        resp.extensions_mut()
+            .insert(audit::Changes::new(None, Some(&a)));
 
         Ok(resp)
     }
@@ -172,15 +215,31 @@ impl ApplicationService for Application {
             )
             .await?;
 
-        let filters = application::Filters {
+        let mut filters = application::Filters {
             tenant_id: Some(tenant_id),
             search: if req.search.is_empty() {
                 None
             } else {
                 Some(req.search.to_string())
             },
+            deleted: req.deleted,
+            ..Default::default()
         };
 
+        // Users which are not global or tenant admin might be restricted to specific
+        // applications within the tenant.
+        if let AuthID::User(id) = request.extensions().get::<AuthID>().unwrap() {
+            let u = user::get(id).await.map_err(|e| e.status())?;
+            if !u.is_admin {
+                let tu = tenant::get_user(&tenant_id, &u.id)
+                    .await
+                    .map_err(|e| e.status())?;
+                if !tu.is_admin {
+                    filters.user_id = Some(u.id);
+                }
+            }
+        }
+
         let count = application::get_count(&filters)
             .await
             .map_err(|e| e.status())?;
@@ -198,6 +257,10 @@ impl ApplicationService for Application {
                     updated_at: Some(helpers::datetime_to_prost_timestamp(&a.updated_at)),
                     name: a.name.clone(),
                     description: a.description.clone(),
+                    deleted_at: a
+                        .deleted_at
+                        .as_ref()
+                        .map(helpers::datetime_to_prost_timestamp),
                 })
                 .collect(),
         });
@@ -217,7 +280,10 @@ impl ApplicationService for Application {
         self.validator
             .validate(
                 request.extensions(),
-                validator::ValidateApplicationAccess::new(validator::Flag::Read, app_id),
+                validator::ValidateApplicationIntegrationsAccess::new(
+                    validator::Flag::Read,
+                    app_id,
+                ),
             )
             .await?;
 
@@ -228,21 +294,7 @@ impl ApplicationService for Application {
         let mut items: Vec<api::IntegrationListItem> = result
             .iter()
             .map(|i| api::IntegrationListItem {
-                kind: match i.kind {
-                    application::IntegrationKind::Http => api::IntegrationKind::Http,
-                    application::IntegrationKind::InfluxDb => api::IntegrationKind::InfluxDb,
-                    application::IntegrationKind::ThingsBoard => api::IntegrationKind::ThingsBoard,
-                    application::IntegrationKind::MyDevices => api::IntegrationKind::MyDevices,
-                    application::IntegrationKind::LoraCloud => api::IntegrationKind::LoraCloud,
-                    application::IntegrationKind::GcpPubSub => api::IntegrationKind::GcpPubSub,
-                    application::IntegrationKind::AwsSns => api::IntegrationKind::AwsSns,
-                    application::IntegrationKind::AzureServiceBus => {
-                        api::IntegrationKind::AzureServiceBus
-                    }
-                    application::IntegrationKind::PilotThings => api::IntegrationKind::PilotThings,
-                    application::IntegrationKind::Ifttt => api::IntegrationKind::Ifttt,
-                }
-                .into(),
+                kind: i.kind.to_proto().into(),
             })
             .collect();
         items.push(api::IntegrationListItem {
@@ -274,36 +326,50 @@ impl ApplicationService for Application {
         self.validator
             .validate(
                 request.extensions(),
-                validator::ValidateApplicationAccess::new(validator::Flag::Update, app_id),
+                validator::ValidateApplicationIntegrationsAccess::new(
+                    validator::Flag::Update,
+                    app_id,
+                ),
             )
             .await?;
 
+        let conf = application::HttpConfiguration {
+            headers: req_int.headers.clone(),
+            json: match req_int.encoding() {
+                api::Encoding::Protobuf => false,
+                api::Encoding::Json => true,
+            },
+            event_endpoint_url: req_int.event_endpoint_url.clone(),
+            timeout_ms: req_int.timeout_ms,
+            signing_secret: req_int.signing_secret.clone(),
+            ca_cert: req_int.ca_cert.clone(),
+            tls_cert: req_int.tls_cert.clone(),
+            tls_key: req_int.tls_key.clone(),
+        };
+        integration::validate_http_configuration(&conf)
+            .map_err(|e| Status::invalid_argument(format!("{:#}", e)))?;
+
         let i = application::Integration {
             application_id: app_id,
             kind: application::IntegrationKind::Http,
-            configuration: application::IntegrationConfiguration::Http(
-                application::HttpConfiguration {
-                    headers: req_int.headers.clone(),
-                    json: match req_int.encoding() {
-                        api::Encoding::Protobuf => false,
-                        api::Encoding::Json => true,
-                    },
-                    event_endpoint_url: req_int.event_endpoint_url.clone(),
-                },
-            ),
+            configuration: application::IntegrationConfiguration::Http(conf),
             ..Default::default()
         };
 
-        let _ = application::create_integration(i)
+        let i = application::create_integration(i)
             .await
             .map_err(|e| e.status())?;
 
+        integration::invalidate_cache(&app_id).await;
+
         let mut resp = Response::new(());
         resp.metadata_mut().insert(
             "x-log-application_id",
             req_int.application_id.parse().unwrap(),
         );
 
+        resp.extensions_mut()
+            .insert(audit::Changes::new(None, Some(&i)));
         Ok(resp)
     }
 
@@ -317,7 +383,10 @@ impl ApplicationService for Application {
         self.validator
             .validate(
                 request.extensions(),
-                validator::ValidateApplicationAccess::new(validator::Flag::Read, app_id),
+                validator::ValidateApplicationIntegrationsAccess::new(
+                    validator::Flag::Read,
+                    app_id,
+                ),
             )
             .await?;
 
@@ -336,6 +405,12 @@ impl ApplicationService for Application {
                     }
                     .into(),
                     event_endpoint_url: conf.event_endpoint_url.clone(),
+                    timeout_ms: conf.timeout_ms,
+                    // The signing secret and TLS key are write-only.
+                    signing_secret: "".into(),
+                    ca_cert: conf.ca_cert.clone(),
+                    tls_cert: conf.tls_cert.clone(),
+                    tls_key: "".into(),
                 }),
             });
             resp.metadata_mut()
@@ -362,34 +437,70 @@ impl ApplicationService for Application {
         self.validator
             .validate(
                 request.extensions(),
-                validator::ValidateApplicationAccess::new(validator::Flag::Update, app_id),
+                validator::ValidateApplicationIntegrationsAccess::new(
+                    validator::Flag::Update,
+                    app_id,
+                ),
             )
             .await?;
 
-        let _ = application::update_integration(application::Integration {
+        // As the signing secret and TLS key are not returned by Get, the current values are
+        // kept when these are not set. The TLS key is removed together with the TLS certificate.
+        let i_before = application::get_integration(&app_id, application::IntegrationKind::Http)
+            .await
+            .map_err(|e| e.status())?;
+        let (signing_secret, tls_key) = match &i_before.configuration {
+            application::IntegrationConfiguration::Http(conf) => {
+                (conf.signing_secret.clone(), conf.tls_key.clone())
+            }
+            _ => return Err(Status::internal("Integration has no Http configuration")),
+        };
+
+        let conf = application::HttpConfiguration {
+            headers: req_int.headers.clone(),
+            json: match req_int.encoding() {
+                api::Encoding::Protobuf => false,
+                api::Encoding::Json => true,
+            },
+            event_endpoint_url: req_int.event_endpoint_url.clone(),
+            timeout_ms: req_int.timeout_ms,
+            signing_secret: if req_int.signing_secret.is_empty() {
+                signing_secret
+            } else {
+                req_int.signing_secret.clone()
+            },
+            ca_cert: req_int.ca_cert.clone(),
+            tls_cert: req_int.tls_cert.clone(),
+            tls_key: if req_int.tls_cert.is_empty() {
+                "".into()
+            } else if req_int.tls_key.is_empty() {
+                tls_key
+            } else {
+                req_int.tls_key.clone()
+            },
+        };
+        integration::validate_http_configuration(&conf)
+            .map_err(|e| Status::invalid_argument(format!("{:#}", e)))?;
+
+        let i = application::update_integration(application::Integration {
             application_id: app_id,
             kind: application::IntegrationKind::Http,
-            configuration: application::IntegrationConfiguration::Http(
-                application::HttpConfiguration {
-                    headers: req_int.headers.clone(),
-                    json: match req_int.encoding() {
-                        api::Encoding::Protobuf => false,
-                        api::Encoding::Json => true,
-                    },
-                    event_endpoint_url: req_int.event_endpoint_url.clone(),
-                },
-            ),
+            configuration: application::IntegrationConfiguration::Http(conf),
             ..Default::default()
         })
         .await
         .map_err(|e| e.status())?;
 
+        integration::invalidate_cache(&app_id).await;
+
         let mut resp = Response::new(());
         resp.metadata_mut().insert(
             "x-log-application_id",
             req_int.application_id.parse().unwrap(),
         );
 
+        resp.extensions_mut()
+            .insert(audit::Changes::new(Some(&i_before), Some(&i)));
         Ok(resp)
     }
 
@@ -403,18 +514,29 @@ impl ApplicationService for Application {
         self.validator
             .validate(
                 request.extensions(),
-                validator::ValidateApplicationAccess::new(validator::Flag::Update, app_id),
+                validator::ValidateApplicationIntegrationsAccess::new(
+                    validator::Flag::Update,
+                    app_id,
+                ),
             )
             .await?;
 
+        let i = application::get_integration(&app_id, application::IntegrationKind::Http)
+            .await
+            .map_err(|e| e.status())?;
+
         application::delete_integration(&app_id, application::IntegrationKind::Http)
             .await
             .map_err(|e| e.status())?;
 
+        integration::invalidate_cache(&app_id).await;
+
         let mut resp = Response::new(());
         resp.metadata_mut()
             .insert("x-log-application_id", req.application_id.parse().unwrap());
 
+        resp.extensions_mut()
+            .insert(audit::Changes::new(Some(&i), None));
         Ok(resp)
     }
 
@@ -433,7 +555,10 @@ impl ApplicationService for Application {
         self.validator
             .validate(
                 request.extensions(),
-                validator::ValidateApplicationAccess::new(validator::Flag::Update, app_id),
+                validator::ValidateApplicationIntegrationsAccess::new(
+                    validator::Flag::Update,
+                    app_id,
+                ),
             )
             .await?;
 
@@ -452,21 +577,28 @@ impl ApplicationService for Application {
                     token: req_int.token.clone(),
                     organization: req_int.organization.clone(),
                     bucket: req_int.bucket.clone(),
+                    flush_interval_ms: req_int.flush_interval_ms,
+                    tag_variables: req_int.tag_variables.clone(),
+                    rx_metadata: req_int.rx_metadata,
                 },
             ),
             ..Default::default()
         };
 
-        let _ = application::create_integration(i)
+        let i = application::create_integration(i)
             .await
             .map_err(|e| e.status())?;
 
+        integration::invalidate_cache(&app_id).await;
+
         let mut resp = Response::new(());
         resp.metadata_mut().insert(
             "x-log-application_id",
             req_int.application_id.parse().unwrap(),
         );
 
+        resp.extensions_mut()
+            .insert(audit::Changes::new(None, Some(&i)));
         Ok(resp)
     }
 
@@ -480,7 +612,10 @@ impl ApplicationService for Application {
         self.validator
             .validate(
                 request.extensions(),
-                validator::ValidateApplicationAccess::new(validator::Flag::Read, app_id),
+                validator::ValidateApplicationIntegrationsAccess::new(
+                    validator::Flag::Read,
+                    app_id,
+                ),
             )
             .await?;
 
@@ -502,6 +637,9 @@ impl ApplicationService for Application {
                     token: conf.token.clone(),
                     organization: conf.organization.clone(),
                     bucket: conf.bucket.clone(),
+                    flush_interval_ms: conf.flush_interval_ms,
+                    tag_variables: conf.tag_variables.clone(),
+                    rx_metadata: conf.rx_metadata,
                 }),
             });
             resp.metadata_mut()
@@ -530,11 +668,19 @@ impl ApplicationService for Application {
         self.validator
             .validate(
                 request.extensions(),
-                validator::ValidateApplicationAccess::new(validator::Flag::Update, app_id),
+                validator::ValidateApplicationIntegrationsAccess::new(
+                    validator::Flag::Update,
+                    app_id,
+                ),
             )
             .await?;
 
-        let _ = application::update_integration(application::Integration {
+        let i_before =
+            application::get_integration(&app_id, application::IntegrationKind::InfluxDb)
+                .await
+                .map_err(|e| e.status())?;
+
+        let i = application::update_integration(application::Integration {
             application_id: app_id,
             kind: application::IntegrationKind::InfluxDb,
             configuration: application::IntegrationConfiguration::InfluxDb(
@@ -549,6 +695,9 @@ impl ApplicationService for Application {
                     token: req_int.token.clone(),
                     organization: req_int.organization.clone(),
                     bucket: req_int.bucket.clone(),
+                    flush_interval_ms: req_int.flush_interval_ms,
+                    tag_variables: req_int.tag_variables.clone(),
+                    rx_metadata: req_int.rx_metadata,
                 },
             ),
             ..Default::default()
@@ -556,12 +705,16 @@ impl ApplicationService for Application {
         .await
         .map_err(|e| e.status())?;
 
+        integration::invalidate_cache(&app_id).await;
+
         let mut resp = Response::new(());
         resp.metadata_mut().insert(
             "x-log-application_id",
             req_int.application_id.parse().unwrap(),
         );
 
+        resp.extensions_mut()
+            .insert(audit::Changes::new(Some(&i_before), Some(&i)));
         Ok(resp)
     }
 
@@ -575,18 +728,29 @@ impl ApplicationService for Application {
         self.validator
             .validate(
                 request.extensions(),
-                validator::ValidateApplicationAccess::new(validator::Flag::Update, app_id),
+                validator::ValidateApplicationIntegrationsAccess::new(
+                    validator::Flag::Update,
+                    app_id,
+                ),
             )
             .await?;
 
+        let i = application::get_integration(&app_id, application::IntegrationKind::InfluxDb)
+            .await
+            .map_err(|e| e.status())?;
+
         application::delete_integration(&app_id, application::IntegrationKind::InfluxDb)
             .await
             .map_err(|e| e.status())?;
 
+        integration::invalidate_cache(&app_id).await;
+
         let mut resp = Response::new(());
         resp.metadata_mut()
             .insert("x-log-application_id", req.application_id.parse().unwrap());
 
+        resp.extensions_mut()
+            .insert(audit::Changes::new(Some(&i), None));
         Ok(resp)
     }
 
@@ -605,7 +769,10 @@ impl ApplicationService for Application {
         self.validator
             .validate(
                 request.extensions(),
-                validator::ValidateApplicationAccess::new(validator::Flag::Update, app_id),
+                validator::ValidateApplicationIntegrationsAccess::new(
+                    validator::Flag::Update,
+                    app_id,
+                ),
             )
             .await?;
 
@@ -620,16 +787,20 @@ impl ApplicationService for Application {
             ..Default::default()
         };
 
-        let _ = application::create_integration(i)
+        let i = application::create_integration(i)
             .await
             .map_err(|e| e.status())?;
 
+        integration::invalidate_cache(&app_id).await;
+
         let mut resp = Response::new(());
         resp.metadata_mut().insert(
             "x-log-application_id",
             req_int.application_id.parse().unwrap(),
         );
 
+        resp.extensions_mut()
+            .insert(audit::Changes::new(None, Some(&i)));
         Ok(resp)
     }
 
@@ -643,7 +814,10 @@ impl ApplicationService for Application {
         self.validator
             .validate(
                 request.extensions(),
-                validator::ValidateApplicationAccess::new(validator::Flag::Read, app_id),
+                validator::ValidateApplicationIntegrationsAccess::new(
+                    validator::Flag::Read,
+                    app_id,
+                ),
             )
             .await?;
 
@@ -684,11 +858,19 @@ impl ApplicationService for Application {
         self.validator
             .validate(
                 request.extensions(),
-                validator::ValidateApplicationAccess::new(validator::Flag::Update, app_id),
+                validator::ValidateApplicationIntegrationsAccess::new(
+                    validator::Flag::Update,
+                    app_id,
+                ),
             )
             .await?;
 
-        let _ = application::update_integration(application::Integration {
+        let i_before =
+            application::get_integration(&app_id, application::IntegrationKind::ThingsBoard)
+                .await
+                .map_err(|e| e.status())?;
+
+        let i = application::update_integration(application::Integration {
             application_id: app_id,
             kind: application::IntegrationKind::ThingsBoard,
             configuration: application::IntegrationConfiguration::ThingsBoard(
@@ -701,12 +883,16 @@ impl ApplicationService for Application {
         .await
         .map_err(|e| e.status())?;
 
+        integration::invalidate_cache(&app_id).await;
+
         let mut resp = Response::new(());
         resp.metadata_mut().insert(
             "x-log-application_id",
             req_int.application_id.parse().unwrap(),
         );
 
+        resp.extensions_mut()
+            .insert(audit::Changes::new(Some(&i_before), Some(&i)));
         Ok(resp)
     }
 
@@ -720,18 +906,29 @@ impl ApplicationService for Application {
         self.validator
             .validate(
                 request.extensions(),
-                validator::ValidateApplicationAccess::new(validator::Flag::Update, app_id),
+                validator::ValidateApplicationIntegrationsAccess::new(
+                    validator::Flag::Update,
+                    app_id,
+                ),
             )
             .await?;
 
+        let i = application::get_integration(&app_id, application::IntegrationKind::ThingsBoard)
+            .await
+            .map_err(|e| e.status())?;
+
         application::delete_integration(&app_id, application::IntegrationKind::ThingsBoard)
             .await
             .map_err(|e| e.status())?;
 
+        integration::invalidate_cache(&app_id).await;
+
         let mut resp = Response::new(());
         resp.metadata_mut()
             .insert("x-log-application_id", req.application_id.parse().unwrap());
 
+        resp.extensions_mut()
+            .insert(audit::Changes::new(Some(&i), None));
         Ok(resp)
     }
 
@@ -750,11 +947,14 @@ impl ApplicationService for Application {
         self.validator
             .validate(
                 request.extensions(),
-                validator::ValidateApplicationAccess::new(validator::Flag::Update, app_id),
+                validator::ValidateApplicationIntegrationsAccess::new(
+                    validator::Flag::Update,
+                    app_id,
+                ),
             )
             .await?;
 
-        let _ = application::create_integration(application::Integration {
+        let i = application::create_integration(application::Integration {
             application_id: app_id,
             kind: application::IntegrationKind::MyDevices,
             configuration: application::IntegrationConfiguration::MyDevices(
@@ -767,12 +967,16 @@ impl ApplicationService for Application {
         .await
         .map_err(|e| e.status())?;
 
+        integration::invalidate_cache(&app_id).await;
+
         let mut resp = Response::new(());
         resp.metadata_mut().insert(
             "x-log-application_id",
             req_int.application_id.parse().unwrap(),
         );
 
+        resp.extensions_mut()
+            .insert(audit::Changes::new(None, Some(&i)));
         Ok(resp)
     }
 
@@ -786,7 +990,10 @@ impl ApplicationService for Application {
         self.validator
             .validate(
                 request.extensions(),
-                validator::ValidateApplicationAccess::new(validator::Flag::Read, app_id),
+                validator::ValidateApplicationIntegrationsAccess::new(
+                    validator::Flag::Read,
+                    app_id,
+                ),
             )
             .await?;
 
@@ -827,11 +1034,19 @@ impl ApplicationService for Application {
         self.validator
             .validate(
                 request.extensions(),
-                validator::ValidateApplicationAccess::new(validator::Flag::Update, app_id),
+                validator::ValidateApplicationIntegrationsAccess::new(
+                    validator::Flag::Update,
+                    app_id,
+                ),
             )
             .await?;
 
-        let _ = application::update_integration(application::Integration {
+        let i_before =
+            application::get_integration(&app_id, application::IntegrationKind::MyDevices)
+                .await
+                .map_err(|e| e.status())?;
+
+        let i = application::update_integration(application::Integration {
             application_id: app_id,
             kind: application::IntegrationKind::MyDevices,
             configuration: application::IntegrationConfiguration::MyDevices(
@@ -844,12 +1059,16 @@ impl ApplicationService for Application {
         .await
         .map_err(|e| e.status())?;
 
+        integration::invalidate_cache(&app_id).await;
+
         let mut resp = Response::new(());
         resp.metadata_mut().insert(
             "x-log-application_id",
             req_int.application_id.parse().unwrap(),
         );
 
+        resp.extensions_mut()
+            .insert(audit::Changes::new(Some(&i_before), Some(&i)));
         Ok(resp)
     }
 
@@ -863,18 +1082,29 @@ impl ApplicationService for Application {
         self.validator
             .validate(
                 request.extensions(),
-                validator::ValidateApplicationAccess::new(validator::Flag::Update, app_id),
+                validator::ValidateApplicationIntegrationsAccess::new(
+                    validator::Flag::Update,
+                    app_id,
+                ),
             )
             .await?;
 
+        let i = application::get_integration(&app_id, application::IntegrationKind::MyDevices)
+            .await
+            .map_err(|e| e.status())?;
+
         application::delete_integration(&app_id, application::IntegrationKind::MyDevices)
             .await
             .map_err(|e| e.status())?;
 
+        integration::invalidate_cache(&app_id).await;
+
         let mut resp = Response::new(());
         resp.metadata_mut()
             .insert("x-log-application_id", req.application_id.parse().unwrap());
 
+        resp.extensions_mut()
+            .insert(audit::Changes::new(Some(&i), None));
         Ok(resp)
     }
 
@@ -893,7 +1123,10 @@ impl ApplicationService for Application {
         self.validator
             .validate(
                 request.extensions(),
-                validator::ValidateApplicationAccess::new(validator::Flag::Update, app_id),
+                validator::ValidateApplicationIntegrationsAccess::new(
+                    validator::Flag::Update,
+                    app_id,
+                ),
             )
             .await?;
 
@@ -906,7 +1139,7 @@ impl ApplicationService for Application {
             }
         };
 
-        let _ = application::create_integration(application::Integration {
+        let i = application::create_integration(application::Integration {
             application_id: app_id,
             kind: application::IntegrationKind::LoraCloud,
             configuration: application::IntegrationConfiguration::LoraCloud(
@@ -940,12 +1173,16 @@ impl ApplicationService for Application {
         .await
         .map_err(|e| e.status())?;
 
+        integration::invalidate_cache(&app_id).await;
+
         let mut resp = Response::new(());
         resp.metadata_mut().insert(
             "x-log-application_id",
             req_int.application_id.parse().unwrap(),
         );
 
+        resp.extensions_mut()
+            .insert(audit::Changes::new(None, Some(&i)));
         Ok(resp)
     }
 
@@ -959,7 +1196,10 @@ impl ApplicationService for Application {
         self.validator
             .validate(
                 request.extensions(),
-                validator::ValidateApplicationAccess::new(validator::Flag::Read, app_id),
+                validator::ValidateApplicationIntegrationsAccess::new(
+                    validator::Flag::Read,
+                    app_id,
+                ),
             )
             .await?;
 
@@ -1018,7 +1258,10 @@ impl ApplicationService for Application {
         self.validator
             .validate(
                 request.extensions(),
-                validator::ValidateApplicationAccess::new(validator::Flag::Update, app_id),
+                validator::ValidateApplicationIntegrationsAccess::new(
+                    validator::Flag::Update,
+                    app_id,
+                ),
             )
             .await?;
 
@@ -1031,7 +1274,12 @@ impl ApplicationService for Application {
             }
         };
 
-        let _ = application::update_integration(application::Integration {
+        let i_before =
+            application::get_integration(&app_id, application::IntegrationKind::LoraCloud)
+                .await
+                .map_err(|e| e.status())?;
+
+        let i = application::update_integration(application::Integration {
             application_id: app_id,
             kind: application::IntegrationKind::LoraCloud,
             configuration: application::IntegrationConfiguration::LoraCloud(
@@ -1065,12 +1313,16 @@ impl ApplicationService for Application {
         .await
         .map_err(|e| e.status())?;
 
+        integration::invalidate_cache(&app_id).await;
+
         let mut resp = Response::new(());
         resp.metadata_mut().insert(
             "x-log-application_id",
             req_int.application_id.parse().unwrap(),
         );
 
+        resp.extensions_mut()
+            .insert(audit::Changes::new(Some(&i_before), Some(&i)));
         Ok(resp)
     }
 
@@ -1084,18 +1336,29 @@ impl ApplicationService for Application {
         self.validator
             .validate(
                 request.extensions(),
-                validator::ValidateApplicationAccess::new(validator::Flag::Update, app_id),
+                validator::ValidateApplicationIntegrationsAccess::new(
+                    validator::Flag::Update,
+                    app_id,
+                ),
             )
             .await?;
 
+        let i = application::get_integration(&app_id, application::IntegrationKind::LoraCloud)
+            .await
+            .map_err(|e| e.status())?;
+
         application::delete_integration(&app_id, application::IntegrationKind::LoraCloud)
             .await
             .map_err(|e| e.status())?;
 
+        integration::invalidate_cache(&app_id).await;
+
         let mut resp = Response::new(());
         resp.metadata_mut()
             .insert("x-log-application_id", req.application_id.parse().unwrap());
 
+        resp.extensions_mut()
+            .insert(audit::Changes::new(Some(&i), None));
         Ok(resp)
     }
 
@@ -1114,11 +1377,14 @@ impl ApplicationService for Application {
         self.validator
             .validate(
                 request.extensions(),
-                validator::ValidateApplicationAccess::new(validator::Flag::Update, app_id),
+                validator::ValidateApplicationIntegrationsAccess::new(
+                    validator::Flag::Update,
+                    app_id,
+                ),
             )
             .await?;
 
-        let _ = application::create_integration(application::Integration {
+        let i = application::create_integration(application::Integration {
             application_id: app_id,
             kind: application::IntegrationKind::GcpPubSub,
             configuration: application::IntegrationConfiguration::GcpPubSub(
@@ -1134,12 +1400,16 @@ impl ApplicationService for Application {
         .await
         .map_err(|e| e.status())?;
 
+        integration::invalidate_cache(&app_id).await;
+
         let mut resp = Response::new(());
         resp.metadata_mut().insert(
             "x-log-application_id",
             req_int.application_id.parse().unwrap(),
         );
 
+        resp.extensions_mut()
+            .insert(audit::Changes::new(None, Some(&i)));
         Ok(resp)
     }
 
@@ -1153,7 +1423,10 @@ impl ApplicationService for Application {
         self.validator
             .validate(
                 request.extensions(),
-                validator::ValidateApplicationAccess::new(validator::Flag::Read, app_id),
+                validator::ValidateApplicationIntegrationsAccess::new(
+                    validator::Flag::Read,
+                    app_id,
+                ),
             )
             .await?;
 
@@ -1197,11 +1470,19 @@ impl ApplicationService for Application {
         self.validator
             .validate(
                 request.extensions(),
-                validator::ValidateApplicationAccess::new(validator::Flag::Update, app_id),
+                validator::ValidateApplicationIntegrationsAccess::new(
+                    validator::Flag::Update,
+                    app_id,
+                ),
             )
             .await?;
 
-        let _ = application::update_integration(application::Integration {
+        let i_before =
+            application::get_integration(&app_id, application::IntegrationKind::GcpPubSub)
+                .await
+                .map_err(|e| e.status())?;
+
+        let i = application::update_integration(application::Integration {
             application_id: app_id,
             kind: application::IntegrationKind::GcpPubSub,
             configuration: application::IntegrationConfiguration::GcpPubSub(
@@ -1217,12 +1498,16 @@ impl ApplicationService for Application {
         .await
         .map_err(|e| e.status())?;
 
+        integration::invalidate_cache(&app_id).await;
+
         let mut resp = Response::new(());
         resp.metadata_mut().insert(
             "x-log-application_id",
             req_int.application_id.parse().unwrap(),
         );
 
+        resp.extensions_mut()
+            .insert(audit::Changes::new(Some(&i_before), Some(&i)));
         Ok(resp)
     }
 
@@ -1236,18 +1521,29 @@ impl ApplicationService for Application {
         self.validator
             .validate(
                 request.extensions(),
-                validator::ValidateApplicationAccess::new(validator::Flag::Update, app_id),
+                validator::ValidateApplicationIntegrationsAccess::new(
+                    validator::Flag::Update,
+                    app_id,
+                ),
             )
             .await?;
 
+        let i = application::get_integration(&app_id, application::IntegrationKind::GcpPubSub)
+            .await
+            .map_err(|e| e.status())?;
+
         application::delete_integration(&app_id, application::IntegrationKind::GcpPubSub)
             .await
             .map_err(|e| e.status())?;
 
+        integration::invalidate_cache(&app_id).await;
+
         let mut resp = Response::new(());
         resp.metadata_mut()
             .insert("x-log-application_id", req.application_id.parse().unwrap());
 
+        resp.extensions_mut()
+            .insert(audit::Changes::new(Some(&i), None));
         Ok(resp)
     }
 
@@ -1266,11 +1562,14 @@ impl ApplicationService for Application {
         self.validator
             .validate(
                 request.extensions(),
-                validator::ValidateApplicationAccess::new(validator::Flag::Update, app_id),
+                validator::ValidateApplicationIntegrationsAccess::new(
+                    validator::Flag::Update,
+                    app_id,
+                ),
             )
             .await?;
 
-        let _ = application::create_integration(application::Integration {
+        let i = application::create_integration(application::Integration {
             application_id: app_id,
             kind: application::IntegrationKind::AwsSns,
             configuration: application::IntegrationConfiguration::AwsSns(
@@ -1287,12 +1586,16 @@ impl ApplicationService for Application {
         .await
         .map_err(|e| e.status())?;
 
+        integration::invalidate_cache(&app_id).await;
+
         let mut resp = Response::new(());
         resp.metadata_mut().insert(
             "x-log-application_id",
             req_int.application_id.parse().unwrap(),
         );
 
+        resp.extensions_mut()
+            .insert(audit::Changes::new(None, Some(&i)));
         Ok(resp)
     }
 
@@ -1306,7 +1609,10 @@ impl ApplicationService for Application {
         self.validator
             .validate(
                 request.extensions(),
-                validator::ValidateApplicationAccess::new(validator::Flag::Read, app_id),
+                validator::ValidateApplicationIntegrationsAccess::new(
+                    validator::Flag::Read,
+                    app_id,
+                ),
             )
             .await?;
 
@@ -1349,11 +1655,18 @@ impl ApplicationService for Application {
         self.validator
             .validate(
                 request.extensions(),
-                validator::ValidateApplicationAccess::new(validator::Flag::Update, app_id),
+                validator::ValidateApplicationIntegrationsAccess::new(
+                    validator::Flag::Update,
+                    app_id,
+                ),
             )
             .await?;
 
-        let _ = application::update_integration(application::Integration {
+        let i_before = application::get_integration(&app_id, application::IntegrationKind::AwsSns)
+            .await
+            .map_err(|e| e.status())?;
+
+        let i = application::update_integration(application::Integration {
             application_id: app_id,
             kind: application::IntegrationKind::AwsSns,
             configuration: application::IntegrationConfiguration::AwsSns(
@@ -1370,12 +1683,16 @@ impl ApplicationService for Application {
         .await
         .map_err(|e| e.status())?;
 
+        integration::invalidate_cache(&app_id).await;
+
         let mut resp = Response::new(());
         resp.metadata_mut().insert(
             "x-log-application_id",
             req_int.application_id.parse().unwrap(),
         );
 
+        resp.extensions_mut()
+            .insert(audit::Changes::new(Some(&i_before), Some(&i)));
         Ok(resp)
     }
 
@@ -1389,18 +1706,29 @@ impl ApplicationService for Application {
         self.validator
             .validate(
                 request.extensions(),
-                validator::ValidateApplicationAccess::new(validator::Flag::Update, app_id),
+                validator::ValidateApplicationIntegrationsAccess::new(
+                    validator::Flag::Update,
+                    app_id,
+                ),
             )
             .await?;
 
+        let i = application::get_integration(&app_id, application::IntegrationKind::AwsSns)
+            .await
+            .map_err(|e| e.status())?;
+
         application::delete_integration(&app_id, application::IntegrationKind::AwsSns)
             .await
             .map_err(|e| e.status())?;
 
+        integration::invalidate_cache(&app_id).await;
+
         let mut resp = Response::new(());
         resp.metadata_mut()
             .insert("x-log-application_id", req.application_id.parse().unwrap());
 
+        resp.extensions_mut()
+            .insert(audit::Changes::new(Some(&i), None));
         Ok(resp)
     }
 
@@ -1419,11 +1747,14 @@ impl ApplicationService for Application {
         self.validator
             .validate(
                 request.extensions(),
-                validator::ValidateApplicationAccess::new(validator::Flag::Update, app_id),
+                validator::ValidateApplicationIntegrationsAccess::new(
+                    validator::Flag::Update,
+                    app_id,
+                ),
             )
             .await?;
 
-        let _ = application::create_integration(application::Integration {
+        let i = application::create_integration(application::Integration {
             application_id: app_id,
             kind: application::IntegrationKind::AzureServiceBus,
             configuration: application::IntegrationConfiguration::AzureServiceBus(
@@ -1438,12 +1769,16 @@ impl ApplicationService for Application {
         .await
         .map_err(|e| e.status())?;
 
+        integration::invalidate_cache(&app_id).await;
+
         let mut resp = Response::new(());
         resp.metadata_mut().insert(
             "x-log-application_id",
             req_int.application_id.parse().unwrap(),
         );
 
+        resp.extensions_mut()
+            .insert(audit::Changes::new(None, Some(&i)));
         Ok(resp)
     }
 
@@ -1457,7 +1792,10 @@ impl ApplicationService for Application {
         self.validator
             .validate(
                 request.extensions(),
-                validator::ValidateApplicationAccess::new(validator::Flag::Read, app_id),
+                validator::ValidateApplicationIntegrationsAccess::new(
+                    validator::Flag::Read,
+                    app_id,
+                ),
             )
             .await?;
 
@@ -1501,11 +1839,19 @@ impl ApplicationService for Application {
         self.validator
             .validate(
                 request.extensions(),
-                validator::ValidateApplicationAccess::new(validator::Flag::Update, app_id),
+                validator::ValidateApplicationIntegrationsAccess::new(
+                    validator::Flag::Update,
+                    app_id,
+                ),
             )
             .await?;
 
-        let _ = application::update_integration(application::Integration {
+        let i_before =
+            application::get_integration(&app_id, application::IntegrationKind::AzureServiceBus)
+                .await
+                .map_err(|e| e.status())?;
+
+        let i = application::update_integration(application::Integration {
             application_id: app_id,
             kind: application::IntegrationKind::AzureServiceBus,
             configuration: application::IntegrationConfiguration::AzureServiceBus(
@@ -1520,12 +1866,16 @@ impl ApplicationService for Application {
         .await
         .map_err(|e| e.status())?;
 
+        integration::invalidate_cache(&app_id).await;
+
         let mut resp = Response::new(());
         resp.metadata_mut().insert(
             "x-log-application_id",
             req_int.application_id.parse().unwrap(),
         );
 
+        resp.extensions_mut()
+            .insert(audit::Changes::new(Some(&i_before), Some(&i)));
         Ok(resp)
     }
 
@@ -1539,18 +1889,30 @@ impl ApplicationService for Application {
         self.validator
             .validate(
                 request.extensions(),
-                validator::ValidateApplicationAccess::new(validator::Flag::Update, app_id),
+                validator::ValidateApplicationIntegrationsAccess::new(
+                    validator::Flag::Update,
+                    app_id,
+                ),
             )
             .await?;
 
+        let i =
+            application::get_integration(&app_id, application::IntegrationKind::AzureServiceBus)
+                .await
+                .map_err(|e| e.status())?;
+
         application::delete_integration(&app_id, application::IntegrationKind::AzureServiceBus)
             .await
             .map_err(|e| e.status())?;
 
+        integration::invalidate_cache(&app_id).await;
+
         let mut resp = Response::new(());
         resp.metadata_mut()
             .insert("x-log-application_id", req.application_id.parse().unwrap());
 
+        resp.extensions_mut()
+            .insert(audit::Changes::new(Some(&i), None));
         Ok(resp)
     }
 
@@ -1569,11 +1931,14 @@ impl ApplicationService for Application {
         self.validator
             .validate(
                 request.extensions(),
-                validator::ValidateApplicationAccess::new(validator::Flag::Update, app_id),
+                validator::ValidateApplicationIntegrationsAccess::new(
+                    validator::Flag::Update,
+                    app_id,
+                ),
             )
             .await?;
 
-        let _ = application::create_integration(application::Integration {
+        let i = application::create_integration(application::Integration {
             application_id: app_id,
             kind: application::IntegrationKind::PilotThings,
             configuration: application::IntegrationConfiguration::PilotThings(
@@ -1587,12 +1952,16 @@ impl ApplicationService for Application {
         .await
         .map_err(|e| e.status())?;
 
+        integration::invalidate_cache(&app_id).await;
+
         let mut resp = Response::new(());
         resp.metadata_mut().insert(
             "x-log-application_id",
             req_int.application_id.parse().unwrap(),
         );
 
+        resp.extensions_mut()
+            .insert(audit::Changes::new(None, Some(&i)));
         Ok(resp)
     }
 
@@ -1606,7 +1975,10 @@ impl ApplicationService for Application {
         self.validator
             .validate(
                 request.extensions(),
-                validator::ValidateApplicationAccess::new(validator::Flag::Read, app_id),
+                validator::ValidateApplicationIntegrationsAccess::new(
+                    validator::Flag::Read,
+                    app_id,
+                ),
             )
             .await?;
 
@@ -1648,11 +2020,19 @@ impl ApplicationService for Application {
         self.validator
             .validate(
                 request.extensions(),
-                validator::ValidateApplicationAccess::new(validator::Flag::Update, app_id),
+                validator::ValidateApplicationIntegrationsAccess::new(
+                    validator::Flag::Update,
+                    app_id,
+                ),
             )
             .await?;
 
-        let _ = application::update_integration(application::Integration {
+        let i_before =
+            application::get_integration(&app_id, application::IntegrationKind::PilotThings)
+                .await
+                .map_err(|e| e.status())?;
+
+        let i = application::update_integration(application::Integration {
             application_id: app_id,
             kind: application::IntegrationKind::PilotThings,
             configuration: application::IntegrationConfiguration::PilotThings(
@@ -1666,12 +2046,16 @@ impl ApplicationService for Application {
         .await
         .map_err(|e| e.status())?;
 
+        integration::invalidate_cache(&app_id).await;
+
         let mut resp = Response::new(());
         resp.metadata_mut().insert(
             "x-log-application_id",
             req_int.application_id.parse().unwrap(),
         );
 
+        resp.extensions_mut()
+            .insert(audit::Changes::new(Some(&i_before), Some(&i)));
         Ok(resp)
     }
 
@@ -1685,18 +2069,29 @@ impl ApplicationService for Application {
         self.validator
             .validate(
                 request.extensions(),
-                validator::ValidateApplicationAccess::new(validator::Flag::Update, app_id),
+                validator::ValidateApplicationIntegrationsAccess::new(
+                    validator::Flag::Update,
+                    app_id,
+                ),
             )
             .await?;
 
+        let i = application::get_integration(&app_id, application::IntegrationKind::PilotThings)
+            .await
+            .map_err(|e| e.status())?;
+
         application::delete_integration(&app_id, application::IntegrationKind::PilotThings)
             .await
             .map_err(|e| e.status())?;
 
+        integration::invalidate_cache(&app_id).await;
+
         let mut resp = Response::new(());
         resp.metadata_mut()
             .insert("x-log-application_id", req.application_id.parse().unwrap());
 
+        resp.extensions_mut()
+            .insert(audit::Changes::new(Some(&i), None));
         Ok(resp)
     }
 
@@ -1715,7 +2110,10 @@ impl ApplicationService for Application {
         self.validator
             .validate(
                 request.extensions(),
-                validator::ValidateApplicationAccess::new(validator::Flag::Update, app_id),
+                validator::ValidateApplicationIntegrationsAccess::new(
+                    validator::Flag::Update,
+                    app_id,
+                ),
             )
             .await?;
 
@@ -1729,7 +2127,7 @@ impl ApplicationService for Application {
             ));
         }
 
-        let _ = application::create_integration(application::Integration {
+        let i = application::create_integration(application::Integration {
             application_id: app_id,
             kind: application::IntegrationKind::Ifttt,
             configuration: application::IntegrationConfiguration::Ifttt(
@@ -1748,12 +2146,16 @@ impl ApplicationService for Application {
         .await
         .map_err(|e| e.status())?;
 
+        integration::invalidate_cache(&app_id).await;
+
         let mut resp = Response::new(());
         resp.metadata_mut().insert(
             "x-log-application_id",
             req_int.application_id.parse().unwrap(),
         );
 
+        resp.extensions_mut()
+            .insert(audit::Changes::new(None, Some(&i)));
         Ok(resp)
     }
 
@@ -1767,7 +2169,10 @@ impl ApplicationService for Application {
         self.validator
             .validate(
                 request.extensions(),
-                validator::ValidateApplicationAccess::new(validator::Flag::Read, app_id),
+                validator::ValidateApplicationIntegrationsAccess::new(
+                    validator::Flag::Read,
+                    app_id,
+                ),
             )
             .await?;
 
@@ -1809,11 +2214,18 @@ impl ApplicationService for Application {
         self.validator
             .validate(
                 request.extensions(),
-                validator::ValidateApplicationAccess::new(validator::Flag::Update, app_id),
+                validator::ValidateApplicationIntegrationsAccess::new(
+                    validator::Flag::Update,
+                    app_id,
+                ),
             )
             .await?;
 
-        let _ = application::update_integration(application::Integration {
+        let i_before = application::get_integration(&app_id, application::IntegrationKind::Ifttt)
+            .await
+            .map_err(|e| e.status())?;
+
+        let i = application::update_integration(application::Integration {
             application_id: app_id,
             kind: application::IntegrationKind::Ifttt,
             configuration: application::IntegrationConfiguration::Ifttt(
@@ -1832,12 +2244,16 @@ impl ApplicationService for Application {
         .await
         .map_err(|e| e.status())?;
 
+        integration::invalidate_cache(&app_id).await;
+
         let mut resp = Response::new(());
         resp.metadata_mut().insert(
             "x-log-application_id",
             req_int.application_id.parse().unwrap(),
         );
 
+        resp.extensions_mut()
+            .insert(audit::Changes::new(Some(&i_before), Some(&i)));
         Ok(resp)
     }
 
@@ -1851,18 +2267,29 @@ impl ApplicationService for Application {
         self.validator
             .validate(
                 request.extensions(),
-                validator::ValidateApplicationAccess::new(validator::Flag::Update, app_id),
+                validator::ValidateApplicationIntegrationsAccess::new(
+                    validator::Flag::Update,
+                    app_id,
+                ),
             )
             .await?;
 
+        let i = application::get_integration(&app_id, application::IntegrationKind::Ifttt)
+            .await
+            .map_err(|e| e.status())?;
+
         application::delete_integration(&app_id, application::IntegrationKind::Ifttt)
             .await
             .map_err(|e| e.status())?;
 
+        integration::invalidate_cache(&app_id).await;
+
         let mut resp = Response::new(());
         resp.metadata_mut()
             .insert("x-log-application_id", req.application_id.parse().unwrap());
 
+        resp.extensions_mut()
+            .insert(audit::Changes::new(Some(&i), None));
         Ok(resp)
     }
 
@@ -1876,68 +2303,585 @@ impl ApplicationService for Application {
         self.validator
             .validate(
                 request.extensions(),
-                validator::ValidateApplicationAccess::new(validator::Flag::Update, app_id),
+                validator::ValidateApplicationIntegrationsAccess::new(
+                    validator::Flag::Update,
+                    app_id,
+                ),
             )
             .await?;
 
-        let (ttl, ca_cert, cert, key) = certificate::client_cert_for_application_id(&app_id)
-            .await
-            .map_err(|e| e.status())?;
+        let (serial_number, ttl, ca_cert, cert, key) =
+            certificate::client_cert_for_application_id(&app_id)
+                .await
+                .map_err(|e| e.status())?;
 
         application::update_mqtt_cls_cert(&app_id, cert.as_bytes())
             .await
             .map_err(|e| e.status())?;
 
+        let c = mqtt_client_certificate::create(mqtt_client_certificate::ClientCertificate {
+            serial_number: hex::encode(serial_number),
+            application_id: app_id,
+            expires_at: ttl.into(),
+            ..Default::default()
+        })
+        .await
+        .map_err(|e| e.status())?;
+
         let mut resp = Response::new(api::GenerateMqttIntegrationClientCertificateResponse {
             ca_cert,
             tls_cert: cert,
             tls_key: key,
             expires_at: Some(ttl.into()),
+            serial_number: c.serial_number,
         });
         resp.metadata_mut()
             .insert("x-log-application_id", req.application_id.parse().unwrap());
 
         Ok(resp)
     }
-}
 
-#[cfg(test)]
-pub mod test {
-    use super::*;
-    use crate::api::auth::validator::RequestValidator;
-    use crate::api::auth::AuthID;
-    use crate::storage::{tenant, user};
-    use crate::test;
+    async fn list_mqtt_integration_client_certificates(
+        &self,
+        request: Request<api::ListMqttIntegrationClientCertificatesRequest>,
+    ) -> Result<Response<api::ListMqttIntegrationClientCertificatesResponse>, Status> {
+        let req = request.get_ref();
+        let app_id = Uuid::from_str(&req.application_id).map_err(|e| e.status())?;
 
-    #[tokio::test]
-    async fn test_application() {
-        let _guard = test::prepare().await;
+        self.validator
+            .validate(
+                request.extensions(),
+                validator::ValidateApplicationIntegrationsAccess::new(
+                    validator::Flag::List,
+                    app_id,
+                ),
+            )
+            .await?;
 
-        // setup admin user
-        let u = user::User {
-            is_admin: true,
-            is_active: true,
-            email: "admin@admin".into(),
-            email_verified: true,
-            ..Default::default()
-        };
-        let u = user::create(u).await.unwrap();
+        let count = mqtt_client_certificate::get_count(&app_id)
+            .await
+            .map_err(|e| e.status())?;
+        let result = mqtt_client_certificate::list(req.limit as i64, req.offset as i64, &app_id)
+            .await
+            .map_err(|e| e.status())?;
 
-        // create tenant
-        let t = tenant::create(tenant::Tenant {
-            name: "test-tenant".into(),
-            can_have_gateways: true,
-            max_gateway_count: 10,
-            ..Default::default()
-        })
-        .await
-        .unwrap();
+        let mut resp = Response::new(api::ListMqttIntegrationClientCertificatesResponse {
+            total_count: count as u32,
+            result: result
+                .iter()
+                .map(|c| api::MqttIntegrationClientCertificate {
+                    serial_number: c.serial_number.clone(),
+                    created_at: Some(helpers::datetime_to_prost_timestamp(&c.created_at)),
+                    expires_at: Some(helpers::datetime_to_prost_timestamp(&c.expires_at)),
+                    revoked_at: c
+                        .revoked_at
+                        .as_ref()
+                        .map(helpers::datetime_to_prost_timestamp),
+                })
+                .collect(),
+        });
+        resp.metadata_mut()
+            .insert("x-log-application_id", req.application_id.parse().unwrap());
 
-        // setup api
-        let service = Application::new(RequestValidator::new());
+        Ok(resp)
+    }
 
-        // create
-        let create_req = api::CreateApplicationRequest {
+    async fn revoke_mqtt_integration_client_certificate(
+        &self,
+        request: Request<api::RevokeMqttIntegrationClientCertificateRequest>,
+    ) -> Result<Response<()>, Status> {
+        let req = request.get_ref();
+        let app_id = Uuid::from_str(&req.application_id).map_err(|e| e.status())?;
+
+        self.validator
+            .validate(
+                request.extensions(),
+                validator::ValidateApplicationIntegrationsAccess::new(
+                    validator::Flag::Update,
+                    app_id,
+                ),
+            )
+            .await?;
+
+        let _ = mqtt_client_certificate::revoke(&app_id, &req.serial_number.to_lowercase())
+            .await
+            .map_err(|e| e.status())?;
+
+        let mut resp = Response::new(());
+        resp.metadata_mut()
+            .insert("x-log-application_id", req.application_id.parse().unwrap());
+
+        Ok(resp)
+    }
+
+    async fn add_user(
+        &self,
+        request: Request<api::AddApplicationUserRequest>,
+    ) -> Result<Response<()>, Status> {
+        let req_user = match &request.get_ref().application_user {
+            Some(v) => v,
+            None => {
+                return Err(Status::invalid_argument("application_user is missing"));
+            }
+        };
+        let app_id = Uuid::from_str(&req_user.application_id).map_err(|e| e.status())?;
+        let user_id = user::get_by_email(&req_user.email)
+            .await
+            .map_err(|e| e.status())?
+            .id;
+
+        self.validator
+            .validate(
+                request.extensions(),
+                validator::ValidateApplicationUsersAccess::new(validator::Flag::Create, app_id),
+            )
+            .await?;
+
+        let _ = application::add_user(application::ApplicationUser {
+            application_id: app_id,
+            user_id,
+            is_admin: req_user.is_admin,
+            is_device_admin: req_user.is_device_admin,
+            ..Default::default()
+        })
+        .await
+        .map_err(|e| e.status())?;
+
+        let mut resp = Response::new(());
+        resp.metadata_mut().insert(
+            "x-log-application_id",
+            req_user.application_id.parse().unwrap(),
+        );
+        resp.metadata_mut()
+            .insert("x-log-user_id", user_id.to_string().parse().unwrap());
+
+        Ok(resp)
+    }
+
+    async fn get_user(
+        &self,
+        request: Request<api::GetApplicationUserRequest>,
+    ) -> Result<Response<api::GetApplicationUserResponse>, Status> {
+        let req = request.get_ref();
+        let app_id = Uuid::from_str(&req.application_id).map_err(|e| e.status())?;
+        let user_id = Uuid::from_str(&req.user_id).map_err(|e| e.status())?;
+
+        self.validator
+            .validate(
+                request.extensions(),
+                validator::ValidateApplicationUserAccess::new(
+                    validator::Flag::Read,
+                    app_id,
+                    user_id,
+                ),
+            )
+            .await?;
+
+        let u = user::get(&user_id).await.map_err(|e| e.status())?;
+        let au = application::get_user(&app_id, &user_id)
+            .await
+            .map_err(|e| e.status())?;
+
+        let mut resp = Response::new(api::GetApplicationUserResponse {
+            application_user: Some(api::ApplicationUser {
+                application_id: app_id.to_string(),
+                user_id: au.user_id.to_string(),
+                email: u.email.clone(),
+                is_admin: au.is_admin,
+                is_device_admin: au.is_device_admin,
+            }),
+            created_at: Some(helpers::datetime_to_prost_timestamp(&au.created_at)),
+            updated_at: Some(helpers::datetime_to_prost_timestamp(&au.updated_at)),
+        });
+        resp.metadata_mut()
+            .insert("x-log-application_id", req.application_id.parse().unwrap());
+        resp.metadata_mut()
+            .insert("x-log-user_id", req.user_id.parse().unwrap());
+
+        Ok(resp)
+    }
+
+    async fn update_user(
+        &self,
+        request: Request<api::UpdateApplicationUserRequest>,
+    ) -> Result<Response<()>, Status> {
+        let req_user = match &request.get_ref().application_user {
+            Some(v) => v,
+            None => {
+                return Err(Status::invalid_argument("application_user is missing"));
+            }
+        };
+        let app_id = Uuid::from_str(&req_user.application_id).map_err(|e| e.status())?;
+        let user_id = Uuid::from_str(&req_user.user_id).map_err(|e| e.status())?;
+
+        self.validator
+            .validate(
+                request.extensions(),
+                validator::ValidateApplicationUserAccess::new(
+                    validator::Flag::Update,
+                    app_id,
+                    user_id,
+                ),
+            )
+            .await?;
+
+        application::update_user(application::ApplicationUser {
+            application_id: app_id,
+            user_id,
+            is_admin: req_user.is_admin,
+            is_device_admin: req_user.is_device_admin,
+            ..Default::default()
+        })
+        .await
+        .map_err(|e| e.status())?;
+
+        let mut resp = Response::new(());
+        resp.metadata_mut().insert(
+            "x-log-application_id",
+            req_user.application_id.parse().unwrap(),
+        );
+        resp.metadata_mut()
+            .insert("x-log-user_id", req_user.user_id.parse().unwrap());
+
+        Ok(resp)
+    }
+
+    async fn delete_user(
+        &self,
+        request: Request<api::DeleteApplicationUserRequest>,
+    ) -> Result<Response<()>, Status> {
+        let req = request.get_ref();
+        let app_id = Uuid::from_str(&req.application_id).map_err(|e| e.status())?;
+        let user_id = Uuid::from_str(&req.user_id).map_err(|e| e.status())?;
+
+        self.validator
+            .validate(
+                request.extensions(),
+                validator::ValidateApplicationUserAccess::new(
+                    validator::Flag::Delete,
+                    app_id,
+                    user_id,
+                ),
+            )
+            .await?;
+
+        application::delete_user(&app_id, &user_id)
+            .await
+            .map_err(|e| e.status())?;
+
+        let mut resp = Response::new(());
+        resp.metadata_mut()
+            .insert("x-log-application_id", req.application_id.parse().unwrap());
+        resp.metadata_mut()
+            .insert("x-log-user_id", req.user_id.parse().unwrap());
+
+        Ok(resp)
+    }
+
+    async fn list_users(
+        &self,
+        request: Request<api::ListApplicationUsersRequest>,
+    ) -> Result<Response<api::ListApplicationUsersResponse>, Status> {
+        let req = request.get_ref();
+        let app_id = Uuid::from_str(&req.application_id).map_err(|e| e.status())?;
+
+        self.validator
+            .validate(
+                request.extensions(),
+                validator::ValidateApplicationUsersAccess::new(validator::Flag::List, app_id),
+            )
+            .await?;
+
+        let count = application::get_user_count(&app_id)
+            .await
+            .map_err(|e| e.status())?;
+        let result = application::get_users(&app_id, req.limit as i64, req.offset as i64)
+            .await
+            .map_err(|e| e.status())?;
+
+        let mut resp = Response::new(api::ListApplicationUsersResponse {
+            total_count: count as u32,
+            result: result
+                .iter()
+                .map(|au| api::ApplicationUserListItem {
+                    application_id: app_id.to_string(),
+                    user_id: au.user_id.to_string(),
+                    created_at: Some(helpers::datetime_to_prost_timestamp(&au.created_at)),
+                    updated_at: Some(helpers::datetime_to_prost_timestamp(&au.updated_at)),
+                    email: au.email.clone(),
+                    is_admin: au.is_admin,
+                    is_device_admin: au.is_device_admin,
+                })
+                .collect(),
+        });
+        resp.metadata_mut()
+            .insert("x-log-application_id", req.application_id.parse().unwrap());
+
+        Ok(resp)
+    }
+
+    async fn list_integration_dead_letters(
+        &self,
+        request: Request<api::ListIntegrationDeadLettersRequest>,
+    ) -> Result<Response<api::ListIntegrationDeadLettersResponse>, Status> {
+        let req = request.get_ref();
+        let app_id = Uuid::from_str(&req.application_id).map_err(|e| e.status())?;
+
+        self.validator
+            .validate(
+                request.extensions(),
+                validator::ValidateApplicationIntegrationsAccess::new(
+                    validator::Flag::List,
+                    app_id,
+                ),
+            )
+            .await?;
+
+        let filters = integration_outbox::Filters {
+            application_id: Some(app_id),
+            kinds: integration_kinds(req.kinds())?,
+        };
+
+        let count = integration_outbox::get_dead_count(&filters)
+            .await
+            .map_err(|e| e.status())?;
+        let result = integration_outbox::list_dead(req.limit as i64, req.offset as i64, &filters)
+            .await
+            .map_err(|e| e.status())?;
+
+        let mut resp = Response::new(api::ListIntegrationDeadLettersResponse {
+            total_count: count as u32,
+            result: result
+                .iter()
+                .map(|i| api::IntegrationDeadLetter {
+                    id: i.id.to_string(),
+                    created_at: Some(helpers::datetime_to_prost_timestamp(&i.created_at)),
+                    kind: i.kind.to_proto().into(),
+                    event: i.event.clone(),
+                    attempts: i.attempts as u32,
+                    last_error: i.last_error.clone(),
+                    dead_at: i.dead_at.as_ref().map(helpers::datetime_to_prost_timestamp),
+                })
+                .collect(),
+        });
+        resp.metadata_mut()
+            .insert("x-log-application_id", req.application_id.parse().unwrap());
+
+        Ok(resp)
+    }
+
+    async fn replay_integration_dead_letters(
+        &self,
+        request: Request<api::ReplayIntegrationDeadLettersRequest>,
+    ) -> Result<Response<api::ReplayIntegrationDeadLettersResponse>, Status> {
+        let req = request.get_ref();
+        let app_id = Uuid::from_str(&req.application_id).map_err(|e| e.status())?;
+
+        self.validator
+            .validate(
+                request.extensions(),
+                validator::ValidateApplicationIntegrationsAccess::new(
+                    validator::Flag::Update,
+                    app_id,
+                ),
+            )
+            .await?;
+
+        let count = integration_outbox::replay_dead(&integration_outbox::Filters {
+            application_id: Some(app_id),
+            kinds: integration_kinds(req.kinds())?,
+        })
+        .await
+        .map_err(|e| e.status())?;
+
+        let mut resp = Response::new(api::ReplayIntegrationDeadLettersResponse {
+            count: count as u32,
+        });
+        resp.metadata_mut()
+            .insert("x-log-application_id", req.application_id.parse().unwrap());
+
+        Ok(resp)
+    }
+
+    async fn purge_integration_dead_letters(
+        &self,
+        request: Request<api::PurgeIntegrationDeadLettersRequest>,
+    ) -> Result<Response<api::PurgeIntegrationDeadLettersResponse>, Status> {
+        let req = request.get_ref();
+        let app_id = Uuid::from_str(&req.application_id).map_err(|e| e.status())?;
+
+        self.validator
+            .validate(
+                request.extensions(),
+                validator::ValidateApplicationIntegrationsAccess::new(
+                    validator::Flag::Delete,
+                    app_id,
+                ),
+            )
+            .await?;
+
+        let count = integration_outbox::purge_dead(&integration_outbox::Filters {
+            application_id: Some(app_id),
+            kinds: integration_kinds(req.kinds())?,
+        })
+        .await
+        .map_err(|e| e.status())?;
+
+        let mut resp = Response::new(api::PurgeIntegrationDeadLettersResponse {
+            count: count as u32,
+        });
+        resp.metadata_mut()
+            .insert("x-log-application_id", req.application_id.parse().unwrap());
+
+        Ok(resp)
+    }
+
+    async fn get_integration_filter(
+        &self,
+        request: Request<api::GetIntegrationFilterRequest>,
+    ) -> Result<Response<api::GetIntegrationFilterResponse>, Status> {
+        let req = request.get_ref();
+        let app_id = Uuid::from_str(&req.application_id).map_err(|e| e.status())?;
+
+        self.validator
+            .validate(
+                request.extensions(),
+                validator::ValidateApplicationIntegrationsAccess::new(
+                    validator::Flag::Read,
+                    app_id,
+                ),
+            )
+            .await?;
+
+        let kind = integration_kind(req.kind())?;
+        let i = application::get_integration(&app_id, kind)
+            .await
+            .map_err(|e| e.status())?;
+
+        let mut resp = Response::new(api::GetIntegrationFilterResponse {
+            filter: Some(api::IntegrationFilter {
+                events: i.filter.events.clone(),
+                f_ports: i.filter.f_ports.clone(),
+                device_profile_ids: i
+                    .filter
+                    .device_profile_ids
+                    .iter()
+                    .map(|v| v.to_string())
+                    .collect(),
+                tags: i.filter.tags.clone(),
+                expression: i.filter.expression.clone(),
+            }),
+        });
+        resp.metadata_mut()
+            .insert("x-log-application_id", req.application_id.parse().unwrap());
+
+        Ok(resp)
+    }
+
+    async fn update_integration_filter(
+        &self,
+        request: Request<api::UpdateIntegrationFilterRequest>,
+    ) -> Result<Response<()>, Status> {
+        let req = request.get_ref();
+        let req_filter = match &req.filter {
+            Some(v) => v,
+            None => {
+                return Err(Status::invalid_argument("filter is missing"));
+            }
+        };
+        let app_id = Uuid::from_str(&req.application_id).map_err(|e| e.status())?;
+
+        self.validator
+            .validate(
+                request.extensions(),
+                validator::ValidateApplicationIntegrationsAccess::new(
+                    validator::Flag::Update,
+                    app_id,
+                ),
+            )
+            .await?;
+
+        let kind = integration_kind(req.kind())?;
+        let filter = application::IntegrationFilter {
+            events: req_filter.events.clone(),
+            f_ports: req_filter.f_ports.clone(),
+            device_profile_ids: req_filter
+                .device_profile_ids
+                .iter()
+                .map(|v| Uuid::from_str(v))
+                .collect::<Result<Vec<Uuid>, _>>()
+                .map_err(|e| e.status())?,
+            tags: req_filter.tags.clone(),
+            expression: req_filter.expression.clone(),
+        };
+
+        let i_before = application::get_integration(&app_id, kind)
+            .await
+            .map_err(|e| e.status())?;
+
+        let i = application::update_integration_filter(&app_id, kind, &filter)
+            .await
+            .map_err(|e| e.status())?;
+
+        integration::invalidate_cache(&app_id).await;
+
+        let mut resp = Response::new(());
+        resp.metadata_mut()
+            .insert("x-log-application_id", req.application_id.parse().unwrap());
+        resp.extensions_mut()
+            .insert(audit::Changes::new(Some(&i_before), Some(&i)));
+
+        Ok(resp)
+    }
+}
+
+fn integration_kinds(
+    kinds: impl Iterator<Item = api::IntegrationKind>,
+) -> Result<Vec<application::IntegrationKind>, Status> {
+    kinds.map(integration_kind).collect()
+}
+
+fn integration_kind(k: api::IntegrationKind) -> Result<application::IntegrationKind, Status> {
+    k.from_proto().ok_or_else(|| {
+        Status::invalid_argument(format!("{:?} is not an application integration", k))
+    })
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use crate::api::auth::validator::RequestValidator;
+    use crate::test;
+
+    #[tokio::test]
+    async fn test_application() {
+        let _guard = test::prepare().await;
+
+        // setup admin user
+        let u = user::User {
+            is_admin: true,
+            is_active: true,
+            email: "admin@admin".into(),
+            email_verified: true,
+            ..Default::default()
+        };
+        let u = user::create(u).await.unwrap();
+
+        // create tenant
+        let t = tenant::create(tenant::Tenant {
+            name: "test-tenant".into(),
+            can_have_gateways: true,
+            max_gateway_count: 10,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        // setup api
+        let service = Application::new(RequestValidator::new());
+
+        // create
+        let create_req = api::CreateApplicationRequest {
             application: Some(api::Application {
                 tenant_id: t.id.to_string(),
                 name: "test-app".into(),
@@ -2004,6 +2948,7 @@ pub mod test {
             tenant_id: t.id.to_string(),
             limit: 10,
             offset: 0,
+            deleted: false,
         };
         let mut list_req = Request::new(list_req);
         list_req.extensions_mut().insert(AuthID::User(u.id.clone()));
@@ -2026,6 +2971,165 @@ pub mod test {
         del_req.extensions_mut().insert(AuthID::User(u.id.clone()));
         let del_resp = service.delete(del_req).await;
         assert!(del_resp.is_err());
+
+        // list deleted
+        let list_req = api::ListApplicationsRequest {
+            search: "updated".into(),
+            tenant_id: t.id.to_string(),
+            limit: 10,
+            offset: 0,
+            deleted: true,
+        };
+        let mut list_req = Request::new(list_req);
+        list_req.extensions_mut().insert(AuthID::User(u.id.clone()));
+        let list_resp = service.list(list_req).await.unwrap();
+        assert_eq!(1, list_resp.get_ref().total_count);
+        assert!(list_resp.get_ref().result[0].deleted_at.is_some());
+
+        // restore
+        let restore_req = api::RestoreApplicationRequest {
+            id: create_resp.id.clone(),
+        };
+        let mut restore_req = Request::new(restore_req);
+        restore_req
+            .extensions_mut()
+            .insert(AuthID::User(u.id.clone()));
+        let _ = service.restore(restore_req).await.unwrap();
+
+        let get_req = api::GetApplicationRequest {
+            id: create_resp.id.clone(),
+        };
+        let mut get_req = Request::new(get_req);
+        get_req.extensions_mut().insert(AuthID::User(u.id.clone()));
+        assert!(service.get(get_req).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_application_user() {
+        let _guard = test::prepare().await;
+
+        let admin = user::create(user::User {
+            is_admin: true,
+            is_active: true,
+            email: "admin@admin".into(),
+            email_verified: true,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let u = user::create(user::User {
+            is_active: true,
+            email: "user@user".into(),
+            email_verified: true,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        let t = tenant::test::create_tenant().await;
+        tenant::add_user(tenant::TenantUser {
+            tenant_id: t.id,
+            user_id: u.id,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let app_a = application::test::create_application(Some(t.id)).await;
+        let _app_b = application::test::create_application(Some(t.id)).await;
+
+        let service = Application::new(RequestValidator::new());
+
+        let list_apps = |user_id: Uuid| {
+            let mut list_req = Request::new(api::ListApplicationsRequest {
+                tenant_id: t.id.to_string(),
+                limit: 10,
+                ..Default::default()
+            });
+            list_req.extensions_mut().insert(AuthID::User(user_id));
+            list_req
+        };
+
+        // without memberships, the tenant user sees all applications
+        let list_resp = service.list(list_apps(u.id)).await.unwrap();
+        assert_eq!(2, list_resp.get_ref().total_count);
+
+        // add
+        let add_req = api::AddApplicationUserRequest {
+            application_user: Some(api::ApplicationUser {
+                application_id: app_a.id.to_string(),
+                email: "user@user".into(),
+                is_device_admin: true,
+                ..Default::default()
+            }),
+        };
+        let mut add_req = Request::new(add_req);
+        add_req.extensions_mut().insert(AuthID::User(admin.id));
+        service.add_user(add_req).await.unwrap();
+
+        // the user only sees the applications it is member of
+        let list_resp = service.list(list_apps(u.id)).await.unwrap();
+        assert_eq!(1, list_resp.get_ref().total_count);
+        assert_eq!(app_a.id.to_string(), list_resp.get_ref().result[0].id);
+
+        // admin still sees all applications
+        let list_resp = service.list(list_apps(admin.id)).await.unwrap();
+        assert_eq!(2, list_resp.get_ref().total_count);
+
+        // update
+        let up_req = api::UpdateApplicationUserRequest {
+            application_user: Some(api::ApplicationUser {
+                application_id: app_a.id.to_string(),
+                user_id: u.id.to_string(),
+                is_admin: true,
+                ..Default::default()
+            }),
+        };
+        let mut up_req = Request::new(up_req);
+        up_req.extensions_mut().insert(AuthID::User(admin.id));
+        service.update_user(up_req).await.unwrap();
+
+        // get (as the user itself)
+        let get_req = api::GetApplicationUserRequest {
+            application_id: app_a.id.to_string(),
+            user_id: u.id.to_string(),
+        };
+        let mut get_req = Request::new(get_req);
+        get_req.extensions_mut().insert(AuthID::User(u.id));
+        let get_resp = service.get_user(get_req).await.unwrap();
+        assert_eq!(
+            Some(api::ApplicationUser {
+                application_id: app_a.id.to_string(),
+                user_id: u.id.to_string(),
+                email: "user@user".into(),
+                is_admin: true,
+                is_device_admin: false,
+            }),
+            get_resp.get_ref().application_user
+        );
+
+        // list
+        let list_req = api::ListApplicationUsersRequest {
+            application_id: app_a.id.to_string(),
+            limit: 10,
+            offset: 0,
+        };
+        let mut list_req = Request::new(list_req);
+        list_req.extensions_mut().insert(AuthID::User(u.id));
+        let list_resp = service.list_users(list_req).await.unwrap();
+        assert_eq!(1, list_resp.get_ref().total_count);
+        assert_eq!("user@user", list_resp.get_ref().result[0].email);
+
+        // delete
+        let del_req = api::DeleteApplicationUserRequest {
+            application_id: app_a.id.to_string(),
+            user_id: u.id.to_string(),
+        };
+        let mut del_req = Request::new(del_req);
+        del_req.extensions_mut().insert(AuthID::User(admin.id));
+        service.delete_user(del_req).await.unwrap();
+
+        let list_resp = service.list(list_apps(u.id)).await.unwrap();
+        assert_eq!(2, list_resp.get_ref().total_count);
     }
 
     async fn get_application() -> application::Application {
@@ -2070,6 +3174,39 @@ pub mod test {
         let u = get_user().await;
         let service = Application::new(RequestValidator::new());
 
+        // create with invalid configuration
+        for int in [
+            api::HttpIntegration {
+                application_id: app.id.to_string(),
+                event_endpoint_url: "http://example.com/{{event".into(),
+                ..Default::default()
+            },
+            api::HttpIntegration {
+                application_id: app.id.to_string(),
+                event_endpoint_url: "http://example.com".into(),
+                ca_cert: "-----BEGIN CERTIFICATE-----\nMIIB\n-----END CERTIFICATE-----".into(),
+                ..Default::default()
+            },
+            api::HttpIntegration {
+                application_id: app.id.to_string(),
+                event_endpoint_url: "http://example.com".into(),
+                tls_cert: "invalid".into(),
+                ..Default::default()
+            },
+        ] {
+            let create_req = get_request(
+                &u.id,
+                api::CreateHttpIntegrationRequest {
+                    integration: Some(int),
+                },
+            );
+            let status = service
+                .create_http_integration(create_req)
+                .await
+                .unwrap_err();
+            assert_eq!(tonic::Code::InvalidArgument, status.code());
+        }
+
         // create
         let create_req = get_request(
             &u.id,
@@ -2082,6 +3219,8 @@ pub mod test {
                         .collect(),
                     encoding: api::Encoding::Json.into(),
                     event_endpoint_url: "http://example.com".into(),
+                    signing_secret: "secret".into(),
+                    ..Default::default()
                 }),
             },
         );
@@ -2105,6 +3244,7 @@ pub mod test {
                     .collect(),
                 encoding: api::Encoding::Json.into(),
                 event_endpoint_url: "http://example.com".into(),
+                ..Default::default()
             }),
             get_resp.integration
         );
@@ -2121,6 +3261,7 @@ pub mod test {
                         .collect(),
                     encoding: api::Encoding::Protobuf.into(),
                     event_endpoint_url: "http://example.org".into(),
+                    ..Default::default()
                 }),
             },
         );
@@ -2144,10 +3285,22 @@ pub mod test {
                     .collect(),
                 encoding: api::Encoding::Protobuf.into(),
                 event_endpoint_url: "http://example.org".into(),
+                ..Default::default()
             }),
             get_resp.integration
         );
 
+        // the (write-only) signing secret has been kept
+        let i = application::get_integration(&app.id, application::IntegrationKind::Http)
+            .await
+            .unwrap();
+        match i.configuration {
+            application::IntegrationConfiguration::Http(conf) => {
+                assert_eq!("secret", conf.signing_secret);
+            }
+            _ => panic!("Invalid configuration"),
+        }
+
         // list
         let list_req = get_request(
             &u.id,
@@ -2224,6 +3377,9 @@ pub mod test {
                     token: "testtoken".into(),
                     organization: "testorg".into(),
                     bucket: "testbucket".into(),
+                    flush_interval_ms: 1000,
+                    tag_variables: vec!["site".into()],
+                    rx_metadata: true,
                 }),
             },
         );
@@ -2254,6 +3410,9 @@ pub mod test {
                 token: "testtoken".into(),
                 organization: "testorg".into(),
                 bucket: "testbucket".into(),
+                flush_interval_ms: 1000,
+                tag_variables: vec!["site".into()],
+                rx_metadata: true,
             }),
             get_resp.integration
         );
@@ -2274,6 +3433,9 @@ pub mod test {
                     token: "testtoken".into(),
                     organization: "testorg".into(),
                     bucket: "testbucket".into(),
+                    flush_interval_ms: 0,
+                    tag_variables: vec![],
+                    rx_metadata: false,
                 }),
             },
         );
@@ -2304,6 +3466,9 @@ pub mod test {
                 token: "testtoken".into(),
                 organization: "testorg".into(),
                 bucket: "testbucket".into(),
+                flush_interval_ms: 0,
+                tag_variables: vec![],
+                rx_metadata: false,
             }),
             get_resp.integration
         );
@@ -3486,4 +4651,251 @@ pub mod test {
             list_resp
         );
     }
+
+    #[tokio::test]
+    async fn test_integration_dead_letters() {
+        let _guard = test::prepare().await;
+        let app = get_application().await;
+        let u = get_user().await;
+        let service = Application::new(RequestValidator::new());
+
+        let mut conf = (*crate::config::get()).clone();
+        conf.integration.outbox.max_attempts = 1;
+        crate::config::set(conf);
+
+        for kind in [
+            application::IntegrationKind::Http,
+            application::IntegrationKind::InfluxDb,
+        ] {
+            let item = integration_outbox::create(integration_outbox::OutboxItem {
+                application_id: app.id,
+                kind,
+                event: "up".into(),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+            integration_outbox::set_failed(&item.id, "timeout")
+                .await
+                .unwrap();
+        }
+
+        // list
+        let list_req = get_request(
+            &u.id,
+            api::ListIntegrationDeadLettersRequest {
+                application_id: app.id.to_string(),
+                kinds: vec![api::IntegrationKind::Http.into()],
+                limit: 10,
+                offset: 0,
+            },
+        );
+        let list_resp = service
+            .list_integration_dead_letters(list_req)
+            .await
+            .unwrap();
+        let list_resp = list_resp.get_ref();
+        assert_eq!(1, list_resp.total_count);
+        assert_eq!(1, list_resp.result.len());
+        assert_eq!(api::IntegrationKind::Http, list_resp.result[0].kind());
+        assert_eq!("timeout", list_resp.result[0].last_error);
+
+        // replay
+        let replay_req = get_request(
+            &u.id,
+            api::ReplayIntegrationDeadLettersRequest {
+                application_id: app.id.to_string(),
+                kinds: vec![api::IntegrationKind::Http.into()],
+            },
+        );
+        let replay_resp = service
+            .replay_integration_dead_letters(replay_req)
+            .await
+            .unwrap();
+        assert_eq!(1, replay_resp.get_ref().count);
+
+        // purge
+        let purge_req = get_request(
+            &u.id,
+            api::PurgeIntegrationDeadLettersRequest {
+                application_id: app.id.to_string(),
+                kinds: vec![],
+            },
+        );
+        let purge_resp = service
+            .purge_integration_dead_letters(purge_req)
+            .await
+            .unwrap();
+        assert_eq!(1, purge_resp.get_ref().count);
+
+        // global mqtt integration
+        let purge_req = get_request(
+            &u.id,
+            api::PurgeIntegrationDeadLettersRequest {
+                application_id: app.id.to_string(),
+                kinds: vec![api::IntegrationKind::MqttGlobal.into()],
+            },
+        );
+        assert!(service
+            .purge_integration_dead_letters(purge_req)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_integration_filter() {
+        let _guard = test::prepare().await;
+        let app = get_application().await;
+        let u = get_user().await;
+        let service = Application::new(RequestValidator::new());
+
+        let create_req = get_request(
+            &u.id,
+            api::CreateHttpIntegrationRequest {
+                integration: Some(api::HttpIntegration {
+                    application_id: app.id.to_string(),
+                    encoding: api::Encoding::Json.into(),
+                    event_endpoint_url: "http://example.com".into(),
+                    ..Default::default()
+                }),
+            },
+        );
+        let _ = service.create_http_integration(create_req).await.unwrap();
+
+        // get (default)
+        let get_req = get_request(
+            &u.id,
+            api::GetIntegrationFilterRequest {
+                application_id: app.id.to_string(),
+                kind: api::IntegrationKind::Http.into(),
+            },
+        );
+        let get_resp = service.get_integration_filter(get_req).await.unwrap();
+        assert_eq!(
+            Some(api::IntegrationFilter::default()),
+            get_resp.get_ref().filter
+        );
+
+        // update
+        let filter = api::IntegrationFilter {
+            events: vec!["up".into()],
+            f_ports: vec![10],
+            device_profile_ids: vec![Uuid::new_v4().to_string()],
+            tags: [("foo".to_string(), "bar".to_string())]
+                .iter()
+                .cloned()
+                .collect(),
+            expression: "data.temperature > 20".into(),
+        };
+        let update_req = get_request(
+            &u.id,
+            api::UpdateIntegrationFilterRequest {
+                application_id: app.id.to_string(),
+                kind: api::IntegrationKind::Http.into(),
+                filter: Some(filter.clone()),
+            },
+        );
+        let _ = service.update_integration_filter(update_req).await.unwrap();
+
+        // get
+        let get_req = get_request(
+            &u.id,
+            api::GetIntegrationFilterRequest {
+                application_id: app.id.to_string(),
+                kind: api::IntegrationKind::Http.into(),
+            },
+        );
+        let get_resp = service.get_integration_filter(get_req).await.unwrap();
+        assert_eq!(Some(filter), get_resp.get_ref().filter);
+
+        // invalid event
+        let update_req = get_request(
+            &u.id,
+            api::UpdateIntegrationFilterRequest {
+                application_id: app.id.to_string(),
+                kind: api::IntegrationKind::Http.into(),
+                filter: Some(api::IntegrationFilter {
+                    events: vec!["foo".into()],
+                    ..Default::default()
+                }),
+            },
+        );
+        assert!(service.update_integration_filter(update_req).await.is_err());
+
+        // integration does not exist
+        let get_req = get_request(
+            &u.id,
+            api::GetIntegrationFilterRequest {
+                application_id: app.id.to_string(),
+                kind: api::IntegrationKind::InfluxDb.into(),
+            },
+        );
+        assert!(service.get_integration_filter(get_req).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_mqtt_integration_client_certificates() {
+        let _guard = test::prepare().await;
+        let app = get_application().await;
+        let u = get_user().await;
+        let service = Application::new(RequestValidator::new());
+
+        let c = mqtt_client_certificate::create(mqtt_client_certificate::ClientCertificate {
+            serial_number: "0a0b0c0d".into(),
+            application_id: app.id,
+            expires_at: chrono::Utc::now() + chrono::Duration::days(1),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        // list
+        let list_req = get_request(
+            &u.id,
+            api::ListMqttIntegrationClientCertificatesRequest {
+                application_id: app.id.to_string(),
+                limit: 10,
+                offset: 0,
+            },
+        );
+        let list_resp = service
+            .list_mqtt_integration_client_certificates(list_req)
+            .await
+            .unwrap();
+        let list_resp = list_resp.get_ref();
+        assert_eq!(1, list_resp.total_count);
+        assert_eq!(1, list_resp.result.len());
+        assert_eq!("0a0b0c0d", list_resp.result[0].serial_number);
+        assert!(list_resp.result[0].revoked_at.is_none());
+
+        // revoke (the serial number is case-insensitive)
+        let revoke_req = get_request(
+            &u.id,
+            api::RevokeMqttIntegrationClientCertificateRequest {
+                application_id: app.id.to_string(),
+                serial_number: "0A0B0C0D".into(),
+            },
+        );
+        let _ = service
+            .revoke_mqtt_integration_client_certificate(revoke_req)
+            .await
+            .unwrap();
+        let c = mqtt_client_certificate::test::get(&c.serial_number)
+            .await
+            .unwrap();
+        assert!(c.revoked_at.is_some());
+
+        // revoke again
+        let revoke_req = get_request(
+            &u.id,
+            api::RevokeMqttIntegrationClientCertificateRequest {
+                application_id: app.id.to_string(),
+                serial_number: c.serial_number.clone(),
+            },
+        );
+        assert!(service
+            .revoke_mqtt_integration_client_certificate(revoke_req)
+            .await
+            .is_err());
+    }
 }
@@ -0,0 +1,710 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use anyhow::Result;
+use chrono::Utc;
+use serde_json::{json, Map, Value};
+use sha2::{Digest, Sha256};
+use tracing::{error, trace};
+use uuid::Uuid;
+
+use lrwn::EUI64;
+
+use super::auth::{self, AuthID};
+use crate::config;
+use crate::helpers::errors::PrintFullError;
+use crate::storage::{
+    api_key, application, audit_log, device, device_keys, device_profile, fields, gateway,
+    multicast, tenant, tenant_role, user,
+};
+
+// Redacted values are represented by this prefix and the digest of the secret, such that a
+// changed secret results in a change entry. The digest is never written to the audit log.
+const REDACTED: &str = "<redacted>";
+
+// Changes made to an entity by an API call. This is set by the API handlers as response
+// extension and picked up by the ApiLogger layer when writing the audit log.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Changes(pub Value);
+
+impl Changes {
+    // Returns the changed fields, in the format {field: {"before": .., "after": ..}}.
+    // For a create, before must be None. For a delete, after must be None.
+    pub fn new<T: Auditable>(before: Option<&T>, after: Option<&T>) -> Self {
+        let before = before.map(|v| v.audit_fields()).unwrap_or_default();
+        let after = after.map(|v| v.audit_fields()).unwrap_or_default();
+        let mut out = Map::new();
+
+        for k in before.keys().chain(after.keys()) {
+            let b = before.get(k).cloned().unwrap_or(Value::Null);
+            let a = after.get(k).cloned().unwrap_or(Value::Null);
+
+            if b != a {
+                out.insert(
+                    k.clone(),
+                    json!({"before": hide_digest(b), "after": hide_digest(a)}),
+                );
+            }
+        }
+
+        Changes(Value::Object(out))
+    }
+
+    // Returns the given field as UUID, preferring the after value over the before value.
+    fn get_uuid(&self, field: &str) -> Option<Uuid> {
+        let v = self.0.get(field)?;
+        ["after", "before"]
+            .iter()
+            .filter_map(|k| v.get(k).and_then(|v| v.as_str()))
+            .find_map(|v| Uuid::from_str(v).ok())
+    }
+}
+
+// Auditable is implemented by the entities for which the API handlers record the changes.
+// Secrets must never be returned as-is.
+pub trait Auditable {
+    fn audit_fields(&self) -> Map<String, Value>;
+}
+
+impl Auditable for tenant::Tenant {
+    fn audit_fields(&self) -> Map<String, Value> {
+        to_map(vec![
+            ("id", json!(self.id)),
+            ("name", json!(self.name)),
+            ("description", json!(self.description)),
+            ("can_have_gateways", json!(self.can_have_gateways)),
+            ("max_device_count", json!(self.max_device_count)),
+            ("max_gateway_count", json!(self.max_gateway_count)),
+            ("private_gateways_up", json!(self.private_gateways_up)),
+            ("private_gateways_down", json!(self.private_gateways_down)),
+            ("tags", json!(*self.tags)),
+        ])
+    }
+}
+
+impl Auditable for application::Application {
+    fn audit_fields(&self) -> Map<String, Value> {
+        to_map(vec![
+            ("id", json!(self.id)),
+            ("tenant_id", json!(self.tenant_id)),
+            ("name", json!(self.name)),
+            ("description", json!(self.description)),
+            ("tags", json!(*self.tags)),
+        ])
+    }
+}
+
+impl Auditable for device::Device {
+    fn audit_fields(&self) -> Map<String, Value> {
+        to_map(vec![
+            ("dev_eui", json!(self.dev_eui)),
+            ("application_id", json!(self.application_id)),
+            ("device_profile_id", json!(self.device_profile_id)),
+            ("name", json!(self.name)),
+            ("description", json!(self.description)),
+            ("join_eui", json!(self.join_eui)),
+            ("skip_fcnt_check", json!(self.skip_fcnt_check)),
+            ("is_disabled", json!(self.is_disabled)),
+            ("tags", json!(*self.tags)),
+            ("variables", json!(*self.variables)),
+        ])
+    }
+}
+
+impl Auditable for device_profile::DeviceProfile {
+    fn audit_fields(&self) -> Map<String, Value> {
+        to_map(vec![
+            ("id", json!(self.id)),
+            ("tenant_id", json!(self.tenant_id)),
+            ("name", json!(self.name)),
+            ("description", json!(self.description)),
+            ("region", json!(self.region)),
+            ("region_config_id", json!(self.region_config_id)),
+            ("mac_version", json!(self.mac_version.to_string())),
+            (
+                "reg_params_revision",
+                json!(self.reg_params_revision.to_string()),
+            ),
+            ("adr_algorithm_id", json!(self.adr_algorithm_id)),
+            (
+                "payload_codec_runtime",
+                json!(self.payload_codec_runtime.to_string()),
+            ),
+            ("payload_codec_script", json!(self.payload_codec_script)),
+            (
+                "flush_queue_on_activate",
+                json!(self.flush_queue_on_activate),
+            ),
+            ("uplink_interval", json!(self.uplink_interval)),
+            (
+                "device_status_req_interval",
+                json!(self.device_status_req_interval),
+            ),
+            ("supports_otaa", json!(self.supports_otaa)),
+            ("supports_class_b", json!(self.supports_class_b)),
+            ("supports_class_c", json!(self.supports_class_c)),
+            ("class_b_timeout", json!(self.class_b_timeout)),
+            ("class_b_ping_slot_nb_k", json!(self.class_b_ping_slot_nb_k)),
+            ("class_b_ping_slot_dr", json!(self.class_b_ping_slot_dr)),
+            ("class_b_ping_slot_freq", json!(self.class_b_ping_slot_freq)),
+            ("class_c_timeout", json!(self.class_c_timeout)),
+            ("abp_rx1_delay", json!(self.abp_rx1_delay)),
+            ("abp_rx1_dr_offset", json!(self.abp_rx1_dr_offset)),
+            ("abp_rx2_dr", json!(self.abp_rx2_dr)),
+            ("abp_rx2_freq", json!(self.abp_rx2_freq)),
+            ("tags", json!(*self.tags)),
+            ("measurements", json!(*self.measurements)),
+            (
+                "auto_detect_measurements",
+                json!(self.auto_detect_measurements),
+            ),
+            ("is_relay", json!(self.is_relay)),
+            ("is_relay_ed", json!(self.is_relay_ed)),
+            ("relay_ed_relay_only", json!(self.relay_ed_relay_only)),
+            ("relay_enabled", json!(self.relay_enabled)),
+            ("relay_cad_periodicity", json!(self.relay_cad_periodicity)),
+            (
+                "relay_default_channel_index",
+                json!(self.relay_default_channel_index),
+            ),
+            (
+                "relay_second_channel_freq",
+                json!(self.relay_second_channel_freq),
+            ),
+            (
+                "relay_second_channel_dr",
+                json!(self.relay_second_channel_dr),
+            ),
+            (
+                "relay_second_channel_ack_offset",
+                json!(self.relay_second_channel_ack_offset),
+            ),
+            (
+                "relay_ed_activation_mode",
+                json!(self.relay_ed_activation_mode),
+            ),
+            (
+                "relay_ed_smart_enable_level",
+                json!(self.relay_ed_smart_enable_level),
+            ),
+            ("relay_ed_back_off", json!(self.relay_ed_back_off)),
+            (
+                "relay_ed_uplink_limit_bucket_size",
+                json!(self.relay_ed_uplink_limit_bucket_size),
+            ),
+            (
+                "relay_ed_uplink_limit_reload_rate",
+                json!(self.relay_ed_uplink_limit_reload_rate),
+            ),
+            (
+                "relay_join_req_limit_reload_rate",
+                json!(self.relay_join_req_limit_reload_rate),
+            ),
+            (
+                "relay_notify_limit_reload_rate",
+                json!(self.relay_notify_limit_reload_rate),
+            ),
+            (
+                "relay_global_uplink_limit_reload_rate",
+                json!(self.relay_global_uplink_limit_reload_rate),
+            ),
+            (
+                "relay_overall_limit_reload_rate",
+                json!(self.relay_overall_limit_reload_rate),
+            ),
+            (
+                "relay_join_req_limit_bucket_size",
+                json!(self.relay_join_req_limit_bucket_size),
+            ),
+            (
+                "relay_notify_limit_bucket_size",
+                json!(self.relay_notify_limit_bucket_size),
+            ),
+            (
+                "relay_global_uplink_limit_bucket_size",
+                json!(self.relay_global_uplink_limit_bucket_size),
+            ),
+            (
+                "relay_overall_limit_bucket_size",
+                json!(self.relay_overall_limit_bucket_size),
+            ),
+            ("allow_roaming", json!(self.allow_roaming)),
+            ("rx1_delay", json!(self.rx1_delay)),
+            ("lr_fhss_rx_window", json!(self.lr_fhss_rx_window)),
+        ])
+    }
+}
+
+impl Auditable for gateway::Gateway {
+    fn audit_fields(&self) -> Map<String, Value> {
+        to_map(vec![
+            ("gateway_id", json!(self.gateway_id)),
+            ("tenant_id", json!(self.tenant_id)),
+            ("name", json!(self.name)),
+            ("description", json!(self.description)),
+            ("latitude", json!(self.latitude)),
+            ("longitude", json!(self.longitude)),
+            ("altitude", json!(self.altitude)),
+            ("stats_interval_secs", json!(self.stats_interval_secs)),
+            ("tags", json!(*self.tags)),
+            (
+                "fine_timestamp_key",
+                redact(self.fine_timestamp_key.as_ref().map(|v| v.to_vec())),
+            ),
+        ])
+    }
+}
+
+impl Auditable for multicast::MulticastGroup {
+    fn audit_fields(&self) -> Map<String, Value> {
+        to_map(vec![
+            ("id", json!(self.id)),
+            ("application_id", json!(self.application_id)),
+            ("name", json!(self.name)),
+            ("region", json!(self.region)),
+            ("mc_addr", json!(self.mc_addr)),
+            ("mc_nwk_s_key", redact(Some(self.mc_nwk_s_key.to_vec()))),
+            ("mc_app_s_key", redact(Some(self.mc_app_s_key.to_vec()))),
+            ("f_cnt", json!(self.f_cnt)),
+            ("group_type", json!(self.group_type)),
+            ("dr", json!(self.dr)),
+            ("frequency", json!(self.frequency)),
+            ("class_b_ping_slot_nb_k", json!(self.class_b_ping_slot_nb_k)),
+            (
+                "class_c_scheduling_type",
+                json!(self.class_c_scheduling_type.to_string()),
+            ),
+        ])
+    }
+}
+
+impl Auditable for tenant_role::TenantRole {
+    fn audit_fields(&self) -> Map<String, Value> {
+        to_map(vec![
+            ("id", json!(self.id)),
+            ("tenant_id", json!(self.tenant_id)),
+            ("name", json!(self.name)),
+            ("description", json!(self.description)),
+            ("can_write_applications", json!(self.can_write_applications)),
+            ("can_write_integrations", json!(self.can_write_integrations)),
+            ("can_write_devices", json!(self.can_write_devices)),
+            ("can_enqueue", json!(self.can_enqueue)),
+            (
+                "can_write_device_profiles",
+                json!(self.can_write_device_profiles),
+            ),
+            ("can_write_gateways", json!(self.can_write_gateways)),
+            (
+                "can_write_multicast_groups",
+                json!(self.can_write_multicast_groups),
+            ),
+        ])
+    }
+}
+
+impl Auditable for user::User {
+    fn audit_fields(&self) -> Map<String, Value> {
+        to_map(vec![
+            ("id", json!(self.id)),
+            ("external_id", json!(self.external_id)),
+            ("email", json!(self.email)),
+            ("email_verified", json!(self.email_verified)),
+            ("is_admin", json!(self.is_admin)),
+            ("is_active", json!(self.is_active)),
+            ("note", json!(self.note)),
+            (
+                "password_hash",
+                redact(Some(self.password_hash.as_bytes().to_vec())),
+            ),
+            ("totp_secret", redact(self.totp_secret.clone())),
+            ("totp_enabled", json!(self.totp_enabled)),
+            ("locked_until", json!(self.locked_until)),
+            ("sessions_revoked_at", json!(self.sessions_revoked_at)),
+        ])
+    }
+}
+
+impl Auditable for api_key::ApiKey {
+    fn audit_fields(&self) -> Map<String, Value> {
+        to_map(vec![
+            ("id", json!(self.id)),
+            ("name", json!(self.name)),
+            ("is_admin", json!(self.is_admin)),
+            ("tenant_id", json!(self.tenant_id)),
+            ("application_id", json!(self.application_id)),
+            ("device_profile_id", json!(self.device_profile_id)),
+            ("can_read", json!(self.can_read)),
+            ("can_write", json!(self.can_write)),
+            ("can_enqueue", json!(self.can_enqueue)),
+            ("can_read_keys", json!(self.can_read_keys)),
+            ("expires_at", json!(self.expires_at)),
+        ])
+    }
+}
+
+impl Auditable for tenant::TenantUser {
+    fn audit_fields(&self) -> Map<String, Value> {
+        to_map(vec![
+            ("tenant_id", json!(self.tenant_id)),
+            ("user_id", json!(self.user_id)),
+            ("is_admin", json!(self.is_admin)),
+            ("is_device_admin", json!(self.is_device_admin)),
+            ("is_gateway_admin", json!(self.is_gateway_admin)),
+            ("scim_group", json!(self.scim_group)),
+        ])
+    }
+}
+
+impl Auditable for device_keys::DeviceKeys {
+    fn audit_fields(&self) -> Map<String, Value> {
+        to_map(vec![
+            ("dev_eui", json!(self.dev_eui)),
+            ("nwk_key", redact(Some(self.nwk_key.to_vec()))),
+            ("app_key", redact(Some(self.app_key.to_vec()))),
+        ])
+    }
+}
+
+// The integration configuration is recorded per field. The fields containing secrets (or
+// headers, which might contain credentials) are redacted.
+impl Auditable for application::Integration {
+    fn audit_fields(&self) -> Map<String, Value> {
+        const SECRETS: [&str; 9] = [
+            "headers",
+            "signing_secret",
+            "tls_key",
+            "password",
+            "token",
+            "secret_access_key",
+            "connection_string",
+            "credentials_file",
+            "key",
+        ];
+
+        fn flatten(prefix: &str, v: &Value, out: &mut Map<String, Value>) {
+            if let Value::Object(m) = v {
+                for (k, v) in m {
+                    let key = format!("{}.{}", prefix, k);
+                    if SECRETS.contains(&k.as_str()) {
+                        let is_set = !(v.is_null()
+                            || v.as_str() == Some("")
+                            || v.as_object().map(|v| v.is_empty()).unwrap_or_default());
+                        out.insert(key, redact(is_set.then(|| v.to_string().into_bytes())));
+                    } else if v.is_object() {
+                        flatten(&key, v, out);
+                    } else {
+                        out.insert(key, v.clone());
+                    }
+                }
+            }
+        }
+
+        let mut out = to_map(vec![
+            ("application_id", json!(self.application_id)),
+            ("kind", json!(self.kind.to_string())),
+            ("filter", json!(self.filter)),
+        ]);
+
+        // The configuration is serialized as {"Kind": {..}}.
+        if let Ok(Value::Object(conf)) = serde_json::to_value(&self.configuration) {
+            for v in conf.values() {
+                flatten("configuration", v, &mut out);
+            }
+        }
+
+        out
+    }
+}
+
+fn to_map(items: Vec<(&str, Value)>) -> Map<String, Value> {
+    items.into_iter().map(|(k, v)| (k.to_string(), v)).collect()
+}
+
+fn redact(secret: Option<Vec<u8>>) -> Value {
+    match secret {
+        Some(v) if !v.is_empty() => {
+            json!(format!("{}:{}", REDACTED, hex::encode(Sha256::digest(&v))))
+        }
+        _ => Value::Null,
+    }
+}
+
+fn hide_digest(v: Value) -> Value {
+    match v {
+        Value::String(s) if s.starts_with(REDACTED) => json!(REDACTED),
+        _ => v,
+    }
+}
+
+// Returns true when the given gRPC method could mutate data. Only these are written to the
+// audit log. Login attempts are written by the login handler (see log_login).
+pub fn is_mutating(service: &str, method: &str) -> bool {
+    if !service.starts_with("api.") {
+        return false;
+    }
+
+    if ["Get", "List", "Stream"]
+        .iter()
+        .any(|p| method.starts_with(p))
+    {
+        return false;
+    }
+
+    ![
+        "GlobalSearch",
+        "Profile",
+        "Settings",
+        "DecodeFrame",
+        "Login",
+        "OpenIdConnectLogin",
+        "OAuth2Login",
+    ]
+    .contains(&method)
+}
+
+// Logs the login attempt. Failed attempts are attributed to the user matching the e-mail (if
+// any), the lockout state of the user is stored in the metadata.
+pub async fn log_login(
+    email: &str,
+    res: &Result<chirpstack_api::api::LoginResponse, tonic::Status>,
+) -> Result<()> {
+    let conf = config::get();
+    if !conf.api.audit_log.enabled {
+        return Ok(());
+    }
+
+    let mut metadata = HashMap::new();
+    metadata.insert("email".to_string(), email.to_string());
+
+    let u = user::get_by_email(email).await.ok();
+    if let Some(u) = &u {
+        if u.is_locked() {
+            if let Some(locked_until) = &u.locked_until {
+                metadata.insert("locked_until".to_string(), locked_until.to_rfc3339());
+            }
+        }
+    }
+
+    let status_code = match res {
+        Ok(v) => {
+            if v.mfa_required {
+                metadata.insert("mfa_required".to_string(), "true".to_string());
+            }
+            tonic::Code::Ok
+        }
+        Err(e) => e.code(),
+    };
+
+    audit_log::create(audit_log::AuditLog {
+        user_id: u.map(|u| u.id),
+        service: "api.InternalService".into(),
+        method: "Login".into(),
+        status_code: format!("{:?}", status_code),
+        metadata: fields::KeyValue::new(metadata),
+        ..Default::default()
+    })
+    .await?;
+
+    Ok(())
+}
+
+pub struct Request {
+    pub service: String,
+    pub method: String,
+    pub status_code: String,
+    pub authorization: Option<String>,
+    // Set when the request has been authenticated using an external token.
+    pub auth_id: Option<AuthID>,
+    pub metadata: HashMap<String, String>,
+    pub changes: Option<Changes>,
+}
+
+pub async fn log(req: Request) -> Result<()> {
+    let conf = config::get();
+    if !conf.api.audit_log.enabled {
+        return Ok(());
+    }
+
+    // Requests without (valid) authorization can not be attributed and are rejected by the
+    // API handlers before making any changes.
+    let auth_id = match (req.auth_id.clone(), &req.authorization) {
+        (Some(v), _) => v,
+        (None, Some(v)) => auth::get_auth_id(v).unwrap_or(AuthID::None),
+        (None, None) => AuthID::None,
+    };
+
+    let (user_id, api_key_id) = match auth_id {
+        AuthID::User(id) => (Some(id), None),
+        AuthID::Key(id) => (None, Some(id)),
+        AuthID::None => {
+            trace!(service = %req.service, method = %req.method, "Skipping audit log for unauthenticated request");
+            return Ok(());
+        }
+    };
+
+    let (tenant_id, application_id, dev_eui) = get_scope(&req.metadata, &req.changes).await;
+
+    audit_log::create(audit_log::AuditLog {
+        user_id,
+        api_key_id,
+        service: req.service,
+        method: req.method,
+        status_code: req.status_code,
+        tenant_id,
+        application_id,
+        dev_eui,
+        metadata: fields::KeyValue::new(req.metadata),
+        changes: req.changes.map(|v| v.0).unwrap_or_else(|| json!({})),
+        ..Default::default()
+    })
+    .await?;
+
+    Ok(())
+}
+
+// Resolves the tenant, application and device to which the request applies, using the
+// x-log- metadata set by the API handlers, the recorded changes and finally the database.
+async fn get_scope(
+    metadata: &HashMap<String, String>,
+    changes: &Option<Changes>,
+) -> (Option<Uuid>, Option<Uuid>, Option<EUI64>) {
+    let get_uuid = |k: &str| -> Option<Uuid> {
+        metadata
+            .get(k)
+            .and_then(|v| Uuid::from_str(v).ok())
+            .or_else(|| changes.as_ref().and_then(|c| c.get_uuid(k)))
+    };
+    let get_eui64 =
+        |k: &str| -> Option<EUI64> { metadata.get(k).and_then(|v| EUI64::from_str(v).ok()) };
+
+    let dev_eui = get_eui64("dev_eui")
+        .or_else(|| get_eui64("device_dev_eui"))
+        .or_else(|| get_eui64("relay_dev_eui"));
+
+    let mut application_id = get_uuid("application_id");
+    if application_id.is_none() {
+        if let Some(dev_eui) = &dev_eui {
+            application_id = device::get(dev_eui).await.ok().map(|d| d.application_id);
+        } else if let Some(id) = get_uuid("multicast_group_id") {
+            application_id = multicast::get(&id).await.ok().map(|mg| mg.application_id);
+        }
+    }
+
+    let mut tenant_id = get_uuid("tenant_id");
+    if tenant_id.is_none() {
+        if let Some(id) = &application_id {
+            tenant_id = application::get(id).await.ok().map(|a| a.tenant_id);
+        } else if let Some(id) = get_uuid("device_profile_id") {
+            tenant_id = device_profile::get(&id).await.ok().map(|dp| dp.tenant_id);
+        } else if let Some(gateway_id) = get_eui64("gateway_id") {
+            tenant_id = gateway::get(&gateway_id).await.ok().map(|gw| gw.tenant_id);
+        }
+    }
+
+    (tenant_id, application_id, dev_eui)
+}
+
+// Periodically removes the audit log entries older than the configured max age.
+pub async fn cleanup_loop() {
+    let conf = config::get();
+    if conf.api.audit_log.max_age.is_zero() {
+        return;
+    }
+
+    let max_age = match chrono::Duration::from_std(conf.api.audit_log.max_age) {
+        Ok(v) => v,
+        Err(e) => {
+            error!(error = %e, "Invalid audit log max_age");
+            return;
+        }
+    };
+
+    let mut interval = tokio::time::interval(std::time::Duration::from_secs(60 * 60));
+    loop {
+        interval.tick().await;
+
+        if let Err(e) = audit_log::delete_older_than(Utc::now() - max_age).await {
+            error!(error = %e.full(), "Audit log cleanup error");
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+
+    #[test]
+    fn test_changes() {
+        let before = tenant::Tenant {
+            name: "before".into(),
+            ..Default::default()
+        };
+        let after = tenant::Tenant {
+            name: "after".into(),
+            can_have_gateways: true,
+            ..before.clone()
+        };
+
+        // update
+        assert_eq!(
+            json!({
+                "name": {"before": "before", "after": "after"},
+                "can_have_gateways": {"before": false, "after": true},
+            }),
+            Changes::new(Some(&before), Some(&after)).0
+        );
+
+        // no changes
+        assert_eq!(json!({}), Changes::new(Some(&before), Some(&before)).0);
+
+        // delete
+        let changes = Changes::new(Some(&before), None);
+        assert_eq!(
+            json!({"before": "before", "after": null}),
+            changes.0["name"]
+        );
+        assert_eq!(Some(before.id), changes.get_uuid("id"));
+    }
+
+    #[test]
+    fn test_redact() {
+        let gw = gateway::Gateway {
+            fine_timestamp_key: Some(lrwn::AES128Key::from_bytes([1; 16])),
+            ..Default::default()
+        };
+        let changes = Changes::new(None, Some(&gw));
+        assert_eq!(
+            json!({"before": null, "after": "<redacted>"}),
+            changes.0["fine_timestamp_key"]
+        );
+
+        // key rotation
+        let gw_rotated = gateway::Gateway {
+            fine_timestamp_key: Some(lrwn::AES128Key::from_bytes([2; 16])),
+            ..gw.clone()
+        };
+        let changes = Changes::new(Some(&gw), Some(&gw_rotated));
+        assert_eq!(
+            json!({"before": "<redacted>", "after": "<redacted>"}),
+            changes.0["fine_timestamp_key"]
+        );
+
+        // unchanged key
+        assert_eq!(json!({}), Changes::new(Some(&gw), Some(&gw)).0);
+    }
+
+    #[test]
+    fn test_is_mutating() {
+        assert!(is_mutating("api.DeviceService", "Update"));
+        assert!(is_mutating("api.DeviceService", "Enqueue"));
+        assert!(is_mutating("api.InternalService", "CreateApiKey"));
+        assert!(!is_mutating("api.DeviceService", "Get"));
+        assert!(!is_mutating("api.DeviceService", "ListQueue"));
+        assert!(!is_mutating("api.InternalService", "StreamDeviceFrames"));
+        assert!(!is_mutating("api.InternalService", "Login"));
+        assert!(!is_mutating(
+            "grpc.reflection.v1alpha.ServerReflection",
+            "ServerReflectionInfo"
+        ));
+    }
+}
@@ -3,15 +3,19 @@ use std::ops::Add;
 
 use anyhow::Result;
 use chrono::{DateTime, Duration, Utc};
-use jsonwebtoken::{decode, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use jsonwebtoken::{Algorithm, Validation};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+use super::keys::KeySet;
+
 #[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
 pub struct AuthClaim {
     pub aud: String,
     #[serde(default, skip_serializing_if = "is_default")]
     pub exp: Option<usize>,
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub iat: Option<usize>,
     pub iss: String,
     pub sub: String,
     pub typ: String,
@@ -29,37 +33,34 @@ impl AuthClaim {
         AuthClaim {
             aud: "chirpstack".to_string(),
             exp: Some(exp.timestamp() as usize),
+            iat: Some(nbf.timestamp() as usize),
             iss: "chirpstack".to_string(),
             sub: id.to_string(),
             typ: "user".to_string(),
         }
     }
 
-    pub fn new_for_api_key(id: &Uuid) -> Self {
+    pub fn new_for_api_key(id: &Uuid, expires_at: Option<DateTime<Utc>>) -> Self {
         AuthClaim {
             aud: "chirpstack".to_string(),
             iss: "chirpstack".to_string(),
             sub: id.to_string(),
             typ: "key".to_string(),
-            exp: None,
+            exp: expires_at.map(|v| v.timestamp() as usize),
+            iat: None,
         }
     }
 
-    pub fn encode(&self, secret: &[u8]) -> Result<String> {
-        Ok(encode(
-            &Header::default(),
-            self,
-            &EncodingKey::from_secret(secret),
-        )?)
+    pub fn encode(&self, keys: &KeySet) -> Result<String> {
+        keys.encode(self)
     }
 
-    pub fn decode(token: &str, secret: &[u8]) -> Result<Self> {
+    pub fn decode(token: &str, keys: &KeySet) -> Result<Self> {
         let mut val = Validation::new(Algorithm::HS256);
         val.set_audience(&["chirpstack"]);
         val.required_spec_claims = HashSet::new(); // make the 'exp' optional
 
-        let claim = decode::<AuthClaim>(token, &DecodingKey::from_secret(secret), &val)?;
-        Ok(claim.claims)
+        keys.decode(token, &val)
     }
 }
 
@@ -69,40 +70,50 @@ pub mod test {
 
     #[test]
     fn test_for_user() {
-        let secrect = &"verysecret";
-        let other_secret = &"notsosecret";
+        let secrect = KeySet::new(b"verysecret");
+        let other_secret = KeySet::new(b"notsosecret");
         let user_id = Uuid::new_v4();
         let key_id = Uuid::new_v4();
 
         let nbf: DateTime<Utc> = Utc::now();
         let exp = nbf.add(-Duration::try_days(1).unwrap());
 
-        let claim = AuthClaim::new_for_api_key(&key_id);
+        let claim = AuthClaim::new_for_api_key(&key_id, None);
         assert_eq!("key", claim.typ);
         assert_eq!(key_id.to_string(), claim.sub);
 
-        let token = claim.encode(secrect.as_ref()).unwrap();
-        let decoded = AuthClaim::decode(&token, secrect.as_ref()).unwrap();
+        let token = claim.encode(&secrect).unwrap();
+        let decoded = AuthClaim::decode(&token, &secrect).unwrap();
         assert_eq!(claim, decoded);
 
+        // expiring api key token
+        let claim =
+            AuthClaim::new_for_api_key(&key_id, Some(nbf.add(Duration::try_days(1).unwrap())));
+        let token = claim.encode(&secrect).unwrap();
+        let decoded = AuthClaim::decode(&token, &secrect).unwrap();
+        assert_eq!(claim, decoded);
+
+        // expired api key token
+        let claim = AuthClaim::new_for_api_key(&key_id, Some(exp));
+        let token = claim.encode(&secrect).unwrap();
+        assert_eq!(true, AuthClaim::decode(&token, &secrect).is_err());
+
         // user token
         let mut claim = AuthClaim::new_for_user(&user_id);
         assert_eq!("user", claim.typ);
         assert_eq!(user_id.to_string(), claim.sub);
+        assert!(claim.iat.is_some());
 
-        let token = claim.encode(secrect.as_ref()).unwrap();
-        let decoded = AuthClaim::decode(&token, secrect.as_ref()).unwrap();
+        let token = claim.encode(&secrect).unwrap();
+        let decoded = AuthClaim::decode(&token, &secrect).unwrap();
         assert_eq!(claim, decoded);
 
         // different key
-        assert_eq!(
-            true,
-            AuthClaim::decode(&token, other_secret.as_ref()).is_err()
-        );
+        assert_eq!(true, AuthClaim::decode(&token, &other_secret).is_err());
 
         // expired
         claim.exp = Some(exp.timestamp() as usize);
-        let token = claim.encode(secrect.as_ref()).unwrap();
-        assert_eq!(true, AuthClaim::decode(&token, secrect.as_ref()).is_err());
+        let token = claim.encode(&secrect).unwrap();
+        assert_eq!(true, AuthClaim::decode(&token, &secrect).is_err());
     }
 }
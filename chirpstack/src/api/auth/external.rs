@@ -0,0 +1,316 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::str::FromStr;
+use std::sync::RwLock;
+use std::task::{Context, Poll};
+
+use anyhow::{Context as _, Result};
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::{Algorithm, DecodingKey, Validation};
+use serde_json::{Map, Value};
+use tower::Service;
+use tracing::{error, info, warn};
+
+use super::{keys, AuthID, IssuedAt};
+use crate::config;
+use crate::helpers::errors::PrintFullError;
+use crate::storage::user;
+
+lazy_static! {
+    static ref JWKS: RwLock<Option<JwkSet>> = RwLock::new(None);
+}
+
+// Periodically fetches the JWKS of the external token issuer.
+pub async fn refresh_loop() {
+    let conf = config::get();
+    if !conf.api.external_token.enabled {
+        return;
+    }
+
+    let mut interval = tokio::time::interval(conf.api.external_token.jwks_refresh_interval);
+    loop {
+        interval.tick().await;
+
+        if let Err(e) = refresh().await {
+            error!(error = %e.full(), "Refresh external token JWKS error");
+        }
+    }
+}
+
+async fn refresh() -> Result<()> {
+    let conf = config::get();
+    let url = &conf.api.external_token.jwks_url;
+
+    info!(url = %url, "Fetching external token JWKS");
+    let client = reqwest::Client::builder()
+        .timeout(std::time::Duration::from_secs(10))
+        .build()?;
+    let jwks: JwkSet = client
+        .get(url)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await
+        .context("Decode JWKS")?;
+
+    set_jwks(jwks);
+    Ok(())
+}
+
+pub fn set_jwks(jwks: JwkSet) {
+    let mut cache = JWKS.write().unwrap();
+    *cache = Some(jwks);
+}
+
+// Authenticates the given authorization value ('Bearer <TOKEN>') against the external token
+// issuer. None is returned when the token is not an external token (e.g. it has been issued by
+// ChirpStack), in which case it is handled by the auth interceptor.
+pub async fn authenticate(auth_str: &str) -> Result<Option<(AuthID, IssuedAt)>> {
+    let conf = config::get();
+    if !conf.api.external_token.enabled {
+        return Ok(None);
+    }
+
+    let token = match auth_str.strip_prefix("Bearer ") {
+        Some(v) => v,
+        None => return Ok(None),
+    };
+
+    let header = jsonwebtoken::decode_header(token)?;
+    let kid = match &header.kid {
+        Some(v) if !keys::get().contains(v) => v,
+        _ => return Ok(None),
+    };
+
+    let (decoding_key, algorithm) = {
+        let cache = JWKS.read().unwrap();
+        let jwk = cache
+            .as_ref()
+            .and_then(|v| v.find(kid))
+            .ok_or_else(|| anyhow!("Unknown kid: {}", kid))?;
+        let key_algorithm = jwk
+            .common
+            .key_algorithm
+            .ok_or_else(|| anyhow!("JWK has no algorithm, kid: {}", kid))?;
+        let algorithm = Algorithm::from_str(&key_algorithm.to_string())
+            .map_err(|_| anyhow!("Unsupported key algorithm: {}", key_algorithm))?;
+        (DecodingKey::from_jwk(jwk)?, algorithm)
+    };
+
+    // The algorithm is taken from the JWK, as the token header is controlled by the sender.
+    if header.alg != algorithm {
+        return Err(anyhow!(
+            "Token algorithm {:?} does not match key algorithm {:?}",
+            header.alg,
+            algorithm
+        ));
+    }
+
+    // Only asymmetric algorithms are accepted, as the public keys are published.
+    if matches!(
+        algorithm,
+        Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512
+    ) {
+        return Err(anyhow!("Unsupported algorithm: {:?}", algorithm));
+    }
+
+    if conf.api.external_token.issuer.is_empty() {
+        return Err(anyhow!("External token issuer is not configured"));
+    }
+
+    let mut validation = Validation::new(algorithm);
+    validation.set_issuer(&[&conf.api.external_token.issuer]);
+    if conf.api.external_token.audience.is_empty() {
+        validation.validate_aud = false;
+    } else {
+        validation.set_audience(&[&conf.api.external_token.audience]);
+    }
+
+    let claims =
+        jsonwebtoken::decode::<Map<String, Value>>(token, &decoding_key, &validation)?.claims;
+    let external_id = claims
+        .get(&conf.api.external_token.external_id_claim)
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| {
+            anyhow!(
+                "Claim '{}' is missing",
+                conf.api.external_token.external_id_claim
+            )
+        })?;
+
+    let u = user::get_by_external_id(external_id).await?;
+    if !u.is_active {
+        return Err(anyhow!("User is not active"));
+    }
+
+    let iat = claims
+        .get("iat")
+        .and_then(|v| v.as_u64())
+        .map(|v| v as usize);
+
+    Ok(Some((AuthID::User(u.id), IssuedAt(iat))))
+}
+
+// ExternalAuth authenticates requests using tokens issued by the external token issuer. As the
+// user lookup is async, this can not be implemented in the (sync) auth interceptor. On success,
+// the AuthID is set as request extension, which is then used by the auth interceptor.
+#[derive(Clone)]
+pub struct ExternalAuth {}
+
+impl<S> tower::Layer<S> for ExternalAuth {
+    type Service = ExternalAuthService<S>;
+
+    fn layer(&self, service: S) -> Self::Service {
+        ExternalAuthService { inner: service }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ExternalAuthService<S> {
+    inner: S,
+}
+
+impl<S, ReqBody, ResBody> Service<http::Request<ReqBody>> for ExternalAuthService<S>
+where
+    S: Service<http::Request<ReqBody>, Response = http::Response<ResBody>> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+    ReqBody: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut request: http::Request<ReqBody>) -> Self::Future {
+        // Take the service that was ready, leaving a clone in its place.
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+
+        let authorization = request
+            .headers()
+            .get("authorization")
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.to_string());
+
+        Box::pin(async move {
+            if let Some(authorization) = authorization {
+                match authenticate(&authorization).await {
+                    Ok(Some((auth_id, issued_at))) => {
+                        request.extensions_mut().insert(auth_id);
+                        request.extensions_mut().insert(issued_at);
+                    }
+                    Ok(None) => {}
+                    Err(e) => {
+                        warn!(error = %e.full(), "External token authentication failed");
+                    }
+                }
+            }
+
+            inner.call(request).await
+        })
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use crate::test;
+    use chrono::Utc;
+    use elliptic_curve::pkcs8::{EncodePrivateKey, LineEnding};
+    use elliptic_curve::SecretKey;
+    use jsonwebtoken::jwk::KeyAlgorithm;
+    use rand_core::OsRng;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_authenticate() {
+        let _guard = test::prepare().await;
+
+        let mut conf = (*config::get()).clone();
+        conf.api.external_token.enabled = true;
+        conf.api.external_token.issuer = "https://idp.example.com".into();
+        conf.api.external_token.audience = "chirpstack".into();
+        conf.api.external_token.external_id_claim = "oid".into();
+        config::set(conf);
+
+        let u = user::create(user::User {
+            email: "idp@example.com".into(),
+            external_id: Some("idp-user".into()),
+            is_active: true,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        // The key-set of the external token issuer.
+        let pkey: SecretKey<p256::NistP256> = SecretKey::random(&mut OsRng);
+        let pem = pkey.to_pkcs8_pem(LineEnding::default()).unwrap();
+        let mut idp = keys::KeySet::new(b"");
+        idp.add_key("idp-1", "ES256", pem.as_str()).unwrap();
+        idp.set_signing_key_id("idp-1").unwrap();
+
+        let exp = Utc::now().timestamp() + 3600;
+        let claims = json!({
+            "iss": "https://idp.example.com",
+            "aud": "chirpstack",
+            "oid": "idp-user",
+            "iat": 1000,
+            "exp": exp,
+        });
+        let token = format!("Bearer {}", idp.encode(&claims).unwrap());
+
+        // JWKS has not been fetched.
+        assert!(authenticate(&token).await.is_err());
+
+        set_jwks(idp.jwks());
+        assert_eq!(
+            Some((AuthID::User(u.id), IssuedAt(Some(1000)))),
+            authenticate(&token).await.unwrap()
+        );
+
+        // Tokens issued by ChirpStack are not handled.
+        let token_cs = format!(
+            "Bearer {}",
+            keys::KeySet::new(b"secret").encode(&claims).unwrap()
+        );
+        assert_eq!(None, authenticate(&token_cs).await.unwrap());
+
+        // Invalid issuer.
+        let mut claims_iss = claims.clone();
+        claims_iss["iss"] = json!("https://other.example.com");
+        let token_iss = format!("Bearer {}", idp.encode(&claims_iss).unwrap());
+        assert!(authenticate(&token_iss).await.is_err());
+
+        // Key algorithm does not match the token algorithm.
+        let mut jwks = idp.jwks();
+        jwks.keys[0].common.key_algorithm = Some(KeyAlgorithm::ES384);
+        set_jwks(jwks);
+        assert!(authenticate(&token).await.is_err());
+        set_jwks(idp.jwks());
+
+        // Issuer is not configured.
+        let mut conf = (*config::get()).clone();
+        conf.api.external_token.issuer = "".into();
+        config::set(conf.clone());
+        assert!(authenticate(&token).await.is_err());
+        assert!(config::validate(&conf).is_err());
+        conf.api.external_token.issuer = "https://idp.example.com".into();
+        config::set(conf);
+
+        // Unknown user.
+        let mut claims_user = claims.clone();
+        claims_user["oid"] = json!("unknown-user");
+        let token_user = format!("Bearer {}", idp.encode(&claims_user).unwrap());
+        assert!(authenticate(&token_user).await.is_err());
+
+        // Disabled.
+        let mut conf = (*config::get()).clone();
+        conf.api.external_token.enabled = false;
+        config::set(conf);
+        assert_eq!(None, authenticate(&token).await.unwrap());
+    }
+}
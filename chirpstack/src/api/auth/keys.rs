@@ -0,0 +1,328 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::sync::{Arc, RwLock};
+
+use anyhow::{Context, Result};
+use base64::{engine::general_purpose, Engine as _};
+use jsonwebtoken::jwk::{
+    AlgorithmParameters, CommonParameters, EllipticCurve, EllipticCurveKeyParameters,
+    EllipticCurveKeyType, Jwk, JwkSet, KeyAlgorithm, PublicKeyUse, RSAKeyParameters, RSAKeyType,
+};
+use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::config;
+use crate::helpers::tls::private_key_to_pkcs8;
+
+lazy_static! {
+    static ref KEY_SET: RwLock<Option<Arc<KeySet>>> = RwLock::new(None);
+}
+
+// Loads the key-set from the configuration. This must be called before the API is started.
+pub fn setup() -> Result<()> {
+    let conf = config::get();
+    set(KeySet::from_config(&conf.api)?);
+    Ok(())
+}
+
+pub fn set(ks: KeySet) {
+    let mut key_set = KEY_SET.write().unwrap();
+    *key_set = Some(Arc::new(ks));
+}
+
+// Returns the key-set. In case the key-set has not been loaded, a key-set containing only the
+// configured api.secret is returned.
+pub fn get() -> Arc<KeySet> {
+    let key_set = KEY_SET.read().unwrap();
+    match &*key_set {
+        Some(v) => v.clone(),
+        None => {
+            let conf = config::get();
+            Arc::new(KeySet::new(conf.api.secret.as_bytes()))
+        }
+    }
+}
+
+struct Key {
+    algorithm: Algorithm,
+    encoding_key: EncodingKey,
+    decoding_key: DecodingKey,
+    jwk: Jwk,
+}
+
+// KeySet contains the keys used for signing and validating tokens.
+//
+// Tokens without kid header are signed and validated using the (HS256) secret. Tokens with kid
+// header are validated using the key with the matching key ID, using the algorithm of that key.
+pub struct KeySet {
+    secret: Vec<u8>,
+    signing_key_id: Option<String>,
+    keys: BTreeMap<String, Key>,
+}
+
+impl KeySet {
+    pub fn new(secret: &[u8]) -> Self {
+        KeySet {
+            secret: secret.to_vec(),
+            signing_key_id: None,
+            keys: BTreeMap::new(),
+        }
+    }
+
+    pub fn from_config(conf: &config::Api) -> Result<Self> {
+        let mut ks = KeySet::new(conf.secret.as_bytes());
+
+        for k in &conf.signing_keys {
+            let pem = fs::read_to_string(&k.private_key)
+                .context(format!("Read signing key: {}", k.private_key))?;
+            ks.add_key(&k.kid, &k.algorithm, &pem)
+                .context(format!("Load signing key, kid: {}", k.kid))?;
+        }
+
+        if !conf.signing_key_id.is_empty() {
+            ks.set_signing_key_id(&conf.signing_key_id)?;
+        }
+
+        Ok(ks)
+    }
+
+    // Adds the private key (PEM) for the given key ID and algorithm (RS256 or ES256).
+    pub fn add_key(&mut self, kid: &str, algorithm: &str, pem: &str) -> Result<()> {
+        if kid.is_empty() {
+            return Err(anyhow!("kid must be set"));
+        }
+        if self.keys.contains_key(kid) {
+            return Err(anyhow!("Duplicate kid: {}", kid));
+        }
+
+        let (algorithm, key_algorithm, encoding_key, params) = match algorithm {
+            "RS256" => {
+                use rsa::{
+                    pkcs1::DecodeRsaPrivateKey, pkcs8::DecodePrivateKey, traits::PublicKeyParts,
+                    RsaPrivateKey,
+                };
+
+                let pkey = if pem.contains("RSA PRIVATE KEY") {
+                    RsaPrivateKey::from_pkcs1_pem(pem).context("Read RSA PKCS#1")?
+                } else {
+                    RsaPrivateKey::from_pkcs8_pem(pem).context("Read RSA PKCS#8")?
+                };
+
+                (
+                    Algorithm::RS256,
+                    KeyAlgorithm::RS256,
+                    EncodingKey::from_rsa_pem(pem.as_bytes())?,
+                    AlgorithmParameters::RSA(RSAKeyParameters {
+                        key_type: RSAKeyType::RSA,
+                        n: general_purpose::URL_SAFE_NO_PAD.encode(pkey.n().to_bytes_be()),
+                        e: general_purpose::URL_SAFE_NO_PAD.encode(pkey.e().to_bytes_be()),
+                    }),
+                )
+            }
+            "ES256" => {
+                use elliptic_curve::{pkcs8::DecodePrivateKey, sec1::ToEncodedPoint, SecretKey};
+
+                let pem = private_key_to_pkcs8(pem)?;
+                let pkey: SecretKey<p256::NistP256> =
+                    SecretKey::from_pkcs8_pem(&pem).context("Read EC PKCS#8")?;
+                let point = pkey.public_key().to_encoded_point(false);
+
+                (
+                    Algorithm::ES256,
+                    KeyAlgorithm::ES256,
+                    EncodingKey::from_ec_pem(pem.as_bytes())?,
+                    AlgorithmParameters::EllipticCurve(EllipticCurveKeyParameters {
+                        key_type: EllipticCurveKeyType::EC,
+                        curve: EllipticCurve::P256,
+                        x: general_purpose::URL_SAFE_NO_PAD
+                            .encode(point.x().ok_or_else(|| anyhow!("Missing x coordinate"))?),
+                        y: general_purpose::URL_SAFE_NO_PAD
+                            .encode(point.y().ok_or_else(|| anyhow!("Missing y coordinate"))?),
+                    }),
+                )
+            }
+            _ => {
+                return Err(anyhow!("Unsupported algorithm: {}", algorithm));
+            }
+        };
+
+        let jwk = Jwk {
+            common: CommonParameters {
+                public_key_use: Some(PublicKeyUse::Signature),
+                key_algorithm: Some(key_algorithm),
+                key_id: Some(kid.to_string()),
+                ..Default::default()
+            },
+            algorithm: params,
+        };
+
+        self.keys.insert(
+            kid.to_string(),
+            Key {
+                algorithm,
+                encoding_key,
+                decoding_key: DecodingKey::from_jwk(&jwk)?,
+                jwk,
+            },
+        );
+
+        Ok(())
+    }
+
+    // Sets the key ID of the key used for signing new tokens.
+    pub fn set_signing_key_id(&mut self, kid: &str) -> Result<()> {
+        if !self.keys.contains_key(kid) {
+            return Err(anyhow!("Signing key does not exist, kid: {}", kid));
+        }
+        self.signing_key_id = Some(kid.to_string());
+        Ok(())
+    }
+
+    pub fn contains(&self, kid: &str) -> bool {
+        self.keys.contains_key(kid)
+    }
+
+    pub fn encode<T: Serialize>(&self, claims: &T) -> Result<String> {
+        match self
+            .signing_key_id
+            .as_ref()
+            .and_then(|kid| self.keys.get(kid).map(|key| (kid, key)))
+        {
+            Some((kid, key)) => {
+                let mut header = Header::new(key.algorithm);
+                header.kid = Some(kid.clone());
+                Ok(jsonwebtoken::encode(&header, claims, &key.encoding_key)?)
+            }
+            None => Ok(jsonwebtoken::encode(
+                &Header::default(),
+                claims,
+                &EncodingKey::from_secret(&self.secret),
+            )?),
+        }
+    }
+
+    // Decodes and validates the token. The algorithms of the given validation are overwritten by
+    // the algorithm of the key, to prevent algorithm confusion.
+    pub fn decode<T: DeserializeOwned>(&self, token: &str, validation: &Validation) -> Result<T> {
+        let header = jsonwebtoken::decode_header(token)?;
+        let mut validation = validation.clone();
+
+        let data = match &header.kid {
+            Some(kid) => {
+                let key = self
+                    .keys
+                    .get(kid)
+                    .ok_or_else(|| anyhow!("Unknown kid: {}", kid))?;
+                validation.algorithms = vec![key.algorithm];
+                jsonwebtoken::decode::<T>(token, &key.decoding_key, &validation)?
+            }
+            None => {
+                validation.algorithms = vec![Algorithm::HS256];
+                jsonwebtoken::decode::<T>(
+                    token,
+                    &DecodingKey::from_secret(&self.secret),
+                    &validation,
+                )?
+            }
+        };
+
+        Ok(data.claims)
+    }
+
+    // Returns the public keys as JSON Web Key Set.
+    pub fn jwks(&self) -> JwkSet {
+        JwkSet {
+            keys: self.keys.values().map(|k| k.jwk.clone()).collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use elliptic_curve::pkcs8::{EncodePrivateKey, LineEnding};
+    use elliptic_curve::SecretKey;
+    use rand_core::OsRng;
+    use serde::Deserialize;
+
+    #[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+    struct Claims {
+        aud: String,
+        sub: String,
+    }
+
+    fn ec_pem() -> String {
+        let pkey: SecretKey<p256::NistP256> = SecretKey::random(&mut OsRng);
+        pkey.to_pkcs8_pem(LineEnding::default())
+            .unwrap()
+            .as_str()
+            .to_string()
+    }
+
+    #[test]
+    fn test_key_set() {
+        let claims = Claims {
+            aud: "chirpstack".into(),
+            sub: "user".into(),
+        };
+        let mut val = Validation::new(Algorithm::HS256);
+        val.set_audience(&["chirpstack"]);
+        val.required_spec_claims.clear();
+
+        let mut ks = KeySet::new(b"secret");
+        assert!(ks.add_key("key-1", "HS256", &ec_pem()).is_err());
+        ks.add_key("key-1", "ES256", &ec_pem()).unwrap();
+        ks.add_key("key-2", "ES256", &ec_pem()).unwrap();
+        assert!(ks.add_key("key-2", "ES256", &ec_pem()).is_err());
+        assert!(ks.set_signing_key_id("key-3").is_err());
+
+        // signed using the secret
+        let token_secret = ks.encode(&claims).unwrap();
+        assert!(jsonwebtoken::decode_header(&token_secret)
+            .unwrap()
+            .kid
+            .is_none());
+        assert_eq!(claims, ks.decode(&token_secret, &val).unwrap());
+
+        // signed using key-1
+        ks.set_signing_key_id("key-1").unwrap();
+        let token_key_1 = ks.encode(&claims).unwrap();
+        let header = jsonwebtoken::decode_header(&token_key_1).unwrap();
+        assert_eq!(Some("key-1".to_string()), header.kid);
+        assert_eq!(Algorithm::ES256, header.alg);
+        assert_eq!(claims, ks.decode(&token_key_1, &val).unwrap());
+
+        // rotate to key-2, tokens signed by key-1 and the secret are still valid
+        ks.set_signing_key_id("key-2").unwrap();
+        let token_key_2 = ks.encode(&claims).unwrap();
+        for token in [&token_secret, &token_key_1, &token_key_2] {
+            assert_eq!(claims, ks.decode::<Claims>(token, &val).unwrap());
+        }
+
+        // a key-set without key-1 rejects the token signed by key-1
+        let mut ks_new = KeySet::new(b"secret");
+        ks_new.add_key("key-2", "ES256", &ec_pem()).unwrap();
+        assert!(ks_new.decode::<Claims>(&token_key_1, &val).is_err());
+
+        // different secret
+        assert!(KeySet::new(b"other")
+            .decode::<Claims>(&token_secret, &val)
+            .is_err());
+
+        // jwks
+        let jwks = ks.jwks();
+        assert_eq!(2, jwks.keys.len());
+        let jwk = jwks.find("key-1").unwrap();
+        assert_eq!(Some(KeyAlgorithm::ES256), jwk.common.key_algorithm);
+
+        let mut val_es256 = val.clone();
+        val_es256.algorithms = vec![Algorithm::ES256];
+        let decoded = jsonwebtoken::decode::<Claims>(
+            &token_key_1,
+            &DecodingKey::from_jwk(jwk).unwrap(),
+            &val_es256,
+        )
+        .unwrap();
+        assert_eq!(claims, decoded.claims);
+    }
+}
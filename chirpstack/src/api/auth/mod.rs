@@ -1,20 +1,29 @@
-use crate::config;
 use tonic::{Request, Status};
 use uuid::Uuid;
 
 pub mod claims;
 pub mod error;
+pub mod external;
+pub mod keys;
 pub mod validator;
 
-#[derive(PartialEq, Eq, Debug)]
+#[derive(PartialEq, Eq, Debug, Clone)]
 pub enum AuthID {
     None,
     User(Uuid),
     Key(Uuid),
 }
 
+// The issued-at timestamp of the token. This is used to reject tokens of users for which the
+// sessions have been revoked.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub struct IssuedAt(pub Option<usize>);
+
 pub fn auth_interceptor(mut req: Request<()>) -> Result<Request<()>, Status> {
-    let conf = config::get();
+    // The request has already been authenticated using an external token.
+    if req.extensions().get::<AuthID>().is_some() {
+        return Ok(req);
+    }
 
     let auth_str = match req.metadata().get("authorization") {
         Some(v) => match v.to_str() {
@@ -31,6 +40,20 @@ pub fn auth_interceptor(mut req: Request<()>) -> Result<Request<()>, Status> {
         }
     };
 
+    let (auth_id, issued_at) = decode_auth(auth_str)?;
+    req.extensions_mut().insert(auth_id);
+    req.extensions_mut().insert(issued_at);
+
+    Ok(req)
+}
+
+// Returns the AuthID for the given authorization metadata value. The value
+// must be in the format 'Bearer <TOKEN>'.
+pub fn get_auth_id(auth_str: &str) -> Result<AuthID, Status> {
+    decode_auth(auth_str).map(|(auth_id, _)| auth_id)
+}
+
+fn decode_auth(auth_str: &str) -> Result<(AuthID, IssuedAt), Status> {
     let auth_str = match auth_str.strip_prefix("Bearer ") {
         Some(v) => v,
         None => {
@@ -40,7 +63,7 @@ pub fn auth_interceptor(mut req: Request<()>) -> Result<Request<()>, Status> {
         }
     };
 
-    let token = match claims::AuthClaim::decode(auth_str, conf.api.secret.as_ref()) {
+    let token = match claims::AuthClaim::decode(auth_str, &keys::get()) {
         Ok(v) => v,
         Err(e) => {
             return Err(Status::unauthenticated(format!("{}", e)));
@@ -54,20 +77,14 @@ pub fn auth_interceptor(mut req: Request<()>) -> Result<Request<()>, Status> {
         }
     };
 
-    match token.typ.as_ref() {
-        "user" => {
-            req.extensions_mut().insert(AuthID::User(id));
-        }
-        "key" => {
-            req.extensions_mut().insert(AuthID::Key(id));
-        }
-        _ => {
-            return Err(Status::unauthenticated(format!(
-                "invalid token typ: {}",
-                token.typ
-            )));
-        }
-    };
+    let issued_at = IssuedAt(token.iat);
 
-    Ok(req)
+    match token.typ.as_ref() {
+        "user" => Ok((AuthID::User(id), issued_at)),
+        "key" => Ok((AuthID::Key(id), issued_at)),
+        _ => Err(Status::unauthenticated(format!(
+            "invalid token typ: {}",
+            token.typ
+        ))),
+    }
 }
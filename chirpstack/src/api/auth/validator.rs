@@ -9,12 +9,16 @@ use uuid::Uuid;
 use lrwn::EUI64;
 
 use super::error::Error;
-use crate::api::auth::AuthID;
+use crate::api::auth::{AuthID, IssuedAt};
 use crate::helpers::errors::PrintFullError;
+use crate::storage::api_key::{ApiKey, Permission};
+use crate::storage::application::UserAccess;
 use crate::storage::get_async_db_conn;
 use crate::storage::schema::{
-    api_key, application, device, device_profile, gateway, multicast_group, tenant_user, user,
+    api_key, application, application_user, device, device_profile, gateway, multicast_group,
+    tenant_role, tenant_user, tenant_user_role, user,
 };
+use crate::storage::tenant_role::{Permission as RolePermission, Scope as RoleScope};
 
 #[derive(Copy, Clone)]
 pub enum Flag {
@@ -26,6 +30,16 @@ pub enum Flag {
     List,
 }
 
+impl Flag {
+    // Returns the API key permission required for the flag.
+    fn api_key_permission(&self) -> Permission {
+        match self {
+            Flag::Read | Flag::List => Permission::Read,
+            _ => Permission::Write,
+        }
+    }
+}
+
 pub struct RequestValidator {}
 
 impl RequestValidator {
@@ -39,8 +53,27 @@ impl RequestValidator {
         auth_validator: impl Validator + Sync,
     ) -> Result<(), Status> {
         let id = ext.get::<AuthID>().unwrap();
+
+        if let AuthID::User(id) = id {
+            let issued_at = ext.get::<IssuedAt>().and_then(|v| v.0);
+            match crate::storage::user::is_session_revoked(id, issued_at).await {
+                Ok(false) => {}
+                Ok(true) => {
+                    return Err(Status::unauthenticated("session has been revoked"));
+                }
+                Err(e) => {
+                    error!(user_id = %id, error = %e.full(), "Get session revocation error");
+                    return Err(Status::internal(""));
+                }
+            }
+        }
+
         auth_validator.validate(id).await?;
 
+        if let AuthID::Key(id) = id {
+            crate::storage::api_key::set_last_used(id);
+        }
+
         Ok(())
     }
 }
@@ -49,10 +82,44 @@ impl RequestValidator {
 pub trait Validator {
     async fn validate_user(&self, id: &Uuid) -> Result<i64, Error>;
     async fn validate_key(&self, id: &Uuid) -> Result<i64, Error>;
+
+    // Returns the permission that the API key must have.
+    fn api_key_permission(&self) -> Permission;
+
+    // Validates that the resource is within the application / device-profile scope of the
+    // API key. By default, scoped API keys are not allowed.
+    async fn validate_key_scope(&self, _ak: &ApiKey) -> Result<bool, Error> {
+        Ok(false)
+    }
+
+    // Validates the expiration, permissions and scope of the API key before validating the
+    // access to the resource.
+    async fn validate_api_key(&self, id: &Uuid) -> Result<i64, Error> {
+        let ak: Option<ApiKey> = api_key::dsl::api_key
+            .find(id)
+            .first(&mut get_async_db_conn().await?)
+            .await
+            .optional()?;
+        let ak = match ak {
+            Some(v) => v,
+            None => return Ok(0),
+        };
+
+        if ak.is_expired() || !ak.has_permission(self.api_key_permission()) {
+            return Ok(0);
+        }
+
+        if ak.is_scoped() && !self.validate_key_scope(&ak).await? {
+            return Ok(0);
+        }
+
+        self.validate_key(id).await
+    }
+
     async fn validate(&self, id: &AuthID) -> Result<(), Status> {
         let res = match id {
             AuthID::User(id) => self.validate_user(id).await,
-            AuthID::Key(id) => self.validate_key(id).await,
+            AuthID::Key(id) => self.validate_api_key(id).await,
             AuthID::None => {
                 return Err(Status::unauthenticated("no authorization provided"));
             }
@@ -87,6 +154,10 @@ impl ValidateActiveUser {
 
 #[async_trait]
 impl Validator for ValidateActiveUser {
+    fn api_key_permission(&self) -> Permission {
+        Permission::Read
+    }
+
     async fn validate_key(&self, _: &Uuid) -> Result<i64, Error> {
         Ok(0)
     }
@@ -112,6 +183,10 @@ impl ValidateIsAdmin {
 
 #[async_trait]
 impl Validator for ValidateIsAdmin {
+    fn api_key_permission(&self) -> Permission {
+        Permission::Read
+    }
+
     async fn validate_key(&self, _: &Uuid) -> Result<i64, Error> {
         Ok(0)
     }
@@ -141,6 +216,10 @@ impl ValidateActiveUserOrKey {
 
 #[async_trait]
 impl Validator for ValidateActiveUserOrKey {
+    fn api_key_permission(&self) -> Permission {
+        Permission::Read
+    }
+
     async fn validate_key(&self, id: &Uuid) -> Result<i64, Error> {
         let count = api_key::dsl::api_key
             .select(dsl::count_star())
@@ -173,6 +252,10 @@ impl ValidateUsersAccess {
 
 #[async_trait]
 impl Validator for ValidateUsersAccess {
+    fn api_key_permission(&self) -> Permission {
+        self.flag.api_key_permission()
+    }
+
     async fn validate_user(&self, id: &Uuid) -> Result<i64, Error> {
         let mut q = user::dsl::user
             .select(dsl::count_star())
@@ -218,6 +301,10 @@ impl ValidateUserAccess {
 
 #[async_trait]
 impl Validator for ValidateUserAccess {
+    fn api_key_permission(&self) -> Permission {
+        self.flag.api_key_permission()
+    }
+
     async fn validate_user(&self, id: &Uuid) -> Result<i64, Error> {
         let mut q = user::dsl::user
             .select(dsl::count_star())
@@ -278,6 +365,10 @@ impl ValidateApiKeysAccess {
 
 #[async_trait]
 impl Validator for ValidateApiKeysAccess {
+    fn api_key_permission(&self) -> Permission {
+        self.flag.api_key_permission()
+    }
+
     async fn validate_user(&self, id: &Uuid) -> Result<i64, Error> {
         let mut q = user::dsl::user
             .select(dsl::count_star())
@@ -338,6 +429,10 @@ impl ValidateApiKeyAccess {
 
 #[async_trait]
 impl Validator for ValidateApiKeyAccess {
+    fn api_key_permission(&self) -> Permission {
+        self.flag.api_key_permission()
+    }
+
     async fn validate_user(&self, id: &Uuid) -> Result<i64, Error> {
         let mut q = user::dsl::user
             .select(dsl::count_star())
@@ -376,6 +471,83 @@ impl Validator for ValidateApiKeyAccess {
     }
 }
 
+pub struct ValidateAuditLogAccess {
+    flag: Flag,
+    tenant_id: Uuid,
+}
+
+impl ValidateAuditLogAccess {
+    pub fn new(flag: Flag, tenant_id: Option<Uuid>) -> Self {
+        ValidateAuditLogAccess {
+            flag,
+            tenant_id: match tenant_id {
+                Some(v) => v,
+                None => Uuid::nil(),
+            },
+        }
+    }
+}
+
+#[async_trait]
+impl Validator for ValidateAuditLogAccess {
+    fn api_key_permission(&self) -> Permission {
+        self.flag.api_key_permission()
+    }
+
+    async fn validate_user(&self, id: &Uuid) -> Result<i64, Error> {
+        let mut q = user::dsl::user
+            .select(dsl::count_star())
+            .filter(user::dsl::id.eq(&id).and(user::dsl::is_active.eq(true)))
+            .into_boxed();
+
+        match self.flag {
+            // admin user
+            // tenant admin
+            Flag::List => {
+                q = q.filter(
+                    user::dsl::is_admin.eq(true).or(dsl::exists(
+                        tenant_user::dsl::tenant_user.filter(
+                            tenant_user::dsl::tenant_id
+                                .eq(&self.tenant_id)
+                                .and(tenant_user::dsl::user_id.eq(user::dsl::id))
+                                .and(tenant_user::dsl::is_admin.eq(true)),
+                        ),
+                    )),
+                );
+            }
+            _ => {
+                return Ok(0);
+            }
+        };
+
+        Ok(q.first(&mut get_async_db_conn().await?).await?)
+    }
+
+    async fn validate_key(&self, id: &Uuid) -> Result<i64, Error> {
+        let mut q = api_key::dsl::api_key
+            .select(dsl::count_star())
+            .find(id)
+            .into_boxed();
+
+        match self.flag {
+            // admin api key
+            // tenant api key
+            Flag::List => {
+                q = q.filter(
+                    api_key::dsl::is_admin
+                        .eq(true)
+                        .or(api_key::dsl::tenant_id.eq(&self.tenant_id)),
+                );
+            }
+            _ => {
+                return Ok(0);
+            }
+        };
+
+        Ok(q.first(&mut get_async_db_conn().await?).await?)
+    }
+}
+
 pub struct ValidateTenantsAccess {
     flag: Flag,
 }
@@ -388,6 +560,10 @@ impl ValidateTenantsAccess {
 
 #[async_trait]
 impl Validator for ValidateTenantsAccess {
+    fn api_key_permission(&self) -> Permission {
+        self.flag.api_key_permission()
+    }
+
     async fn validate_user(&self, id: &Uuid) -> Result<i64, Error> {
         let mut q = user::dsl::user
             .select(dsl::count_star())
@@ -435,6 +611,10 @@ impl ValidateTenantAccess {
 
 #[async_trait]
 impl Validator for ValidateTenantAccess {
+    fn api_key_permission(&self) -> Permission {
+        self.flag.api_key_permission()
+    }
+
     async fn validate_user(&self, id: &Uuid) -> Result<i64, Error> {
         let mut q = user::dsl::user
             .select(dsl::count_star())
@@ -510,6 +690,10 @@ impl ValidateTenantUsersAccess {
 
 #[async_trait]
 impl Validator for ValidateTenantUsersAccess {
+    fn api_key_permission(&self) -> Permission {
+        self.flag.api_key_permission()
+    }
+
     async fn validate_user(&self, id: &Uuid) -> Result<i64, Error> {
         let mut q = user::dsl::user
             .select(dsl::count_star())
@@ -595,6 +779,10 @@ impl ValidateTenantUserAccess {
 
 #[async_trait]
 impl Validator for ValidateTenantUserAccess {
+    fn api_key_permission(&self) -> Permission {
+        self.flag.api_key_permission()
+    }
+
     async fn validate_user(&self, id: &Uuid) -> Result<i64, Error> {
         let mut q = user::dsl::user
             .select(dsl::count_star())
@@ -681,6 +869,10 @@ impl ValidateApplicationsAccess {
 
 #[async_trait]
 impl Validator for ValidateApplicationsAccess {
+    fn api_key_permission(&self) -> Permission {
+        self.flag.api_key_permission()
+    }
+
     async fn validate_user(&self, id: &Uuid) -> Result<i64, Error> {
         let mut q = user::dsl::user
             .select(dsl::count_star())
@@ -725,7 +917,17 @@ impl Validator for ValidateApplicationsAccess {
             }
         };
 
-        Ok(q.first(&mut get_async_db_conn().await?).await?)
+        let count = q.first(&mut get_async_db_conn().await?).await?;
+        if count == 0 && matches!(self.flag, Flag::Create) {
+            return validate_user_role(
+                id,
+                RolePermission::ApplicationWrite,
+                RoleScope::Tenant(self.tenant_id),
+            )
+            .await;
+        }
+
+        Ok(count)
     }
 
     async fn validate_key(&self, id: &Uuid) -> Result<i64, Error> {
@@ -778,6 +980,19 @@ impl ValidateApplicationAccess {
 
 #[async_trait]
 impl Validator for ValidateApplicationAccess {
+    fn api_key_permission(&self) -> Permission {
+        self.flag.api_key_permission()
+    }
+
+    async fn validate_key_scope(&self, ak: &ApiKey) -> Result<bool, Error> {
+        // Keys scoped to a device-profile only are allowed to read the application.
+        if ak.application_id.is_none() {
+            return Ok(matches!(self.flag, Flag::Read));
+        }
+
+        Ok(ak.in_scope(&self.application_id, None))
+    }
+
     async fn validate_user(&self, id: &Uuid) -> Result<i64, Error> {
         let mut q = user::dsl::user
             .select(dsl::count_star())
@@ -832,7 +1047,24 @@ impl Validator for ValidateApplicationAccess {
             }
         };
 
-        Ok(q.first(&mut get_async_db_conn().await?).await?)
+        let mut count = q.first(&mut get_async_db_conn().await?).await?;
+        if count == 0 && matches!(self.flag, Flag::Update | Flag::Delete) {
+            count = validate_user_role(
+                id,
+                RolePermission::ApplicationWrite,
+                RoleScope::Application(self.application_id),
+            )
+            .await?;
+        }
+
+        validate_application_user(
+            id,
+            RoleScope::Application(self.application_id),
+            matches!(self.flag, Flag::Update | Flag::Delete)
+                .then_some(RolePermission::ApplicationWrite),
+            count,
+        )
+        .await
     }
 
     async fn validate_key(&self, id: &Uuid) -> Result<i64, Error> {
@@ -862,129 +1094,198 @@ impl Validator for ValidateApplicationAccess {
     }
 }
 
-pub struct ValidateDeviceProfileTemplatesAccess {
+pub struct ValidateApplicationIntegrationsAccess {
     flag: Flag,
+    application_id: Uuid,
 }
 
-impl ValidateDeviceProfileTemplatesAccess {
-    pub fn new(flag: Flag) -> Self {
-        ValidateDeviceProfileTemplatesAccess { flag }
+impl ValidateApplicationIntegrationsAccess {
+    pub fn new(flag: Flag, app_id: Uuid) -> Self {
+        ValidateApplicationIntegrationsAccess {
+            flag,
+            application_id: app_id,
+        }
+    }
+
+    fn application_flag(&self) -> Flag {
+        match self.flag {
+            Flag::Read | Flag::List => Flag::Read,
+            _ => Flag::Update,
+        }
     }
 }
 
 #[async_trait]
-impl Validator for ValidateDeviceProfileTemplatesAccess {
-    async fn validate_user(&self, id: &Uuid) -> Result<i64, Error> {
-        let mut q = user::dsl::user
-            .select(dsl::count_star())
-            .filter(user::dsl::id.eq(id).and(user::dsl::is_active.eq(true)))
-            .into_boxed();
-
-        match self.flag {
-            // global admin
-            Flag::Create => {
-                q = q.filter(user::dsl::is_admin.eq(true));
-            }
-            // any active user
-            Flag::List => {}
-            _ => {
-                return Ok(0);
-            }
-        };
+impl Validator for ValidateApplicationIntegrationsAccess {
+    fn api_key_permission(&self) -> Permission {
+        self.flag.api_key_permission()
+    }
 
-        Ok(q.first(&mut get_async_db_conn().await?).await?)
+    async fn validate_key_scope(&self, ak: &ApiKey) -> Result<bool, Error> {
+        ValidateApplicationAccess::new(self.application_flag(), self.application_id)
+            .validate_key_scope(ak)
+            .await
     }
 
-    async fn validate_key(&self, id: &Uuid) -> Result<i64, Error> {
-        let mut q = api_key::dsl::api_key
-            .select(dsl::count_star())
-            .find(id)
-            .into_boxed();
+    async fn validate_user(&self, id: &Uuid) -> Result<i64, Error> {
+        // Integrations can be managed by the users that are allowed to update the application.
+        let mut count =
+            ValidateApplicationAccess::new(self.application_flag(), self.application_id)
+                .validate_user(id)
+                .await?;
+        if count == 0 && !matches!(self.flag, Flag::Read | Flag::List) {
+            count = validate_user_role(
+                id,
+                RolePermission::IntegrationWrite,
+                RoleScope::Application(self.application_id),
+            )
+            .await?;
+        }
 
-        match self.flag {
-            // admin api key
-            Flag::Create => {
-                q = q.filter(api_key::dsl::is_admin.eq(true));
-            }
-            // any api key
-            Flag::List => {}
-            _ => {
-                return Ok(0);
-            }
-        };
+        validate_application_user(
+            id,
+            RoleScope::Application(self.application_id),
+            (!matches!(self.flag, Flag::Read | Flag::List))
+                .then_some(RolePermission::IntegrationWrite),
+            count,
+        )
+        .await
+    }
 
-        Ok(q.first(&mut get_async_db_conn().await?).await?)
+    async fn validate_key(&self, id: &Uuid) -> Result<i64, Error> {
+        ValidateApplicationAccess::new(self.application_flag(), self.application_id)
+            .validate_key(id)
+            .await
     }
 }
 
-pub struct ValidateDeviceProfileTemplateAccess {
+pub struct ValidateApplicationUsersAccess {
     flag: Flag,
+    application_id: Uuid,
 }
 
-impl ValidateDeviceProfileTemplateAccess {
-    pub fn new(flag: Flag) -> Self {
-        ValidateDeviceProfileTemplateAccess { flag }
+impl ValidateApplicationUsersAccess {
+    pub fn new(flag: Flag, app_id: Uuid) -> Self {
+        ValidateApplicationUsersAccess {
+            flag,
+            application_id: app_id,
+        }
     }
 }
 
 #[async_trait]
-impl Validator for ValidateDeviceProfileTemplateAccess {
-    async fn validate_user(&self, id: &Uuid) -> Result<i64, Error> {
-        let mut q = user::dsl::user
-            .select(dsl::count_star())
-            .filter(user::dsl::id.eq(id).and(user::dsl::is_active.eq(true)))
-            .into_boxed();
+impl Validator for ValidateApplicationUsersAccess {
+    fn api_key_permission(&self) -> Permission {
+        self.flag.api_key_permission()
+    }
 
+    async fn validate_user(&self, id: &Uuid) -> Result<i64, Error> {
         match self.flag {
-            // any active user
-            Flag::Read => {}
-            // global admin user
-            Flag::Update | Flag::Delete => {
-                q = q.filter(user::dsl::is_admin.eq(true));
-            }
-            _ => {
-                return Ok(0);
+            // global admin
+            // tenant admin
+            // application admin
+            Flag::Create => validate_application_admin(id, &self.application_id).await,
+            // users that can read the application
+            Flag::List => {
+                ValidateApplicationAccess::new(Flag::Read, self.application_id)
+                    .validate_user(id)
+                    .await
             }
-        };
-
-        Ok(q.first(&mut get_async_db_conn().await?).await?)
+            _ => Ok(0),
+        }
     }
 
     async fn validate_key(&self, id: &Uuid) -> Result<i64, Error> {
-        let mut q = api_key::dsl::api_key
-            .select(dsl::count_star())
-            .find(id)
-            .into_boxed();
-
         match self.flag {
-            // any api key
-            Flag::Read => {}
             // admin api key
-            Flag::Update | Flag::Delete => {
-                q = q.filter(api_key::dsl::is_admin.eq(true));
-            }
-            _ => {
-                return Ok(0);
+            // tenant api key
+            Flag::Create | Flag::List => {
+                ValidateApplicationAccess::new(Flag::Update, self.application_id)
+                    .validate_key(id)
+                    .await
             }
-        };
-
-        Ok(q.first(&mut get_async_db_conn().await?).await?)
+            _ => Ok(0),
+        }
     }
 }
 
-pub struct ValidateDeviceProfilesAccess {
+pub struct ValidateApplicationUserAccess {
     flag: Flag,
-    tenant_id: Uuid,
+    application_id: Uuid,
+    user_id: Uuid,
 }
 
-impl ValidateDeviceProfilesAccess {
-    pub fn new(flag: Flag, tenant_id: Uuid) -> Self {
-        ValidateDeviceProfilesAccess { flag, tenant_id }
-    }
-}
+impl ValidateApplicationUserAccess {
+    pub fn new(flag: Flag, app_id: Uuid, user_id: Uuid) -> Self {
+        ValidateApplicationUserAccess {
+            flag,
+            application_id: app_id,
+            user_id,
+        }
+    }
+}
 
 #[async_trait]
-impl Validator for ValidateDeviceProfilesAccess {
+impl Validator for ValidateApplicationUserAccess {
+    fn api_key_permission(&self) -> Permission {
+        self.flag.api_key_permission()
+    }
+
+    async fn validate_user(&self, id: &Uuid) -> Result<i64, Error> {
+        match self.flag {
+            // global admin
+            // tenant admin
+            // application admin
+            // user itself
+            Flag::Read => {
+                if id == &self.user_id {
+                    return ValidateApplicationAccess::new(Flag::Read, self.application_id)
+                        .validate_user(id)
+                        .await;
+                }
+                validate_application_admin(id, &self.application_id).await
+            }
+            // global admin
+            // tenant admin
+            // application admin
+            Flag::Update | Flag::Delete => {
+                validate_application_admin(id, &self.application_id).await
+            }
+            _ => Ok(0),
+        }
+    }
+
+    async fn validate_key(&self, id: &Uuid) -> Result<i64, Error> {
+        match self.flag {
+            // admin api key
+            // tenant api key
+            Flag::Read | Flag::Update | Flag::Delete => {
+                ValidateApplicationAccess::new(Flag::Update, self.application_id)
+                    .validate_key(id)
+                    .await
+            }
+            _ => Ok(0),
+        }
+    }
+}
+
+pub struct ValidateTenantRolesAccess {
+    flag: Flag,
+    tenant_id: Uuid,
+}
+
+impl ValidateTenantRolesAccess {
+    pub fn new(flag: Flag, tenant_id: Uuid) -> Self {
+        ValidateTenantRolesAccess { flag, tenant_id }
+    }
+}
+
+#[async_trait]
+impl Validator for ValidateTenantRolesAccess {
+    fn api_key_permission(&self) -> Permission {
+        self.flag.api_key_permission()
+    }
+
     async fn validate_user(&self, id: &Uuid) -> Result<i64, Error> {
         let mut q = user::dsl::user
             .select(dsl::count_star())
@@ -994,7 +1295,6 @@ impl Validator for ValidateDeviceProfilesAccess {
         match self.flag {
             // global admin
             // tenant admin
-            // tenant device admin
             Flag::Create => {
                 q = q.filter(
                     user::dsl::is_admin.eq(true).or(dsl::exists(
@@ -1002,11 +1302,7 @@ impl Validator for ValidateDeviceProfilesAccess {
                             tenant_user::dsl::user_id
                                 .eq(user::dsl::id)
                                 .and(tenant_user::dsl::tenant_id.eq(&self.tenant_id))
-                                .and(
-                                    tenant_user::dsl::is_admin
-                                        .eq(true)
-                                        .or(tenant_user::dsl::is_device_admin.eq(true)),
-                                ),
+                                .and(tenant_user::dsl::is_admin.eq(true)),
                         ),
                     )),
                 );
@@ -1057,22 +1353,23 @@ impl Validator for ValidateDeviceProfilesAccess {
     }
 }
 
-pub struct ValidateDeviceProfileAccess {
+pub struct ValidateTenantRoleAccess {
     flag: Flag,
-    device_profile_id: Uuid,
+    role_id: Uuid,
 }
 
-impl ValidateDeviceProfileAccess {
-    pub fn new(flag: Flag, dp_id: Uuid) -> Self {
-        ValidateDeviceProfileAccess {
-            flag,
-            device_profile_id: dp_id,
-        }
+impl ValidateTenantRoleAccess {
+    pub fn new(flag: Flag, role_id: Uuid) -> Self {
+        ValidateTenantRoleAccess { flag, role_id }
     }
 }
 
 #[async_trait]
-impl Validator for ValidateDeviceProfileAccess {
+impl Validator for ValidateTenantRoleAccess {
+    fn api_key_permission(&self) -> Permission {
+        self.flag.api_key_permission()
+    }
+
     async fn validate_user(&self, id: &Uuid) -> Result<i64, Error> {
         let mut q = user::dsl::user
             .select(dsl::count_star())
@@ -1086,38 +1383,33 @@ impl Validator for ValidateDeviceProfileAccess {
                 q =
                     q.filter(
                         user::dsl::is_admin.eq(true).or(dsl::exists(
-                            device_profile::dsl::device_profile
+                            tenant_role::dsl::tenant_role
                                 .inner_join(tenant_user::table.on(
-                                    tenant_user::dsl::tenant_id.eq(device_profile::dsl::tenant_id),
+                                    tenant_user::dsl::tenant_id.eq(tenant_role::dsl::tenant_id),
                                 ))
                                 .filter(
-                                    device_profile::dsl::id
-                                        .eq(&self.device_profile_id)
+                                    tenant_role::dsl::id
+                                        .eq(&self.role_id)
                                         .and(tenant_user::dsl::user_id.eq(user::dsl::id)),
                                 ),
                         )),
                     );
             }
             // global admin
-            // tenant admin user
-            // tenant device admin
+            // tenant admin
             Flag::Update | Flag::Delete => {
                 q =
                     q.filter(
                         user::dsl::is_admin.eq(true).or(dsl::exists(
-                            device_profile::dsl::device_profile
+                            tenant_role::dsl::tenant_role
                                 .inner_join(tenant_user::table.on(
-                                    tenant_user::dsl::tenant_id.eq(device_profile::dsl::tenant_id),
+                                    tenant_user::dsl::tenant_id.eq(tenant_role::dsl::tenant_id),
                                 ))
                                 .filter(
-                                    device_profile::dsl::id
-                                        .eq(&self.device_profile_id)
+                                    tenant_role::dsl::id
+                                        .eq(&self.role_id)
                                         .and(tenant_user::dsl::user_id.eq(user::dsl::id))
-                                        .and(
-                                            tenant_user::dsl::is_admin
-                                                .eq(true)
-                                                .or(tenant_user::dsl::is_device_admin.eq(true)),
-                                        ),
+                                        .and(tenant_user::dsl::is_admin.eq(true)),
                                 ),
                         )),
                     );
@@ -1133,7 +1425,7 @@ impl Validator for ValidateDeviceProfileAccess {
     async fn validate_key(&self, id: &Uuid) -> Result<i64, Error> {
         let mut q = api_key::dsl::api_key
             .select(dsl::count_star())
-            .filter(api_key::dsl::id.eq(id))
+            .find(id)
             .into_boxed();
 
         match self.flag {
@@ -1141,9 +1433,9 @@ impl Validator for ValidateDeviceProfileAccess {
             // tenant api key
             Flag::Read | Flag::Update | Flag::Delete => {
                 q = q.filter(api_key::dsl::is_admin.eq(true).or(dsl::exists(
-                    device_profile::dsl::device_profile.filter(
-                        device_profile::dsl::id.eq(&self.device_profile_id).and(
-                            api_key::dsl::tenant_id.eq(device_profile::dsl::tenant_id.nullable()),
+                    tenant_role::dsl::tenant_role.filter(
+                        tenant_role::dsl::id.eq(&self.role_id).and(
+                            api_key::dsl::tenant_id.eq(tenant_role::dsl::tenant_id.nullable()),
                         ),
                     ),
                 )));
@@ -1157,22 +1449,22 @@ impl Validator for ValidateDeviceProfileAccess {
     }
 }
 
-pub struct ValidateDevicesAccess {
+pub struct ValidateDeviceProfileTemplatesAccess {
     flag: Flag,
-    application_id: Uuid,
 }
 
-impl ValidateDevicesAccess {
-    pub fn new(flag: Flag, app_id: Uuid) -> Self {
-        ValidateDevicesAccess {
-            flag,
-            application_id: app_id,
-        }
+impl ValidateDeviceProfileTemplatesAccess {
+    pub fn new(flag: Flag) -> Self {
+        ValidateDeviceProfileTemplatesAccess { flag }
     }
 }
 
 #[async_trait]
-impl Validator for ValidateDevicesAccess {
+impl Validator for ValidateDeviceProfileTemplatesAccess {
+    fn api_key_permission(&self) -> Permission {
+        self.flag.api_key_permission()
+    }
+
     async fn validate_user(&self, id: &Uuid) -> Result<i64, Error> {
         let mut q = user::dsl::user
             .select(dsl::count_star())
@@ -1180,52 +1472,16 @@ impl Validator for ValidateDevicesAccess {
             .into_boxed();
 
         match self.flag {
-            // admin user
-            // tenant admin
-            // tenant device admin
+            // global admin
             Flag::Create => {
-                q =
-                    q.filter(
-                        user::dsl::is_admin.eq(true).or(dsl::exists(
-                            application::dsl::application
-                                .inner_join(tenant_user::table.on(
-                                    tenant_user::dsl::tenant_id.eq(application::dsl::tenant_id),
-                                ))
-                                .filter(
-                                    application::dsl::id
-                                        .eq(&self.application_id)
-                                        .and(tenant_user::dsl::user_id.eq(user::dsl::id))
-                                        .and(
-                                            tenant_user::dsl::is_admin
-                                                .eq(true)
-                                                .or(tenant_user::dsl::is_device_admin.eq(true)),
-                                        ),
-                                ),
-                        )),
-                    );
-            }
-            // admin user
-            // tenant user
-            Flag::List => {
-                q =
-                    q.filter(
-                        user::dsl::is_admin.eq(true).or(dsl::exists(
-                            application::dsl::application
-                                .inner_join(tenant_user::table.on(
-                                    tenant_user::dsl::tenant_id.eq(application::dsl::tenant_id),
-                                ))
-                                .filter(
-                                    application::dsl::id
-                                        .eq(&self.application_id)
-                                        .and(tenant_user::dsl::user_id.eq(user::dsl::id)),
-                                ),
-                        )),
-                    );
+                q = q.filter(user::dsl::is_admin.eq(true));
             }
+            // any active user
+            Flag::List => {}
             _ => {
                 return Ok(0);
             }
-        }
+        };
 
         Ok(q.first(&mut get_async_db_conn().await?).await?)
     }
@@ -1233,43 +1489,41 @@ impl Validator for ValidateDevicesAccess {
     async fn validate_key(&self, id: &Uuid) -> Result<i64, Error> {
         let mut q = api_key::dsl::api_key
             .select(dsl::count_star())
-            .filter(api_key::dsl::id.eq(id))
+            .find(id)
             .into_boxed();
 
         match self.flag {
             // admin api key
-            // tenant api key
-            Flag::Create | Flag::List => {
-                q = q.filter(api_key::dsl::is_admin.eq(true).or(dsl::exists(
-                    application::dsl::application.filter(
-                        application::dsl::id.eq(&self.application_id).and(
-                            api_key::dsl::tenant_id.eq(application::dsl::tenant_id.nullable()),
-                        ),
-                    ),
-                )));
+            Flag::Create => {
+                q = q.filter(api_key::dsl::is_admin.eq(true));
             }
+            // any api key
+            Flag::List => {}
             _ => {
                 return Ok(0);
             }
-        }
+        };
 
         Ok(q.first(&mut get_async_db_conn().await?).await?)
     }
 }
 
-pub struct ValidateDeviceAccess {
+pub struct ValidateDeviceProfileTemplateAccess {
     flag: Flag,
-    dev_eui: EUI64,
 }
 
-impl ValidateDeviceAccess {
-    pub fn new(flag: Flag, dev_eui: EUI64) -> Self {
-        ValidateDeviceAccess { flag, dev_eui }
+impl ValidateDeviceProfileTemplateAccess {
+    pub fn new(flag: Flag) -> Self {
+        ValidateDeviceProfileTemplateAccess { flag }
     }
 }
 
 #[async_trait]
-impl Validator for ValidateDeviceAccess {
+impl Validator for ValidateDeviceProfileTemplateAccess {
+    fn api_key_permission(&self) -> Permission {
+        self.flag.api_key_permission()
+    }
+
     async fn validate_user(&self, id: &Uuid) -> Result<i64, Error> {
         let mut q = user::dsl::user
             .select(dsl::count_star())
@@ -1277,54 +1531,16 @@ impl Validator for ValidateDeviceAccess {
             .into_boxed();
 
         match self.flag {
-            // admin user
-            // tenant user
-            Flag::Read => {
-                q =
-                    q.filter(
-                        user::dsl::is_admin.eq(true).or(dsl::exists(
-                            device::dsl::device
-                                .inner_join(application::table)
-                                .inner_join(tenant_user::table.on(
-                                    tenant_user::dsl::tenant_id.eq(application::dsl::tenant_id),
-                                ))
-                                .filter(
-                                    device::dsl::dev_eui
-                                        .eq(&self.dev_eui)
-                                        .and(tenant_user::dsl::user_id.eq(user::dsl::id)),
-                                ),
-                        )),
-                    );
-            }
-            // admin user
-            // tenant admin
-            // tenant device admin
+            // any active user
+            Flag::Read => {}
+            // global admin user
             Flag::Update | Flag::Delete => {
-                q =
-                    q.filter(
-                        user::dsl::is_admin.eq(true).or(dsl::exists(
-                            device::dsl::device
-                                .inner_join(application::table)
-                                .inner_join(tenant_user::table.on(
-                                    tenant_user::dsl::tenant_id.eq(application::dsl::tenant_id),
-                                ))
-                                .filter(
-                                    device::dsl::dev_eui
-                                        .eq(&self.dev_eui)
-                                        .and(tenant_user::dsl::user_id.eq(user::dsl::id))
-                                        .and(
-                                            tenant_user::dsl::is_admin
-                                                .eq(true)
-                                                .or(tenant_user::dsl::is_device_admin.eq(true)),
-                                        ),
-                                ),
-                        )),
-                    );
+                q = q.filter(user::dsl::is_admin.eq(true));
             }
             _ => {
                 return Ok(0);
             }
-        }
+        };
 
         Ok(q.first(&mut get_async_db_conn().await?).await?)
     }
@@ -1332,117 +1548,42 @@ impl Validator for ValidateDeviceAccess {
     async fn validate_key(&self, id: &Uuid) -> Result<i64, Error> {
         let mut q = api_key::dsl::api_key
             .select(dsl::count_star())
-            .filter(api_key::dsl::id.eq(id))
+            .find(id)
             .into_boxed();
 
         match self.flag {
+            // any api key
+            Flag::Read => {}
             // admin api key
-            // tenant api key
-            Flag::Read | Flag::Update | Flag::Delete => {
-                q = q.filter(api_key::dsl::is_admin.eq(true).or(dsl::exists(
-                    device::dsl::device.inner_join(application::table).filter(
-                        device::dsl::dev_eui.eq(self.dev_eui).and(
-                            api_key::dsl::tenant_id.eq(application::dsl::tenant_id.nullable()),
-                        ),
-                    ),
-                )))
+            Flag::Update | Flag::Delete => {
+                q = q.filter(api_key::dsl::is_admin.eq(true));
             }
             _ => {
                 return Ok(0);
             }
-        }
+        };
 
         Ok(q.first(&mut get_async_db_conn().await?).await?)
     }
 }
 
-pub struct ValidateDeviceQueueAccess {
+pub struct ValidateDeviceProfilesAccess {
     flag: Flag,
-    dev_eui: EUI64,
+    tenant_id: Uuid,
 }
 
-impl ValidateDeviceQueueAccess {
-    pub fn new(flag: Flag, dev_eui: EUI64) -> Self {
-        ValidateDeviceQueueAccess { flag, dev_eui }
+impl ValidateDeviceProfilesAccess {
+    pub fn new(flag: Flag, tenant_id: Uuid) -> Self {
+        ValidateDeviceProfilesAccess { flag, tenant_id }
     }
 }
 
 #[async_trait]
-impl Validator for ValidateDeviceQueueAccess {
-    async fn validate_user(&self, id: &Uuid) -> Result<i64, Error> {
-        let mut q = user::dsl::user
-            .select(dsl::count_star())
-            .filter(user::dsl::id.eq(id).and(user::dsl::is_active.eq(true)))
-            .into_boxed();
-
-        match self.flag {
-            // admin user
-            // tenant user
-            Flag::Create | Flag::List | Flag::Delete => {
-                q =
-                    q.filter(
-                        user::dsl::is_admin.eq(true).or(dsl::exists(
-                            device::dsl::device
-                                .inner_join(application::table)
-                                .inner_join(tenant_user::table.on(
-                                    tenant_user::dsl::tenant_id.eq(application::dsl::tenant_id),
-                                ))
-                                .filter(
-                                    device::dsl::dev_eui
-                                        .eq(&self.dev_eui)
-                                        .and(tenant_user::dsl::user_id.eq(user::dsl::id)),
-                                ),
-                        )),
-                    );
-            }
-            _ => {
-                return Ok(0);
-            }
-        }
-
-        Ok(q.first(&mut get_async_db_conn().await?).await?)
-    }
-
-    async fn validate_key(&self, id: &Uuid) -> Result<i64, Error> {
-        let mut q = api_key::dsl::api_key
-            .select(dsl::count_star())
-            .filter(api_key::dsl::id.eq(id))
-            .into_boxed();
-
-        match self.flag {
-            // admin api key
-            // tenant api key
-            Flag::Create | Flag::List | Flag::Delete => {
-                q = q.filter(api_key::dsl::is_admin.eq(true).or(dsl::exists(
-                    device::dsl::device.inner_join(application::table).filter(
-                        device::dsl::dev_eui.eq(&self.dev_eui).and(
-                            api_key::dsl::tenant_id.eq(application::dsl::tenant_id.nullable()),
-                        ),
-                    ),
-                )));
-            }
-            _ => {
-                return Ok(0);
-            }
-        }
-
-        Ok(q.first(&mut get_async_db_conn().await?).await?)
-    }
-}
-
-pub struct ValidateGatewaysAccess {
-    flag: Flag,
-    tenant_id: Uuid,
-}
-
-impl ValidateGatewaysAccess {
-    pub fn new(flag: Flag, tenant_id: Uuid) -> Self {
-        ValidateGatewaysAccess { flag, tenant_id }
-    }
-}
-
-#[async_trait]
-impl Validator for ValidateGatewaysAccess {
+impl Validator for ValidateDeviceProfilesAccess {
+    fn api_key_permission(&self) -> Permission {
+        self.flag.api_key_permission()
+    }
+
     async fn validate_user(&self, id: &Uuid) -> Result<i64, Error> {
         let mut q = user::dsl::user
             .select(dsl::count_star())
@@ -1452,18 +1593,18 @@ impl Validator for ValidateGatewaysAccess {
         match self.flag {
             // global admin
             // tenant admin
-            // gateway admin
+            // tenant device admin
             Flag::Create => {
                 q = q.filter(
                     user::dsl::is_admin.eq(true).or(dsl::exists(
                         tenant_user::dsl::tenant_user.filter(
-                            tenant_user::dsl::tenant_id
-                                .eq(&self.tenant_id)
-                                .and(tenant_user::dsl::user_id.eq(user::dsl::id))
+                            tenant_user::dsl::user_id
+                                .eq(user::dsl::id)
+                                .and(tenant_user::dsl::tenant_id.eq(&self.tenant_id))
                                 .and(
                                     tenant_user::dsl::is_admin
                                         .eq(true)
-                                        .or(tenant_user::dsl::is_gateway_admin.eq(true)),
+                                        .or(tenant_user::dsl::is_device_admin.eq(true)),
                                 ),
                         ),
                     )),
@@ -1475,9 +1616,9 @@ impl Validator for ValidateGatewaysAccess {
                 q = q.filter(
                     user::dsl::is_admin.eq(true).or(dsl::exists(
                         tenant_user::dsl::tenant_user.filter(
-                            tenant_user::dsl::tenant_id
-                                .eq(&self.tenant_id)
-                                .and(tenant_user::dsl::user_id.eq(user::dsl::id)),
+                            tenant_user::dsl::user_id
+                                .eq(user::dsl::id)
+                                .and(tenant_user::dsl::tenant_id.eq(&self.tenant_id)),
                         ),
                     )),
                 );
@@ -1485,9 +1626,19 @@ impl Validator for ValidateGatewaysAccess {
             _ => {
                 return Ok(0);
             }
+        };
+
+        let count = q.first(&mut get_async_db_conn().await?).await?;
+        if count == 0 && matches!(self.flag, Flag::Create) {
+            return validate_user_role(
+                id,
+                RolePermission::DeviceProfileWrite,
+                RoleScope::Tenant(self.tenant_id),
+            )
+            .await;
         }
 
-        Ok(q.first(&mut get_async_db_conn().await?).await?)
+        Ok(count)
     }
 
     async fn validate_key(&self, id: &Uuid) -> Result<i64, Error> {
@@ -1509,25 +1660,40 @@ impl Validator for ValidateGatewaysAccess {
             _ => {
                 return Ok(0);
             }
-        }
+        };
 
         Ok(q.first(&mut get_async_db_conn().await?).await?)
     }
 }
 
-pub struct ValidateGatewayAccess {
+pub struct ValidateDeviceProfileAccess {
     flag: Flag,
-    gateway_id: EUI64,
+    device_profile_id: Uuid,
 }
 
-impl ValidateGatewayAccess {
-    pub fn new(flag: Flag, gateway_id: EUI64) -> Self {
-        ValidateGatewayAccess { flag, gateway_id }
+impl ValidateDeviceProfileAccess {
+    pub fn new(flag: Flag, dp_id: Uuid) -> Self {
+        ValidateDeviceProfileAccess {
+            flag,
+            device_profile_id: dp_id,
+        }
     }
 }
 
 #[async_trait]
-impl Validator for ValidateGatewayAccess {
+impl Validator for ValidateDeviceProfileAccess {
+    fn api_key_permission(&self) -> Permission {
+        self.flag.api_key_permission()
+    }
+
+    async fn validate_key_scope(&self, ak: &ApiKey) -> Result<bool, Error> {
+        // Keys scoped to an application only are allowed to read the device-profiles.
+        match &ak.device_profile_id {
+            Some(v) => Ok(v == &self.device_profile_id),
+            None => Ok(matches!(self.flag, Flag::Read)),
+        }
+    }
+
     async fn validate_user(&self, id: &Uuid) -> Result<i64, Error> {
         let mut q = user::dsl::user
             .select(dsl::count_star())
@@ -1535,54 +1701,64 @@ impl Validator for ValidateGatewayAccess {
             .into_boxed();
 
         match self.flag {
-            // admin user
+            // global admin
             // tenant user
             Flag::Read => {
-                q = q.filter(
-                    user::dsl::is_admin.eq(true).or(dsl::exists(
-                        gateway::dsl::gateway
-                            .inner_join(
-                                tenant_user::table
-                                    .on(tenant_user::dsl::tenant_id.eq(gateway::dsl::tenant_id)),
-                            )
-                            .filter(
-                                gateway::dsl::gateway_id
-                                    .eq(&self.gateway_id)
-                                    .and(tenant_user::dsl::user_id.eq(user::dsl::id)),
-                            ),
-                    )),
-                );
+                q =
+                    q.filter(
+                        user::dsl::is_admin.eq(true).or(dsl::exists(
+                            device_profile::dsl::device_profile
+                                .inner_join(tenant_user::table.on(
+                                    tenant_user::dsl::tenant_id.eq(device_profile::dsl::tenant_id),
+                                ))
+                                .filter(
+                                    device_profile::dsl::id
+                                        .eq(&self.device_profile_id)
+                                        .and(tenant_user::dsl::user_id.eq(user::dsl::id)),
+                                ),
+                        )),
+                    );
             }
-            // admin user
-            // tenant admin
-            // gateway admin
+            // global admin
+            // tenant admin user
+            // tenant device admin
             Flag::Update | Flag::Delete => {
-                q = q.filter(
-                    user::dsl::is_admin.eq(true).or(dsl::exists(
-                        gateway::dsl::gateway
-                            .inner_join(
-                                tenant_user::table
-                                    .on(tenant_user::dsl::tenant_id.eq(gateway::dsl::tenant_id)),
-                            )
-                            .filter(
-                                gateway::dsl::gateway_id
-                                    .eq(&self.gateway_id)
-                                    .and(tenant_user::dsl::user_id.eq(user::dsl::id))
-                                    .and(
-                                        tenant_user::dsl::is_admin
-                                            .eq(true)
-                                            .or(tenant_user::dsl::is_gateway_admin.eq(true)),
-                                    ),
-                            ),
-                    )),
-                );
+                q =
+                    q.filter(
+                        user::dsl::is_admin.eq(true).or(dsl::exists(
+                            device_profile::dsl::device_profile
+                                .inner_join(tenant_user::table.on(
+                                    tenant_user::dsl::tenant_id.eq(device_profile::dsl::tenant_id),
+                                ))
+                                .filter(
+                                    device_profile::dsl::id
+                                        .eq(&self.device_profile_id)
+                                        .and(tenant_user::dsl::user_id.eq(user::dsl::id))
+                                        .and(
+                                            tenant_user::dsl::is_admin
+                                                .eq(true)
+                                                .or(tenant_user::dsl::is_device_admin.eq(true)),
+                                        ),
+                                ),
+                        )),
+                    );
             }
             _ => {
                 return Ok(0);
             }
+        };
+
+        let count = q.first(&mut get_async_db_conn().await?).await?;
+        if count == 0 && matches!(self.flag, Flag::Update | Flag::Delete) {
+            return validate_user_role(
+                id,
+                RolePermission::DeviceProfileWrite,
+                RoleScope::DeviceProfile(self.device_profile_id),
+            )
+            .await;
         }
 
-        Ok(q.first(&mut get_async_db_conn().await?).await?)
+        Ok(count)
     }
 
     async fn validate_key(&self, id: &Uuid) -> Result<i64, Error> {
@@ -1595,40 +1771,47 @@ impl Validator for ValidateGatewayAccess {
             // admin api key
             // tenant api key
             Flag::Read | Flag::Update | Flag::Delete => {
-                q =
-                    q.filter(api_key::dsl::is_admin.eq(true).or(dsl::exists(
-                        gateway::dsl::gateway.filter(
-                            gateway::dsl::gateway_id.eq(&self.gateway_id).and(
-                                api_key::dsl::tenant_id.eq(gateway::dsl::tenant_id.nullable()),
-                            ),
+                q = q.filter(api_key::dsl::is_admin.eq(true).or(dsl::exists(
+                    device_profile::dsl::device_profile.filter(
+                        device_profile::dsl::id.eq(&self.device_profile_id).and(
+                            api_key::dsl::tenant_id.eq(device_profile::dsl::tenant_id.nullable()),
                         ),
-                    )));
+                    ),
+                )));
             }
             _ => {
                 return Ok(0);
             }
-        }
+        };
 
         Ok(q.first(&mut get_async_db_conn().await?).await?)
     }
 }
 
-pub struct ValidateMulticastGroupsAccess {
+pub struct ValidateDevicesAccess {
     flag: Flag,
     application_id: Uuid,
 }
 
-impl ValidateMulticastGroupsAccess {
-    pub fn new(flag: Flag, application_id: Uuid) -> Self {
-        ValidateMulticastGroupsAccess {
+impl ValidateDevicesAccess {
+    pub fn new(flag: Flag, app_id: Uuid) -> Self {
+        ValidateDevicesAccess {
             flag,
-            application_id,
+            application_id: app_id,
         }
     }
 }
 
 #[async_trait]
-impl Validator for ValidateMulticastGroupsAccess {
+impl Validator for ValidateDevicesAccess {
+    fn api_key_permission(&self) -> Permission {
+        self.flag.api_key_permission()
+    }
+
+    async fn validate_key_scope(&self, ak: &ApiKey) -> Result<bool, Error> {
+        Ok(ak.in_scope(&self.application_id, None))
+    }
+
     async fn validate_user(&self, id: &Uuid) -> Result<i64, Error> {
         let mut q = user::dsl::user
             .select(dsl::count_star())
@@ -1683,7 +1866,23 @@ impl Validator for ValidateMulticastGroupsAccess {
             }
         }
 
-        Ok(q.first(&mut get_async_db_conn().await?).await?)
+        let mut count = q.first(&mut get_async_db_conn().await?).await?;
+        if count == 0 && matches!(self.flag, Flag::Create) {
+            count = validate_user_role(
+                id,
+                RolePermission::DeviceWrite,
+                RoleScope::Application(self.application_id),
+            )
+            .await?;
+        }
+
+        validate_application_user(
+            id,
+            RoleScope::Application(self.application_id),
+            matches!(self.flag, Flag::Create).then_some(RolePermission::DeviceWrite),
+            count,
+        )
+        .await
     }
 
     async fn validate_key(&self, id: &Uuid) -> Result<i64, Error> {
@@ -1713,22 +1912,27 @@ impl Validator for ValidateMulticastGroupsAccess {
     }
 }
 
-pub struct ValidateMulticastGroupAccess {
+pub struct ValidateDeviceAccess {
     flag: Flag,
-    multicast_group_id: Uuid,
+    dev_eui: EUI64,
 }
 
-impl ValidateMulticastGroupAccess {
-    pub fn new(flag: Flag, multicast_group_id: Uuid) -> Self {
-        ValidateMulticastGroupAccess {
-            flag,
-            multicast_group_id,
-        }
+impl ValidateDeviceAccess {
+    pub fn new(flag: Flag, dev_eui: EUI64) -> Self {
+        ValidateDeviceAccess { flag, dev_eui }
     }
 }
 
 #[async_trait]
-impl Validator for ValidateMulticastGroupAccess {
+impl Validator for ValidateDeviceAccess {
+    fn api_key_permission(&self) -> Permission {
+        self.flag.api_key_permission()
+    }
+
+    async fn validate_key_scope(&self, ak: &ApiKey) -> Result<bool, Error> {
+        validate_device_key_scope(ak, &self.dev_eui).await
+    }
+
     async fn validate_user(&self, id: &Uuid) -> Result<i64, Error> {
         let mut q = user::dsl::user
             .select(dsl::count_star())
@@ -1742,14 +1946,14 @@ impl Validator for ValidateMulticastGroupAccess {
                 q =
                     q.filter(
                         user::dsl::is_admin.eq(true).or(dsl::exists(
-                            multicast_group::dsl::multicast_group
+                            device::dsl::device
                                 .inner_join(application::table)
                                 .inner_join(tenant_user::table.on(
                                     tenant_user::dsl::tenant_id.eq(application::dsl::tenant_id),
                                 ))
                                 .filter(
-                                    multicast_group::dsl::id
-                                        .eq(&self.multicast_group_id)
+                                    device::dsl::dev_eui
+                                        .eq(&self.dev_eui)
                                         .and(tenant_user::dsl::user_id.eq(user::dsl::id)),
                                 ),
                         )),
@@ -1762,14 +1966,14 @@ impl Validator for ValidateMulticastGroupAccess {
                 q =
                     q.filter(
                         user::dsl::is_admin.eq(true).or(dsl::exists(
-                            multicast_group::dsl::multicast_group
+                            device::dsl::device
                                 .inner_join(application::table)
                                 .inner_join(tenant_user::table.on(
                                     tenant_user::dsl::tenant_id.eq(application::dsl::tenant_id),
                                 ))
                                 .filter(
-                                    multicast_group::dsl::id
-                                        .eq(&self.multicast_group_id)
+                                    device::dsl::dev_eui
+                                        .eq(&self.dev_eui)
                                         .and(tenant_user::dsl::user_id.eq(user::dsl::id))
                                         .and(
                                             tenant_user::dsl::is_admin
@@ -1785,7 +1989,23 @@ impl Validator for ValidateMulticastGroupAccess {
             }
         }
 
-        Ok(q.first(&mut get_async_db_conn().await?).await?)
+        let mut count = q.first(&mut get_async_db_conn().await?).await?;
+        if count == 0 && matches!(self.flag, Flag::Update | Flag::Delete) {
+            count = validate_user_role(
+                id,
+                RolePermission::DeviceWrite,
+                RoleScope::Device(self.dev_eui),
+            )
+            .await?;
+        }
+
+        validate_application_user(
+            id,
+            RoleScope::Device(self.dev_eui),
+            matches!(self.flag, Flag::Update | Flag::Delete).then_some(RolePermission::DeviceWrite),
+            count,
+        )
+        .await
     }
 
     async fn validate_key(&self, id: &Uuid) -> Result<i64, Error> {
@@ -1798,15 +2018,13 @@ impl Validator for ValidateMulticastGroupAccess {
             // admin api key
             // tenant api key
             Flag::Read | Flag::Update | Flag::Delete => {
-                q = q.filter(
-                    api_key::dsl::is_admin.eq(true).or(dsl::exists(
-                        multicast_group::dsl::multicast_group
-                            .inner_join(application::table)
-                            .filter(multicast_group::dsl::id.eq(&self.multicast_group_id).and(
-                                api_key::dsl::tenant_id.eq(application::dsl::tenant_id.nullable()),
-                            )),
-                    )),
-                );
+                q = q.filter(api_key::dsl::is_admin.eq(true).or(dsl::exists(
+                    device::dsl::device.inner_join(application::table).filter(
+                        device::dsl::dev_eui.eq(self.dev_eui).and(
+                            api_key::dsl::tenant_id.eq(application::dsl::tenant_id.nullable()),
+                        ),
+                    ),
+                )))
             }
             _ => {
                 return Ok(0);
@@ -1817,22 +2035,30 @@ impl Validator for ValidateMulticastGroupAccess {
     }
 }
 
-pub struct ValidateMulticastGroupQueueAccess {
+pub struct ValidateDeviceQueueAccess {
     flag: Flag,
-    multicast_group_id: Uuid,
+    dev_eui: EUI64,
 }
 
-impl ValidateMulticastGroupQueueAccess {
-    pub fn new(flag: Flag, multicast_group_id: Uuid) -> Self {
-        ValidateMulticastGroupQueueAccess {
-            flag,
-            multicast_group_id,
-        }
+impl ValidateDeviceQueueAccess {
+    pub fn new(flag: Flag, dev_eui: EUI64) -> Self {
+        ValidateDeviceQueueAccess { flag, dev_eui }
     }
 }
 
 #[async_trait]
-impl Validator for ValidateMulticastGroupQueueAccess {
+impl Validator for ValidateDeviceQueueAccess {
+    fn api_key_permission(&self) -> Permission {
+        match self.flag {
+            Flag::Create => Permission::Enqueue,
+            _ => self.flag.api_key_permission(),
+        }
+    }
+
+    async fn validate_key_scope(&self, ak: &ApiKey) -> Result<bool, Error> {
+        validate_device_key_scope(ak, &self.dev_eui).await
+    }
+
     async fn validate_user(&self, id: &Uuid) -> Result<i64, Error> {
         let mut q = user::dsl::user
             .select(dsl::count_star())
@@ -1843,23 +2069,34 @@ impl Validator for ValidateMulticastGroupQueueAccess {
             // admin user
             // tenant admin
             // tenant device admin
+            // tenant user without roles (users with roles need the enqueue permission)
             Flag::Create | Flag::Delete => {
                 q =
                     q.filter(
                         user::dsl::is_admin.eq(true).or(dsl::exists(
-                            multicast_group::dsl::multicast_group
+                            device::dsl::device
                                 .inner_join(application::table)
                                 .inner_join(tenant_user::table.on(
                                     tenant_user::dsl::tenant_id.eq(application::dsl::tenant_id),
                                 ))
                                 .filter(
-                                    multicast_group::dsl::id
-                                        .eq(&self.multicast_group_id)
+                                    device::dsl::dev_eui
+                                        .eq(&self.dev_eui)
                                         .and(tenant_user::dsl::user_id.eq(user::dsl::id))
                                         .and(
                                             tenant_user::dsl::is_admin
                                                 .eq(true)
-                                                .or(tenant_user::dsl::is_device_admin.eq(true)),
+                                                .or(tenant_user::dsl::is_device_admin.eq(true))
+                                                .or(dsl::not(dsl::exists(
+                                                    tenant_user_role::dsl::tenant_user_role.filter(
+                                                        tenant_user_role::dsl::tenant_id
+                                                            .eq(tenant_user::dsl::tenant_id)
+                                                            .and(
+                                                                tenant_user_role::dsl::user_id
+                                                                    .eq(tenant_user::dsl::user_id),
+                                                            ),
+                                                    ),
+                                                ))),
                                         ),
                                 ),
                         )),
@@ -1871,14 +2108,14 @@ impl Validator for ValidateMulticastGroupQueueAccess {
                 q =
                     q.filter(
                         user::dsl::is_admin.eq(true).or(dsl::exists(
-                            multicast_group::dsl::multicast_group
+                            device::dsl::device
                                 .inner_join(application::table)
                                 .inner_join(tenant_user::table.on(
                                     tenant_user::dsl::tenant_id.eq(application::dsl::tenant_id),
                                 ))
                                 .filter(
-                                    multicast_group::dsl::id
-                                        .eq(&self.multicast_group_id)
+                                    device::dsl::dev_eui
+                                        .eq(&self.dev_eui)
                                         .and(tenant_user::dsl::user_id.eq(user::dsl::id)),
                                 ),
                         )),
@@ -1889,7 +2126,20 @@ impl Validator for ValidateMulticastGroupQueueAccess {
             }
         }
 
-        Ok(q.first(&mut get_async_db_conn().await?).await?)
+        let mut count = q.first(&mut get_async_db_conn().await?).await?;
+        if count == 0 && matches!(self.flag, Flag::Create | Flag::Delete) {
+            count =
+                validate_user_role(id, RolePermission::Enqueue, RoleScope::Device(self.dev_eui))
+                    .await?;
+        }
+
+        validate_application_user(
+            id,
+            RoleScope::Device(self.dev_eui),
+            matches!(self.flag, Flag::Create | Flag::Delete).then_some(RolePermission::Enqueue),
+            count,
+        )
+        .await
     }
 
     async fn validate_key(&self, id: &Uuid) -> Result<i64, Error> {
@@ -1902,15 +2152,13 @@ impl Validator for ValidateMulticastGroupQueueAccess {
             // admin api key
             // tenant api key
             Flag::Create | Flag::List | Flag::Delete => {
-                q = q.filter(
-                    api_key::dsl::is_admin.eq(true).or(dsl::exists(
-                        multicast_group::dsl::multicast_group
-                            .inner_join(application::table)
-                            .filter(multicast_group::dsl::id.eq(&self.multicast_group_id).and(
-                                api_key::dsl::tenant_id.eq(application::dsl::tenant_id.nullable()),
-                            )),
-                    )),
-                );
+                q = q.filter(api_key::dsl::is_admin.eq(true).or(dsl::exists(
+                    device::dsl::device.inner_join(application::table).filter(
+                        device::dsl::dev_eui.eq(&self.dev_eui).and(
+                            api_key::dsl::tenant_id.eq(application::dsl::tenant_id.nullable()),
+                        ),
+                    ),
+                )));
             }
             _ => {
                 return Ok(0);
@@ -1921,117 +2169,859 @@ impl Validator for ValidateMulticastGroupQueueAccess {
     }
 }
 
-#[cfg(test)]
-pub mod test {
-    use super::*;
-    use crate::storage::{
-        api_key, application, device, device_profile, gateway, multicast, tenant, user,
-    };
-    use crate::test;
-    use std::str::FromStr;
+pub struct ValidateGatewaysAccess {
+    flag: Flag,
+    tenant_id: Uuid,
+}
 
-    struct ValidatorTest<V>
-    where
-        V: Validator + Sync,
-    {
-        validators: Vec<V>,
-        id: AuthID,
-        ok: bool,
+impl ValidateGatewaysAccess {
+    pub fn new(flag: Flag, tenant_id: Uuid) -> Self {
+        ValidateGatewaysAccess { flag, tenant_id }
     }
+}
 
-    async fn run_tests<V>(tests: Vec<ValidatorTest<V>>)
-    where
-        V: Validator + Sync,
-    {
-        println!("Running tests");
-        for (i, tst) in tests.iter().enumerate() {
-            for (j, v) in tst.validators.iter().enumerate() {
-                assert_eq!(
-                    tst.ok,
-                    v.validate(&tst.id).await.is_ok(),
-                    "Test {}, assertion {}",
-                    i,
-                    j
+#[async_trait]
+impl Validator for ValidateGatewaysAccess {
+    fn api_key_permission(&self) -> Permission {
+        self.flag.api_key_permission()
+    }
+
+    async fn validate_user(&self, id: &Uuid) -> Result<i64, Error> {
+        let mut q = user::dsl::user
+            .select(dsl::count_star())
+            .filter(user::dsl::id.eq(id).and(user::dsl::is_active.eq(true)))
+            .into_boxed();
+
+        match self.flag {
+            // global admin
+            // tenant admin
+            // gateway admin
+            Flag::Create => {
+                q = q.filter(
+                    user::dsl::is_admin.eq(true).or(dsl::exists(
+                        tenant_user::dsl::tenant_user.filter(
+                            tenant_user::dsl::tenant_id
+                                .eq(&self.tenant_id)
+                                .and(tenant_user::dsl::user_id.eq(user::dsl::id))
+                                .and(
+                                    tenant_user::dsl::is_admin
+                                        .eq(true)
+                                        .or(tenant_user::dsl::is_gateway_admin.eq(true)),
+                                ),
+                        ),
+                    )),
+                );
+            }
+            // global admin
+            // tenant user
+            Flag::List => {
+                q = q.filter(
+                    user::dsl::is_admin.eq(true).or(dsl::exists(
+                        tenant_user::dsl::tenant_user.filter(
+                            tenant_user::dsl::tenant_id
+                                .eq(&self.tenant_id)
+                                .and(tenant_user::dsl::user_id.eq(user::dsl::id)),
+                        ),
+                    )),
                 );
             }
+            _ => {
+                return Ok(0);
+            }
         }
-    }
 
-    #[tokio::test]
-    async fn validate_is_admin() {
-        let _guard = test::prepare().await;
-        let users = vec![
-            user::User {
-                email: "admin@user".into(),
-                is_active: true,
-                is_admin: true,
-                ..Default::default()
-            },
-            user::User {
-                email: "inactive@user".into(),
-                is_active: false,
-                is_admin: false,
-                ..Default::default()
-            },
-            user::User {
-                email: "normal@user".into(),
-                is_active: true,
-                is_admin: false,
-                ..Default::default()
-            },
-        ];
-        for u in &users {
-            user::create(u.clone()).await.unwrap();
+        let count = q.first(&mut get_async_db_conn().await?).await?;
+        if count == 0 && matches!(self.flag, Flag::Create) {
+            return validate_user_role(
+                id,
+                RolePermission::GatewayWrite,
+                RoleScope::Tenant(self.tenant_id),
+            )
+            .await;
         }
 
-        let tests = vec![
-            // admin user
-            ValidatorTest {
-                validators: vec![ValidateIsAdmin::new()],
-                id: AuthID::User(users[0].id),
-                ok: true,
-            },
-            // inactive
-            ValidatorTest {
-                validators: vec![ValidateIsAdmin::new()],
-                id: AuthID::User(users[1].id),
-                ok: false,
-            },
-            // active regular user
-            ValidatorTest {
-                validators: vec![ValidateIsAdmin::new()],
-                id: AuthID::User(users[2].id),
-                ok: false,
-            },
-        ];
-
-        run_tests(tests).await;
+        Ok(count)
     }
 
-    #[tokio::test]
-    async fn validate_active_user() {
-        let _guard = test::prepare().await;
-        let users = vec![
-            user::User {
-                email: "active@user".into(),
-                is_active: true,
-                is_admin: false,
-                ..Default::default()
-            },
-            user::User {
-                email: "inactive@user".into(),
-                is_active: false,
-                is_admin: false,
-                ..Default::default()
-            },
-        ];
-        for u in &users {
-            user::create(u.clone()).await.unwrap();
-        }
+    async fn validate_key(&self, id: &Uuid) -> Result<i64, Error> {
+        let mut q = api_key::dsl::api_key
+            .select(dsl::count_star())
+            .find(id)
+            .into_boxed();
 
-        let api_key = api_key::test::create_api_key(true, false).await;
+        match self.flag {
+            // admin api key
+            // tenant api key
+            Flag::Create | Flag::List => {
+                q = q.filter(
+                    api_key::dsl::is_admin
+                        .eq(true)
+                        .or(api_key::dsl::tenant_id.eq(&self.tenant_id)),
+                );
+            }
+            _ => {
+                return Ok(0);
+            }
+        }
 
-        let tests = vec![
+        Ok(q.first(&mut get_async_db_conn().await?).await?)
+    }
+}
+
+pub struct ValidateGatewayAccess {
+    flag: Flag,
+    gateway_id: EUI64,
+}
+
+impl ValidateGatewayAccess {
+    pub fn new(flag: Flag, gateway_id: EUI64) -> Self {
+        ValidateGatewayAccess { flag, gateway_id }
+    }
+}
+
+#[async_trait]
+impl Validator for ValidateGatewayAccess {
+    fn api_key_permission(&self) -> Permission {
+        self.flag.api_key_permission()
+    }
+
+    async fn validate_user(&self, id: &Uuid) -> Result<i64, Error> {
+        let mut q = user::dsl::user
+            .select(dsl::count_star())
+            .filter(user::dsl::id.eq(id).and(user::dsl::is_active.eq(true)))
+            .into_boxed();
+
+        match self.flag {
+            // admin user
+            // tenant user
+            Flag::Read => {
+                q = q.filter(
+                    user::dsl::is_admin.eq(true).or(dsl::exists(
+                        gateway::dsl::gateway
+                            .inner_join(
+                                tenant_user::table
+                                    .on(tenant_user::dsl::tenant_id.eq(gateway::dsl::tenant_id)),
+                            )
+                            .filter(
+                                gateway::dsl::gateway_id
+                                    .eq(&self.gateway_id)
+                                    .and(tenant_user::dsl::user_id.eq(user::dsl::id)),
+                            ),
+                    )),
+                );
+            }
+            // admin user
+            // tenant admin
+            // gateway admin
+            Flag::Update | Flag::Delete => {
+                q = q.filter(
+                    user::dsl::is_admin.eq(true).or(dsl::exists(
+                        gateway::dsl::gateway
+                            .inner_join(
+                                tenant_user::table
+                                    .on(tenant_user::dsl::tenant_id.eq(gateway::dsl::tenant_id)),
+                            )
+                            .filter(
+                                gateway::dsl::gateway_id
+                                    .eq(&self.gateway_id)
+                                    .and(tenant_user::dsl::user_id.eq(user::dsl::id))
+                                    .and(
+                                        tenant_user::dsl::is_admin
+                                            .eq(true)
+                                            .or(tenant_user::dsl::is_gateway_admin.eq(true)),
+                                    ),
+                            ),
+                    )),
+                );
+            }
+            _ => {
+                return Ok(0);
+            }
+        }
+
+        let count = q.first(&mut get_async_db_conn().await?).await?;
+        if count == 0 && matches!(self.flag, Flag::Update | Flag::Delete) {
+            return validate_user_role(
+                id,
+                RolePermission::GatewayWrite,
+                RoleScope::Gateway(self.gateway_id),
+            )
+            .await;
+        }
+
+        Ok(count)
+    }
+
+    async fn validate_key(&self, id: &Uuid) -> Result<i64, Error> {
+        let mut q = api_key::dsl::api_key
+            .select(dsl::count_star())
+            .filter(api_key::dsl::id.eq(id))
+            .into_boxed();
+
+        match self.flag {
+            // admin api key
+            // tenant api key
+            Flag::Read | Flag::Update | Flag::Delete => {
+                q =
+                    q.filter(api_key::dsl::is_admin.eq(true).or(dsl::exists(
+                        gateway::dsl::gateway.filter(
+                            gateway::dsl::gateway_id.eq(&self.gateway_id).and(
+                                api_key::dsl::tenant_id.eq(gateway::dsl::tenant_id.nullable()),
+                            ),
+                        ),
+                    )));
+            }
+            _ => {
+                return Ok(0);
+            }
+        }
+
+        Ok(q.first(&mut get_async_db_conn().await?).await?)
+    }
+}
+
+pub struct ValidateMulticastGroupsAccess {
+    flag: Flag,
+    application_id: Uuid,
+}
+
+impl ValidateMulticastGroupsAccess {
+    pub fn new(flag: Flag, application_id: Uuid) -> Self {
+        ValidateMulticastGroupsAccess {
+            flag,
+            application_id,
+        }
+    }
+}
+
+#[async_trait]
+impl Validator for ValidateMulticastGroupsAccess {
+    fn api_key_permission(&self) -> Permission {
+        self.flag.api_key_permission()
+    }
+
+    async fn validate_key_scope(&self, ak: &ApiKey) -> Result<bool, Error> {
+        Ok(ak.in_scope(&self.application_id, None))
+    }
+
+    async fn validate_user(&self, id: &Uuid) -> Result<i64, Error> {
+        let mut q = user::dsl::user
+            .select(dsl::count_star())
+            .filter(user::dsl::id.eq(id).and(user::dsl::is_active.eq(true)))
+            .into_boxed();
+
+        match self.flag {
+            // admin user
+            // tenant admin
+            // tenant device admin
+            Flag::Create => {
+                q =
+                    q.filter(
+                        user::dsl::is_admin.eq(true).or(dsl::exists(
+                            application::dsl::application
+                                .inner_join(tenant_user::table.on(
+                                    tenant_user::dsl::tenant_id.eq(application::dsl::tenant_id),
+                                ))
+                                .filter(
+                                    application::dsl::id
+                                        .eq(&self.application_id)
+                                        .and(tenant_user::dsl::user_id.eq(user::dsl::id))
+                                        .and(
+                                            tenant_user::dsl::is_admin
+                                                .eq(true)
+                                                .or(tenant_user::dsl::is_device_admin.eq(true)),
+                                        ),
+                                ),
+                        )),
+                    );
+            }
+            // admin user
+            // tenant user
+            Flag::List => {
+                q =
+                    q.filter(
+                        user::dsl::is_admin.eq(true).or(dsl::exists(
+                            application::dsl::application
+                                .inner_join(tenant_user::table.on(
+                                    tenant_user::dsl::tenant_id.eq(application::dsl::tenant_id),
+                                ))
+                                .filter(
+                                    application::dsl::id
+                                        .eq(&self.application_id)
+                                        .and(tenant_user::dsl::user_id.eq(user::dsl::id)),
+                                ),
+                        )),
+                    );
+            }
+            _ => {
+                return Ok(0);
+            }
+        }
+
+        let mut count = q.first(&mut get_async_db_conn().await?).await?;
+        if count == 0 && matches!(self.flag, Flag::Create) {
+            count = validate_user_role(
+                id,
+                RolePermission::MulticastGroupWrite,
+                RoleScope::Application(self.application_id),
+            )
+            .await?;
+        }
+
+        validate_application_user(
+            id,
+            RoleScope::Application(self.application_id),
+            matches!(self.flag, Flag::Create).then_some(RolePermission::MulticastGroupWrite),
+            count,
+        )
+        .await
+    }
+
+    async fn validate_key(&self, id: &Uuid) -> Result<i64, Error> {
+        let mut q = api_key::dsl::api_key
+            .select(dsl::count_star())
+            .filter(api_key::dsl::id.eq(id))
+            .into_boxed();
+
+        match self.flag {
+            // admin api key
+            // tenant api key
+            Flag::Create | Flag::List => {
+                q = q.filter(api_key::dsl::is_admin.eq(true).or(dsl::exists(
+                    application::dsl::application.filter(
+                        application::dsl::id.eq(&self.application_id).and(
+                            api_key::dsl::tenant_id.eq(application::dsl::tenant_id.nullable()),
+                        ),
+                    ),
+                )));
+            }
+            _ => {
+                return Ok(0);
+            }
+        }
+
+        Ok(q.first(&mut get_async_db_conn().await?).await?)
+    }
+}
+
+pub struct ValidateMulticastGroupAccess {
+    flag: Flag,
+    multicast_group_id: Uuid,
+}
+
+impl ValidateMulticastGroupAccess {
+    pub fn new(flag: Flag, multicast_group_id: Uuid) -> Self {
+        ValidateMulticastGroupAccess {
+            flag,
+            multicast_group_id,
+        }
+    }
+}
+
+#[async_trait]
+impl Validator for ValidateMulticastGroupAccess {
+    fn api_key_permission(&self) -> Permission {
+        self.flag.api_key_permission()
+    }
+
+    async fn validate_key_scope(&self, ak: &ApiKey) -> Result<bool, Error> {
+        validate_multicast_group_key_scope(ak, &self.multicast_group_id).await
+    }
+
+    async fn validate_user(&self, id: &Uuid) -> Result<i64, Error> {
+        let mut q = user::dsl::user
+            .select(dsl::count_star())
+            .filter(user::dsl::id.eq(id).and(user::dsl::is_active.eq(true)))
+            .into_boxed();
+
+        match self.flag {
+            // admin user
+            // tenant user
+            Flag::Read => {
+                q =
+                    q.filter(
+                        user::dsl::is_admin.eq(true).or(dsl::exists(
+                            multicast_group::dsl::multicast_group
+                                .inner_join(application::table)
+                                .inner_join(tenant_user::table.on(
+                                    tenant_user::dsl::tenant_id.eq(application::dsl::tenant_id),
+                                ))
+                                .filter(
+                                    multicast_group::dsl::id
+                                        .eq(&self.multicast_group_id)
+                                        .and(tenant_user::dsl::user_id.eq(user::dsl::id)),
+                                ),
+                        )),
+                    );
+            }
+            // admin user
+            // tenant admin
+            // tenant device admin
+            Flag::Update | Flag::Delete => {
+                q =
+                    q.filter(
+                        user::dsl::is_admin.eq(true).or(dsl::exists(
+                            multicast_group::dsl::multicast_group
+                                .inner_join(application::table)
+                                .inner_join(tenant_user::table.on(
+                                    tenant_user::dsl::tenant_id.eq(application::dsl::tenant_id),
+                                ))
+                                .filter(
+                                    multicast_group::dsl::id
+                                        .eq(&self.multicast_group_id)
+                                        .and(tenant_user::dsl::user_id.eq(user::dsl::id))
+                                        .and(
+                                            tenant_user::dsl::is_admin
+                                                .eq(true)
+                                                .or(tenant_user::dsl::is_device_admin.eq(true)),
+                                        ),
+                                ),
+                        )),
+                    );
+            }
+            _ => {
+                return Ok(0);
+            }
+        }
+
+        let mut count = q.first(&mut get_async_db_conn().await?).await?;
+        if count == 0 && matches!(self.flag, Flag::Update | Flag::Delete) {
+            count = validate_user_role(
+                id,
+                RolePermission::MulticastGroupWrite,
+                RoleScope::MulticastGroup(self.multicast_group_id),
+            )
+            .await?;
+        }
+
+        validate_application_user(
+            id,
+            RoleScope::MulticastGroup(self.multicast_group_id),
+            matches!(self.flag, Flag::Update | Flag::Delete)
+                .then_some(RolePermission::MulticastGroupWrite),
+            count,
+        )
+        .await
+    }
+
+    async fn validate_key(&self, id: &Uuid) -> Result<i64, Error> {
+        let mut q = api_key::dsl::api_key
+            .select(dsl::count_star())
+            .filter(api_key::dsl::id.eq(id))
+            .into_boxed();
+
+        match self.flag {
+            // admin api key
+            // tenant api key
+            Flag::Read | Flag::Update | Flag::Delete => {
+                q = q.filter(
+                    api_key::dsl::is_admin.eq(true).or(dsl::exists(
+                        multicast_group::dsl::multicast_group
+                            .inner_join(application::table)
+                            .filter(multicast_group::dsl::id.eq(&self.multicast_group_id).and(
+                                api_key::dsl::tenant_id.eq(application::dsl::tenant_id.nullable()),
+                            )),
+                    )),
+                );
+            }
+            _ => {
+                return Ok(0);
+            }
+        }
+
+        Ok(q.first(&mut get_async_db_conn().await?).await?)
+    }
+}
+
+pub struct ValidateMulticastGroupQueueAccess {
+    flag: Flag,
+    multicast_group_id: Uuid,
+}
+
+impl ValidateMulticastGroupQueueAccess {
+    pub fn new(flag: Flag, multicast_group_id: Uuid) -> Self {
+        ValidateMulticastGroupQueueAccess {
+            flag,
+            multicast_group_id,
+        }
+    }
+}
+
+#[async_trait]
+impl Validator for ValidateMulticastGroupQueueAccess {
+    fn api_key_permission(&self) -> Permission {
+        match self.flag {
+            Flag::Create => Permission::Enqueue,
+            _ => self.flag.api_key_permission(),
+        }
+    }
+
+    async fn validate_key_scope(&self, ak: &ApiKey) -> Result<bool, Error> {
+        validate_multicast_group_key_scope(ak, &self.multicast_group_id).await
+    }
+
+    async fn validate_user(&self, id: &Uuid) -> Result<i64, Error> {
+        let mut q = user::dsl::user
+            .select(dsl::count_star())
+            .filter(user::dsl::id.eq(id).and(user::dsl::is_active.eq(true)))
+            .into_boxed();
+
+        match self.flag {
+            // admin user
+            // tenant admin
+            // tenant device admin
+            Flag::Create | Flag::Delete => {
+                q =
+                    q.filter(
+                        user::dsl::is_admin.eq(true).or(dsl::exists(
+                            multicast_group::dsl::multicast_group
+                                .inner_join(application::table)
+                                .inner_join(tenant_user::table.on(
+                                    tenant_user::dsl::tenant_id.eq(application::dsl::tenant_id),
+                                ))
+                                .filter(
+                                    multicast_group::dsl::id
+                                        .eq(&self.multicast_group_id)
+                                        .and(tenant_user::dsl::user_id.eq(user::dsl::id))
+                                        .and(
+                                            tenant_user::dsl::is_admin
+                                                .eq(true)
+                                                .or(tenant_user::dsl::is_device_admin.eq(true)),
+                                        ),
+                                ),
+                        )),
+                    );
+            }
+            // admin user
+            // tenant user
+            Flag::List => {
+                q =
+                    q.filter(
+                        user::dsl::is_admin.eq(true).or(dsl::exists(
+                            multicast_group::dsl::multicast_group
+                                .inner_join(application::table)
+                                .inner_join(tenant_user::table.on(
+                                    tenant_user::dsl::tenant_id.eq(application::dsl::tenant_id),
+                                ))
+                                .filter(
+                                    multicast_group::dsl::id
+                                        .eq(&self.multicast_group_id)
+                                        .and(tenant_user::dsl::user_id.eq(user::dsl::id)),
+                                ),
+                        )),
+                    );
+            }
+            _ => {
+                return Ok(0);
+            }
+        }
+
+        let mut count = q.first(&mut get_async_db_conn().await?).await?;
+        if count == 0 && matches!(self.flag, Flag::Create | Flag::Delete) {
+            count = validate_user_role(
+                id,
+                RolePermission::Enqueue,
+                RoleScope::MulticastGroup(self.multicast_group_id),
+            )
+            .await?;
+        }
+
+        validate_application_user(
+            id,
+            RoleScope::MulticastGroup(self.multicast_group_id),
+            matches!(self.flag, Flag::Create | Flag::Delete).then_some(RolePermission::Enqueue),
+            count,
+        )
+        .await
+    }
+
+    async fn validate_key(&self, id: &Uuid) -> Result<i64, Error> {
+        let mut q = api_key::dsl::api_key
+            .select(dsl::count_star())
+            .filter(api_key::dsl::id.eq(id))
+            .into_boxed();
+
+        match self.flag {
+            // admin api key
+            // tenant api key
+            Flag::Create | Flag::List | Flag::Delete => {
+                q = q.filter(
+                    api_key::dsl::is_admin.eq(true).or(dsl::exists(
+                        multicast_group::dsl::multicast_group
+                            .inner_join(application::table)
+                            .filter(multicast_group::dsl::id.eq(&self.multicast_group_id).and(
+                                api_key::dsl::tenant_id.eq(application::dsl::tenant_id.nullable()),
+                            )),
+                    )),
+                );
+            }
+            _ => {
+                return Ok(0);
+            }
+        }
+
+        Ok(q.first(&mut get_async_db_conn().await?).await?)
+    }
+}
+
+pub struct ValidateDeviceKeysAccess {
+    flag: Flag,
+    dev_eui: EUI64,
+}
+
+impl ValidateDeviceKeysAccess {
+    pub fn new(flag: Flag, dev_eui: EUI64) -> Self {
+        ValidateDeviceKeysAccess { flag, dev_eui }
+    }
+}
+
+#[async_trait]
+impl Validator for ValidateDeviceKeysAccess {
+    fn api_key_permission(&self) -> Permission {
+        match self.flag {
+            Flag::Read => Permission::ReadKeys,
+            _ => self.flag.api_key_permission(),
+        }
+    }
+
+    async fn validate_key_scope(&self, ak: &ApiKey) -> Result<bool, Error> {
+        validate_device_key_scope(ak, &self.dev_eui).await
+    }
+
+    async fn validate_user(&self, id: &Uuid) -> Result<i64, Error> {
+        ValidateDeviceAccess::new(self.flag, self.dev_eui)
+            .validate_user(id)
+            .await
+    }
+
+    async fn validate_key(&self, id: &Uuid) -> Result<i64, Error> {
+        ValidateDeviceAccess::new(self.flag, self.dev_eui)
+            .validate_key(id)
+            .await
+    }
+}
+
+// Validates that the user has been granted the given permission through one of its tenant roles.
+async fn validate_user_role(
+    id: &Uuid,
+    perm: RolePermission,
+    scope: RoleScope,
+) -> Result<i64, Error> {
+    Ok(crate::storage::tenant_role::has_permission(id, perm, scope)
+        .await
+        .map_err(anyhow::Error::from)?
+        .into())
+}
+
+// Validates that the user is global admin, tenant admin or admin of the given application.
+async fn validate_application_admin(id: &Uuid, application_id: &Uuid) -> Result<i64, Error> {
+    let q = user::dsl::user
+        .select(dsl::count_star())
+        .filter(user::dsl::id.eq(id).and(user::dsl::is_active.eq(true)))
+        .filter(
+            user::dsl::is_admin
+                .eq(true)
+                .or(dsl::exists(
+                    application::dsl::application
+                        .inner_join(
+                            tenant_user::table
+                                .on(tenant_user::dsl::tenant_id.eq(application::dsl::tenant_id)),
+                        )
+                        .filter(
+                            application::dsl::id
+                                .eq(application_id)
+                                .and(tenant_user::dsl::user_id.eq(user::dsl::id))
+                                .and(tenant_user::dsl::is_admin.eq(true)),
+                        ),
+                ))
+                .or(dsl::exists(
+                    application_user::dsl::application_user.filter(
+                        application_user::dsl::application_id
+                            .eq(application_id)
+                            .and(application_user::dsl::user_id.eq(user::dsl::id))
+                            .and(application_user::dsl::is_admin.eq(true)),
+                    ),
+                )),
+        );
+
+    Ok(q.first(&mut get_async_db_conn().await?).await?)
+}
+
+// Validates the application membership of the user on top of the given (tenant) validation
+// result. Users which are member of one or more applications within a tenant can only access
+// these applications. Within these applications, the membership can grant write permissions.
+async fn validate_application_user(
+    id: &Uuid,
+    scope: RoleScope,
+    perm: Option<RolePermission>,
+    count: i64,
+) -> Result<i64, Error> {
+    let application_id = match scope
+        .get_tenant_and_application_id()
+        .await
+        .map_err(anyhow::Error::from)?
+    {
+        (_, Some(v)) => v,
+        _ => return Ok(count),
+    };
+
+    let au = match crate::storage::application::get_user_access(&application_id, id)
+        .await
+        .map_err(anyhow::Error::from)?
+    {
+        UserAccess::Tenant => return Ok(count),
+        UserAccess::None => return Ok(0),
+        UserAccess::Member(v) => v,
+    };
+
+    let granted = match perm {
+        None => true,
+        Some(RolePermission::DeviceWrite)
+        | Some(RolePermission::MulticastGroupWrite)
+        | Some(RolePermission::Enqueue) => au.is_admin || au.is_device_admin,
+        Some(_) => au.is_admin,
+    };
+
+    Ok(if granted { 1 } else { count })
+}
+
+async fn validate_device_key_scope(ak: &ApiKey, dev_eui: &EUI64) -> Result<bool, Error> {
+    let dev: Option<(Uuid, Uuid)> = device::dsl::device
+        .find(dev_eui)
+        .select((device::dsl::application_id, device::dsl::device_profile_id))
+        .first(&mut get_async_db_conn().await?)
+        .await
+        .optional()?;
+
+    Ok(dev
+        .map(|(app_id, dp_id)| ak.in_scope(&app_id, Some(&dp_id)))
+        .unwrap_or(false))
+}
+
+async fn validate_multicast_group_key_scope(ak: &ApiKey, id: &Uuid) -> Result<bool, Error> {
+    let app_id: Option<Uuid> = multicast_group::dsl::multicast_group
+        .find(id)
+        .select(multicast_group::dsl::application_id)
+        .first(&mut get_async_db_conn().await?)
+        .await
+        .optional()?;
+
+    Ok(app_id
+        .map(|app_id| ak.in_scope(&app_id, None))
+        .unwrap_or(false))
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use crate::storage::{
+        api_key, application, device, device_profile, gateway, multicast, tenant, tenant_role, user,
+    };
+    use crate::test;
+    use std::str::FromStr;
+
+    struct ValidatorTest<V>
+    where
+        V: Validator + Sync,
+    {
+        validators: Vec<V>,
+        id: AuthID,
+        ok: bool,
+    }
+
+    async fn run_tests<V>(tests: Vec<ValidatorTest<V>>)
+    where
+        V: Validator + Sync,
+    {
+        println!("Running tests");
+        for (i, tst) in tests.iter().enumerate() {
+            for (j, v) in tst.validators.iter().enumerate() {
+                assert_eq!(
+                    tst.ok,
+                    v.validate(&tst.id).await.is_ok(),
+                    "Test {}, assertion {}",
+                    i,
+                    j
+                );
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn validate_is_admin() {
+        let _guard = test::prepare().await;
+        let users = vec![
+            user::User {
+                email: "admin@user".into(),
+                is_active: true,
+                is_admin: true,
+                ..Default::default()
+            },
+            user::User {
+                email: "inactive@user".into(),
+                is_active: false,
+                is_admin: false,
+                ..Default::default()
+            },
+            user::User {
+                email: "normal@user".into(),
+                is_active: true,
+                is_admin: false,
+                ..Default::default()
+            },
+        ];
+        for u in &users {
+            user::create(u.clone()).await.unwrap();
+        }
+
+        let tests = vec![
+            // admin user
+            ValidatorTest {
+                validators: vec![ValidateIsAdmin::new()],
+                id: AuthID::User(users[0].id),
+                ok: true,
+            },
+            // inactive
+            ValidatorTest {
+                validators: vec![ValidateIsAdmin::new()],
+                id: AuthID::User(users[1].id),
+                ok: false,
+            },
+            // active regular user
+            ValidatorTest {
+                validators: vec![ValidateIsAdmin::new()],
+                id: AuthID::User(users[2].id),
+                ok: false,
+            },
+        ];
+
+        run_tests(tests).await;
+    }
+
+    #[tokio::test]
+    async fn validate_active_user() {
+        let _guard = test::prepare().await;
+        let users = vec![
+            user::User {
+                email: "active@user".into(),
+                is_active: true,
+                is_admin: false,
+                ..Default::default()
+            },
+            user::User {
+                email: "inactive@user".into(),
+                is_active: false,
+                is_admin: false,
+                ..Default::default()
+            },
+        ];
+        for u in &users {
+            user::create(u.clone()).await.unwrap();
+        }
+
+        let api_key = api_key::test::create_api_key(true, false).await;
+
+        let tests = vec![
             // active user
             ValidatorTest {
                 validators: vec![ValidateActiveUser::new()],
@@ -2082,26 +3072,122 @@ pub mod test {
         let tests = vec![
             // active user
             ValidatorTest {
-                validators: vec![ValidateActiveUserOrKey::new()],
-                id: AuthID::User(users[0].id),
+                validators: vec![ValidateActiveUserOrKey::new()],
+                id: AuthID::User(users[0].id),
+                ok: true,
+            },
+            // inactive user
+            ValidatorTest {
+                validators: vec![ValidateActiveUserOrKey::new()],
+                id: AuthID::User(users[1].id),
+                ok: false,
+            },
+            // api key
+            ValidatorTest {
+                validators: vec![ValidateActiveUserOrKey::new()],
+                id: AuthID::Key(api_key.id),
+                ok: true,
+            },
+            // non-existing key
+            ValidatorTest {
+                validators: vec![ValidateActiveUserOrKey::new()],
+                id: AuthID::Key(Uuid::new_v4()),
+                ok: false,
+            },
+        ];
+
+        run_tests(tests).await;
+    }
+
+    #[tokio::test]
+    async fn validate_audit_log() {
+        let _guard = test::prepare().await;
+        let tenant_user = user::User {
+            email: "tenant@user".into(),
+            is_active: true,
+            ..Default::default()
+        };
+        let tenant_admin = user::User {
+            email: "tenant-admin@user".into(),
+            is_active: true,
+            ..Default::default()
+        };
+        let user_admin = user::User {
+            email: "admin@user".into(),
+            is_active: true,
+            is_admin: true,
+            ..Default::default()
+        };
+
+        for u in [&tenant_user, &tenant_admin, &user_admin] {
+            user::create(u.clone()).await.unwrap();
+        }
+
+        let api_key_admin = api_key::test::create_api_key(true, false).await;
+        let api_key_tenant = api_key::test::create_api_key(false, true).await;
+        let tenant_id = api_key_tenant.tenant_id.unwrap();
+
+        tenant::add_user(tenant::TenantUser {
+            tenant_id,
+            user_id: tenant_user.id,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        tenant::add_user(tenant::TenantUser {
+            tenant_id,
+            user_id: tenant_admin.id,
+            is_admin: true,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        let tests = vec![
+            // admin user can list all
+            ValidatorTest {
+                validators: vec![
+                    ValidateAuditLogAccess::new(Flag::List, None),
+                    ValidateAuditLogAccess::new(Flag::List, Some(tenant_id)),
+                ],
+                id: AuthID::User(user_admin.id),
+                ok: true,
+            },
+            // tenant admin can list tenant
+            ValidatorTest {
+                validators: vec![ValidateAuditLogAccess::new(Flag::List, Some(tenant_id))],
+                id: AuthID::User(tenant_admin.id),
                 ok: true,
             },
-            // inactive user
+            // tenant admin can not list all
             ValidatorTest {
-                validators: vec![ValidateActiveUserOrKey::new()],
-                id: AuthID::User(users[1].id),
+                validators: vec![ValidateAuditLogAccess::new(Flag::List, None)],
+                id: AuthID::User(tenant_admin.id),
                 ok: false,
             },
-            // api key
+            // tenant user can not list tenant
             ValidatorTest {
-                validators: vec![ValidateActiveUserOrKey::new()],
-                id: AuthID::Key(api_key.id),
+                validators: vec![ValidateAuditLogAccess::new(Flag::List, Some(tenant_id))],
+                id: AuthID::User(tenant_user.id),
+                ok: false,
+            },
+            // admin api key can list all
+            ValidatorTest {
+                validators: vec![ValidateAuditLogAccess::new(Flag::List, None)],
+                id: AuthID::Key(api_key_admin.id),
                 ok: true,
             },
-            // non-existing key
+            // tenant api key can list tenant
             ValidatorTest {
-                validators: vec![ValidateActiveUserOrKey::new()],
-                id: AuthID::Key(Uuid::new_v4()),
+                validators: vec![ValidateAuditLogAccess::new(Flag::List, Some(tenant_id))],
+                id: AuthID::Key(api_key_tenant.id),
+                ok: true,
+            },
+            // tenant api key can not list all
+            ValidatorTest {
+                validators: vec![ValidateAuditLogAccess::new(Flag::List, None)],
+                id: AuthID::Key(api_key_tenant.id),
                 ok: false,
             },
         ];
@@ -2931,106 +4017,409 @@ pub mod test {
             // admin user can create and list
             ValidatorTest {
                 validators: vec![
-                    ValidateDeviceProfileTemplatesAccess::new(Flag::Create),
-                    ValidateDeviceProfileTemplatesAccess::new(Flag::List),
+                    ValidateDeviceProfileTemplatesAccess::new(Flag::Create),
+                    ValidateDeviceProfileTemplatesAccess::new(Flag::List),
+                ],
+                id: AuthID::User(user_admin.id),
+                ok: true,
+            },
+            // user can list
+            ValidatorTest {
+                validators: vec![ValidateDeviceProfileTemplatesAccess::new(Flag::List)],
+                id: AuthID::User(user_active.id),
+                ok: true,
+            },
+            // user can not create
+            ValidatorTest {
+                validators: vec![ValidateDeviceProfileTemplatesAccess::new(Flag::Create)],
+                id: AuthID::User(user_active.id),
+                ok: false,
+            },
+        ];
+        run_tests(tests).await;
+
+        // device-profile templates with api key
+        let tests = vec![
+            // admin api can create and list
+            ValidatorTest {
+                validators: vec![
+                    ValidateDeviceProfileTemplatesAccess::new(Flag::Create),
+                    ValidateDeviceProfileTemplatesAccess::new(Flag::List),
+                ],
+                id: AuthID::Key(api_key_admin.id),
+                ok: true,
+            },
+            // tenant api key can list
+            ValidatorTest {
+                validators: vec![ValidateDeviceProfileTemplatesAccess::new(Flag::List)],
+                id: AuthID::Key(api_key_tenant.id),
+                ok: true,
+            },
+            // tenant api can not create
+            ValidatorTest {
+                validators: vec![ValidateDeviceProfileTemplatesAccess::new(Flag::Create)],
+                id: AuthID::Key(api_key_tenant.id),
+                ok: false,
+            },
+        ];
+        run_tests(tests).await;
+
+        // device-profile template with user
+        let tests = vec![
+            // admin user can read, update and delete
+            ValidatorTest {
+                validators: vec![
+                    ValidateDeviceProfileTemplateAccess::new(Flag::Read),
+                    ValidateDeviceProfileTemplateAccess::new(Flag::Update),
+                    ValidateDeviceProfileTemplateAccess::new(Flag::Delete),
+                ],
+                id: AuthID::User(user_admin.id),
+                ok: true,
+            },
+            // user can read
+            ValidatorTest {
+                validators: vec![ValidateDeviceProfileTemplateAccess::new(Flag::Read)],
+                id: AuthID::User(user_active.id),
+                ok: true,
+            },
+            // user can not update or delete
+            ValidatorTest {
+                validators: vec![
+                    ValidateDeviceProfileTemplateAccess::new(Flag::Update),
+                    ValidateDeviceProfileTemplateAccess::new(Flag::Delete),
+                ],
+                id: AuthID::User(user_active.id),
+                ok: false,
+            },
+        ];
+        run_tests(tests).await;
+
+        // device-profile template with api key
+        let tests = vec![
+            // admin api key can read, update and delete
+            ValidatorTest {
+                validators: vec![
+                    ValidateDeviceProfileTemplateAccess::new(Flag::Read),
+                    ValidateDeviceProfileTemplateAccess::new(Flag::Update),
+                    ValidateDeviceProfileTemplateAccess::new(Flag::Delete),
+                ],
+                id: AuthID::Key(api_key_admin.id),
+                ok: true,
+            },
+            // tenant api key can read
+            ValidatorTest {
+                validators: vec![ValidateDeviceProfileTemplateAccess::new(Flag::Read)],
+                id: AuthID::Key(api_key_tenant.id),
+                ok: true,
+            },
+            // tenant api key can not update or delete
+            ValidatorTest {
+                validators: vec![
+                    ValidateDeviceProfileTemplateAccess::new(Flag::Update),
+                    ValidateDeviceProfileTemplateAccess::new(Flag::Delete),
+                ],
+                id: AuthID::Key(api_key_tenant.id),
+                ok: false,
+            },
+        ];
+        run_tests(tests).await;
+    }
+
+    #[tokio::test]
+    async fn device_profile() {
+        let _guard = test::prepare().await;
+
+        let user_active = user::User {
+            email: "user@user".into(),
+            is_active: true,
+            ..Default::default()
+        };
+        let user_admin = user::User {
+            email: "admin@user".into(),
+            is_active: true,
+            is_admin: true,
+            ..Default::default()
+        };
+        let tenant_admin = user::User {
+            email: "tenant-admin@user".into(),
+            is_active: true,
+            ..Default::default()
+        };
+        let tenant_device_admin = user::User {
+            email: "tenant-device-admin@user".into(),
+            is_active: true,
+            ..Default::default()
+        };
+        let tenant_gateway_admin = user::User {
+            email: "tenant-gateway-admin@user".into(),
+            is_active: true,
+            ..Default::default()
+        };
+        let tenant_user = user::User {
+            email: "tenant-user@user".into(),
+            is_active: true,
+            ..Default::default()
+        };
+
+        for u in vec![
+            &user_active,
+            &user_admin,
+            &tenant_admin,
+            &tenant_gateway_admin,
+            &tenant_device_admin,
+            &tenant_user,
+        ] {
+            user::create(u.clone()).await.unwrap();
+        }
+
+        let tenant_a = tenant::test::create_tenant().await;
+
+        let api_key_admin = api_key::test::create_api_key(true, false).await;
+        let api_key_tenant = api_key::test::create_api_key(false, true).await;
+
+        let dp = device_profile::create(device_profile::DeviceProfile {
+            name: "test-dp".into(),
+            tenant_id: tenant_a.id.clone(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let dp_api_key_tenant = device_profile::create(device_profile::DeviceProfile {
+            name: "test-dp-tenant".into(),
+            tenant_id: api_key_tenant.tenant_id.unwrap().clone(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        tenant::add_user(tenant::TenantUser {
+            tenant_id: tenant_a.id,
+            user_id: tenant_admin.id,
+            is_admin: true,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        tenant::add_user(tenant::TenantUser {
+            tenant_id: tenant_a.id,
+            user_id: tenant_device_admin.id,
+            is_device_admin: true,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        tenant::add_user(tenant::TenantUser {
+            tenant_id: tenant_a.id,
+            user_id: tenant_gateway_admin.id,
+            is_gateway_admin: true,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        tenant::add_user(tenant::TenantUser {
+            tenant_id: tenant_a.id,
+            user_id: tenant_user.id,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        // device profiles with user
+        let tests = vec![
+            // admin user can create and list
+            ValidatorTest {
+                validators: vec![
+                    ValidateDeviceProfilesAccess::new(Flag::Create, tenant_a.id),
+                    ValidateDeviceProfilesAccess::new(Flag::List, tenant_a.id),
+                ],
+                id: AuthID::User(user_admin.id),
+                ok: true,
+            },
+            // tenant admin user can create and list
+            ValidatorTest {
+                validators: vec![
+                    ValidateDeviceProfilesAccess::new(Flag::Create, tenant_a.id),
+                    ValidateDeviceProfilesAccess::new(Flag::List, tenant_a.id),
+                ],
+                id: AuthID::User(tenant_admin.id),
+                ok: true,
+            },
+            // tenant device admin can create and list
+            ValidatorTest {
+                validators: vec![
+                    ValidateDeviceProfilesAccess::new(Flag::Create, tenant_a.id),
+                    ValidateDeviceProfilesAccess::new(Flag::List, tenant_a.id),
                 ],
-                id: AuthID::User(user_admin.id),
+                id: AuthID::User(tenant_device_admin.id),
                 ok: true,
             },
-            // user can list
+            // tenant gateway admin can list
             ValidatorTest {
-                validators: vec![ValidateDeviceProfileTemplatesAccess::new(Flag::List)],
-                id: AuthID::User(user_active.id),
+                validators: vec![ValidateDeviceProfilesAccess::new(Flag::List, tenant_a.id)],
+                id: AuthID::User(tenant_gateway_admin.id),
                 ok: true,
             },
-            // user can not create
+            // tenant users can list
             ValidatorTest {
-                validators: vec![ValidateDeviceProfileTemplatesAccess::new(Flag::Create)],
+                validators: vec![ValidateDeviceProfilesAccess::new(Flag::List, tenant_a.id)],
+                id: AuthID::User(tenant_user.id),
+                ok: true,
+            },
+            // tenant users can not create
+            ValidatorTest {
+                validators: vec![ValidateDeviceProfilesAccess::new(Flag::Create, tenant_a.id)],
+                id: AuthID::User(tenant_user.id),
+                ok: false,
+            },
+            // tenant gateway admin can not create
+            ValidatorTest {
+                validators: vec![ValidateDeviceProfilesAccess::new(Flag::Create, tenant_a.id)],
+                id: AuthID::User(tenant_gateway_admin.id),
+                ok: false,
+            },
+            // non-tenant users can not list or create
+            ValidatorTest {
+                validators: vec![
+                    ValidateDeviceProfilesAccess::new(Flag::Create, tenant_a.id),
+                    ValidateDeviceProfilesAccess::new(Flag::List, tenant_a.id),
+                ],
                 id: AuthID::User(user_active.id),
                 ok: false,
             },
         ];
         run_tests(tests).await;
 
-        // device-profile templates with api key
+        // device profiles with api key
         let tests = vec![
-            // admin api can create and list
+            // admin api key can create and list
             ValidatorTest {
                 validators: vec![
-                    ValidateDeviceProfileTemplatesAccess::new(Flag::Create),
-                    ValidateDeviceProfileTemplatesAccess::new(Flag::List),
+                    ValidateDeviceProfilesAccess::new(Flag::Create, tenant_a.id),
+                    ValidateDeviceProfilesAccess::new(Flag::List, tenant_a.id),
                 ],
                 id: AuthID::Key(api_key_admin.id),
                 ok: true,
             },
-            // tenant api key can list
+            // tenant api key can create and list
             ValidatorTest {
-                validators: vec![ValidateDeviceProfileTemplatesAccess::new(Flag::List)],
+                validators: vec![
+                    ValidateDeviceProfilesAccess::new(
+                        Flag::Create,
+                        api_key_tenant.tenant_id.unwrap(),
+                    ),
+                    ValidateDeviceProfilesAccess::new(
+                        Flag::List,
+                        api_key_tenant.tenant_id.unwrap(),
+                    ),
+                ],
                 id: AuthID::Key(api_key_tenant.id),
                 ok: true,
             },
-            // tenant api can not create
+            // tenant api key can not create or list for other tenant
             ValidatorTest {
-                validators: vec![ValidateDeviceProfileTemplatesAccess::new(Flag::Create)],
+                validators: vec![
+                    ValidateDeviceProfilesAccess::new(Flag::Create, tenant_a.id),
+                    ValidateDeviceProfilesAccess::new(Flag::List, tenant_a.id),
+                ],
                 id: AuthID::Key(api_key_tenant.id),
                 ok: false,
             },
         ];
         run_tests(tests).await;
 
-        // device-profile template with user
+        // device profile with user
         let tests = vec![
             // admin user can read, update and delete
             ValidatorTest {
                 validators: vec![
-                    ValidateDeviceProfileTemplateAccess::new(Flag::Read),
-                    ValidateDeviceProfileTemplateAccess::new(Flag::Update),
-                    ValidateDeviceProfileTemplateAccess::new(Flag::Delete),
+                    ValidateDeviceProfileAccess::new(Flag::Read, dp.id),
+                    ValidateDeviceProfileAccess::new(Flag::Update, dp.id),
+                    ValidateDeviceProfileAccess::new(Flag::Delete, dp.id),
                 ],
                 id: AuthID::User(user_admin.id),
                 ok: true,
             },
-            // user can read
+            // tenant admin can read, update and delete
             ValidatorTest {
-                validators: vec![ValidateDeviceProfileTemplateAccess::new(Flag::Read)],
-                id: AuthID::User(user_active.id),
+                validators: vec![
+                    ValidateDeviceProfileAccess::new(Flag::Read, dp.id),
+                    ValidateDeviceProfileAccess::new(Flag::Update, dp.id),
+                    ValidateDeviceProfileAccess::new(Flag::Delete, dp.id),
+                ],
+                id: AuthID::User(tenant_admin.id),
                 ok: true,
             },
-            // user can not update or delete
+            // tenant device admin can read, update and delete
             ValidatorTest {
                 validators: vec![
-                    ValidateDeviceProfileTemplateAccess::new(Flag::Update),
-                    ValidateDeviceProfileTemplateAccess::new(Flag::Delete),
+                    ValidateDeviceProfileAccess::new(Flag::Read, dp.id),
+                    ValidateDeviceProfileAccess::new(Flag::Update, dp.id),
+                    ValidateDeviceProfileAccess::new(Flag::Delete, dp.id),
                 ],
-                id: AuthID::User(user_active.id),
+                id: AuthID::User(tenant_device_admin.id),
+                ok: true,
+            },
+            // tenant gateway admin can read
+            ValidatorTest {
+                validators: vec![ValidateDeviceProfileAccess::new(Flag::Read, dp.id)],
+                id: AuthID::User(tenant_gateway_admin.id),
+                ok: true,
+            },
+            // tenant user can read
+            ValidatorTest {
+                validators: vec![ValidateDeviceProfileAccess::new(Flag::Read, dp.id)],
+                id: AuthID::User(tenant_user.id),
+                ok: true,
+            },
+            // tenant gateway admin can not update or delete
+            ValidatorTest {
+                validators: vec![
+                    ValidateDeviceProfileAccess::new(Flag::Update, dp.id),
+                    ValidateDeviceProfileAccess::new(Flag::Delete, dp.id),
+                ],
+                id: AuthID::User(tenant_gateway_admin.id),
+                ok: false,
+            },
+            // tenant user can not update or delete
+            ValidatorTest {
+                validators: vec![
+                    ValidateDeviceProfileAccess::new(Flag::Update, dp.id),
+                    ValidateDeviceProfileAccess::new(Flag::Delete, dp.id),
+                ],
+                id: AuthID::User(tenant_user.id),
                 ok: false,
             },
         ];
         run_tests(tests).await;
 
-        // device-profile template with api key
+        // device profile with api key
         let tests = vec![
             // admin api key can read, update and delete
             ValidatorTest {
                 validators: vec![
-                    ValidateDeviceProfileTemplateAccess::new(Flag::Read),
-                    ValidateDeviceProfileTemplateAccess::new(Flag::Update),
-                    ValidateDeviceProfileTemplateAccess::new(Flag::Delete),
+                    ValidateDeviceProfileAccess::new(Flag::Read, dp.id),
+                    ValidateDeviceProfileAccess::new(Flag::Update, dp.id),
+                    ValidateDeviceProfileAccess::new(Flag::Delete, dp.id),
                 ],
                 id: AuthID::Key(api_key_admin.id),
                 ok: true,
             },
-            // tenant api key can read
+            // tenant api key can read update and delete
             ValidatorTest {
-                validators: vec![ValidateDeviceProfileTemplateAccess::new(Flag::Read)],
+                validators: vec![
+                    ValidateDeviceProfileAccess::new(Flag::Read, dp_api_key_tenant.id),
+                    ValidateDeviceProfileAccess::new(Flag::Update, dp_api_key_tenant.id),
+                    ValidateDeviceProfileAccess::new(Flag::Delete, dp_api_key_tenant.id),
+                ],
                 id: AuthID::Key(api_key_tenant.id),
                 ok: true,
             },
-            // tenant api key can not update or delete
+            // tenant api key can not read, update or delete for other tenant
             ValidatorTest {
                 validators: vec![
-                    ValidateDeviceProfileTemplateAccess::new(Flag::Update),
-                    ValidateDeviceProfileTemplateAccess::new(Flag::Delete),
+                    ValidateDeviceProfileAccess::new(Flag::Read, dp.id),
+                    ValidateDeviceProfileAccess::new(Flag::Update, dp.id),
+                    ValidateDeviceProfileAccess::new(Flag::Delete, dp.id),
                 ],
                 id: AuthID::Key(api_key_tenant.id),
                 ok: false,
@@ -3040,7 +4429,7 @@ pub mod test {
     }
 
     #[tokio::test]
-    async fn device_profile() {
+    async fn device() {
         let _guard = test::prepare().await;
 
         let user_active = user::User {
@@ -3086,28 +4475,15 @@ pub mod test {
             user::create(u.clone()).await.unwrap();
         }
 
-        let tenant_a = tenant::test::create_tenant().await;
-
         let api_key_admin = api_key::test::create_api_key(true, false).await;
         let api_key_tenant = api_key::test::create_api_key(false, true).await;
+        let api_key_other_tenant = api_key::test::create_api_key(false, true).await;
 
-        let dp = device_profile::create(device_profile::DeviceProfile {
-            name: "test-dp".into(),
-            tenant_id: tenant_a.id.clone(),
-            ..Default::default()
-        })
-        .await
-        .unwrap();
-        let dp_api_key_tenant = device_profile::create(device_profile::DeviceProfile {
-            name: "test-dp-tenant".into(),
-            tenant_id: api_key_tenant.tenant_id.unwrap().clone(),
-            ..Default::default()
-        })
-        .await
-        .unwrap();
+        let app =
+            application::test::create_application(Some(api_key_tenant.tenant_id.unwrap())).await;
 
         tenant::add_user(tenant::TenantUser {
-            tenant_id: tenant_a.id,
+            tenant_id: api_key_tenant.tenant_id.unwrap(),
             user_id: tenant_admin.id,
             is_admin: true,
             ..Default::default()
@@ -3115,7 +4491,7 @@ pub mod test {
         .await
         .unwrap();
         tenant::add_user(tenant::TenantUser {
-            tenant_id: tenant_a.id,
+            tenant_id: api_key_tenant.tenant_id.unwrap(),
             user_id: tenant_device_admin.id,
             is_device_admin: true,
             ..Default::default()
@@ -3123,7 +4499,7 @@ pub mod test {
         .await
         .unwrap();
         tenant::add_user(tenant::TenantUser {
-            tenant_id: tenant_a.id,
+            tenant_id: api_key_tenant.tenant_id.unwrap(),
             user_id: tenant_gateway_admin.id,
             is_gateway_admin: true,
             ..Default::default()
@@ -3131,20 +4507,19 @@ pub mod test {
         .await
         .unwrap();
         tenant::add_user(tenant::TenantUser {
-            tenant_id: tenant_a.id,
+            tenant_id: api_key_tenant.tenant_id.unwrap(),
             user_id: tenant_user.id,
             ..Default::default()
         })
         .await
         .unwrap();
 
-        // device profiles with user
         let tests = vec![
             // admin user can create and list
             ValidatorTest {
                 validators: vec![
-                    ValidateDeviceProfilesAccess::new(Flag::Create, tenant_a.id),
-                    ValidateDeviceProfilesAccess::new(Flag::List, tenant_a.id),
+                    ValidateDevicesAccess::new(Flag::Create, app.id),
+                    ValidateDevicesAccess::new(Flag::List, app.id),
                 ],
                 id: AuthID::User(user_admin.id),
                 ok: true,
@@ -3152,8 +4527,8 @@ pub mod test {
             // tenant admin user can create and list
             ValidatorTest {
                 validators: vec![
-                    ValidateDeviceProfilesAccess::new(Flag::Create, tenant_a.id),
-                    ValidateDeviceProfilesAccess::new(Flag::List, tenant_a.id),
+                    ValidateDevicesAccess::new(Flag::Create, app.id),
+                    ValidateDevicesAccess::new(Flag::List, app.id),
                 ],
                 id: AuthID::User(tenant_admin.id),
                 ok: true,
@@ -3161,41 +4536,29 @@ pub mod test {
             // tenant device admin can create and list
             ValidatorTest {
                 validators: vec![
-                    ValidateDeviceProfilesAccess::new(Flag::Create, tenant_a.id),
-                    ValidateDeviceProfilesAccess::new(Flag::List, tenant_a.id),
+                    ValidateDevicesAccess::new(Flag::Create, app.id),
+                    ValidateDevicesAccess::new(Flag::List, app.id),
                 ],
                 id: AuthID::User(tenant_device_admin.id),
                 ok: true,
             },
-            // tenant gateway admin can list
-            ValidatorTest {
-                validators: vec![ValidateDeviceProfilesAccess::new(Flag::List, tenant_a.id)],
-                id: AuthID::User(tenant_gateway_admin.id),
-                ok: true,
-            },
-            // tenant users can list
+            // tenant user can list
             ValidatorTest {
-                validators: vec![ValidateDeviceProfilesAccess::new(Flag::List, tenant_a.id)],
+                validators: vec![ValidateDevicesAccess::new(Flag::List, app.id)],
                 id: AuthID::User(tenant_user.id),
                 ok: true,
             },
-            // tenant users can not create
+            // tenant user can not create
             ValidatorTest {
-                validators: vec![ValidateDeviceProfilesAccess::new(Flag::Create, tenant_a.id)],
+                validators: vec![ValidateDevicesAccess::new(Flag::Create, app.id)],
                 id: AuthID::User(tenant_user.id),
                 ok: false,
             },
-            // tenant gateway admin can not create
-            ValidatorTest {
-                validators: vec![ValidateDeviceProfilesAccess::new(Flag::Create, tenant_a.id)],
-                id: AuthID::User(tenant_gateway_admin.id),
-                ok: false,
-            },
-            // non-tenant users can not list or create
+            // other users can not create or list
             ValidatorTest {
                 validators: vec![
-                    ValidateDeviceProfilesAccess::new(Flag::Create, tenant_a.id),
-                    ValidateDeviceProfilesAccess::new(Flag::List, tenant_a.id),
+                    ValidateDevicesAccess::new(Flag::Create, app.id),
+                    ValidateDevicesAccess::new(Flag::List, app.id),
                 ],
                 id: AuthID::User(user_active.id),
                 ok: false,
@@ -3203,13 +4566,12 @@ pub mod test {
         ];
         run_tests(tests).await;
 
-        // device profiles with api key
         let tests = vec![
             // admin api key can create and list
             ValidatorTest {
                 validators: vec![
-                    ValidateDeviceProfilesAccess::new(Flag::Create, tenant_a.id),
-                    ValidateDeviceProfilesAccess::new(Flag::List, tenant_a.id),
+                    ValidateDevicesAccess::new(Flag::Create, app.id),
+                    ValidateDevicesAccess::new(Flag::List, app.id),
                 ],
                 id: AuthID::Key(api_key_admin.id),
                 ok: true,
@@ -3217,14 +4579,8 @@ pub mod test {
             // tenant api key can create and list
             ValidatorTest {
                 validators: vec![
-                    ValidateDeviceProfilesAccess::new(
-                        Flag::Create,
-                        api_key_tenant.tenant_id.unwrap(),
-                    ),
-                    ValidateDeviceProfilesAccess::new(
-                        Flag::List,
-                        api_key_tenant.tenant_id.unwrap(),
-                    ),
+                    ValidateDevicesAccess::new(Flag::Create, app.id),
+                    ValidateDevicesAccess::new(Flag::List, app.id),
                 ],
                 id: AuthID::Key(api_key_tenant.id),
                 ok: true,
@@ -3232,23 +4588,32 @@ pub mod test {
             // tenant api key can not create or list for other tenant
             ValidatorTest {
                 validators: vec![
-                    ValidateDeviceProfilesAccess::new(Flag::Create, tenant_a.id),
-                    ValidateDeviceProfilesAccess::new(Flag::List, tenant_a.id),
+                    ValidateDevicesAccess::new(Flag::Create, app.id),
+                    ValidateDevicesAccess::new(Flag::List, app.id),
                 ],
-                id: AuthID::Key(api_key_tenant.id),
+                id: AuthID::Key(api_key_other_tenant.id),
                 ok: false,
             },
         ];
         run_tests(tests).await;
 
-        // device profile with user
+        let dp =
+            device_profile::test::create_device_profile(Some(api_key_tenant.tenant_id.unwrap()))
+                .await;
+        let dev = device::test::create_device(
+            EUI64::from_be_bytes([1, 2, 3, 4, 5, 6, 7, 8]),
+            dp.id,
+            Some(app.id),
+        )
+        .await;
+
         let tests = vec![
             // admin user can read, update and delete
             ValidatorTest {
                 validators: vec![
-                    ValidateDeviceProfileAccess::new(Flag::Read, dp.id),
-                    ValidateDeviceProfileAccess::new(Flag::Update, dp.id),
-                    ValidateDeviceProfileAccess::new(Flag::Delete, dp.id),
+                    ValidateDeviceAccess::new(Flag::Read, dev.dev_eui),
+                    ValidateDeviceAccess::new(Flag::Update, dev.dev_eui),
+                    ValidateDeviceAccess::new(Flag::Delete, dev.dev_eui),
                 ],
                 id: AuthID::User(user_admin.id),
                 ok: true,
@@ -3256,9 +4621,9 @@ pub mod test {
             // tenant admin can read, update and delete
             ValidatorTest {
                 validators: vec![
-                    ValidateDeviceProfileAccess::new(Flag::Read, dp.id),
-                    ValidateDeviceProfileAccess::new(Flag::Update, dp.id),
-                    ValidateDeviceProfileAccess::new(Flag::Delete, dp.id),
+                    ValidateDeviceAccess::new(Flag::Read, dev.dev_eui),
+                    ValidateDeviceAccess::new(Flag::Update, dev.dev_eui),
+                    ValidateDeviceAccess::new(Flag::Delete, dev.dev_eui),
                 ],
                 id: AuthID::User(tenant_admin.id),
                 ok: true,
@@ -3266,76 +4631,70 @@ pub mod test {
             // tenant device admin can read, update and delete
             ValidatorTest {
                 validators: vec![
-                    ValidateDeviceProfileAccess::new(Flag::Read, dp.id),
-                    ValidateDeviceProfileAccess::new(Flag::Update, dp.id),
-                    ValidateDeviceProfileAccess::new(Flag::Delete, dp.id),
+                    ValidateDeviceAccess::new(Flag::Read, dev.dev_eui),
+                    ValidateDeviceAccess::new(Flag::Update, dev.dev_eui),
+                    ValidateDeviceAccess::new(Flag::Delete, dev.dev_eui),
                 ],
                 id: AuthID::User(tenant_device_admin.id),
                 ok: true,
             },
-            // tenant gateway admin can read
-            ValidatorTest {
-                validators: vec![ValidateDeviceProfileAccess::new(Flag::Read, dp.id)],
-                id: AuthID::User(tenant_gateway_admin.id),
-                ok: true,
-            },
             // tenant user can read
             ValidatorTest {
-                validators: vec![ValidateDeviceProfileAccess::new(Flag::Read, dp.id)],
+                validators: vec![ValidateDeviceAccess::new(Flag::Read, dev.dev_eui)],
                 id: AuthID::User(tenant_user.id),
                 ok: true,
             },
-            // tenant gateway admin can not update or delete
+            // tenant user can not update or delete
             ValidatorTest {
                 validators: vec![
-                    ValidateDeviceProfileAccess::new(Flag::Update, dp.id),
-                    ValidateDeviceProfileAccess::new(Flag::Delete, dp.id),
+                    ValidateDeviceAccess::new(Flag::Update, dev.dev_eui),
+                    ValidateDeviceAccess::new(Flag::Delete, dev.dev_eui),
                 ],
-                id: AuthID::User(tenant_gateway_admin.id),
+                id: AuthID::User(tenant_user.id),
                 ok: false,
             },
-            // tenant user can not update or delete
+            // other user can not read, update and delete
             ValidatorTest {
                 validators: vec![
-                    ValidateDeviceProfileAccess::new(Flag::Update, dp.id),
-                    ValidateDeviceProfileAccess::new(Flag::Delete, dp.id),
+                    ValidateDeviceAccess::new(Flag::Read, dev.dev_eui),
+                    ValidateDeviceAccess::new(Flag::Update, dev.dev_eui),
+                    ValidateDeviceAccess::new(Flag::Delete, dev.dev_eui),
                 ],
-                id: AuthID::User(tenant_user.id),
+                id: AuthID::User(user_active.id),
                 ok: false,
             },
         ];
         run_tests(tests).await;
 
-        // device profile with api key
         let tests = vec![
             // admin api key can read, update and delete
             ValidatorTest {
                 validators: vec![
-                    ValidateDeviceProfileAccess::new(Flag::Read, dp.id),
-                    ValidateDeviceProfileAccess::new(Flag::Update, dp.id),
-                    ValidateDeviceProfileAccess::new(Flag::Delete, dp.id),
+                    ValidateDeviceAccess::new(Flag::Read, dev.dev_eui),
+                    ValidateDeviceAccess::new(Flag::Update, dev.dev_eui),
+                    ValidateDeviceAccess::new(Flag::Delete, dev.dev_eui),
                 ],
                 id: AuthID::Key(api_key_admin.id),
                 ok: true,
             },
-            // tenant api key can read update and delete
+            // tenant api key can read, update and delete
             ValidatorTest {
                 validators: vec![
-                    ValidateDeviceProfileAccess::new(Flag::Read, dp_api_key_tenant.id),
-                    ValidateDeviceProfileAccess::new(Flag::Update, dp_api_key_tenant.id),
-                    ValidateDeviceProfileAccess::new(Flag::Delete, dp_api_key_tenant.id),
+                    ValidateDeviceAccess::new(Flag::Read, dev.dev_eui),
+                    ValidateDeviceAccess::new(Flag::Update, dev.dev_eui),
+                    ValidateDeviceAccess::new(Flag::Delete, dev.dev_eui),
                 ],
                 id: AuthID::Key(api_key_tenant.id),
                 ok: true,
             },
-            // tenant api key can not read, update or delete for other tenant
+            // other api key can not read, update or delete
             ValidatorTest {
                 validators: vec![
-                    ValidateDeviceProfileAccess::new(Flag::Read, dp.id),
-                    ValidateDeviceProfileAccess::new(Flag::Update, dp.id),
-                    ValidateDeviceProfileAccess::new(Flag::Delete, dp.id),
+                    ValidateDeviceAccess::new(Flag::Read, dev.dev_eui),
+                    ValidateDeviceAccess::new(Flag::Update, dev.dev_eui),
+                    ValidateDeviceAccess::new(Flag::Delete, dev.dev_eui),
                 ],
-                id: AuthID::Key(api_key_tenant.id),
+                id: AuthID::Key(api_key_other_tenant.id),
                 ok: false,
             },
         ];
@@ -3343,7 +4702,7 @@ pub mod test {
     }
 
     #[tokio::test]
-    async fn device() {
+    async fn device_queue() {
         let _guard = test::prepare().await;
 
         let user_active = user::User {
@@ -3357,35 +4716,13 @@ pub mod test {
             is_admin: true,
             ..Default::default()
         };
-        let tenant_admin = user::User {
-            email: "tenant-admin@user".into(),
-            is_active: true,
-            ..Default::default()
-        };
-        let tenant_device_admin = user::User {
-            email: "tenant-device-admin@user".into(),
-            is_active: true,
-            ..Default::default()
-        };
-        let tenant_gateway_admin = user::User {
-            email: "tenant-gateway-admin@user".into(),
-            is_active: true,
-            ..Default::default()
-        };
         let tenant_user = user::User {
             email: "tenant-user@user".into(),
             is_active: true,
             ..Default::default()
         };
 
-        for u in vec![
-            &user_active,
-            &user_admin,
-            &tenant_admin,
-            &tenant_gateway_admin,
-            &tenant_device_admin,
-            &tenant_user,
-        ] {
+        for u in vec![&user_active, &user_admin, &tenant_user] {
             user::create(u.clone()).await.unwrap();
         }
 
@@ -3398,236 +4735,432 @@ pub mod test {
 
         tenant::add_user(tenant::TenantUser {
             tenant_id: api_key_tenant.tenant_id.unwrap(),
-            user_id: tenant_admin.id,
-            is_admin: true,
+            user_id: tenant_user.id,
             ..Default::default()
         })
         .await
         .unwrap();
-        tenant::add_user(tenant::TenantUser {
-            tenant_id: api_key_tenant.tenant_id.unwrap(),
-            user_id: tenant_device_admin.id,
-            is_device_admin: true,
+
+        let dp =
+            device_profile::test::create_device_profile(Some(api_key_tenant.tenant_id.unwrap()))
+                .await;
+        let dev = device::test::create_device(
+            EUI64::from_be_bytes([1, 2, 3, 4, 5, 6, 7, 8]),
+            dp.id,
+            Some(app.id),
+        )
+        .await;
+
+        let tests = vec![
+            // admin user can create list and delete
+            ValidatorTest {
+                validators: vec![
+                    ValidateDeviceQueueAccess::new(Flag::Create, dev.dev_eui),
+                    ValidateDeviceQueueAccess::new(Flag::List, dev.dev_eui),
+                    ValidateDeviceQueueAccess::new(Flag::Delete, dev.dev_eui),
+                ],
+                id: AuthID::User(user_admin.id),
+                ok: true,
+            },
+            // tenant user can create list and delete
+            ValidatorTest {
+                validators: vec![
+                    ValidateDeviceQueueAccess::new(Flag::Create, dev.dev_eui),
+                    ValidateDeviceQueueAccess::new(Flag::List, dev.dev_eui),
+                    ValidateDeviceQueueAccess::new(Flag::Delete, dev.dev_eui),
+                ],
+                id: AuthID::User(tenant_user.id),
+                ok: true,
+            },
+            // other user can not create, list or delete
+            ValidatorTest {
+                validators: vec![
+                    ValidateDeviceQueueAccess::new(Flag::Create, dev.dev_eui),
+                    ValidateDeviceQueueAccess::new(Flag::List, dev.dev_eui),
+                    ValidateDeviceQueueAccess::new(Flag::Delete, dev.dev_eui),
+                ],
+                id: AuthID::User(user_active.id),
+                ok: false,
+            },
+        ];
+        run_tests(tests).await;
+
+        let tests = vec![
+            // admin api key can create, list and delete
+            ValidatorTest {
+                validators: vec![
+                    ValidateDeviceQueueAccess::new(Flag::Create, dev.dev_eui),
+                    ValidateDeviceQueueAccess::new(Flag::List, dev.dev_eui),
+                    ValidateDeviceQueueAccess::new(Flag::Delete, dev.dev_eui),
+                ],
+                id: AuthID::Key(api_key_admin.id),
+                ok: true,
+            },
+            // tenant api key can create, list and delete
+            ValidatorTest {
+                validators: vec![
+                    ValidateDeviceQueueAccess::new(Flag::Create, dev.dev_eui),
+                    ValidateDeviceQueueAccess::new(Flag::List, dev.dev_eui),
+                    ValidateDeviceQueueAccess::new(Flag::Delete, dev.dev_eui),
+                ],
+                id: AuthID::Key(api_key_tenant.id),
+                ok: true,
+            },
+            // api key for other tenant cna not create, list or delete
+            ValidatorTest {
+                validators: vec![
+                    ValidateDeviceQueueAccess::new(Flag::Create, dev.dev_eui),
+                    ValidateDeviceQueueAccess::new(Flag::List, dev.dev_eui),
+                    ValidateDeviceQueueAccess::new(Flag::Delete, dev.dev_eui),
+                ],
+                id: AuthID::Key(api_key_other_tenant.id),
+                ok: false,
+            },
+        ];
+        run_tests(tests).await;
+
+        let app_other =
+            application::test::create_application(Some(api_key_tenant.tenant_id.unwrap())).await;
+        let api_key_enqueue = api_key::create(api_key::ApiKey {
+            name: "enqueue".into(),
+            tenant_id: api_key_tenant.tenant_id,
+            application_id: Some(app.id),
+            can_read: false,
+            can_write: false,
+            can_read_keys: false,
             ..Default::default()
         })
         .await
         .unwrap();
-        tenant::add_user(tenant::TenantUser {
-            tenant_id: api_key_tenant.tenant_id.unwrap(),
-            user_id: tenant_gateway_admin.id,
-            is_gateway_admin: true,
+        let api_key_other_app = api_key::create(api_key::ApiKey {
+            name: "other app".into(),
+            tenant_id: api_key_tenant.tenant_id,
+            application_id: Some(app_other.id),
             ..Default::default()
         })
         .await
         .unwrap();
-        tenant::add_user(tenant::TenantUser {
-            tenant_id: api_key_tenant.tenant_id.unwrap(),
-            user_id: tenant_user.id,
+        let api_key_expired = api_key::create(api_key::ApiKey {
+            name: "expired".into(),
+            tenant_id: api_key_tenant.tenant_id,
+            expires_at: Some(chrono::Utc::now() - chrono::Duration::try_minutes(1).unwrap()),
             ..Default::default()
         })
         .await
         .unwrap();
 
         let tests = vec![
-            // admin user can create and list
+            // enqueue api key scoped to the application can create
+            ValidatorTest {
+                validators: vec![ValidateDeviceQueueAccess::new(Flag::Create, dev.dev_eui)],
+                id: AuthID::Key(api_key_enqueue.id),
+                ok: true,
+            },
+            // enqueue api key can not list or delete, or access other resources
+            ValidatorTest {
+                validators: vec![
+                    ValidateDeviceQueueAccess::new(Flag::List, dev.dev_eui),
+                    ValidateDeviceQueueAccess::new(Flag::Delete, dev.dev_eui),
+                ],
+                id: AuthID::Key(api_key_enqueue.id),
+                ok: false,
+            },
+            // api key scoped to other application can not create, list or delete
+            ValidatorTest {
+                validators: vec![
+                    ValidateDeviceQueueAccess::new(Flag::Create, dev.dev_eui),
+                    ValidateDeviceQueueAccess::new(Flag::List, dev.dev_eui),
+                    ValidateDeviceQueueAccess::new(Flag::Delete, dev.dev_eui),
+                ],
+                id: AuthID::Key(api_key_other_app.id),
+                ok: false,
+            },
+            // expired api key can not create, list or delete
+            ValidatorTest {
+                validators: vec![
+                    ValidateDeviceQueueAccess::new(Flag::Create, dev.dev_eui),
+                    ValidateDeviceQueueAccess::new(Flag::List, dev.dev_eui),
+                    ValidateDeviceQueueAccess::new(Flag::Delete, dev.dev_eui),
+                ],
+                id: AuthID::Key(api_key_expired.id),
+                ok: false,
+            },
+        ];
+        run_tests(tests).await;
+
+        // scoped api keys can not access tenant resources
+        run_tests(vec![ValidatorTest {
+            validators: vec![ValidateTenantAccess::new(
+                Flag::Read,
+                api_key_tenant.tenant_id.unwrap(),
+            )],
+            id: AuthID::Key(api_key_other_app.id),
+            ok: false,
+        }])
+        .await;
+    }
+
+    #[tokio::test]
+    async fn tenant_role() {
+        let _guard = test::prepare().await;
+
+        let tenant_admin = user::User {
+            email: "tenant-admin@user".into(),
+            is_active: true,
+            ..Default::default()
+        };
+        let device_writer = user::User {
+            email: "device-writer@user".into(),
+            is_active: true,
+            ..Default::default()
+        };
+        let viewer = user::User {
+            email: "viewer@user".into(),
+            is_active: true,
+            ..Default::default()
+        };
+
+        for u in vec![&tenant_admin, &device_writer, &viewer] {
+            user::create(u.clone()).await.unwrap();
+        }
+
+        let api_key_tenant = api_key::test::create_api_key(false, true).await;
+        let api_key_other_tenant = api_key::test::create_api_key(false, true).await;
+        let tenant_id = api_key_tenant.tenant_id.unwrap();
+
+        let app = application::test::create_application(Some(tenant_id)).await;
+        let app_other = application::test::create_application(Some(tenant_id)).await;
+        let dp = device_profile::test::create_device_profile(Some(tenant_id)).await;
+        let dev = device::test::create_device(
+            EUI64::from_be_bytes([1, 2, 3, 4, 5, 6, 7, 8]),
+            dp.id,
+            Some(app.id),
+        )
+        .await;
+
+        for (u, is_admin) in [
+            (&tenant_admin, true),
+            (&device_writer, false),
+            (&viewer, false),
+        ] {
+            tenant::add_user(tenant::TenantUser {
+                tenant_id,
+                user_id: u.id,
+                is_admin,
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        }
+
+        let role_writer = tenant_role::test::create_role(
+            tenant_id,
+            &[
+                RolePermission::DeviceWrite,
+                RolePermission::Enqueue,
+                RolePermission::IntegrationWrite,
+            ],
+        )
+        .await;
+        let role_viewer = tenant_role::test::create_role(tenant_id, &[]).await;
+
+        tenant_role::add_user_role(tenant_role::TenantUserRole {
+            tenant_id,
+            user_id: device_writer.id,
+            role_id: role_writer.id,
+            application_id: Some(app.id),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        tenant_role::add_user_role(tenant_role::TenantUserRole {
+            tenant_id,
+            user_id: viewer.id,
+            role_id: role_viewer.id,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        // role permissions apply to the application of the role assignment
+        run_tests(vec![
             ValidatorTest {
-                validators: vec![
-                    ValidateDevicesAccess::new(Flag::Create, app.id),
-                    ValidateDevicesAccess::new(Flag::List, app.id),
-                ],
-                id: AuthID::User(user_admin.id),
+                validators: vec![ValidateDevicesAccess::new(Flag::Create, app.id)],
+                id: AuthID::User(device_writer.id),
                 ok: true,
             },
-            // tenant admin user can create and list
             ValidatorTest {
-                validators: vec![
-                    ValidateDevicesAccess::new(Flag::Create, app.id),
-                    ValidateDevicesAccess::new(Flag::List, app.id),
-                ],
-                id: AuthID::User(tenant_admin.id),
-                ok: true,
+                validators: vec![ValidateDevicesAccess::new(Flag::Create, app_other.id)],
+                id: AuthID::User(device_writer.id),
+                ok: false,
             },
-            // tenant device admin can create and list
+        ])
+        .await;
+        run_tests(vec![ValidatorTest {
+            validators: vec![
+                ValidateDeviceAccess::new(Flag::Update, dev.dev_eui),
+                ValidateDeviceAccess::new(Flag::Delete, dev.dev_eui),
+            ],
+            id: AuthID::User(device_writer.id),
+            ok: true,
+        }])
+        .await;
+        run_tests(vec![
             ValidatorTest {
                 validators: vec![
-                    ValidateDevicesAccess::new(Flag::Create, app.id),
-                    ValidateDevicesAccess::new(Flag::List, app.id),
+                    ValidateApplicationIntegrationsAccess::new(Flag::Read, app.id),
+                    ValidateApplicationIntegrationsAccess::new(Flag::Update, app.id),
                 ],
-                id: AuthID::User(tenant_device_admin.id),
-                ok: true,
-            },
-            // tenant user can list
-            ValidatorTest {
-                validators: vec![ValidateDevicesAccess::new(Flag::List, app.id)],
-                id: AuthID::User(tenant_user.id),
+                id: AuthID::User(device_writer.id),
                 ok: true,
             },
-            // tenant user can not create
-            ValidatorTest {
-                validators: vec![ValidateDevicesAccess::new(Flag::Create, app.id)],
-                id: AuthID::User(tenant_user.id),
-                ok: false,
-            },
-            // other users can not create or list
             ValidatorTest {
-                validators: vec![
-                    ValidateDevicesAccess::new(Flag::Create, app.id),
-                    ValidateDevicesAccess::new(Flag::List, app.id),
-                ],
-                id: AuthID::User(user_active.id),
+                validators: vec![ValidateApplicationIntegrationsAccess::new(
+                    Flag::Update,
+                    app_other.id,
+                )],
+                id: AuthID::User(device_writer.id),
                 ok: false,
             },
-        ];
-        run_tests(tests).await;
+        ])
+        .await;
 
-        let tests = vec![
-            // admin api key can create and list
+        // but not to other permissions
+        run_tests(vec![ValidatorTest {
+            validators: vec![ValidateApplicationAccess::new(Flag::Update, app.id)],
+            id: AuthID::User(device_writer.id),
+            ok: false,
+        }])
+        .await;
+        run_tests(vec![ValidatorTest {
+            validators: vec![ValidateGatewaysAccess::new(Flag::Create, tenant_id)],
+            id: AuthID::User(device_writer.id),
+            ok: false,
+        }])
+        .await;
+
+        // users with roles need the enqueue permission to enqueue or flush
+        run_tests(vec![
             ValidatorTest {
                 validators: vec![
-                    ValidateDevicesAccess::new(Flag::Create, app.id),
-                    ValidateDevicesAccess::new(Flag::List, app.id),
+                    ValidateDeviceQueueAccess::new(Flag::Create, dev.dev_eui),
+                    ValidateDeviceQueueAccess::new(Flag::List, dev.dev_eui),
+                    ValidateDeviceQueueAccess::new(Flag::Delete, dev.dev_eui),
                 ],
-                id: AuthID::Key(api_key_admin.id),
+                id: AuthID::User(device_writer.id),
                 ok: true,
             },
-            // tenant api key can create and list
             ValidatorTest {
-                validators: vec![
-                    ValidateDevicesAccess::new(Flag::Create, app.id),
-                    ValidateDevicesAccess::new(Flag::List, app.id),
-                ],
-                id: AuthID::Key(api_key_tenant.id),
+                validators: vec![ValidateDeviceQueueAccess::new(Flag::List, dev.dev_eui)],
+                id: AuthID::User(viewer.id),
                 ok: true,
             },
-            // tenant api key can not create or list for other tenant
             ValidatorTest {
                 validators: vec![
-                    ValidateDevicesAccess::new(Flag::Create, app.id),
-                    ValidateDevicesAccess::new(Flag::List, app.id),
+                    ValidateDeviceQueueAccess::new(Flag::Create, dev.dev_eui),
+                    ValidateDeviceQueueAccess::new(Flag::Delete, dev.dev_eui),
                 ],
-                id: AuthID::Key(api_key_other_tenant.id),
+                id: AuthID::User(viewer.id),
                 ok: false,
             },
-        ];
-        run_tests(tests).await;
-
-        let dp =
-            device_profile::test::create_device_profile(Some(api_key_tenant.tenant_id.unwrap()))
-                .await;
-        let dev = device::test::create_device(
-            EUI64::from_be_bytes([1, 2, 3, 4, 5, 6, 7, 8]),
-            dp.id,
-            Some(app.id),
-        )
+        ])
         .await;
 
-        let tests = vec![
-            // admin user can read, update and delete
+        // tenant admin and tenant api key can manage roles, tenant users can list them
+        run_tests(vec![
             ValidatorTest {
                 validators: vec![
-                    ValidateDeviceAccess::new(Flag::Read, dev.dev_eui),
-                    ValidateDeviceAccess::new(Flag::Update, dev.dev_eui),
-                    ValidateDeviceAccess::new(Flag::Delete, dev.dev_eui),
-                ],
-                id: AuthID::User(user_admin.id),
-                ok: true,
-            },
-            // tenant admin can read, update and delete
-            ValidatorTest {
-                validators: vec![
-                    ValidateDeviceAccess::new(Flag::Read, dev.dev_eui),
-                    ValidateDeviceAccess::new(Flag::Update, dev.dev_eui),
-                    ValidateDeviceAccess::new(Flag::Delete, dev.dev_eui),
+                    ValidateTenantRolesAccess::new(Flag::Create, tenant_id),
+                    ValidateTenantRolesAccess::new(Flag::List, tenant_id),
                 ],
                 id: AuthID::User(tenant_admin.id),
                 ok: true,
             },
-            // tenant device admin can read, update and delete
             ValidatorTest {
                 validators: vec![
-                    ValidateDeviceAccess::new(Flag::Read, dev.dev_eui),
-                    ValidateDeviceAccess::new(Flag::Update, dev.dev_eui),
-                    ValidateDeviceAccess::new(Flag::Delete, dev.dev_eui),
+                    ValidateTenantRolesAccess::new(Flag::Create, tenant_id),
+                    ValidateTenantRolesAccess::new(Flag::List, tenant_id),
                 ],
-                id: AuthID::User(tenant_device_admin.id),
+                id: AuthID::Key(api_key_tenant.id),
                 ok: true,
             },
-            // tenant user can read
             ValidatorTest {
-                validators: vec![ValidateDeviceAccess::new(Flag::Read, dev.dev_eui)],
-                id: AuthID::User(tenant_user.id),
+                validators: vec![ValidateTenantRolesAccess::new(Flag::List, tenant_id)],
+                id: AuthID::User(viewer.id),
                 ok: true,
             },
-            // tenant user can not update or delete
             ValidatorTest {
-                validators: vec![
-                    ValidateDeviceAccess::new(Flag::Update, dev.dev_eui),
-                    ValidateDeviceAccess::new(Flag::Delete, dev.dev_eui),
-                ],
-                id: AuthID::User(tenant_user.id),
+                validators: vec![ValidateTenantRolesAccess::new(Flag::Create, tenant_id)],
+                id: AuthID::User(viewer.id),
                 ok: false,
             },
-            // other user can not read, update and delete
             ValidatorTest {
                 validators: vec![
-                    ValidateDeviceAccess::new(Flag::Read, dev.dev_eui),
-                    ValidateDeviceAccess::new(Flag::Update, dev.dev_eui),
-                    ValidateDeviceAccess::new(Flag::Delete, dev.dev_eui),
+                    ValidateTenantRolesAccess::new(Flag::Create, tenant_id),
+                    ValidateTenantRolesAccess::new(Flag::List, tenant_id),
                 ],
-                id: AuthID::User(user_active.id),
+                id: AuthID::Key(api_key_other_tenant.id),
                 ok: false,
             },
-        ];
-        run_tests(tests).await;
-
-        let tests = vec![
-            // admin api key can read, update and delete
+        ])
+        .await;
+        run_tests(vec![
             ValidatorTest {
                 validators: vec![
-                    ValidateDeviceAccess::new(Flag::Read, dev.dev_eui),
-                    ValidateDeviceAccess::new(Flag::Update, dev.dev_eui),
-                    ValidateDeviceAccess::new(Flag::Delete, dev.dev_eui),
+                    ValidateTenantRoleAccess::new(Flag::Read, role_viewer.id),
+                    ValidateTenantRoleAccess::new(Flag::Update, role_viewer.id),
+                    ValidateTenantRoleAccess::new(Flag::Delete, role_viewer.id),
                 ],
-                id: AuthID::Key(api_key_admin.id),
+                id: AuthID::User(tenant_admin.id),
                 ok: true,
             },
-            // tenant api key can read, update and delete
             ValidatorTest {
                 validators: vec![
-                    ValidateDeviceAccess::new(Flag::Read, dev.dev_eui),
-                    ValidateDeviceAccess::new(Flag::Update, dev.dev_eui),
-                    ValidateDeviceAccess::new(Flag::Delete, dev.dev_eui),
+                    ValidateTenantRoleAccess::new(Flag::Read, role_viewer.id),
+                    ValidateTenantRoleAccess::new(Flag::Update, role_viewer.id),
+                    ValidateTenantRoleAccess::new(Flag::Delete, role_viewer.id),
                 ],
                 id: AuthID::Key(api_key_tenant.id),
                 ok: true,
             },
-            // other api key can not read, update or delete
+            ValidatorTest {
+                validators: vec![ValidateTenantRoleAccess::new(Flag::Read, role_viewer.id)],
+                id: AuthID::User(viewer.id),
+                ok: true,
+            },
             ValidatorTest {
                 validators: vec![
-                    ValidateDeviceAccess::new(Flag::Read, dev.dev_eui),
-                    ValidateDeviceAccess::new(Flag::Update, dev.dev_eui),
-                    ValidateDeviceAccess::new(Flag::Delete, dev.dev_eui),
+                    ValidateTenantRoleAccess::new(Flag::Update, role_viewer.id),
+                    ValidateTenantRoleAccess::new(Flag::Delete, role_viewer.id),
+                ],
+                id: AuthID::User(viewer.id),
+                ok: false,
+            },
+            ValidatorTest {
+                validators: vec![
+                    ValidateTenantRoleAccess::new(Flag::Read, role_viewer.id),
+                    ValidateTenantRoleAccess::new(Flag::Update, role_viewer.id),
+                    ValidateTenantRoleAccess::new(Flag::Delete, role_viewer.id),
                 ],
                 id: AuthID::Key(api_key_other_tenant.id),
                 ok: false,
             },
-        ];
-        run_tests(tests).await;
+        ])
+        .await;
     }
 
     #[tokio::test]
-    async fn device_queue() {
+    async fn application_user() {
         let _guard = test::prepare().await;
 
-        let user_active = user::User {
-            email: "user@user".into(),
+        let member = user::User {
+            email: "member@user".into(),
             is_active: true,
             ..Default::default()
         };
-        let user_admin = user::User {
-            email: "admin@user".into(),
+        let app_admin = user::User {
+            email: "app-admin@user".into(),
             is_active: true,
-            is_admin: true,
             ..Default::default()
         };
         let tenant_user = user::User {
@@ -3636,102 +5169,187 @@ pub mod test {
             ..Default::default()
         };
 
-        for u in vec![&user_active, &user_admin, &tenant_user] {
+        for u in vec![&member, &app_admin, &tenant_user] {
             user::create(u.clone()).await.unwrap();
         }
 
-        let api_key_admin = api_key::test::create_api_key(true, false).await;
         let api_key_tenant = api_key::test::create_api_key(false, true).await;
-        let api_key_other_tenant = api_key::test::create_api_key(false, true).await;
-
-        let app =
-            application::test::create_application(Some(api_key_tenant.tenant_id.unwrap())).await;
-
-        tenant::add_user(tenant::TenantUser {
-            tenant_id: api_key_tenant.tenant_id.unwrap(),
-            user_id: tenant_user.id,
-            ..Default::default()
-        })
-        .await
-        .unwrap();
+        let tenant_id = api_key_tenant.tenant_id.unwrap();
 
-        let dp =
-            device_profile::test::create_device_profile(Some(api_key_tenant.tenant_id.unwrap()))
-                .await;
-        let dev = device::test::create_device(
+        let app_a = application::test::create_application(Some(tenant_id)).await;
+        let app_b = application::test::create_application(Some(tenant_id)).await;
+        let dp = device_profile::test::create_device_profile(Some(tenant_id)).await;
+        let dev_a = device::test::create_device(
             EUI64::from_be_bytes([1, 2, 3, 4, 5, 6, 7, 8]),
             dp.id,
-            Some(app.id),
+            Some(app_a.id),
+        )
+        .await;
+        let dev_b = device::test::create_device(
+            EUI64::from_be_bytes([2, 2, 3, 4, 5, 6, 7, 8]),
+            dp.id,
+            Some(app_b.id),
         )
         .await;
 
-        let tests = vec![
-            // admin user can create list and delete
+        for u in [&member, &app_admin, &tenant_user] {
+            tenant::add_user(tenant::TenantUser {
+                tenant_id,
+                user_id: u.id,
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        }
+        for (u, is_admin) in [(&member, false), (&app_admin, true)] {
+            application::add_user(application::ApplicationUser {
+                application_id: app_a.id,
+                user_id: u.id,
+                is_admin,
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        }
+
+        run_tests(vec![
+            // members can read their application
+            ValidatorTest {
+                validators: vec![ValidateApplicationAccess::new(Flag::Read, app_a.id)],
+                id: AuthID::User(member.id),
+                ok: true,
+            },
+            // but not the other applications within the tenant
             ValidatorTest {
                 validators: vec![
-                    ValidateDeviceQueueAccess::new(Flag::Create, dev.dev_eui),
-                    ValidateDeviceQueueAccess::new(Flag::List, dev.dev_eui),
-                    ValidateDeviceQueueAccess::new(Flag::Delete, dev.dev_eui),
+                    ValidateApplicationAccess::new(Flag::Read, app_b.id),
+                    ValidateApplicationAccess::new(Flag::Update, app_a.id),
                 ],
-                id: AuthID::User(user_admin.id),
+                id: AuthID::User(member.id),
+                ok: false,
+            },
+            // application admin can update its application
+            ValidatorTest {
+                validators: vec![
+                    ValidateApplicationAccess::new(Flag::Read, app_a.id),
+                    ValidateApplicationAccess::new(Flag::Update, app_a.id),
+                    ValidateApplicationAccess::new(Flag::Delete, app_a.id),
+                ],
+                id: AuthID::User(app_admin.id),
                 ok: true,
             },
-            // tenant user can create list and delete
+            ValidatorTest {
+                validators: vec![ValidateApplicationAccess::new(Flag::Read, app_b.id)],
+                id: AuthID::User(app_admin.id),
+                ok: false,
+            },
+            // tenant users without memberships are not restricted
             ValidatorTest {
                 validators: vec![
-                    ValidateDeviceQueueAccess::new(Flag::Create, dev.dev_eui),
-                    ValidateDeviceQueueAccess::new(Flag::List, dev.dev_eui),
-                    ValidateDeviceQueueAccess::new(Flag::Delete, dev.dev_eui),
+                    ValidateApplicationAccess::new(Flag::Read, app_a.id),
+                    ValidateApplicationAccess::new(Flag::Read, app_b.id),
                 ],
                 id: AuthID::User(tenant_user.id),
                 ok: true,
             },
-            // other user can not create, list or delete
+        ])
+        .await;
+
+        run_tests(vec![
+            ValidatorTest {
+                validators: vec![ValidateDeviceAccess::new(Flag::Read, dev_a.dev_eui)],
+                id: AuthID::User(member.id),
+                ok: true,
+            },
             ValidatorTest {
                 validators: vec![
-                    ValidateDeviceQueueAccess::new(Flag::Create, dev.dev_eui),
-                    ValidateDeviceQueueAccess::new(Flag::List, dev.dev_eui),
-                    ValidateDeviceQueueAccess::new(Flag::Delete, dev.dev_eui),
+                    ValidateDeviceAccess::new(Flag::Read, dev_b.dev_eui),
+                    ValidateDeviceAccess::new(Flag::Update, dev_a.dev_eui),
                 ],
-                id: AuthID::User(user_active.id),
+                id: AuthID::User(member.id),
                 ok: false,
             },
-        ];
-        run_tests(tests).await;
+            ValidatorTest {
+                validators: vec![
+                    ValidateDeviceAccess::new(Flag::Update, dev_a.dev_eui),
+                    ValidateDeviceAccess::new(Flag::Delete, dev_a.dev_eui),
+                ],
+                id: AuthID::User(app_admin.id),
+                ok: true,
+            },
+        ])
+        .await;
 
-        let tests = vec![
-            // admin api key can create, list and delete
+        run_tests(vec![ValidatorTest {
+            validators: vec![ValidateDevicesAccess::new(Flag::List, app_b.id)],
+            id: AuthID::User(member.id),
+            ok: false,
+        }])
+        .await;
+
+        run_tests(vec![
             ValidatorTest {
                 validators: vec![
-                    ValidateDeviceQueueAccess::new(Flag::Create, dev.dev_eui),
-                    ValidateDeviceQueueAccess::new(Flag::List, dev.dev_eui),
-                    ValidateDeviceQueueAccess::new(Flag::Delete, dev.dev_eui),
+                    ValidateApplicationUsersAccess::new(Flag::Create, app_a.id),
+                    ValidateApplicationUsersAccess::new(Flag::List, app_a.id),
                 ],
-                id: AuthID::Key(api_key_admin.id),
+                id: AuthID::User(app_admin.id),
+                ok: true,
+            },
+            ValidatorTest {
+                validators: vec![ValidateApplicationUsersAccess::new(Flag::List, app_a.id)],
+                id: AuthID::User(member.id),
                 ok: true,
             },
-            // tenant api key can create, list and delete
             ValidatorTest {
                 validators: vec![
-                    ValidateDeviceQueueAccess::new(Flag::Create, dev.dev_eui),
-                    ValidateDeviceQueueAccess::new(Flag::List, dev.dev_eui),
-                    ValidateDeviceQueueAccess::new(Flag::Delete, dev.dev_eui),
+                    ValidateApplicationUsersAccess::new(Flag::Create, app_a.id),
+                    ValidateApplicationUsersAccess::new(Flag::Create, app_b.id),
+                ],
+                id: AuthID::User(member.id),
+                ok: false,
+            },
+            ValidatorTest {
+                validators: vec![
+                    ValidateApplicationUsersAccess::new(Flag::Create, app_a.id),
+                    ValidateApplicationUsersAccess::new(Flag::List, app_a.id),
                 ],
                 id: AuthID::Key(api_key_tenant.id),
                 ok: true,
             },
-            // api key for other tenant cna not create, list or delete
+        ])
+        .await;
+
+        run_tests(vec![
+            ValidatorTest {
+                validators: vec![ValidateApplicationUserAccess::new(
+                    Flag::Read,
+                    app_a.id,
+                    member.id,
+                )],
+                id: AuthID::User(member.id),
+                ok: true,
+            },
             ValidatorTest {
                 validators: vec![
-                    ValidateDeviceQueueAccess::new(Flag::Create, dev.dev_eui),
-                    ValidateDeviceQueueAccess::new(Flag::List, dev.dev_eui),
-                    ValidateDeviceQueueAccess::new(Flag::Delete, dev.dev_eui),
+                    ValidateApplicationUserAccess::new(Flag::Read, app_a.id, app_admin.id),
+                    ValidateApplicationUserAccess::new(Flag::Update, app_a.id, member.id),
+                    ValidateApplicationUserAccess::new(Flag::Delete, app_a.id, member.id),
                 ],
-                id: AuthID::Key(api_key_other_tenant.id),
+                id: AuthID::User(member.id),
                 ok: false,
             },
-        ];
-        run_tests(tests).await;
+            ValidatorTest {
+                validators: vec![
+                    ValidateApplicationUserAccess::new(Flag::Read, app_a.id, member.id),
+                    ValidateApplicationUserAccess::new(Flag::Update, app_a.id, member.id),
+                    ValidateApplicationUserAccess::new(Flag::Delete, app_a.id, member.id),
+                ],
+                id: AuthID::User(app_admin.id),
+                ok: true,
+            },
+        ])
+        .await;
     }
 
     #[tokio::test]
@@ -9,9 +9,10 @@ use tonic::{Request, Response, Status};
 use uuid::Uuid;
 
 use chirpstack_api::api::device_service_server::DeviceService;
-use chirpstack_api::{api, common, internal};
+use chirpstack_api::{api, common, integration as integration_pb, internal};
 use lrwn::{AES128Key, DevAddr, EUI64};
 
+use super::audit;
 use super::auth::validator;
 use super::error::ToStatus;
 use super::helpers::{self, FromProto, ToProto};
@@ -19,9 +20,11 @@ use crate::storage::{
     device::{self, DeviceClass},
     device_keys, device_profile, device_queue,
     error::Error as StorageError,
-    fields, metrics,
+    fields,
+    helpers::get_all_device_data,
+    metrics,
 };
-use crate::{codec, devaddr::get_random_dev_addr};
+use crate::{codec, devaddr::get_random_dev_addr, downlink, integration};
 
 pub struct Device {
     validator: validator::RequestValidator,
@@ -76,7 +79,7 @@ impl DeviceService for Device {
             ..Default::default()
         };
 
-        let _ = device::create(d).await.map_err(|e| e.status())?;
+        let d = device::create(d).await.map_err(|e| e.status())?;
 
         let mut resp = Response::new(());
         resp.metadata_mut()
@@ -85,6 +88,8 @@ impl DeviceService for Device {
             "x-log-is_disabled",
             req_d.is_disabled.to_string().parse().unwrap(),
         );
+        resp.extensions_mut()
+            .insert(audit::Changes::new(None, Some(&d)));
 
         Ok(resp)
     }
@@ -188,8 +193,10 @@ impl DeviceService for Device {
             )
             .await?;
 
+        let d_before = device::get(&dev_eui).await.map_err(|e| e.status())?;
+
         // update
-        let _ = device::update(device::Device {
+        let d = device::update(device::Device {
             dev_eui,
             application_id: app_id,
             device_profile_id: dp_id,
@@ -212,6 +219,8 @@ impl DeviceService for Device {
             "x-log-is_disabled",
             req_d.is_disabled.to_string().parse().unwrap(),
         );
+        resp.extensions_mut()
+            .insert(audit::Changes::new(Some(&d_before), Some(&d)));
 
         Ok(resp)
     }
@@ -230,11 +239,40 @@ impl DeviceService for Device {
             )
             .await?;
 
+        let d = device::get(&dev_eui).await.map_err(|e| e.status())?;
         device::delete(&dev_eui).await.map_err(|e| e.status())?;
 
         let mut resp = Response::new(());
         resp.metadata_mut()
             .insert("x-log-dev_eui", req.dev_eui.parse().unwrap());
+        resp.extensions_mut()
+            .insert(audit::Changes::new(Some(&d), None));
+
+        Ok(resp)
+    }
+
+    async fn restore(
+        &self,
+        request: Request<api::RestoreDeviceRequest>,
+    ) -> Result<Response<()>, Status> {
+        let req = request.get_ref();
+        let dev_eui = EUI64::from_str(&req.dev_eui).map_err(|e| e.status())?;
+
+        self.validator
+            .validate(
+                request.extensions(),
+                validator::ValidateDeviceAccess::new(validator::Flag::Delete, dev_eui),
+            )
+            .await?;
+
+        device::restore(&dev_eui).await.map_err(|e| e.status())?;
+        let d = device::get(&dev_eui).await.map_err(|e| e.status())?;
+
+        let mut resp = Response::new(());
+        resp.metadata_mut()
+            .insert("x-log-dev_eui", req.dev_eui.parse().unwrap());
+        resp.extensions_mut()
+            .insert(audit::Changes::new(None, Some(&d)));
 
         Ok(resp)
     }
@@ -275,6 +313,7 @@ impl DeviceService for Device {
             } else {
                 Some(req.search.to_string())
             },
+            deleted: req.deleted,
         };
 
         let count = device::get_count(&filters).await.map_err(|e| e.status())?;
@@ -309,6 +348,10 @@ impl DeviceService for Device {
                         }),
                         false => None,
                     },
+                    deleted_at: d
+                        .deleted_at
+                        .as_ref()
+                        .map(helpers::datetime_to_prost_timestamp),
                 })
                 .collect(),
         });
@@ -348,11 +391,13 @@ impl DeviceService for Device {
             ..Default::default()
         };
 
-        let _ = device_keys::create(dk).await.map_err(|e| e.status())?;
+        let dk = device_keys::create(dk).await.map_err(|e| e.status())?;
 
         let mut resp = Response::new(());
         resp.metadata_mut()
             .insert("x-log-dev_eui", req_dk.dev_eui.parse().unwrap());
+        resp.extensions_mut()
+            .insert(audit::Changes::new(None, Some(&dk)));
 
         Ok(resp)
     }
@@ -367,7 +412,7 @@ impl DeviceService for Device {
         self.validator
             .validate(
                 request.extensions(),
-                validator::ValidateDeviceAccess::new(validator::Flag::Read, dev_eui),
+                validator::ValidateDeviceKeysAccess::new(validator::Flag::Read, dev_eui),
             )
             .await?;
 
@@ -407,12 +452,12 @@ impl DeviceService for Device {
             )
             .await?;
 
-        let dk = device_keys::get(&dev_eui).await.map_err(|e| e.status())?;
+        let dk_before = device_keys::get(&dev_eui).await.map_err(|e| e.status())?;
         let dk = device_keys::DeviceKeys {
-            dev_eui: dk.dev_eui,
-            created_at: dk.created_at,
-            dev_nonces: dk.dev_nonces,
-            join_nonce: dk.join_nonce,
+            dev_eui: dk_before.dev_eui,
+            created_at: dk_before.created_at,
+            dev_nonces: dk_before.dev_nonces.clone(),
+            join_nonce: dk_before.join_nonce,
             nwk_key: AES128Key::from_str(&req_dk.nwk_key).map_err(|e| e.status())?,
             app_key: if !req_dk.app_key.is_empty() {
                 AES128Key::from_str(&req_dk.app_key).map_err(|e| e.status())?
@@ -421,11 +466,13 @@ impl DeviceService for Device {
             },
             ..Default::default()
         };
-        let _ = device_keys::update(dk).await.map_err(|e| e.status())?;
+        let dk = device_keys::update(dk).await.map_err(|e| e.status())?;
 
         let mut resp = Response::new(());
         resp.metadata_mut()
             .insert("x-log-dev_eui", req_dk.dev_eui.parse().unwrap());
+        resp.extensions_mut()
+            .insert(audit::Changes::new(Some(&dk_before), Some(&dk)));
 
         Ok(resp)
     }
@@ -444,6 +491,7 @@ impl DeviceService for Device {
             )
             .await?;
 
+        let dk = device_keys::get(&dev_eui).await.map_err(|e| e.status())?;
         device_keys::delete(&dev_eui)
             .await
             .map_err(|e| e.status())?;
@@ -451,6 +499,8 @@ impl DeviceService for Device {
         let mut resp = Response::new(());
         resp.metadata_mut()
             .insert("x-log-dev_eui", req.dev_eui.parse().unwrap());
+        resp.extensions_mut()
+            .insert(audit::Changes::new(Some(&dk), None));
 
         Ok(resp)
     }
@@ -611,7 +661,7 @@ impl DeviceService for Device {
         self.validator
             .validate(
                 request.extensions(),
-                validator::ValidateDeviceAccess::new(validator::Flag::Read, dev_eui),
+                validator::ValidateDeviceKeysAccess::new(validator::Flag::Read, dev_eui),
             )
             .await?;
 
@@ -1069,43 +1119,68 @@ impl DeviceService for Device {
             )
             .await?;
 
-        let mut data = req_qi.data.clone();
+        let (dev, app, t, dp) = get_all_device_data(dev_eui).await.map_err(|e| e.status())?;
+
+        let id = Uuid::new_v4();
+        let mut event = integration_pb::DownlinkEvent {
+            time: Some(Utc::now().into()),
+            device_info: Some(integration::device_info(&t, &app, &dp, &dev)),
+            queue_item_id: id.to_string(),
+            correlation_id: req_qi.correlation_id.clone(),
+            f_port: req_qi.f_port,
+            confirmed: req_qi.confirmed,
+            ..Default::default()
+        };
 
-        if let Some(obj) = &req_qi.object {
-            let dev = device::get(&dev_eui).await.map_err(|e| e.status())?;
-            let dp = device_profile::get(&dev.device_profile_id)
+        let res = async {
+            let mut data = req_qi.data.clone();
+
+            if let Some(obj) = &req_qi.object {
+                data = codec::struct_to_binary(
+                    dp.payload_codec_runtime,
+                    req_qi.f_port as u8,
+                    &dev.variables,
+                    &dp.payload_codec_script,
+                    obj,
+                )
                 .await
                 .map_err(|e| e.status())?;
+            }
 
-            data = codec::struct_to_binary(
-                dp.payload_codec_runtime,
-                req_qi.f_port as u8,
-                &dev.variables,
-                &dp.payload_codec_script,
-                obj,
-            )
-            .await
-            .map_err(|e| e.status())?;
+            downlink::validate_payload_size(&dev, &dp, data.len()).map_err(|e| e.status())?;
+
+            let qi = device_queue::DeviceQueueItem {
+                id,
+                dev_eui,
+                f_port: req_qi.f_port as i16,
+                confirmed: req_qi.confirmed,
+                is_encrypted: req_qi.is_encrypted,
+                f_cnt_down: if req_qi.is_encrypted {
+                    Some(req_qi.f_cnt_down.into())
+                } else {
+                    None
+                },
+                data,
+                correlation_id: req_qi.correlation_id.clone(),
+                ..Default::default()
+            };
+
+            device_queue::enqueue_item(qi).await.map_err(|e| e.status())
         }
+        .await;
 
-        let qi = device_queue::DeviceQueueItem {
-            id: Uuid::new_v4(),
-            dev_eui,
-            f_port: req_qi.f_port as i16,
-            confirmed: req_qi.confirmed,
-            is_encrypted: req_qi.is_encrypted,
-            f_cnt_down: if req_qi.is_encrypted {
-                Some(req_qi.f_cnt_down.into())
-            } else {
-                None
-            },
-            data,
-            ..Default::default()
-        };
+        match &res {
+            Ok(_) => {
+                event.state = integration_pb::DownlinkState::Enqueued.into();
+            }
+            Err(e) => {
+                event.state = integration_pb::DownlinkState::Rejected.into();
+                event.reason = e.message().to_string();
+            }
+        }
+        integration::downlink_event(app.id, &dev.variables, &event).await;
 
-        let qi = device_queue::enqueue_item(qi)
-            .await
-            .map_err(|e| e.status())?;
+        let qi = res?;
 
         let mut resp = Response::new(api::EnqueueDeviceQueueItemResponse {
             id: qi.id.to_string(),
@@ -1173,6 +1248,7 @@ impl DeviceService for Device {
                     is_pending: qi.is_pending,
                     f_cnt_down: qi.f_cnt_down.unwrap_or(0) as u32,
                     is_encrypted: qi.is_encrypted,
+                    correlation_id: qi.correlation_id.clone(),
                 })
                 .collect(),
         });
@@ -1228,6 +1304,7 @@ pub mod test {
     use crate::storage::{application, device, tenant, user};
     use crate::test;
     use lrwn::NetID;
+    use tokio::time::{sleep, Duration};
 
     #[tokio::test]
     async fn test_device() {
@@ -1354,6 +1431,7 @@ pub mod test {
                 multicast_group_id: "".into(),
                 limit: 10,
                 offset: 0,
+                deleted: false,
             },
         );
         let list_resp = service.list(list_req).await.unwrap();
@@ -1623,6 +1701,9 @@ pub mod test {
         assert_eq!(dev_addr, dev_addr_copy);
 
         // enqueue
+        integration::set_mock().await;
+        integration::mock::reset().await;
+
         let enqueue_req = get_request(
             &u.id,
             api::EnqueueDeviceQueueItemRequest {
@@ -1631,6 +1712,7 @@ pub mod test {
                     confirmed: true,
                     f_port: 2,
                     data: vec![3, 2, 1],
+                    correlation_id: "my-correlation-id".into(),
                     ..Default::default()
                 }),
             },
@@ -1653,6 +1735,44 @@ pub mod test {
         );
         let _ = service.enqueue(enqueue_req).await.unwrap();
 
+        // enqueue exceeding max. payload size
+        let enqueue_req = get_request(
+            &u.id,
+            api::EnqueueDeviceQueueItemRequest {
+                queue_item: Some(api::DeviceQueueItem {
+                    dev_eui: "0102030405060708".into(),
+                    f_port: 2,
+                    data: vec![0; 250],
+                    ..Default::default()
+                }),
+            },
+        );
+        let err = service.enqueue(enqueue_req).await.unwrap_err();
+        assert_eq!(tonic::Code::InvalidArgument, err.code());
+
+        // downlink events, integrations are handled async
+        sleep(Duration::from_millis(100)).await;
+        let events = integration::mock::get_downlink_events().await;
+        assert_eq!(3, events.len());
+        let enqueued: Vec<&integration_pb::DownlinkEvent> = events
+            .iter()
+            .filter(|e| e.state() == integration_pb::DownlinkState::Enqueued)
+            .collect();
+        assert_eq!(2, enqueued.len());
+        assert!(enqueued
+            .iter()
+            .any(|e| e.correlation_id == "my-correlation-id" && e.confirmed && e.f_port == 2));
+        let rejected: Vec<&integration_pb::DownlinkEvent> = events
+            .iter()
+            .filter(|e| e.state() == integration_pb::DownlinkState::Rejected)
+            .collect();
+        assert_eq!(1, rejected.len());
+        assert_ne!("", rejected[0].reason);
+        assert_eq!(
+            "0102030405060708",
+            rejected[0].device_info.as_ref().unwrap().dev_eui
+        );
+
         // get queue
         let get_queue_req = get_request(
             &u.id,
@@ -1668,6 +1788,7 @@ pub mod test {
         assert_eq!(vec![3, 2, 1], get_queue_resp.result[0].data);
         assert_eq!(false, get_queue_resp.result[0].is_encrypted);
         assert_eq!(0, get_queue_resp.result[0].f_cnt_down);
+        assert_eq!("my-correlation-id", get_queue_resp.result[0].correlation_id);
         assert_eq!(vec![1, 2, 3], get_queue_resp.result[1].data);
         assert_eq!(true, get_queue_resp.result[1].is_encrypted);
         assert_eq!(10, get_queue_resp.result[1].f_cnt_down);
@@ -1722,6 +1843,37 @@ pub mod test {
         );
         let del_resp = service.delete(del_req).await;
         assert!(del_resp.is_err());
+
+        // list deleted
+        let list_req = get_request(
+            &u.id,
+            api::ListDevicesRequest {
+                application_id: app.id.to_string(),
+                limit: 10,
+                deleted: true,
+                ..Default::default()
+            },
+        );
+        let list_resp = service.list(list_req).await.unwrap();
+        assert_eq!(1, list_resp.get_ref().total_count);
+        assert!(list_resp.get_ref().result[0].deleted_at.is_some());
+
+        // restore
+        let restore_req = get_request(
+            &u.id,
+            api::RestoreDeviceRequest {
+                dev_eui: "0102030405060708".into(),
+            },
+        );
+        let _ = service.restore(restore_req).await.unwrap();
+
+        let get_req = get_request(
+            &u.id,
+            api::GetDeviceRequest {
+                dev_eui: "0102030405060708".into(),
+            },
+        );
+        assert!(service.get(get_req).await.is_ok());
     }
 
     fn get_request<T>(user_id: &Uuid, req: T) -> Request<T> {
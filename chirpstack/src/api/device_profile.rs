@@ -6,6 +6,7 @@ use uuid::Uuid;
 use chirpstack_api::api;
 use chirpstack_api::api::device_profile_service_server::DeviceProfileService;
 
+use super::audit;
 use super::auth::validator;
 use super::error::ToStatus;
 use super::helpers;
@@ -114,6 +115,7 @@ impl DeviceProfileService for DeviceProfile {
             relay_overall_limit_bucket_size: req_dp.relay_overall_limit_bucket_size as i16,
             allow_roaming: req_dp.allow_roaming,
             rx1_delay: req_dp.rx1_delay as i16,
+            lr_fhss_rx_window: req_dp.lr_fhss_rx_window as i16,
             ..Default::default()
         };
 
@@ -126,6 +128,8 @@ impl DeviceProfileService for DeviceProfile {
             "x-log-device_profile_id",
             dp.id.to_string().parse().unwrap(),
         );
+        resp.extensions_mut()
+            .insert(audit::Changes::new(None, Some(&dp)));
 
         Ok(resp)
     }
@@ -216,6 +220,7 @@ impl DeviceProfileService for DeviceProfile {
                 relay_overall_limit_bucket_size: dp.relay_overall_limit_bucket_size as u32,
                 allow_roaming: dp.allow_roaming,
                 rx1_delay: dp.rx1_delay as u32,
+                lr_fhss_rx_window: dp.lr_fhss_rx_window as u32,
             }),
             created_at: Some(helpers::datetime_to_prost_timestamp(&dp.created_at)),
             updated_at: Some(helpers::datetime_to_prost_timestamp(&dp.updated_at)),
@@ -245,8 +250,10 @@ impl DeviceProfileService for DeviceProfile {
             )
             .await?;
 
+        let dp_before = device_profile::get(&dp_id).await.map_err(|e| e.status())?;
+
         // update
-        let _ = device_profile::update(device_profile::DeviceProfile {
+        let dp = device_profile::update(device_profile::DeviceProfile {
             id: dp_id,
             name: req_dp.name.clone(),
             description: req_dp.description.clone(),
@@ -316,6 +323,7 @@ impl DeviceProfileService for DeviceProfile {
             relay_overall_limit_bucket_size: req_dp.relay_overall_limit_bucket_size as i16,
             allow_roaming: req_dp.allow_roaming,
             rx1_delay: req_dp.rx1_delay as i16,
+            lr_fhss_rx_window: req_dp.lr_fhss_rx_window as i16,
             ..Default::default()
         })
         .await
@@ -324,6 +332,8 @@ impl DeviceProfileService for DeviceProfile {
         let mut resp = Response::new(());
         resp.metadata_mut()
             .insert("x-log-device_profile_id", req_dp.id.parse().unwrap());
+        resp.extensions_mut()
+            .insert(audit::Changes::new(Some(&dp_before), Some(&dp)));
 
         Ok(resp)
     }
@@ -342,6 +352,7 @@ impl DeviceProfileService for DeviceProfile {
             )
             .await?;
 
+        let dp = device_profile::get(&dp_id).await.map_err(|e| e.status())?;
         device_profile::delete(&dp_id)
             .await
             .map_err(|e| e.status())?;
@@ -349,6 +360,8 @@ impl DeviceProfileService for DeviceProfile {
         let mut resp = Response::new(());
         resp.metadata_mut()
             .insert("x-log-device_profile_id", req.id.parse().unwrap());
+        resp.extensions_mut()
+            .insert(audit::Changes::new(Some(&dp), None));
 
         Ok(resp)
     }
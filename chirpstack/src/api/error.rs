@@ -22,6 +22,12 @@ impl ToStatus for storage::error::Error {
             storage::error::Error::InvalidUsernameOrPassword => {
                 Status::new(Code::Unauthenticated, format!("{:#}", self))
             }
+            storage::error::Error::UserLocked => {
+                Status::new(Code::Unauthenticated, format!("{:#}", self))
+            }
+            storage::error::Error::InvalidMfaCode => {
+                Status::new(Code::Unauthenticated, format!("{:#}", self))
+            }
             storage::error::Error::InvalidPayload(_) => {
                 Status::new(Code::Internal, format!("{:#}", self))
             }
@@ -8,8 +8,9 @@ use uuid::Uuid;
 
 use chirpstack_api::api::gateway_service_server::GatewayService;
 use chirpstack_api::{api, common};
-use lrwn::EUI64;
+use lrwn::{AES128Key, EUI64};
 
+use super::audit;
 use super::auth::validator;
 use super::error::ToStatus;
 use super::helpers::{self, FromProto};
@@ -62,14 +63,17 @@ impl GatewayService for Gateway {
             altitude: alt,
             tags: fields::KeyValue::new(req_gw.tags.clone()),
             stats_interval_secs: req_gw.stats_interval as i32,
+            fine_timestamp_key: get_fine_timestamp_key(&req_gw.fine_timestamp_key)?,
             ..Default::default()
         };
 
-        let _ = gateway::create(gw).await.map_err(|e| e.status())?;
+        let gw = gateway::create(gw).await.map_err(|e| e.status())?;
 
         let mut resp = Response::new(());
         resp.metadata_mut()
             .insert("x-log-gateway_id", req_gw.gateway_id.parse().unwrap());
+        resp.extensions_mut()
+            .insert(audit::Changes::new(None, Some(&gw)));
 
         Ok(resp)
     }
@@ -90,6 +94,17 @@ impl GatewayService for Gateway {
 
         let gw = gateway::get(&gw_id).await.map_err(|e| e.status())?;
 
+        // The fine-timestamp key is only returned to users that are allowed to update the gateway
+        // (and thus are able to overwrite the key).
+        let can_update = self
+            .validator
+            .validate(
+                request.extensions(),
+                validator::ValidateGatewayAccess::new(validator::Flag::Update, gw_id),
+            )
+            .await
+            .is_ok();
+
         let mut resp = Response::new(api::GetGatewayResponse {
             gateway: Some(api::Gateway {
                 gateway_id: gw.gateway_id.to_string(),
@@ -105,6 +120,11 @@ impl GatewayService for Gateway {
                 tags: gw.tags.into_hashmap(),
                 metadata: gw.properties.into_hashmap(),
                 stats_interval: gw.stats_interval_secs as u32,
+                fine_timestamp_key: gw
+                    .fine_timestamp_key
+                    .filter(|_| can_update)
+                    .map(|k| k.to_string())
+                    .unwrap_or_default(),
             }),
             created_at: Some(helpers::datetime_to_prost_timestamp(&gw.created_at)),
             updated_at: Some(helpers::datetime_to_prost_timestamp(&gw.updated_at)),
@@ -143,8 +163,10 @@ impl GatewayService for Gateway {
             None => (0.0, 0.0, 0.0),
         };
 
+        let gw_before = gateway::get(&gw_id).await.map_err(|e| e.status())?;
+
         // update
-        let _ = gateway::update(gateway::Gateway {
+        let gw = gateway::update(gateway::Gateway {
             gateway_id: gw_id,
             name: req_gw.name.clone(),
             description: req_gw.description.clone(),
@@ -153,6 +175,7 @@ impl GatewayService for Gateway {
             altitude: alt,
             tags: fields::KeyValue::new(req_gw.tags.clone()),
             stats_interval_secs: req_gw.stats_interval as i32,
+            fine_timestamp_key: get_fine_timestamp_key(&req_gw.fine_timestamp_key)?,
             ..Default::default()
         })
         .await
@@ -161,6 +184,8 @@ impl GatewayService for Gateway {
         let mut resp = Response::new(());
         resp.metadata_mut()
             .insert("x-log-gateway_id", req_gw.gateway_id.parse().unwrap());
+        resp.extensions_mut()
+            .insert(audit::Changes::new(Some(&gw_before), Some(&gw)));
 
         Ok(resp)
     }
@@ -179,11 +204,40 @@ impl GatewayService for Gateway {
             )
             .await?;
 
+        let gw = gateway::get(&gw_id).await.map_err(|e| e.status())?;
         gateway::delete(&gw_id).await.map_err(|e| e.status())?;
 
         let mut resp = Response::new(());
         resp.metadata_mut()
             .insert("x-log-gateway_id", req.gateway_id.parse().unwrap());
+        resp.extensions_mut()
+            .insert(audit::Changes::new(Some(&gw), None));
+
+        Ok(resp)
+    }
+
+    async fn restore(
+        &self,
+        request: Request<api::RestoreGatewayRequest>,
+    ) -> Result<Response<()>, Status> {
+        let req = request.get_ref();
+        let gw_id = EUI64::from_str(&req.gateway_id).map_err(|e| e.status())?;
+
+        self.validator
+            .validate(
+                request.extensions(),
+                validator::ValidateGatewayAccess::new(validator::Flag::Delete, gw_id),
+            )
+            .await?;
+
+        gateway::restore(&gw_id).await.map_err(|e| e.status())?;
+        let gw = gateway::get(&gw_id).await.map_err(|e| e.status())?;
+
+        let mut resp = Response::new(());
+        resp.metadata_mut()
+            .insert("x-log-gateway_id", req.gateway_id.parse().unwrap());
+        resp.extensions_mut()
+            .insert(audit::Changes::new(None, Some(&gw)));
 
         Ok(resp)
     }
@@ -231,6 +285,7 @@ impl GatewayService for Gateway {
             } else {
                 Some(req.search.to_string())
             },
+            deleted: req.deleted,
         };
 
         let count = gateway::get_count(&filters).await.map_err(|e| e.status())?;
@@ -275,6 +330,10 @@ impl GatewayService for Gateway {
                         }
                     }
                     .into(),
+                    deleted_at: gw
+                        .deleted_at
+                        .as_ref()
+                        .map(helpers::datetime_to_prost_timestamp),
                 })
                 .collect(),
         });
@@ -785,6 +844,14 @@ impl GatewayService for Gateway {
     }
 }
 
+fn get_fine_timestamp_key(key: &str) -> Result<Option<AES128Key>, Status> {
+    if key.is_empty() {
+        return Ok(None);
+    }
+
+    Ok(Some(AES128Key::from_str(key).map_err(|e| e.status())?))
+}
+
 #[cfg(test)]
 pub mod test {
     use chrono::{Datelike, Local, TimeZone, Timelike};
@@ -880,6 +947,7 @@ pub mod test {
                     altitude: 2.0,
                     ..Default::default()
                 }),
+                fine_timestamp_key: "01020304050607080102030405060708".into(),
                 ..Default::default()
             }),
         };
@@ -905,11 +973,44 @@ pub mod test {
                     altitude: 2.0,
                     ..Default::default()
                 }),
+                fine_timestamp_key: "01020304050607080102030405060708".into(),
                 ..Default::default()
             }),
             get_resp.get_ref().gateway
         );
 
+        // get as (non gateway admin) tenant user, the fine-timestamp key is not returned
+        let tu = user::create(user::User {
+            is_active: true,
+            email: "user@user".into(),
+            email_verified: true,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        tenant::add_user(tenant::TenantUser {
+            tenant_id: t.id,
+            user_id: tu.id,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let get_req = api::GetGatewayRequest {
+            gateway_id: "0102030405060708".into(),
+        };
+        let mut get_req = Request::new(get_req);
+        get_req.extensions_mut().insert(AuthID::User(tu.id));
+        let get_resp = service.get(get_req).await.unwrap();
+        assert_eq!(
+            "",
+            get_resp
+                .get_ref()
+                .gateway
+                .as_ref()
+                .unwrap()
+                .fine_timestamp_key
+        );
+
         // list
         let list_req = api::ListGatewaysRequest {
             search: "updated".into(),
@@ -939,6 +1040,36 @@ pub mod test {
         del_req.extensions_mut().insert(AuthID::User(u.id.clone()));
         let del_resp = service.delete(del_req).await;
         assert!(del_resp.is_err());
+
+        // list deleted
+        let list_req = api::ListGatewaysRequest {
+            tenant_id: t.id.to_string(),
+            limit: 10,
+            deleted: true,
+            ..Default::default()
+        };
+        let mut list_req = Request::new(list_req);
+        list_req.extensions_mut().insert(AuthID::User(u.id.clone()));
+        let list_resp = service.list(list_req).await.unwrap();
+        assert_eq!(1, list_resp.get_ref().total_count);
+        assert!(list_resp.get_ref().result[0].deleted_at.is_some());
+
+        // restore
+        let restore_req = api::RestoreGatewayRequest {
+            gateway_id: "0102030405060708".into(),
+        };
+        let mut restore_req = Request::new(restore_req);
+        restore_req
+            .extensions_mut()
+            .insert(AuthID::User(u.id.clone()));
+        let _ = service.restore(restore_req).await.unwrap();
+
+        let get_req = api::GetGatewayRequest {
+            gateway_id: "0102030405060708".into(),
+        };
+        let mut get_req = Request::new(get_req);
+        get_req.extensions_mut().insert(AuthID::User(u.id.clone()));
+        assert!(service.get(get_req).await.is_ok());
     }
 
     #[tokio::test]
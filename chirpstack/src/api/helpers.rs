@@ -1,8 +1,9 @@
 use chrono::{DateTime, Utc};
 
 use crate::codec::Codec;
+use crate::storage::api_key::Permission;
 use crate::storage::fields::{MeasurementKind, MulticastGroupSchedulingType};
-use crate::storage::{device::DeviceClass, metrics::Aggregation};
+use crate::storage::{application, device::DeviceClass, metrics::Aggregation, tenant_role};
 use chirpstack_api::{api, common};
 use lrwn::region::{CommonName, MacVersion, Revision};
 
@@ -263,6 +264,108 @@ impl ToProto<common::DeviceClass> for DeviceClass {
     }
 }
 
+impl ToProto<api::ApiKeyPermission> for Permission {
+    fn to_proto(self) -> api::ApiKeyPermission {
+        match self {
+            Permission::Read => api::ApiKeyPermission::Read,
+            Permission::Write => api::ApiKeyPermission::Write,
+            Permission::Enqueue => api::ApiKeyPermission::Enqueue,
+            Permission::ReadKeys => api::ApiKeyPermission::KeysRead,
+        }
+    }
+}
+
+impl FromProto<Permission> for api::ApiKeyPermission {
+    fn from_proto(self) -> Permission {
+        match self {
+            api::ApiKeyPermission::Read => Permission::Read,
+            api::ApiKeyPermission::Write => Permission::Write,
+            api::ApiKeyPermission::Enqueue => Permission::Enqueue,
+            api::ApiKeyPermission::KeysRead => Permission::ReadKeys,
+        }
+    }
+}
+
+impl ToProto<api::TenantRolePermission> for tenant_role::Permission {
+    fn to_proto(self) -> api::TenantRolePermission {
+        match self {
+            tenant_role::Permission::ApplicationWrite => {
+                api::TenantRolePermission::ApplicationWrite
+            }
+            tenant_role::Permission::IntegrationWrite => {
+                api::TenantRolePermission::IntegrationWrite
+            }
+            tenant_role::Permission::DeviceWrite => api::TenantRolePermission::DeviceWrite,
+            tenant_role::Permission::Enqueue => api::TenantRolePermission::QueueWrite,
+            tenant_role::Permission::DeviceProfileWrite => {
+                api::TenantRolePermission::DeviceProfileWrite
+            }
+            tenant_role::Permission::GatewayWrite => api::TenantRolePermission::GatewayWrite,
+            tenant_role::Permission::MulticastGroupWrite => {
+                api::TenantRolePermission::MulticastGroupWrite
+            }
+        }
+    }
+}
+
+impl FromProto<tenant_role::Permission> for api::TenantRolePermission {
+    fn from_proto(self) -> tenant_role::Permission {
+        match self {
+            api::TenantRolePermission::ApplicationWrite => {
+                tenant_role::Permission::ApplicationWrite
+            }
+            api::TenantRolePermission::IntegrationWrite => {
+                tenant_role::Permission::IntegrationWrite
+            }
+            api::TenantRolePermission::DeviceWrite => tenant_role::Permission::DeviceWrite,
+            api::TenantRolePermission::QueueWrite => tenant_role::Permission::Enqueue,
+            api::TenantRolePermission::DeviceProfileWrite => {
+                tenant_role::Permission::DeviceProfileWrite
+            }
+            api::TenantRolePermission::GatewayWrite => tenant_role::Permission::GatewayWrite,
+            api::TenantRolePermission::MulticastGroupWrite => {
+                tenant_role::Permission::MulticastGroupWrite
+            }
+        }
+    }
+}
+
+impl ToProto<api::IntegrationKind> for application::IntegrationKind {
+    fn to_proto(self) -> api::IntegrationKind {
+        match self {
+            application::IntegrationKind::Http => api::IntegrationKind::Http,
+            application::IntegrationKind::InfluxDb => api::IntegrationKind::InfluxDb,
+            application::IntegrationKind::ThingsBoard => api::IntegrationKind::ThingsBoard,
+            application::IntegrationKind::MyDevices => api::IntegrationKind::MyDevices,
+            application::IntegrationKind::LoraCloud => api::IntegrationKind::LoraCloud,
+            application::IntegrationKind::GcpPubSub => api::IntegrationKind::GcpPubSub,
+            application::IntegrationKind::AwsSns => api::IntegrationKind::AwsSns,
+            application::IntegrationKind::AzureServiceBus => api::IntegrationKind::AzureServiceBus,
+            application::IntegrationKind::PilotThings => api::IntegrationKind::PilotThings,
+            application::IntegrationKind::Ifttt => api::IntegrationKind::Ifttt,
+        }
+    }
+}
+
+// The global MQTT integration is not an application integration, in which case None is returned.
+impl FromProto<Option<application::IntegrationKind>> for api::IntegrationKind {
+    fn from_proto(self) -> Option<application::IntegrationKind> {
+        Some(match self {
+            api::IntegrationKind::Http => application::IntegrationKind::Http,
+            api::IntegrationKind::InfluxDb => application::IntegrationKind::InfluxDb,
+            api::IntegrationKind::ThingsBoard => application::IntegrationKind::ThingsBoard,
+            api::IntegrationKind::MyDevices => application::IntegrationKind::MyDevices,
+            api::IntegrationKind::LoraCloud => application::IntegrationKind::LoraCloud,
+            api::IntegrationKind::GcpPubSub => application::IntegrationKind::GcpPubSub,
+            api::IntegrationKind::AwsSns => application::IntegrationKind::AwsSns,
+            api::IntegrationKind::AzureServiceBus => application::IntegrationKind::AzureServiceBus,
+            api::IntegrationKind::PilotThings => application::IntegrationKind::PilotThings,
+            api::IntegrationKind::Ifttt => application::IntegrationKind::Ifttt,
+            api::IntegrationKind::MqttGlobal => return None,
+        })
+    }
+}
+
 pub fn datetime_to_prost_timestamp(dt: &DateTime<Utc>) -> prost_types::Timestamp {
     let ts = dt.timestamp_nanos_opt().unwrap_or_default();
 
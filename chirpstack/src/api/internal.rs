@@ -1,9 +1,10 @@
 use std::pin::Pin;
 use std::str::FromStr;
 use std::task::{Context, Poll};
-use std::time::Duration;
+use std::time::{Duration, SystemTime};
 
 use anyhow::{Context as AnyhowContext, Result};
+use chrono::{DateTime, Utc};
 use futures::Stream;
 use reqwest::header::{HeaderMap, CONTENT_TYPE};
 use reqwest::Client;
@@ -14,33 +15,81 @@ use tonic::{Request, Response, Status};
 use tracing::{debug, error, trace};
 use uuid::Uuid;
 
-use chirpstack_api::api;
 use chirpstack_api::api::internal_service_server::InternalService;
+use chirpstack_api::{api, common};
 
-use super::auth::claims;
+use super::auth::{claims, keys};
 use super::auth::{validator, AuthID};
 use super::error::ToStatus;
-use super::helpers::ToProto;
-use super::{helpers, oauth2, oidc};
-use crate::storage::{api_key, device, error::Error, gateway, redis_key, search, tenant, user};
-use crate::{config, region, stream};
+use super::helpers::{FromProto, ToProto};
+use super::{audit, helpers, oauth2, oidc};
+use crate::helpers::errors::PrintFullError;
+use crate::storage::{
+    api_key, application, audit_log, device, device_profile, error::Error, gateway, redis_key,
+    search, tenant, user,
+};
+use crate::{codec, config, frame_decoder, region, stream};
 use lrwn::EUI64;
 
 pub struct Internal {
     validator: validator::RequestValidator,
-    jwt_secret: String,
 }
 
 impl Internal {
-    pub fn new(validator: validator::RequestValidator, jwt_secret: String) -> Self {
-        Internal {
-            validator,
-            jwt_secret,
+    pub fn new(validator: validator::RequestValidator) -> Self {
+        Internal { validator }
+    }
+
+    // Authenticates the user using the e-mail, password and TOTP or recovery code (if enabled).
+    async fn login_user(&self, req: &api::LoginRequest) -> Result<api::LoginResponse, Status> {
+        let u = user::get_by_email_and_pw(&req.email, &req.password)
+            .await
+            .map_err(|e| e.status())?;
+
+        if u.totp_enabled {
+            let res = if !req.totp_code.is_empty() {
+                user::verify_totp(&u, &req.totp_code).await
+            } else if !req.recovery_code.is_empty() {
+                user::use_recovery_code(&u.id, &req.recovery_code).await
+            } else {
+                return Ok(api::LoginResponse {
+                    jwt: "".into(),
+                    mfa_required: true,
+                });
+            };
+
+            if let Err(e) = res {
+                if matches!(e, Error::InvalidMfaCode) {
+                    user::register_failed_login(&u.id)
+                        .await
+                        .map_err(|e| e.status())?;
+                }
+                return Err(e.status());
+            }
+
+            if u.failed_login_count != 0 || u.locked_until.is_some() {
+                user::reset_failed_logins(&u.id)
+                    .await
+                    .map_err(|e| e.status())?;
+            }
         }
+
+        let token = claims::AuthClaim::new_for_user(&u.id)
+            .encode(&keys::get())
+            .map_err(|e| e.status())?;
+
+        Ok(api::LoginResponse {
+            jwt: token,
+            mfa_required: false,
+        })
     }
 
+    // Creates the user and calls the registration callback URL (if configured). The user is
+    // removed again in case the callback fails, which allows the callback to reject the
+    // registration.
     async fn create_and_provision_user<S>(
         &self,
+        registration_callback_url: &str,
         external_id: &str,
         email: &str,
         email_verified: bool,
@@ -57,7 +106,10 @@ impl Internal {
             ..Default::default()
         };
         let mut u = user::create(u).await?;
-        if let Err(e) = self.provision_user(&u.id, user_info).await {
+        if let Err(e) = self
+            .provision_user(registration_callback_url, &u.id, user_info)
+            .await
+        {
             error!(error = %e, "Provisioning user failed");
             user::delete(&u.id).await?;
             return Err(e);
@@ -69,17 +121,16 @@ impl Internal {
         Ok(u)
     }
 
-    async fn provision_user<S>(&self, user_id: &Uuid, user_info: &S) -> Result<()>
+    async fn provision_user<S>(
+        &self,
+        registration_callback_url: &str,
+        user_id: &Uuid,
+        user_info: &S,
+    ) -> Result<()>
     where
         S: Serialize,
     {
-        let conf = config::get();
-        if conf
-            .user_authentication
-            .openid_connect
-            .registration_callback_url
-            .is_empty()
-        {
+        if registration_callback_url.is_empty() {
             return Ok(());
         }
 
@@ -88,12 +139,7 @@ impl Internal {
         headers.insert(CONTENT_TYPE, "application/json".parse().unwrap());
 
         let res = client
-            .post(
-                &conf
-                    .user_authentication
-                    .openid_connect
-                    .registration_callback_url,
-            )
+            .post(registration_callback_url)
             .json(user_info)
             .query(&[("user_id", user_id.to_string())])
             .headers(headers)
@@ -147,15 +193,14 @@ impl InternalService for Internal {
         request: Request<api::LoginRequest>,
     ) -> Result<Response<api::LoginResponse>, Status> {
         let req = request.get_ref();
-        let u = user::get_by_email_and_pw(&req.email, &req.password)
-            .await
-            .map_err(|e| e.status())?;
+        let res = self.login_user(req).await;
 
-        let token = claims::AuthClaim::new_for_user(&u.id)
-            .encode(self.jwt_secret.as_ref())
-            .map_err(|e| e.status())?;
+        // Login requests are not authenticated, therefore these are not logged by the ApiLogger.
+        if let Err(e) = audit::log_login(&req.email, &res).await {
+            error!(error = %e.full(), "Audit log error");
+        }
 
-        Ok(Response::new(api::LoginResponse { jwt: token }))
+        res.map(Response::new)
     }
 
     async fn profile(
@@ -309,22 +354,84 @@ impl InternalService for Internal {
             )
             .await?;
 
-        let ak = api_key::ApiKey {
+        let application_id = if req_key.application_id.is_empty() {
+            None
+        } else {
+            let app_id = Uuid::from_str(&req_key.application_id).map_err(|e| e.status())?;
+            let app = application::get(&app_id).await.map_err(|e| e.status())?;
+            if Some(app.tenant_id) != tenant_id {
+                return Err(Status::invalid_argument(
+                    "application_id must be under the given tenant_id",
+                ));
+            }
+            Some(app_id)
+        };
+
+        let device_profile_id = if req_key.device_profile_id.is_empty() {
+            None
+        } else {
+            let dp_id = Uuid::from_str(&req_key.device_profile_id).map_err(|e| e.status())?;
+            let dp = device_profile::get(&dp_id).await.map_err(|e| e.status())?;
+            if Some(dp.tenant_id) != tenant_id {
+                return Err(Status::invalid_argument(
+                    "device_profile_id must be under the given tenant_id",
+                ));
+            }
+            Some(dp_id)
+        };
+
+        let expires_at: Option<DateTime<Utc>> = match &req_key.expires_at {
+            Some(v) => {
+                let ts = SystemTime::try_from(v.clone()).map_err(|e| e.status())?;
+                Some(ts.into())
+            }
+            None => None,
+        };
+        if let Some(v) = &expires_at {
+            if *v <= Utc::now() {
+                return Err(Status::invalid_argument("expires_at must be in the future"));
+            }
+        }
+
+        let mut ak = api_key::ApiKey {
             name: req_key.name.clone(),
             is_admin: req_key.is_admin,
             tenant_id,
+            application_id,
+            device_profile_id,
+            expires_at,
             ..Default::default()
         };
 
+        if !req_key.permissions.is_empty() {
+            ak.can_read = false;
+            ak.can_write = false;
+            ak.can_enqueue = false;
+            ak.can_read_keys = false;
+
+            for p in req_key.permissions() {
+                match p.from_proto() {
+                    api_key::Permission::Read => ak.can_read = true,
+                    api_key::Permission::Write => ak.can_write = true,
+                    api_key::Permission::Enqueue => ak.can_enqueue = true,
+                    api_key::Permission::ReadKeys => ak.can_read_keys = true,
+                }
+            }
+        }
+
         let ak = api_key::create(ak).await.map_err(|e| e.status())?;
-        let token = claims::AuthClaim::new_for_api_key(&ak.id)
-            .encode(self.jwt_secret.as_ref())
+        let token = claims::AuthClaim::new_for_api_key(&ak.id, ak.expires_at)
+            .encode(&keys::get())
             .map_err(|e| e.status())?;
 
-        Ok(Response::new(api::CreateApiKeyResponse {
+        let mut resp = Response::new(api::CreateApiKeyResponse {
             id: ak.id.to_string(),
             token,
-        }))
+        });
+        resp.extensions_mut()
+            .insert(audit::Changes::new(None, Some(&ak)));
+
+        Ok(resp)
     }
 
     async fn delete_api_key(
@@ -341,8 +448,14 @@ impl InternalService for Internal {
             )
             .await?;
 
+        let ak = api_key::get(&api_key_id).await.map_err(|e| e.status())?;
         api_key::delete(&api_key_id).await.map_err(|e| e.status())?;
-        Ok(Response::new(()))
+
+        let mut resp = Response::new(());
+        resp.extensions_mut()
+            .insert(audit::Changes::new(Some(&ak), None));
+
+        Ok(resp)
     }
 
     async fn list_api_keys(
@@ -398,6 +511,24 @@ impl InternalService for Internal {
                         Some(v) => v.to_string(),
                         None => "".to_string(),
                     },
+                    application_id: ak.application_id.map(|v| v.to_string()).unwrap_or_default(),
+                    device_profile_id: ak
+                        .device_profile_id
+                        .map(|v| v.to_string())
+                        .unwrap_or_default(),
+                    permissions: ak
+                        .permissions()
+                        .into_iter()
+                        .map(|p| p.to_proto().into())
+                        .collect(),
+                    expires_at: ak
+                        .expires_at
+                        .as_ref()
+                        .map(helpers::datetime_to_prost_timestamp),
+                    last_used_at: ak
+                        .last_used_at
+                        .as_ref()
+                        .map(helpers::datetime_to_prost_timestamp),
                 })
                 .collect(),
         }))
@@ -486,9 +617,18 @@ impl InternalService for Internal {
         // register the user (if enabled)
         if u.is_none() && conf.user_authentication.openid_connect.registration_enabled {
             u = Some(
-                self.create_and_provision_user(&external_id, &email, email_verified, &oidc_user)
-                    .await
-                    .map_err(|e| e.status())?,
+                self.create_and_provision_user(
+                    &conf
+                        .user_authentication
+                        .openid_connect
+                        .registration_callback_url,
+                    &external_id,
+                    &email,
+                    email_verified,
+                    &oidc_user,
+                )
+                .await
+                .map_err(|e| e.status())?,
             );
         }
 
@@ -507,7 +647,7 @@ impl InternalService for Internal {
         let u = user::update(u).await.map_err(|e| e.status())?;
 
         let token = claims::AuthClaim::new_for_user(&u.id)
-            .encode(self.jwt_secret.as_ref())
+            .encode(&keys::get())
             .map_err(|e| e.status())?;
         Ok(Response::new(api::OpenIdConnectLoginResponse { token }))
     }
@@ -563,6 +703,7 @@ impl InternalService for Internal {
         if u.is_none() && conf.user_authentication.oauth2.registration_enabled {
             u = Some(
                 self.create_and_provision_user(
+                    &conf.user_authentication.oauth2.registration_callback_url,
                     &oauth_user.external_id,
                     &oauth_user.email,
                     email_verified,
@@ -588,7 +729,7 @@ impl InternalService for Internal {
         let u = user::update(u).await.map_err(|e| e.status())?;
 
         let token = claims::AuthClaim::new_for_user(&u.id)
-            .encode(self.jwt_secret.as_ref())
+            .encode(&keys::get())
             .map_err(|e| e.status())?;
         Ok(Response::new(api::OAuth2LoginResponse { token }))
     }
@@ -947,9 +1088,50 @@ impl InternalService for Internal {
                 out.rx2_frequency = region_conf.network.rx2_frequency;
                 out.class_b_ping_slot_dr = region_conf.network.class_b.ping_slot_dr as u32;
                 out.class_b_ping_slot_frequency = region_conf.network.class_b.ping_slot_frequency;
+                out.uplink_max_eirp = region_conf.network.uplink_max_eirp;
+                out.uplink_dwell_time_400ms = region_conf.network.uplink_dwell_time_400ms;
+                out.downlink_dwell_time_400ms = region_conf.network.downlink_dwell_time_400ms;
             }
         }
 
+        if out.uplink_max_eirp == 0.0 {
+            out.uplink_max_eirp = reg.get_default_max_uplink_eirp();
+        }
+        out.supports_user_channels = reg.supports_user_channels();
+        out.class_b_beacon_dr = reg.get_defaults().beacon_dr as u32;
+
+        for (dr, data_rate) in reg.get_data_rates() {
+            let mut item = api::RegionDataRate {
+                dr: dr as u32,
+                uplink: data_rate.uplink,
+                downlink: data_rate.downlink,
+                ..Default::default()
+            };
+
+            match data_rate.modulation {
+                lrwn::region::DataRateModulation::Lora(v) => {
+                    item.modulation = common::Modulation::Lora.into();
+                    item.spreading_factor = v.spreading_factor as u32;
+                    item.bandwidth = v.bandwidth;
+                    item.coding_rate = v.coding_rate;
+                }
+                lrwn::region::DataRateModulation::Fsk(v) => {
+                    item.modulation = common::Modulation::Fsk.into();
+                    item.bitrate = v.bitrate;
+                }
+                lrwn::region::DataRateModulation::LrFhss(v) => {
+                    item.modulation = common::Modulation::LrFhss.into();
+                    item.coding_rate = v.coding_rate;
+                    item.occupied_channel_width = v.occupied_channel_width;
+                    item.grid_steps =
+                        lrwn::region::lr_fhss::get_grid_steps(v.occupied_channel_width)
+                            .unwrap_or_default();
+                }
+            }
+
+            out.data_rates.push(item);
+        }
+
         let enabled_channels = reg.get_enabled_uplink_channel_indices();
 
         for i in enabled_channels {
@@ -976,4 +1158,144 @@ impl InternalService for Internal {
             version: env!("CARGO_PKG_VERSION").to_string(),
         }))
     }
+
+    async fn decode_frame(
+        &self,
+        request: Request<api::DecodeFrameRequest>,
+    ) -> Result<Response<api::DecodeFrameResponse>, Status> {
+        let req = request.get_ref();
+
+        let dev_eui = if req.dev_eui.is_empty() {
+            None
+        } else {
+            Some(EUI64::from_str(&req.dev_eui).map_err(|e| e.status())?)
+        };
+
+        // As this returns the decrypted payload, this requires update permissions on the device
+        // (read permissions are not sufficient for obtaining the payload).
+        if let Some(dev_eui) = dev_eui {
+            self.validator
+                .validate(
+                    request.extensions(),
+                    validator::ValidateDeviceAccess::new(validator::Flag::Update, dev_eui),
+                )
+                .await?;
+        } else if req.lookup_device {
+            // The DevAddr based lookup is not scoped to a single tenant.
+            self.validator
+                .validate(request.extensions(), validator::ValidateIsAdmin::new())
+                .await?;
+        } else {
+            self.validator
+                .validate(
+                    request.extensions(),
+                    validator::ValidateActiveUserOrKey::new(),
+                )
+                .await?;
+        }
+
+        let phy = lrwn::PhyPayload::from_slice(&req.phy_payload)
+            .map_err(|e| Status::invalid_argument(format!("{:#}", e)))?;
+
+        let out = frame_decoder::decode(
+            phy,
+            &frame_decoder::Options {
+                lookup_device: req.lookup_device,
+                dev_eui,
+                tx_dr: req.tx_dr as u8,
+                tx_ch: req.tx_ch as u8,
+                run_codec: req.run_codec,
+            },
+        )
+        .await
+        .map_err(|e| e.status())?;
+
+        Ok(Response::new(api::DecodeFrameResponse {
+            json: serde_json::to_string_pretty(&out)
+                .map_err(|e| Status::internal(e.to_string()))?,
+            dev_eui: match &out.dev_eui {
+                Some(v) => v.to_string(),
+                None => "".to_string(),
+            },
+            mic_valid: out.mic_valid.unwrap_or_default(),
+            f_cnt: out.f_cnt.unwrap_or_default(),
+            object: out.object.as_ref().map(codec::convert::pb_json_to_prost),
+            codec_error: out.codec_error.clone().unwrap_or_default(),
+        }))
+    }
+
+    async fn list_audit_log(
+        &self,
+        request: Request<api::ListAuditLogRequest>,
+    ) -> Result<Response<api::ListAuditLogResponse>, Status> {
+        let req = request.get_ref();
+
+        let parse_uuid = |s: &str| -> Result<Option<Uuid>, Status> {
+            if s.is_empty() {
+                Ok(None)
+            } else {
+                Ok(Some(Uuid::from_str(s).map_err(|e| e.status())?))
+            }
+        };
+        let parse_ts =
+            |ts: &Option<prost_types::Timestamp>| -> Result<Option<DateTime<Utc>>, Status> {
+                match ts {
+                    Some(v) => {
+                        let ts = SystemTime::try_from(v.clone()).map_err(|e| e.status())?;
+                        Ok(Some(ts.into()))
+                    }
+                    None => Ok(None),
+                }
+            };
+
+        let filters = audit_log::Filters {
+            tenant_id: parse_uuid(&req.tenant_id)?,
+            application_id: parse_uuid(&req.application_id)?,
+            dev_eui: if req.dev_eui.is_empty() {
+                None
+            } else {
+                Some(EUI64::from_str(&req.dev_eui).map_err(|e| e.status())?)
+            },
+            user_id: parse_uuid(&req.user_id)?,
+            api_key_id: parse_uuid(&req.api_key_id)?,
+            method: (!req.method.is_empty()).then(|| req.method.clone()),
+            start: parse_ts(&req.start)?,
+            end: parse_ts(&req.end)?,
+        };
+
+        self.validator
+            .validate(
+                request.extensions(),
+                validator::ValidateAuditLogAccess::new(validator::Flag::List, filters.tenant_id),
+            )
+            .await?;
+
+        let count = audit_log::get_count(&filters)
+            .await
+            .map_err(|e| e.status())?;
+        let items = audit_log::list(req.limit as i64, req.offset as i64, &filters)
+            .await
+            .map_err(|e| e.status())?;
+
+        Ok(Response::new(api::ListAuditLogResponse {
+            total_count: count as u32,
+            result: items
+                .iter()
+                .map(|al| api::AuditLogItem {
+                    id: al.id.to_string(),
+                    created_at: Some(helpers::datetime_to_prost_timestamp(&al.created_at)),
+                    user_id: al.user_id.map(|v| v.to_string()).unwrap_or_default(),
+                    api_key_id: al.api_key_id.map(|v| v.to_string()).unwrap_or_default(),
+                    service: al.service.clone(),
+                    method: al.method.clone(),
+                    status_code: al.status_code.clone(),
+                    tenant_id: al.tenant_id.map(|v| v.to_string()).unwrap_or_default(),
+                    application_id: al.application_id.map(|v| v.to_string()).unwrap_or_default(),
+                    dev_eui: al.dev_eui.map(|v| v.to_string()).unwrap_or_default(),
+                    metadata: al.metadata.into_hashmap(),
+                    changes: al.changes.to_string(),
+                })
+                .collect(),
+        }))
+    }
 }
@@ -41,9 +41,10 @@ use super::config;
 use crate::api::auth::validator;
 use crate::helpers::errors::PrintFullError;
 use crate::monitoring::prometheus;
-use crate::stream;
+use crate::{storage, stream};
 
 pub mod application;
+pub mod audit;
 pub mod auth;
 pub mod backend;
 pub mod device;
@@ -54,10 +55,12 @@ pub mod gateway;
 pub mod helpers;
 pub mod internal;
 pub mod monitoring;
+pub mod mqtt;
 pub mod multicast;
 pub mod oauth2;
 pub mod oidc;
 pub mod relay;
+pub mod scim;
 pub mod tenant;
 pub mod user;
 
@@ -101,6 +104,8 @@ pub async fn setup() -> Result<()> {
 
     info!(bind = %conf.api.bind, "Setting up API interface");
 
+    auth::keys::setup()?;
+
     // Taken from the tonic hyper_warp_multiplex example:
     // https://github.com/hyperium/tonic/blob/master/examples/src/hyper_warp_multiplex/server.rs#L101
     let service = make_service_fn(move |_| {
@@ -115,10 +120,7 @@ pub async fn setup() -> Result<()> {
                     .unwrap(),
             )
             .add_service(InternalServiceServer::with_interceptor(
-                internal::Internal::new(
-                    validator::RequestValidator::new(),
-                    conf.api.secret.clone(),
-                ),
+                internal::Internal::new(validator::RequestValidator::new()),
                 auth::auth_interceptor,
             ))
             .add_service(ApplicationServiceServer::with_interceptor(
@@ -172,13 +174,15 @@ pub async fn setup() -> Result<()> {
                     .on_request(OnRequest {})
                     .on_response(OnResponse {}),
             )
+            .layer(auth::external::ExternalAuth {})
             .layer(ApiLogger {})
             .service(tonic_service);
 
         // HTTP service
         let warp_service = warp::service(
-            warp::path!("auth" / "oidc" / "login")
-                .and_then(oidc::login_handler)
+            warp::path!(".well-known" / "jwks.json")
+                .and_then(jwks_handler)
+                .or(warp::path!("auth" / "oidc" / "login").and_then(oidc::login_handler))
                 .or(warp::path!("auth" / "oidc" / "callback")
                     .and(warp::query::<oidc::CallbackArgs>())
                     .and_then(oidc::callback_handler))
@@ -186,6 +190,8 @@ pub async fn setup() -> Result<()> {
                 .or(warp::path!("auth" / "oauth2" / "callback")
                     .and(warp::query::<oauth2::CallbackArgs>())
                     .and_then(oauth2::callback_handler))
+                .or(scim::routes())
+                .or(mqtt::routes())
                 .or(warp::path::tail().and_then(http_serve)),
         );
         let mut warp_service = ServiceBuilder::new()
@@ -204,29 +210,51 @@ pub async fn setup() -> Result<()> {
             )
             .service(warp_service);
 
+        // The SCIM and MQTT auth hook endpoints use other HTTP methods than GET, the other HTTP
+        // endpoints only use GET.
         future::ok::<_, Infallible>(tower::service_fn(
-            move |req: hyper::Request<hyper::Body>| match req.method() {
-                &hyper::Method::GET => Either::Left(
-                    warp_service
-                        .call(req)
-                        .map_ok(|res| res.map(EitherBody::Right))
-                        .map_err(Error::from),
-                ),
-                _ => Either::Right(
-                    tonic_service
-                        .call(req)
-                        .map_ok(|res| res.map(EitherBody::Left))
-                        .map_err(Error::from),
-                ),
+            move |req: hyper::Request<hyper::Body>| {
+                if req.method() == hyper::Method::GET
+                    || req.uri().path().starts_with("/scim/")
+                    || req.uri().path().starts_with("/mqtt/")
+                {
+                    Either::Left(
+                        warp_service
+                            .call(req)
+                            .map_ok(|res| res.map(EitherBody::Right))
+                            .map_err(Error::from),
+                    )
+                } else {
+                    Either::Right(
+                        tonic_service
+                            .call(req)
+                            .map_ok(|res| res.map(EitherBody::Left))
+                            .map_err(Error::from),
+                    )
+                }
             },
         ))
     });
 
     let backend_handle = tokio::spawn(backend::setup());
     let monitoring_handle = tokio::spawn(monitoring::setup());
+    let audit_log_handle = tokio::spawn(audit::cleanup_loop());
+    let soft_delete_handle = tokio::spawn(storage::soft_delete::purge_loop());
+    let tenant_usage_handle = tokio::spawn(storage::tenant_usage::persist_loop());
+    let api_key_last_used_handle = tokio::spawn(storage::api_key::persist_last_used_loop());
+    let external_token_handle = tokio::spawn(auth::external::refresh_loop());
     let api_handle = tokio::spawn(Server::bind(&addr).serve(service));
 
-    let _ = try_join!(api_handle, backend_handle, monitoring_handle)?;
+    let _ = try_join!(
+        api_handle,
+        backend_handle,
+        monitoring_handle,
+        audit_log_handle,
+        soft_delete_handle,
+        tenant_usage_handle,
+        api_key_last_used_handle,
+        external_token_handle
+    )?;
 
     Ok(())
 }
@@ -278,6 +306,10 @@ fn map_option_err<T, U: Into<Error>>(err: Option<Result<T, U>>) -> Option<Result
     err.map(|e| e.map_err(Into::into))
 }
 
+async fn jwks_handler() -> Result<impl Reply, Rejection> {
+    Ok(warp::reply::json(&auth::keys::get().jwks()))
+}
+
 async fn http_serve(path: Tail) -> Result<impl Reply, Rejection> {
     let mut path = path.as_str();
     if path.is_empty() {
@@ -343,7 +375,7 @@ where
 {
     type Response = S::Response;
     type Error = S::Error;
-    type Future = ApiLoggerResponseFuture<S::Future>;
+    type Future = ApiLoggerResponseFuture<S::Future, ResBody, S::Error>;
 
     fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
         self.inner.poll_ready(cx)
@@ -352,6 +384,12 @@ where
     fn call(&mut self, request: http::Request<ReqBody>) -> Self::Future {
         let uri = request.uri().path().to_string();
         let uri_parts: Vec<&str> = uri.split('/').collect();
+        let authorization = request
+            .headers()
+            .get("authorization")
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.to_string());
+        let auth_id = request.extensions().get::<auth::AuthID>().cloned();
         let response_future = self.inner.call(request);
         let start = Instant::now();
         ApiLoggerResponseFuture {
@@ -359,20 +397,30 @@ where
             start,
             service: uri_parts.get(1).map(|v| v.to_string()).unwrap_or_default(),
             method: uri_parts.get(2).map(|v| v.to_string()).unwrap_or_default(),
+            authorization,
+            auth_id,
+            audit_future: None,
+            result: None,
         }
     }
 }
 
 #[pin_project]
-struct ApiLoggerResponseFuture<F> {
+struct ApiLoggerResponseFuture<F, ResBody, Error> {
     #[pin]
     response_future: F,
     start: Instant,
     service: String,
     method: String,
+    authorization: Option<String>,
+    auth_id: Option<auth::AuthID>,
+    // The audit log entry is persisted before the response is returned, such that the entry is
+    // not lost (e.g. on shutdown) once the client has received the response.
+    audit_future: Option<Pin<Box<dyn Future<Output = ()> + Send>>>,
+    result: Option<Result<http::Response<ResBody>, Error>>,
 }
 
-impl<F, ResBody, Error> Future for ApiLoggerResponseFuture<F>
+impl<F, ResBody, Error> Future for ApiLoggerResponseFuture<F, ResBody, Error>
 where
     F: Future<Output = Result<http::Response<ResBody>, Error>>,
     ResBody: http_body::Body,
@@ -382,6 +430,16 @@ where
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         let this = self.project();
 
+        if let Some(audit_future) = this.audit_future {
+            return match audit_future.as_mut().poll(cx) {
+                Poll::Ready(()) => {
+                    *this.audit_future = None;
+                    Poll::Ready(this.result.take().expect("result must be set"))
+                }
+                Poll::Pending => Poll::Pending,
+            };
+        }
+
         match this.response_future.poll(cx) {
             Poll::Ready(result) => {
                 if let Ok(response) = &result {
@@ -425,11 +483,41 @@ where
                             .collect(),
                     };
 
+                    // Log mutating API request to the audit log
+                    let audit_req = if audit::is_mutating(this.service, this.method) {
+                        Some(audit::Request {
+                            service: this.service.to_string(),
+                            method: this.method.to_string(),
+                            status_code: format!("{:?}", status_code),
+                            authorization: this.authorization.take(),
+                            auth_id: this.auth_id.take(),
+                            metadata: req_log.metadata.clone(),
+                            changes: response.extensions().get::<audit::Changes>().cloned(),
+                        })
+                    } else {
+                        None
+                    };
+
                     task::spawn(async move {
                         if let Err(e) = stream::api_request::log_request(&req_log).await {
                             error!(error = %e.full(), "Log request error");
                         }
                     });
+
+                    if let Some(audit_req) = audit_req {
+                        let mut audit_future: Pin<Box<dyn Future<Output = ()> + Send>> =
+                            Box::pin(async move {
+                                if let Err(e) = audit::log(audit_req).await {
+                                    error!(error = %e.full(), "Audit log error");
+                                }
+                            });
+
+                        if audit_future.as_mut().poll(cx).is_pending() {
+                            *this.audit_future = Some(audit_future);
+                            *this.result = Some(result);
+                            return Poll::Pending;
+                        }
+                    }
                 }
                 Poll::Ready(result)
             }
@@ -0,0 +1,751 @@
+use std::str::FromStr;
+use std::time::SystemTime;
+
+use anyhow::Result;
+use bytes::Bytes;
+use handlebars::Handlebars;
+use serde::{Deserialize, Serialize};
+use subtle::ConstantTimeEq;
+use tracing::{error, trace};
+use uuid::Uuid;
+use warp::http::{header, HeaderValue, Method, StatusCode};
+use warp::reply::Response;
+use warp::{Filter, Rejection, Reply};
+
+use crate::certificate;
+use crate::config;
+use crate::helpers::errors::PrintFullError;
+use crate::storage::mqtt_client_certificate;
+
+// MQTT broker endpoints for the application client-certificates.
+//
+// The CRL endpoints return the certificate revocation list of the revoked client-certificates.
+// The auth hook endpoints can be used by the MQTT broker (e.g. Mosquitto using the
+// mosquitto-go-auth HTTP backend, or EMQX using HTTP authentication / authorization) to
+// authenticate clients and to authorize topics per application.
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct HookRequest {
+    username: String,
+    password: String,
+    clientid: String,
+    // Subject of the client-certificate, as set by the broker (e.g. ${cert_subject} in EMQX).
+    cert_subject: String,
+    topic: String,
+    // mosquitto-go-auth: 1 = read, 2 = write, 3 = read + write, 4 = subscribe.
+    acc: Option<u8>,
+    // EMQX: publish or subscribe.
+    action: Option<String>,
+}
+
+#[derive(Serialize, Debug, PartialEq, Eq)]
+struct HookResponse {
+    // mosquitto-go-auth (http_response_mode=json).
+    ok: bool,
+    error: String,
+    // EMQX.
+    result: String,
+    is_superuser: bool,
+}
+
+impl HookResponse {
+    fn new(ok: bool, is_superuser: bool, error: &str) -> Self {
+        HookResponse {
+            ok,
+            error: error.to_string(),
+            result: if ok { "allow" } else { "deny" }.to_string(),
+            is_superuser,
+        }
+    }
+}
+
+// The identity of the client, based on the client-certificate.
+#[derive(Debug, PartialEq, Eq)]
+struct ClientIdentity {
+    application_id: Uuid,
+    serial_number: String,
+}
+
+impl ClientIdentity {
+    // The identity is only taken from the certificate subject set by the broker, which contains
+    // the application ID as Common Name and the serial number of the certificate. The username
+    // is set by the client and is therefore never used as identity.
+    fn from_request(req: &HookRequest) -> Option<ClientIdentity> {
+        let mut common_name = None;
+        let mut serial_number = None;
+
+        for attr in req.cert_subject.split([',', '/']) {
+            if let Some((k, v)) = attr.trim().split_once('=') {
+                if k.eq_ignore_ascii_case("CN") {
+                    common_name = Some(v);
+                } else if k.eq_ignore_ascii_case("serialNumber") {
+                    serial_number = Some(v);
+                }
+            }
+        }
+
+        Some(ClientIdentity {
+            application_id: Uuid::from_str(common_name?).ok()?,
+            serial_number: serial_number?.to_lowercase(),
+        })
+    }
+}
+
+#[derive(Serialize)]
+struct TopicContext {
+    application_id: String,
+    dev_eui: String,
+    event: String,
+    command: String,
+}
+
+pub fn routes() -> impl Filter<Extract = (Response,), Error = Rejection> + Clone {
+    warp::path!("mqtt" / String)
+        .and(warp::method())
+        .and(warp::header::optional::<String>("authorization"))
+        .and(warp::body::bytes())
+        .and_then(handler)
+}
+
+async fn handler(
+    endpoint: String,
+    method: Method,
+    authorization: Option<String>,
+    body: Bytes,
+) -> Result<Response, Rejection> {
+    match (method.as_str(), endpoint.as_str()) {
+        ("GET", "crl") => crl(false).await,
+        ("GET", "crl.pem") => crl(true).await,
+        ("POST", "auth" | "superuser" | "acl") => hook(&endpoint, authorization, &body).await,
+        _ => Err(warp::reject::not_found()),
+    }
+}
+
+async fn crl(pem: bool) -> Result<Response, Rejection> {
+    let conf = config::get();
+    if conf.integration.mqtt.client.ca_cert.is_empty() {
+        return Err(warp::reject::not_found());
+    }
+
+    let res: Result<Vec<u8>> = async {
+        let revoked = mqtt_client_certificate::list_revoked()
+            .await?
+            .iter()
+            .map(|c| {
+                Ok((
+                    hex::decode(&c.serial_number)?,
+                    SystemTime::from(c.revoked_at.unwrap_or(c.created_at)),
+                ))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        certificate::crl_for_application_certs(&revoked).await
+    }
+    .await;
+
+    let der = match res {
+        Ok(v) => v,
+        Err(e) => {
+            error!(error = %e.full(), "Generate certificate revocation list error");
+            return Ok(warp::reply::with_status(
+                "Internal error",
+                StatusCode::INTERNAL_SERVER_ERROR,
+            )
+            .into_response());
+        }
+    };
+
+    let mut resp = if pem {
+        pem::encode(&pem::Pem::new("X509 CRL", der)).into_response()
+    } else {
+        der.into_response()
+    };
+    resp.headers_mut().insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static(if pem {
+            "application/x-pem-file"
+        } else {
+            "application/pkix-crl"
+        }),
+    );
+    Ok(resp)
+}
+
+async fn hook(
+    endpoint: &str,
+    authorization: Option<String>,
+    body: &[u8],
+) -> Result<Response, Rejection> {
+    let conf = config::get();
+    if !conf.integration.mqtt.auth_hook.enabled {
+        return Err(warp::reject::not_found());
+    }
+
+    // The hook exposes the superuser authentication, therefore it is not enabled without bearer
+    // token.
+    if conf.integration.mqtt.auth_hook.bearer_token.is_empty() {
+        error!("MQTT auth hook is enabled, but bearer_token is not configured");
+        return Err(warp::reject::not_found());
+    }
+
+    let token = authorization
+        .as_deref()
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .unwrap_or_default();
+    if !bool::from(
+        token
+            .as_bytes()
+            .ct_eq(conf.integration.mqtt.auth_hook.bearer_token.as_bytes()),
+    ) {
+        return Ok(reply(
+            StatusCode::UNAUTHORIZED,
+            &HookResponse::new(false, false, "Invalid bearer token"),
+        ));
+    }
+
+    let req: HookRequest = match serde_json::from_slice(body) {
+        Ok(v) => v,
+        Err(e) => {
+            return Ok(reply(
+                StatusCode::BAD_REQUEST,
+                &HookResponse::new(false, false, &e.to_string()),
+            ));
+        }
+    };
+
+    let resp = match handle(endpoint, &req).await {
+        Ok(v) => v,
+        Err(e) => {
+            error!(endpoint = %endpoint, username = %req.username, error = %e.full(), "Handle MQTT auth hook request error");
+            HookResponse::new(false, false, "Internal error")
+        }
+    };
+
+    trace!(endpoint = %endpoint, username = %req.username, client_id = %req.clientid, topic = %req.topic, ok = resp.ok, "MQTT auth hook request handled");
+
+    // The response is always returned with status 200, as EMQX ignores the result in case of
+    // other status codes.
+    Ok(reply(StatusCode::OK, &resp))
+}
+
+async fn handle(endpoint: &str, req: &HookRequest) -> Result<HookResponse> {
+    let superuser = is_superuser(req);
+
+    Ok(match endpoint {
+        "auth" => {
+            if superuser {
+                let conf = config::get();
+                let ok = req
+                    .password
+                    .as_bytes()
+                    .ct_eq(conf.integration.mqtt.password.as_bytes());
+                HookResponse::new(ok.into(), true, "")
+            } else if !req.password.is_empty() {
+                // Clients are authenticated by their certificate only.
+                HookResponse::new(false, false, "")
+            } else {
+                HookResponse::new(get_valid_identity(req).await?.is_some(), false, "")
+            }
+        }
+        "superuser" => HookResponse::new(superuser, superuser, ""),
+        "acl" => {
+            if superuser {
+                HookResponse::new(true, true, "")
+            } else {
+                let ok = match get_valid_identity(req).await? {
+                    Some(id) => is_allowed_topic(&id, req)?,
+                    None => false,
+                };
+                HookResponse::new(ok, false, "")
+            }
+        }
+        _ => return Err(anyhow!("Unexpected endpoint: {}", endpoint)),
+    })
+}
+
+// The username configured for the MQTT integration, which is used by ChirpStack, is the
+// superuser.
+fn is_superuser(req: &HookRequest) -> bool {
+    let conf = config::get();
+    !conf.integration.mqtt.username.is_empty() && req.username == conf.integration.mqtt.username
+}
+
+// Returns the identity of the client, if the client-certificate it presented is valid.
+async fn get_valid_identity(req: &HookRequest) -> Result<Option<ClientIdentity>> {
+    let id = match ClientIdentity::from_request(req) {
+        Some(v) => v,
+        None => return Ok(None),
+    };
+
+    if mqtt_client_certificate::is_valid(&id.application_id, &id.serial_number).await? {
+        Ok(Some(id))
+    } else {
+        Ok(None)
+    }
+}
+
+// Clients are allowed to subscribe to the events and to publish commands of their own
+// application.
+fn is_allowed_topic(id: &ClientIdentity, req: &HookRequest) -> Result<bool> {
+    let conf = config::get();
+
+    let (read, write) = match (req.acc, req.action.as_deref()) {
+        (Some(1 | 4), _) | (_, Some("subscribe")) => (true, false),
+        (Some(2), _) | (_, Some("publish")) => (false, true),
+        (Some(3), _) => (true, true),
+        _ => return Ok(false),
+    };
+
+    let mut templates = Handlebars::new();
+    templates.register_escape_fn(handlebars::no_escape);
+    let ctx = TopicContext {
+        application_id: id.application_id.to_string(),
+        dev_eui: "+".into(),
+        event: "+".into(),
+        command: "+".into(),
+    };
+
+    if read {
+        let pattern = templates.render_template(&conf.integration.mqtt.event_topic, &ctx)?;
+        if !topic_matches(&pattern, &req.topic) {
+            return Ok(false);
+        }
+    }
+
+    if write {
+        let pattern = templates.render_template(&conf.integration.mqtt.command_topic, &ctx)?;
+        if !topic_matches(&pattern, &req.topic) {
+            return Ok(false);
+        }
+    }
+
+    Ok(true)
+}
+
+// Returns true when all the topics matched by the given topic (which can be a topic filter in
+// case of a subscription) are matched by the pattern.
+fn topic_matches(pattern: &str, topic: &str) -> bool {
+    let mut pattern = pattern.split('/');
+    let mut topic = topic.split('/');
+
+    loop {
+        match (pattern.next(), topic.next()) {
+            (Some("#"), _) => return true,
+            (Some("+"), Some(t)) if t != "#" => {}
+            (Some(p), Some(t)) if p == t && t != "+" && t != "#" => {}
+            (None, None) => return true,
+            _ => return false,
+        }
+    }
+}
+
+fn reply(status: StatusCode, resp: &HookResponse) -> Response {
+    warp::reply::with_status(warp::reply::json(resp), status).into_response()
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use crate::storage::{application, tenant};
+    use crate::test;
+    use chrono::{Duration, Utc};
+
+    #[test]
+    fn test_topic_matches() {
+        struct Test {
+            name: String,
+            pattern: String,
+            topic: String,
+            matches: bool,
+        }
+
+        let tests = vec![
+            Test {
+                name: "exact match".into(),
+                pattern: "application/123/device/+/event/+".into(),
+                topic: "application/123/device/0102030405060708/event/up".into(),
+                matches: true,
+            },
+            Test {
+                name: "single level wildcard".into(),
+                pattern: "application/123/device/+/event/+".into(),
+                topic: "application/123/device/+/event/up".into(),
+                matches: true,
+            },
+            Test {
+                name: "multi level wildcard not allowed by pattern".into(),
+                pattern: "application/123/device/+/event/+".into(),
+                topic: "application/123/#".into(),
+                matches: false,
+            },
+            Test {
+                name: "multi level wildcard allowed by pattern".into(),
+                pattern: "application/123/#".into(),
+                topic: "application/123/device/#".into(),
+                matches: true,
+            },
+            Test {
+                name: "other application".into(),
+                pattern: "application/123/device/+/event/+".into(),
+                topic: "application/456/device/0102030405060708/event/up".into(),
+                matches: false,
+            },
+            Test {
+                name: "single level wildcard for literal level".into(),
+                pattern: "application/123/device/+/event/+".into(),
+                topic: "application/+/device/0102030405060708/event/up".into(),
+                matches: false,
+            },
+            Test {
+                name: "topic too short".into(),
+                pattern: "application/123/device/+/event/+".into(),
+                topic: "application/123/device/0102030405060708".into(),
+                matches: false,
+            },
+            Test {
+                name: "topic too long".into(),
+                pattern: "application/123/device/+/event/+".into(),
+                topic: "application/123/device/0102030405060708/event/up/foo".into(),
+                matches: false,
+            },
+        ];
+
+        for tst in &tests {
+            println!("> {}", tst.name);
+            assert_eq!(tst.matches, topic_matches(&tst.pattern, &tst.topic));
+        }
+    }
+
+    #[test]
+    fn test_client_identity() {
+        struct Test {
+            name: String,
+            req: HookRequest,
+            expected: Option<ClientIdentity>,
+        }
+
+        let app_id = Uuid::new_v4();
+        let tests = vec![
+            Test {
+                name: "subject (RFC 2253)".into(),
+                req: HookRequest {
+                    cert_subject: format!("serialNumber=0A0B,CN={}", app_id),
+                    ..Default::default()
+                },
+                expected: Some(ClientIdentity {
+                    application_id: app_id,
+                    serial_number: "0a0b".into(),
+                }),
+            },
+            Test {
+                name: "subject (OpenSSL oneline)".into(),
+                req: HookRequest {
+                    cert_subject: format!("/CN={}/serialNumber=0a0b", app_id),
+                    ..Default::default()
+                },
+                expected: Some(ClientIdentity {
+                    application_id: app_id,
+                    serial_number: "0a0b".into(),
+                }),
+            },
+            Test {
+                name: "subject as username".into(),
+                req: HookRequest {
+                    username: format!("serialNumber=0a0b,CN={}", app_id),
+                    ..Default::default()
+                },
+                expected: None,
+            },
+            Test {
+                name: "common name only".into(),
+                req: HookRequest {
+                    cert_subject: format!("CN={}", app_id),
+                    ..Default::default()
+                },
+                expected: None,
+            },
+            Test {
+                name: "invalid common name".into(),
+                req: HookRequest {
+                    cert_subject: "CN=foo,serialNumber=0a0b".into(),
+                    ..Default::default()
+                },
+                expected: None,
+            },
+        ];
+
+        for tst in &tests {
+            println!("> {}", tst.name);
+            assert_eq!(tst.expected, ClientIdentity::from_request(&tst.req));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_hook_bearer_token() {
+        let _guard = test::prepare().await;
+        let body = serde_json::to_vec(&serde_json::json!({"username": "foo"})).unwrap();
+
+        // no bearer token configured
+        let mut conf = (*config::get()).clone();
+        conf.integration.mqtt.auth_hook.enabled = true;
+        config::set(conf);
+        assert!(hook("auth", Some("Bearer ".into()), &body).await.is_err());
+
+        let mut conf = (*config::get()).clone();
+        conf.integration.mqtt.auth_hook.bearer_token = "secret".into();
+        config::set(conf);
+
+        // invalid bearer token
+        let resp = hook("auth", Some("Bearer invalid".into()), &body)
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::UNAUTHORIZED, resp.status());
+        let resp = hook("auth", None, &body).await.unwrap();
+        assert_eq!(StatusCode::UNAUTHORIZED, resp.status());
+
+        // valid bearer token
+        let resp = hook("auth", Some("Bearer secret".into()), &body)
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::OK, resp.status());
+    }
+
+    #[tokio::test]
+    async fn test_hook() {
+        let _guard = test::prepare().await;
+
+        let mut conf = (*config::get()).clone();
+        conf.integration.mqtt.username = "chirpstack".into();
+        conf.integration.mqtt.password = "secret".into();
+        config::set(conf);
+
+        let t = tenant::create(tenant::Tenant {
+            name: "test-tenant".into(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let app = application::create(application::Application {
+            tenant_id: t.id,
+            name: "test-app".into(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let event_topic = format!("application/{}/device/0102030405060708/event/up", app.id);
+        let command_topic = format!(
+            "application/{}/device/0102030405060708/command/down",
+            app.id
+        );
+
+        // superuser
+        let resp = handle(
+            "auth",
+            &HookRequest {
+                username: "chirpstack".into(),
+                password: "secret".into(),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(HookResponse::new(true, true, ""), resp);
+
+        let resp = handle(
+            "auth",
+            &HookRequest {
+                username: "chirpstack".into(),
+                password: "invalid".into(),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        assert!(!resp.ok);
+
+        // no certificate
+        let resp = handle(
+            "auth",
+            &HookRequest {
+                cert_subject: format!("CN={},serialNumber=01020304", app.id),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        assert!(!resp.ok);
+
+        let c = mqtt_client_certificate::create(mqtt_client_certificate::ClientCertificate {
+            serial_number: "01020304".into(),
+            application_id: app.id,
+            expires_at: Utc::now() + Duration::days(1),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let _ = mqtt_client_certificate::create(mqtt_client_certificate::ClientCertificate {
+            serial_number: "05060708".into(),
+            application_id: app.id,
+            expires_at: Utc::now() + Duration::days(1),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let subject = format!("serialNumber=01020304,CN={}", app.id);
+
+        // valid certificate
+        let resp = handle(
+            "auth",
+            &HookRequest {
+                cert_subject: subject.clone(),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(HookResponse::new(true, false, ""), resp);
+
+        // forged username, without certificate
+        let resp = handle(
+            "auth",
+            &HookRequest {
+                username: subject.clone(),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        assert!(!resp.ok);
+
+        let resp = handle(
+            "acl",
+            &HookRequest {
+                username: subject.clone(),
+                topic: event_topic.clone(),
+                acc: Some(4),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        assert!(!resp.ok);
+
+        // password is not accepted for certificate clients
+        let resp = handle(
+            "auth",
+            &HookRequest {
+                password: "secret".into(),
+                cert_subject: subject.clone(),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        assert!(!resp.ok);
+
+        // the serial number is required
+        let resp = handle(
+            "auth",
+            &HookRequest {
+                cert_subject: format!("CN={}", app.id),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        assert!(!resp.ok);
+
+        // serial number of an other application
+        let resp = handle(
+            "auth",
+            &HookRequest {
+                cert_subject: format!("serialNumber=01020304,CN={}", Uuid::new_v4()),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        assert!(!resp.ok);
+
+        let resp = handle(
+            "superuser",
+            &HookRequest {
+                cert_subject: subject.clone(),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        assert!(!resp.ok);
+
+        // acl
+        let resp = handle(
+            "acl",
+            &HookRequest {
+                cert_subject: subject.clone(),
+                topic: event_topic.clone(),
+                acc: Some(4),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        assert!(resp.ok);
+
+        let resp = handle(
+            "acl",
+            &HookRequest {
+                cert_subject: subject.clone(),
+                topic: event_topic.clone(),
+                action: Some("publish".into()),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        assert!(!resp.ok);
+
+        let resp = handle(
+            "acl",
+            &HookRequest {
+                cert_subject: subject.clone(),
+                topic: command_topic.clone(),
+                action: Some("publish".into()),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        assert!(resp.ok);
+
+        // revoked certificate, while the application has an other valid certificate
+        mqtt_client_certificate::revoke(&app.id, &c.serial_number)
+            .await
+            .unwrap();
+        let resp = handle(
+            "acl",
+            &HookRequest {
+                cert_subject: subject.clone(),
+                topic: event_topic.clone(),
+                acc: Some(1),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        assert!(!resp.ok);
+
+        let resp = handle(
+            "acl",
+            &HookRequest {
+                cert_subject: format!("CN={},serialNumber=05060708", app.id),
+                topic: event_topic.clone(),
+                acc: Some(1),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        assert!(resp.ok);
+    }
+}
@@ -8,6 +8,7 @@ use chirpstack_api::api;
 use chirpstack_api::api::multicast_group_service_server::MulticastGroupService;
 use lrwn::{AES128Key, DevAddr, EUI64};
 
+use super::audit;
 use super::auth::validator;
 use super::error::ToStatus;
 use super::helpers::{self, FromProto, ToProto};
@@ -81,6 +82,8 @@ impl MulticastGroupService for MulticastGroup {
             "x-log-multicast_group_id",
             mg.id.to_string().parse().unwrap(),
         );
+        resp.extensions_mut()
+            .insert(audit::Changes::new(None, Some(&mg)));
 
         Ok(resp)
     }
@@ -153,7 +156,9 @@ impl MulticastGroupService for MulticastGroup {
             )
             .await?;
 
-        let _ = multicast::update(multicast::MulticastGroup {
+        let mg_before = multicast::get(&mg_id).await.map_err(|e| e.status())?;
+
+        let mg = multicast::update(multicast::MulticastGroup {
             id: mg_id,
             name: req_mg.name.clone(),
             region: req_mg.region().from_proto(),
@@ -185,6 +190,8 @@ impl MulticastGroupService for MulticastGroup {
         let mut resp = Response::new(());
         resp.metadata_mut()
             .insert("x-log-multicast_group_id", req_mg.id.parse().unwrap());
+        resp.extensions_mut()
+            .insert(audit::Changes::new(Some(&mg_before), Some(&mg)));
 
         Ok(resp)
     }
@@ -203,11 +210,14 @@ impl MulticastGroupService for MulticastGroup {
             )
             .await?;
 
+        let mg = multicast::get(&mg_id).await.map_err(|e| e.status())?;
         multicast::delete(&mg_id).await.map_err(|e| e.status())?;
 
         let mut resp = Response::new(());
         resp.metadata_mut()
             .insert("x-log-multicast_group_id", req.id.parse().unwrap());
+        resp.extensions_mut()
+            .insert(audit::Changes::new(Some(&mg), None));
 
         Ok(resp)
     }
@@ -0,0 +1,904 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use bytes::Bytes;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use subtle::ConstantTimeEq;
+use tracing::error;
+use uuid::Uuid;
+use warp::http::{header, HeaderValue, Method, StatusCode};
+use warp::path::Tail;
+use warp::reply::Response;
+use warp::{Filter, Rejection, Reply};
+
+use crate::config;
+use crate::helpers::errors::PrintFullError;
+use crate::storage::{error::Error, tenant, user};
+
+// SCIM 2.0 (RFC 7643, RFC 7644) user provisioning.
+//
+// Users are matched by their external ID. Only users with an external ID, which are not global
+// admin users, can be read or modified through SCIM. Groups are not stored by ChirpStack, these
+// are mapped to tenant memberships using the user_authentication.scim.groups configuration.
+// Only the tenant memberships created through SCIM are managed by SCIM, memberships that were
+// created otherwise are left untouched.
+
+const SCHEMA_USER: &str = "urn:ietf:params:scim:schemas:core:2.0:User";
+const SCHEMA_GROUP: &str = "urn:ietf:params:scim:schemas:core:2.0:Group";
+const SCHEMA_LIST_RESPONSE: &str = "urn:ietf:params:scim:api:messages:2.0:ListResponse";
+const SCHEMA_ERROR: &str = "urn:ietf:params:scim:api:messages:2.0:Error";
+
+#[derive(Deserialize, Default)]
+#[serde(default, rename_all = "camelCase")]
+struct UserResource {
+    external_id: Option<String>,
+    user_name: String,
+    active: Option<bool>,
+    emails: Vec<Email>,
+}
+
+impl UserResource {
+    // Returns the primary email, falling back to the userName.
+    fn email(&self) -> String {
+        self.emails
+            .iter()
+            .find(|e| e.primary)
+            .or(self.emails.first())
+            .map(|e| e.value.clone())
+            .unwrap_or_else(|| self.user_name.clone())
+    }
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct Email {
+    value: String,
+    primary: bool,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, rename_all = "camelCase")]
+struct GroupResource {
+    display_name: String,
+    members: Vec<Member>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+struct Member {
+    value: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct PatchRequest {
+    operations: Vec<PatchOperation>,
+}
+
+#[derive(Deserialize)]
+struct PatchOperation {
+    op: String,
+    #[serde(default)]
+    path: String,
+    #[serde(default)]
+    value: Value,
+}
+
+pub fn routes() -> impl Filter<Extract = (Response,), Error = Rejection> + Clone {
+    warp::path!("scim" / "v2" / ..)
+        .and(warp::path::tail())
+        .and(warp::method())
+        .and(warp::header::optional::<String>("authorization"))
+        .and(warp::query::<HashMap<String, String>>())
+        .and(warp::body::bytes())
+        .and_then(handler)
+}
+
+async fn handler(
+    tail: Tail,
+    method: Method,
+    authorization: Option<String>,
+    query: HashMap<String, String>,
+    body: Bytes,
+) -> Result<Response, Rejection> {
+    let conf = config::get();
+    if !conf.user_authentication.scim.enabled {
+        return Err(warp::reject::not_found());
+    }
+
+    let token = authorization
+        .as_deref()
+        .and_then(|v| v.strip_prefix("Bearer "));
+    let token_valid = match token {
+        Some(token) => token
+            .as_bytes()
+            .ct_eq(conf.user_authentication.scim.bearer_token.as_bytes())
+            .into(),
+        None => false,
+    };
+    if conf.user_authentication.scim.bearer_token.is_empty() || !token_valid {
+        return Ok(error_reply(
+            StatusCode::UNAUTHORIZED,
+            "Invalid bearer token",
+        ));
+    }
+
+    let path: Vec<&str> = tail.as_str().split('/').filter(|v| !v.is_empty()).collect();
+    Ok(match handle(method.as_str(), &path, &query, &body).await {
+        Ok(v) => v,
+        Err(e) => {
+            let status = match e {
+                Error::NotFound(_) => StatusCode::NOT_FOUND,
+                Error::AlreadyExists(_) => StatusCode::CONFLICT,
+                Error::InvalidEmail | Error::Validation(_) => StatusCode::BAD_REQUEST,
+                _ => {
+                    error!(error = %e.full(), "Handle SCIM request error");
+                    StatusCode::INTERNAL_SERVER_ERROR
+                }
+            };
+            error_reply(status, &e.to_string())
+        }
+    })
+}
+
+async fn handle(
+    method: &str,
+    path: &[&str],
+    query: &HashMap<String, String>,
+    body: &[u8],
+) -> Result<Response, Error> {
+    match (method, path) {
+        ("GET", ["Users"]) => list_users(query).await,
+        ("POST", ["Users"]) => create_user(parse(body)?).await,
+        ("GET", ["Users", id]) => get_user(id).await,
+        ("PUT", ["Users", id]) => replace_user(id, parse(body)?).await,
+        ("PATCH", ["Users", id]) => patch_user(id, parse(body)?).await,
+        ("DELETE", ["Users", id]) => delete_user(id).await,
+        ("GET", ["Groups"]) => list_groups(query).await,
+        ("POST", ["Groups"]) => create_group(parse(body)?).await,
+        ("GET", ["Groups", id]) => get_group(id).await,
+        ("PUT", ["Groups", id]) => replace_group(id, parse(body)?).await,
+        ("PATCH", ["Groups", id]) => patch_group(id, parse(body)?).await,
+        ("DELETE", ["Groups", id]) => delete_group(id).await,
+        _ => Err(Error::NotFound(path.join("/"))),
+    }
+}
+
+async fn list_users(query: &HashMap<String, String>) -> Result<Response, Error> {
+    let (start_index, count) = get_pagination(query);
+
+    let (users, total) = match query.get("filter") {
+        Some(filter) => {
+            let (attr, value) = parse_filter(filter)?;
+            let res = match attr.to_lowercase().as_ref() {
+                "username" => user::get_by_email(&value).await,
+                "externalid" => user::get_by_external_id(&value).await,
+                _ => {
+                    return Err(Error::Validation(format!(
+                        "Unsupported filter attribute: {}",
+                        attr
+                    )));
+                }
+            }
+            .and_then(is_scim_user);
+
+            match res {
+                Ok(v) => (vec![v], 1),
+                Err(Error::NotFound(_)) => (vec![], 0),
+                Err(e) => return Err(e),
+            }
+        }
+        None => (
+            user::list_external(count, start_index - 1).await?,
+            user::get_external_count().await?,
+        ),
+    };
+
+    Ok(list_reply(
+        start_index,
+        total,
+        users.iter().map(user_to_json).collect(),
+    ))
+}
+
+async fn create_user(r: UserResource) -> Result<Response, Error> {
+    let external_id = r
+        .external_id
+        .clone()
+        .filter(|v| !v.is_empty())
+        .ok_or_else(|| Error::Validation("externalId is required".into()))?;
+
+    match user::get_by_external_id(&external_id).await {
+        Ok(_) => return Err(Error::AlreadyExists(external_id)),
+        Err(Error::NotFound(_)) => {}
+        Err(e) => return Err(e),
+    }
+
+    // Existing users are never linked by email, as this would hand over a (local) account to
+    // the identity provider. The email must be unique, thus this fails if the user exists.
+    let u = user::create(user::User {
+        external_id: Some(external_id),
+        email: r.email(),
+        email_verified: true,
+        is_active: r.active.unwrap_or(true),
+        ..Default::default()
+    })
+    .await?;
+
+    Ok(reply(StatusCode::CREATED, &user_to_json(&u)))
+}
+
+async fn get_user(id: &str) -> Result<Response, Error> {
+    let u = get_scim_user(id).await?;
+    Ok(reply(StatusCode::OK, &user_to_json(&u)))
+}
+
+async fn replace_user(id: &str, r: UserResource) -> Result<Response, Error> {
+    let mut u = get_scim_user(id).await?;
+    u.email = r.email();
+    u.is_active = r.active.unwrap_or(true);
+    if let Some(external_id) = r.external_id.filter(|v| !v.is_empty()) {
+        u.external_id = Some(external_id);
+    }
+
+    let u = user::update(u).await?;
+    Ok(reply(StatusCode::OK, &user_to_json(&u)))
+}
+
+async fn patch_user(id: &str, req: PatchRequest) -> Result<Response, Error> {
+    let mut u = get_scim_user(id).await?;
+
+    for op in &req.operations {
+        let op_name = op.op.to_lowercase();
+        if op_name != "add" && op_name != "replace" {
+            continue;
+        }
+
+        if op.path.is_empty() {
+            if let Value::Object(attrs) = &op.value {
+                for (k, v) in attrs {
+                    set_user_attribute(&mut u, k, v)?;
+                }
+            }
+        } else {
+            set_user_attribute(&mut u, &op.path, &op.value)?;
+        }
+    }
+
+    let u = user::update(u).await?;
+    Ok(reply(StatusCode::OK, &user_to_json(&u)))
+}
+
+async fn delete_user(id: &str) -> Result<Response, Error> {
+    let u = get_scim_user(id).await?;
+    user::delete(&u.id).await?;
+    Ok(warp::reply::with_status(warp::reply(), StatusCode::NO_CONTENT).into_response())
+}
+
+async fn list_groups(query: &HashMap<String, String>) -> Result<Response, Error> {
+    let (start_index, count) = get_pagination(query);
+    let conf = config::get();
+
+    let mut groups: Vec<&config::ScimGroup> = conf.user_authentication.scim.groups.iter().collect();
+    if let Some(filter) = query.get("filter") {
+        let (attr, value) = parse_filter(filter)?;
+        if attr.to_lowercase() != "displayname" {
+            return Err(Error::Validation(format!(
+                "Unsupported filter attribute: {}",
+                attr
+            )));
+        }
+        groups.retain(|g| g.display_name == value);
+    }
+
+    let total = groups.len() as i64;
+    let mut resources: Vec<Value> = Vec::new();
+    for g in groups
+        .into_iter()
+        .skip((start_index - 1) as usize)
+        .take(count as usize)
+    {
+        resources.push(group_to_json(g, &get_members(g).await?));
+    }
+
+    Ok(list_reply(start_index, total, resources))
+}
+
+async fn create_group(r: GroupResource) -> Result<Response, Error> {
+    let g = get_group_config(&r.display_name)
+        .map_err(|_| Error::Validation("Group is not mapped to a tenant".into()))?;
+    set_members(&g, &parse_members(&r.members)?).await?;
+    Ok(reply(
+        StatusCode::CREATED,
+        &group_to_json(&g, &get_members(&g).await?),
+    ))
+}
+
+async fn get_group(id: &str) -> Result<Response, Error> {
+    let g = get_group_config(id)?;
+    Ok(reply(
+        StatusCode::OK,
+        &group_to_json(&g, &get_members(&g).await?),
+    ))
+}
+
+async fn replace_group(id: &str, r: GroupResource) -> Result<Response, Error> {
+    let g = get_group_config(id)?;
+    set_members(&g, &parse_members(&r.members)?).await?;
+    Ok(reply(
+        StatusCode::OK,
+        &group_to_json(&g, &get_members(&g).await?),
+    ))
+}
+
+async fn patch_group(id: &str, req: PatchRequest) -> Result<Response, Error> {
+    let g = get_group_config(id)?;
+
+    for op in &req.operations {
+        let members: Vec<Member> = match &op.value {
+            Value::Array(_) => serde_json::from_value(op.value.clone())
+                .map_err(|e| Error::Validation(e.to_string()))?,
+            Value::Object(o) => match o.get("members") {
+                Some(v) => serde_json::from_value(v.clone())
+                    .map_err(|e| Error::Validation(e.to_string()))?,
+                None => continue,
+            },
+            _ => Vec::new(),
+        };
+        let mut user_ids = parse_members(&members)?;
+
+        // e.g. members[value eq "2819c223-7f76-453a-919d-413861904646"]
+        if let Some(filter) = op
+            .path
+            .strip_prefix("members[")
+            .and_then(|v| v.strip_suffix(']'))
+        {
+            let (_, value) = parse_filter(filter)?;
+            user_ids.push(parse_id(&value)?);
+        } else if !op.path.is_empty() && op.path != "members" {
+            continue;
+        }
+
+        match op.op.to_lowercase().as_ref() {
+            "add" => {
+                for user_id in &user_ids {
+                    add_member(&g, user_id).await?;
+                }
+            }
+            "remove" => {
+                if user_ids.is_empty() {
+                    set_members(&g, &[]).await?;
+                }
+                for user_id in &user_ids {
+                    remove_member(&g, user_id).await?;
+                }
+            }
+            "replace" => {
+                set_members(&g, &user_ids).await?;
+            }
+            _ => {
+                return Err(Error::Validation(format!("Unsupported op: {}", op.op)));
+            }
+        }
+    }
+
+    Ok(reply(
+        StatusCode::OK,
+        &group_to_json(&g, &get_members(&g).await?),
+    ))
+}
+
+async fn delete_group(id: &str) -> Result<Response, Error> {
+    let g = get_group_config(id)?;
+    set_members(&g, &[]).await?;
+    Ok(warp::reply::with_status(warp::reply(), StatusCode::NO_CONTENT).into_response())
+}
+
+fn get_group_config(id: &str) -> Result<config::ScimGroup, Error> {
+    let conf = config::get();
+    let id = urlencoding::decode(id).map_err(|e| Error::Validation(e.to_string()))?;
+
+    conf.user_authentication
+        .scim
+        .groups
+        .iter()
+        .find(|g| g.display_name == id)
+        .cloned()
+        .ok_or_else(|| Error::NotFound(id.to_string()))
+}
+
+// Returns the members of the group, these are the tenant users which were added through the
+// group.
+async fn get_members(g: &config::ScimGroup) -> Result<Vec<tenant::TenantUserListItem>, Error> {
+    let mut out = Vec::new();
+    loop {
+        let items = tenant::get_users(&g.tenant_id, 100, out.len() as i64).await?;
+        if items.is_empty() {
+            break;
+        }
+        out.extend(items);
+    }
+
+    Ok(out
+        .into_iter()
+        .filter(|tu| tu.scim_group.as_ref() == Some(&g.display_name))
+        .collect())
+}
+
+async fn set_members(g: &config::ScimGroup, user_ids: &[Uuid]) -> Result<(), Error> {
+    let members = get_members(g).await?;
+
+    for tu in &members {
+        if !user_ids.contains(&tu.user_id) {
+            remove_member(g, &tu.user_id).await?;
+        }
+    }
+
+    for user_id in user_ids {
+        if !members.iter().any(|tu| tu.user_id == *user_id) {
+            add_member(g, user_id).await?;
+        }
+    }
+
+    Ok(())
+}
+
+// Adds the user to the tenant. An existing membership (e.g. added manually or through an other
+// group) is left untouched, as this might otherwise change the permissions of the user.
+async fn add_member(g: &config::ScimGroup, user_id: &Uuid) -> Result<(), Error> {
+    // Validate that the user exists and is provisioned through SCIM.
+    is_scim_user(user::get(user_id).await?)?;
+
+    match tenant::get_user(&g.tenant_id, user_id).await {
+        Ok(_) => Ok(()),
+        Err(Error::NotFound(_)) => {
+            tenant::add_user(tenant::TenantUser {
+                tenant_id: g.tenant_id,
+                user_id: *user_id,
+                is_admin: g.is_admin,
+                is_device_admin: g.is_device_admin,
+                is_gateway_admin: g.is_gateway_admin,
+                scim_group: Some(g.display_name.clone()),
+                ..Default::default()
+            })
+            .await?;
+            Ok(())
+        }
+        Err(e) => Err(e),
+    }
+}
+
+// Removes the user from the tenant, unless the membership was not added through this group.
+async fn remove_member(g: &config::ScimGroup, user_id: &Uuid) -> Result<(), Error> {
+    match tenant::get_user(&g.tenant_id, user_id).await {
+        Ok(tu) => {
+            if tu.scim_group.as_ref() == Some(&g.display_name) {
+                tenant::delete_user(&g.tenant_id, user_id).await?;
+            }
+            Ok(())
+        }
+        Err(Error::NotFound(_)) => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+// Returns the user, if it can be managed through SCIM.
+async fn get_scim_user(id: &str) -> Result<user::User, Error> {
+    is_scim_user(user::get(&parse_id(id)?).await?)
+}
+
+// Users without external ID and global admin users can not be managed through SCIM. These are
+// reported as not found, such that their existence is not exposed.
+fn is_scim_user(u: user::User) -> Result<user::User, Error> {
+    if u.external_id.is_none() || u.is_admin {
+        return Err(Error::NotFound(u.id.to_string()));
+    }
+    Ok(u)
+}
+
+fn set_user_attribute(u: &mut user::User, attr: &str, value: &Value) -> Result<(), Error> {
+    match attr.to_lowercase().as_ref() {
+        "active" => {
+            // Some identity providers send the boolean as string ("True" / "False").
+            u.is_active = match value {
+                Value::Bool(v) => *v,
+                Value::String(v) => bool::from_str(&v.to_lowercase())
+                    .map_err(|e| Error::Validation(e.to_string()))?,
+                _ => return Err(Error::Validation("active must be a boolean".into())),
+            };
+        }
+        "username" => {
+            u.email = value
+                .as_str()
+                .ok_or_else(|| Error::Validation("userName must be a string".into()))?
+                .to_string();
+        }
+        "externalid" => {
+            u.external_id = Some(
+                value
+                    .as_str()
+                    .filter(|v| !v.is_empty())
+                    .ok_or_else(|| Error::Validation("externalId must be a string".into()))?
+                    .to_string(),
+            );
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
+fn user_to_json(u: &user::User) -> Value {
+    json!({
+        "schemas": [SCHEMA_USER],
+        "id": u.id,
+        "externalId": u.external_id,
+        "userName": u.email,
+        "active": u.is_active,
+        "emails": [{
+            "value": u.email,
+            "primary": true,
+        }],
+        "meta": {
+            "resourceType": "User",
+            "created": u.created_at.to_rfc3339(),
+            "lastModified": u.updated_at.to_rfc3339(),
+            "location": format!("/scim/v2/Users/{}", u.id),
+        },
+    })
+}
+
+fn group_to_json(g: &config::ScimGroup, members: &[tenant::TenantUserListItem]) -> Value {
+    json!({
+        "schemas": [SCHEMA_GROUP],
+        "id": g.display_name,
+        "displayName": g.display_name,
+        "members": members.iter().map(|tu| json!({
+            "value": tu.user_id,
+            "display": tu.email,
+        })).collect::<Vec<Value>>(),
+        "meta": {
+            "resourceType": "Group",
+            "location": format!("/scim/v2/Groups/{}", urlencoding::encode(&g.display_name)),
+        },
+    })
+}
+
+fn list_reply(start_index: i64, total: i64, resources: Vec<Value>) -> Response {
+    reply(
+        StatusCode::OK,
+        &json!({
+            "schemas": [SCHEMA_LIST_RESPONSE],
+            "totalResults": total,
+            "startIndex": start_index,
+            "itemsPerPage": resources.len(),
+            "Resources": resources,
+        }),
+    )
+}
+
+fn error_reply(status: StatusCode, detail: &str) -> Response {
+    reply(
+        status,
+        &json!({
+            "schemas": [SCHEMA_ERROR],
+            "status": status.as_str(),
+            "detail": detail,
+        }),
+    )
+}
+
+fn reply(status: StatusCode, v: &Value) -> Response {
+    let mut resp = warp::reply::with_status(warp::reply::json(v), status).into_response();
+    resp.headers_mut().insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("application/scim+json"),
+    );
+    resp
+}
+
+fn parse<T: DeserializeOwned>(body: &[u8]) -> Result<T, Error> {
+    serde_json::from_slice(body).map_err(|e| Error::Validation(e.to_string()))
+}
+
+fn parse_id(id: &str) -> Result<Uuid, Error> {
+    Uuid::from_str(id).map_err(|_| Error::NotFound(id.to_string()))
+}
+
+fn parse_members(members: &[Member]) -> Result<Vec<Uuid>, Error> {
+    members
+        .iter()
+        .map(|m| Uuid::from_str(&m.value).map_err(|e| Error::Validation(e.to_string())))
+        .collect()
+}
+
+// Returns the (1-based) start index and count.
+fn get_pagination(query: &HashMap<String, String>) -> (i64, i64) {
+    let start_index = query
+        .get("startIndex")
+        .and_then(|v| v.parse::<i64>().ok())
+        .unwrap_or(1)
+        .max(1);
+    let count = query
+        .get("count")
+        .and_then(|v| v.parse::<i64>().ok())
+        .unwrap_or(100)
+        .clamp(0, 1000);
+    (start_index, count)
+}
+
+// Parses a filter of the form 'attribute eq "value"', which is the only filter that is
+// supported.
+fn parse_filter(filter: &str) -> Result<(String, String), Error> {
+    let parts: Vec<&str> = filter.trim().splitn(3, ' ').collect();
+    if parts.len() != 3 || parts[1].to_lowercase() != "eq" {
+        return Err(Error::Validation(format!("Unsupported filter: {}", filter)));
+    }
+
+    Ok((parts[0].to_string(), parts[2].trim_matches('"').to_string()))
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use crate::storage::tenant::test::create_tenant;
+    use crate::test;
+
+    async fn request(method: &str, path: &str, body: Value) -> (StatusCode, Value) {
+        let path: Vec<&str> = path.split('/').collect();
+        let resp = handle(
+            method,
+            &path,
+            &HashMap::new(),
+            &serde_json::to_vec(&body).unwrap(),
+        )
+        .await
+        .unwrap();
+        let status = resp.status();
+        let b = hyper::body::to_bytes(resp.into_body()).await.unwrap();
+        (status, serde_json::from_slice(&b).unwrap_or(Value::Null))
+    }
+
+    #[tokio::test]
+    async fn test_scim() {
+        let _guard = test::prepare().await;
+        let t = create_tenant().await;
+
+        let mut conf = (*config::get()).clone();
+        conf.user_authentication.scim.enabled = true;
+        conf.user_authentication.scim.bearer_token = "secret".into();
+        conf.user_authentication.scim.groups = vec![config::ScimGroup {
+            display_name: "acme admins".into(),
+            tenant_id: t.id,
+            is_admin: true,
+            ..Default::default()
+        }];
+        config::set(conf);
+
+        // create user
+        let (status, u) = request(
+            "POST",
+            "Users",
+            json!({
+                "schemas": [SCHEMA_USER],
+                "externalId": "idp-1",
+                "userName": "scim@example.com",
+                "active": true,
+            }),
+        )
+        .await;
+        assert_eq!(StatusCode::CREATED, status);
+        assert_eq!("scim@example.com", u["userName"]);
+        let user_id = Uuid::from_str(u["id"].as_str().unwrap()).unwrap();
+
+        let u = user::get_by_external_id("idp-1").await.unwrap();
+        assert_eq!(user_id, u.id);
+        assert!(u.is_active);
+
+        // create user with same external id
+        assert!(matches!(
+            handle(
+                "POST",
+                &["Users"],
+                &HashMap::new(),
+                br#"{"externalId": "idp-1", "userName": "other@example.com"}"#,
+            )
+            .await,
+            Err(Error::AlreadyExists(_))
+        ));
+
+        // filter by external id
+        let query: HashMap<String, String> =
+            [("filter".to_string(), r#"externalId eq "idp-1""#.to_string())]
+                .into_iter()
+                .collect();
+        let resp = handle("GET", &["Users"], &query, b"").await.unwrap();
+        let b = hyper::body::to_bytes(resp.into_body()).await.unwrap();
+        let list: Value = serde_json::from_slice(&b).unwrap();
+        assert_eq!(1, list["totalResults"]);
+        assert_eq!(user_id.to_string(), list["Resources"][0]["id"]);
+
+        // add to group
+        let (status, g) = request(
+            "PATCH",
+            "Groups/acme%20admins",
+            json!({
+                "Operations": [{
+                    "op": "add",
+                    "path": "members",
+                    "value": [{"value": user_id}],
+                }],
+            }),
+        )
+        .await;
+        assert_eq!(StatusCode::OK, status);
+        assert_eq!(user_id.to_string(), g["members"][0]["value"]);
+        assert!(tenant::get_user(&t.id, &user_id).await.unwrap().is_admin);
+
+        // deactivate user
+        let (status, u) = request(
+            "PATCH",
+            &format!("Users/{}", user_id),
+            json!({
+                "Operations": [{
+                    "op": "Replace",
+                    "path": "active",
+                    "value": "False",
+                }],
+            }),
+        )
+        .await;
+        assert_eq!(StatusCode::OK, status);
+        assert_eq!(false, u["active"]);
+        let u = user::get(&user_id).await.unwrap();
+        assert!(!u.is_active);
+        assert!(u.sessions_revoked_at.is_some());
+
+        // remove from group
+        let (status, g) = request(
+            "PATCH",
+            "Groups/acme%20admins",
+            json!({
+                "Operations": [{
+                    "op": "remove",
+                    "path": format!("members[value eq \"{}\"]", user_id),
+                }],
+            }),
+        )
+        .await;
+        assert_eq!(StatusCode::OK, status);
+        assert_eq!(0, g["members"].as_array().unwrap().len());
+        assert!(tenant::get_user(&t.id, &user_id).await.is_err());
+
+        // unknown group
+        assert!(matches!(
+            handle("GET", &["Groups", "other"], &HashMap::new(), b"").await,
+            Err(Error::NotFound(_))
+        ));
+
+        // local (global admin) user
+        let local = user::create(user::User {
+            email: "local@example.com".into(),
+            is_admin: true,
+            is_active: true,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        tenant::add_user(tenant::TenantUser {
+            tenant_id: t.id,
+            user_id: local.id,
+            is_admin: true,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        // the local user is not linked by email
+        assert!(matches!(
+            handle(
+                "POST",
+                &["Users"],
+                &HashMap::new(),
+                br#"{"externalId": "idp-2", "userName": "local@example.com"}"#,
+            )
+            .await,
+            Err(Error::AlreadyExists(_))
+        ));
+        assert!(user::get(&local.id).await.unwrap().external_id.is_none());
+
+        // the local user can not be read or modified
+        assert!(matches!(
+            handle(
+                "GET",
+                &["Users", &local.id.to_string()],
+                &HashMap::new(),
+                b""
+            )
+            .await,
+            Err(Error::NotFound(_))
+        ));
+        assert!(matches!(
+            handle(
+                "DELETE",
+                &["Users", &local.id.to_string()],
+                &HashMap::new(),
+                b""
+            )
+            .await,
+            Err(Error::NotFound(_))
+        ));
+        let resp = handle("GET", &["Users"], &HashMap::new(), b"")
+            .await
+            .unwrap();
+        let b = hyper::body::to_bytes(resp.into_body()).await.unwrap();
+        let list: Value = serde_json::from_slice(&b).unwrap();
+        assert_eq!(1, list["totalResults"]);
+
+        // replacing the group members does not remove the manually added tenant user
+        let (status, g) = request(
+            "PUT",
+            "Groups/acme%20admins",
+            json!({
+                "displayName": "acme admins",
+                "members": [{"value": user_id}],
+            }),
+        )
+        .await;
+        assert_eq!(StatusCode::OK, status);
+        assert_eq!(1, g["members"].as_array().unwrap().len());
+        assert_eq!(user_id.to_string(), g["members"][0]["value"]);
+        assert!(tenant::get_user(&t.id, &local.id).await.unwrap().is_admin);
+        assert_eq!(
+            Some("acme admins".to_string()),
+            tenant::get_user(&t.id, &user_id).await.unwrap().scim_group
+        );
+
+        let (status, _) = request(
+            "PUT",
+            "Groups/acme%20admins",
+            json!({
+                "displayName": "acme admins",
+                "members": [],
+            }),
+        )
+        .await;
+        assert_eq!(StatusCode::OK, status);
+        assert!(tenant::get_user(&t.id, &user_id).await.is_err());
+        assert!(tenant::get_user(&t.id, &local.id).await.is_ok());
+
+        // adding a user to a group does not change an existing membership
+        tenant::add_user(tenant::TenantUser {
+            tenant_id: t.id,
+            user_id,
+            is_admin: true,
+            is_device_admin: true,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let (status, g) = request(
+            "PATCH",
+            "Groups/acme%20admins",
+            json!({
+                "Operations": [{
+                    "op": "add",
+                    "value": [{"value": user_id}],
+                }],
+            }),
+        )
+        .await;
+        assert_eq!(StatusCode::OK, status);
+        assert_eq!(0, g["members"].as_array().unwrap().len());
+        let tu = tenant::get_user(&t.id, &user_id).await.unwrap();
+        assert!(tu.is_device_admin);
+        assert!(tu.scim_group.is_none());
+
+        // delete user
+        let (status, _) = request("DELETE", &format!("Users/{}", user_id), Value::Null).await;
+        assert_eq!(StatusCode::NO_CONTENT, status);
+        assert!(user::get(&user_id).await.is_err());
+    }
+}
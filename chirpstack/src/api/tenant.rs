@@ -1,15 +1,19 @@
 use std::str::FromStr;
+use std::time::SystemTime;
 
+use chrono::{DateTime, Local, Utc};
 use tonic::{Request, Response, Status};
 use uuid::Uuid;
 
 use chirpstack_api::api;
 use chirpstack_api::api::tenant_service_server::TenantService;
 
+use super::audit;
 use super::auth::{validator, AuthID};
 use super::error::ToStatus;
-use super::helpers;
-use crate::storage::{fields, tenant, user};
+use super::helpers::{self, FromProto, ToProto};
+use crate::integration;
+use crate::storage::{application, fields, tenant, tenant_role, tenant_usage, user};
 
 pub struct Tenant {
     validator: validator::RequestValidator,
@@ -60,6 +64,8 @@ impl TenantService for Tenant {
         });
         resp.metadata_mut()
             .insert("x-log-tenant_id", t.id.to_string().parse().unwrap());
+        resp.extensions_mut()
+            .insert(audit::Changes::new(None, Some(&t)));
 
         Ok(resp)
     }
@@ -120,8 +126,10 @@ impl TenantService for Tenant {
             )
             .await?;
 
+        let t_before = tenant::get(&tenant_id).await.map_err(|e| e.status())?;
+
         // update
-        let _ = tenant::update(tenant::Tenant {
+        let t = tenant::update(tenant::Tenant {
             id: tenant_id,
             name: req_tenant.name.clone(),
             description: req_tenant.description.clone(),
@@ -139,6 +147,8 @@ impl TenantService for Tenant {
         let mut resp = Response::new(());
         resp.metadata_mut()
             .insert("x-log-tenant_id", req_tenant.id.parse().unwrap());
+        resp.extensions_mut()
+            .insert(audit::Changes::new(Some(&t_before), Some(&t)));
 
         Ok(resp)
     }
@@ -157,11 +167,52 @@ impl TenantService for Tenant {
             )
             .await?;
 
+        let t = tenant::get(&tenant_id).await.map_err(|e| e.status())?;
         tenant::delete(&tenant_id).await.map_err(|e| e.status())?;
+        for app_id in application::get_ids_for_tenant(&tenant_id)
+            .await
+            .map_err(|e| e.status())?
+        {
+            integration::invalidate_cache(&app_id).await;
+        }
+
+        let mut resp = Response::new(());
+        resp.metadata_mut()
+            .insert("x-log-tenant_id", req.id.parse().unwrap());
+        resp.extensions_mut()
+            .insert(audit::Changes::new(Some(&t), None));
+
+        Ok(resp)
+    }
+
+    async fn restore(
+        &self,
+        request: Request<api::RestoreTenantRequest>,
+    ) -> Result<Response<()>, Status> {
+        let req = request.get_ref();
+        let tenant_id = Uuid::from_str(&req.id).map_err(|e| e.status())?;
+
+        self.validator
+            .validate(
+                request.extensions(),
+                validator::ValidateTenantAccess::new(validator::Flag::Delete, tenant_id),
+            )
+            .await?;
+
+        tenant::restore(&tenant_id).await.map_err(|e| e.status())?;
+        for app_id in application::get_ids_for_tenant(&tenant_id)
+            .await
+            .map_err(|e| e.status())?
+        {
+            integration::invalidate_cache(&app_id).await;
+        }
+        let t = tenant::get(&tenant_id).await.map_err(|e| e.status())?;
 
         let mut resp = Response::new(());
         resp.metadata_mut()
             .insert("x-log-tenant_id", req.id.parse().unwrap());
+        resp.extensions_mut()
+            .insert(audit::Changes::new(None, Some(&t)));
 
         Ok(resp)
     }
@@ -179,7 +230,10 @@ impl TenantService for Tenant {
 
         let auth_id = request.extensions().get::<AuthID>().unwrap();
         let req = request.get_ref();
-        let mut filters = tenant::Filters::default();
+        let mut filters = tenant::Filters {
+            deleted: req.deleted,
+            ..Default::default()
+        };
 
         if !req.search.is_empty() {
             filters.search = Some(req.search.clone());
@@ -229,6 +283,10 @@ impl TenantService for Tenant {
                     private_gateways_down: t.private_gateways_down,
                     max_gateway_count: t.max_gateway_count as u32,
                     max_device_count: t.max_device_count as u32,
+                    deleted_at: t
+                        .deleted_at
+                        .as_ref()
+                        .map(helpers::datetime_to_prost_timestamp),
                 })
                 .collect(),
         }))
@@ -257,7 +315,7 @@ impl TenantService for Tenant {
             )
             .await?;
 
-        let _ = tenant::add_user(tenant::TenantUser {
+        let tu = tenant::add_user(tenant::TenantUser {
             tenant_id,
             user_id,
             is_admin: req_user.is_admin,
@@ -273,6 +331,8 @@ impl TenantService for Tenant {
             .insert("x-log-tenant_id", req_user.tenant_id.parse().unwrap());
         resp.metadata_mut()
             .insert("x-log-user_id", user_id.to_string().parse().unwrap());
+        resp.extensions_mut()
+            .insert(audit::Changes::new(None, Some(&tu)));
 
         Ok(resp)
     }
@@ -341,7 +401,11 @@ impl TenantService for Tenant {
             )
             .await?;
 
-        tenant::update_user(tenant::TenantUser {
+        let tu_before = tenant::get_user(&tenant_id, &user_id)
+            .await
+            .map_err(|e| e.status())?;
+
+        let tu = tenant::update_user(tenant::TenantUser {
             tenant_id,
             user_id,
             is_admin: req_user.is_admin,
@@ -357,6 +421,8 @@ impl TenantService for Tenant {
             .insert("x-log-tenant_id", req_user.tenant_id.parse().unwrap());
         resp.metadata_mut()
             .insert("x-log-user_id", req_user.user_id.parse().unwrap());
+        resp.extensions_mut()
+            .insert(audit::Changes::new(Some(&tu_before), Some(&tu)));
 
         Ok(resp)
     }
@@ -389,6 +455,9 @@ impl TenantService for Tenant {
             }
         }
 
+        let tu = tenant::get_user(&tenant_id, &user_id)
+            .await
+            .map_err(|e| e.status())?;
         tenant::delete_user(&tenant_id, &user_id)
             .await
             .map_err(|e| e.status())?;
@@ -398,6 +467,8 @@ impl TenantService for Tenant {
             .insert("x-log-tenant_id", req.tenant_id.parse().unwrap());
         resp.metadata_mut()
             .insert("x-log-user_id", req.user_id.parse().unwrap());
+        resp.extensions_mut()
+            .insert(audit::Changes::new(Some(&tu), None));
 
         Ok(resp)
     }
@@ -444,6 +515,397 @@ impl TenantService for Tenant {
 
         Ok(resp)
     }
+
+    async fn create_role(
+        &self,
+        request: Request<api::CreateTenantRoleRequest>,
+    ) -> Result<Response<api::CreateTenantRoleResponse>, Status> {
+        let req_role = match &request.get_ref().role {
+            Some(v) => v,
+            None => {
+                return Err(Status::invalid_argument("role is missing"));
+            }
+        };
+        let tenant_id = Uuid::from_str(&req_role.tenant_id).map_err(|e| e.status())?;
+
+        self.validator
+            .validate(
+                request.extensions(),
+                validator::ValidateTenantRolesAccess::new(validator::Flag::Create, tenant_id),
+            )
+            .await?;
+
+        let mut r = tenant_role::TenantRole {
+            tenant_id,
+            name: req_role.name.clone(),
+            description: req_role.description.clone(),
+            ..Default::default()
+        };
+        r.set_permissions(
+            &req_role
+                .permissions()
+                .map(|p| p.from_proto())
+                .collect::<Vec<tenant_role::Permission>>(),
+        );
+
+        let r = tenant_role::create(r).await.map_err(|e| e.status())?;
+
+        let mut resp = Response::new(api::CreateTenantRoleResponse {
+            id: r.id.to_string(),
+        });
+        resp.metadata_mut()
+            .insert("x-log-tenant_id", req_role.tenant_id.parse().unwrap());
+        resp.metadata_mut()
+            .insert("x-log-role_id", r.id.to_string().parse().unwrap());
+        resp.extensions_mut()
+            .insert(audit::Changes::new(None, Some(&r)));
+
+        Ok(resp)
+    }
+
+    async fn get_role(
+        &self,
+        request: Request<api::GetTenantRoleRequest>,
+    ) -> Result<Response<api::GetTenantRoleResponse>, Status> {
+        let req = request.get_ref();
+        let role_id = Uuid::from_str(&req.id).map_err(|e| e.status())?;
+
+        self.validator
+            .validate(
+                request.extensions(),
+                validator::ValidateTenantRoleAccess::new(validator::Flag::Read, role_id),
+            )
+            .await?;
+
+        let r = tenant_role::get(&role_id).await.map_err(|e| e.status())?;
+
+        let mut resp = Response::new(api::GetTenantRoleResponse {
+            role: Some(api::TenantRole {
+                id: r.id.to_string(),
+                tenant_id: r.tenant_id.to_string(),
+                name: r.name.clone(),
+                description: r.description.clone(),
+                permissions: r
+                    .permissions()
+                    .into_iter()
+                    .map(|p| p.to_proto().into())
+                    .collect(),
+            }),
+            created_at: Some(helpers::datetime_to_prost_timestamp(&r.created_at)),
+            updated_at: Some(helpers::datetime_to_prost_timestamp(&r.updated_at)),
+        });
+        resp.metadata_mut()
+            .insert("x-log-role_id", req.id.parse().unwrap());
+
+        Ok(resp)
+    }
+
+    async fn update_role(
+        &self,
+        request: Request<api::UpdateTenantRoleRequest>,
+    ) -> Result<Response<()>, Status> {
+        let req_role = match &request.get_ref().role {
+            Some(v) => v,
+            None => {
+                return Err(Status::invalid_argument("role is missing"));
+            }
+        };
+        let role_id = Uuid::from_str(&req_role.id).map_err(|e| e.status())?;
+
+        self.validator
+            .validate(
+                request.extensions(),
+                validator::ValidateTenantRoleAccess::new(validator::Flag::Update, role_id),
+            )
+            .await?;
+
+        let before = tenant_role::get(&role_id).await.map_err(|e| e.status())?;
+        let mut r = tenant_role::TenantRole {
+            name: req_role.name.clone(),
+            description: req_role.description.clone(),
+            ..before.clone()
+        };
+        r.set_permissions(
+            &req_role
+                .permissions()
+                .map(|p| p.from_proto())
+                .collect::<Vec<tenant_role::Permission>>(),
+        );
+
+        let r = tenant_role::update(r).await.map_err(|e| e.status())?;
+
+        let mut resp = Response::new(());
+        resp.metadata_mut()
+            .insert("x-log-role_id", req_role.id.parse().unwrap());
+        resp.extensions_mut()
+            .insert(audit::Changes::new(Some(&before), Some(&r)));
+
+        Ok(resp)
+    }
+
+    async fn delete_role(
+        &self,
+        request: Request<api::DeleteTenantRoleRequest>,
+    ) -> Result<Response<()>, Status> {
+        let req = request.get_ref();
+        let role_id = Uuid::from_str(&req.id).map_err(|e| e.status())?;
+
+        self.validator
+            .validate(
+                request.extensions(),
+                validator::ValidateTenantRoleAccess::new(validator::Flag::Delete, role_id),
+            )
+            .await?;
+
+        let r = tenant_role::get(&role_id).await.map_err(|e| e.status())?;
+        tenant_role::delete(&role_id)
+            .await
+            .map_err(|e| e.status())?;
+
+        let mut resp = Response::new(());
+        resp.metadata_mut()
+            .insert("x-log-role_id", req.id.parse().unwrap());
+        resp.extensions_mut()
+            .insert(audit::Changes::new(Some(&r), None));
+
+        Ok(resp)
+    }
+
+    async fn list_roles(
+        &self,
+        request: Request<api::ListTenantRolesRequest>,
+    ) -> Result<Response<api::ListTenantRolesResponse>, Status> {
+        let req = request.get_ref();
+        let tenant_id = Uuid::from_str(&req.tenant_id).map_err(|e| e.status())?;
+
+        self.validator
+            .validate(
+                request.extensions(),
+                validator::ValidateTenantRolesAccess::new(validator::Flag::List, tenant_id),
+            )
+            .await?;
+
+        let count = tenant_role::get_count(&tenant_id)
+            .await
+            .map_err(|e| e.status())?;
+        let result = tenant_role::list(req.limit as i64, req.offset as i64, &tenant_id)
+            .await
+            .map_err(|e| e.status())?;
+
+        let mut resp = Response::new(api::ListTenantRolesResponse {
+            total_count: count as u32,
+            result: result
+                .iter()
+                .map(|r| api::TenantRoleListItem {
+                    id: r.id.to_string(),
+                    created_at: Some(helpers::datetime_to_prost_timestamp(&r.created_at)),
+                    updated_at: Some(helpers::datetime_to_prost_timestamp(&r.updated_at)),
+                    name: r.name.clone(),
+                    permissions: r
+                        .permissions()
+                        .into_iter()
+                        .map(|p| p.to_proto().into())
+                        .collect(),
+                })
+                .collect(),
+        });
+        resp.metadata_mut()
+            .insert("x-log-tenant_id", req.tenant_id.parse().unwrap());
+
+        Ok(resp)
+    }
+
+    async fn add_user_role(
+        &self,
+        request: Request<api::AddTenantUserRoleRequest>,
+    ) -> Result<Response<api::AddTenantUserRoleResponse>, Status> {
+        let req_ur = match &request.get_ref().user_role {
+            Some(v) => v,
+            None => {
+                return Err(Status::invalid_argument("user_role is missing"));
+            }
+        };
+        let tenant_id = Uuid::from_str(&req_ur.tenant_id).map_err(|e| e.status())?;
+        let user_id = Uuid::from_str(&req_ur.user_id).map_err(|e| e.status())?;
+        let role_id = Uuid::from_str(&req_ur.role_id).map_err(|e| e.status())?;
+        let application_id = if req_ur.application_id.is_empty() {
+            None
+        } else {
+            Some(Uuid::from_str(&req_ur.application_id).map_err(|e| e.status())?)
+        };
+
+        self.validator
+            .validate(
+                request.extensions(),
+                validator::ValidateTenantUserAccess::new(
+                    validator::Flag::Update,
+                    tenant_id,
+                    user_id,
+                ),
+            )
+            .await?;
+
+        let ur = tenant_role::add_user_role(tenant_role::TenantUserRole {
+            tenant_id,
+            user_id,
+            role_id,
+            application_id,
+            ..Default::default()
+        })
+        .await
+        .map_err(|e| e.status())?;
+
+        let mut resp = Response::new(api::AddTenantUserRoleResponse {
+            id: ur.id.to_string(),
+        });
+        resp.metadata_mut()
+            .insert("x-log-tenant_id", req_ur.tenant_id.parse().unwrap());
+        resp.metadata_mut()
+            .insert("x-log-user_id", req_ur.user_id.parse().unwrap());
+        resp.metadata_mut()
+            .insert("x-log-role_id", req_ur.role_id.parse().unwrap());
+
+        Ok(resp)
+    }
+
+    async fn remove_user_role(
+        &self,
+        request: Request<api::RemoveTenantUserRoleRequest>,
+    ) -> Result<Response<()>, Status> {
+        let req = request.get_ref();
+        let tenant_id = Uuid::from_str(&req.tenant_id).map_err(|e| e.status())?;
+        let user_id = Uuid::from_str(&req.user_id).map_err(|e| e.status())?;
+        let id = Uuid::from_str(&req.id).map_err(|e| e.status())?;
+
+        self.validator
+            .validate(
+                request.extensions(),
+                validator::ValidateTenantUserAccess::new(
+                    validator::Flag::Update,
+                    tenant_id,
+                    user_id,
+                ),
+            )
+            .await?;
+
+        tenant_role::delete_user_role(&tenant_id, &user_id, &id)
+            .await
+            .map_err(|e| e.status())?;
+
+        let mut resp = Response::new(());
+        resp.metadata_mut()
+            .insert("x-log-tenant_id", req.tenant_id.parse().unwrap());
+        resp.metadata_mut()
+            .insert("x-log-user_id", req.user_id.parse().unwrap());
+
+        Ok(resp)
+    }
+
+    async fn list_user_roles(
+        &self,
+        request: Request<api::ListTenantUserRolesRequest>,
+    ) -> Result<Response<api::ListTenantUserRolesResponse>, Status> {
+        let req = request.get_ref();
+        let tenant_id = Uuid::from_str(&req.tenant_id).map_err(|e| e.status())?;
+        let user_id = Uuid::from_str(&req.user_id).map_err(|e| e.status())?;
+
+        self.validator
+            .validate(
+                request.extensions(),
+                validator::ValidateTenantUserAccess::new(validator::Flag::Read, tenant_id, user_id),
+            )
+            .await?;
+
+        let result = tenant_role::get_user_roles(&tenant_id, &user_id)
+            .await
+            .map_err(|e| e.status())?;
+
+        let mut resp = Response::new(api::ListTenantUserRolesResponse {
+            result: result
+                .iter()
+                .map(|ur| api::TenantUserRole {
+                    id: ur.id.to_string(),
+                    tenant_id: ur.tenant_id.to_string(),
+                    user_id: ur.user_id.to_string(),
+                    role_id: ur.role_id.to_string(),
+                    application_id: ur.application_id.map(|v| v.to_string()).unwrap_or_default(),
+                    created_at: Some(helpers::datetime_to_prost_timestamp(&ur.created_at)),
+                })
+                .collect(),
+        });
+        resp.metadata_mut()
+            .insert("x-log-tenant_id", req.tenant_id.parse().unwrap());
+        resp.metadata_mut()
+            .insert("x-log-user_id", req.user_id.parse().unwrap());
+
+        Ok(resp)
+    }
+
+    async fn get_usage(
+        &self,
+        request: Request<api::GetTenantUsageRequest>,
+    ) -> Result<Response<api::GetTenantUsageResponse>, Status> {
+        let req = request.get_ref();
+        let tenant_id = Uuid::from_str(&req.tenant_id).map_err(|e| e.status())?;
+
+        self.validator
+            .validate(
+                request.extensions(),
+                validator::ValidateTenantAccess::new(validator::Flag::Read, tenant_id),
+            )
+            .await?;
+
+        let start = SystemTime::try_from(
+            req.start
+                .as_ref()
+                .ok_or_else(|| anyhow!("start is None"))
+                .map_err(|e| e.status())?
+                .clone(),
+        )
+        .map_err(|e| e.status())?;
+
+        let end = SystemTime::try_from(
+            req.end
+                .as_ref()
+                .ok_or_else(|| anyhow!("end is None"))
+                .map_err(|e| e.status())?
+                .clone(),
+        )
+        .map_err(|e| e.status())?;
+
+        let start: DateTime<Local> = start.into();
+        let end: DateTime<Local> = end.into();
+        let aggregation = req.aggregation().from_proto();
+
+        tenant_usage::validate_range(aggregation, start, end).map_err(|e| e.status())?;
+
+        let usage = tenant_usage::get(&tenant_id, aggregation, start, end)
+            .await
+            .map_err(|e| e.status())?;
+
+        let mut resp = Response::new(api::GetTenantUsageResponse {
+            result: usage
+                .iter()
+                .map(|row| {
+                    let ts: DateTime<Utc> = row.time.into();
+                    api::TenantUsage {
+                        time: Some(helpers::datetime_to_prost_timestamp(&ts)),
+                        uplinks: row.uplinks,
+                        uplink_bytes: row.uplink_bytes,
+                        downlinks: row.downlinks,
+                        downlink_bytes: row.downlink_bytes,
+                        active_devices: row.active_devices,
+                        gateway_airtime_ms: row.gateway_airtime_ms,
+                        roaming_uplinks: row.roaming_uplinks,
+                    }
+                })
+                .collect(),
+        });
+        resp.metadata_mut()
+            .insert("x-log-tenant_id", req.tenant_id.parse().unwrap());
+
+        Ok(resp)
+    }
 }
 
 #[cfg(test)]
@@ -452,6 +914,7 @@ pub mod test {
     use crate::api::auth::validator::RequestValidator;
     use crate::api::auth::AuthID;
     use crate::test;
+    use chirpstack_api::common;
 
     #[tokio::test]
     async fn test_tenant() {
@@ -521,7 +984,13 @@ pub mod test {
         };
         let mut up_req = Request::new(up_req);
         up_req.extensions_mut().insert(AuthID::User(u.id.clone()));
-        let _ = service.update(up_req).await.unwrap();
+        let up_resp = service.update(up_req).await.unwrap();
+        assert_eq!(
+            Some(&audit::Changes(serde_json::json!({
+                "name": {"before": "Test tenant", "after": "Test tenant updated"},
+            }))),
+            up_resp.extensions().get::<audit::Changes>()
+        );
 
         // get
         let get_req = api::GetTenantRequest {
@@ -549,6 +1018,7 @@ pub mod test {
             offset: 0,
             limit: 10,
             user_id: "".into(),
+            deleted: false,
         };
         let mut list_req = Request::new(list_req);
         list_req.extensions_mut().insert(AuthID::User(u.id.clone()));
@@ -556,6 +1026,43 @@ pub mod test {
         assert_eq!(1, list_resp.get_ref().total_count);
         assert_eq!(1, list_resp.get_ref().result.len());
 
+        // get usage
+        let tenant_id = Uuid::from_str(&create_resp.get_ref().id).unwrap();
+        tenant_usage::save_uplink(&tenant_id, &lrwn::EUI64::from_be_bytes([1; 8]), 20, false)
+            .await
+            .unwrap();
+        let now: prost_types::Timestamp = SystemTime::now().into();
+        let usage_req = api::GetTenantUsageRequest {
+            tenant_id: create_resp.get_ref().id.clone(),
+            start: Some(now.clone()),
+            end: Some(now),
+            aggregation: common::Aggregation::Day.into(),
+        };
+        let mut usage_req = Request::new(usage_req);
+        usage_req
+            .extensions_mut()
+            .insert(AuthID::User(u.id.clone()));
+        let usage_resp = service.get_usage(usage_req).await.unwrap();
+        let usage_resp = usage_resp.get_ref();
+        assert_eq!(1, usage_resp.result.len());
+        assert_eq!(1, usage_resp.result[0].uplinks);
+        assert_eq!(20, usage_resp.result[0].uplink_bytes);
+        assert_eq!(1, usage_resp.result[0].active_devices);
+
+        // get usage exceeding the max. interval
+        let usage_req = api::GetTenantUsageRequest {
+            tenant_id: create_resp.get_ref().id.clone(),
+            start: Some((SystemTime::now() - std::time::Duration::from_secs(365 * 86400)).into()),
+            end: Some(SystemTime::now().into()),
+            aggregation: common::Aggregation::Hour.into(),
+        };
+        let mut usage_req = Request::new(usage_req);
+        usage_req
+            .extensions_mut()
+            .insert(AuthID::User(u.id.clone()));
+        let usage_resp = service.get_usage(usage_req).await;
+        assert_eq!(tonic::Code::InvalidArgument, usage_resp.unwrap_err().code());
+
         // delete
         let del_req = api::DeleteTenantRequest {
             id: create_resp.get_ref().id.clone(),
@@ -571,5 +1078,183 @@ pub mod test {
         del_req.extensions_mut().insert(AuthID::User(u.id.clone()));
         let del_resp = service.delete(del_req).await;
         assert!(del_resp.is_err());
+
+        // list deleted
+        let list_req = api::ListTenantsRequest {
+            search: "update".into(),
+            offset: 0,
+            limit: 10,
+            user_id: "".into(),
+            deleted: true,
+        };
+        let mut list_req = Request::new(list_req);
+        list_req.extensions_mut().insert(AuthID::User(u.id.clone()));
+        let list_resp = service.list(list_req).await.unwrap();
+        assert_eq!(1, list_resp.get_ref().total_count);
+        assert!(list_resp.get_ref().result[0].deleted_at.is_some());
+
+        // restore
+        let restore_req = api::RestoreTenantRequest {
+            id: create_resp.get_ref().id.clone(),
+        };
+        let mut restore_req = Request::new(restore_req);
+        restore_req
+            .extensions_mut()
+            .insert(AuthID::User(u.id.clone()));
+        let _ = service.restore(restore_req).await.unwrap();
+
+        let get_req = api::GetTenantRequest {
+            id: create_resp.get_ref().id.clone(),
+        };
+        let mut get_req = Request::new(get_req);
+        get_req.extensions_mut().insert(AuthID::User(u.id.clone()));
+        assert!(service.get(get_req).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_tenant_role() {
+        let _guard = test::prepare().await;
+
+        // setup admin user
+        let u = user::create(user::User {
+            is_admin: true,
+            is_active: true,
+            email: "admin@admin".into(),
+            email_verified: true,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        let t = tenant::test::create_tenant().await;
+        tenant::add_user(tenant::TenantUser {
+            tenant_id: t.id,
+            user_id: u.id,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        // setup api
+        let service = Tenant::new(RequestValidator::new());
+
+        // create
+        let create_req = api::CreateTenantRoleRequest {
+            role: Some(api::TenantRole {
+                tenant_id: t.id.to_string(),
+                name: "operator".into(),
+                description: "Can enqueue downlinks".into(),
+                permissions: vec![api::TenantRolePermission::QueueWrite.into()],
+                ..Default::default()
+            }),
+        };
+        let mut create_req = Request::new(create_req);
+        create_req.extensions_mut().insert(AuthID::User(u.id));
+        let create_resp = service.create_role(create_req).await.unwrap();
+        let role_id = create_resp.get_ref().id.clone();
+
+        // update
+        let up_req = api::UpdateTenantRoleRequest {
+            role: Some(api::TenantRole {
+                id: role_id.clone(),
+                tenant_id: t.id.to_string(),
+                name: "operator".into(),
+                description: "Can enqueue downlinks".into(),
+                permissions: vec![
+                    api::TenantRolePermission::QueueWrite.into(),
+                    api::TenantRolePermission::DeviceWrite.into(),
+                ],
+            }),
+        };
+        let mut up_req = Request::new(up_req);
+        up_req.extensions_mut().insert(AuthID::User(u.id));
+        let up_resp = service.update_role(up_req).await.unwrap();
+        assert_eq!(
+            Some(&audit::Changes(serde_json::json!({
+                "can_write_devices": {"before": false, "after": true},
+            }))),
+            up_resp.extensions().get::<audit::Changes>()
+        );
+
+        // get
+        let get_req = api::GetTenantRoleRequest {
+            id: role_id.clone(),
+        };
+        let mut get_req = Request::new(get_req);
+        get_req.extensions_mut().insert(AuthID::User(u.id));
+        let get_resp = service.get_role(get_req).await.unwrap();
+        assert_eq!(
+            Some(api::TenantRole {
+                id: role_id.clone(),
+                tenant_id: t.id.to_string(),
+                name: "operator".into(),
+                description: "Can enqueue downlinks".into(),
+                permissions: vec![
+                    api::TenantRolePermission::DeviceWrite.into(),
+                    api::TenantRolePermission::QueueWrite.into(),
+                ],
+            }),
+            get_resp.get_ref().role
+        );
+
+        // list
+        let list_req = api::ListTenantRolesRequest {
+            tenant_id: t.id.to_string(),
+            limit: 10,
+            offset: 0,
+        };
+        let mut list_req = Request::new(list_req);
+        list_req.extensions_mut().insert(AuthID::User(u.id));
+        let list_resp = service.list_roles(list_req).await.unwrap();
+        assert_eq!(1, list_resp.get_ref().total_count);
+        assert_eq!(1, list_resp.get_ref().result.len());
+
+        // add user role
+        let add_req = api::AddTenantUserRoleRequest {
+            user_role: Some(api::TenantUserRole {
+                tenant_id: t.id.to_string(),
+                user_id: u.id.to_string(),
+                role_id: role_id.clone(),
+                ..Default::default()
+            }),
+        };
+        let mut add_req = Request::new(add_req);
+        add_req.extensions_mut().insert(AuthID::User(u.id));
+        let add_resp = service.add_user_role(add_req).await.unwrap();
+
+        // list user roles
+        let list_req = api::ListTenantUserRolesRequest {
+            tenant_id: t.id.to_string(),
+            user_id: u.id.to_string(),
+        };
+        let mut list_req = Request::new(list_req);
+        list_req.extensions_mut().insert(AuthID::User(u.id));
+        let list_resp = service.list_user_roles(list_req).await.unwrap();
+        assert_eq!(1, list_resp.get_ref().result.len());
+        assert_eq!(add_resp.get_ref().id, list_resp.get_ref().result[0].id);
+        assert_eq!(role_id, list_resp.get_ref().result[0].role_id);
+
+        // remove user role
+        let rm_req = api::RemoveTenantUserRoleRequest {
+            tenant_id: t.id.to_string(),
+            user_id: u.id.to_string(),
+            id: add_resp.get_ref().id.clone(),
+        };
+        let mut rm_req = Request::new(rm_req);
+        rm_req.extensions_mut().insert(AuthID::User(u.id));
+        service.remove_user_role(rm_req).await.unwrap();
+
+        // delete
+        let del_req = api::DeleteTenantRoleRequest {
+            id: role_id.clone(),
+        };
+        let mut del_req = Request::new(del_req);
+        del_req.extensions_mut().insert(AuthID::User(u.id));
+        service.delete_role(del_req).await.unwrap();
+
+        let del_req = api::DeleteTenantRoleRequest { id: role_id };
+        let mut del_req = Request::new(del_req);
+        del_req.extensions_mut().insert(AuthID::User(u.id));
+        assert!(service.delete_role(del_req).await.is_err());
     }
 }
@@ -7,9 +7,12 @@ use uuid::Uuid;
 use chirpstack_api::api;
 use chirpstack_api::api::user_service_server::UserService;
 
+use super::audit;
 use super::auth::{validator, AuthID};
 use super::error::ToStatus;
 use super::helpers;
+use crate::config;
+use crate::helpers::totp;
 use crate::storage::{tenant, user};
 
 pub struct User {
@@ -55,6 +58,7 @@ impl UserService for User {
             ..Default::default()
         };
 
+        user::validate_password(&req.password).map_err(|e| e.status())?;
         u.set_password_hash(&req.password, self.pw_hash_iterations)
             .map_err(|e| e.status())?;
 
@@ -80,6 +84,8 @@ impl UserService for User {
         });
         resp.metadata_mut()
             .insert("x-log-user_id", u.id.to_string().parse().unwrap());
+        resp.extensions_mut()
+            .insert(audit::Changes::new(None, Some(&u)));
 
         Ok(resp)
     }
@@ -110,6 +116,14 @@ impl UserService for User {
             }),
             created_at: Some(helpers::datetime_to_prost_timestamp(&u.created_at)),
             updated_at: Some(helpers::datetime_to_prost_timestamp(&u.updated_at)),
+            totp_enabled: u.totp_enabled,
+            locked_until: if u.is_locked() {
+                u.locked_until
+                    .as_ref()
+                    .map(helpers::datetime_to_prost_timestamp)
+            } else {
+                None
+            },
         });
         resp.metadata_mut()
             .insert("x-log-user_id", req.id.parse().unwrap());
@@ -136,8 +150,10 @@ impl UserService for User {
             )
             .await?;
 
+        let u_before = user::get(&user_id).await.map_err(|e| e.status())?;
+
         // update
-        let _ = user::update(user::User {
+        let u = user::update(user::User {
             id: user_id,
             is_admin: req_user.is_admin,
             is_active: req_user.is_active,
@@ -152,6 +168,8 @@ impl UserService for User {
         let mut resp = Response::new(());
         resp.metadata_mut()
             .insert("x-log-user_id", req_user.id.parse().unwrap());
+        resp.extensions_mut()
+            .insert(audit::Changes::new(Some(&u_before), Some(&u)));
 
         Ok(resp)
     }
@@ -179,11 +197,14 @@ impl UserService for User {
             }
         }
 
+        let u = user::get(&user_id).await.map_err(|e| e.status())?;
         user::delete(&user_id).await.map_err(|e| e.status())?;
 
         let mut resp = Response::new(());
         resp.metadata_mut()
             .insert("x-log-user_id", req.id.parse().unwrap());
+        resp.extensions_mut()
+            .insert(audit::Changes::new(Some(&u), None));
 
         Ok(resp)
     }
@@ -234,8 +255,15 @@ impl UserService for User {
             )
             .await?;
 
+        // validate
+        user::validate_password(&req.password).map_err(|e| e.status())?;
+        user::validate_password_history(&user_id, &req.password)
+            .await
+            .map_err(|e| e.status())?;
+
         // get
-        let mut u = user::get(&user_id).await.map_err(|e| e.status())?;
+        let u_before = user::get(&user_id).await.map_err(|e| e.status())?;
+        let mut u = u_before.clone();
 
         // set password
         u.updated_at = Utc::now();
@@ -243,13 +271,154 @@ impl UserService for User {
             .map_err(|e| e.status())?;
 
         // update
-        let _ = user::set_password_hash(&u.id, &u.password_hash)
+        let u = user::set_password_hash(&u.id, &u.password_hash)
             .await
             .map_err(|e| e.status())?;
 
         let mut resp = Response::new(());
         resp.metadata_mut()
             .insert("x-log-user_id", req.user_id.parse().unwrap());
+        resp.extensions_mut()
+            .insert(audit::Changes::new(Some(&u_before), Some(&u)));
+
+        Ok(resp)
+    }
+
+    async fn enroll_totp(
+        &self,
+        request: Request<api::EnrollUserTotpRequest>,
+    ) -> Result<Response<api::EnrollUserTotpResponse>, Status> {
+        let req = request.get_ref();
+        let user_id = Uuid::from_str(&req.user_id).map_err(|e| e.status())?;
+        self.validator
+            .validate(
+                request.extensions(),
+                validator::ValidateUserAccess::new(validator::Flag::UpdateProfile, user_id),
+            )
+            .await?;
+
+        let u_before = user::get(&user_id).await.map_err(|e| e.status())?;
+        let u = user::enroll_totp(&user_id).await.map_err(|e| e.status())?;
+        let secret = u.get_totp_secret().map_err(|e| e.status())?;
+
+        let conf = config::get();
+        let mut resp = Response::new(api::EnrollUserTotpResponse {
+            provisioning_uri: totp::provisioning_uri(
+                &conf.user_authentication.internal.totp_issuer,
+                &u.email,
+                &secret,
+            )
+            .map_err(|e| e.status())?,
+            secret: totp::encode_secret(&secret).map_err(|e| e.status())?,
+        });
+        resp.metadata_mut()
+            .insert("x-log-user_id", req.user_id.parse().unwrap());
+        resp.extensions_mut()
+            .insert(audit::Changes::new(Some(&u_before), Some(&u)));
+
+        Ok(resp)
+    }
+
+    async fn verify_totp(
+        &self,
+        request: Request<api::VerifyUserTotpRequest>,
+    ) -> Result<Response<api::VerifyUserTotpResponse>, Status> {
+        let req = request.get_ref();
+        let user_id = Uuid::from_str(&req.user_id).map_err(|e| e.status())?;
+        self.validator
+            .validate(
+                request.extensions(),
+                validator::ValidateUserAccess::new(validator::Flag::UpdateProfile, user_id),
+            )
+            .await?;
+
+        let u_before = user::get(&user_id).await.map_err(|e| e.status())?;
+        let recovery_codes = user::enable_totp(&user_id, &req.code)
+            .await
+            .map_err(|e| e.status())?;
+        let u = user::get(&user_id).await.map_err(|e| e.status())?;
+
+        let mut resp = Response::new(api::VerifyUserTotpResponse { recovery_codes });
+        resp.metadata_mut()
+            .insert("x-log-user_id", req.user_id.parse().unwrap());
+        resp.extensions_mut()
+            .insert(audit::Changes::new(Some(&u_before), Some(&u)));
+
+        Ok(resp)
+    }
+
+    async fn disable_totp(
+        &self,
+        request: Request<api::DisableUserTotpRequest>,
+    ) -> Result<Response<()>, Status> {
+        let req = request.get_ref();
+        let user_id = Uuid::from_str(&req.user_id).map_err(|e| e.status())?;
+        self.validator
+            .validate(
+                request.extensions(),
+                validator::ValidateUserAccess::new(validator::Flag::UpdateProfile, user_id),
+            )
+            .await?;
+
+        let u_before = user::get(&user_id).await.map_err(|e| e.status())?;
+        let u = user::disable_totp(&user_id).await.map_err(|e| e.status())?;
+
+        let mut resp = Response::new(());
+        resp.metadata_mut()
+            .insert("x-log-user_id", req.user_id.parse().unwrap());
+        resp.extensions_mut()
+            .insert(audit::Changes::new(Some(&u_before), Some(&u)));
+
+        Ok(resp)
+    }
+
+    async fn regenerate_recovery_codes(
+        &self,
+        request: Request<api::RegenerateUserRecoveryCodesRequest>,
+    ) -> Result<Response<api::RegenerateUserRecoveryCodesResponse>, Status> {
+        let req = request.get_ref();
+        let user_id = Uuid::from_str(&req.user_id).map_err(|e| e.status())?;
+        self.validator
+            .validate(
+                request.extensions(),
+                validator::ValidateUserAccess::new(validator::Flag::UpdateProfile, user_id),
+            )
+            .await?;
+
+        let recovery_codes = user::regenerate_recovery_codes(&user_id)
+            .await
+            .map_err(|e| e.status())?;
+
+        let mut resp = Response::new(api::RegenerateUserRecoveryCodesResponse { recovery_codes });
+        resp.metadata_mut()
+            .insert("x-log-user_id", req.user_id.parse().unwrap());
+
+        Ok(resp)
+    }
+
+    async fn revoke_sessions(
+        &self,
+        request: Request<api::RevokeUserSessionsRequest>,
+    ) -> Result<Response<()>, Status> {
+        let req = request.get_ref();
+        let user_id = Uuid::from_str(&req.user_id).map_err(|e| e.status())?;
+        self.validator
+            .validate(
+                request.extensions(),
+                validator::ValidateUserAccess::new(validator::Flag::UpdateProfile, user_id),
+            )
+            .await?;
+
+        let u_before = user::get(&user_id).await.map_err(|e| e.status())?;
+        let u = user::revoke_sessions(&user_id)
+            .await
+            .map_err(|e| e.status())?;
+
+        let mut resp = Response::new(());
+        resp.metadata_mut()
+            .insert("x-log-user_id", req.user_id.parse().unwrap());
+        resp.extensions_mut()
+            .insert(audit::Changes::new(Some(&u_before), Some(&u)));
 
         Ok(resp)
     }
@@ -259,7 +428,7 @@ impl UserService for User {
 pub mod test {
     use super::*;
     use crate::api::auth::validator::RequestValidator;
-    use crate::api::auth::AuthID;
+    use crate::api::auth::{AuthID, IssuedAt};
     use crate::test;
 
     #[tokio::test]
@@ -359,6 +528,76 @@ pub mod test {
         up_req.extensions_mut().insert(AuthID::User(u.id.clone()));
         let _ = service.update_password(up_req).await.unwrap();
 
+        // enroll totp
+        let mut conf = (*config::get()).clone();
+        conf.keks.push(config::Kek {
+            label: "totp-kek".into(),
+            kek: lrwn::AES128Key::from_bytes([1, 2, 3, 4, 5, 6, 7, 8, 1, 2, 3, 4, 5, 6, 7, 8]),
+        });
+        conf.user_authentication.internal.totp_kek_label = "totp-kek".into();
+        config::set(conf);
+
+        let enroll_req = api::EnrollUserTotpRequest {
+            user_id: create_resp.get_ref().id.clone(),
+        };
+        let mut enroll_req = Request::new(enroll_req);
+        enroll_req
+            .extensions_mut()
+            .insert(AuthID::User(u.id.clone()));
+        let enroll_resp = service.enroll_totp(enroll_req).await.unwrap();
+        assert!(enroll_resp
+            .get_ref()
+            .provisioning_uri
+            .starts_with("otpauth://totp/ChirpStack:foo%40bar?"));
+        let secret = totp_rs::Secret::Encoded(enroll_resp.get_ref().secret.clone())
+            .to_bytes()
+            .unwrap();
+
+        // verify totp
+        let verify_req = api::VerifyUserTotpRequest {
+            user_id: create_resp.get_ref().id.clone(),
+            code: totp::test::generate(&secret, Utc::now().timestamp() as u64),
+        };
+        let mut verify_req = Request::new(verify_req);
+        verify_req
+            .extensions_mut()
+            .insert(AuthID::User(u.id.clone()));
+        let verify_resp = service.verify_totp(verify_req).await.unwrap();
+        assert_eq!(10, verify_resp.get_ref().recovery_codes.len());
+
+        // get
+        let get_req = api::GetUserRequest {
+            id: create_resp.get_ref().id.clone(),
+        };
+        let mut get_req = Request::new(get_req);
+        get_req.extensions_mut().insert(AuthID::User(u.id.clone()));
+        let get_resp = service.get(get_req).await.unwrap();
+        assert!(get_resp.get_ref().totp_enabled);
+
+        // revoke sessions
+        let revoke_req = api::RevokeUserSessionsRequest {
+            user_id: create_resp.get_ref().id.clone(),
+        };
+        let mut revoke_req = Request::new(revoke_req);
+        revoke_req
+            .extensions_mut()
+            .insert(AuthID::User(u.id.clone()));
+        let _ = service.revoke_sessions(revoke_req).await.unwrap();
+
+        // get as the user itself, using a token issued before revoking the sessions
+        let get_req = api::GetUserRequest {
+            id: create_resp.get_ref().id.clone(),
+        };
+        let mut get_req = Request::new(get_req);
+        get_req.extensions_mut().insert(AuthID::User(
+            Uuid::from_str(&create_resp.get_ref().id).unwrap(),
+        ));
+        get_req
+            .extensions_mut()
+            .insert(IssuedAt(Some(Utc::now().timestamp() as usize - 10)));
+        let get_resp = service.get(get_req).await;
+        assert_eq!(tonic::Code::Unauthenticated, get_resp.unwrap_err().code());
+
         // list
         let list_req = api::ListUsersRequest {
             offset: 0,
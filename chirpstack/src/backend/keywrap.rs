@@ -1,3 +1,4 @@
+use aes_kw::KekAes128;
 use anyhow::Result;
 use tracing::trace;
 
@@ -38,3 +39,28 @@ pub fn wrap(label: &str, key: AES128Key) -> Result<KeyEnvelope> {
 
     Err(anyhow!("KEK label {} does not exist", label))
 }
+
+// Wraps arbitrary data (e.g. a configuration containing secrets) using the KEK of the given
+// label. This uses AES key wrap with padding (RFC 5649).
+pub fn wrap_data(label: &str, data: &[u8]) -> Result<Vec<u8>> {
+    get_kek(label)?
+        .wrap_with_padding_vec(data)
+        .map_err(|e| anyhow!("Wrap data error: {}", e))
+}
+
+pub fn unwrap_data(label: &str, data: &[u8]) -> Result<Vec<u8>> {
+    get_kek(label)?
+        .unwrap_with_padding_vec(data)
+        .map_err(|e| anyhow!("Unwrap data error: {}", e))
+}
+
+fn get_kek(label: &str) -> Result<KekAes128> {
+    let conf = config::get();
+    for kek in &conf.keks {
+        if kek.label == *label {
+            return Ok(KekAes128::from(kek.kek.to_bytes()));
+        }
+    }
+
+    Err(anyhow!("KEK label {} does not exist", label))
+}
@@ -1,9 +1,11 @@
-use std::time::SystemTime;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use anyhow::{Context, Result};
+use rand::RngCore;
 use rcgen::{
-    Certificate, CertificateParams, DnType, ExtendedKeyUsagePurpose, KeyPair, KeyUsagePurpose,
-    SignatureAlgorithm,
+    Certificate, CertificateParams, CertificateRevocationListParams, CrlDistributionPoint, DnType,
+    DnValue, ExtendedKeyUsagePurpose, KeyIdMethod, KeyPair, KeyUsagePurpose, RevokedCertParams,
+    SerialNumber, SignatureAlgorithm,
 };
 use tokio::fs;
 use uuid::Uuid;
@@ -12,10 +14,16 @@ use crate::config;
 use crate::helpers::tls::private_key_to_pkcs8;
 use lrwn::EUI64;
 
+// X.520 serialNumber attribute.
+const OID_SERIAL_NUMBER: &[u64] = &[2, 5, 4, 5];
+
 fn gen_client_cert(
     id: &str,
+    serial_number: &[u8],
+    serial_number_in_subject: bool,
     not_before: SystemTime,
     not_after: SystemTime,
+    crl_url: &str,
     issuer: &Certificate,
     issuer_key: &KeyPair,
 ) -> Result<(Certificate, KeyPair)> {
@@ -23,9 +31,21 @@ fn gen_client_cert(
     params
         .distinguished_name
         .push(DnType::CommonName, id.to_string());
+    if serial_number_in_subject {
+        params.distinguished_name.push(
+            DnType::CustomDnType(OID_SERIAL_NUMBER.to_vec()),
+            DnValue::PrintableString(hex::encode(serial_number).try_into()?),
+        );
+    }
     params.use_authority_key_identifier_extension = true;
+    params.serial_number = Some(SerialNumber::from_slice(serial_number));
     params.not_before = not_before.into();
     params.not_after = not_after.into();
+    if !crl_url.is_empty() {
+        params.crl_distribution_points.push(CrlDistributionPoint {
+            uris: vec![crl_url.to_string()],
+        });
+    }
     params.key_usages.push(KeyUsagePurpose::DigitalSignature);
     params
         .extended_key_usages
@@ -35,6 +55,16 @@ fn gen_client_cert(
     Ok((params.signed_by(&kp, issuer, issuer_key)?, kp))
 }
 
+// This returns a random 128 bit serial number. The most significant bit is cleared such that the
+// serial number is always positive and the second most significant bit is set such that its
+// encoding has a fixed length.
+fn gen_serial_number() -> Vec<u8> {
+    let mut b = vec![0; 16];
+    rand::thread_rng().fill_bytes(&mut b);
+    b[0] = (b[0] & 0x7f) | 0x40;
+    b
+}
+
 async fn get_ca_cert(ca_cert_file: &str, ca_key_file: &str) -> Result<(Certificate, KeyPair)> {
     let ca_cert_s = fs::read_to_string(ca_cert_file)
         .await
@@ -65,8 +95,11 @@ pub async fn client_cert_for_gateway_id(
     let not_after = SystemTime::now() + conf.gateway.client_cert_lifetime;
     let (gw_cert, gw_key) = gen_client_cert(
         &gateway_id.to_string(),
+        &gen_serial_number(),
+        false,
         not_before,
         not_after,
+        "",
         &ca_cert,
         &ca_key,
     )
@@ -80,26 +113,36 @@ pub async fn client_cert_for_gateway_id(
     ))
 }
 
+// This returns the serial number, expiration, CA, certificate and private-key. The serial number
+// is returned as bytes, the other values as PEM encoded strings.
+//
+// The (HEX encoded) serial number is also added to the subject of the certificate, such that the
+// MQTT broker can pass it to the auth hook when the subject is used as username.
 pub async fn client_cert_for_application_id(
     application_id: &Uuid,
-) -> Result<(SystemTime, String, String, String)> {
+) -> Result<(Vec<u8>, SystemTime, String, String, String)> {
     let conf = config::get();
     let (ca_cert, ca_key) = get_ca_cert(
         &conf.integration.mqtt.client.ca_cert,
         &conf.integration.mqtt.client.ca_key,
     )
     .await?;
+    let serial_number = gen_serial_number();
     let not_before = SystemTime::now();
     let not_after = SystemTime::now() + conf.integration.mqtt.client.client_cert_lifetime;
     let (app_cert, app_key) = gen_client_cert(
         &application_id.to_string(),
+        &serial_number,
+        true,
         not_before,
         not_after,
+        &conf.integration.mqtt.client.crl_url,
         &ca_cert,
         &ca_key,
     )?;
 
     Ok((
+        serial_number,
         not_after,
         ca_cert.pem(),
         app_cert.pem(),
@@ -107,6 +150,41 @@ pub async fn client_cert_for_application_id(
     ))
 }
 
+// This returns the DER encoded certificate revocation list for the application client
+// certificates, given the serial numbers and revocation timestamps of the revoked certificates.
+pub async fn crl_for_application_certs(revoked: &[(Vec<u8>, SystemTime)]) -> Result<Vec<u8>> {
+    let conf = config::get();
+    let (ca_cert, ca_key) = get_ca_cert(
+        &conf.integration.mqtt.client.ca_cert,
+        &conf.integration.mqtt.client.ca_key,
+    )
+    .await?;
+    let now = SystemTime::now();
+
+    let params = CertificateRevocationListParams {
+        this_update: now.into(),
+        next_update: (now + conf.integration.mqtt.client.crl_lifetime).into(),
+        // The CRL number must be increasing, the timestamp is used for this.
+        crl_number: SerialNumber::from(now.duration_since(UNIX_EPOCH)?.as_secs()),
+        issuing_distribution_point: None,
+        revoked_certs: revoked
+            .iter()
+            .map(|(serial_number, revoked_at)| RevokedCertParams {
+                serial_number: SerialNumber::from_slice(serial_number),
+                revocation_time: (*revoked_at).into(),
+                reason_code: None,
+                invalidity_date: None,
+            })
+            .collect(),
+        key_identifier_method: KeyIdMethod::Sha256,
+    };
+    let crl = params
+        .signed_by(&ca_cert, &ca_key)
+        .context("Sign certificate revocation list")?;
+
+    Ok(crl.der().to_vec())
+}
+
 // we are using String here, because else we run into lifetime issues.
 fn read_algo(cert: &str) -> Result<&'static SignatureAlgorithm> {
     let cert = pem::parse(cert).context("Parse PEM")?;
@@ -110,6 +110,113 @@ pub fn run() {
   #   openssl rand -base64 32
   secret="{{ api.secret }}"
 
+  # Signing key ID.
+  #
+  # When set, login and API tokens are signed using the signing key with
+  # this key ID (kid) instead of the above secret. Tokens signed by any of
+  # the configured signing keys (and tokens signed using the above secret)
+  # are accepted. To rotate the signing key, add a new signing key, update
+  # this key ID and remove the old signing key once the tokens signed by it
+  # are no longer used.
+  signing_key_id="{{ api.signing_key_id }}"
+
+  # Signing keys.
+  #
+  # The public keys are exposed as JSON Web Key Set at /.well-known/jwks.json.
+  #
+  # Example (can be repeated):
+  # [[api.signing_keys]]
+  #
+  #   # Key ID.
+  #   kid="2024-06"
+  #
+  #   # Algorithm.
+  #   #
+  #   # Options are:
+  #   #  * RS256 - RSA private key (PKCS#1 or PKCS#8 PEM).
+  #   #  * ES256 - P-256 EC private key (SEC1 or PKCS#8 PEM).
+  #   algorithm="RS256"
+  #
+  #   # Private key (PEM file).
+  #   private_key="/etc/chirpstack/jwt/2024-06.pem"
+{{#each api.signing_keys}}
+
+  [[api.signing_keys]]
+    kid="{{ this.kid }}"
+    algorithm="{{ this.algorithm }}"
+    private_key="{{ this.private_key }}"
+{{/each}}
+
+  # External tokens.
+  #
+  # When enabled, tokens issued by an external identity provider are accepted
+  # as user tokens. These tokens are validated using the JSON Web Key Set of
+  # the identity provider and are mapped to the user with the matching
+  # external ID (see the OpenID Connect and OAuth2 authentication backends).
+  [api.external_token]
+
+    # Enable external tokens.
+    enabled={{ api.external_token.enabled }}
+
+    # JWKS URL.
+    #
+    # Example: https://auth.example.org/.well-known/jwks.json
+    jwks_url="{{ api.external_token.jwks_url }}"
+
+    # Issuer.
+    #
+    # The iss claim of the token must match this value. This must be set when
+    # external tokens are enabled.
+    issuer="{{ api.external_token.issuer }}"
+
+    # Audience.
+    #
+    # When set, the aud claim of the token must contain this value.
+    audience="{{ api.external_token.audience }}"
+
+    # External ID claim.
+    #
+    # The claim containing the external ID of the user.
+    external_id_claim="{{ api.external_token.external_id_claim }}"
+
+    # JWKS refresh interval.
+    jwks_refresh_interval="{{ api.external_token.jwks_refresh_interval }}"
+
+  # Audit log settings.
+  [api.audit_log]
+
+    # Enable the audit log.
+    #
+    # When enabled, all mutating API calls (create, update, delete, ...) are
+    # stored in the database, together with the user or API key performing the
+    # request and the changes made to the entity. The audit log can be
+    # retrieved using the InternalService.ListAuditLog API method.
+    enabled={{ api.audit_log.enabled }}
+
+    # Max age.
+    #
+    # Audit log entries older than the configured duration are removed from
+    # the database. Setting this to 0s disables the cleanup, in which case the
+    # audit log is retained forever.
+    max_age="{{ api.audit_log.max_age }}"
+
+
+# Soft-delete configuration.
+#
+# Deleted tenants, applications, devices and gateways are first marked as
+# deleted, after which they can be listed and restored using the API during the
+# retention period. After the retention period, these are permanently removed
+# from the database. Creating a device or gateway using the DevEUI or Gateway ID
+# of a deleted device or gateway permanently removes the deleted one.
+[soft_delete]
+
+  # Retention period.
+  #
+  # Setting this to 0s (the default) disables soft-delete, in which case deleted
+  # entities are permanently removed immediately. E.g. set this to 720h to keep
+  # deleted entities for 30 days.
+  retention="{{ soft_delete.retention }}"
+
 
 # Global gateway configuration.
 # Please note that backend configuration can be found in the per-region
@@ -210,6 +317,15 @@ pub fn run() {
   # Mac-commands disabled.
   mac_commands_disabled={{ network.mac_commands_disabled }}
 
+  # Allow LR-FHSS grid mismatches.
+  #
+  # By default, LR-FHSS uplinks for which the gateway reports a number of grid
+  # steps that does not match the occupied channel width are rejected. When
+  # enabled, these uplinks are accepted and the data-rate is resolved using
+  # the occupied channel width and coding-rate only. Mismatches are always
+  # counted in the uplink_lr_fhss_grid_mismatch_count metric.
+  lr_fhss_grid_mismatch_allowed={{ network.lr_fhss_grid_mismatch_allowed }}
+
   # Custom ADR plugins.
   #
   # The custom ADR plugin must be implemented in JavaScript. For an example
@@ -439,6 +555,66 @@ pub fn run() {
       # This defines how long (after generating) the certificate remains valid.
       client_cert_lifetime="{{ integration.mqtt.client.client_cert_lifetime }}"
 
+      # CRL lifetime.
+      #
+      # Issued certificates can be revoked using the API. The certificate
+      # revocation list (CRL) is served at /mqtt/crl.pem (PEM) and /mqtt/crl
+      # (DER). This defines the next update interval of the generated CRL.
+      crl_lifetime="{{ integration.mqtt.client.crl_lifetime }}"
+
+      # CRL URL (optional).
+      #
+      # When set, this URL is added as CRL distribution point to the generated
+      # client certificates, e.g. https://chirpstack.example.com/mqtt/crl. This
+      # allows MQTT brokers that support CRL distribution points (e.g. EMQX)
+      # to fetch the CRL automatically.
+      crl_url="{{ integration.mqtt.client.crl_url }}"
+
+
+    # MQTT broker authentication and ACL hook.
+    #
+    # When enabled, ChirpStack exposes HTTP endpoints which can be used by
+    # the MQTT broker to authenticate clients and to authorize the topics
+    # per application:
+    #
+    #  * POST /mqtt/auth - Authenticate client
+    #  * POST /mqtt/superuser - Superuser check
+    #  * POST /mqtt/acl - Authorize topic
+    #
+    # The request body must be JSON, containing the username, password,
+    # clientid, cert_subject, topic and acc (mosquitto-go-auth HTTP backend)
+    # or action (EMQX HTTP authentication / authorization). The response is
+    # always returned with status 200, with a JSON body containing both the
+    # "ok" (mosquitto-go-auth with http_response_mode=json) and "result"
+    # (EMQX) fields.
+    #
+    # Clients are identified by the subject of the presented client
+    # certificate, which must be set by the broker in the cert_subject field
+    # (e.g. "cert_subject": "${cert_subject}" in EMQX). The username is set by
+    # the client and is never used to identify the client. As the
+    # mosquitto-go-auth HTTP backend does not forward the certificate subject,
+    # it can only be used to authenticate the superuser. The subject of the
+    # generated client certificates contains the application ID (Common Name)
+    # and the serial number of the certificate. Clients are authenticated when
+    # the presented certificate belongs to the application, has not been
+    # revoked and has not expired. Client certificates that were generated
+    # before the serial number was added to the subject must be re-generated.
+    # Requests of certificate clients containing a password are rejected.
+    #
+    # Clients are allowed to subscribe to the event topic and to publish to
+    # the command topic of their application. The username and password
+    # configured above (used by ChirpStack) are authenticated as superuser.
+    [integration.mqtt.auth_hook]
+
+      # Enable the auth hook endpoints.
+      enabled={{ integration.mqtt.auth_hook.enabled }}
+
+      # Bearer token.
+      #
+      # The requests must contain an Authorization: Bearer <token> header.
+      # The hook is not enabled when the bearer token is not configured.
+      bearer_token="{{ integration.mqtt.auth_hook.bearer_token }}"
+
 
   # PostgreSQL integration configuration.
   [integration.postgresql]
@@ -486,6 +662,21 @@ pub fn run() {
     # Use JSON encoding instead of Protobuf (binary).
     json={{ integration.amqp.json }}
 
+    # Command queue.
+    #
+    # This is the (durable) queue from which ChirpStack consumes (enqueue)
+    # commands. The queue will be bound to the "amq.topic" exchange. The
+    # result of a command is published as "downlink" event (enqueued or
+    # rejected). Leave blank to disable.
+    command_queue="{{ integration.amqp.command_queue }}"
+
+    # Command routing key.
+    #
+    # This is the command routing-key template. The application ID, DevEUI
+    # and command type are obtained from the routing-key of the received
+    # message.
+    command_routing_key="{{ integration.amqp.command_routing_key }}"
+
 
   # Kafka integration configuration.
   [integration.kafka]
@@ -530,6 +721,212 @@ pub fn run() {
     # Use JSON encoding instead of Protobuf (binary).
     json={{ integration.kafka.json }}
 
+    # Topic for commands.
+    #
+    # This is the topic from which ChirpStack consumes (enqueue) commands.
+    # The result of a command is published as "downlink" event (enqueued or
+    # rejected). Leave blank to disable.
+    command_topic="{{ integration.kafka.command_topic }}"
+
+    # Template for keys of command messages.
+    #
+    # The application ID, DevEUI and command type are obtained from the key of
+    # the received message.
+    command_key="{{ integration.kafka.command_key }}"
+
+    # Consumer group.
+    #
+    # ChirpStack instances sharing the same consumer group will share the
+    # consumption of the commands.
+    consumer_group="{{ integration.kafka.consumer_group }}"
+
+
+  # NATS JetStream integration configuration.
+  [integration.nats]
+
+    # NATS server URL.
+    server="{{ integration.nats.server }}"
+
+    # JetStream stream.
+    #
+    # The stream will be created if it does not yet exist. The stream subjects
+    # must cover the event and command subjects.
+    stream="{{ integration.nats.stream }}"
+
+    # JetStream stream subjects.
+    stream_subjects=[
+      {{#each integration.nats.stream_subjects}}
+      "{{this}}",
+      {{/each}}
+    ]
+
+    # Event subject template.
+    event_subject="{{ integration.nats.event_subject }}"
+
+    # Command subject template.
+    #
+    # This is the subject template on which the NATS integration consumes
+    # (enqueue) commands.
+    command_subject="{{ integration.nats.command_subject }}"
+
+    # Durable consumer name.
+    #
+    # ChirpStack instances sharing the same consumer name will share the
+    # consumption of the commands.
+    consumer_name="{{ integration.nats.consumer_name }}"
+
+    # Username (optional).
+    username="{{ integration.nats.username }}"
+
+    # Password (optional).
+    password="{{ integration.nats.password }}"
+
+    # Token (optional).
+    token="{{ integration.nats.token }}"
+
+    # Credentials file (optional).
+    credentials_file="{{ integration.nats.credentials_file }}"
+
+    # Use JSON encoding instead of Protobuf (binary).
+    json={{ integration.nats.json }}
+
+
+  # Apache Pulsar integration configuration.
+  [integration.pulsar]
+
+    # Pulsar server URL (e.g. pulsar://localhost:6650 or pulsar+ssl://...).
+    server="{{ integration.pulsar.server }}"
+
+    # Authentication token (optional).
+    token="{{ integration.pulsar.token }}"
+
+    # Topic for events.
+    event_topic="{{ integration.pulsar.event_topic }}"
+
+    # Template for the key of event messages.
+    #
+    # The key is used for distributing messages over partitions. A property
+    # "event" with the event type is included in each message.
+    event_key="{{ integration.pulsar.event_key }}"
+
+    # Topic for commands.
+    command_topic="{{ integration.pulsar.command_topic }}"
+
+    # Template for the key of command messages.
+    #
+    # The application ID, DevEUI and command type are obtained from the key
+    # of the received message.
+    command_key="{{ integration.pulsar.command_key }}"
+
+    # Subscription name.
+    #
+    # ChirpStack instances sharing the same subscription name will share the
+    # consumption of the commands.
+    subscription_name="{{ integration.pulsar.subscription_name }}"
+
+    # Use JSON encoding instead of Protobuf (binary).
+    json={{ integration.pulsar.json }}
+
+
+  # SQL integration configuration.
+  #
+  # This integration writes the decoded uplink measurements into typed
+  # columns, using one table per device-profile. The measurements are taken
+  # from the measurements configured in the device-profile. When new
+  # measurements are added, the table is automatically migrated. Numeric
+  # measurements are stored as double precision values, string measurements
+  # as text.
+  [integration.sql]
+
+    # Backend.
+    #
+    # Options are:
+    #  * timescaledb - TimescaleDB (PostgreSQL), using hypertables
+    #  * clickhouse  - ClickHouse, using its HTTP interface
+    backend="{{ integration.sql.backend }}"
+
+    # Table prefix.
+    #
+    # The table name is the prefix, followed by the device-profile ID.
+    table_prefix="{{ integration.sql.table_prefix }}"
+
+    # Batch size.
+    #
+    # The measurements are inserted in batches. A batch is inserted when it
+    # reaches the batch size, or when the batch interval has elapsed. Batches
+    # that failed to be inserted are retried, while the database is unavailable
+    # up to 100 batches are kept in memory.
+    batch_size={{ integration.sql.batch_size }}
+
+    # Batch interval.
+    batch_interval="{{ integration.sql.batch_interval }}"
+
+    # TimescaleDB configuration.
+    [integration.sql.timescaledb]
+
+      # PostgreSQL DSN.
+      #
+      # Format example: postgres://<USERNAME>:<PASSWORD>@<HOSTNAME>/<DATABASE>?sslmode=<SSLMODE>.
+      dsn="{{ integration.sql.timescaledb.dsn }}"
+
+      # CA certificate (optional).
+      ca_cert="{{ integration.sql.timescaledb.ca_cert }}"
+
+    # ClickHouse configuration.
+    [integration.sql.clickhouse]
+
+      # HTTP interface URL.
+      url="{{ integration.sql.clickhouse.url }}"
+
+      # Database.
+      database="{{ integration.sql.clickhouse.database }}"
+
+      # Username (optional).
+      username="{{ integration.sql.clickhouse.username }}"
+
+      # Password (optional).
+      password="{{ integration.sql.clickhouse.password }}"
+
+  # Integration outbox configuration.
+  #
+  # Events are stored in the outbox before they are delivered to an
+  # application integration and are removed once delivered. Events that could
+  # not be delivered (e.g. the HTTP endpoint was unavailable) are retried
+  # using an exponential backoff. After max_attempts, the event is moved to
+  # the dead-letter store, from which it can be replayed or purged using the
+  # API. Events that are in-flight when ChirpStack is stopped are retried
+  # after the lock_duration. Global integrations (e.g. MQTT) are not covered
+  # by the outbox, failures are only logged.
+  [integration.outbox]
+
+    # Enable the outbox.
+    #
+    # When disabled, events that failed to be delivered are only logged.
+    enabled={{ integration.outbox.enabled }}
+
+    # Max. number of delivery attempts before the event is dead-lettered.
+    max_attempts={{ integration.outbox.max_attempts }}
+
+    # Backoff after the first failed attempt.
+    #
+    # This value is doubled after each failed attempt.
+    initial_backoff="{{ integration.outbox.initial_backoff }}"
+
+    # Max. backoff between attempts.
+    max_backoff="{{ integration.outbox.max_backoff }}"
+
+    # Interval in which the outbox is checked for events to retry.
+    interval="{{ integration.outbox.interval }}"
+
+    # Max. number of events to retry per interval.
+    batch_size={{ integration.outbox.batch_size }}
+
+    # Lock duration.
+    #
+    # Events are locked for this duration while being retried, such that other
+    # ChirpStack instances will not retry the same events.
+    lock_duration="{{ integration.outbox.lock_duration }}"
+
 
 # Codec configuration.
 [codec]
@@ -552,6 +949,61 @@ pub fn run() {
   #  * oauth2         - OAuth2 based backend.
   enabled="{{ user_authentication.enabled }}"
 
+  # Internal authentication backend.
+  [user_authentication.internal]
+
+    # TOTP issuer.
+    #
+    # This is the issuer name that is shown by the authenticator app when
+    # enrolling TOTP based multi-factor authentication.
+    totp_issuer="{{ user_authentication.internal.totp_issuer }}"
+
+    # TOTP KEK label.
+    #
+    # The TOTP secrets are encrypted using the KEK with this label (see the
+    # [[keks]] configuration). This must be configured for enrolling TOTP
+    # based multi-factor authentication. The label is stored together with
+    # the encrypted secret, such that the KEK can be rotated by adding a new
+    # KEK and updating this label.
+    totp_kek_label="{{ user_authentication.internal.totp_kek_label }}"
+
+    # Max failed logins.
+    #
+    # After this number of consecutive failed login attempts (including
+    # invalid TOTP codes), the user is locked for the lockout duration.
+    # Set this to 0 to disable the account lockout.
+    max_failed_logins={{ user_authentication.internal.max_failed_logins }}
+
+    # Lockout duration.
+    lockout_duration="{{ user_authentication.internal.lockout_duration }}"
+
+    # Password policy.
+    #
+    # The password policy is validated when a password is set. Existing
+    # passwords are not affected.
+    [user_authentication.internal.password_policy]
+
+      # Min. password length.
+      min_length={{ user_authentication.internal.password_policy.min_length }}
+
+      # Require at least one uppercase character.
+      require_uppercase={{ user_authentication.internal.password_policy.require_uppercase }}
+
+      # Require at least one lowercase character.
+      require_lowercase={{ user_authentication.internal.password_policy.require_lowercase }}
+
+      # Require at least one digit.
+      require_digit={{ user_authentication.internal.password_policy.require_digit }}
+
+      # Require at least one special (non-alphanumeric) character.
+      require_special={{ user_authentication.internal.password_policy.require_special }}
+
+      # Password history.
+      #
+      # The number of previous passwords that can not be re-used. When set
+      # to 0, the password history is not stored.
+      history={{ user_authentication.internal.password_policy.history }}
+
   # OpenID Connect.
   [user_authentication.openid_connect]
 
@@ -697,6 +1149,54 @@ pub fn run() {
     # from the userinfo URL, assuming it will be true.
     assume_email_verified={{ user_authentication.oauth2.assume_email_verified }}
 
+  # SCIM 2.0 user provisioning.
+  #
+  # When enabled, the identity provider can provision users using the SCIM
+  # endpoint at /scim/v2 (Users and Groups resources). Users are matched by
+  # their external ID, deactivating a user in the identity provider will
+  # deactivate the user in ChirpStack and revoke its sessions. Users without
+  # external ID and global admin users can not be managed through SCIM.
+  [user_authentication.scim]
+
+    # Enable SCIM provisioning.
+    enabled={{ user_authentication.scim.enabled }}
+
+    # Bearer token.
+    #
+    # The identity provider must use this token in the Authorization header
+    # (Authorization: Bearer <TOKEN>).
+    bearer_token="{{ user_authentication.scim.bearer_token }}"
+
+    # Groups to tenant mapping.
+    #
+    # The members of the group are added to the tenant with the configured
+    # permissions. Removing a user from the group removes the user from the
+    # tenant. Tenant memberships which were not added through the group are
+    # never modified or removed.
+    #
+    # Example (can be repeated):
+    # [[user_authentication.scim.groups]]
+    #
+    #   # Group display name (as configured in the identity provider).
+    #   display_name="chirpstack-acme-admins"
+    #
+    #   # Tenant ID.
+    #   tenant_id="52f14cd4-c6f1-4fbd-8f87-4025e1d49242"
+    #
+    #   # Tenant user permissions.
+    #   is_admin=true
+    #   is_device_admin=false
+    #   is_gateway_admin=false
+{{#each user_authentication.scim.groups}}
+
+    [[user_authentication.scim.groups]]
+      display_name="{{ this.display_name }}"
+      tenant_id="{{ this.tenant_id }}"
+      is_admin={{ this.is_admin }}
+      is_device_admin={{ this.is_device_admin }}
+      is_gateway_admin={{ this.is_gateway_admin }}
+{{/each}}
+
 
 # Join Server configuration.
 [join_server]
@@ -1,13 +1,11 @@
 use anyhow::Result;
 
-use crate::api::auth::claims;
-use crate::config;
+use crate::api::auth::{claims, keys};
 use crate::storage::api_key;
 
 pub async fn run(name: &str) -> Result<()> {
-    let conf = config::get();
-
     crate::storage::setup().await?;
+    keys::setup()?;
 
     let key = api_key::create(api_key::ApiKey {
         name: name.to_string(),
@@ -16,7 +14,7 @@ pub async fn run(name: &str) -> Result<()> {
     })
     .await?;
 
-    let token = claims::AuthClaim::new_for_api_key(&key.id).encode(conf.api.secret.as_ref())?;
+    let token = claims::AuthClaim::new_for_api_key(&key.id, None).encode(&keys::get())?;
 
     println!("id: {}", key.id);
     println!("token: {}", token);
@@ -0,0 +1,19 @@
+use anyhow::{Context, Result};
+
+use crate::frame_decoder;
+use crate::storage;
+
+pub async fn run(phy_payload: &str, opts: &frame_decoder::Options) -> Result<()> {
+    let b = hex::decode(phy_payload).context("Decode PHYPayload hex")?;
+    let phy = lrwn::PhyPayload::from_slice(&b).context("Decode PHYPayload")?;
+
+    if opts.lookup_device || opts.dev_eui.is_some() {
+        storage::setup().await.context("Setup storage")?;
+    }
+
+    let out = frame_decoder::decode(phy, opts).await?;
+    let json = serde_json::to_string_pretty(&out)?;
+    println!("{}", json);
+
+    Ok(())
+}
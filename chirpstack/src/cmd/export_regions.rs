@@ -0,0 +1,16 @@
+use anyhow::{anyhow, Result};
+
+use lrwn::region::export;
+
+pub fn run(format: &str) -> Result<()> {
+    let regions = export::export_all()?;
+
+    let out = match format {
+        "json" => serde_json::to_string_pretty(&regions)?,
+        "yaml" => serde_yaml::to_string(&regions)?,
+        _ => return Err(anyhow!("Unexpected format: {}", format)),
+    };
+    println!("{}", out);
+
+    Ok(())
+}
@@ -0,0 +1,17 @@
+use std::str::FromStr;
+
+use anyhow::{Context, Result};
+use uuid::Uuid;
+
+use crate::storage::{self, tenant_archive};
+
+pub async fn run(tenant_id: &str, kek_label: &str) -> Result<()> {
+    let tenant_id = Uuid::from_str(tenant_id).context("Parse tenant ID")?;
+
+    storage::setup().await.context("Setup storage")?;
+
+    let a = tenant_archive::export(&tenant_id, kek_label).await?;
+    println!("{}", serde_json::to_string_pretty(&a)?);
+
+    Ok(())
+}
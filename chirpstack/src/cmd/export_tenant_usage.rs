@@ -0,0 +1,132 @@
+use std::str::FromStr;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local, NaiveDate, TimeZone};
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::storage::{self, metrics::Aggregation, tenant, tenant_usage};
+
+pub async fn run(
+    tenant_id: Option<&str>,
+    aggregation: &str,
+    start: &str,
+    end: &str,
+    format: &str,
+) -> Result<()> {
+    let aggregation = match aggregation.to_uppercase().as_ref() {
+        "HOUR" => Aggregation::HOUR,
+        "DAY" => Aggregation::DAY,
+        "MONTH" => Aggregation::MONTH,
+        _ => return Err(anyhow!("Unexpected aggregation: {}", aggregation)),
+    };
+    let start = parse_time(start).context("Parse start")?;
+    let end = parse_time(end).context("Parse end")?;
+
+    storage::setup().await.context("Setup storage")?;
+
+    let tenants = match tenant_id {
+        Some(v) => vec![tenant::get(&Uuid::from_str(v)?).await?],
+        None => {
+            let mut out = Vec::new();
+            loop {
+                let items =
+                    tenant::list(100, out.len() as i64, &tenant::Filters::default()).await?;
+                if items.is_empty() {
+                    break;
+                }
+                out.extend(items);
+            }
+            out
+        }
+    };
+
+    let mut rows: Vec<(tenant::Tenant, tenant_usage::Usage)> = Vec::new();
+    for t in tenants {
+        for u in tenant_usage::get(&t.id, aggregation, start, end).await? {
+            rows.push((t.clone(), u));
+        }
+    }
+
+    match format {
+        "csv" => {
+            println!(
+                "{}",
+                [
+                    "tenant_id",
+                    "tenant_name",
+                    "time",
+                    "uplinks",
+                    "uplink_bytes",
+                    "downlinks",
+                    "downlink_bytes",
+                    "active_devices",
+                    "gateway_airtime_ms",
+                    "roaming_uplinks",
+                ]
+                .join(",")
+            );
+            for (t, u) in &rows {
+                println!(
+                    "{},{},{},{},{},{},{},{},{},{}",
+                    t.id,
+                    csv_escape(&t.name),
+                    u.time.to_rfc3339(),
+                    u.uplinks,
+                    u.uplink_bytes,
+                    u.downlinks,
+                    u.downlink_bytes,
+                    u.active_devices,
+                    u.gateway_airtime_ms,
+                    u.roaming_uplinks
+                );
+            }
+        }
+        "json" => {
+            let out: Vec<serde_json::Value> = rows
+                .iter()
+                .map(|(t, u)| {
+                    json!({
+                        "tenant_id": t.id,
+                        "tenant_name": t.name,
+                        "time": u.time.to_rfc3339(),
+                        "uplinks": u.uplinks,
+                        "uplink_bytes": u.uplink_bytes,
+                        "downlinks": u.downlinks,
+                        "downlink_bytes": u.downlink_bytes,
+                        "active_devices": u.active_devices,
+                        "gateway_airtime_ms": u.gateway_airtime_ms,
+                        "roaming_uplinks": u.roaming_uplinks,
+                    })
+                })
+                .collect();
+            println!("{}", serde_json::to_string_pretty(&out)?);
+        }
+        _ => return Err(anyhow!("Unexpected format: {}", format)),
+    }
+
+    Ok(())
+}
+
+// Parses the given RFC3339 timestamp or date (YYYY-MM-DD, local time).
+fn parse_time(s: &str) -> Result<DateTime<Local>> {
+    if let Ok(v) = DateTime::parse_from_rfc3339(s) {
+        return Ok(v.into());
+    }
+
+    let d = NaiveDate::parse_from_str(s, "%Y-%m-%d")?
+        .and_hms_opt(0, 0, 0)
+        .ok_or_else(|| anyhow!("Invalid time"))?;
+    Local
+        .from_local_datetime(&d)
+        .earliest()
+        .ok_or_else(|| anyhow!("Invalid local time: {}", s))
+}
+
+fn csv_escape(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
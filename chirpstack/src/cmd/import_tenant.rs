@@ -0,0 +1,17 @@
+use std::fs;
+
+use anyhow::{Context, Result};
+
+use crate::storage::{self, tenant_archive};
+
+pub async fn run(file: &str) -> Result<()> {
+    let b = fs::read(file).context("Read archive")?;
+    let a: tenant_archive::Archive = serde_json::from_slice(&b).context("Decode archive")?;
+
+    storage::setup().await.context("Setup storage")?;
+
+    let tenant_id = tenant_archive::import(&a).await?;
+    println!("tenant_id: {}", tenant_id);
+
+    Ok(())
+}
@@ -1,6 +1,11 @@
 pub mod configfile;
 pub mod create_api_key;
+pub mod decode_frame;
+pub mod export_regions;
+pub mod export_tenant;
+pub mod export_tenant_usage;
 pub mod import_legacy_lorawan_devices_repository;
+pub mod import_tenant;
 pub mod migrate_ds_to_pg;
 pub mod print_ds;
 pub mod root;
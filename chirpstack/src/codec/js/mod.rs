@@ -187,6 +187,49 @@ pub async fn encode(
     })
 }
 
+// Evaluates the given expression and returns its result as boolean. The decoded payload is
+// available as data.
+pub async fn evaluate(
+    f_port: u8,
+    variables: &HashMap<String, String>,
+    expression: &str,
+    s: &prost_types::Struct,
+) -> Result<bool> {
+    let conf = config::get();
+    let max_run_ts = SystemTime::now() + conf.codec.js.max_execution_time;
+
+    let rt = rquickjs::Runtime::new()?;
+    rt.set_interrupt_handler(Some(Box::new(move || SystemTime::now() > max_run_ts)));
+
+    let ctx = rquickjs::Context::full(&rt)?;
+
+    let script = format!(
+        r#"
+        Boolean(
+            {}
+        )
+        "#,
+        expression
+    );
+
+    ctx.with(|ctx| -> Result<bool> {
+        let globals = ctx.globals();
+        globals.set("fPort", f_port.into_js(&ctx)?)?;
+        globals.set("variables", variables.into_js(&ctx)?)?;
+        globals.set("data", convert::struct_to_rquickjs(&ctx, s))?;
+
+        let mut eval_options = rquickjs::context::EvalOptions::default();
+        eval_options.strict = false;
+
+        let res: bool = ctx
+            .eval_with_options(script, eval_options)
+            .catch(&ctx)
+            .map_err(|e| anyhow!("JS error: {}", e))?;
+
+        Ok(res)
+    })
+}
+
 #[cfg(test)]
 pub mod test {
     use super::*;
@@ -396,4 +439,31 @@ pub mod test {
         let out = encode(10, &vars, &encoder, &input).await.unwrap();
         assert_eq!(vec![1], out);
     }
+
+    #[tokio::test]
+    pub async fn test_evaluate() {
+        let mut vars: HashMap<String, String> = HashMap::new();
+        vars.insert("threshold".into(), "20".into());
+
+        let mut input = prost_types::Struct::default();
+        input.fields.insert(
+            "temperature".to_string(),
+            prost_types::Value {
+                kind: Some(prost_types::value::Kind::NumberValue(25.0)),
+            },
+        );
+
+        assert!(evaluate(
+            10,
+            &vars,
+            "data.temperature > Number(variables.threshold) && fPort == 10",
+            &input
+        )
+        .await
+        .unwrap());
+        assert!(!evaluate(10, &vars, "data.temperature > 30", &input)
+            .await
+            .unwrap());
+        assert!(evaluate(10, &vars, "data.foo.bar", &input).await.is_err());
+    }
 }
@@ -93,6 +93,17 @@ pub async fn struct_to_binary(
     })
 }
 
+// Evaluates the given (JavaScript) expression using the decoded payload. This is used for
+// filtering events.
+pub async fn evaluate_expression(
+    f_port: u8,
+    variables: &HashMap<String, String>,
+    expression: &str,
+    obj: &prost_types::Struct,
+) -> Result<bool> {
+    js::evaluate(f_port, variables, expression, obj).await
+}
+
 pub fn get_measurements(s: &pbjson_types::Struct) -> HashMap<String, pbjson_types::value::Kind> {
     let mut out: HashMap<String, pbjson_types::value::Kind> = HashMap::new();
 
@@ -376,13 +376,14 @@ impl Data {
     }
 
     fn set_tx_info(&mut self) -> Result<()> {
+        let rx_window = self.get_rx_window()?;
         let mut prefer_rx2_over_rx1 = self._prefer_rx2_dr()?;
         if self.network_conf.rx2_prefer_on_link_budget {
             prefer_rx2_over_rx1 = prefer_rx2_over_rx1 || self._prefer_rx2_link_budget()?;
         }
 
         // RX2 is prefered and the RX window is set to automatic.
-        if prefer_rx2_over_rx1 && self.network_conf.rx_window == 0 {
+        if prefer_rx2_over_rx1 && rx_window == 0 {
             // RX2
             self.set_tx_info_for_rx2()?;
 
@@ -390,12 +391,12 @@ impl Data {
             self.set_tx_info_for_rx1()?;
         } else {
             // RX1
-            if [0, 1].contains(&self.network_conf.rx_window) {
+            if [0, 1].contains(&rx_window) {
                 self.set_tx_info_for_rx1()?;
             }
 
             // RX2
-            if [0, 2].contains(&self.network_conf.rx_window) {
+            if [0, 2].contains(&rx_window) {
                 self.set_tx_info_for_rx2()?;
             }
         }
@@ -403,6 +404,22 @@ impl Data {
         Ok(())
     }
 
+    // Returns the RX window to use. For devices uplinking using LR-FHSS, the device-profile
+    // can override the system RX window.
+    fn get_rx_window(&self) -> Result<u8> {
+        if self.device_profile.lr_fhss_rx_window != 0 {
+            if let Some(ufs) = &self.uplink_frame_set {
+                if let lrwn::region::DataRateModulation::LrFhss(_) =
+                    self.region_conf.get_data_rate(ufs.dr)?
+                {
+                    return Ok(self.device_profile.lr_fhss_rx_window as u8);
+                }
+            }
+        }
+
+        Ok(self.network_conf.rx_window)
+    }
+
     fn set_tx_info_relayed(&mut self) -> Result<()> {
         let mut prefer_rx2_over_rx1 = self._prefer_rx2_dr()?;
         if self.network_conf.rx2_prefer_on_link_budget {
@@ -517,9 +534,17 @@ impl Data {
                         Some(v) => v as u32,
                         None => 0,
                     },
+                    correlation_id: qi.correlation_id.clone(),
                 };
 
                 integration::ack_event(self.application.id, &self.device.variables, &pl).await;
+                self.send_downlink_event(
+                    &device_info,
+                    &qi,
+                    integration_pb::DownlinkState::Expired,
+                    "Confirmed downlink was not acknowledged before the timeout",
+                )
+                .await;
                 warn!(dev_eui = %self.device.dev_eui, device_queue_item_id = %qi.id, "Device queue-item discarded because of timeout");
 
                 continue;
@@ -550,6 +575,13 @@ impl Data {
                 };
 
                 integration::log_event(self.application.id, &self.device.variables, &pl).await;
+                self.send_downlink_event(
+                    &device_info,
+                    &qi,
+                    integration_pb::DownlinkState::Dropped,
+                    "Device queue-item exceeds the max. payload size",
+                )
+                .await;
                 warn!(dev_eui = %self.device.dev_eui, device_queue_item_id = %qi.id, "Device queue-item discarded because of max. payload size");
 
                 continue;
@@ -586,6 +618,13 @@ impl Data {
                 };
 
                 integration::log_event(self.application.id, &self.device.variables, &pl).await;
+                self.send_downlink_event(
+                    &device_info,
+                    &qi,
+                    integration_pb::DownlinkState::Dropped,
+                    "Device queue-item frame-counter is invalid",
+                )
+                .await;
                 warn!(dev_eui = %self.device.dev_eui, device_queue_item_id = %qi.id, "Device queue-item discarded because of invalid frame-counter");
 
                 continue;
@@ -593,6 +632,27 @@ impl Data {
         }
     }
 
+    async fn send_downlink_event(
+        &self,
+        device_info: &integration_pb::DeviceInfo,
+        qi: &device_queue::DeviceQueueItem,
+        state: integration_pb::DownlinkState,
+        reason: &str,
+    ) {
+        let pl = integration_pb::DownlinkEvent {
+            time: Some(Utc::now().into()),
+            device_info: Some(device_info.clone()),
+            queue_item_id: qi.id.to_string(),
+            correlation_id: qi.correlation_id.clone(),
+            state: state.into(),
+            reason: reason.to_string(),
+            f_port: qi.f_port as u32,
+            confirmed: qi.confirmed,
+        };
+
+        integration::downlink_event(self.application.id, &self.device.variables, &pl).await;
+    }
+
     async fn set_mac_commands(&mut self) -> Result<()> {
         let conf = config::get();
         if conf.network.mac_commands_disabled {
@@ -1254,6 +1314,8 @@ impl Data {
             installation_margin: self.network_conf.installation_margin,
             min_dr: self.network_conf.min_dr,
             max_dr: self.network_conf.max_dr,
+            uplink_dwell_time_400ms: self.network_conf.uplink_dwell_time_400ms
+                || self.region_conf.get_dwell_time_400ms(),
             uplink_history: ds.uplink_adr_history.clone(),
             skip_f_cnt_check: ds.skip_f_cnt_check,
             device_variables: self.device.variables.into_hashmap(),
@@ -2162,7 +2224,7 @@ impl Data {
         });
 
         // get remaining payload size
-        let max_pl_size = self.region_conf.get_max_payload_size(
+        let max_pl_size = self.get_max_payload_size(
             ds.mac_version().from_proto(),
             self.device_profile.reg_params_revision,
             rx1_dr_index,
@@ -2231,7 +2293,7 @@ impl Data {
         });
 
         // get remaining payload size (relay)
-        let max_pl_size_relay = self.region_conf.get_max_payload_size(
+        let max_pl_size_relay = self.get_max_payload_size(
             relay_ds.mac_version().from_proto(),
             relay_ctx.device_profile.reg_params_revision,
             rx1_dr_index_relay,
@@ -2241,7 +2303,7 @@ impl Data {
         let rx1_dr_index_ed = self
             .region_conf
             .get_rx1_data_rate_index(relay_ctx.req.metadata.dr, ds.rx1_dr_offset as usize)?;
-        let max_pl_size_ed = self.region_conf.get_max_payload_size(
+        let max_pl_size_ed = self.get_max_payload_size(
             ds.mac_version().from_proto(),
             self.device_profile.reg_params_revision,
             rx1_dr_index_ed,
@@ -2320,7 +2382,7 @@ impl Data {
         }
 
         // get remaining payload size
-        let max_pl_size = self.region_conf.get_max_payload_size(
+        let max_pl_size = self.get_max_payload_size(
             ds.mac_version().from_proto(),
             self.device_profile.reg_params_revision,
             ds.rx2_dr as u8,
@@ -2390,14 +2452,14 @@ impl Data {
         }
 
         // get remaining payload size (relay).
-        let max_pl_size_relay = self.region_conf.get_max_payload_size(
+        let max_pl_size_relay = self.get_max_payload_size(
             relay_ds.mac_version().from_proto(),
             relay_ctx.device_profile.reg_params_revision,
             relay_ds.rx2_dr as u8,
         )?;
 
         // get remaining payload size (end-device).
-        let max_pl_size_ed = self.region_conf.get_max_payload_size(
+        let max_pl_size_ed = self.get_max_payload_size(
             ds.mac_version().from_proto(),
             self.device_profile.reg_params_revision,
             ds.rx2_dr as u8,
@@ -2507,7 +2569,7 @@ impl Data {
         }
 
         // get remaining payload size
-        let max_pl_size = self.region_conf.get_max_payload_size(
+        let max_pl_size = self.get_max_payload_size(
             ds.mac_version().from_proto(),
             self.device_profile.reg_params_revision,
             ds.class_b_ping_slot_dr as u8,
@@ -2526,6 +2588,22 @@ impl Data {
         Ok(())
     }
 
+    fn get_max_payload_size(
+        &self,
+        mac_version: lrwn::region::MacVersion,
+        reg_params_revision: lrwn::region::Revision,
+        dr: u8,
+    ) -> Result<lrwn::region::MaxPayloadSize> {
+        region::get_max_payload_size(
+            &**self.region_conf,
+            mac_version,
+            reg_params_revision,
+            dr,
+            self.network_conf.downlink_dwell_time_400ms || self.region_conf.get_dwell_time_400ms(),
+            false,
+        )
+    }
+
     fn _prefer_rx2_dr(&self) -> Result<bool> {
         let ds = self.device.get_device_session()?;
 
@@ -2720,9 +2798,21 @@ mod test {
             expected_queue_item: Option<device_queue::DeviceQueueItem>,
             expected_ack_event: Option<integration_pb::AckEvent>,
             expected_log_event: Option<integration_pb::LogEvent>,
+            expected_downlink_event: Option<integration_pb::DownlinkEvent>,
         }
 
         let qi_id = Uuid::new_v4();
+        let device_info = integration_pb::DeviceInfo {
+            tenant_id: t.id.to_string(),
+            tenant_name: t.name.clone(),
+            application_id: app.id.to_string(),
+            application_name: app.name.clone(),
+            device_profile_id: dp.id.to_string(),
+            device_profile_name: dp.name.clone(),
+            device_name: d.name.clone(),
+            dev_eui: d.dev_eui.to_string(),
+            ..Default::default()
+        };
 
         let tests = vec![
             Test {
@@ -2733,6 +2823,7 @@ mod test {
                     dev_eui: d.dev_eui,
                     f_port: 1,
                     data: vec![1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11],
+                    correlation_id: "size".into(),
                     ..Default::default()
                 }],
                 expected_queue_item: None,
@@ -2764,6 +2855,16 @@ mod test {
                     .collect(),
                     ..Default::default()
                 }),
+                expected_downlink_event: Some(integration_pb::DownlinkEvent {
+                    device_info: Some(device_info.clone()),
+                    queue_item_id: qi_id.to_string(),
+                    correlation_id: "size".into(),
+                    state: integration_pb::DownlinkState::Dropped.into(),
+                    reason: "Device queue-item exceeds the max. payload size".into(),
+                    f_port: 1,
+                    confirmed: false,
+                    ..Default::default()
+                }),
             },
             Test {
                 name: "is pending".into(),
@@ -2775,6 +2876,8 @@ mod test {
                     f_cnt_down: Some(10),
                     data: vec![1, 2, 3],
                     is_pending: true,
+                    confirmed: true,
+                    correlation_id: "pending".into(),
                     ..Default::default()
                 }],
                 expected_queue_item: None,
@@ -2794,6 +2897,17 @@ mod test {
                     queue_item_id: qi_id.to_string(),
                     acknowledged: false,
                     f_cnt_down: 10,
+                    correlation_id: "pending".into(),
+                    ..Default::default()
+                }),
+                expected_downlink_event: Some(integration_pb::DownlinkEvent {
+                    device_info: Some(device_info.clone()),
+                    queue_item_id: qi_id.to_string(),
+                    correlation_id: "pending".into(),
+                    state: integration_pb::DownlinkState::Expired.into(),
+                    reason: "Confirmed downlink was not acknowledged before the timeout".into(),
+                    f_port: 1,
+                    confirmed: true,
                     ..Default::default()
                 }),
             },
@@ -2807,6 +2921,7 @@ mod test {
                     data: vec![1, 2, 3],
                     f_cnt_down: Some(5),
                     is_encrypted: true,
+                    correlation_id: "f_cnt".into(),
                     ..Default::default()
                 }],
                 expected_queue_item: None,
@@ -2837,6 +2952,16 @@ mod test {
                     .collect(),
                     ..Default::default()
                 }),
+                expected_downlink_event: Some(integration_pb::DownlinkEvent {
+                    device_info: Some(device_info.clone()),
+                    queue_item_id: qi_id.to_string(),
+                    correlation_id: "f_cnt".into(),
+                    state: integration_pb::DownlinkState::Dropped.into(),
+                    reason: "Device queue-item frame-counter is invalid".into(),
+                    f_port: 1,
+                    confirmed: false,
+                    ..Default::default()
+                }),
             },
             Test {
                 name: "valid payload".into(),
@@ -2857,6 +2982,7 @@ mod test {
                 }),
                 expected_log_event: None,
                 expected_ack_event: None,
+                expected_downlink_event: None,
             },
         ];
 
@@ -2925,6 +3051,17 @@ mod test {
                 assert_eq!(ack, &event);
             }
 
+            let downlink_events = integration::mock::get_downlink_events().await;
+            if let Some(dl) = &tst.expected_downlink_event {
+                assert_eq!(1, downlink_events.len());
+                let mut event = downlink_events[0].clone();
+                assert_ne!(None, event.time);
+                event.time = None;
+                assert_eq!(dl, &event);
+            } else {
+                assert!(downlink_events.is_empty());
+            }
+
             if let Some(qi) = &tst.expected_queue_item {
                 assert_ne!(None, ctx.device_queue_item);
                 assert_eq!(qi.id, ctx.device_queue_item.as_ref().unwrap().id);
@@ -2932,6 +3069,116 @@ mod test {
         }
     }
 
+    #[tokio::test]
+    async fn test_get_rx_window() {
+        let _guard = test::prepare().await;
+
+        let ufs = |dr: u8| UplinkFrameSet {
+            uplink_set_id: Uuid::new_v4(),
+            dr,
+            ch: 0,
+            phy_payload: lrwn::PhyPayload {
+                mhdr: lrwn::MHDR {
+                    m_type: lrwn::MType::UnconfirmedDataUp,
+                    major: lrwn::Major::LoRaWANR1,
+                },
+                payload: lrwn::Payload::MACPayload(lrwn::MACPayload {
+                    fhdr: Default::default(),
+                    f_port: None,
+                    frm_payload: None,
+                }),
+                mic: None,
+            },
+            tx_info: Default::default(),
+            rx_info_set: vec![],
+            gateway_private_up_map: HashMap::new(),
+            gateway_private_down_map: HashMap::new(),
+            gateway_tenant_id_map: HashMap::new(),
+            region_common_name: lrwn::region::CommonName::EU868,
+            region_config_id: "eu868".into(),
+            roaming_meta_data: None,
+        };
+
+        struct Test {
+            name: String,
+            network_rx_window: u8,
+            lr_fhss_rx_window: i16,
+            uplink_frame_set: Option<UplinkFrameSet>,
+            expected_rx_window: u8,
+        }
+
+        let tests = vec![
+            Test {
+                name: "lr-fhss rx window not set".into(),
+                network_rx_window: 0,
+                lr_fhss_rx_window: 0,
+                uplink_frame_set: Some(ufs(8)),
+                expected_rx_window: 0,
+            },
+            Test {
+                name: "lr-fhss uplink".into(),
+                network_rx_window: 0,
+                lr_fhss_rx_window: 2,
+                uplink_frame_set: Some(ufs(8)),
+                expected_rx_window: 2,
+            },
+            Test {
+                name: "lr-fhss uplink overrides network rx window".into(),
+                network_rx_window: 2,
+                lr_fhss_rx_window: 1,
+                uplink_frame_set: Some(ufs(10)),
+                expected_rx_window: 1,
+            },
+            Test {
+                name: "lora uplink".into(),
+                network_rx_window: 1,
+                lr_fhss_rx_window: 2,
+                uplink_frame_set: Some(ufs(5)),
+                expected_rx_window: 1,
+            },
+            Test {
+                name: "no uplink".into(),
+                network_rx_window: 0,
+                lr_fhss_rx_window: 2,
+                uplink_frame_set: None,
+                expected_rx_window: 0,
+            },
+        ];
+
+        for tst in &tests {
+            println!("> {}", tst.name);
+
+            let mut network_conf = config::get_region_network("eu868").unwrap();
+            network_conf.rx_window = tst.network_rx_window;
+
+            let ctx = Data {
+                relay_context: None,
+                uplink_frame_set: tst.uplink_frame_set.clone(),
+                tenant: Default::default(),
+                application: Default::default(),
+                device_profile: device_profile::DeviceProfile {
+                    lr_fhss_rx_window: tst.lr_fhss_rx_window,
+                    ..Default::default()
+                },
+                device: Default::default(),
+                network_conf,
+                region_conf: region::get("eu868").unwrap(),
+                must_send: false,
+                must_ack: false,
+                mac_commands: vec![],
+                device_gateway_rx_info: None,
+                downlink_gateway: None,
+                downlink_frame: Default::default(),
+                downlink_frame_items: vec![],
+                immediately: false,
+                device_queue_item: None,
+                more_device_queue_items: false,
+            };
+
+            assert_eq!(tst.expected_rx_window, ctx.get_rx_window().unwrap());
+        }
+    }
+
     #[test]
     fn test_filter_mac_commands() {
         struct Test {
@@ -3468,7 +3715,7 @@ mod test {
 
             // cleanup devices
             for (dev_eui, _) in &test.relay_devices {
-                device::delete(dev_eui).await.unwrap();
+                device::purge(dev_eui).await.unwrap();
             }
 
             // We can not predict the w_f_cnt_last_request timestamp.
@@ -3917,7 +4164,7 @@ mod test {
 
             // cleanup devices
             for (dev_eui, _) in &test.relay_devices {
-                device::delete(dev_eui).await.unwrap();
+                device::purge(dev_eui).await.unwrap();
             }
 
             assert_eq!(test.expected_mac_commands, ctx.mac_commands);
@@ -4537,7 +4784,7 @@ mod test {
 
             // cleanup devices
             for dev_eui in &test.relay_devices {
-                device::delete(dev_eui).await.unwrap();
+                device::purge(dev_eui).await.unwrap();
             }
 
             assert_eq!(test.expected_mac_commands, ctx.mac_commands);
@@ -1,11 +1,12 @@
 use std::str::FromStr;
+use std::time::Duration;
 
 use anyhow::Result;
 use rand::seq::SliceRandom;
 use uuid::Uuid;
 
 use chirpstack_api::{gw, internal};
-use lrwn::region::DataRateModulation;
+use lrwn::region::{airtime, DataRateModulation, FskDataRate, LoraDataRate};
 
 use crate::config;
 use crate::region;
@@ -116,6 +117,33 @@ pub fn set_tx_info_data_rate(
     Ok(())
 }
 
+// Returns the time on air of the downlink, based on the modulation of the tx_info.
+pub fn get_time_on_air(
+    tx_info: &chirpstack_api::gw::DownlinkTxInfo,
+    phy_payload_size: usize,
+) -> Result<Duration> {
+    let dr = match tx_info
+        .modulation
+        .as_ref()
+        .and_then(|v| v.parameters.as_ref())
+        .ok_or_else(|| anyhow!("modulation parameters must not be None"))?
+    {
+        gw::modulation::Parameters::Lora(v) => DataRateModulation::Lora(LoraDataRate {
+            spreading_factor: v.spreading_factor as u8,
+            bandwidth: v.bandwidth,
+            coding_rate: v.code_rate().into(),
+        }),
+        gw::modulation::Parameters::Fsk(v) => DataRateModulation::Fsk(FskDataRate {
+            bitrate: v.datarate,
+        }),
+        gw::modulation::Parameters::LrFhss(_) => {
+            return Err(anyhow!("LR-FHSS is not supported for downlink"));
+        }
+    };
+
+    airtime::get_time_on_air(&dr, phy_payload_size, false)
+}
+
 #[cfg(test)]
 mod tests {
     use std::collections::HashMap;
@@ -337,4 +365,25 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn test_get_time_on_air() {
+        let tx_info = gw::DownlinkTxInfo {
+            modulation: Some(gw::Modulation {
+                parameters: Some(gw::modulation::Parameters::Lora(gw::LoraModulationInfo {
+                    bandwidth: 125000,
+                    spreading_factor: 12,
+                    code_rate: gw::CodeRate::Cr45.into(),
+                    ..Default::default()
+                })),
+            }),
+            ..Default::default()
+        };
+        assert_eq!(
+            Duration::from_micros(1_155_072),
+            get_time_on_air(&tx_info, 13).unwrap()
+        );
+
+        assert!(get_time_on_air(&gw::DownlinkTxInfo::default(), 13).is_err());
+    }
 }
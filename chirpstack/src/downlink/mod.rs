@@ -1,5 +1,8 @@
 use tracing::info;
 
+use crate::region;
+use crate::storage::{device, device_profile, error::Error};
+
 pub mod classb;
 pub mod data;
 pub mod data_fns;
@@ -22,3 +25,35 @@ pub async fn setup() {
         scheduler::multicast_group_queue_scheduler_loop().await;
     });
 }
+
+// Validates that a downlink payload of the given size can be sent to the device using at
+// least one of the downlink data-rates (taking the dwell-time into account). When the device
+// is not yet activated, the first region configuration matching the device-profile is used.
+pub fn validate_payload_size(
+    dev: &device::Device,
+    dp: &device_profile::DeviceProfile,
+    size: usize,
+) -> Result<(), Error> {
+    let region_config_id = match &dev.device_session {
+        Some(ds) if !ds.region_config_id.is_empty() => ds.region_config_id.clone(),
+        _ => match region::get_region_config_id(dp.region) {
+            Ok(v) => v,
+            Err(_) => return Ok(()),
+        },
+    };
+
+    let max_pl_size = region::get_max_downlink_payload_size(
+        &region_config_id,
+        dp.mac_version,
+        dp.reg_params_revision,
+    )?;
+
+    if size > max_pl_size {
+        return Err(Error::Validation(format!(
+            "Payload size {} exceeds the max. downlink payload size {} (region: {})",
+            size, max_pl_size, region_config_id
+        )));
+    }
+
+    Ok(())
+}
@@ -94,11 +94,15 @@ impl Multicast {
         trace!("Validating payload size for DR");
         let mg = self.multicast_group.as_ref().unwrap();
         let region_conf = region::get(&self.region_config_id)?;
+        let network_conf = config::get_region_network(&self.region_config_id)?;
 
-        let max_pl_size = region_conf.get_max_payload_size(
+        let max_pl_size = region::get_max_payload_size(
+            &**region_conf,
             lrwn::region::MacVersion::Latest,
             lrwn::region::Revision::Latest,
             mg.dr as u8,
+            network_conf.downlink_dwell_time_400ms || region_conf.get_dwell_time_400ms(),
+            false,
         )?;
 
         if self.multicast_group_queue_item.data.len() > max_pl_size.n {
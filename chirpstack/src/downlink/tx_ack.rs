@@ -5,13 +5,14 @@ use uuid::Uuid;
 
 use lrwn::{AES128Key, MType, Payload, PhyPayload, EUI64};
 
+use super::helpers;
 use crate::api::helpers::ToProto;
 use crate::storage::{
     application,
     device::{self, DeviceClass},
     device_profile, device_queue, downlink_frame,
     helpers::get_all_device_data,
-    multicast, tenant,
+    multicast, tenant, tenant_usage,
 };
 use crate::{integration, stream};
 use chirpstack_api::{common, gw, integration as integration_pb, internal, stream as stream_pb};
@@ -117,6 +118,7 @@ impl TxAck {
                 }
 
                 ctx.save_device_session().await?;
+                ctx.save_tenant_usage().await?;
             }
 
             if ctx.is_multicast_downlink() {
@@ -402,6 +404,23 @@ impl TxAck {
         Ok(())
     }
 
+    async fn save_tenant_usage(&self) -> Result<()> {
+        trace!("Saving tenant usage");
+        let tenant = self.tenant.as_ref().unwrap();
+        let dfi = self.downlink_frame_item.as_ref().unwrap();
+        let tx_info = dfi
+            .tx_info
+            .as_ref()
+            .ok_or_else(|| anyhow!("tx_info is None"))?;
+
+        tenant_usage::save_downlink(
+            &tenant.id,
+            dfi.phy_payload.len(),
+            helpers::get_time_on_air(tx_info, dfi.phy_payload.len())?,
+        )
+        .await
+    }
+
     async fn log_tx_ack_error(&self) -> Result<()> {
         trace!("Logging tx ack error");
 
@@ -481,6 +500,7 @@ impl TxAck {
             f_cnt_down: qi.f_cnt_down.unwrap_or(0) as u32,
             gateway_id,
             tx_info: self.downlink_frame_item.as_ref().unwrap().tx_info.clone(),
+            correlation_id: qi.correlation_id.clone(),
         };
 
         integration::txack_event(app.id, &dev.variables, &pl).await;
@@ -530,6 +550,7 @@ impl TxAck {
             f_cnt_down: qi.f_cnt_down.unwrap_or(0) as u32,
             gateway_id,
             tx_info: self.downlink_frame_item.as_ref().unwrap().tx_info.clone(),
+            correlation_id: qi.correlation_id.clone(),
         };
 
         integration::txack_event(app.id, &dev.variables, &pl).await;
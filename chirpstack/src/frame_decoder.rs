@@ -0,0 +1,383 @@
+use anyhow::{Context, Result};
+use chrono::Utc;
+use serde::Serialize;
+use tracing::{info, warn};
+
+use crate::api::helpers::FromProto;
+use crate::codec;
+use crate::helpers::errors::PrintFullError;
+use crate::storage::{device, device_keys, device_profile, error::Error};
+use lrwn::{AES128Key, MType, Payload, PhyPayload, EUI64};
+
+#[derive(Default, Clone)]
+pub struct Options {
+    // Lookup the device to validate the MIC and to decrypt the FOpts and FRMPayload.
+    pub lookup_device: bool,
+    // Use this device instead of the DevAddr (or JoinRequest DevEUI) based lookup.
+    pub dev_eui: Option<EUI64>,
+    // Uplink data-rate and channel index, only used for the LoRaWAN 1.1 uplink MIC.
+    pub tx_dr: u8,
+    pub tx_ch: u8,
+    // Decode the decrypted FRMPayload using the device-profile codec.
+    pub run_codec: bool,
+}
+
+#[derive(Serialize)]
+pub struct DecodedFrame {
+    pub phy_payload: PhyPayload,
+    pub dev_eui: Option<EUI64>,
+    pub mic_valid: Option<bool>,
+    pub f_cnt: Option<u32>,
+    pub object: Option<pbjson_types::Struct>,
+    pub codec_error: Option<String>,
+}
+
+// Decode the given PhyPayload. When device lookup is enabled, the device-session is used to
+// validate the MIC, decrypt the FOpts and FRMPayload and optionally to run the payload codec.
+// As the FOpts are encrypted in case of LoRaWAN 1.1, these are only decoded to mac-commands
+// after the MIC has been validated using the device-session. Otherwise these are returned as-is.
+pub async fn decode(phy: PhyPayload, opts: &Options) -> Result<DecodedFrame> {
+    let mut out = DecodedFrame {
+        phy_payload: phy,
+        dev_eui: None,
+        mic_valid: None,
+        f_cnt: None,
+        object: None,
+        codec_error: None,
+    };
+
+    if opts.lookup_device || opts.dev_eui.is_some() {
+        match out.phy_payload.mhdr.m_type {
+            MType::JoinRequest => decode_join_request(&mut out, opts).await?,
+            MType::UnconfirmedDataUp
+            | MType::ConfirmedDataUp
+            | MType::UnconfirmedDataDown
+            | MType::ConfirmedDataDown => decode_data(&mut out, opts).await?,
+            _ => {}
+        }
+    }
+
+    Ok(out)
+}
+
+async fn decode_join_request(out: &mut DecodedFrame, opts: &Options) -> Result<()> {
+    let dev_eui = match &out.phy_payload.payload {
+        Payload::JoinRequest(pl) => opts.dev_eui.unwrap_or(pl.dev_eui),
+        _ => return Ok(()),
+    };
+
+    let dk = match device_keys::get(&dev_eui).await {
+        Ok(v) => v,
+        Err(Error::NotFound(_)) => {
+            info!(dev_eui = %dev_eui, "Device-keys do not exist");
+            return Ok(());
+        }
+        Err(e) => return Err(anyhow::Error::new(e)),
+    };
+
+    out.dev_eui = Some(dev_eui);
+    out.mic_valid = Some(
+        out.phy_payload
+            .validate_join_request_mic(&dk.nwk_key)
+            .context("Validate MIC")?,
+    );
+
+    Ok(())
+}
+
+async fn decode_data(out: &mut DecodedFrame, opts: &Options) -> Result<()> {
+    let uplink = matches!(
+        out.phy_payload.mhdr.m_type,
+        MType::UnconfirmedDataUp | MType::ConfirmedDataUp
+    );
+    let (dev_addr, f_cnt_orig, f_port, ack) = match &out.phy_payload.payload {
+        Payload::MACPayload(pl) => (
+            pl.fhdr.devaddr,
+            pl.fhdr.f_cnt,
+            pl.f_port.unwrap_or(0),
+            pl.fhdr.f_ctrl.ack,
+        ),
+        _ => return Ok(()),
+    };
+
+    let devices = match &opts.dev_eui {
+        Some(v) => vec![device::get(v).await?],
+        None => device::get_by_dev_addr(dev_addr).await?,
+    };
+
+    for d in &devices {
+        let mut sessions = vec![];
+
+        if let Some(ds) = &d.device_session {
+            sessions.push(ds.clone());
+            if let Some(ds) = &ds.pending_rejoin_device_session {
+                sessions.push(*ds.clone());
+            }
+        }
+
+        for ds in &sessions {
+            if ds.dev_addr != dev_addr.to_vec() {
+                continue;
+            }
+
+            let mac_version = ds.mac_version().from_proto();
+            let s_nwk_s_int_key = AES128Key::from_slice(&ds.s_nwk_s_int_key)?;
+
+            // The device-session holds the next expected frame-counter, we therefore use the
+            // same logic for the downlink counters to get the full frame-counter.
+            let full_f_cnt = if uplink {
+                device::get_full_f_cnt_up(ds.f_cnt_up, f_cnt_orig)
+            } else if f_port > 0 {
+                device::get_full_f_cnt_up(ds.get_a_f_cnt_down(), f_cnt_orig)
+            } else {
+                device::get_full_f_cnt_up(ds.n_f_cnt_down, f_cnt_orig)
+            };
+
+            if let Payload::MACPayload(pl) = &mut out.phy_payload.payload {
+                pl.fhdr.f_cnt = full_f_cnt;
+            }
+
+            let mic_ok = if uplink {
+                let f_nwk_s_int_key = AES128Key::from_slice(&ds.f_nwk_s_int_key)?;
+                out.phy_payload
+                    .validate_uplink_data_mic(
+                        mac_version,
+                        ds.conf_f_cnt,
+                        opts.tx_dr,
+                        opts.tx_ch,
+                        &f_nwk_s_int_key,
+                        &s_nwk_s_int_key,
+                    )
+                    .context("Validate MIC")?
+            } else {
+                // For LoRaWAN 1.1, the ACK of a downlink refers to the last uplink.
+                let conf_f_cnt = if ack { ds.f_cnt_up.wrapping_sub(1) } else { 0 };
+                out.phy_payload
+                    .validate_downlink_data_mic(mac_version, conf_f_cnt, &s_nwk_s_int_key)
+                    .context("Validate MIC")?
+            };
+
+            if !mic_ok {
+                // Restore the original f_cnt.
+                if let Payload::MACPayload(pl) = &mut out.phy_payload.payload {
+                    pl.fhdr.f_cnt = f_cnt_orig;
+                }
+                continue;
+            }
+
+            out.dev_eui = Some(d.dev_eui);
+            out.mic_valid = Some(true);
+            out.f_cnt = Some(full_f_cnt);
+
+            let nwk_s_enc_key = AES128Key::from_slice(&ds.nwk_s_enc_key)?;
+
+            if mac_version == lrwn::MACVersion::LoRaWAN1_0 {
+                if let Err(e) = out.phy_payload.decode_f_opts_to_mac_commands() {
+                    warn!(error = %e.full(), "Decoding f_opts mac-commands failed");
+                }
+            } else if let Err(e) = out.phy_payload.decrypt_f_opts(&nwk_s_enc_key) {
+                warn!(error = %e.full(), "Decrypting f_opts mac-commands failed");
+            }
+
+            // Mac-commands (f_port=0) or Relay payload (f_port=226).
+            if f_port == 0 || f_port == lrwn::LA_FPORT_RELAY {
+                if let Err(e) = out.phy_payload.decrypt_frm_payload(&nwk_s_enc_key) {
+                    warn!(error = %e.full(), "Decrypting frm_payload failed");
+                }
+                return Ok(());
+            }
+
+            // In case the AppSKey is not known (end-to-end encryption), we can't decrypt the
+            // FRMPayload.
+            let app_s_key = match &ds.app_s_key {
+                Some(v) if ds.js_session_key_id.is_empty() && v.kek_label.is_empty() => {
+                    AES128Key::from_slice(&v.aes_key)?
+                }
+                _ => return Ok(()),
+            };
+            out.phy_payload
+                .decrypt_frm_payload(&app_s_key)
+                .context("Decrypt frm_payload")?;
+
+            if uplink && opts.run_codec {
+                let data = match &out.phy_payload.payload {
+                    Payload::MACPayload(lrwn::MACPayload {
+                        frm_payload: Some(lrwn::FRMPayload::Raw(b)),
+                        ..
+                    }) => b.clone(),
+                    _ => Vec::new(),
+                };
+
+                let dp = device_profile::get(&d.device_profile_id).await?;
+                match codec::binary_to_struct(
+                    dp.payload_codec_runtime,
+                    Utc::now(),
+                    f_port,
+                    &d.variables,
+                    &dp.payload_codec_script,
+                    &data,
+                )
+                .await
+                {
+                    Ok(v) => out.object = v,
+                    Err(e) => out.codec_error = Some(format!("{:#}", e)),
+                }
+            }
+
+            return Ok(());
+        }
+    }
+
+    out.mic_valid = Some(false);
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::storage::{application, tenant};
+    use crate::test;
+    use chirpstack_api::internal;
+    use lrwn::{DevAddr, FCtrl, MACPayload, Major, FHDR, MHDR};
+
+    fn uplink_phy(f_cnt: u32, key: &AES128Key) -> PhyPayload {
+        let mut phy = PhyPayload {
+            mhdr: MHDR {
+                m_type: MType::UnconfirmedDataUp,
+                major: Major::LoRaWANR1,
+            },
+            payload: Payload::MACPayload(MACPayload {
+                fhdr: FHDR {
+                    devaddr: DevAddr::from_be_bytes([1, 2, 3, 4]),
+                    f_cnt,
+                    f_ctrl: FCtrl::default(),
+                    ..Default::default()
+                },
+                f_port: Some(10),
+                frm_payload: Some(lrwn::FRMPayload::Raw(vec![1, 2, 3])),
+            }),
+            mic: None,
+        };
+        phy.encrypt_frm_payload(key).unwrap();
+        phy.set_uplink_data_mic(lrwn::MACVersion::LoRaWAN1_0, 0, 0, 0, key, key)
+            .unwrap();
+        phy
+    }
+
+    #[tokio::test]
+    async fn test_decode_without_lookup() {
+        let key = AES128Key::from_bytes([1, 2, 3, 4, 5, 6, 7, 8, 1, 2, 3, 4, 5, 6, 7, 8]);
+        let phy = uplink_phy(10, &key);
+
+        let out = decode(phy.clone(), &Options::default()).await.unwrap();
+        assert_eq!(phy, out.phy_payload);
+        assert_eq!(None, out.dev_eui);
+        assert_eq!(None, out.mic_valid);
+        assert_eq!(None, out.f_cnt);
+
+        // The FOpts are not decoded, as these could be encrypted (LoRaWAN 1.1).
+        let mut phy = uplink_phy(10, &key);
+        if let Payload::MACPayload(pl) = &mut phy.payload {
+            pl.fhdr.f_opts = lrwn::MACCommandSet::new(vec![lrwn::MACCommand::Raw(vec![0x02])]);
+        }
+        let phy = PhyPayload::from_slice(&phy.to_vec().unwrap()).unwrap();
+        let out = decode(phy.clone(), &Options::default()).await.unwrap();
+        assert_eq!(phy, out.phy_payload);
+        if let Payload::MACPayload(pl) = &out.phy_payload.payload {
+            assert_eq!(
+                lrwn::MACCommandSet::new(vec![lrwn::MACCommand::Raw(vec![0x02])]),
+                pl.fhdr.f_opts
+            );
+        } else {
+            panic!("Expected MACPayload");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_decode_with_lookup() {
+        let _guard = test::prepare().await;
+
+        let key = AES128Key::from_bytes([1, 2, 3, 4, 5, 6, 7, 8, 1, 2, 3, 4, 5, 6, 7, 8]);
+
+        let t = tenant::create(tenant::Tenant {
+            name: "test-tenant".into(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        let dp = device_profile::create(device_profile::DeviceProfile {
+            name: "test-dp".into(),
+            tenant_id: t.id,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        let app = application::create(application::Application {
+            name: "test-app".into(),
+            tenant_id: t.id,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        let d = device::create(device::Device {
+            application_id: app.id,
+            device_profile_id: dp.id,
+            name: "test-dev".into(),
+            dev_eui: EUI64::from_be_bytes([1, 1, 1, 1, 1, 1, 1, 1]),
+            dev_addr: Some(DevAddr::from_be_bytes([1, 2, 3, 4])),
+            device_session: Some(internal::DeviceSession {
+                dev_addr: vec![1, 2, 3, 4],
+                mac_version: chirpstack_api::common::MacVersion::Lorawan104.into(),
+                s_nwk_s_int_key: key.to_vec(),
+                f_nwk_s_int_key: key.to_vec(),
+                nwk_s_enc_key: key.to_vec(),
+                app_s_key: Some(chirpstack_api::common::KeyEnvelope {
+                    kek_label: "".into(),
+                    aes_key: key.to_vec(),
+                }),
+                f_cnt_up: 11,
+                ..Default::default()
+            }),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        // Valid MIC, the already received frame is decrypted.
+        let out = decode(
+            uplink_phy(10, &key),
+            &Options {
+                lookup_device: true,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(Some(d.dev_eui), out.dev_eui);
+        assert_eq!(Some(true), out.mic_valid);
+        assert_eq!(Some(10), out.f_cnt);
+        if let Payload::MACPayload(pl) = &out.phy_payload.payload {
+            assert_eq!(Some(lrwn::FRMPayload::Raw(vec![1, 2, 3])), pl.frm_payload);
+        } else {
+            panic!("Expected MACPayload");
+        }
+
+        // Invalid MIC.
+        let mut phy = uplink_phy(10, &key);
+        phy.mic = Some([0, 0, 0, 0]);
+        let out = decode(
+            phy,
+            &Options {
+                lookup_device: true,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(None, out.dev_eui);
+        assert_eq!(Some(false), out.mic_valid);
+    }
+}
@@ -0,0 +1,113 @@
+use anyhow::Result;
+use rand::RngCore;
+use totp_rs::{Algorithm, TOTP};
+
+const DIGITS: usize = 6;
+const PERIOD: u64 = 30;
+
+// Number of time-steps before and after the current time-step that are accepted to compensate
+// for clock drift.
+const SKEW: u64 = 1;
+
+// Generates a new random (160 bit) secret.
+pub fn generate_secret() -> Vec<u8> {
+    let mut b = vec![0u8; 20];
+    rand::thread_rng().fill_bytes(&mut b);
+    b
+}
+
+// Returns the base32 encoded secret, which can be used to manually enroll the secret in an
+// authenticator app.
+pub fn encode_secret(secret: &[u8]) -> Result<String> {
+    Ok(get_totp(secret, None, "")?.get_secret_base32())
+}
+
+// Returns the otpauth:// URI which can be used to enroll the secret in an authenticator app.
+pub fn provisioning_uri(issuer: &str, account: &str, secret: &[u8]) -> Result<String> {
+    Ok(get_totp(secret, Some(issuer), account)?.get_url())
+}
+
+// Validates the code against the given secret and returns the matching time-step counter.
+// None is returned in case the code is not valid.
+pub fn verify(secret: &[u8], code: &str, timestamp: u64) -> Result<Option<u64>> {
+    let totp = get_totp(secret, None, "")?;
+    let code = code.trim();
+    let current = timestamp / PERIOD;
+
+    // The time-steps are validated one by one, as the matching time-step is needed to reject
+    // codes that have already been used.
+    for c in current.saturating_sub(SKEW)..=current + SKEW {
+        if totp.check(code, c * PERIOD) {
+            return Ok(Some(c));
+        }
+    }
+
+    Ok(None)
+}
+
+fn get_totp(secret: &[u8], issuer: Option<&str>, account: &str) -> Result<TOTP> {
+    Ok(TOTP::new(
+        Algorithm::SHA1,
+        DIGITS,
+        0,
+        PERIOD,
+        secret.to_vec(),
+        issuer.map(|v| v.to_string()),
+        account.to_string(),
+    )?)
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+
+    // Returns the code for the given secret and unix timestamp.
+    pub fn generate(secret: &[u8], timestamp: u64) -> String {
+        get_totp(secret, None, "").unwrap().generate(timestamp)
+    }
+
+    #[test]
+    fn test_verify() {
+        let secret = b"12345678901234567890";
+
+        // RFC 6238, Appendix B (last 6 digits of the SHA1 test vectors).
+        assert_eq!(Some(1), verify(secret, "287082", 59).unwrap());
+        assert_eq!(
+            Some(37037036),
+            verify(secret, "081804", 1111111109).unwrap()
+        );
+
+        // accepted within the skew window
+        assert_eq!(Some(1), verify(secret, "287082", 89).unwrap());
+        assert_eq!(None, verify(secret, "287082", 120).unwrap());
+        assert_eq!(None, verify(secret, "000000", 59).unwrap());
+
+        assert_eq!("287082", generate(secret, 59));
+    }
+
+    #[test]
+    fn test_encode_secret() {
+        assert_eq!(
+            "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ",
+            encode_secret(b"12345678901234567890").unwrap()
+        );
+
+        // the secret must be at least 128 bits
+        assert!(encode_secret(b"1234567890").is_err());
+    }
+
+    #[test]
+    fn test_provisioning_uri() {
+        assert_eq!(
+            "otpauth://totp/ChirpStack:foo%40bar?secret=GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ&issuer=ChirpStack",
+            provisioning_uri("ChirpStack", "foo@bar", b"12345678901234567890").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_generate_secret() {
+        let secret = generate_secret();
+        assert_eq!(20, secret.len());
+        assert_ne!(secret, generate_secret());
+    }
+}
@@ -1,18 +1,28 @@
 use std::collections::HashMap;
+use std::time::Duration;
 
 use anyhow::Result;
 use async_trait::async_trait;
+use futures::StreamExt;
 use handlebars::Handlebars;
 use lapin::{
-    options::BasicPublishOptions, BasicProperties, Channel, Connection, ConnectionProperties,
+    options::{
+        BasicAckOptions, BasicConsumeOptions, BasicPublishOptions, QueueBindOptions,
+        QueueDeclareOptions,
+    },
+    types::FieldTable,
+    BasicProperties, Channel, Connection, ConnectionProperties,
 };
 use prost::Message;
+use regex::Regex;
 use serde::Serialize;
 use tokio::sync::RwLock;
-use tracing::{error, info};
+use tokio::time::sleep;
+use tracing::{error, info, trace, warn};
 
 use super::Integration as IntegrationTrait;
 use crate::config::AmqpIntegration as Config;
+use crate::helpers::errors::PrintFullError;
 use chirpstack_api::integration;
 
 // We define the connection and channel outside the Integration struct as the AMQP client does not
@@ -37,6 +47,13 @@ struct EventRoutingKeyContext {
     pub event: String,
 }
 
+#[derive(Serialize)]
+struct CommandRoutingKeyContext {
+    pub application_id: String,
+    pub dev_eui: String,
+    pub command: String,
+}
+
 impl<'a> Integration<'a> {
     pub async fn new(conf: &Config) -> Result<Integration<'a>> {
         info!("Initializing AMQP integration");
@@ -45,6 +62,37 @@ impl<'a> Integration<'a> {
         let mut templates = Handlebars::new();
         templates.register_escape_fn(handlebars::no_escape);
         templates.register_template_string("event_routing_key", &conf.event_routing_key)?;
+        templates.register_template_string("command_routing_key", &conf.command_routing_key)?;
+
+        // Command consumer loop
+        if !conf.command_queue.is_empty() {
+            let command_regex = Regex::new(&format!(
+                "^{}$",
+                templates.render(
+                    "command_routing_key",
+                    &CommandRoutingKeyContext {
+                        application_id: r"(?P<application_id>[\w-]+)".to_string(),
+                        dev_eui: r"(?P<dev_eui>[\w]+)".to_string(),
+                        command: r"(?P<command>[\w]+)".to_string(),
+                    },
+                )?
+            ))?;
+
+            tokio::spawn({
+                let templates = templates.clone();
+                let conf = conf.clone();
+
+                async move {
+                    loop {
+                        if let Err(e) = consume_commands(&conf, &templates, &command_regex).await {
+                            error!(error = %e.full(), "AMQP command consumer error");
+                        }
+
+                        sleep(Duration::from_secs(1)).await;
+                    }
+                }
+            });
+        }
 
         let i = Integration {
             templates,
@@ -240,6 +288,108 @@ impl<'a> IntegrationTrait for Integration<'a> {
         };
         self.publish_event(key, &b).await
     }
+
+    async fn downlink_event(
+        &self,
+        _vars: &HashMap<String, String>,
+        pl: &integration::DownlinkEvent,
+    ) -> Result<()> {
+        let di = pl.device_info.as_ref().unwrap();
+        let key = self.get_routing_key(&di.application_id, &di.dev_eui, "downlink")?;
+        let b = match self.json {
+            true => serde_json::to_vec(&pl)?,
+            false => pl.encode_to_vec(),
+        };
+        self.publish_event(key, &b).await
+    }
+}
+
+// Consumes the commands from the command queue. The consumer uses its own connection, as the
+// connection used for publishing events is re-created on error. The result of a command is
+// published as downlink event (enqueued or rejected).
+async fn consume_commands(
+    conf: &Config,
+    templates: &Handlebars<'_>,
+    command_regex: &Regex,
+) -> Result<()> {
+    let options = ConnectionProperties::default()
+        .with_executor(tokio_executor_trait::Tokio::current())
+        .with_reactor(tokio_reactor_trait::Tokio);
+
+    let conn = Connection::connect(&conf.url, options).await?;
+    let chan = conn.create_channel().await?;
+
+    let binding_key = templates.render(
+        "command_routing_key",
+        &CommandRoutingKeyContext {
+            application_id: "*".to_string(),
+            dev_eui: "*".to_string(),
+            command: "*".to_string(),
+        },
+    )?;
+
+    chan.queue_declare(
+        &conf.command_queue,
+        QueueDeclareOptions {
+            durable: true,
+            ..Default::default()
+        },
+        FieldTable::default(),
+    )
+    .await?;
+    chan.queue_bind(
+        &conf.command_queue,
+        "amq.topic",
+        &binding_key,
+        QueueBindOptions::default(),
+        FieldTable::default(),
+    )
+    .await?;
+
+    let mut consumer = chan
+        .basic_consume(
+            &conf.command_queue,
+            "",
+            BasicConsumeOptions::default(),
+            FieldTable::default(),
+        )
+        .await?;
+
+    info!(queue = %conf.command_queue, binding_key = %binding_key, "Consuming commands");
+
+    while let Some(delivery) = consumer.next().await {
+        let delivery = delivery?;
+        let routing_key = delivery.routing_key.to_string();
+        trace!(routing_key = %routing_key, "Command received");
+
+        match command_regex.captures(&routing_key) {
+            Some(caps) => {
+                info!(routing_key = %routing_key, "Command received for device");
+
+                match super::decode_down_command(
+                    &caps["dev_eui"],
+                    &caps["command"],
+                    conf.json,
+                    &delivery.data,
+                ) {
+                    Ok(cmd) => {
+                        super::handle_down_command(caps["application_id"].to_string(), cmd).await;
+                    }
+                    Err(e) => {
+                        warn!(routing_key = %routing_key, error = %e.full(), "Processing command error");
+                    }
+                }
+            }
+            None => {
+                warn!(routing_key = %routing_key, "Error parsing command routing-key (regex captures returned None)");
+            }
+        }
+
+        // Invalid commands are acknowledged as well, as a redelivery would fail again.
+        delivery.ack(BasicAckOptions::default()).await?;
+    }
+
+    Err(anyhow!("Command consumer stream closed"))
 }
 
 #[cfg(all(test, feature = "test-integration-amqp"))]
@@ -247,13 +397,9 @@ pub mod test {
     use std::env;
 
     use super::*;
-    use futures::stream::StreamExt;
-    use lapin::options::{
-        BasicAckOptions, BasicConsumeOptions, QueueBindOptions, QueueDeclareOptions,
-    };
-    use lapin::types::FieldTable;
-    use std::time::Duration;
-    use tokio::time::sleep;
+    use crate::storage::{application, device, device_profile, device_queue, tenant};
+    use crate::test;
+    use lrwn::EUI64;
     use uuid::Uuid;
 
     #[tokio::test]
@@ -266,6 +412,7 @@ pub mod test {
             json: true,
             event_routing_key: "application.{{application_id}}.device.{{dev_eui}}.event.{{event}}"
                 .to_string(),
+            ..Default::default()
         };
 
         let conn = loop {
@@ -338,4 +485,106 @@ pub mod test {
         );
         assert_eq!(serde_json::to_vec(&pl).unwrap(), delivery.data);
     }
+
+    #[tokio::test]
+    async fn test_amqp_commands() {
+        dotenv::dotenv().ok();
+        dotenv::from_filename(".env.local").ok();
+
+        let _guard = test::prepare().await;
+        crate::integration::set_mock().await;
+        crate::integration::mock::reset().await;
+
+        // setup base objects
+        let t = tenant::create(tenant::Tenant {
+            name: "test-tenant".into(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let app = application::create(application::Application {
+            name: "test-app".into(),
+            tenant_id: t.id,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let dp = device_profile::create(device_profile::DeviceProfile {
+            name: "test-dp".into(),
+            tenant_id: t.id,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let dev = device::create(device::Device {
+            name: "test-device".into(),
+            dev_eui: EUI64::from_be_bytes([1, 2, 3, 4, 5, 6, 7, 8]),
+            application_id: app.id,
+            device_profile_id: dp.id,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        // The routing-keys are different from test_amqp, such that the tests do not interfere.
+        let conf = Config {
+            url: env::var("TEST_AMQP_URL").unwrap(),
+            json: true,
+            event_routing_key: "test.event.{{application_id}}.{{dev_eui}}.{{event}}".to_string(),
+            command_queue: "test-command-queue".to_string(),
+            command_routing_key: "test.command.{{application_id}}.{{dev_eui}}.{{command}}"
+                .to_string(),
+        };
+
+        let conn = Connection::connect(
+            &conf.url,
+            ConnectionProperties::default()
+                .with_executor(tokio_executor_trait::Tokio::current())
+                .with_reactor(tokio_reactor_trait::Tokio),
+        )
+        .await
+        .unwrap();
+        let chan = conn.create_channel().await.unwrap();
+
+        let _i = Integration::new(&conf).await.unwrap();
+
+        // give the async consumer some time to setup the command queue
+        sleep(Duration::from_millis(200)).await;
+
+        // downlink command
+        let down_cmd = integration::DownlinkCommand {
+            id: Uuid::new_v4().to_string(),
+            dev_eui: dev.dev_eui.to_string(),
+            confirmed: false,
+            f_port: 10,
+            data: vec![1, 2, 3],
+            object: None,
+            correlation_id: "".into(),
+        };
+        chan.basic_publish(
+            "amq.topic",
+            &format!("test.command.{}.{}.down", app.id, dev.dev_eui),
+            BasicPublishOptions::default(),
+            &serde_json::to_vec(&down_cmd).unwrap(),
+            BasicProperties::default(),
+        )
+        .await
+        .unwrap()
+        .await
+        .unwrap();
+
+        sleep(Duration::from_millis(200)).await;
+
+        // enqueued event
+        let events = crate::integration::mock::get_downlink_events().await;
+        assert_eq!(1, events.len());
+        assert_eq!(integration::DownlinkState::Enqueued, events[0].state());
+        assert_eq!(down_cmd.id, events[0].queue_item_id);
+
+        let queue_items = device_queue::get_for_dev_eui(&dev.dev_eui).await.unwrap();
+        assert_eq!(1, queue_items.len());
+        assert_eq!(down_cmd.id, queue_items[0].id.to_string());
+        assert_eq!(10, queue_items[0].f_port);
+        assert_eq!(vec![1, 2, 3], queue_items[0].data);
+    }
 }
@@ -7,11 +7,10 @@ use base64::{engine::general_purpose, Engine as _};
 use hmac::{Hmac, Mac};
 use prost::Message;
 use reqwest::header::{HeaderMap, HeaderName, AUTHORIZATION, CONTENT_TYPE};
-use reqwest::Client;
 use sha2::Sha256;
 use tracing::{info, trace};
 
-use super::Integration as IntegrationTrait;
+use super::{http_client, Integration as IntegrationTrait};
 use crate::storage::application::AzureServiceBusConfiguration;
 use chirpstack_api::api::Encoding;
 use chirpstack_api::integration;
@@ -65,7 +64,7 @@ impl Integration {
             &(SystemTime::now() + Duration::from_secs(60 * 5)),
         )?;
 
-        let client = Client::builder().timeout(self.timeout).build()?;
+        let client = http_client();
         let mut headers = HeaderMap::new();
 
         headers.insert(AUTHORIZATION, token.parse()?);
@@ -91,6 +90,7 @@ impl Integration {
         info!(event = %event, dev_eui = %dev_eui, "Publishing event");
         let res = client
             .post(format!("{}/messages", self.uri))
+            .timeout(self.timeout)
             .body(pl.to_string())
             .headers(headers)
             .send()
@@ -222,6 +222,21 @@ impl IntegrationTrait for Integration {
         self.publish("integration", &di.application_id, &di.dev_eui, &pl)
             .await
     }
+
+    async fn downlink_event(
+        &self,
+        _vars: &HashMap<String, String>,
+        pl: &integration::DownlinkEvent,
+    ) -> Result<()> {
+        let di = pl.device_info.as_ref().unwrap();
+        let pl = match self.json {
+            true => serde_json::to_string(&pl)?,
+            false => general_purpose::STANDARD.encode(pl.encode_to_vec()),
+        };
+
+        self.publish("downlink", &di.application_id, &di.dev_eui, &pl)
+            .await
+    }
 }
 
 type HmacSha256 = Hmac<Sha256>;
@@ -0,0 +1,281 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use futures::StreamExt;
+use tokio::sync::RwLock;
+use tokio::time::sleep;
+use tracing::{error, info, trace, warn};
+use uuid::Uuid;
+
+use super::{for_configuration, ApplicationIntegration, ApplicationIntegrations};
+use crate::config;
+use crate::helpers::errors::PrintFullError;
+use crate::storage::{application, get_async_redis_conn, redis_key};
+
+// The application integrations are cached, such that these (and their HTTP clients and auth
+// tokens) are re-used across events. Updates of the integrations are published using Redis
+// pub/sub, such that all ChirpStack instances invalidate their cache. As a missed invalidation
+// would otherwise never be corrected, the cached integrations expire after CACHE_TTL.
+
+// Max. age of a cached entry.
+const CACHE_TTL: Duration = Duration::from_secs(60 * 10);
+
+// Max. number of cached applications.
+const CACHE_MAX_SIZE: usize = 10_000;
+
+struct CacheItem {
+    created_at: Instant,
+    integrations: Arc<ApplicationIntegrations>,
+}
+
+impl CacheItem {
+    fn is_expired(&self) -> bool {
+        self.created_at.elapsed() >= CACHE_TTL
+    }
+}
+
+lazy_static! {
+    static ref CACHE: RwLock<HashMap<Uuid, CacheItem>> = RwLock::new(HashMap::new());
+}
+
+// Incremented on every invalidation. This is used to detect that the cache has been invalidated
+// while the integrations were being constructed.
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+fn channel() -> String {
+    redis_key("integration:cache:invalidate".to_string())
+}
+
+// Returns the (cached) integrations for the given application ID.
+pub async fn get(application_id: &Uuid) -> Result<Arc<ApplicationIntegrations>> {
+    if let Some(v) = CACHE.read().await.get(application_id) {
+        if !v.is_expired() {
+            return Ok(v.integrations.clone());
+        }
+    }
+
+    let generation = GENERATION.load(Ordering::SeqCst);
+    let mut out: ApplicationIntegrations = Vec::new();
+    for app_i in application::get_integrations_for_application(application_id).await? {
+        // A broken integration (e.g. an invalid configuration) must not affect the other
+        // integrations of the application.
+        match for_configuration(&app_i.configuration).await {
+            Ok(Some(i)) => out.push(ApplicationIntegration {
+                kind: Some(app_i.kind),
+                filter: app_i.filter,
+                integration: i,
+            }),
+            Ok(None) => {}
+            Err(e) => {
+                error!(application_id = %application_id, kind = %app_i.kind, error = %e.full(), "Initializing application integration failed");
+            }
+        }
+    }
+    let out = Arc::new(out);
+
+    // The integrations are not cached when the cache has been invalidated in the meantime, as
+    // these could have been constructed using an outdated configuration.
+    let mut cache = CACHE.write().await;
+    if GENERATION.load(Ordering::SeqCst) == generation {
+        if cache.len() >= CACHE_MAX_SIZE && !cache.contains_key(application_id) {
+            evict(&mut cache);
+        }
+
+        trace!(application_id = %application_id, "Caching application integrations");
+        cache.insert(
+            *application_id,
+            CacheItem {
+                created_at: Instant::now(),
+                integrations: out.clone(),
+            },
+        );
+    }
+
+    Ok(out)
+}
+
+// Invalidates the cached integrations of the given application ID, on this and all other
+// instances.
+pub async fn invalidate(application_id: &Uuid) {
+    remove(application_id).await;
+
+    let res: Result<usize> = async {
+        Ok(redis::cmd("PUBLISH")
+            .arg(channel())
+            .arg(application_id.to_string())
+            .query_async(&mut get_async_redis_conn().await?)
+            .await?)
+    }
+    .await;
+
+    if let Err(e) = res {
+        warn!(application_id = %application_id, error = %e.full(), "Publishing integration cache invalidation failed");
+    }
+}
+
+// Removes the expired entries from the cache, or the oldest entry in case none are expired.
+fn evict(cache: &mut HashMap<Uuid, CacheItem>) {
+    cache.retain(|_, v| !v.is_expired());
+
+    if cache.len() >= CACHE_MAX_SIZE {
+        if let Some(id) = cache
+            .iter()
+            .min_by_key(|(_, v)| v.created_at)
+            .map(|(k, _)| *k)
+        {
+            cache.remove(&id);
+        }
+    }
+}
+
+async fn remove(application_id: &Uuid) {
+    let mut cache = CACHE.write().await;
+    GENERATION.fetch_add(1, Ordering::SeqCst);
+    cache.remove(application_id);
+}
+
+async fn clear() {
+    let mut cache = CACHE.write().await;
+    GENERATION.fetch_add(1, Ordering::SeqCst);
+    cache.clear();
+}
+
+pub async fn subscribe_loop() {
+    loop {
+        if let Err(e) = subscribe().await {
+            error!(error = %e.full(), "Integration cache invalidation subscription error");
+        }
+
+        sleep(Duration::from_secs(1)).await;
+    }
+}
+
+async fn subscribe() -> Result<()> {
+    let conf = config::get();
+
+    // Redis (Cluster) publishes the messages to all nodes, therefore it is sufficient to
+    // subscribe to the first server.
+    let client = redis::Client::open(conf.redis.servers[0].clone())?;
+    let mut pubsub = client.get_async_pubsub().await?;
+    pubsub.subscribe(channel()).await?;
+
+    info!("Subscribed to integration cache invalidations");
+
+    // Invalidations could have been missed while not subscribed.
+    clear().await;
+
+    let mut stream = pubsub.on_message();
+    while let Some(msg) = stream.next().await {
+        let payload: String = msg.get_payload()?;
+        match Uuid::from_str(&payload) {
+            Ok(v) => remove(&v).await,
+            Err(e) => {
+                warn!(payload = %payload, error = %e, "Invalid integration cache invalidation")
+            }
+        }
+    }
+
+    Err(anyhow!("Subscription stream closed"))
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use crate::storage::tenant;
+    use crate::test;
+
+    #[tokio::test]
+    async fn test_cache() {
+        let _guard = test::prepare().await;
+
+        let t = tenant::create(tenant::Tenant {
+            name: "test-tenant".into(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let app = application::create(application::Application {
+            tenant_id: t.id,
+            name: "test-app".into(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        assert!(get(&app.id).await.unwrap().is_empty());
+
+        // The cached value is returned.
+        application::create_integration(application::Integration {
+            application_id: app.id,
+            kind: application::IntegrationKind::Http,
+            configuration: application::IntegrationConfiguration::Http(
+                application::HttpConfiguration {
+                    headers: HashMap::new(),
+                    json: true,
+                    event_endpoint_url: "http://localhost:1234".into(),
+                    ..Default::default()
+                },
+            ),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        assert!(get(&app.id).await.unwrap().is_empty());
+
+        // After invalidation, the integrations are re-constructed.
+        invalidate(&app.id).await;
+        let ints = get(&app.id).await.unwrap();
+        assert_eq!(1, ints.len());
+        assert_eq!(Some(application::IntegrationKind::Http), ints[0].kind);
+
+        // An expired entry is re-constructed.
+        application::delete_integration(&app.id, application::IntegrationKind::Http)
+            .await
+            .unwrap();
+        assert_eq!(1, get(&app.id).await.unwrap().len());
+        CACHE.write().await.get_mut(&app.id).unwrap().created_at -= CACHE_TTL;
+        assert!(get(&app.id).await.unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_evict() {
+        let mut cache: HashMap<Uuid, CacheItem> = HashMap::new();
+        let now = Instant::now();
+        for i in 0..CACHE_MAX_SIZE {
+            cache.insert(
+                Uuid::new_v4(),
+                CacheItem {
+                    created_at: now - Duration::from_millis(i as u64),
+                    integrations: Arc::new(Vec::new()),
+                },
+            );
+        }
+        let oldest = *cache
+            .iter()
+            .min_by_key(|(_, v)| v.created_at)
+            .map(|(k, _)| k)
+            .unwrap();
+
+        evict(&mut cache);
+        assert_eq!(CACHE_MAX_SIZE - 1, cache.len());
+        assert!(!cache.contains_key(&oldest));
+
+        // Expired entries are removed first.
+        let id = *cache.keys().next().unwrap();
+        cache.get_mut(&id).unwrap().created_at = now - CACHE_TTL;
+        cache.insert(
+            Uuid::new_v4(),
+            CacheItem {
+                created_at: now,
+                integrations: Arc::new(Vec::new()),
+            },
+        );
+        evict(&mut cache);
+        assert_eq!(CACHE_MAX_SIZE - 1, cache.len());
+        assert!(!cache.contains_key(&id));
+    }
+}
@@ -0,0 +1,229 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use anyhow::Result;
+use uuid::Uuid;
+
+use super::Event;
+use crate::codec;
+use crate::storage::application::IntegrationFilter;
+
+// Returns true when the event matches the given integration filter, in which case it must be
+// forwarded to the integration.
+pub async fn matches(
+    filter: &IntegrationFilter,
+    vars: &HashMap<String, String>,
+    pl: &Event,
+) -> Result<bool> {
+    if !filter.events.is_empty() && !filter.events.iter().any(|e| e == pl.name()) {
+        return Ok(false);
+    }
+
+    if !filter.device_profile_ids.is_empty() || !filter.tags.is_empty() {
+        let di = match pl.device_info() {
+            Some(v) => v,
+            None => return Ok(false),
+        };
+
+        if !filter.device_profile_ids.is_empty()
+            && !filter
+                .device_profile_ids
+                .contains(&Uuid::from_str(&di.device_profile_id)?)
+        {
+            return Ok(false);
+        }
+
+        for (k, v) in &filter.tags {
+            if di.tags.get(k) != Some(v) {
+                return Ok(false);
+            }
+        }
+    }
+
+    // The FPort and expression filters only apply to uplink events.
+    if let Event::Up(pl) = pl {
+        if !filter.f_ports.is_empty() && !filter.f_ports.contains(&pl.f_port) {
+            return Ok(false);
+        }
+
+        if !filter.expression.is_empty() {
+            return match &pl.object {
+                Some(obj) => {
+                    codec::evaluate_expression(
+                        pl.f_port as u8,
+                        vars,
+                        &filter.expression,
+                        &codec::convert::pb_json_to_prost(obj),
+                    )
+                    .await
+                }
+                None => Ok(false),
+            };
+        }
+    }
+
+    Ok(true)
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use chirpstack_api::integration;
+
+    #[tokio::test]
+    async fn test_matches() {
+        let dp_id = Uuid::new_v4();
+        let up = Event::Up(integration::UplinkEvent {
+            device_info: Some(integration::DeviceInfo {
+                device_profile_id: dp_id.to_string(),
+                tags: [("type".to_string(), "alarm".to_string())]
+                    .iter()
+                    .cloned()
+                    .collect(),
+                ..Default::default()
+            }),
+            f_port: 10,
+            object: Some(pbjson_types::Struct {
+                fields: [(
+                    "alarm".to_string(),
+                    pbjson_types::Value {
+                        kind: Some(pbjson_types::value::Kind::BoolValue(true)),
+                    },
+                )]
+                .iter()
+                .cloned()
+                .collect(),
+            }),
+            ..Default::default()
+        });
+        let status = Event::Status(integration::StatusEvent {
+            device_info: Some(integration::DeviceInfo {
+                device_profile_id: dp_id.to_string(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        });
+
+        struct Test {
+            name: String,
+            filter: IntegrationFilter,
+            event: Event,
+            expected: bool,
+        }
+
+        let tests = vec![
+            Test {
+                name: "empty filter".into(),
+                filter: IntegrationFilter::default(),
+                event: up.clone(),
+                expected: true,
+            },
+            Test {
+                name: "event matches".into(),
+                filter: IntegrationFilter {
+                    events: vec!["up".into()],
+                    ..Default::default()
+                },
+                event: up.clone(),
+                expected: true,
+            },
+            Test {
+                name: "event does not match".into(),
+                filter: IntegrationFilter {
+                    events: vec!["up".into()],
+                    ..Default::default()
+                },
+                event: status.clone(),
+                expected: false,
+            },
+            Test {
+                name: "f_port does not match".into(),
+                filter: IntegrationFilter {
+                    f_ports: vec![20],
+                    ..Default::default()
+                },
+                event: up.clone(),
+                expected: false,
+            },
+            Test {
+                name: "f_port does not apply to status event".into(),
+                filter: IntegrationFilter {
+                    f_ports: vec![20],
+                    ..Default::default()
+                },
+                event: status.clone(),
+                expected: true,
+            },
+            Test {
+                name: "device-profile matches".into(),
+                filter: IntegrationFilter {
+                    device_profile_ids: vec![dp_id],
+                    ..Default::default()
+                },
+                event: status.clone(),
+                expected: true,
+            },
+            Test {
+                name: "device-profile does not match".into(),
+                filter: IntegrationFilter {
+                    device_profile_ids: vec![Uuid::new_v4()],
+                    ..Default::default()
+                },
+                event: up.clone(),
+                expected: false,
+            },
+            Test {
+                name: "tags match".into(),
+                filter: IntegrationFilter {
+                    tags: [("type".to_string(), "alarm".to_string())]
+                        .iter()
+                        .cloned()
+                        .collect(),
+                    ..Default::default()
+                },
+                event: up.clone(),
+                expected: true,
+            },
+            Test {
+                name: "tags do not match".into(),
+                filter: IntegrationFilter {
+                    tags: [("type".to_string(), "telemetry".to_string())]
+                        .iter()
+                        .cloned()
+                        .collect(),
+                    ..Default::default()
+                },
+                event: up.clone(),
+                expected: false,
+            },
+            Test {
+                name: "expression matches".into(),
+                filter: IntegrationFilter {
+                    expression: "data.alarm === true && fPort == 10".into(),
+                    ..Default::default()
+                },
+                event: up.clone(),
+                expected: true,
+            },
+            Test {
+                name: "expression does not match".into(),
+                filter: IntegrationFilter {
+                    expression: "data.alarm === false".into(),
+                    ..Default::default()
+                },
+                event: up.clone(),
+                expected: false,
+            },
+        ];
+
+        for tst in &tests {
+            println!("> {}", tst.name);
+            assert_eq!(
+                tst.expected,
+                matches(&tst.filter, &HashMap::new(), &tst.event)
+                    .await
+                    .unwrap()
+            );
+        }
+    }
+}
@@ -7,11 +7,10 @@ use base64::{engine::general_purpose, Engine as _};
 use gcp_auth::{AuthenticationManager, CustomServiceAccount};
 use prost::Message;
 use reqwest::header::{HeaderMap, AUTHORIZATION, CONTENT_TYPE};
-use reqwest::Client;
 use serde::Serialize;
 use tracing::{info, trace};
 
-use super::Integration as IntegrationTrait;
+use super::{http_client, Integration as IntegrationTrait};
 use crate::storage::application::GcpPubSubConfiguration;
 use chirpstack_api::api::Encoding;
 use chirpstack_api::integration;
@@ -94,7 +93,7 @@ impl Integration {
             .await
             .context("Get GCP bearer token")?;
 
-        let client = Client::builder().timeout(self.timeout).build()?;
+        let client = http_client();
         let mut headers = HeaderMap::new();
         headers.insert(CONTENT_TYPE, "application/json".parse().unwrap());
         headers.insert(
@@ -107,6 +106,7 @@ impl Integration {
                 "https://pubsub.googleapis.com/v1/{}:publish",
                 topic
             ))
+            .timeout(self.timeout)
             .body(pl)
             .headers(headers)
             .send()
@@ -238,4 +238,19 @@ impl IntegrationTrait for Integration {
         self.publish("integration", &di.application_id, &di.dev_eui, &pl)
             .await
     }
+
+    async fn downlink_event(
+        &self,
+        _vars: &HashMap<String, String>,
+        pl: &integration::DownlinkEvent,
+    ) -> Result<()> {
+        let di = pl.device_info.as_ref().unwrap();
+        let pl = match self.json {
+            true => serde_json::to_vec(&pl)?,
+            false => pl.encode_to_vec(),
+        };
+
+        self.publish("downlink", &di.application_id, &di.dev_eui, &pl)
+            .await
+    }
 }
@@ -1,42 +1,99 @@
 use std::collections::HashMap;
-use std::time::Duration;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 use async_trait::async_trait;
+use handlebars::Handlebars;
+use hmac::{Hmac, Mac};
 use prost::Message;
 use reqwest::header::{HeaderMap, HeaderName, CONTENT_TYPE};
-use reqwest::Client;
+use reqwest::{Certificate, Client, Identity};
+use serde::Serialize;
+use sha2::Sha256;
 use tracing::{info, trace, warn};
 
-use super::Integration as IntegrationTrait;
+use super::{http_client, Integration as IntegrationTrait};
 use crate::storage::application::HttpConfiguration;
 use chirpstack_api::integration;
 
-pub struct Integration {
+type HmacSha256 = Hmac<Sha256>;
+
+pub struct Integration<'a> {
+    client: Client,
     timeout: Duration,
+    templates: Handlebars<'a>,
     endpoints: Vec<String>,
     headers: HashMap<String, String>,
     json: bool,
+    signing_secret: String,
+}
+
+#[derive(Serialize)]
+struct EndpointContext {
+    pub event: String,
+    pub application_id: String,
+    pub dev_eui: String,
 }
 
-impl Integration {
-    pub fn new(conf: &HttpConfiguration) -> Integration {
+impl<'a> Integration<'a> {
+    pub fn new(conf: &HttpConfiguration) -> Result<Integration<'a>> {
         trace!("Initializing http integration");
 
-        Integration {
-            timeout: Duration::from_secs(5),
+        let endpoints: Vec<String> = conf
+            .event_endpoint_url
+            .split(',')
+            .map(|s| s.trim().to_string())
+            .collect();
+
+        // endpoint templates
+        let mut templates = Handlebars::new();
+        templates.register_escape_fn(handlebars::no_escape);
+        for url in &endpoints {
+            templates
+                .register_template_string(url, url)
+                .context("Register endpoint template")?;
+        }
+
+        // A dedicated client is used when a CA or client certificate has been configured.
+        let client = if conf.ca_cert.is_empty() && conf.tls_cert.is_empty() {
+            http_client()
+        } else {
+            let mut builder = Client::builder().use_rustls_tls();
+            if !conf.ca_cert.is_empty() {
+                builder = builder.add_root_certificate(
+                    Certificate::from_pem(conf.ca_cert.as_bytes())
+                        .context("Read CA certificate")?,
+                );
+            }
+            if !conf.tls_cert.is_empty() {
+                let pem = format!("{}\n{}", conf.tls_cert, conf.tls_key);
+                builder = builder.identity(
+                    Identity::from_pem(pem.as_bytes()).context("Read TLS certificate and key")?,
+                );
+            }
+            builder.build()?
+        };
+
+        Ok(Integration {
+            client,
+            timeout: match conf.timeout_ms {
+                0 => Duration::from_secs(5),
+                _ => Duration::from_millis(conf.timeout_ms.into()),
+            },
+            templates,
             headers: conf.headers.clone(),
             json: conf.json,
-            endpoints: conf
-                .event_endpoint_url
-                .split(',')
-                .map(|s| s.trim().to_string())
-                .collect(),
-        }
+            signing_secret: conf.signing_secret.clone(),
+            endpoints,
+        })
     }
 
-    async fn post_event(&self, event: &str, b: Vec<u8>) -> Result<()> {
-        let client = Client::builder().timeout(self.timeout).build()?;
+    async fn post_event(
+        &self,
+        event: &str,
+        di: Option<&integration::DeviceInfo>,
+        b: Vec<u8>,
+    ) -> Result<()> {
         let mut headers = HeaderMap::new();
 
         for (k, v) in &self.headers {
@@ -49,10 +106,29 @@ impl Integration {
             headers.insert(CONTENT_TYPE, "application/octet-stream".parse().unwrap());
         }
 
-        for url in &self.endpoints {
+        if !self.signing_secret.is_empty() {
+            let ts = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+            headers.insert("x-chirpstack-timestamp", ts.into());
+            headers.insert(
+                "x-chirpstack-signature",
+                format!("sha256={}", sign(&self.signing_secret, ts, &b)?).parse()?,
+            );
+        }
+
+        let ctx = EndpointContext {
+            event: event.to_string(),
+            application_id: di.map(|v| v.application_id.clone()).unwrap_or_default(),
+            dev_eui: di.map(|v| v.dev_eui.clone()).unwrap_or_default(),
+        };
+
+        for endpoint in &self.endpoints {
+            let url = self.templates.render(endpoint, &ctx)?;
+
             info!(event = %event, url = %url, "Posting event");
-            let res = client
-                .post(url)
+            let res = self
+                .client
+                .post(&url)
+                .timeout(self.timeout)
                 .body(b.clone())
                 .query(&[("event", event)])
                 .headers(headers.clone())
@@ -77,8 +153,17 @@ impl Integration {
     }
 }
 
+// Returns the hex encoded HMAC-SHA256 signature over "{timestamp}.{body}". Including the timestamp
+// allows the receiver to reject replayed requests.
+fn sign(secret: &str, ts: u64, b: &[u8]) -> Result<String> {
+    let mut m = HmacSha256::new_from_slice(secret.as_bytes())?;
+    m.update(format!("{}.", ts).as_bytes());
+    m.update(b);
+    Ok(hex::encode(m.finalize().into_bytes()))
+}
+
 #[async_trait]
-impl IntegrationTrait for Integration {
+impl IntegrationTrait for Integration<'_> {
     async fn uplink_event(
         &self,
         _vars: &HashMap<String, String>,
@@ -89,7 +174,7 @@ impl IntegrationTrait for Integration {
             false => pl.encode_to_vec(),
         };
 
-        self.post_event("up", b).await
+        self.post_event("up", pl.device_info.as_ref(), b).await
     }
 
     async fn join_event(
@@ -102,7 +187,7 @@ impl IntegrationTrait for Integration {
             false => pl.encode_to_vec(),
         };
 
-        self.post_event("join", b).await
+        self.post_event("join", pl.device_info.as_ref(), b).await
     }
 
     async fn ack_event(
@@ -115,7 +200,7 @@ impl IntegrationTrait for Integration {
             false => pl.encode_to_vec(),
         };
 
-        self.post_event("ack", b).await
+        self.post_event("ack", pl.device_info.as_ref(), b).await
     }
 
     async fn txack_event(
@@ -128,7 +213,7 @@ impl IntegrationTrait for Integration {
             false => pl.encode_to_vec(),
         };
 
-        self.post_event("txack", b).await
+        self.post_event("txack", pl.device_info.as_ref(), b).await
     }
 
     async fn log_event(
@@ -141,7 +226,7 @@ impl IntegrationTrait for Integration {
             false => pl.encode_to_vec(),
         };
 
-        self.post_event("log", b).await
+        self.post_event("log", pl.device_info.as_ref(), b).await
     }
 
     async fn status_event(
@@ -154,7 +239,7 @@ impl IntegrationTrait for Integration {
             false => pl.encode_to_vec(),
         };
 
-        self.post_event("status", b).await
+        self.post_event("status", pl.device_info.as_ref(), b).await
     }
 
     async fn location_event(
@@ -167,7 +252,8 @@ impl IntegrationTrait for Integration {
             false => pl.encode_to_vec(),
         };
 
-        self.post_event("location", b).await
+        self.post_event("location", pl.device_info.as_ref(), b)
+            .await
     }
 
     async fn integration_event(
@@ -180,7 +266,22 @@ impl IntegrationTrait for Integration {
             false => pl.encode_to_vec(),
         };
 
-        self.post_event("integration", b).await
+        self.post_event("integration", pl.device_info.as_ref(), b)
+            .await
+    }
+
+    async fn downlink_event(
+        &self,
+        _vars: &HashMap<String, String>,
+        pl: &integration::DownlinkEvent,
+    ) -> Result<()> {
+        let b = match self.json {
+            true => serde_json::to_vec(&pl)?,
+            false => pl.encode_to_vec(),
+        };
+
+        self.post_event("downlink", pl.device_info.as_ref(), b)
+            .await
     }
 }
 
@@ -196,7 +297,9 @@ pub mod test {
             json: true,
             event_endpoint_url: "http://a.com,http://b.com, http://c.com , http://d.com"
                 .to_string(),
-        });
+            ..Default::default()
+        })
+        .unwrap();
 
         assert_eq!(
             vec![
@@ -213,15 +316,16 @@ pub mod test {
     async fn test_http() {
         let server = MockServer::start();
 
-        let i = Integration {
-            timeout: Duration::from_secs(5),
-            endpoints: vec![server.url("/")],
+        let i = Integration::new(&HttpConfiguration {
+            event_endpoint_url: server.url("/"),
             headers: [("Foo".to_string(), "Bar".to_string())]
                 .iter()
                 .cloned()
                 .collect(),
             json: true,
-        };
+            ..Default::default()
+        })
+        .unwrap();
 
         // uplink event
         let pl: integration::UplinkEvent = Default::default();
@@ -345,4 +449,71 @@ pub mod test {
         mock.assert();
         mock.delete();
     }
+
+    #[tokio::test]
+    async fn test_http_signing_and_templates() {
+        let server = MockServer::start();
+
+        let i = Integration::new(&HttpConfiguration {
+            event_endpoint_url: format!(
+                "{},{}",
+                server.url("/{{application_id}}/{{dev_eui}}/{{event}}"),
+                server.url("/all")
+            ),
+            json: true,
+            signing_secret: "secret".into(),
+            timeout_ms: 1000,
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(Duration::from_secs(1), i.timeout);
+
+        let pl = integration::UplinkEvent {
+            device_info: Some(integration::DeviceInfo {
+                application_id: "app-id".into(),
+                dev_eui: "0102030405060708".into(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let body = serde_json::to_vec(&pl).unwrap();
+
+        let mock_event = server.mock(|when, then| {
+            when.method(POST)
+                .path("/app-id/0102030405060708/up")
+                .header_exists("x-chirpstack-timestamp")
+                .header_exists("x-chirpstack-signature")
+                .body(String::from_utf8(body.clone()).unwrap());
+
+            then.status(200);
+        });
+        let mock_all = server.mock(|when, then| {
+            when.method(POST).path("/all");
+            then.status(200);
+        });
+
+        i.uplink_event(&HashMap::new(), &pl).await.unwrap();
+        mock_event.assert();
+        mock_all.assert();
+    }
+
+    #[test]
+    fn test_sign() {
+        // printf '1700000000.{}' | openssl dgst -sha256 -hmac secret
+        assert_eq!(
+            "b8569b78799ff9e3cbff0fc2d63a33a2b57f3282abd07c37ae5e8e7d79a5f163",
+            sign("secret", 1700000000, b"{}").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_invalid_tls_cert() {
+        assert!(Integration::new(&HttpConfiguration {
+            event_endpoint_url: "https://example.com".into(),
+            tls_cert: "invalid".into(),
+            tls_key: "invalid".into(),
+            ..Default::default()
+        })
+        .is_err());
+    }
 }
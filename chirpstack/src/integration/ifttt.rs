@@ -4,11 +4,10 @@ use std::time::Duration;
 use anyhow::Result;
 use async_trait::async_trait;
 use reqwest::header::{HeaderMap, CONTENT_TYPE};
-use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use tracing::{info, trace, warn};
 
-use super::Integration as IntegrationTrait;
+use super::{http_client, Integration as IntegrationTrait};
 use crate::codec;
 use crate::storage::application::IftttConfiguration;
 use chirpstack_api::integration;
@@ -63,12 +62,18 @@ impl Integration {
             format!("{}/trigger/{}/with/key/{}", self.server, event, self.key)
         };
 
-        let client = Client::builder().timeout(Duration::from_secs(5)).build()?;
+        let client = http_client();
         let mut headers = HeaderMap::new();
         headers.insert(CONTENT_TYPE, "application/json".parse().unwrap());
 
         info!(event = %event, "Sending event to IFTTT");
-        let res = client.post(url).json(&v).headers(headers).send().await?;
+        let res = client
+            .post(url)
+            .timeout(Duration::from_secs(5))
+            .json(&v)
+            .headers(headers)
+            .send()
+            .await?;
         match res.error_for_status() {
             Ok(_) => Ok(()),
             Err(e) => {
@@ -174,6 +179,14 @@ impl IntegrationTrait for Integration {
     ) -> Result<()> {
         Ok(())
     }
+
+    async fn downlink_event(
+        &self,
+        _vars: &HashMap<String, String>,
+        _pl: &integration::DownlinkEvent,
+    ) -> Result<()> {
+        Ok(())
+    }
 }
 
 fn kind_to_string(k: &pbjson_types::value::Kind) -> String {
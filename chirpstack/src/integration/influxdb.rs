@@ -1,32 +1,58 @@
 use std::collections::HashMap;
 use std::fmt;
+use std::sync::{Arc, Weak};
 use std::time::Duration;
 
 use anyhow::Result;
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 use reqwest::header::{HeaderMap, AUTHORIZATION, CONTENT_TYPE};
-use reqwest::Client;
-use tracing::{info, trace};
+use tokio::sync::Mutex;
+use tokio::time::sleep;
+use tracing::{error, info, trace, warn};
 
-use super::Integration as IntegrationTrait;
+use super::{http_client, Integration as IntegrationTrait};
+use crate::helpers::errors::PrintFullError;
 use crate::storage::application::InfluxDbConfiguration;
 use chirpstack_api::api::{InfluxDbPrecision, InfluxDbVersion};
 use chirpstack_api::integration;
 
+// Max. number of batched lines. When reached, the batch is written without waiting for the
+// flush interval.
+const MAX_BATCH_SIZE: usize = 5000;
+
+// Max. number of lines that are kept while writing the batches fails. When exceeded, the oldest
+// lines are dropped.
+const MAX_BUFFERED_LINES: usize = 10 * MAX_BATCH_SIZE;
+
 pub struct Integration {
+    writer: Writer,
+    tag_variables: Vec<String>,
+    rx_metadata: bool,
+
+    // Batched lines, in case a flush interval has been configured.
+    batch: Option<Arc<Mutex<Vec<String>>>>,
+}
+
+#[derive(Clone)]
+struct Writer {
     timeout: Duration,
     endpoint: String,
     version: InfluxDbVersion,
 
-    // v1
+    // v1 and v3
     db: String,
+
+    // v1
     username: String,
     password: String,
     retention_policy_name: String,
     precision: String,
 
-    // v2
+    // v2 and v3
     token: String,
+
+    // v2
     organization: String,
     bucket: String,
 }
@@ -35,7 +61,7 @@ impl Integration {
     pub fn new(conf: &InfluxDbConfiguration) -> Result<Integration> {
         trace!("Initializing InfluxDB integration");
 
-        Ok(Integration {
+        let writer = Writer {
             timeout: Duration::from_secs(5),
             endpoint: conf.endpoint.clone(),
             version: InfluxDbVersion::try_from(conf.version)
@@ -58,20 +84,199 @@ impl Integration {
             token: conf.token.clone(),
             organization: conf.organization.clone(),
             bucket: conf.bucket.clone(),
+        };
+
+        let batch = if conf.flush_interval_ms > 0 {
+            let batch = Arc::new(Mutex::new(Vec::new()));
+            tokio::spawn(flush_loop(
+                writer.clone(),
+                Arc::downgrade(&batch),
+                Duration::from_millis(conf.flush_interval_ms.into()),
+            ));
+            Some(batch)
+        } else {
+            None
+        };
+
+        Ok(Integration {
+            writer,
+            tag_variables: conf.tag_variables.clone(),
+            rx_metadata: conf.rx_metadata,
+            batch,
+        })
+    }
+
+    fn tags(
+        &self,
+        vars: &HashMap<String, String>,
+        di: &integration::DeviceInfo,
+    ) -> HashMap<String, String> {
+        let mut tags: HashMap<String, String> = di
+            .tags
+            .iter()
+            .filter(|(_, v)| !v.is_empty())
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect();
+
+        for k in &self.tag_variables {
+            if let Some(v) = vars.get(k) {
+                if !v.is_empty() {
+                    tags.insert(k.clone(), v.clone());
+                }
+            }
+        }
+
+        tags.insert("application_name".into(), di.application_name.clone());
+        tags.insert("device_name".into(), di.device_name.clone());
+        tags.insert("dev_eui".into(), di.dev_eui.clone());
+        tags
+    }
+
+    async fn publish(
+        &self,
+        measurements: &[Measurement],
+        time: Option<&pbjson_types::Timestamp>,
+    ) -> Result<()> {
+        let mut lines: Vec<String> = measurements.iter().map(|m| m.to_string()).collect();
+        lines.sort();
+
+        match &self.batch {
+            None => self.writer.write(&lines).await,
+            Some(batch) => {
+                // As the lines are written at a later moment, the timestamp of the event must be
+                // included.
+                if let Some(ts) = time.and_then(|t| self.writer.timestamp(t)) {
+                    for line in lines.iter_mut() {
+                        line.push_str(&format!(" {}", ts));
+                    }
+                }
+
+                let lines = {
+                    let mut batch = batch.lock().await;
+                    batch.append(&mut lines);
+                    if batch.len() < MAX_BATCH_SIZE {
+                        return Ok(());
+                    }
+                    std::mem::take(&mut *batch)
+                };
+
+                // The event is not returned as failed, as its lines are kept in the batch and
+                // retried by the flush loop.
+                if let Err(e) = self.writer.write(&lines).await {
+                    error!(error = %e.full(), "Writing InfluxDB batch error");
+                    rebuffer(batch, lines).await;
+                }
+
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Drop for Integration {
+    fn drop(&mut self) {
+        // Write the remaining lines, e.g. when the integration has been updated.
+        if let Some(batch) = &self.batch {
+            let lines = match batch.try_lock() {
+                Ok(mut v) => std::mem::take(&mut *v),
+                Err(_) => return,
+            };
+
+            if !lines.is_empty() {
+                if let Ok(handle) = tokio::runtime::Handle::try_current() {
+                    let writer = self.writer.clone();
+                    handle.spawn(async move {
+                        if let Err(e) = writer.write(&lines).await {
+                            error!(error = %e.full(), "Writing InfluxDB batch error");
+                        }
+                    });
+                }
+            }
+        }
+    }
+}
+
+async fn flush_loop(writer: Writer, batch: Weak<Mutex<Vec<String>>>, interval: Duration) {
+    loop {
+        sleep(interval).await;
+
+        // The integration has been dropped.
+        let batch = match batch.upgrade() {
+            Some(v) => v,
+            None => return,
+        };
+
+        let lines = std::mem::take(&mut *batch.lock().await);
+        if lines.is_empty() {
+            continue;
+        }
+
+        match writer.write(&lines).await {
+            Ok(_) => {
+                info!(lines = lines.len(), "Batch written to InfluxDB");
+            }
+            Err(e) => {
+                error!(error = %e.full(), "Writing InfluxDB batch error");
+                rebuffer(&batch, lines).await;
+            }
+        }
+    }
+}
+
+// Puts the lines that failed to be written back into the batch, in front of the lines that were
+// added in the meantime, such that these are retried on the next flush.
+async fn rebuffer(batch: &Mutex<Vec<String>>, mut lines: Vec<String>) {
+    let mut batch = batch.lock().await;
+    lines.append(&mut batch);
+
+    if lines.len() > MAX_BUFFERED_LINES {
+        let dropped = lines.len() - MAX_BUFFERED_LINES;
+        lines.drain(..dropped);
+        warn!(
+            lines = dropped,
+            "InfluxDB batch buffer is full, dropping oldest lines"
+        );
+    }
+
+    *batch = lines;
+}
+
+impl Writer {
+    // Returns the timestamp, using the precision expected by the endpoint.
+    fn timestamp(&self, t: &pbjson_types::Timestamp) -> Option<i64> {
+        let t: DateTime<Utc> = t.clone().try_into().ok()?;
+        let ns = t.timestamp_nanos_opt()?;
+
+        Some(match self.version {
+            InfluxDbVersion::Influxdb1 => match self.precision.as_ref() {
+                "u" => ns / 1_000,
+                "ms" => ns / 1_000_000,
+                "s" => ns / 1_000_000_000,
+                "m" => ns / 60_000_000_000,
+                "h" => ns / 3_600_000_000_000,
+                _ => ns,
+            },
+            InfluxDbVersion::Influxdb2 | InfluxDbVersion::Influxdb3 => ns,
         })
     }
 
-    async fn publish(&self, measurements: &[Measurement]) -> Result<()> {
-        let mut measurements: Vec<String> = measurements.iter().map(|m| m.to_string()).collect();
-        measurements.sort();
-        let body = measurements.join("\n");
+    async fn write(&self, lines: &[String]) -> Result<()> {
+        let body = lines.join("\n");
 
-        let client = Client::builder().timeout(self.timeout).build()?;
+        let client = http_client();
 
         let mut headers = HeaderMap::new();
         headers.insert(CONTENT_TYPE, "text/plain".parse().unwrap());
-        if self.version == InfluxDbVersion::Influxdb2 {
-            headers.insert(AUTHORIZATION, format!("Token {}", self.token).parse()?);
+        match self.version {
+            InfluxDbVersion::Influxdb1 => {}
+            InfluxDbVersion::Influxdb2 => {
+                headers.insert(AUTHORIZATION, format!("Token {}", self.token).parse()?);
+            }
+            InfluxDbVersion::Influxdb3 => {
+                if !self.token.is_empty() {
+                    headers.insert(AUTHORIZATION, format!("Bearer {}", self.token).parse()?);
+                }
+            }
         }
 
         let mut query: Vec<(String, String)> = Vec::new();
@@ -85,10 +290,15 @@ impl Integration {
                 query.push(("org".into(), self.organization.clone()));
                 query.push(("bucket".into(), self.bucket.clone()));
             }
+            InfluxDbVersion::Influxdb3 => {
+                query.push(("db".into(), self.db.clone()));
+                query.push(("precision".into(), "nanosecond".into()));
+            }
         }
 
         let mut req = client
             .post(&self.endpoint)
+            .timeout(self.timeout)
             .body(body)
             .query(&query)
             .headers(headers);
@@ -111,15 +321,11 @@ impl Integration {
 impl IntegrationTrait for Integration {
     async fn uplink_event(
         &self,
-        _vars: &HashMap<String, String>,
+        vars: &HashMap<String, String>,
         pl: &integration::UplinkEvent,
     ) -> Result<()> {
         let di = pl.device_info.as_ref().unwrap();
-
-        let mut tags = di.tags.clone();
-        tags.insert("application_name".into(), di.application_name.clone());
-        tags.insert("device_name".into(), di.device_name.clone());
-        tags.insert("dev_eui".into(), di.dev_eui.clone());
+        let mut tags = self.tags(vars, di);
 
         let mut measurements: Vec<Measurement> = Vec::new();
         measurements.push(Measurement {
@@ -160,13 +366,34 @@ impl IntegrationTrait for Integration {
             },
         });
 
+        if self.rx_metadata {
+            for rx_info in &pl.rx_info {
+                measurements.push(Measurement {
+                    name: "device_uplink_rx".into(),
+                    tags: {
+                        let mut tags = tags.clone();
+                        tags.insert("gateway_id".into(), rx_info.gateway_id.clone());
+                        tags
+                    },
+                    values: [
+                        ("rssi".to_string(), Value::Integer(rx_info.rssi.into())),
+                        ("snr".to_string(), Value::Float(rx_info.snr.into())),
+                        ("dr".to_string(), Value::Integer(pl.dr.into())),
+                    ]
+                    .iter()
+                    .cloned()
+                    .collect(),
+                });
+            }
+        }
+
         tags.insert("f_port".into(), format!("{}", pl.f_port));
 
         if let Some(obj) = &pl.object {
             measurements.append(&mut struct_to_measurements(&tags, obj));
         }
 
-        self.publish(&measurements).await?;
+        self.publish(&measurements, pl.time.as_ref()).await?;
 
         info!(dev_eui = %di.dev_eui, "Uplink measurements sent to InfluxDB");
 
@@ -207,15 +434,11 @@ impl IntegrationTrait for Integration {
 
     async fn status_event(
         &self,
-        _vars: &HashMap<String, String>,
+        vars: &HashMap<String, String>,
         pl: &integration::StatusEvent,
     ) -> Result<()> {
         let di = pl.device_info.as_ref().unwrap();
-
-        let mut tags = di.tags.clone();
-        tags.insert("application_name".into(), di.application_name.clone());
-        tags.insert("device_name".into(), di.device_name.clone());
-        tags.insert("dev_eui".into(), di.dev_eui.clone());
+        let tags = self.tags(vars, di);
 
         let mut measurements: Vec<Measurement> = Vec::new();
         if !pl.external_power_source && !pl.battery_level_unavailable {
@@ -240,7 +463,7 @@ impl IntegrationTrait for Integration {
             },
         });
 
-        self.publish(&measurements).await?;
+        self.publish(&measurements, pl.time.as_ref()).await?;
 
         info!(dev_eui = %di.dev_eui, "Status measurements sent to InfluxDB");
 
@@ -262,6 +485,14 @@ impl IntegrationTrait for Integration {
     ) -> Result<()> {
         Ok(())
     }
+
+    async fn downlink_event(
+        &self,
+        _vars: &HashMap<String, String>,
+        _pl: &integration::DownlinkEvent,
+    ) -> Result<()> {
+        Ok(())
+    }
 }
 
 #[derive(Clone)]
@@ -477,17 +708,22 @@ pub mod test {
         let server = MockServer::start();
 
         let i = Integration {
-            timeout: Duration::from_secs(5),
-            endpoint: server.url("/write"),
-            version: InfluxDbVersion::Influxdb1,
-            db: "testdb".into(),
-            username: "foo".into(),
-            password: "bar".into(),
-            retention_policy_name: "DEFAULT".into(),
-            precision: "s".into(),
-            token: "".into(),
-            organization: "".into(),
-            bucket: "".into(),
+            writer: Writer {
+                timeout: Duration::from_secs(5),
+                endpoint: server.url("/write"),
+                version: InfluxDbVersion::Influxdb1,
+                db: "testdb".into(),
+                username: "foo".into(),
+                password: "bar".into(),
+                retention_policy_name: "DEFAULT".into(),
+                precision: "s".into(),
+                token: "".into(),
+                organization: "".into(),
+                bucket: "".into(),
+            },
+            tag_variables: vec![],
+            rx_metadata: false,
+            batch: None,
         };
 
         // status
@@ -832,17 +1068,22 @@ device_uplink,application_name=test-app,dev_eui=0102030405060708,device_name=tes
         let server = MockServer::start();
 
         let i = Integration {
-            timeout: Duration::from_secs(5),
-            endpoint: server.url("/write"),
-            version: InfluxDbVersion::Influxdb2,
-            db: "".into(),
-            username: "".into(),
-            password: "".into(),
-            retention_policy_name: "".into(),
-            precision: "".into(),
-            token: "testtoken".into(),
-            organization: "testorg".into(),
-            bucket: "testbucket".into(),
+            writer: Writer {
+                timeout: Duration::from_secs(5),
+                endpoint: server.url("/write"),
+                version: InfluxDbVersion::Influxdb2,
+                db: "".into(),
+                username: "".into(),
+                password: "".into(),
+                retention_policy_name: "".into(),
+                precision: "".into(),
+                token: "testtoken".into(),
+                organization: "testorg".into(),
+                bucket: "testbucket".into(),
+            },
+            tag_variables: vec![],
+            rx_metadata: false,
+            batch: None,
         };
 
         // status
@@ -879,4 +1120,91 @@ device_status_margin,application_name=test-app,dev_eui=0102030405060708,device_n
         mock.assert();
         mock.delete();
     }
+
+    #[tokio::test]
+    async fn test_v3_batch() {
+        let server = MockServer::start();
+
+        let i = Integration::new(&InfluxDbConfiguration {
+            endpoint: server.url("/api/v3/write_lp"),
+            db: "testdb".into(),
+            username: "".into(),
+            password: "".into(),
+            retention_policy_name: "".into(),
+            precision: InfluxDbPrecision::Ns.into(),
+            version: InfluxDbVersion::Influxdb3.into(),
+            token: "testtoken".into(),
+            organization: "".into(),
+            bucket: "".into(),
+            flush_interval_ms: 10,
+            tag_variables: vec!["site".into()],
+            rx_metadata: true,
+        })
+        .unwrap();
+
+        // The InfluxDB write fails initially.
+        let mut error_mock = server.mock(|when, then| {
+            when.method(POST).path("/api/v3/write_lp");
+            then.status(500);
+        });
+
+        i.uplink_event(
+            &[
+                ("site".to_string(), "office".to_string()),
+                ("secret".to_string(), "s3cr3t".to_string()),
+            ]
+            .iter()
+            .cloned()
+            .collect(),
+            &integration::UplinkEvent {
+                time: Some(
+                    DateTime::parse_from_rfc3339("2024-06-10T12:00:00Z")
+                        .unwrap()
+                        .with_timezone(&Utc)
+                        .into(),
+                ),
+                device_info: Some(integration::DeviceInfo {
+                    application_name: "test-app".into(),
+                    device_name: "test-dev".into(),
+                    dev_eui: "0102030405060708".into(),
+                    ..Default::default()
+                }),
+                tx_info: Some(gw::UplinkTxInfo {
+                    frequency: 868100000,
+                    ..Default::default()
+                }),
+                rx_info: vec![gw::UplinkRxInfo {
+                    gateway_id: "0016c001ff10d3f6".into(),
+                    rssi: -55,
+                    snr: 2.5,
+                    ..Default::default()
+                }],
+                dr: 2,
+                f_cnt: 10,
+                f_port: 20,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        // The measurements are written by the flush loop, the failed batch is retried.
+        sleep(Duration::from_millis(100)).await;
+        assert!(error_mock.hits() > 1);
+        error_mock.delete();
+
+        let mut mock = server.mock(|when, then| {
+            when.method(POST)
+                .path("/api/v3/write_lp")
+                .query_param("db", "testdb")
+                .query_param("precision", "nanosecond")
+                .header("Authorization", "Bearer testtoken")
+                .body(r#"device_uplink,application_name=test-app,dev_eui=0102030405060708,device_name=test-dev,dr=2,frequency=868100000,site=office f_cnt=10i,rssi=-55i,snr=2.500000,value=1i 1718020800000000000
+device_uplink_rx,application_name=test-app,dev_eui=0102030405060708,device_name=test-dev,gateway_id=0016c001ff10d3f6,site=office dr=2i,rssi=-55i,snr=2.500000 1718020800000000000"#);
+            then.status(200);
+        });
+        sleep(Duration::from_millis(100)).await;
+        mock.assert();
+        mock.delete();
+    }
 }
@@ -6,13 +6,18 @@ use async_trait::async_trait;
 use handlebars::Handlebars;
 use prost::Message;
 use rdkafka::config::ClientConfig;
+use rdkafka::consumer::{CommitMode, Consumer, StreamConsumer};
 use rdkafka::message::{Header, OwnedHeaders};
 use rdkafka::producer::{FutureProducer, FutureRecord};
+use rdkafka::Message as _;
+use regex::Regex;
 use serde::Serialize;
-use tracing::{error, info};
+use tokio::time::sleep;
+use tracing::{error, info, trace, warn};
 
 use super::Integration as IntegrationTrait;
 use crate::config::KafkaIntegration as Config;
+use crate::helpers::errors::PrintFullError;
 use chirpstack_api::integration;
 
 pub struct Integration<'a> {
@@ -29,36 +34,56 @@ struct EventKeyContext {
     pub event: String,
 }
 
+#[derive(Serialize)]
+struct CommandKeyContext {
+    pub application_id: String,
+    pub dev_eui: String,
+    pub command: String,
+}
+
 impl<'a> Integration<'a> {
     pub fn new(conf: &Config) -> Result<Integration<'a>> {
         info!("Initializing Kafka integration");
 
-        // event-key template.
+        // event-key and command-key templates.
         let mut templates = Handlebars::new();
         templates.register_escape_fn(handlebars::no_escape);
         templates.register_template_string("event_key", &conf.event_key)?;
+        templates.register_template_string("command_key", &conf.command_key)?;
 
-        let producer: FutureProducer = ClientConfig::new()
-            .set("bootstrap.servers", &conf.brokers.join(","))
+        let producer: FutureProducer = client_config(conf)?
             .set("message.timeout.ms", "5000")
-            .set("allow.auto.create.topics", "true")
-            .set(
-                "sasl.mechanism",
-                match conf.mechanism.as_ref() {
-                    "PLAIN" => "PLAIN",
-                    "SCRAM-SHA-256" => "SCRAM-SHA-256",
-                    "SCRAM-SHA-512" => "SCRAM-SHA-512",
-                    _ => {
-                        return Err(anyhow!(
-                            "mechanism must be PLAIN, SCRAM-SHA-256 or SCRAM-SHA-512"
-                        ));
-                    }
-                },
-            )
-            .set("sasl.username", &conf.username)
-            .set("sasl.password", &conf.password)
             .create()?;
 
+        // Command consumer loop
+        if !conf.command_topic.is_empty() {
+            let command_regex = Regex::new(&format!(
+                "^{}$",
+                templates.render(
+                    "command_key",
+                    &CommandKeyContext {
+                        application_id: r"(?P<application_id>[\w-]+)".to_string(),
+                        dev_eui: r"(?P<dev_eui>[\w]+)".to_string(),
+                        command: r"(?P<command>[\w]+)".to_string(),
+                    },
+                )?
+            ))?;
+
+            tokio::spawn({
+                let conf = conf.clone();
+
+                async move {
+                    loop {
+                        if let Err(e) = consume_commands(&conf, &command_regex).await {
+                            error!(error = %e.full(), "Kafka command consumer error");
+                        }
+
+                        sleep(Duration::from_secs(1)).await;
+                    }
+                }
+            });
+        }
+
         let i = Integration {
             templates,
             producer,
@@ -70,28 +95,7 @@ impl<'a> Integration<'a> {
     }
 
     async fn publish_event(&self, event: &str, event_key: String, b: &[u8]) -> Result<()> {
-        info!(topic = %self.topic, event_key = %event_key, "Publishing event");
-
-        let res = self
-            .producer
-            .send(
-                FutureRecord::to(&self.topic)
-                    .key(&event_key)
-                    .headers(OwnedHeaders::new().insert(Header {
-                        key: "event",
-                        value: Some(event),
-                    }))
-                    .payload(b),
-                Duration::from_secs(0),
-            )
-            .await;
-
-        if let Err(e) = res {
-            error!(error = ?e, "Publishing event error");
-            return Err(anyhow!("{:?}", e));
-        }
-
-        Ok(())
+        publish(&self.producer, &self.topic, event, &event_key, b).await
     }
 
     fn get_event_key(&self, application_id: &str, dev_eui: &str, event: &str) -> Result<String> {
@@ -219,6 +223,118 @@ impl<'a> IntegrationTrait for Integration<'a> {
         };
         self.publish_event("integration", key, &b).await
     }
+
+    async fn downlink_event(
+        &self,
+        _vars: &HashMap<String, String>,
+        pl: &integration::DownlinkEvent,
+    ) -> Result<()> {
+        let di = pl.device_info.as_ref().unwrap();
+        let key = self.get_event_key(&di.application_id, &di.dev_eui, "downlink")?;
+        let b = match self.json {
+            true => serde_json::to_vec(&pl)?,
+            false => pl.encode_to_vec(),
+        };
+        self.publish_event("downlink", key, &b).await
+    }
+}
+
+fn client_config(conf: &Config) -> Result<ClientConfig> {
+    let mut client_config = ClientConfig::new();
+    client_config
+        .set("bootstrap.servers", conf.brokers.join(","))
+        .set("allow.auto.create.topics", "true")
+        .set(
+            "sasl.mechanism",
+            match conf.mechanism.as_ref() {
+                "PLAIN" => "PLAIN",
+                "SCRAM-SHA-256" => "SCRAM-SHA-256",
+                "SCRAM-SHA-512" => "SCRAM-SHA-512",
+                _ => {
+                    return Err(anyhow!(
+                        "mechanism must be PLAIN, SCRAM-SHA-256 or SCRAM-SHA-512"
+                    ));
+                }
+            },
+        )
+        .set("sasl.username", &conf.username)
+        .set("sasl.password", &conf.password);
+
+    Ok(client_config)
+}
+
+async fn publish(
+    producer: &FutureProducer,
+    topic: &str,
+    event: &str,
+    event_key: &str,
+    b: &[u8],
+) -> Result<()> {
+    info!(topic = %topic, event_key = %event_key, "Publishing event");
+
+    let res = producer
+        .send(
+            FutureRecord::to(topic)
+                .key(event_key)
+                .headers(OwnedHeaders::new().insert(Header {
+                    key: "event",
+                    value: Some(event),
+                }))
+                .payload(b),
+            Duration::from_secs(0),
+        )
+        .await;
+
+    if let Err(e) = res {
+        error!(error = ?e, "Publishing event error");
+        return Err(anyhow!("{:?}", e));
+    }
+
+    Ok(())
+}
+
+// Consumes the commands from the command topic. The offset of a command is committed after it has
+// been handled. The result of a command is published as downlink event (enqueued or rejected).
+async fn consume_commands(conf: &Config, command_regex: &Regex) -> Result<()> {
+    let consumer: StreamConsumer = client_config(conf)?
+        .set("group.id", &conf.consumer_group)
+        .set("enable.auto.commit", "false")
+        .create()?;
+    consumer.subscribe(&[conf.command_topic.as_str()])?;
+
+    info!(topic = %conf.command_topic, consumer_group = %conf.consumer_group, "Consuming commands");
+
+    loop {
+        let msg = consumer.recv().await?;
+        let key = String::from_utf8_lossy(msg.key().unwrap_or_default()).to_string();
+        trace!(key = %key, "Command received");
+
+        match command_regex.captures(&key) {
+            Some(caps) => {
+                info!(key = %key, "Command received for device");
+
+                match super::decode_down_command(
+                    &caps["dev_eui"],
+                    &caps["command"],
+                    conf.json,
+                    msg.payload().unwrap_or_default(),
+                ) {
+                    Ok(cmd) => {
+                        super::handle_down_command(caps["application_id"].to_string(), cmd).await;
+                    }
+                    Err(e) => {
+                        warn!(key = %key, error = %e.full(), "Processing command error");
+                    }
+                }
+            }
+            None => {
+                warn!(key = %key, "Error parsing command key (regex captures returned None)");
+            }
+        }
+
+        // Invalid commands are committed as well, as a redelivery would fail again.
+        consumer.commit_message(&msg, CommitMode::Async)?;
+    }
 }
 
 #[cfg(all(test, feature = "test-integration-kafka"))]
@@ -226,14 +342,11 @@ pub mod test {
     use std::env;
 
     use super::*;
+    use crate::storage::{application, device, device_profile, device_queue, tenant};
     use crate::test;
-    use rdkafka::consumer::stream_consumer::StreamConsumer;
-    use rdkafka::consumer::Consumer;
+    use lrwn::EUI64;
     use rdkafka::message::Headers;
     use rdkafka::Message;
-    use std::time::Duration;
-    use tokio::time::sleep;
-    use tracing::trace;
 
     use uuid::Uuid;
 
@@ -304,4 +417,103 @@ pub mod test {
             msg.headers().unwrap().get(0)
         );
     }
+
+    #[tokio::test]
+    async fn test_kafka_commands() {
+        let _guard = test::prepare().await;
+        crate::integration::set_mock().await;
+        crate::integration::mock::reset().await;
+
+        dotenv::dotenv().ok();
+        dotenv::from_filename(".env.local").ok();
+
+        // setup base objects
+        let t = tenant::create(tenant::Tenant {
+            name: "test-tenant".into(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let app = application::create(application::Application {
+            name: "test-app".into(),
+            tenant_id: t.id,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let dp = device_profile::create(device_profile::DeviceProfile {
+            name: "test-dp".into(),
+            tenant_id: t.id,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let dev = device::create(device::Device {
+            name: "test-device".into(),
+            dev_eui: EUI64::from_be_bytes([1, 2, 3, 4, 5, 6, 7, 8]),
+            application_id: app.id,
+            device_profile_id: dp.id,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        // The topics are different from test_kafka, such that the tests do not interfere.
+        let conf = Config {
+            brokers: vec![env::var("TEST_KAFKA_BROKER").unwrap()],
+            topic: "chirpstack-test-events".to_string(),
+            command_topic: "chirpstack-test-commands".to_string(),
+            json: true,
+            ..Default::default()
+        };
+
+        let _i = Integration::new(&conf).unwrap();
+
+        // downlink command
+        let down_cmd = integration::DownlinkCommand {
+            id: Uuid::new_v4().to_string(),
+            dev_eui: dev.dev_eui.to_string(),
+            confirmed: false,
+            f_port: 10,
+            data: vec![1, 2, 3],
+            object: None,
+            correlation_id: "".into(),
+        };
+        let producer: FutureProducer = ClientConfig::new()
+            .set("bootstrap.servers", env::var("TEST_KAFKA_BROKER").unwrap())
+            .set("allow.auto.create.topics", "true")
+            .create()
+            .unwrap();
+        producer
+            .send(
+                FutureRecord::to("chirpstack-test-commands")
+                    .key(&format!(
+                        "application.{}.device.{}.command.down",
+                        app.id, dev.dev_eui
+                    ))
+                    .payload(&serde_json::to_vec(&down_cmd).unwrap()),
+                Duration::from_secs(0),
+            )
+            .await
+            .unwrap();
+
+        // enqueued event, joining the consumer group might take a while
+        let mut events = Vec::new();
+        for _ in 0..50 {
+            events = crate::integration::mock::get_downlink_events().await;
+            if !events.is_empty() {
+                break;
+            }
+            sleep(Duration::from_millis(200)).await;
+        }
+        assert_eq!(1, events.len());
+        assert_eq!(integration::DownlinkState::Enqueued, events[0].state());
+        assert_eq!(down_cmd.id, events[0].queue_item_id);
+
+        let queue_items = device_queue::get_for_dev_eui(&dev.dev_eui).await.unwrap();
+        assert_eq!(1, queue_items.len());
+        assert_eq!(down_cmd.id, queue_items[0].id.to_string());
+        assert_eq!(10, queue_items[0].f_port);
+        assert_eq!(vec![1, 2, 3], queue_items[0].data);
+    }
 }
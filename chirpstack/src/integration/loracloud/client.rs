@@ -4,12 +4,12 @@ use std::time::Duration;
 use anyhow::Result;
 use chirpstack_api::{common, gw};
 use reqwest::header::{HeaderMap, HeaderName, CONTENT_TYPE};
-use reqwest::Client;
 use serde::de::{self, Visitor};
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use thiserror::Error;
 
 use crate::gpstime::ToGpsTime;
+use crate::integration::http_client;
 use crate::uplink::helpers;
 use lrwn::EUI64;
 
@@ -117,7 +117,7 @@ impl ApiClient {
 
     pub async fn uplink_send(&self, req: &UplinkRequest) -> Result<UplinkResponse> {
         let endpoint = format!("{}/api/v1/device/send", self.uri);
-        let client = Client::builder().timeout(self.timeout).build()?;
+        let client = http_client();
         let mut headers = HeaderMap::new();
         headers.insert(CONTENT_TYPE, "application/json".parse().unwrap());
         headers.insert(
@@ -127,6 +127,7 @@ impl ApiClient {
 
         let res = client
             .post(endpoint)
+            .timeout(self.timeout)
             .headers(headers)
             .json(req)
             .send()
@@ -138,7 +139,7 @@ impl ApiClient {
 
     async fn request(&self, endpoint: &str, body: &str) -> Result<Response> {
         let endpoint = format!("{}{}", self.uri, endpoint);
-        let client = Client::builder().timeout(self.timeout).build()?;
+        let client = http_client();
         let mut headers = HeaderMap::new();
         headers.insert(CONTENT_TYPE, "application/json".parse().unwrap());
         headers.insert(
@@ -148,6 +149,7 @@ impl ApiClient {
 
         let res = client
             .post(endpoint)
+            .timeout(self.timeout)
             .body(body.to_string())
             .headers(headers)
             .send()
@@ -160,7 +162,7 @@ impl ApiClient {
 
     async fn v3_request(&self, endpoint: &str, body: &str) -> Result<V3Response> {
         let endpoint = format!("{}{}", self.uri, endpoint);
-        let client = Client::builder().timeout(self.timeout).build()?;
+        let client = http_client();
         let mut headers = HeaderMap::new();
         headers.insert(CONTENT_TYPE, "application/json".parse().unwrap());
         headers.insert(
@@ -170,6 +172,7 @@ impl ApiClient {
 
         let res = client
             .post(endpoint)
+            .timeout(self.timeout)
             .body(body.to_string())
             .headers(headers)
             .send()
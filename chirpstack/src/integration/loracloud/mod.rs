@@ -805,4 +805,12 @@ impl IntegrationTrait for Integration {
     ) -> Result<()> {
         Ok(())
     }
+
+    async fn downlink_event(
+        &self,
+        _vars: &HashMap<String, String>,
+        _pl: &integration::DownlinkEvent,
+    ) -> Result<()> {
+        Ok(())
+    }
 }
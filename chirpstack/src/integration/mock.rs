@@ -18,6 +18,7 @@ lazy_static! {
     static ref LOCATION_EVENTS: RwLock<Vec<integration::LocationEvent>> = RwLock::new(Vec::new());
     static ref INTEGRATION_EVENTS: RwLock<Vec<integration::IntegrationEvent>> =
         RwLock::new(Vec::new());
+    static ref DOWNLINK_EVENTS: RwLock<Vec<integration::DownlinkEvent>> = RwLock::new(Vec::new());
 }
 
 pub async fn reset() {
@@ -29,6 +30,7 @@ pub async fn reset() {
     STATUS_EVENTS.write().await.drain(..);
     LOCATION_EVENTS.write().await.drain(..);
     INTEGRATION_EVENTS.write().await.drain(..);
+    DOWNLINK_EVENTS.write().await.drain(..);
 }
 
 pub struct Integration {}
@@ -106,6 +108,15 @@ impl IntegrationTrait for Integration {
         INTEGRATION_EVENTS.write().await.push(pl.clone());
         Ok(())
     }
+
+    async fn downlink_event(
+        &self,
+        _vars: &HashMap<String, String>,
+        pl: &integration::DownlinkEvent,
+    ) -> Result<()> {
+        DOWNLINK_EVENTS.write().await.push(pl.clone());
+        Ok(())
+    }
 }
 
 pub async fn get_join_event() -> Option<integration::JoinEvent> {
@@ -191,3 +202,7 @@ pub async fn get_location_events() -> Vec<integration::LocationEvent> {
 pub async fn get_integration_events() -> Vec<integration::IntegrationEvent> {
     INTEGRATION_EVENTS.write().await.drain(..).collect()
 }
+
+pub async fn get_downlink_events() -> Vec<integration::DownlinkEvent> {
+    DOWNLINK_EVENTS.write().await.drain(..).collect()
+}
@@ -1,22 +1,29 @@
 use std::collections::HashMap;
 use std::str::FromStr;
+use std::sync::Arc;
 
 use anyhow::{Context, Result};
 use async_trait::async_trait;
+use chrono::Utc;
 use futures::future::join_all;
+use prost::Message;
 use tokio::sync::RwLock;
-use tracing::{info, warn};
+use tracing::{info, trace, warn};
 use uuid::Uuid;
 
+use crate::api::helpers::ToProto;
 use crate::helpers::errors::PrintFullError;
-use crate::storage::{application, device, device_profile, device_queue};
-use crate::{codec, config};
+use crate::storage::helpers::get_all_device_data;
+use crate::storage::{application, device, device_profile, device_queue, tenant};
+use crate::{codec, config, downlink};
 use chirpstack_api::integration;
 use lrwn::EUI64;
 
 mod amqp;
 mod aws_sns;
 mod azure_service_bus;
+mod cache;
+mod filter;
 mod gcp_pub_sub;
 mod http;
 mod ifttt;
@@ -27,12 +34,26 @@ mod loracloud;
 pub mod mock;
 mod mqtt;
 mod mydevices;
+mod nats;
+pub mod outbox;
 mod pilot_things;
 mod postgresql;
+mod pulsar;
 mod redis;
+mod sql;
 mod thingsboard;
 
+struct ApplicationIntegration {
+    kind: Option<application::IntegrationKind>,
+    filter: application::IntegrationFilter,
+    integration: Box<dyn Integration + Sync + Send>,
+}
+
+type ApplicationIntegrations = Vec<ApplicationIntegration>;
+
 lazy_static! {
+    // Shared HTTP client, such that connections are re-used across events and integrations.
+    static ref HTTP_CLIENT: reqwest::Client = reqwest::Client::new();
     static ref GLOBAL_INTEGRATIONS: RwLock<Vec<Box<dyn Integration + Sync + Send>>> =
         RwLock::new(Vec::new());
     static ref MOCK_INTEGRATION: RwLock<bool> = RwLock::new(false);
@@ -68,15 +89,47 @@ pub async fn setup() -> Result<()> {
                 kafka::Integration::new(&conf.integration.kafka)
                     .context("Setup Kafka integration")?,
             )),
+            "nats" => integrations.push(Box::new(
+                nats::Integration::new(&conf.integration.nats)
+                    .await
+                    .context("Setup NATS integration")?,
+            )),
+            "pulsar" => integrations.push(Box::new(
+                pulsar::Integration::new(&conf.integration.pulsar)
+                    .await
+                    .context("Setup Pulsar integration")?,
+            )),
+            "sql" => integrations.push(Box::new(
+                sql::Integration::new(&conf.integration.sql)
+                    .await
+                    .context("Setup SQL integration")?,
+            )),
             _ => {
                 return Err(anyhow!("Unexpected integration: {}", name));
             }
         }
     }
 
+    tokio::spawn(cache::subscribe_loop());
+    tokio::spawn(outbox::retry_loop());
+
     Ok(())
 }
 
+fn http_client() -> reqwest::Client {
+    HTTP_CLIENT.clone()
+}
+
+pub async fn invalidate_cache(application_id: &Uuid) {
+    cache::invalidate(application_id).await;
+}
+
+// Validates the HTTP integration configuration (endpoint templates, certificates and key) by
+// constructing the integration.
+pub fn validate_http_configuration(conf: &application::HttpConfiguration) -> Result<()> {
+    http::Integration::new(conf).map(|_| ())
+}
+
 #[cfg(test)]
 pub async fn set_mock() {
     let mut m = MOCK_INTEGRATION.write().await;
@@ -132,144 +185,181 @@ pub trait Integration {
         vars: &HashMap<String, String>,
         pl: &integration::IntegrationEvent,
     ) -> Result<()>;
+
+    async fn downlink_event(
+        &self,
+        vars: &HashMap<String, String>,
+        pl: &integration::DownlinkEvent,
+    ) -> Result<()>;
 }
 
-// Returns a Vec of integrations for the given Application ID.
-async fn for_application_id(id: Uuid) -> Result<Vec<Box<dyn Integration + Sync + Send>>> {
-    #[cfg(test)]
-    {
-        let m = MOCK_INTEGRATION.read().await;
-        if *m {
-            return Ok(vec![Box::new(mock::Integration {})]);
+// Event wraps the different integration event payloads, such that these can be stored in and
+// retried from the outbox.
+#[derive(Clone)]
+pub enum Event {
+    Up(integration::UplinkEvent),
+    Join(integration::JoinEvent),
+    Ack(integration::AckEvent),
+    TxAck(integration::TxAckEvent),
+    Log(integration::LogEvent),
+    Status(integration::StatusEvent),
+    Location(integration::LocationEvent),
+    Integration(integration::IntegrationEvent),
+    Downlink(integration::DownlinkEvent),
+}
+
+impl Event {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Event::Up(_) => "up",
+            Event::Join(_) => "join",
+            Event::Ack(_) => "ack",
+            Event::TxAck(_) => "txack",
+            Event::Log(_) => "log",
+            Event::Status(_) => "status",
+            Event::Location(_) => "location",
+            Event::Integration(_) => "integration",
+            Event::Downlink(_) => "downlink",
         }
     }
 
-    let mut out: Vec<Box<dyn Integration + Sync + Send>> = Vec::new();
-    let integrations = application::get_integrations_for_application(&id).await?;
+    pub fn encode_to_vec(&self) -> Vec<u8> {
+        match self {
+            Event::Up(pl) => pl.encode_to_vec(),
+            Event::Join(pl) => pl.encode_to_vec(),
+            Event::Ack(pl) => pl.encode_to_vec(),
+            Event::TxAck(pl) => pl.encode_to_vec(),
+            Event::Log(pl) => pl.encode_to_vec(),
+            Event::Status(pl) => pl.encode_to_vec(),
+            Event::Location(pl) => pl.encode_to_vec(),
+            Event::Integration(pl) => pl.encode_to_vec(),
+            Event::Downlink(pl) => pl.encode_to_vec(),
+        }
+    }
 
-    for app_i in &integrations {
-        out.push(match &app_i.configuration {
-            application::IntegrationConfiguration::AwsSns(conf) => {
-                Box::new(aws_sns::Integration::new(conf).await?)
-            }
-            application::IntegrationConfiguration::AzureServiceBus(conf) => {
-                Box::new(azure_service_bus::Integration::new(conf)?)
-            }
-            application::IntegrationConfiguration::GcpPubSub(conf) => {
-                Box::new(gcp_pub_sub::Integration::new(conf).await?)
-            }
-            application::IntegrationConfiguration::Http(conf) => {
-                Box::new(http::Integration::new(conf))
-            }
-            application::IntegrationConfiguration::InfluxDb(conf) => {
-                Box::new(influxdb::Integration::new(conf)?)
-            }
-            application::IntegrationConfiguration::LoraCloud(conf) => {
-                Box::new(loracloud::Integration::new(conf))
-            }
-            application::IntegrationConfiguration::MyDevices(conf) => {
-                Box::new(mydevices::Integration::new(conf))
-            }
-            application::IntegrationConfiguration::PilotThings(conf) => {
-                Box::new(pilot_things::Integration::new(conf))
-            }
-            application::IntegrationConfiguration::ThingsBoard(conf) => {
-                Box::new(thingsboard::Integration::new(conf))
-            }
-            application::IntegrationConfiguration::Ifttt(conf) => {
-                Box::new(ifttt::Integration::new(conf))
-            }
-            _ => {
-                continue;
-            }
+    pub fn decode(name: &str, b: &[u8]) -> Result<Self> {
+        Ok(match name {
+            "up" => Event::Up(integration::UplinkEvent::decode(b)?),
+            "join" => Event::Join(integration::JoinEvent::decode(b)?),
+            "ack" => Event::Ack(integration::AckEvent::decode(b)?),
+            "txack" => Event::TxAck(integration::TxAckEvent::decode(b)?),
+            "log" => Event::Log(integration::LogEvent::decode(b)?),
+            "status" => Event::Status(integration::StatusEvent::decode(b)?),
+            "location" => Event::Location(integration::LocationEvent::decode(b)?),
+            "integration" => Event::Integration(integration::IntegrationEvent::decode(b)?),
+            "downlink" => Event::Downlink(integration::DownlinkEvent::decode(b)?),
+            _ => return Err(anyhow!("Unexpected event: {}", name)),
         })
     }
 
-    Ok(out)
-}
-
-pub async fn uplink_event(
-    application_id: Uuid,
-    vars: &HashMap<String, String>,
-    pl: &integration::UplinkEvent,
-) {
-    tokio::spawn({
-        let vars = vars.clone();
-        let pl = pl.clone();
+    fn device_info(&self) -> Option<&integration::DeviceInfo> {
+        match self {
+            Event::Up(pl) => pl.device_info.as_ref(),
+            Event::Join(pl) => pl.device_info.as_ref(),
+            Event::Ack(pl) => pl.device_info.as_ref(),
+            Event::TxAck(pl) => pl.device_info.as_ref(),
+            Event::Log(pl) => pl.device_info.as_ref(),
+            Event::Status(pl) => pl.device_info.as_ref(),
+            Event::Location(pl) => pl.device_info.as_ref(),
+            Event::Integration(pl) => pl.device_info.as_ref(),
+            Event::Downlink(pl) => pl.device_info.as_ref(),
+        }
+    }
 
-        async move {
-            if let Err(err) = _uplink_event(application_id, &vars, &pl).await {
-                warn!(application_id = %application_id, error = %err.full(), "Uplink event error");
-            }
+    async fn send(
+        &self,
+        i: &(dyn Integration + Sync + Send),
+        vars: &HashMap<String, String>,
+    ) -> Result<()> {
+        match self {
+            Event::Up(pl) => i.uplink_event(vars, pl).await,
+            Event::Join(pl) => i.join_event(vars, pl).await,
+            Event::Ack(pl) => i.ack_event(vars, pl).await,
+            Event::TxAck(pl) => i.txack_event(vars, pl).await,
+            Event::Log(pl) => i.log_event(vars, pl).await,
+            Event::Status(pl) => i.status_event(vars, pl).await,
+            Event::Location(pl) => i.location_event(vars, pl).await,
+            Event::Integration(pl) => i.integration_event(vars, pl).await,
+            Event::Downlink(pl) => i.downlink_event(vars, pl).await,
         }
-    });
+    }
 }
 
-async fn _uplink_event(
-    application_id: Uuid,
-    vars: &HashMap<String, String>,
-    pl: &integration::UplinkEvent,
-) -> Result<()> {
-    let app_ints = for_application_id(application_id)
-        .await
-        .context("Get integrations for application")?;
-    let global_ints = GLOBAL_INTEGRATIONS.read().await;
-    let mut futures = Vec::new();
-
-    for (i, _) in app_ints.iter().enumerate() {
-        futures.push(app_ints[i].uplink_event(vars, pl));
-    }
-    for (i, _) in global_ints.iter().enumerate() {
-        futures.push(global_ints[i].uplink_event(vars, pl));
-    }
+// Returns the integration for the given application integration configuration. None is returned
+// when the configuration does not have an integration implementation.
+async fn for_configuration(
+    conf: &application::IntegrationConfiguration,
+) -> Result<Option<Box<dyn Integration + Sync + Send>>> {
+    Ok(Some(match conf {
+        application::IntegrationConfiguration::AwsSns(conf) => {
+            Box::new(aws_sns::Integration::new(conf).await?)
+        }
+        application::IntegrationConfiguration::AzureServiceBus(conf) => {
+            Box::new(azure_service_bus::Integration::new(conf)?)
+        }
+        application::IntegrationConfiguration::GcpPubSub(conf) => {
+            Box::new(gcp_pub_sub::Integration::new(conf).await?)
+        }
+        application::IntegrationConfiguration::Http(conf) => {
+            Box::new(http::Integration::new(conf)?)
+        }
+        application::IntegrationConfiguration::InfluxDb(conf) => {
+            Box::new(influxdb::Integration::new(conf)?)
+        }
+        application::IntegrationConfiguration::LoraCloud(conf) => {
+            Box::new(loracloud::Integration::new(conf))
+        }
+        application::IntegrationConfiguration::MyDevices(conf) => {
+            Box::new(mydevices::Integration::new(conf))
+        }
+        application::IntegrationConfiguration::PilotThings(conf) => {
+            Box::new(pilot_things::Integration::new(conf))
+        }
+        application::IntegrationConfiguration::ThingsBoard(conf) => {
+            Box::new(thingsboard::Integration::new(conf))
+        }
+        application::IntegrationConfiguration::Ifttt(conf) => {
+            Box::new(ifttt::Integration::new(conf))
+        }
+        application::IntegrationConfiguration::None => {
+            return Ok(None);
+        }
+    }))
+}
 
-    for e in join_all(futures).await {
-        e?;
+// Returns the integrations for the given Application ID, together with the integration kind and
+// filter. The kind is used for storing failed events in the outbox. It is None for the mock
+// integration.
+async fn for_application_id(id: Uuid) -> Result<Arc<ApplicationIntegrations>> {
+    #[cfg(test)]
+    {
+        let m = MOCK_INTEGRATION.read().await;
+        if *m {
+            return Ok(Arc::new(vec![ApplicationIntegration {
+                kind: None,
+                filter: Default::default(),
+                integration: Box::new(mock::Integration {}),
+            }]));
+        }
     }
 
-    Ok(())
+    cache::get(&id).await
 }
 
-pub async fn join_event(
+pub async fn uplink_event(
     application_id: Uuid,
     vars: &HashMap<String, String>,
-    pl: &integration::JoinEvent,
+    pl: &integration::UplinkEvent,
 ) {
-    tokio::spawn({
-        let vars = vars.clone();
-        let pl = pl.clone();
-
-        async move {
-            if let Err(err) = _join_event(application_id, &vars, &pl).await {
-                warn!(application_id = %application_id, error = %err.full(), "Join event error");
-            }
-        }
-    });
+    handle_event(application_id, vars, Event::Up(pl.clone()));
 }
 
-async fn _join_event(
+pub async fn join_event(
     application_id: Uuid,
     vars: &HashMap<String, String>,
     pl: &integration::JoinEvent,
-) -> Result<()> {
-    let app_ints = for_application_id(application_id)
-        .await
-        .context("Get integrations for application")?;
-    let global_ints = GLOBAL_INTEGRATIONS.read().await;
-    let mut futures = Vec::new();
-
-    for (i, _) in app_ints.iter().enumerate() {
-        futures.push(app_ints[i].join_event(vars, pl));
-    }
-    for (i, _) in global_ints.iter().enumerate() {
-        futures.push(global_ints[i].join_event(vars, pl));
-    }
-
-    for e in join_all(futures).await {
-        e?;
-    }
-
-    Ok(())
+) {
+    handle_event(application_id, vars, Event::Join(pl.clone()));
 }
 
 pub async fn ack_event(
@@ -277,41 +367,7 @@ pub async fn ack_event(
     vars: &HashMap<String, String>,
     pl: &integration::AckEvent,
 ) {
-    tokio::spawn({
-        let vars = vars.clone();
-        let pl = pl.clone();
-
-        async move {
-            if let Err(err) = _ack_event(application_id, &vars, &pl).await {
-                warn!(application_id = %application_id, error = %err.full(), "Ack event error");
-            }
-        }
-    });
-}
-
-async fn _ack_event(
-    application_id: Uuid,
-    vars: &HashMap<String, String>,
-    pl: &integration::AckEvent,
-) -> Result<()> {
-    let app_ints = for_application_id(application_id)
-        .await
-        .context("Get integrations for application")?;
-    let global_ints = GLOBAL_INTEGRATIONS.read().await;
-    let mut futures = Vec::new();
-
-    for (i, _) in app_ints.iter().enumerate() {
-        futures.push(app_ints[i].ack_event(vars, pl));
-    }
-    for (i, _) in global_ints.iter().enumerate() {
-        futures.push(global_ints[i].ack_event(vars, pl));
-    }
-
-    for e in join_all(futures).await {
-        e?;
-    }
-
-    Ok(())
+    handle_event(application_id, vars, Event::Ack(pl.clone()));
 }
 
 pub async fn txack_event(
@@ -319,41 +375,7 @@ pub async fn txack_event(
     vars: &HashMap<String, String>,
     pl: &integration::TxAckEvent,
 ) {
-    tokio::spawn({
-        let vars = vars.clone();
-        let pl = pl.clone();
-
-        async move {
-            if let Err(err) = _txack_event(application_id, &vars, &pl).await {
-                warn!(application_id = %application_id, error = %err.full(), "Txack event error");
-            }
-        }
-    });
-}
-
-async fn _txack_event(
-    application_id: Uuid,
-    vars: &HashMap<String, String>,
-    pl: &integration::TxAckEvent,
-) -> Result<()> {
-    let app_ints = for_application_id(application_id)
-        .await
-        .context("Get integrations for application")?;
-    let global_ints = GLOBAL_INTEGRATIONS.read().await;
-    let mut futures = Vec::new();
-
-    for (i, _) in app_ints.iter().enumerate() {
-        futures.push(app_ints[i].txack_event(vars, pl));
-    }
-    for (i, _) in global_ints.iter().enumerate() {
-        futures.push(global_ints[i].txack_event(vars, pl));
-    }
-
-    for e in join_all(futures).await {
-        e?;
-    }
-
-    Ok(())
+    handle_event(application_id, vars, Event::TxAck(pl.clone()));
 }
 
 pub async fn log_event(
@@ -361,188 +383,204 @@ pub async fn log_event(
     vars: &HashMap<String, String>,
     pl: &integration::LogEvent,
 ) {
-    tokio::spawn({
-        let vars = vars.clone();
-        let pl = pl.clone();
-
-        async move {
-            if let Err(err) = _log_event(application_id, &vars, &pl).await {
-                warn!(application_id = %application_id, error = %err.full(), "Log event error");
-            }
-        }
-    });
+    handle_event(application_id, vars, Event::Log(pl.clone()));
 }
 
-async fn _log_event(
+pub async fn status_event(
     application_id: Uuid,
     vars: &HashMap<String, String>,
-    pl: &integration::LogEvent,
-) -> Result<()> {
-    let app_ints = for_application_id(application_id)
-        .await
-        .context("Get integrations for application")?;
-    let global_ints = GLOBAL_INTEGRATIONS.read().await;
-    let mut futures = Vec::new();
-
-    for (i, _) in app_ints.iter().enumerate() {
-        futures.push(app_ints[i].log_event(vars, pl));
-    }
-    for (i, _) in global_ints.iter().enumerate() {
-        futures.push(global_ints[i].log_event(vars, pl));
-    }
-
-    for e in join_all(futures).await {
-        e?;
-    }
-
-    Ok(())
+    pl: &integration::StatusEvent,
+) {
+    handle_event(application_id, vars, Event::Status(pl.clone()));
 }
 
-pub async fn status_event(
+pub async fn location_event(
     application_id: Uuid,
     vars: &HashMap<String, String>,
-    pl: &integration::StatusEvent,
+    pl: &integration::LocationEvent,
 ) {
-    tokio::spawn({
-        let vars = vars.clone();
-        let pl = pl.clone();
-
-        async move {
-            if let Err(err) = _status_event(application_id, &vars, &pl).await {
-                warn!(application_id = %application_id, error = %err.full(), "Status event error");
-            }
-        }
-    });
+    handle_event(application_id, vars, Event::Location(pl.clone()));
 }
 
-async fn _status_event(
+pub async fn integration_event(
     application_id: Uuid,
     vars: &HashMap<String, String>,
-    pl: &integration::StatusEvent,
-) -> Result<()> {
-    let app_ints = for_application_id(application_id)
-        .await
-        .context("Get integrations for application")?;
-    let global_ints = GLOBAL_INTEGRATIONS.read().await;
-    let mut futures = Vec::new();
-
-    for (i, _) in app_ints.iter().enumerate() {
-        futures.push(app_ints[i].status_event(vars, pl));
-    }
-    for (i, _) in global_ints.iter().enumerate() {
-        futures.push(global_ints[i].status_event(vars, pl));
-    }
-
-    for e in join_all(futures).await {
-        e?;
-    }
-
-    Ok(())
+    pl: &integration::IntegrationEvent,
+) {
+    handle_event(application_id, vars, Event::Integration(pl.clone()));
 }
 
-pub async fn location_event(
+pub async fn downlink_event(
     application_id: Uuid,
     vars: &HashMap<String, String>,
-    pl: &integration::LocationEvent,
+    pl: &integration::DownlinkEvent,
 ) {
+    handle_event(application_id, vars, Event::Downlink(pl.clone()));
+}
+
+fn handle_event(application_id: Uuid, vars: &HashMap<String, String>, pl: Event) {
     tokio::spawn({
         let vars = vars.clone();
-        let pl = pl.clone();
 
         async move {
-            if let Err(err) = _location_event(application_id, &vars, &pl).await {
-                warn!(application_id = %application_id, error = %err.full(), "Location event error");
+            if let Err(err) = _handle_event(application_id, &vars, &pl).await {
+                warn!(application_id = %application_id, event = pl.name(), error = %err.full(), "Handle event error");
             }
         }
     });
 }
 
-async fn _location_event(
+async fn _handle_event(
     application_id: Uuid,
     vars: &HashMap<String, String>,
-    pl: &integration::LocationEvent,
+    pl: &Event,
 ) -> Result<()> {
     let app_ints = for_application_id(application_id)
         .await
         .context("Get integrations for application")?;
     let global_ints = GLOBAL_INTEGRATIONS.read().await;
-    let mut futures = Vec::new();
 
-    for (i, _) in app_ints.iter().enumerate() {
-        futures.push(app_ints[i].location_event(vars, pl));
+    let mut app_futures = Vec::new();
+    for ai in app_ints.iter() {
+        app_futures.push(async move {
+            let res = match filter::matches(&ai.filter, vars, pl).await {
+                Ok(true) => match ai.kind {
+                    Some(kind) if config::get().integration.outbox.enabled => {
+                        outbox::send(application_id, kind, ai.integration.as_ref(), vars, pl)
+                            .await
+                    }
+                    _ => pl.send(ai.integration.as_ref(), vars).await,
+                },
+                Ok(false) => {
+                    trace!(application_id = %application_id, kind = ?ai.kind, event = pl.name(), "Event does not match integration filter");
+                    Ok(())
+                }
+                Err(err) => {
+                    warn!(application_id = %application_id, kind = ?ai.kind, event = pl.name(), error = %err.full(), "Evaluating integration filter failed, event is not forwarded");
+                    Ok(())
+                }
+            };
+            (&ai.kind, res)
+        });
     }
-    for (i, _) in global_ints.iter().enumerate() {
-        futures.push(global_ints[i].location_event(vars, pl));
+    let mut global_futures = Vec::new();
+    for i in global_ints.iter() {
+        global_futures.push(pl.send(i.as_ref(), vars));
     }
 
-    for e in join_all(futures).await {
-        e?;
+    let (app_res, global_res) = tokio::join!(join_all(app_futures), join_all(global_futures));
+
+    // Events sent to application integrations through the outbox are retried from the outbox,
+    // an error is only returned when the event could not be stored. Global integrations are not
+    // covered by the outbox, failures are only logged. The failures are logged per integration,
+    // such that one failure does not hide the others.
+    let mut errors = 0;
+    for (kind, res) in app_res {
+        if let Err(err) = res {
+            warn!(application_id = %application_id, kind = ?kind, event = pl.name(), error = %err.full(), "Sending event failed");
+            errors += 1;
+        }
     }
 
-    Ok(())
-}
+    for err in global_res.into_iter().filter_map(|r| r.err()) {
+        warn!(application_id = %application_id, event = pl.name(), error = %err.full(), "Sending event to global integration failed");
+        errors += 1;
+    }
 
-pub async fn integration_event(
-    application_id: Uuid,
-    vars: &HashMap<String, String>,
-    pl: &integration::IntegrationEvent,
-) {
-    tokio::spawn({
-        let vars = vars.clone();
-        let pl = pl.clone();
+    if errors > 0 {
+        return Err(anyhow!(
+            "Sending event failed for {} integration(s)",
+            errors
+        ));
+    }
 
-        async move {
-            if let Err(err) = _integration_event(application_id, &vars, &pl).await {
-                warn!(application_id = %application_id, error = %err.full(), "Location event error");
-            }
-        }
-    });
+    Ok(())
 }
 
-async fn _integration_event(
-    application_id: Uuid,
-    vars: &HashMap<String, String>,
-    pl: &integration::IntegrationEvent,
+// Handles a command received by one of the global integrations. The application ID, DevEUI and
+// command type are obtained from the topic / subject / key of the received message.
+fn handle_command(
+    application_id: String,
+    dev_eui: String,
+    command: &str,
+    json: bool,
+    b: &[u8],
 ) -> Result<()> {
-    let app_ints = for_application_id(application_id)
-        .await
-        .context("Get integrations for application")?;
-    let global_ints = GLOBAL_INTEGRATIONS.read().await;
-    let mut futures = Vec::new();
+    let cmd = decode_down_command(&dev_eui, command, json, b)?;
+    tokio::spawn(handle_down_command(application_id, cmd));
 
-    for (i, _) in app_ints.iter().enumerate() {
-        futures.push(app_ints[i].integration_event(vars, pl));
-    }
-    for (i, _) in global_ints.iter().enumerate() {
-        futures.push(global_ints[i].integration_event(vars, pl));
-    }
+    Ok(())
+}
 
-    for e in join_all(futures).await {
-        e?;
+fn decode_down_command(
+    dev_eui: &str,
+    command: &str,
+    json: bool,
+    b: &[u8],
+) -> Result<integration::DownlinkCommand> {
+    match command {
+        "down" => {
+            let cmd: integration::DownlinkCommand = match json {
+                true => serde_json::from_slice(b)?,
+                false => integration::DownlinkCommand::decode(b)?,
+            };
+            if dev_eui != cmd.dev_eui {
+                return Err(anyhow!(
+                    "Payload dev_eui {} does not match topic dev_eui {}",
+                    cmd.dev_eui,
+                    dev_eui
+                ));
+            }
+            Ok(cmd)
+        }
+        _ => Err(anyhow!("Unknown command type")),
     }
-
-    Ok(())
 }
 
+// Handles the decoded downlink command. The result is sent as enqueued or rejected downlink event.
 async fn handle_down_command(application_id: String, pl: integration::DownlinkCommand) {
-    let err = async {
-        info!(dev_eui = %pl.dev_eui, "Handling downlink command for device");
-        let dev_eui = EUI64::from_str(&pl.dev_eui)?;
-        let app_id = Uuid::from_str(&application_id)?;
-
-        // Validate that the application_id from the topic is indeed the application ID to which
-        // the device belongs.
-        let dev = device::get(&dev_eui).await?;
-        if dev.application_id != app_id {
-            return Err(anyhow!(
-                "Application ID from topic does not match application ID from device"
-            ));
-        }
+    if let Err(e) = enqueue_down_command(&application_id, &pl).await {
+        warn!(dev_eui = %pl.dev_eui, error = %e.full(), "Handling downlink command error");
+    }
+}
+
+// Enqueues the downlink command and returns the ID of the queue item. For devices belonging to the
+// given application, the enqueued or rejected downlink event is sent.
+async fn enqueue_down_command(
+    application_id: &str,
+    pl: &integration::DownlinkCommand,
+) -> Result<Uuid> {
+    info!(dev_eui = %pl.dev_eui, "Handling downlink command for device");
+    let dev_eui = EUI64::from_str(&pl.dev_eui)?;
+    let app_id = Uuid::from_str(application_id)?;
+
+    // Validate that the application_id from the topic is indeed the application ID to which
+    // the device belongs.
+    let (dev, app, t, dp) = get_all_device_data(dev_eui).await?;
+    if dev.application_id != app_id {
+        return Err(anyhow!(
+            "Application ID from topic does not match application ID from device"
+        ));
+    }
 
+    let id = match pl.id.is_empty() {
+        true => Uuid::new_v4(),
+        false => Uuid::from_str(&pl.id)?,
+    };
+
+    let mut event = integration::DownlinkEvent {
+        time: Some(Utc::now().into()),
+        device_info: Some(device_info(&t, &app, &dp, &dev)),
+        queue_item_id: id.to_string(),
+        correlation_id: pl.correlation_id.clone(),
+        f_port: pl.f_port,
+        confirmed: pl.confirmed,
+        ..Default::default()
+    };
+
+    let res = async {
         let mut data = pl.data.clone();
         if let Some(obj) = &pl.object {
-            let dp = device_profile::get(&dev.device_profile_id).await?;
-
             data = codec::struct_to_binary(
                 dp.payload_codec_runtime,
                 pl.f_port as u8,
@@ -553,26 +591,169 @@ async fn handle_down_command(application_id: String, pl: integration::DownlinkCo
             .await?;
         }
 
+        downlink::validate_payload_size(&dev, &dp, data.len())?;
+
         let qi = device_queue::DeviceQueueItem {
-            id: match pl.id.is_empty() {
-                true => Uuid::new_v4(),
-                false => Uuid::from_str(&pl.id)?,
-            },
+            id,
             f_port: pl.f_port as i16,
             confirmed: pl.confirmed,
             data,
             dev_eui,
+            correlation_id: pl.correlation_id.clone(),
             ..Default::default()
         };
 
-        device_queue::enqueue_item(qi).await?;
+        Ok::<_, anyhow::Error>(device_queue::enqueue_item(qi).await?)
+    }
+    .await;
+
+    match &res {
+        Ok(_) => {
+            event.state = integration::DownlinkState::Enqueued.into();
+        }
+        Err(e) => {
+            event.state = integration::DownlinkState::Rejected.into();
+            event.reason = e.full();
+        }
+    }
+    downlink_event(app.id, &dev.variables, &event).await;
+
+    Ok(res?.id)
+}
 
-        Ok(())
+// Returns the device information, as included in the integration events.
+pub fn device_info(
+    t: &tenant::Tenant,
+    app: &application::Application,
+    dp: &device_profile::DeviceProfile,
+    dev: &device::Device,
+) -> integration::DeviceInfo {
+    let mut tags = (*app.tags).clone();
+    tags.extend((*dp.tags).clone());
+    tags.extend((*dev.tags).clone());
+
+    integration::DeviceInfo {
+        tenant_id: t.id.to_string(),
+        tenant_name: t.name.clone(),
+        application_id: app.id.to_string(),
+        application_name: app.name.to_string(),
+        device_profile_id: dp.id.to_string(),
+        device_profile_name: dp.name.clone(),
+        device_name: dev.name.clone(),
+        device_class_enabled: dev.enabled_class.to_proto().into(),
+        dev_eui: dev.dev_eui.to_string(),
+        tags,
     }
-    .await
-    .err();
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use crate::test;
+    use tokio::time::{sleep, Duration};
+
+    #[tokio::test]
+    async fn test_enqueue_down_command() {
+        let _guard = test::prepare().await;
+        set_mock().await;
+        mock::reset().await;
+
+        let t = tenant::create(tenant::Tenant {
+            name: "test-tenant".into(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let app = application::create(application::Application {
+            name: "test-app".into(),
+            tenant_id: t.id,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let dp = device_profile::create(device_profile::DeviceProfile {
+            name: "test-dp".into(),
+            tenant_id: t.id,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let dev = device::create(device::Device {
+            name: "test-device".into(),
+            dev_eui: EUI64::from_be_bytes([1, 2, 3, 4, 5, 6, 7, 8]),
+            application_id: app.id,
+            device_profile_id: dp.id,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        // enqueued
+        let cmd = integration::DownlinkCommand {
+            id: Uuid::new_v4().to_string(),
+            dev_eui: dev.dev_eui.to_string(),
+            confirmed: true,
+            f_port: 10,
+            data: vec![1, 2, 3],
+            correlation_id: "enqueued".into(),
+            ..Default::default()
+        };
+        let id = enqueue_down_command(&app.id.to_string(), &cmd)
+            .await
+            .unwrap();
+        assert_eq!(cmd.id, id.to_string());
+
+        sleep(Duration::from_millis(100)).await;
+        let events = mock::get_downlink_events().await;
+        assert_eq!(1, events.len());
+        assert_eq!(integration::DownlinkState::Enqueued, events[0].state());
+        assert_eq!(cmd.id, events[0].queue_item_id);
+        assert_eq!("enqueued", events[0].correlation_id);
+        assert_eq!(10, events[0].f_port);
+        assert!(events[0].confirmed);
+        assert_eq!("", events[0].reason);
+        assert_eq!(
+            dev.dev_eui.to_string(),
+            events[0].device_info.as_ref().unwrap().dev_eui
+        );
+
+        // rejected because of the max. payload size
+        let cmd = integration::DownlinkCommand {
+            id: Uuid::new_v4().to_string(),
+            dev_eui: dev.dev_eui.to_string(),
+            f_port: 10,
+            data: vec![0; 250],
+            correlation_id: "rejected".into(),
+            ..Default::default()
+        };
+        assert!(enqueue_down_command(&app.id.to_string(), &cmd)
+            .await
+            .is_err());
+
+        sleep(Duration::from_millis(100)).await;
+        let events = mock::get_downlink_events().await;
+        assert_eq!(1, events.len());
+        assert_eq!(integration::DownlinkState::Rejected, events[0].state());
+        assert_eq!(cmd.id, events[0].queue_item_id);
+        assert_eq!("rejected", events[0].correlation_id);
+        assert_ne!("", events[0].reason);
+
+        let queue_items = device_queue::get_for_dev_eui(&dev.dev_eui).await.unwrap();
+        assert_eq!(1, queue_items.len());
+
+        // application ID mismatch, no event is sent as the device does not belong to the
+        // application
+        let cmd = integration::DownlinkCommand {
+            dev_eui: dev.dev_eui.to_string(),
+            f_port: 10,
+            data: vec![1, 2, 3],
+            ..Default::default()
+        };
+        assert!(enqueue_down_command(&Uuid::new_v4().to_string(), &cmd)
+            .await
+            .is_err());
 
-    if err.is_some() {
-        warn!(dev_eui = %pl.dev_eui, error = %err.as_ref().unwrap().full(), "Handling downlink command error");
+        sleep(Duration::from_millis(100)).await;
+        assert!(mock::get_downlink_events().await.is_empty());
     }
 }
@@ -1,5 +1,4 @@
 use std::collections::HashMap;
-use std::io::Cursor;
 use std::time::Duration;
 
 use anyhow::Result;
@@ -396,6 +395,26 @@ impl IntegrationTrait for Integration<'_> {
 
         self.publish_event(&topic, b).await
     }
+
+    async fn downlink_event(
+        &self,
+        _vars: &HashMap<String, String>,
+        pl: &integration::DownlinkEvent,
+    ) -> Result<()> {
+        let dev_info = pl
+            .device_info
+            .as_ref()
+            .ok_or_else(|| anyhow!("device_info is None"))?;
+
+        let topic =
+            self.get_event_topic(&dev_info.application_id, &dev_info.dev_eui, "downlink")?;
+        let b = match self.json {
+            true => serde_json::to_vec(&pl)?,
+            false => pl.encode_to_vec(),
+        };
+
+        self.publish_event(&topic, b).await
+    }
 }
 
 async fn message_callback(
@@ -409,30 +428,7 @@ async fn message_callback(
 
     info!(topic = %topic, qos = ?p.qos, "Command received for device");
 
-    let err = || -> Result<()> {
-        match command.as_ref() {
-            "down" => {
-                let cmd: integration::DownlinkCommand = match json {
-                    true => serde_json::from_slice(&p.payload)?,
-                    false => integration::DownlinkCommand::decode(&mut Cursor::new(&p.payload))?,
-                };
-                if dev_eui != cmd.dev_eui {
-                    return Err(anyhow!(
-                        "Payload dev_eui {} does not match topic dev_eui {}",
-                        cmd.dev_eui,
-                        dev_eui
-                    ));
-                }
-                tokio::spawn(super::handle_down_command(application_id, cmd));
-            }
-            _ => {
-                return Err(anyhow!("Unknown command type"));
-            }
-        }
-
-        Ok(())
-    }()
-    .err();
+    let err = super::handle_command(application_id, dev_eui, &command, json, &p.payload).err();
 
     if err.is_some() {
         warn!(
@@ -702,6 +698,7 @@ pub mod test {
             f_port: 10,
             data: vec![1, 2, 3],
             object: None,
+            correlation_id: "my-correlation-id".into(),
         };
         let down_cmd_json = serde_json::to_string(&down_cmd).unwrap();
         client
@@ -723,5 +720,6 @@ pub mod test {
         assert_eq!(dev.dev_eui, queue_items[0].dev_eui);
         assert_eq!(10, queue_items[0].f_port);
         assert_eq!(vec![1, 2, 3], queue_items[0].data);
+        assert_eq!("my-correlation-id", queue_items[0].correlation_id);
     }
 }
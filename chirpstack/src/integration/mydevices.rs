@@ -5,11 +5,10 @@ use anyhow::Result;
 use async_trait::async_trait;
 use base64::{engine::general_purpose, Engine as _};
 use reqwest::header::{HeaderMap, CONTENT_TYPE};
-use reqwest::Client;
 use serde::Serialize;
 use tracing::{info, trace};
 
-use super::Integration as IntegrationTrait;
+use super::{http_client, Integration as IntegrationTrait};
 use crate::storage::application::MyDevicesConfiguration;
 use chirpstack_api::integration;
 
@@ -120,12 +119,13 @@ impl IntegrationTrait for Integration {
         let pl = UplinkPayload::from_uplink_event(pl);
         let b = serde_json::to_string(&pl)?;
 
-        let client = Client::builder().timeout(self.timeout).build()?;
+        let client = http_client();
         let mut headers = HeaderMap::new();
         headers.insert(CONTENT_TYPE, "application/json".parse().unwrap());
 
         let req = client
             .post(&self.endpoint)
+            .timeout(self.timeout)
             .body(b)
             .headers(headers)
             .send()
@@ -190,6 +190,14 @@ impl IntegrationTrait for Integration {
     ) -> Result<()> {
         Ok(())
     }
+
+    async fn downlink_event(
+        &self,
+        _vars: &HashMap<String, String>,
+        _pl: &integration::DownlinkEvent,
+    ) -> Result<()> {
+        Ok(())
+    }
 }
 
 #[cfg(test)]
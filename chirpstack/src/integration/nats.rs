@@ -0,0 +1,487 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use anyhow::Result;
+use async_nats::jetstream::{self, consumer::pull, stream};
+use async_trait::async_trait;
+use futures::StreamExt;
+use handlebars::Handlebars;
+use prost::Message;
+use regex::Regex;
+use serde::Serialize;
+use tokio::time::sleep;
+use tracing::{error, info, trace, warn};
+
+use super::Integration as IntegrationTrait;
+use crate::config::NatsIntegration as Config;
+use crate::helpers::errors::PrintFullError;
+use chirpstack_api::integration;
+
+pub struct Integration<'a> {
+    context: jetstream::Context,
+    templates: Handlebars<'a>,
+    json: bool,
+}
+
+#[derive(Serialize)]
+struct EventSubjectContext {
+    pub application_id: String,
+    pub dev_eui: String,
+    pub event: String,
+}
+
+#[derive(Serialize)]
+struct CommandSubjectContext {
+    pub application_id: String,
+    pub dev_eui: String,
+    pub command: String,
+}
+
+impl<'a> Integration<'a> {
+    pub async fn new(conf: &Config) -> Result<Integration<'a>> {
+        info!("Initializing NATS integration");
+
+        // subject templates
+        let mut templates = Handlebars::new();
+        templates.register_escape_fn(handlebars::no_escape);
+        templates.register_template_string("event_subject", &conf.event_subject)?;
+        templates.register_template_string("command_subject", &conf.command_subject)?;
+
+        let command_filter = templates.render(
+            "command_subject",
+            &CommandSubjectContext {
+                application_id: "*".to_string(),
+                dev_eui: "*".to_string(),
+                command: "*".to_string(),
+            },
+        )?;
+        let command_regex = Regex::new(&format!(
+            "^{}$",
+            templates.render(
+                "command_subject",
+                &CommandSubjectContext {
+                    application_id: r"(?P<application_id>[\w-]+)".to_string(),
+                    dev_eui: r"(?P<dev_eui>[\w]+)".to_string(),
+                    command: r"(?P<command>[\w]+)".to_string(),
+                },
+            )?
+        ))?;
+
+        let mut opts = async_nats::ConnectOptions::new();
+        if !conf.username.is_empty() {
+            opts = opts.user_and_password(conf.username.clone(), conf.password.clone());
+        }
+        if !conf.token.is_empty() {
+            opts = opts.token(conf.token.clone());
+        }
+        if !conf.credentials_file.is_empty() {
+            opts = opts.credentials_file(&conf.credentials_file).await?;
+        }
+
+        info!(server = %conf.server, "Connecting to NATS server");
+        let client = opts.connect(conf.server.as_str()).await?;
+        let context = jetstream::new(client);
+
+        info!(stream = %conf.stream, subjects = ?conf.stream_subjects, "Creating JetStream stream (if not exists)");
+        context
+            .get_or_create_stream(stream::Config {
+                name: conf.stream.clone(),
+                subjects: conf.stream_subjects.clone(),
+                ..Default::default()
+            })
+            .await?;
+
+        // Command consumer loop
+        tokio::spawn({
+            let context = context.clone();
+            let conf = conf.clone();
+
+            async move {
+                loop {
+                    if let Err(e) =
+                        consume_commands(&context, &conf, &command_filter, &command_regex).await
+                    {
+                        error!(error = %e.full(), "NATS command consumer error");
+                    }
+
+                    sleep(Duration::from_secs(1)).await;
+                }
+            }
+        });
+
+        Ok(Integration {
+            context,
+            templates,
+            json: conf.json,
+        })
+    }
+
+    fn get_event_subject(
+        &self,
+        application_id: &str,
+        dev_eui: &str,
+        event: &str,
+    ) -> Result<String> {
+        Ok(self.templates.render(
+            "event_subject",
+            &EventSubjectContext {
+                application_id: application_id.to_string(),
+                dev_eui: dev_eui.to_string(),
+                event: event.to_string(),
+            },
+        )?)
+    }
+
+    async fn publish_event(&self, subject: String, b: Vec<u8>) -> Result<()> {
+        info!(subject = %subject, "Publishing event");
+
+        // The first await sends the message, the second await waits for the
+        // JetStream acknowledgement.
+        self.context.publish(subject, b.into()).await?.await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl IntegrationTrait for Integration<'_> {
+    async fn uplink_event(
+        &self,
+        _vars: &HashMap<String, String>,
+        pl: &integration::UplinkEvent,
+    ) -> Result<()> {
+        let di = pl
+            .device_info
+            .as_ref()
+            .ok_or_else(|| anyhow!("device_info is None"))?;
+
+        let subject = self.get_event_subject(&di.application_id, &di.dev_eui, "up")?;
+        let b = match self.json {
+            true => serde_json::to_vec(&pl)?,
+            false => pl.encode_to_vec(),
+        };
+
+        self.publish_event(subject, b).await
+    }
+
+    async fn join_event(
+        &self,
+        _vars: &HashMap<String, String>,
+        pl: &integration::JoinEvent,
+    ) -> Result<()> {
+        let di = pl
+            .device_info
+            .as_ref()
+            .ok_or_else(|| anyhow!("device_info is None"))?;
+
+        let subject = self.get_event_subject(&di.application_id, &di.dev_eui, "join")?;
+        let b = match self.json {
+            true => serde_json::to_vec(&pl)?,
+            false => pl.encode_to_vec(),
+        };
+
+        self.publish_event(subject, b).await
+    }
+
+    async fn ack_event(
+        &self,
+        _vars: &HashMap<String, String>,
+        pl: &integration::AckEvent,
+    ) -> Result<()> {
+        let di = pl
+            .device_info
+            .as_ref()
+            .ok_or_else(|| anyhow!("device_info is None"))?;
+
+        let subject = self.get_event_subject(&di.application_id, &di.dev_eui, "ack")?;
+        let b = match self.json {
+            true => serde_json::to_vec(&pl)?,
+            false => pl.encode_to_vec(),
+        };
+
+        self.publish_event(subject, b).await
+    }
+
+    async fn txack_event(
+        &self,
+        _vars: &HashMap<String, String>,
+        pl: &integration::TxAckEvent,
+    ) -> Result<()> {
+        let di = pl
+            .device_info
+            .as_ref()
+            .ok_or_else(|| anyhow!("device_info is None"))?;
+
+        let subject = self.get_event_subject(&di.application_id, &di.dev_eui, "txack")?;
+        let b = match self.json {
+            true => serde_json::to_vec(&pl)?,
+            false => pl.encode_to_vec(),
+        };
+
+        self.publish_event(subject, b).await
+    }
+
+    async fn log_event(
+        &self,
+        _vars: &HashMap<String, String>,
+        pl: &integration::LogEvent,
+    ) -> Result<()> {
+        let di = pl
+            .device_info
+            .as_ref()
+            .ok_or_else(|| anyhow!("device_info is None"))?;
+
+        let subject = self.get_event_subject(&di.application_id, &di.dev_eui, "log")?;
+        let b = match self.json {
+            true => serde_json::to_vec(&pl)?,
+            false => pl.encode_to_vec(),
+        };
+
+        self.publish_event(subject, b).await
+    }
+
+    async fn status_event(
+        &self,
+        _vars: &HashMap<String, String>,
+        pl: &integration::StatusEvent,
+    ) -> Result<()> {
+        let di = pl
+            .device_info
+            .as_ref()
+            .ok_or_else(|| anyhow!("device_info is None"))?;
+
+        let subject = self.get_event_subject(&di.application_id, &di.dev_eui, "status")?;
+        let b = match self.json {
+            true => serde_json::to_vec(&pl)?,
+            false => pl.encode_to_vec(),
+        };
+
+        self.publish_event(subject, b).await
+    }
+
+    async fn location_event(
+        &self,
+        _vars: &HashMap<String, String>,
+        pl: &integration::LocationEvent,
+    ) -> Result<()> {
+        let di = pl
+            .device_info
+            .as_ref()
+            .ok_or_else(|| anyhow!("device_info is None"))?;
+
+        let subject = self.get_event_subject(&di.application_id, &di.dev_eui, "location")?;
+        let b = match self.json {
+            true => serde_json::to_vec(&pl)?,
+            false => pl.encode_to_vec(),
+        };
+
+        self.publish_event(subject, b).await
+    }
+
+    async fn integration_event(
+        &self,
+        _vars: &HashMap<String, String>,
+        pl: &integration::IntegrationEvent,
+    ) -> Result<()> {
+        let di = pl
+            .device_info
+            .as_ref()
+            .ok_or_else(|| anyhow!("device_info is None"))?;
+
+        let subject = self.get_event_subject(&di.application_id, &di.dev_eui, "integration")?;
+        let b = match self.json {
+            true => serde_json::to_vec(&pl)?,
+            false => pl.encode_to_vec(),
+        };
+
+        self.publish_event(subject, b).await
+    }
+
+    async fn downlink_event(
+        &self,
+        _vars: &HashMap<String, String>,
+        pl: &integration::DownlinkEvent,
+    ) -> Result<()> {
+        let di = pl
+            .device_info
+            .as_ref()
+            .ok_or_else(|| anyhow!("device_info is None"))?;
+
+        let subject = self.get_event_subject(&di.application_id, &di.dev_eui, "downlink")?;
+        let b = match self.json {
+            true => serde_json::to_vec(&pl)?,
+            false => pl.encode_to_vec(),
+        };
+
+        self.publish_event(subject, b).await
+    }
+}
+
+// Consumes the commands using a durable pull consumer, such that commands published while
+// ChirpStack is not connected are not lost and are shared between ChirpStack instances using the
+// same consumer name.
+async fn consume_commands(
+    context: &jetstream::Context,
+    conf: &Config,
+    command_filter: &str,
+    command_regex: &Regex,
+) -> Result<()> {
+    let consumer = context
+        .get_stream(&conf.stream)
+        .await?
+        .get_or_create_consumer(
+            &conf.consumer_name,
+            pull::Config {
+                durable_name: Some(conf.consumer_name.clone()),
+                filter_subject: command_filter.to_string(),
+                ..Default::default()
+            },
+        )
+        .await?;
+
+    info!(stream = %conf.stream, consumer = %conf.consumer_name, command_subject = %command_filter, "Consuming commands");
+
+    let mut messages = consumer.messages().await?;
+    while let Some(msg) = messages.next().await {
+        let msg = msg.map_err(|e| anyhow!("{}", e))?;
+        let subject = msg.subject.to_string();
+        trace!(subject = %subject, "Command received");
+
+        match command_regex.captures(&subject) {
+            Some(caps) => {
+                info!(subject = %subject, "Command received for device");
+
+                if let Err(e) = super::handle_command(
+                    caps["application_id"].to_string(),
+                    caps["dev_eui"].to_string(),
+                    &caps["command"],
+                    conf.json,
+                    &msg.payload,
+                ) {
+                    warn!(subject = %subject, error = %e.full(), "Processing command error");
+                }
+            }
+            None => {
+                warn!(subject = %subject, "Error parsing command subject (regex captures returned None)");
+            }
+        }
+
+        // Invalid commands are acknowledged as well, as a redelivery would fail again.
+        msg.ack().await.map_err(|e| anyhow!("{}", e))?;
+    }
+
+    Err(anyhow!("Command consumer stream closed"))
+}
+
+#[cfg(all(test, feature = "test-integration-nats"))]
+pub mod test {
+    use std::env;
+
+    use super::*;
+    use crate::storage::{application, device, device_profile, device_queue, tenant};
+    use crate::test;
+    use lrwn::EUI64;
+    use uuid::Uuid;
+
+    #[tokio::test]
+    async fn test_nats() {
+        dotenv::dotenv().ok();
+        dotenv::from_filename(".env.local").ok();
+
+        let _guard = test::prepare().await;
+
+        // setup base objects
+        let t = tenant::create(tenant::Tenant {
+            name: "test-tenant".into(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let app = application::create(application::Application {
+            name: "test-app".into(),
+            tenant_id: t.id,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let dp = device_profile::create(device_profile::DeviceProfile {
+            name: "test-dp".into(),
+            tenant_id: t.id,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let dev = device::create(device::Device {
+            name: "test-device".into(),
+            dev_eui: EUI64::from_be_bytes([1, 2, 3, 4, 5, 6, 7, 8]),
+            application_id: app.id,
+            device_profile_id: dp.id,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        let conf = Config {
+            server: env::var("TEST_NATS_URL").unwrap(),
+            stream: "chirpstack-test".into(),
+            consumer_name: "chirpstack-test".into(),
+            json: true,
+            ..Default::default()
+        };
+
+        let client = async_nats::connect(conf.server.as_str()).await.unwrap();
+        let mut sub = client
+            .subscribe("application.00000000-0000-0000-0000-000000000000.device.*.event.*")
+            .await
+            .unwrap();
+
+        let i = Integration::new(&conf).await.unwrap();
+
+        // uplink event
+        let pl = integration::UplinkEvent {
+            device_info: Some(integration::DeviceInfo {
+                application_id: Uuid::nil().to_string(),
+                dev_eui: "0102030405060708".to_string(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        i.uplink_event(&HashMap::new(), &pl).await.unwrap();
+
+        let msg = sub.next().await.unwrap();
+        assert_eq!(
+            "application.00000000-0000-0000-0000-000000000000.device.0102030405060708.event.up",
+            msg.subject.to_string()
+        );
+        assert_eq!(serde_json::to_vec(&pl).unwrap(), msg.payload.to_vec());
+
+        // downlink command
+        let down_cmd = integration::DownlinkCommand {
+            id: Uuid::new_v4().to_string(),
+            dev_eui: dev.dev_eui.to_string(),
+            confirmed: false,
+            f_port: 10,
+            data: vec![1, 2, 3],
+            object: None,
+            correlation_id: "".into(),
+        };
+        jetstream::new(client)
+            .publish(
+                format!("application.{}.device.{}.command.down", app.id, dev.dev_eui),
+                serde_json::to_vec(&down_cmd).unwrap().into(),
+            )
+            .await
+            .unwrap()
+            .await
+            .unwrap();
+
+        // give the async consumer some time to process
+        sleep(Duration::from_millis(500)).await;
+
+        let queue_items = device_queue::get_for_dev_eui(&dev.dev_eui).await.unwrap();
+        assert_eq!(1, queue_items.len());
+        assert_eq!(down_cmd.id, queue_items[0].id.to_string());
+        assert_eq!(dev.dev_eui, queue_items[0].dev_eui);
+        assert_eq!(10, queue_items[0].f_port);
+        assert_eq!(vec![1, 2, 3], queue_items[0].data);
+    }
+}
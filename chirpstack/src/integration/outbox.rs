@@ -0,0 +1,275 @@
+use std::collections::HashMap;
+
+use anyhow::{Context, Result};
+use chrono::{Duration, Utc};
+use prometheus_client::encoding::EncodeLabelSet;
+use prometheus_client::metrics::family::Family;
+use prometheus_client::metrics::gauge::Gauge;
+use tokio::time::sleep;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+use super::{for_application_id, Event, Integration};
+use crate::config;
+use crate::helpers::errors::PrintFullError;
+use crate::monitoring::prometheus;
+use crate::storage::{application, fields, integration_outbox};
+
+#[derive(Clone, Hash, PartialEq, Eq, EncodeLabelSet, Debug)]
+struct BacklogLabels {
+    kind: String,
+    state: String,
+}
+
+lazy_static! {
+    static ref BACKLOG_GAUGE: Family<BacklogLabels, Gauge> = {
+        let gauge = Family::<BacklogLabels, Gauge>::default();
+        prometheus::register(
+            "integration_outbox_backlog",
+            "Number of events in the integration outbox",
+            gauge.clone(),
+        );
+        gauge
+    };
+}
+
+// Sends the event to the given application integration. The event is stored in the outbox before
+// it is sent and it is removed once it has been delivered, such that an event is not lost when
+// ChirpStack is stopped while the event is in-flight. When the delivery fails, the event is
+// retried from the outbox.
+pub async fn send(
+    application_id: Uuid,
+    kind: application::IntegrationKind,
+    integration: &(dyn Integration + Sync + Send),
+    vars: &HashMap<String, String>,
+    pl: &Event,
+) -> Result<()> {
+    let id = match enqueue(application_id, kind, vars, pl).await {
+        Ok(v) => Some(v),
+        Err(e) => {
+            error!(application_id = %application_id, kind = %kind, event = pl.name(), error = %e.full(), "Storing event in outbox failed");
+            None
+        }
+    };
+
+    let res = pl.send(integration, vars).await;
+
+    let id = match id {
+        Some(v) => v,
+        None => return res,
+    };
+
+    match res {
+        Ok(_) => {
+            if let Err(e) = integration_outbox::delete(&id).await {
+                warn!(id = %id, application_id = %application_id, kind = %kind, event = pl.name(), error = %e.full(), "Removing delivered event from outbox failed, event might be delivered again");
+            }
+        }
+        Err(err) => {
+            warn!(id = %id, application_id = %application_id, kind = %kind, event = pl.name(), error = %err.full(), "Sending event failed, event will be retried from outbox");
+            integration_outbox::set_failed(&id, &err.full())
+                .await
+                .context("Set outbox item failed")?;
+        }
+    }
+
+    Ok(())
+}
+
+// Stores the event in the outbox. The first retry is scheduled after the lock_duration, such that
+// the event is only retried by the outbox loop when the initial delivery did not complete.
+async fn enqueue(
+    application_id: Uuid,
+    kind: application::IntegrationKind,
+    vars: &HashMap<String, String>,
+    pl: &Event,
+) -> Result<Uuid> {
+    let conf = config::get();
+    let item = integration_outbox::create(integration_outbox::OutboxItem {
+        application_id,
+        kind,
+        event: pl.name().to_string(),
+        vars: fields::KeyValue::new(vars.clone()),
+        payload: pl.encode_to_vec(),
+        next_attempt_at: Utc::now() + Duration::from_std(conf.integration.outbox.lock_duration)?,
+        ..Default::default()
+    })
+    .await?;
+
+    Ok(item.id)
+}
+
+pub async fn retry_loop() {
+    let conf = config::get();
+    if !conf.integration.outbox.enabled {
+        return;
+    }
+
+    info!("Starting integration outbox loop");
+
+    loop {
+        if let Err(err) = retry_batch().await {
+            error!(error = %err.full(), "Integration outbox retry error");
+        }
+
+        if let Err(err) = update_backlog_metrics().await {
+            error!(error = %err.full(), "Update integration outbox metrics error");
+        }
+
+        sleep(conf.integration.outbox.interval).await;
+    }
+}
+
+async fn retry_batch() -> Result<()> {
+    let conf = config::get();
+    let items = integration_outbox::get_pending(conf.integration.outbox.batch_size)
+        .await
+        .context("Get pending outbox items")?;
+
+    for item in &items {
+        match retry(item).await {
+            Ok(_) => {
+                // An error is logged and the next items are retried, the item will be retried
+                // (and delivered again) after the lock_duration.
+                if let Err(e) = integration_outbox::delete(&item.id).await {
+                    error!(id = %item.id, error = %e.full(), "Removing delivered event from outbox failed");
+                }
+            }
+            Err(err) => match integration_outbox::set_failed(&item.id, &err.full()).await {
+                Ok(item) => {
+                    if item.dead_at.is_some() {
+                        warn!(id = %item.id, application_id = %item.application_id, kind = %item.kind, event = %item.event, attempts = item.attempts, error = %err.full(), "Max. attempts reached, event has been dead-lettered");
+                    }
+                }
+                Err(e) => {
+                    error!(id = %item.id, error = %e.full(), "Set outbox item failed");
+                }
+            },
+        }
+    }
+
+    Ok(())
+}
+
+async fn retry(item: &integration_outbox::OutboxItem) -> Result<()> {
+    let pl = Event::decode(&item.event, &item.payload).context("Decode event")?;
+    let app_ints = for_application_id(item.application_id)
+        .await
+        .context("Get integrations for application")?;
+
+    match app_ints.iter().find(|ai| ai.kind == Some(item.kind)) {
+        Some(ai) => {
+            pl.send(ai.integration.as_ref(), &item.vars.into_hashmap())
+                .await
+        }
+        None => {
+            // The integration has been removed, there is nothing to retry.
+            warn!(id = %item.id, application_id = %item.application_id, kind = %item.kind, "Integration no longer exists, removing event from outbox");
+            Ok(())
+        }
+    }
+}
+
+async fn update_backlog_metrics() -> Result<()> {
+    let backlog = integration_outbox::get_backlog().await?;
+
+    BACKLOG_GAUGE.clear();
+    for b in &backlog {
+        BACKLOG_GAUGE
+            .get_or_create(&BacklogLabels {
+                kind: b.kind.to_string(),
+                state: "pending".into(),
+            })
+            .set(b.pending);
+        BACKLOG_GAUGE
+            .get_or_create(&BacklogLabels {
+                kind: b.kind.to_string(),
+                state: "dead".into(),
+            })
+            .set(b.dead);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use crate::integration::http;
+    use crate::storage::{application, tenant};
+    use crate::test;
+    use chirpstack_api::integration;
+    use httpmock::prelude::*;
+
+    #[tokio::test]
+    async fn test_send() {
+        let _guard = test::prepare().await;
+        let server = MockServer::start();
+
+        let t = tenant::create(tenant::Tenant {
+            name: "test-tenant".into(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        let app = application::create(application::Application {
+            tenant_id: t.id,
+            name: "test-app".into(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        let i = http::Integration::new(&application::HttpConfiguration {
+            event_endpoint_url: server.url("/"),
+            json: true,
+            ..Default::default()
+        })
+        .unwrap();
+        let pl = Event::Up(integration::UplinkEvent::default());
+
+        // delivered, the event is removed from the outbox
+        let mut mock = server.mock(|when, then| {
+            when.method(POST).path("/");
+            then.status(200);
+        });
+        send(
+            app.id,
+            application::IntegrationKind::Http,
+            &i,
+            &HashMap::new(),
+            &pl,
+        )
+        .await
+        .unwrap();
+        mock.assert();
+        mock.delete();
+        assert!(integration_outbox::get_backlog().await.unwrap().is_empty());
+
+        // not delivered, the event is kept in the outbox
+        let mut mock = server.mock(|when, then| {
+            when.method(POST).path("/");
+            then.status(500);
+        });
+        send(
+            app.id,
+            application::IntegrationKind::Http,
+            &i,
+            &HashMap::new(),
+            &pl,
+        )
+        .await
+        .unwrap();
+        mock.assert();
+        mock.delete();
+        assert_eq!(
+            vec![integration_outbox::Backlog {
+                kind: application::IntegrationKind::Http,
+                pending: 1,
+                dead: 0,
+            }],
+            integration_outbox::get_backlog().await.unwrap()
+        );
+    }
+}
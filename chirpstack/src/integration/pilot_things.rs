@@ -4,11 +4,10 @@ use std::time::Duration;
 use anyhow::Result;
 use async_trait::async_trait;
 use reqwest::header::{HeaderMap, CONTENT_TYPE};
-use reqwest::Client;
 use serde::Serialize;
 use tracing::{info, trace};
 
-use super::Integration as IntegrationTrait;
+use super::{http_client, Integration as IntegrationTrait};
 use crate::storage::application::PilotThingsConfiguration;
 use chirpstack_api::integration;
 
@@ -45,12 +44,13 @@ impl IntegrationTrait for Integration {
         let pl = UplinkPayload::from_uplink_event(pl);
         let b = serde_json::to_string(&pl)?;
 
-        let client = Client::builder().timeout(self.timeout).build()?;
+        let client = http_client();
         let mut headers = HeaderMap::new();
         headers.insert(CONTENT_TYPE, "application/json".parse().unwrap());
 
         let res = client
             .post(endpoint)
+            .timeout(self.timeout)
             .body(b)
             .query(&[("token", self.token.clone())])
             .headers(headers)
@@ -116,6 +116,14 @@ impl IntegrationTrait for Integration {
     ) -> Result<()> {
         Ok(())
     }
+
+    async fn downlink_event(
+        &self,
+        _vars: &HashMap<String, String>,
+        _pl: &integration::DownlinkEvent,
+    ) -> Result<()> {
+        Ok(())
+    }
 }
 
 #[derive(Serialize)]
@@ -0,0 +1,494 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use futures::TryStreamExt;
+use handlebars::Handlebars;
+use prost::Message;
+use pulsar::consumer::{ConsumerOptions, InitialPosition};
+use pulsar::{producer, Authentication, Consumer, Producer, Pulsar, SubType, TokioExecutor};
+use regex::Regex;
+use serde::Serialize;
+use tokio::sync::Mutex;
+use tokio::time::sleep;
+use tracing::{error, info, trace, warn};
+
+use super::Integration as IntegrationTrait;
+use crate::config::PulsarIntegration as Config;
+use crate::helpers::errors::PrintFullError;
+use chirpstack_api::integration;
+
+pub struct Integration<'a> {
+    templates: Handlebars<'a>,
+    topic: String,
+    json: bool,
+    producer: Mutex<Producer<TokioExecutor>>,
+}
+
+#[derive(Serialize)]
+struct EventKeyContext {
+    pub application_id: String,
+    pub dev_eui: String,
+    pub event: String,
+}
+
+#[derive(Serialize)]
+struct CommandKeyContext {
+    pub application_id: String,
+    pub dev_eui: String,
+    pub command: String,
+}
+
+impl<'a> Integration<'a> {
+    pub async fn new(conf: &Config) -> Result<Integration<'a>> {
+        info!("Initializing Pulsar integration");
+
+        // key templates
+        let mut templates = Handlebars::new();
+        templates.register_escape_fn(handlebars::no_escape);
+        templates.register_template_string("event_key", &conf.event_key)?;
+        templates.register_template_string("command_key", &conf.command_key)?;
+
+        let command_regex = Regex::new(&format!(
+            "^{}$",
+            templates.render(
+                "command_key",
+                &CommandKeyContext {
+                    application_id: r"(?P<application_id>[\w-]+)".to_string(),
+                    dev_eui: r"(?P<dev_eui>[\w]+)".to_string(),
+                    command: r"(?P<command>[\w]+)".to_string(),
+                },
+            )?
+        ))?;
+
+        let mut builder = Pulsar::builder(conf.server.clone(), TokioExecutor);
+        if !conf.token.is_empty() {
+            builder = builder.with_auth(Authentication {
+                name: "token".to_string(),
+                data: conf.token.clone().into_bytes(),
+            });
+        }
+
+        info!(server = %conf.server, "Connecting to Pulsar");
+        let pulsar: Pulsar<TokioExecutor> = builder.build().await?;
+
+        let producer = pulsar
+            .producer()
+            .with_topic(&conf.event_topic)
+            .build()
+            .await?;
+
+        // Command consumer loop
+        tokio::spawn({
+            let pulsar = pulsar.clone();
+            let conf = conf.clone();
+
+            async move {
+                loop {
+                    if let Err(e) = consume_commands(&pulsar, &conf, &command_regex).await {
+                        error!(error = %e.full(), "Pulsar command consumer error");
+                    }
+
+                    sleep(Duration::from_secs(1)).await;
+                }
+            }
+        });
+
+        Ok(Integration {
+            templates,
+            topic: conf.event_topic.clone(),
+            json: conf.json,
+            producer: Mutex::new(producer),
+        })
+    }
+
+    async fn publish_event(&self, event: &str, event_key: String, b: Vec<u8>) -> Result<()> {
+        info!(topic = %self.topic, event_key = %event_key, "Publishing event");
+
+        // The producer lock is only held while queueing the message, not while waiting for the
+        // receipt.
+        let receipt = {
+            let mut producer = self.producer.lock().await;
+            producer
+                .send_non_blocking(producer::Message {
+                    payload: b,
+                    partition_key: Some(event_key),
+                    properties: [("event".to_string(), event.to_string())].into(),
+                    ..Default::default()
+                })
+                .await?
+        };
+        receipt.await?;
+
+        Ok(())
+    }
+
+    fn get_event_key(&self, application_id: &str, dev_eui: &str, event: &str) -> Result<String> {
+        Ok(self.templates.render(
+            "event_key",
+            &EventKeyContext {
+                application_id: application_id.to_string(),
+                dev_eui: dev_eui.to_string(),
+                event: event.to_string(),
+            },
+        )?)
+    }
+}
+
+#[async_trait]
+impl IntegrationTrait for Integration<'_> {
+    async fn uplink_event(
+        &self,
+        _vars: &HashMap<String, String>,
+        pl: &integration::UplinkEvent,
+    ) -> Result<()> {
+        let di = pl
+            .device_info
+            .as_ref()
+            .ok_or_else(|| anyhow!("device_info is None"))?;
+
+        let key = self.get_event_key(&di.application_id, &di.dev_eui, "up")?;
+        let b = match self.json {
+            true => serde_json::to_vec(&pl)?,
+            false => pl.encode_to_vec(),
+        };
+
+        self.publish_event("up", key, b).await
+    }
+
+    async fn join_event(
+        &self,
+        _vars: &HashMap<String, String>,
+        pl: &integration::JoinEvent,
+    ) -> Result<()> {
+        let di = pl
+            .device_info
+            .as_ref()
+            .ok_or_else(|| anyhow!("device_info is None"))?;
+
+        let key = self.get_event_key(&di.application_id, &di.dev_eui, "join")?;
+        let b = match self.json {
+            true => serde_json::to_vec(&pl)?,
+            false => pl.encode_to_vec(),
+        };
+
+        self.publish_event("join", key, b).await
+    }
+
+    async fn ack_event(
+        &self,
+        _vars: &HashMap<String, String>,
+        pl: &integration::AckEvent,
+    ) -> Result<()> {
+        let di = pl
+            .device_info
+            .as_ref()
+            .ok_or_else(|| anyhow!("device_info is None"))?;
+
+        let key = self.get_event_key(&di.application_id, &di.dev_eui, "ack")?;
+        let b = match self.json {
+            true => serde_json::to_vec(&pl)?,
+            false => pl.encode_to_vec(),
+        };
+
+        self.publish_event("ack", key, b).await
+    }
+
+    async fn txack_event(
+        &self,
+        _vars: &HashMap<String, String>,
+        pl: &integration::TxAckEvent,
+    ) -> Result<()> {
+        let di = pl
+            .device_info
+            .as_ref()
+            .ok_or_else(|| anyhow!("device_info is None"))?;
+
+        let key = self.get_event_key(&di.application_id, &di.dev_eui, "txack")?;
+        let b = match self.json {
+            true => serde_json::to_vec(&pl)?,
+            false => pl.encode_to_vec(),
+        };
+
+        self.publish_event("txack", key, b).await
+    }
+
+    async fn log_event(
+        &self,
+        _vars: &HashMap<String, String>,
+        pl: &integration::LogEvent,
+    ) -> Result<()> {
+        let di = pl
+            .device_info
+            .as_ref()
+            .ok_or_else(|| anyhow!("device_info is None"))?;
+
+        let key = self.get_event_key(&di.application_id, &di.dev_eui, "log")?;
+        let b = match self.json {
+            true => serde_json::to_vec(&pl)?,
+            false => pl.encode_to_vec(),
+        };
+
+        self.publish_event("log", key, b).await
+    }
+
+    async fn status_event(
+        &self,
+        _vars: &HashMap<String, String>,
+        pl: &integration::StatusEvent,
+    ) -> Result<()> {
+        let di = pl
+            .device_info
+            .as_ref()
+            .ok_or_else(|| anyhow!("device_info is None"))?;
+
+        let key = self.get_event_key(&di.application_id, &di.dev_eui, "status")?;
+        let b = match self.json {
+            true => serde_json::to_vec(&pl)?,
+            false => pl.encode_to_vec(),
+        };
+
+        self.publish_event("status", key, b).await
+    }
+
+    async fn location_event(
+        &self,
+        _vars: &HashMap<String, String>,
+        pl: &integration::LocationEvent,
+    ) -> Result<()> {
+        let di = pl
+            .device_info
+            .as_ref()
+            .ok_or_else(|| anyhow!("device_info is None"))?;
+
+        let key = self.get_event_key(&di.application_id, &di.dev_eui, "location")?;
+        let b = match self.json {
+            true => serde_json::to_vec(&pl)?,
+            false => pl.encode_to_vec(),
+        };
+
+        self.publish_event("location", key, b).await
+    }
+
+    async fn integration_event(
+        &self,
+        _vars: &HashMap<String, String>,
+        pl: &integration::IntegrationEvent,
+    ) -> Result<()> {
+        let di = pl
+            .device_info
+            .as_ref()
+            .ok_or_else(|| anyhow!("device_info is None"))?;
+
+        let key = self.get_event_key(&di.application_id, &di.dev_eui, "integration")?;
+        let b = match self.json {
+            true => serde_json::to_vec(&pl)?,
+            false => pl.encode_to_vec(),
+        };
+
+        self.publish_event("integration", key, b).await
+    }
+
+    async fn downlink_event(
+        &self,
+        _vars: &HashMap<String, String>,
+        pl: &integration::DownlinkEvent,
+    ) -> Result<()> {
+        let di = pl
+            .device_info
+            .as_ref()
+            .ok_or_else(|| anyhow!("device_info is None"))?;
+
+        let key = self.get_event_key(&di.application_id, &di.dev_eui, "downlink")?;
+        let b = match self.json {
+            true => serde_json::to_vec(&pl)?,
+            false => pl.encode_to_vec(),
+        };
+
+        self.publish_event("downlink", key, b).await
+    }
+}
+
+// Consumes the commands using a shared subscription, such that ChirpStack instances using the
+// same subscription name share the consumption of the commands.
+async fn consume_commands(
+    pulsar: &Pulsar<TokioExecutor>,
+    conf: &Config,
+    command_regex: &Regex,
+) -> Result<()> {
+    let mut consumer: Consumer<Vec<u8>, TokioExecutor> = pulsar
+        .consumer()
+        .with_topic(&conf.command_topic)
+        .with_subscription_type(SubType::Shared)
+        .with_subscription(&conf.subscription_name)
+        // Commands published before the subscription was created are consumed as well.
+        .with_options(ConsumerOptions::default().with_initial_position(InitialPosition::Earliest))
+        .build()
+        .await?;
+
+    info!(topic = %conf.command_topic, subscription = %conf.subscription_name, "Consuming commands");
+
+    while let Some(msg) = consumer.try_next().await? {
+        let key = msg.key().unwrap_or_default();
+        trace!(key = %key, "Command received");
+
+        match command_regex.captures(&key) {
+            Some(caps) => {
+                info!(key = %key, "Command received for device");
+
+                if let Err(e) = super::handle_command(
+                    caps["application_id"].to_string(),
+                    caps["dev_eui"].to_string(),
+                    &caps["command"],
+                    conf.json,
+                    &msg.payload.data,
+                ) {
+                    warn!(key = %key, error = %e.full(), "Processing command error");
+                }
+            }
+            None => {
+                warn!(key = %key, "Error parsing command key (regex captures returned None)");
+            }
+        }
+
+        // Invalid commands are acknowledged as well, as a redelivery would fail again.
+        consumer.ack(&msg).await?;
+    }
+
+    Err(anyhow!("Command consumer stream closed"))
+}
+
+#[cfg(all(test, feature = "test-integration-pulsar"))]
+pub mod test {
+    use std::env;
+
+    use super::*;
+    use crate::storage::{application, device, device_profile, device_queue, tenant};
+    use crate::test;
+    use lrwn::EUI64;
+    use uuid::Uuid;
+
+    #[tokio::test]
+    async fn test_pulsar() {
+        dotenv::dotenv().ok();
+        dotenv::from_filename(".env.local").ok();
+
+        let _guard = test::prepare().await;
+
+        // setup base objects
+        let t = tenant::create(tenant::Tenant {
+            name: "test-tenant".into(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let app = application::create(application::Application {
+            name: "test-app".into(),
+            tenant_id: t.id,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let dp = device_profile::create(device_profile::DeviceProfile {
+            name: "test-dp".into(),
+            tenant_id: t.id,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let dev = device::create(device::Device {
+            name: "test-device".into(),
+            dev_eui: EUI64::from_be_bytes([1, 2, 3, 4, 5, 6, 7, 8]),
+            application_id: app.id,
+            device_profile_id: dp.id,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        let conf = Config {
+            server: env::var("TEST_PULSAR_URL").unwrap(),
+            event_topic: format!("persistent://public/default/events-{}", Uuid::new_v4()),
+            command_topic: format!("persistent://public/default/commands-{}", Uuid::new_v4()),
+            json: true,
+            ..Default::default()
+        };
+
+        let pulsar: Pulsar<TokioExecutor> = Pulsar::builder(conf.server.clone(), TokioExecutor)
+            .build()
+            .await
+            .unwrap();
+        let mut consumer: Consumer<Vec<u8>, TokioExecutor> = pulsar
+            .consumer()
+            .with_topic(&conf.event_topic)
+            .with_subscription("test")
+            .build()
+            .await
+            .unwrap();
+
+        let i = Integration::new(&conf).await.unwrap();
+
+        // uplink event
+        let pl = integration::UplinkEvent {
+            device_info: Some(integration::DeviceInfo {
+                application_id: Uuid::nil().to_string(),
+                dev_eui: "0102030405060708".to_string(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        i.uplink_event(&HashMap::new(), &pl).await.unwrap();
+
+        let msg = consumer.try_next().await.unwrap().unwrap();
+        consumer.ack(&msg).await.unwrap();
+        assert_eq!(
+            Some(
+                "application.00000000-0000-0000-0000-000000000000.device.0102030405060708.event.up"
+                    .to_string()
+            ),
+            msg.key()
+        );
+        assert_eq!(serde_json::to_vec(&pl).unwrap(), msg.payload.data);
+
+        // downlink command
+        let down_cmd = integration::DownlinkCommand {
+            id: Uuid::new_v4().to_string(),
+            dev_eui: dev.dev_eui.to_string(),
+            confirmed: false,
+            f_port: 10,
+            data: vec![1, 2, 3],
+            object: None,
+            correlation_id: "".into(),
+        };
+        let mut producer = pulsar
+            .producer()
+            .with_topic(&conf.command_topic)
+            .build()
+            .await
+            .unwrap();
+        producer
+            .send_non_blocking(producer::Message {
+                payload: serde_json::to_vec(&down_cmd).unwrap(),
+                partition_key: Some(format!(
+                    "application.{}.device.{}.command.down",
+                    app.id, dev.dev_eui
+                )),
+                ..Default::default()
+            })
+            .await
+            .unwrap()
+            .await
+            .unwrap();
+
+        // give the async consumer some time to process
+        sleep(Duration::from_millis(500)).await;
+
+        let queue_items = device_queue::get_for_dev_eui(&dev.dev_eui).await.unwrap();
+        assert_eq!(1, queue_items.len());
+        assert_eq!(down_cmd.id, queue_items[0].id.to_string());
+        assert_eq!(dev.dev_eui, queue_items[0].dev_eui);
+        assert_eq!(10, queue_items[0].f_port);
+        assert_eq!(vec![1, 2, 3], queue_items[0].data);
+    }
+}
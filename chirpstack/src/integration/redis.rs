@@ -123,6 +123,19 @@ impl IntegrationTrait for Integration {
         let b = pl.encode_to_vec();
         stream::event::log_event_for_device("integration", &dev_info.dev_eui, &b).await
     }
+
+    async fn downlink_event(
+        &self,
+        _vars: &HashMap<String, String>,
+        pl: &integration::DownlinkEvent,
+    ) -> Result<()> {
+        let dev_info = pl
+            .device_info
+            .as_ref()
+            .ok_or_else(|| anyhow!("device_info is None"))?;
+        let b = pl.encode_to_vec();
+        stream::event::log_event_for_device("downlink", &dev_info.dev_eui, &b).await
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,1139 @@
+use std::collections::{BTreeMap, HashMap};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, SecondsFormat, Utc};
+use tokio::sync::Mutex;
+use tokio::time::sleep;
+use tokio_postgres::types::ToSql;
+use tracing::{error, info, trace, warn};
+use uuid::Uuid;
+
+use super::{http_client, Integration as IntegrationTrait};
+use crate::codec;
+use crate::config::{
+    SqlIntegration as Config, SqlIntegrationBackend, SqlIntegrationClickHouse,
+    SqlIntegrationTimescaleDb,
+};
+use crate::helpers::errors::PrintFullError;
+use crate::helpers::tls::get_root_certs;
+use crate::storage::{device_profile, fields};
+use chirpstack_api::integration;
+
+// Columns which are included in every table. Measurement columns using one of these names are
+// prefixed with "m_".
+const FIXED_COLUMNS: [&str; 8] = [
+    "time",
+    "tenant_id",
+    "application_id",
+    "dev_eui",
+    "device_name",
+    "f_cnt",
+    "f_port",
+    "dr",
+];
+
+// Max. length of a PostgreSQL identifier.
+const MAX_IDENTIFIER_LEN: usize = 63;
+
+// Max. number of batches that are kept in the buffer while the inserts are failing.
+const MAX_BUFFERED_BATCHES: usize = 100;
+
+// Max. age of the cached device-profile measurements.
+const MEASUREMENTS_CACHE_TTL: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ColumnKind {
+    Float,
+    Text,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Float(f64),
+    Text(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Row {
+    table: String,
+    time: DateTime<Utc>,
+    tenant_id: Uuid,
+    application_id: Uuid,
+    dev_eui: String,
+    device_name: String,
+    f_cnt: u32,
+    f_port: u32,
+    dr: u32,
+    columns: BTreeMap<String, (ColumnKind, Option<Value>)>,
+}
+
+#[async_trait]
+trait Backend {
+    async fn create_table(&self, table: &str) -> Result<()>;
+    // Returns the existing (measurement) columns of the given table.
+    async fn get_columns(&self, table: &str) -> Result<HashMap<String, ColumnKind>>;
+    async fn add_column(&self, table: &str, column: &str, kind: ColumnKind) -> Result<()>;
+    async fn insert(
+        &self,
+        table: &str,
+        columns: &[(String, ColumnKind)],
+        rows: &[Row],
+    ) -> Result<()>;
+}
+
+struct Inner {
+    table_prefix: String,
+    batch_size: usize,
+    backend: Box<dyn Backend + Sync + Send>,
+    buffer: Mutex<Vec<Row>>,
+
+    // Known columns per table. This lock is also held during a flush, such that flushes are
+    // serialized.
+    schema: Mutex<HashMap<String, HashMap<String, ColumnKind>>>,
+
+    // Device-profile measurements, such that the device-profile is not fetched on every uplink.
+    measurements: Mutex<HashMap<Uuid, (Instant, fields::Measurements)>>,
+}
+
+pub struct Integration {
+    inner: Arc<Inner>,
+}
+
+impl Integration {
+    pub async fn new(conf: &Config) -> Result<Integration> {
+        info!(backend = ?conf.backend, "Initializing SQL integration");
+
+        let backend: Box<dyn Backend + Sync + Send> = match conf.backend {
+            SqlIntegrationBackend::TimescaleDb => {
+                Box::new(TimescaleDb::new(&conf.timescaledb).await?)
+            }
+            SqlIntegrationBackend::ClickHouse => Box::new(ClickHouse::new(&conf.clickhouse)),
+        };
+
+        let i = Integration {
+            inner: Arc::new(Inner {
+                table_prefix: identifier(&conf.table_prefix),
+                batch_size: conf.batch_size,
+                backend,
+                buffer: Mutex::new(Vec::new()),
+                schema: Mutex::new(HashMap::new()),
+                measurements: Mutex::new(HashMap::new()),
+            }),
+        };
+
+        let inner = i.inner.clone();
+        let batch_interval = conf.batch_interval;
+        tokio::spawn(async move {
+            loop {
+                sleep(batch_interval).await;
+
+                if let Err(e) = inner.flush().await {
+                    error!(error = %e.full(), "Flushing SQL integration batch error");
+                }
+            }
+        });
+
+        Ok(i)
+    }
+}
+
+impl Inner {
+    async fn push(&self, row: Row) -> Result<()> {
+        let flush = {
+            let mut buffer = self.buffer.lock().await;
+            buffer.push(row);
+            buffer.len() >= self.batch_size
+        };
+
+        if flush {
+            self.flush().await?;
+        }
+
+        Ok(())
+    }
+
+    // Inserts the buffered rows. The rows of a table that failed to be inserted are put back
+    // into the buffer, such that these are retried on the next flush.
+    async fn flush(&self) -> Result<()> {
+        let mut schema = self.schema.lock().await;
+        let rows: Vec<Row> = std::mem::take(&mut *self.buffer.lock().await);
+        if rows.is_empty() {
+            return Ok(());
+        }
+
+        let mut tables: BTreeMap<String, Vec<Row>> = BTreeMap::new();
+        for row in rows {
+            tables.entry(row.table.clone()).or_default().push(row);
+        }
+
+        let mut failed: Vec<Row> = Vec::new();
+        for (table, rows) in tables {
+            if let Err(e) = self.flush_table(&mut schema, &table, &rows).await {
+                error!(table = %table, rows = rows.len(), error = %e.full(), "Inserting measurements failed");
+                failed.extend(rows);
+            }
+        }
+
+        if !failed.is_empty() {
+            let count = failed.len();
+            self.requeue(failed).await;
+            return Err(anyhow!("Inserting {} measurements failed", count));
+        }
+
+        Ok(())
+    }
+
+    async fn flush_table(
+        &self,
+        schema: &mut HashMap<String, HashMap<String, ColumnKind>>,
+        table: &str,
+        rows: &[Row],
+    ) -> Result<()> {
+        let mut columns: BTreeMap<String, ColumnKind> = BTreeMap::new();
+        for row in rows {
+            for (k, (kind, _)) in &row.columns {
+                columns.insert(k.clone(), *kind);
+            }
+        }
+
+        // The columns of an existing table are read from the database, as the column kinds
+        // can not be derived from the measurements (see below).
+        if !schema.contains_key(table) {
+            self.backend
+                .create_table(table)
+                .await
+                .context("Create table")?;
+            let existing = self
+                .backend
+                .get_columns(table)
+                .await
+                .context("Get columns")?;
+            schema.insert(table.to_string(), existing);
+        }
+        let known = schema.get_mut(table).unwrap();
+
+        // Columns are only added, never removed or altered. If the kind of a measurement has
+        // changed, the value is stored using the kind of the existing column.
+        for (column, kind) in &columns {
+            if !known.contains_key(column) {
+                trace!(table = %table, column = %column, "Adding column");
+                self.backend
+                    .add_column(table, column, *kind)
+                    .await
+                    .context("Add column")?;
+                known.insert(column.clone(), *kind);
+            }
+        }
+
+        let columns: Vec<(String, ColumnKind)> = columns
+            .keys()
+            .map(|k| (k.clone(), *known.get(k).unwrap()))
+            .collect();
+
+        self.backend
+            .insert(table, &columns, rows)
+            .await
+            .context("Insert rows")?;
+
+        info!(table = %table, rows = rows.len(), "Measurements inserted");
+
+        Ok(())
+    }
+
+    // Puts the given rows back into the buffer, in front of the rows that were pushed in the
+    // meantime. When the buffer exceeds MAX_BUFFERED_BATCHES, the oldest rows are dropped.
+    async fn requeue(&self, mut rows: Vec<Row>) {
+        let mut buffer = self.buffer.lock().await;
+        rows.append(&mut buffer);
+
+        let max_rows = self.batch_size.max(1) * MAX_BUFFERED_BATCHES;
+        if rows.len() > max_rows {
+            let dropped = rows.len() - max_rows;
+            rows.drain(..dropped);
+            warn!(
+                rows = dropped,
+                "SQL integration buffer is full, dropping oldest measurements"
+            );
+        }
+
+        *buffer = rows;
+    }
+
+    async fn get_measurements(&self, device_profile_id: &Uuid) -> Result<fields::Measurements> {
+        let mut cache = self.measurements.lock().await;
+        if let Some((created_at, measurements)) = cache.get(device_profile_id) {
+            if created_at.elapsed() < MEASUREMENTS_CACHE_TTL {
+                return Ok(measurements.clone());
+            }
+        }
+
+        let dp = device_profile::get(device_profile_id).await?;
+        cache.retain(|_, (created_at, _)| created_at.elapsed() < MEASUREMENTS_CACHE_TTL);
+        cache.insert(
+            *device_profile_id,
+            (Instant::now(), dp.measurements.clone()),
+        );
+
+        Ok(dp.measurements)
+    }
+}
+
+#[async_trait]
+impl IntegrationTrait for Integration {
+    async fn uplink_event(
+        &self,
+        _vars: &HashMap<String, String>,
+        pl: &integration::UplinkEvent,
+    ) -> Result<()> {
+        let obj = match &pl.object {
+            Some(v) => v,
+            None => {
+                return Ok(());
+            }
+        };
+
+        let di = pl.device_info.as_ref().unwrap();
+        let device_profile_id = Uuid::from_str(&di.device_profile_id)?;
+        let measurements = self.inner.get_measurements(&device_profile_id).await?;
+
+        let row = Row {
+            table: format!("{}{}", self.inner.table_prefix, device_profile_id.simple()),
+            time: pl
+                .time
+                .as_ref()
+                .unwrap()
+                .clone()
+                .try_into()
+                .map_err(anyhow::Error::msg)?,
+            tenant_id: Uuid::from_str(&di.tenant_id)?,
+            application_id: Uuid::from_str(&di.application_id)?,
+            dev_eui: di.dev_eui.clone(),
+            device_name: di.device_name.clone(),
+            f_cnt: pl.f_cnt,
+            f_port: pl.f_port,
+            dr: pl.dr,
+            columns: get_columns(&measurements, &codec::get_measurements(obj)),
+        };
+
+        self.inner.push(row).await
+    }
+
+    async fn join_event(
+        &self,
+        _vars: &HashMap<String, String>,
+        _pl: &integration::JoinEvent,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    async fn ack_event(
+        &self,
+        _vars: &HashMap<String, String>,
+        _pl: &integration::AckEvent,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    async fn txack_event(
+        &self,
+        _vars: &HashMap<String, String>,
+        _pl: &integration::TxAckEvent,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    async fn log_event(
+        &self,
+        _vars: &HashMap<String, String>,
+        _pl: &integration::LogEvent,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    async fn status_event(
+        &self,
+        _vars: &HashMap<String, String>,
+        _pl: &integration::StatusEvent,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    async fn location_event(
+        &self,
+        _vars: &HashMap<String, String>,
+        _pl: &integration::LocationEvent,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    async fn integration_event(
+        &self,
+        _vars: &HashMap<String, String>,
+        _pl: &integration::IntegrationEvent,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    async fn downlink_event(
+        &self,
+        _vars: &HashMap<String, String>,
+        _pl: &integration::DownlinkEvent,
+    ) -> Result<()> {
+        Ok(())
+    }
+}
+
+struct TimescaleDb {
+    dsn: String,
+    ca_cert: String,
+    client: Mutex<Option<tokio_postgres::Client>>,
+}
+
+impl TimescaleDb {
+    async fn new(conf: &SqlIntegrationTimescaleDb) -> Result<TimescaleDb> {
+        let b = TimescaleDb {
+            dsn: conf.dsn.clone(),
+            ca_cert: conf.ca_cert.clone(),
+            client: Mutex::new(None),
+        };
+
+        // Validate the configuration by connecting once.
+        b.execute("select 1").await?;
+
+        Ok(b)
+    }
+
+    async fn connect(&self) -> Result<tokio_postgres::Client> {
+        let root_certs = get_root_certs(if self.ca_cert.is_empty() {
+            None
+        } else {
+            Some(self.ca_cert.clone())
+        })?;
+        let rustls_config = rustls::ClientConfig::builder()
+            .with_root_certificates(root_certs)
+            .with_no_client_auth();
+        let tls = tokio_postgres_rustls::MakeRustlsConnect::new(rustls_config);
+        let (client, conn) = tokio_postgres::connect(&self.dsn, tls).await?;
+        tokio::spawn(async move {
+            if let Err(e) = conn.await {
+                error!(error = %e, "TimescaleDB connection error");
+            }
+        });
+
+        Ok(client)
+    }
+
+    async fn execute(&self, sql: &str) -> Result<()> {
+        self.execute_params(sql, &[]).await
+    }
+
+    async fn execute_params(&self, sql: &str, params: &[&(dyn ToSql + Sync)]) -> Result<()> {
+        let mut client = self.client.lock().await;
+        if client.as_ref().map(|c| c.is_closed()).unwrap_or(true) {
+            *client = Some(self.connect().await.context("Connect to TimescaleDB")?);
+        }
+
+        if params.is_empty() {
+            client.as_ref().unwrap().batch_execute(sql).await?;
+        } else {
+            client.as_ref().unwrap().execute(sql, params).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn query(
+        &self,
+        sql: &str,
+        params: &[&(dyn ToSql + Sync)],
+    ) -> Result<Vec<tokio_postgres::Row>> {
+        let mut client = self.client.lock().await;
+        if client.as_ref().map(|c| c.is_closed()).unwrap_or(true) {
+            *client = Some(self.connect().await.context("Connect to TimescaleDB")?);
+        }
+
+        Ok(client.as_ref().unwrap().query(sql, params).await?)
+    }
+}
+
+#[async_trait]
+impl Backend for TimescaleDb {
+    async fn create_table(&self, table: &str) -> Result<()> {
+        self.execute(&timescaledb_create_table(table)).await
+    }
+
+    async fn get_columns(&self, table: &str) -> Result<HashMap<String, ColumnKind>> {
+        let rows = self
+            .query(
+                r#"select column_name::text, data_type::text
+from information_schema.columns
+where table_schema = current_schema() and table_name = $1"#,
+                &[&table],
+            )
+            .await?;
+
+        Ok(rows
+            .iter()
+            .filter_map(|r| {
+                let name: String = r.get(0);
+                let data_type: String = r.get(1);
+                column_kind(&name, &data_type).map(|kind| (name, kind))
+            })
+            .collect())
+    }
+
+    async fn add_column(&self, table: &str, column: &str, kind: ColumnKind) -> Result<()> {
+        self.execute(&format!(
+            r#"alter table "{}" add column if not exists "{}" {}"#,
+            table,
+            column,
+            match kind {
+                ColumnKind::Float => "double precision",
+                ColumnKind::Text => "text",
+            }
+        ))
+        .await
+    }
+
+    async fn insert(
+        &self,
+        table: &str,
+        columns: &[(String, ColumnKind)],
+        rows: &[Row],
+    ) -> Result<()> {
+        // PostgreSQL supports max. 65535 parameters per statement.
+        let params_per_row = FIXED_COLUMNS.len() + columns.len();
+        for rows in rows.chunks(u16::MAX as usize / params_per_row) {
+            let mut params: Vec<Box<dyn ToSql + Sync + Send>> = Vec::new();
+            let mut values: Vec<String> = Vec::new();
+
+            for row in rows {
+                let mut placeholders: Vec<String> = Vec::new();
+                let mut add = |p: Box<dyn ToSql + Sync + Send>, cast: &str| {
+                    params.push(p);
+                    placeholders.push(format!("${}{}", params.len(), cast));
+                };
+
+                add(Box::new(SystemTime::from(row.time)), "");
+                add(Box::new(row.tenant_id.to_string()), "::text::uuid");
+                add(Box::new(row.application_id.to_string()), "::text::uuid");
+                add(Box::new(row.dev_eui.clone()), "");
+                add(Box::new(row.device_name.clone()), "");
+                add(Box::new(row.f_cnt as i64), "");
+                add(Box::new(row.f_port as i16), "");
+                add(Box::new(row.dr as i16), "");
+
+                for (column, kind) in columns {
+                    let v = row.columns.get(column).and_then(|(_, v)| v.as_ref());
+                    match kind {
+                        ColumnKind::Float => add(Box::new(v.and_then(value_to_f64)), ""),
+                        ColumnKind::Text => add(Box::new(v.map(value_to_string)), ""),
+                    }
+                }
+
+                values.push(format!("({})", placeholders.join(", ")));
+            }
+
+            let sql = format!(
+                r#"insert into "{}" ({}) values {}"#,
+                table,
+                column_names(columns),
+                values.join(", ")
+            );
+            let params: Vec<&(dyn ToSql + Sync)> = params
+                .iter()
+                .map(|p| p.as_ref() as &(dyn ToSql + Sync))
+                .collect();
+            self.execute_params(&sql, &params).await?;
+        }
+
+        Ok(())
+    }
+}
+
+struct ClickHouse {
+    url: String,
+    database: String,
+    username: String,
+    password: String,
+}
+
+impl ClickHouse {
+    fn new(conf: &SqlIntegrationClickHouse) -> ClickHouse {
+        ClickHouse {
+            url: conf.url.clone(),
+            database: conf.database.clone(),
+            username: conf.username.clone(),
+            password: conf.password.clone(),
+        }
+    }
+
+    async fn post(&self, query: &[(&str, &str)], body: String) -> Result<()> {
+        self.post_text(query, body).await.map(|_| ())
+    }
+
+    // Posts the given query and returns the response body.
+    async fn post_text(&self, query: &[(&str, &str)], body: String) -> Result<String> {
+        let mut req = http_client()
+            .post(&self.url)
+            .query(&[("database", self.database.as_str())])
+            .query(query)
+            .body(body);
+
+        if !self.username.is_empty() {
+            req = req
+                .header("X-ClickHouse-User", &self.username)
+                .header("X-ClickHouse-Key", &self.password);
+        }
+
+        let res = req.send().await?;
+        if let Err(e) = res.error_for_status_ref() {
+            let body = res.text().await.unwrap_or_default();
+            return Err(anyhow!("{}: {}", e, body.trim()));
+        }
+
+        Ok(res.text().await?)
+    }
+}
+
+#[async_trait]
+impl Backend for ClickHouse {
+    async fn create_table(&self, table: &str) -> Result<()> {
+        self.post(&[], clickhouse_create_table(table)).await
+    }
+
+    async fn get_columns(&self, table: &str) -> Result<HashMap<String, ColumnKind>> {
+        let body = self.post_text(&[], clickhouse_get_columns(table)).await?;
+
+        let mut out = HashMap::new();
+        for line in body.lines().filter(|l| !l.trim().is_empty()) {
+            let v: serde_json::Value = serde_json::from_str(line)?;
+            let name = v["name"].as_str().unwrap_or_default();
+            let data_type = v["type"].as_str().unwrap_or_default();
+            if let Some(kind) = column_kind(name, data_type) {
+                out.insert(name.to_string(), kind);
+            }
+        }
+
+        Ok(out)
+    }
+
+    async fn add_column(&self, table: &str, column: &str, kind: ColumnKind) -> Result<()> {
+        self.post(
+            &[],
+            format!(
+                r#"alter table "{}" add column if not exists "{}" {}"#,
+                table,
+                column,
+                match kind {
+                    ColumnKind::Float => "Nullable(Float64)",
+                    ColumnKind::Text => "Nullable(String)",
+                }
+            ),
+        )
+        .await
+    }
+
+    async fn insert(
+        &self,
+        table: &str,
+        columns: &[(String, ColumnKind)],
+        rows: &[Row],
+    ) -> Result<()> {
+        let query = format!(
+            r#"insert into "{}" ({}) format JSONEachRow"#,
+            table,
+            column_names(columns)
+        );
+
+        let mut body: Vec<String> = Vec::new();
+        for row in rows {
+            let mut m = serde_json::Map::new();
+            m.insert(
+                "time".into(),
+                row.time.to_rfc3339_opts(SecondsFormat::Millis, true).into(),
+            );
+            m.insert("tenant_id".into(), row.tenant_id.to_string().into());
+            m.insert(
+                "application_id".into(),
+                row.application_id.to_string().into(),
+            );
+            m.insert("dev_eui".into(), row.dev_eui.clone().into());
+            m.insert("device_name".into(), row.device_name.clone().into());
+            m.insert("f_cnt".into(), row.f_cnt.into());
+            m.insert("f_port".into(), row.f_port.into());
+            m.insert("dr".into(), row.dr.into());
+
+            for (column, kind) in columns {
+                let v = row.columns.get(column).and_then(|(_, v)| v.as_ref());
+                m.insert(
+                    column.clone(),
+                    match kind {
+                        ColumnKind::Float => v.and_then(value_to_f64).into(),
+                        ColumnKind::Text => v.map(value_to_string).into(),
+                    },
+                );
+            }
+
+            body.push(serde_json::to_string(&m)?);
+        }
+
+        self.post(
+            &[
+                ("query", query.as_str()),
+                ("date_time_input_format", "best_effort"),
+            ],
+            body.join("\n"),
+        )
+        .await
+    }
+}
+
+fn timescaledb_create_table(table: &str) -> String {
+    format!(
+        r#"create table if not exists "{table}" (
+    time timestamp with time zone not null,
+    tenant_id uuid not null,
+    application_id uuid not null,
+    dev_eui varchar(16) not null,
+    device_name varchar(100) not null,
+    f_cnt bigint not null,
+    f_port smallint not null,
+    dr smallint not null
+);
+select create_hypertable('"{table}"', 'time', if_not_exists => true);
+create index if not exists "idx_{table}_dev_eui_time" on "{table}" (dev_eui, time desc);"#
+    )
+}
+
+fn clickhouse_create_table(table: &str) -> String {
+    format!(
+        r#"create table if not exists "{table}" (
+    time DateTime64(3, 'UTC'),
+    tenant_id UUID,
+    application_id UUID,
+    dev_eui String,
+    device_name String,
+    f_cnt UInt32,
+    f_port UInt8,
+    dr UInt8
+) engine = MergeTree order by (dev_eui, time)"#
+    )
+}
+
+fn clickhouse_get_columns(table: &str) -> String {
+    format!(
+        r#"select name, type
+from system.columns
+where database = currentDatabase() and table = '{table}'
+format JSONEachRow"#
+    )
+}
+
+// Returns the kind of the given measurement column, based on its database type. None is returned
+// for the fixed columns and unknown types.
+fn column_kind(name: &str, data_type: &str) -> Option<ColumnKind> {
+    if FIXED_COLUMNS.contains(&name) {
+        return None;
+    }
+
+    match data_type {
+        "double precision" | "Float64" | "Nullable(Float64)" => Some(ColumnKind::Float),
+        "text" | "String" | "Nullable(String)" => Some(ColumnKind::Text),
+        _ => None,
+    }
+}
+
+fn column_names(columns: &[(String, ColumnKind)]) -> String {
+    FIXED_COLUMNS
+        .iter()
+        .map(|c| c.to_string())
+        .chain(columns.iter().map(|(c, _)| c.clone()))
+        .map(|c| format!(r#""{}""#, c))
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+// Returns the typed measurement columns for the given (flattened) object, based on the
+// device-profile measurements.
+fn get_columns(
+    measurements: &fields::Measurements,
+    values: &HashMap<String, pbjson_types::value::Kind>,
+) -> BTreeMap<String, (ColumnKind, Option<Value>)> {
+    let mut out = BTreeMap::new();
+
+    for (k, m) in measurements.iter() {
+        let kind = match m.kind {
+            fields::MeasurementKind::UNKNOWN => continue,
+            fields::MeasurementKind::STRING => ColumnKind::Text,
+            fields::MeasurementKind::COUNTER
+            | fields::MeasurementKind::ABSOLUTE
+            | fields::MeasurementKind::GAUGE => ColumnKind::Float,
+        };
+
+        let v = values.get(k).and_then(|v| match v {
+            pbjson_types::value::Kind::NumberValue(v) => Some(Value::Float(*v)),
+            pbjson_types::value::Kind::StringValue(v) => Some(Value::Text(v.clone())),
+            pbjson_types::value::Kind::BoolValue(v) => Some(Value::Text(v.to_string())),
+            _ => None,
+        });
+
+        out.insert(column_name(k), (kind, v));
+    }
+
+    out
+}
+
+fn column_name(key: &str) -> String {
+    let name = identifier(key);
+    if name.is_empty() || FIXED_COLUMNS.contains(&name.as_str()) {
+        identifier(&format!("m_{}", name))
+    } else {
+        name
+    }
+}
+
+// Returns the given name as a safe (lowercase, alphanumeric) identifier.
+fn identifier(s: &str) -> String {
+    s.to_lowercase()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .take(MAX_IDENTIFIER_LEN)
+        .collect()
+}
+
+fn value_to_f64(v: &Value) -> Option<f64> {
+    match v {
+        Value::Float(v) => Some(*v),
+        Value::Text(v) => match v.as_str() {
+            "true" => Some(1.0),
+            "false" => Some(0.0),
+            _ => v.parse().ok(),
+        },
+    }
+}
+
+fn value_to_string(v: &Value) -> String {
+    match v {
+        Value::Float(v) => v.to_string(),
+        Value::Text(v) => v.clone(),
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use crate::storage::{application, device_profile, tenant};
+    use crate::test;
+    use httpmock::prelude::*;
+
+    #[test]
+    fn test_column_name() {
+        assert_eq!("temperature", column_name("temperature"));
+        assert_eq!("sensor_0_temp", column_name("Sensor_0.Temp"));
+        assert_eq!("m_time", column_name("time"));
+        assert_eq!("m_", column_name(""));
+        assert_eq!(63, column_name(&"a".repeat(100)).len());
+    }
+
+    #[test]
+    fn test_get_columns() {
+        let measurements = fields::Measurements::new(
+            [
+                (
+                    "temperature".to_string(),
+                    fields::Measurement {
+                        name: "Temperature".into(),
+                        kind: fields::MeasurementKind::GAUGE,
+                    },
+                ),
+                (
+                    "status_active".to_string(),
+                    fields::Measurement {
+                        name: "Active".into(),
+                        kind: fields::MeasurementKind::STRING,
+                    },
+                ),
+                (
+                    "humidity".to_string(),
+                    fields::Measurement {
+                        name: "Humidity".into(),
+                        kind: fields::MeasurementKind::GAUGE,
+                    },
+                ),
+                (
+                    "raw".to_string(),
+                    fields::Measurement {
+                        name: "".into(),
+                        kind: fields::MeasurementKind::UNKNOWN,
+                    },
+                ),
+            ]
+            .iter()
+            .cloned()
+            .collect(),
+        );
+        let values: HashMap<String, pbjson_types::value::Kind> = [
+            (
+                "temperature".to_string(),
+                pbjson_types::value::Kind::NumberValue(22.5),
+            ),
+            (
+                "status_active".to_string(),
+                pbjson_types::value::Kind::BoolValue(true),
+            ),
+            (
+                "raw".to_string(),
+                pbjson_types::value::Kind::NumberValue(1.0),
+            ),
+        ]
+        .iter()
+        .cloned()
+        .collect();
+
+        let columns = get_columns(&measurements, &values);
+        assert_eq!(
+            vec![
+                ("humidity".to_string(), (ColumnKind::Float, None)),
+                (
+                    "status_active".to_string(),
+                    (ColumnKind::Text, Some(Value::Text("true".into())))
+                ),
+                (
+                    "temperature".to_string(),
+                    (ColumnKind::Float, Some(Value::Float(22.5)))
+                ),
+            ],
+            columns.into_iter().collect::<Vec<_>>()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_clickhouse() {
+        let _guard = test::prepare().await;
+        let server = MockServer::start();
+
+        let t = tenant::create(tenant::Tenant {
+            name: "test-tenant".into(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let app = application::create(application::Application {
+            tenant_id: t.id,
+            name: "test-app".into(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let dp = device_profile::create(device_profile::DeviceProfile {
+            tenant_id: t.id,
+            name: "test-dp".into(),
+            measurements: fields::Measurements::new(
+                [(
+                    "temperature".to_string(),
+                    fields::Measurement {
+                        name: "Temperature".into(),
+                        kind: fields::MeasurementKind::GAUGE,
+                    },
+                )]
+                .iter()
+                .cloned()
+                .collect(),
+            ),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let table = format!("measurements_{}", dp.id.simple());
+
+        let new_integration = || Integration {
+            inner: Arc::new(Inner {
+                table_prefix: "measurements_".into(),
+                batch_size: 1,
+                backend: Box::new(ClickHouse::new(&SqlIntegrationClickHouse {
+                    url: server.url("/"),
+                    database: "chirpstack".into(),
+                    username: "user".into(),
+                    password: "secret".into(),
+                })),
+                buffer: Mutex::new(Vec::new()),
+                schema: Mutex::new(HashMap::new()),
+                measurements: Mutex::new(HashMap::new()),
+            }),
+        };
+        let i = new_integration();
+
+        let mut create_mock = server.mock(|when, then| {
+            when.method(POST)
+                .path("/")
+                .query_param("database", "chirpstack")
+                .header("X-ClickHouse-User", "user")
+                .header("X-ClickHouse-Key", "secret")
+                .body(clickhouse_create_table(&table));
+            then.status(200);
+        });
+        let mut columns_mock = server.mock(|when, then| {
+            when.method(POST)
+                .path("/")
+                .body(clickhouse_get_columns(&table));
+            then.status(200).body("");
+        });
+        let mut alter_mock = server.mock(|when, then| {
+            when.method(POST).path("/").body(format!(
+                r#"alter table "{}" add column if not exists "temperature" Nullable(Float64)"#,
+                table
+            ));
+            then.status(200);
+        });
+        let mut insert_mock = server.mock(|when, then| {
+            when.method(POST)
+                .path("/")
+                .query_param(
+                    "query",
+                    format!(
+                        r#"insert into "{}" ({}, "temperature") format JSONEachRow"#,
+                        table,
+                        column_names(&[]),
+                    ),
+                )
+                .json_body(serde_json::json!({
+                    "time": "2024-06-10T12:00:00.000Z",
+                    "tenant_id": t.id.to_string(),
+                    "application_id": app.id.to_string(),
+                    "dev_eui": "0102030405060708",
+                    "device_name": "test-device",
+                    "f_cnt": 10,
+                    "f_port": 1,
+                    "dr": 3,
+                    "temperature": 22.5,
+                }));
+            then.status(200);
+        });
+
+        let pl = integration::UplinkEvent {
+            time: Some(
+                DateTime::parse_from_rfc3339("2024-06-10T12:00:00Z")
+                    .unwrap()
+                    .with_timezone(&Utc)
+                    .into(),
+            ),
+            device_info: Some(integration::DeviceInfo {
+                tenant_id: t.id.to_string(),
+                application_id: app.id.to_string(),
+                device_profile_id: dp.id.to_string(),
+                device_name: "test-device".into(),
+                dev_eui: "0102030405060708".into(),
+                ..Default::default()
+            }),
+            f_cnt: 10,
+            f_port: 1,
+            dr: 3,
+            object: Some(pbjson_types::Struct {
+                fields: [(
+                    "temperature".to_string(),
+                    pbjson_types::Value {
+                        kind: Some(pbjson_types::value::Kind::NumberValue(22.5)),
+                    },
+                )]
+                .iter()
+                .cloned()
+                .collect(),
+            }),
+            ..Default::default()
+        };
+        i.uplink_event(&HashMap::new(), &pl).await.unwrap();
+
+        create_mock.assert();
+        columns_mock.assert();
+        alter_mock.assert();
+        insert_mock.assert();
+
+        // The schema is cached.
+        i.uplink_event(&HashMap::new(), &pl).await.unwrap();
+        create_mock.assert_hits(1);
+        columns_mock.assert_hits(1);
+        alter_mock.assert_hits(1);
+        insert_mock.assert_hits(2);
+
+        // Failed rows are retried on the next flush.
+        insert_mock.delete();
+        let mut insert_error_mock = server.mock(|when, then| {
+            when.method(POST).path("/").query_param_exists("query");
+            then.status(500);
+        });
+        assert!(i.uplink_event(&HashMap::new(), &pl).await.is_err());
+        assert_eq!(1, i.inner.buffer.lock().await.len());
+        insert_error_mock.delete();
+        let mut insert_mock = server.mock(|when, then| {
+            when.method(POST).path("/").query_param_exists("query");
+            then.status(200);
+        });
+        i.inner.flush().await.unwrap();
+        insert_mock.assert_hits(1);
+        assert!(i.inner.buffer.lock().await.is_empty());
+
+        create_mock.delete();
+        columns_mock.delete();
+        alter_mock.delete();
+        insert_mock.delete();
+
+        // After a restart, the column kinds are read from the database. As the existing column
+        // is a string column, the measurement is stored as string.
+        let i = new_integration();
+        let mut create_mock = server.mock(|when, then| {
+            when.method(POST)
+                .path("/")
+                .body(clickhouse_create_table(&table));
+            then.status(200);
+        });
+        let mut columns_mock = server.mock(|when, then| {
+            when.method(POST)
+                .path("/")
+                .body(clickhouse_get_columns(&table));
+            then.status(200).body(
+                r#"{"name":"time","type":"DateTime64(3, 'UTC')"}
+{"name":"temperature","type":"Nullable(String)"}"#,
+            );
+        });
+        let mut insert_mock = server.mock(|when, then| {
+            when.method(POST)
+                .path("/")
+                .query_param_exists("query")
+                .json_body_partial(r#"{"temperature": "22.5"}"#);
+            then.status(200);
+        });
+        i.uplink_event(&HashMap::new(), &pl).await.unwrap();
+        create_mock.assert();
+        columns_mock.assert();
+        insert_mock.assert();
+
+        create_mock.delete();
+        columns_mock.delete();
+        insert_mock.delete();
+    }
+
+    #[test]
+    fn test_column_kind() {
+        assert_eq!(None, column_kind("time", "timestamp with time zone"));
+        assert_eq!(None, column_kind("f_cnt", "UInt32"));
+        assert_eq!(
+            Some(ColumnKind::Float),
+            column_kind("temperature", "double precision")
+        );
+        assert_eq!(
+            Some(ColumnKind::Float),
+            column_kind("temperature", "Nullable(Float64)")
+        );
+        assert_eq!(Some(ColumnKind::Text), column_kind("status", "text"));
+        assert_eq!(
+            Some(ColumnKind::Text),
+            column_kind("status", "Nullable(String)")
+        );
+        assert_eq!(None, column_kind("other", "integer"));
+    }
+}
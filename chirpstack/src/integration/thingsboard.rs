@@ -4,11 +4,10 @@ use std::time::Duration;
 use anyhow::Result;
 use async_trait::async_trait;
 use reqwest::header::{HeaderMap, CONTENT_TYPE};
-use reqwest::Client;
 use serde::{Serialize, Serializer};
 use tracing::{info, trace};
 
-use super::Integration as IntegrationTrait;
+use super::{http_client, Integration as IntegrationTrait};
 use crate::storage::application::ThingsBoardConfiguration;
 use chirpstack_api::integration;
 
@@ -39,12 +38,13 @@ impl Integration {
         let endpoint = format!("{}/api/v1/{}/attributes", self.server, access_token);
         let b = serde_json::to_string(&attributes)?;
 
-        let client = Client::builder().timeout(self.timeout).build()?;
+        let client = http_client();
         let mut headers = HeaderMap::new();
         headers.insert(CONTENT_TYPE, "application/json".parse().unwrap());
 
         let res = client
             .post(endpoint)
+            .timeout(self.timeout)
             .body(b)
             .headers(headers)
             .send()
@@ -65,12 +65,13 @@ impl Integration {
         let endpoint = format!("{}/api/v1/{}/telemetry", self.server, access_token);
         let b = serde_json::to_string(&telemetry)?;
 
-        let client = Client::builder().timeout(self.timeout).build()?;
+        let client = http_client();
         let mut headers = HeaderMap::new();
         headers.insert(CONTENT_TYPE, "application/json".parse().unwrap());
 
         let res = client
             .post(endpoint)
+            .timeout(self.timeout)
             .body(b)
             .headers(headers)
             .send()
@@ -240,6 +241,14 @@ impl IntegrationTrait for Integration {
     ) -> Result<()> {
         Ok(())
     }
+
+    async fn downlink_event(
+        &self,
+        _vars: &HashMap<String, String>,
+        _pl: &integration::DownlinkEvent,
+    ) -> Result<()> {
+        Ok(())
+    }
 }
 
 #[derive(Clone)]
@@ -28,6 +28,7 @@ mod codec;
 mod config;
 mod devaddr;
 mod downlink;
+mod frame_decoder;
 mod gateway;
 mod gpstime;
 mod helpers;
@@ -81,6 +82,82 @@ enum Commands {
 
     /// Migrate device-sessions from Redis to PostgreSQL.
     MigrateDeviceSessionsToPostgres {},
+
+    /// Decode the given LoRaWAN PHYPayload and print it as JSON.
+    DecodeFrame {
+        /// PHYPayload (HEX encoded).
+        #[arg(value_name = "PHY_PAYLOAD")]
+        phy_payload: String,
+
+        /// Lookup the device by DevAddr (or JoinRequest DevEUI) to validate the MIC and to
+        /// decrypt the FOpts and FRMPayload.
+        #[arg(long)]
+        lookup_device: bool,
+
+        /// Device EUI, use this device instead of the DevAddr based lookup.
+        #[arg(long, value_name = "DEV_EUI")]
+        dev_eui: Option<String>,
+
+        /// Uplink data-rate (only used for the LoRaWAN 1.1 uplink MIC).
+        #[arg(long, value_name = "DR", default_value_t = 0)]
+        tx_dr: u8,
+
+        /// Uplink channel index (only used for the LoRaWAN 1.1 uplink MIC).
+        #[arg(long, value_name = "CH", default_value_t = 0)]
+        tx_ch: u8,
+
+        /// Decode the FRMPayload using the device-profile codec.
+        #[arg(long)]
+        run_codec: bool,
+    },
+
+    /// Export the regional parameters of all regions and revisions.
+    ExportRegions {
+        /// Output format (json or yaml).
+        #[arg(short, long, value_name = "FORMAT", default_value = "json")]
+        format: String,
+    },
+
+    /// Export the tenant usage (e.g. for billing).
+    ExportTenantUsage {
+        /// Tenant ID, all tenants are exported when not set.
+        #[arg(long, value_name = "TENANT_ID")]
+        tenant_id: Option<String>,
+
+        /// Aggregation (hour, day or month).
+        #[arg(short, long, value_name = "AGGREGATION", default_value = "month")]
+        aggregation: String,
+
+        /// Start (RFC3339 timestamp or YYYY-MM-DD date).
+        #[arg(long, value_name = "START")]
+        start: String,
+
+        /// End (RFC3339 timestamp or YYYY-MM-DD date).
+        #[arg(long, value_name = "END")]
+        end: String,
+
+        /// Output format (csv or json).
+        #[arg(short, long, value_name = "FORMAT", default_value = "csv")]
+        format: String,
+    },
+
+    /// Export the tenant, including its applications, devices and gateways, as JSON archive.
+    ExportTenant {
+        /// Tenant ID.
+        #[arg(long, value_name = "TENANT_ID")]
+        tenant_id: String,
+
+        /// Label of the KEK (configured under keks) used for wrapping the keys.
+        #[arg(long, value_name = "KEK_LABEL")]
+        kek_label: String,
+    },
+
+    /// Import the tenant archive (created by export-tenant) as new tenant.
+    ImportTenant {
+        /// Path to the archive.
+        #[arg(short, long, value_name = "FILE")]
+        file: String,
+    },
 }
 
 #[tokio::main]
@@ -120,6 +197,42 @@ async fn main() -> Result<()> {
         }
         Some(Commands::CreateApiKey { name }) => cmd::create_api_key::run(name).await?,
         Some(Commands::MigrateDeviceSessionsToPostgres {}) => cmd::migrate_ds_to_pg::run().await?,
+        Some(Commands::DecodeFrame {
+            phy_payload,
+            lookup_device,
+            dev_eui,
+            tx_dr,
+            tx_ch,
+            run_codec,
+        }) => {
+            let opts = frame_decoder::Options {
+                lookup_device: *lookup_device,
+                dev_eui: match dev_eui {
+                    Some(v) => Some(EUI64::from_str(v)?),
+                    None => None,
+                },
+                tx_dr: *tx_dr,
+                tx_ch: *tx_ch,
+                run_codec: *run_codec,
+            };
+            cmd::decode_frame::run(phy_payload, &opts).await?
+        }
+        Some(Commands::ExportRegions { format }) => cmd::export_regions::run(format)?,
+        Some(Commands::ExportTenantUsage {
+            tenant_id,
+            aggregation,
+            start,
+            end,
+            format,
+        }) => {
+            cmd::export_tenant_usage::run(tenant_id.as_deref(), aggregation, start, end, format)
+                .await?
+        }
+        Some(Commands::ExportTenant {
+            tenant_id,
+            kek_label,
+        }) => cmd::export_tenant::run(tenant_id, kek_label).await?,
+        Some(Commands::ImportTenant { file }) => cmd::import_tenant::run(file).await?,
         None => cmd::root::run().await?,
     }
 
@@ -103,3 +103,53 @@ pub fn get_region_config_id(common_name: region::CommonName) -> Result<String> {
         common_name
     ))
 }
+
+/// Returns the max. payload-size for the given data-rate. When the 400ms dwell-time applies,
+/// this is limited to what can be transmitted within the dwell-time.
+pub fn get_max_payload_size(
+    region_conf: &(dyn region::Region + Sync + Send),
+    mac_version: region::MacVersion,
+    reg_params_revision: region::Revision,
+    dr: u8,
+    dwell_time_400ms: bool,
+    uplink: bool,
+) -> Result<region::MaxPayloadSize> {
+    let max_pl_size = region_conf.get_max_payload_size(mac_version, reg_params_revision, dr)?;
+    if !dwell_time_400ms {
+        return Ok(max_pl_size);
+    }
+
+    let modulation = region_conf.get_data_rate(dr)?;
+    region::airtime::apply_dwell_time_400ms(max_pl_size, &modulation, uplink)
+}
+
+/// Returns the largest application payload-size that can be sent to a device in the given
+/// region, using any of the downlink data-rates.
+pub fn get_max_downlink_payload_size(
+    region_config_id: &str,
+    mac_version: region::MacVersion,
+    reg_params_revision: region::Revision,
+) -> Result<usize> {
+    let region_conf = get(region_config_id)?;
+    let network_conf = config::get_region_network(region_config_id)?;
+
+    let mut out = 0;
+    for (dr, data_rate) in region_conf.get_data_rates() {
+        if !data_rate.downlink {
+            continue;
+        }
+
+        if let Ok(max_pl_size) = get_max_payload_size(
+            &**region_conf,
+            mac_version,
+            reg_params_revision,
+            dr,
+            network_conf.downlink_dwell_time_400ms || region_conf.get_dwell_time_400ms(),
+            false,
+        ) {
+            out = out.max(max_pl_size.n);
+        }
+    }
+
+    Ok(out)
+}
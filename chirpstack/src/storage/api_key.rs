@@ -1,14 +1,23 @@
+use std::collections::HashSet;
+use std::sync::Mutex;
+
 use anyhow::Result;
 use chrono::{DateTime, Utc};
 use diesel::dsl;
 use diesel::prelude::*;
 use diesel_async::RunQueryDsl;
-use tracing::info;
+use tracing::{error, info};
 use uuid::Uuid;
 
 use super::error::Error;
+use super::get_async_db_conn;
 use super::schema::api_key;
-use super::{error, get_async_db_conn};
+use crate::helpers::errors::PrintFullError;
+
+lazy_static! {
+    // API keys used since the last-used timestamps were persisted.
+    static ref LAST_USED: Mutex<HashSet<Uuid>> = Mutex::new(HashSet::new());
+}
 
 #[derive(Queryable, Insertable, PartialEq, Eq, Debug)]
 #[diesel(table_name = api_key)]
@@ -18,6 +27,22 @@ pub struct ApiKey {
     pub name: String,
     pub is_admin: bool,
     pub tenant_id: Option<Uuid>,
+    pub application_id: Option<Uuid>,
+    pub device_profile_id: Option<Uuid>,
+    pub can_read: bool,
+    pub can_write: bool,
+    pub can_enqueue: bool,
+    pub can_read_keys: bool,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Permission {
+    Read,
+    Write,
+    Enqueue,
+    ReadKeys,
 }
 
 impl ApiKey {
@@ -26,8 +51,66 @@ impl ApiKey {
             return Err(Error::Validation("name is not set".into()));
         }
 
+        if self.is_scoped() && (self.is_admin || self.tenant_id.is_none()) {
+            return Err(Error::Validation(
+                "application or device-profile scope requires a tenant API key".into(),
+            ));
+        }
+
+        if !(self.can_read || self.can_write || self.can_enqueue || self.can_read_keys) {
+            return Err(Error::Validation(
+                "at least one permission must be set".into(),
+            ));
+        }
+
         Ok(())
     }
+
+    // Returns true when the API key is scoped to an application and / or device-profile.
+    pub fn is_scoped(&self) -> bool {
+        self.application_id.is_some() || self.device_profile_id.is_some()
+    }
+
+    pub fn has_permission(&self, p: Permission) -> bool {
+        match p {
+            Permission::Read => self.can_read,
+            Permission::Write => self.can_write,
+            Permission::Enqueue => self.can_enqueue,
+            Permission::ReadKeys => self.can_read_keys,
+        }
+    }
+
+    // Returns true when the given application and device-profile are within the scope of the
+    // API key. A device_profile_id of None means that the resource is not limited to a single
+    // device-profile (e.g. the list of devices of an application).
+    pub fn in_scope(&self, application_id: &Uuid, device_profile_id: Option<&Uuid>) -> bool {
+        if let Some(v) = &self.application_id {
+            if v != application_id {
+                return false;
+            }
+        }
+
+        match &self.device_profile_id {
+            Some(v) => device_profile_id == Some(v),
+            None => true,
+        }
+    }
+
+    pub fn permissions(&self) -> Vec<Permission> {
+        [
+            Permission::Read,
+            Permission::Write,
+            Permission::Enqueue,
+            Permission::ReadKeys,
+        ]
+        .into_iter()
+        .filter(|p| self.has_permission(*p))
+        .collect()
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.expires_at.map(|v| v <= Utc::now()).unwrap_or(false)
+    }
 }
 
 impl Default for ApiKey {
@@ -38,6 +121,14 @@ impl Default for ApiKey {
             name: "".into(),
             is_admin: false,
             tenant_id: None,
+            application_id: None,
+            device_profile_id: None,
+            can_read: true,
+            can_write: true,
+            can_enqueue: true,
+            can_read_keys: true,
+            expires_at: None,
+            last_used_at: None,
         }
     }
 }
@@ -55,11 +146,19 @@ pub async fn create(ak: ApiKey) -> Result<ApiKey, Error> {
         .values(&ak)
         .get_result(&mut get_async_db_conn().await?)
         .await
-        .map_err(|e| error::Error::from_diesel(e, ak.id.to_string()))?;
+        .map_err(|e| Error::from_diesel(e, ak.id.to_string()))?;
     info!(id = %ak.id, "Api-key created");
     Ok(ak)
 }
 
+pub async fn get(id: &Uuid) -> Result<ApiKey, Error> {
+    api_key::dsl::api_key
+        .find(&id)
+        .first(&mut get_async_db_conn().await?)
+        .await
+        .map_err(|e| Error::from_diesel(e, id.to_string()))
+}
+
 pub async fn delete(id: &Uuid) -> Result<(), Error> {
     let ra = diesel::delete(api_key::dsl::api_key.find(&id))
         .execute(&mut get_async_db_conn().await?)
@@ -71,6 +170,40 @@ pub async fn delete(id: &Uuid) -> Result<(), Error> {
     Ok(())
 }
 
+// Marks the API key as used. To avoid a database write on every API request, the last-used
+// timestamps are persisted periodically by persist_last_used_loop.
+pub fn set_last_used(id: &Uuid) {
+    LAST_USED.lock().unwrap().insert(*id);
+}
+
+pub async fn persist_last_used_loop() {
+    let mut interval = tokio::time::interval(std::time::Duration::from_secs(60));
+    loop {
+        interval.tick().await;
+
+        if let Err(e) = persist_last_used().await {
+            error!(error = %e.full(), "Persist API key last-used error");
+        }
+    }
+}
+
+// Sets the last-used timestamp of the API keys used since the previous call.
+async fn persist_last_used() -> Result<(), Error> {
+    let ids: Vec<Uuid> = std::mem::take(&mut *LAST_USED.lock().unwrap())
+        .into_iter()
+        .collect();
+    if ids.is_empty() {
+        return Ok(());
+    }
+
+    diesel::update(api_key::dsl::api_key.filter(api_key::dsl::id.eq_any(&ids)))
+        .set(api_key::last_used_at.eq(Utc::now()))
+        .execute(&mut get_async_db_conn().await?)
+        .await?;
+
+    Ok(())
+}
+
 pub async fn get_count(filters: &Filters) -> Result<i64, Error> {
     let mut q = api_key::dsl::api_key
         .select(dsl::count_star())
@@ -116,14 +249,6 @@ pub mod test {
         offset: i64,
     }
 
-    pub async fn get(id: &Uuid) -> Result<ApiKey, Error> {
-        api_key::dsl::api_key
-            .find(&id)
-            .first(&mut get_async_db_conn().await?)
-            .await
-            .map_err(|e| error::Error::from_diesel(e, id.to_string()))
-    }
-
     pub async fn create_api_key(is_admin: bool, is_tenant: bool) -> ApiKey {
         let ak = ApiKey {
             name: "test api key".into(),
@@ -189,6 +314,22 @@ pub mod test {
             );
         }
 
+        // last used
+        assert!(ak_tenant.last_used_at.is_none());
+        set_last_used(&ak_tenant.id);
+        persist_last_used().await.unwrap();
+        let ak_get = get(&ak_tenant.id).await.unwrap();
+        assert!(ak_get.last_used_at.is_some());
+
+        // scoped admin key is not allowed
+        let ak = ApiKey {
+            name: "scoped admin key".into(),
+            is_admin: true,
+            application_id: Some(Uuid::new_v4()),
+            ..Default::default()
+        };
+        assert!(create(ak).await.is_err());
+
         // delete
         delete(&ak_admin.id).await.unwrap();
         assert_eq!(true, delete(&ak_admin.id).await.is_err());
@@ -18,8 +18,11 @@ use tracing::info;
 use uuid::Uuid;
 
 use super::error::Error;
-use super::schema::{application, application_integration};
+use super::schema::{
+    application, application_integration, application_user, device, tenant, tenant_user, user,
+};
 use super::{fields, get_async_db_conn};
+use crate::config;
 
 #[derive(Clone, Queryable, Insertable, PartialEq, Eq, Debug)]
 #[diesel(table_name = application)]
@@ -32,6 +35,7 @@ pub struct Application {
     pub description: String,
     pub mqtt_tls_cert: Option<Vec<u8>>,
     pub tags: fields::KeyValue,
+    pub deleted_at: Option<DateTime<Utc>>,
 }
 
 impl Application {
@@ -56,6 +60,7 @@ impl Default for Application {
             description: "".into(),
             mqtt_tls_cert: None,
             tags: fields::KeyValue::new(HashMap::new()),
+            deleted_at: None,
         }
     }
 }
@@ -64,6 +69,11 @@ impl Default for Application {
 pub struct Filters {
     pub tenant_id: Option<Uuid>,
     pub search: Option<String>,
+    // When set, only the applications visible to the given user are returned. This should
+    // not be set for global and tenant admin users.
+    pub user_id: Option<Uuid>,
+    // When set, only the (soft) deleted applications are returned.
+    pub deleted: bool,
 }
 
 #[derive(Queryable, PartialEq, Eq, Debug)]
@@ -73,6 +83,58 @@ pub struct ApplicationListItem {
     pub updated_at: DateTime<Utc>,
     pub name: String,
     pub description: String,
+    pub deleted_at: Option<DateTime<Utc>>,
+}
+
+// Membership of a tenant user to an application. Tenant users which are member of one or
+// more applications can only access these applications within the tenant.
+#[derive(Queryable, Insertable, AsChangeset, PartialEq, Eq, Debug, Clone)]
+#[diesel(table_name = application_user)]
+pub struct ApplicationUser {
+    pub application_id: Uuid,
+    pub user_id: Uuid,
+    pub tenant_id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub is_admin: bool,
+    pub is_device_admin: bool,
+}
+
+impl Default for ApplicationUser {
+    fn default() -> Self {
+        let now = Utc::now();
+
+        ApplicationUser {
+            application_id: Uuid::nil(),
+            user_id: Uuid::nil(),
+            tenant_id: Uuid::nil(),
+            created_at: now,
+            updated_at: now,
+            is_admin: false,
+            is_device_admin: false,
+        }
+    }
+}
+
+#[derive(Queryable, PartialEq, Eq, Debug)]
+pub struct ApplicationUserListItem {
+    pub application_id: Uuid,
+    pub user_id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub email: String,
+    pub is_admin: bool,
+    pub is_device_admin: bool,
+}
+
+#[derive(PartialEq, Eq, Debug)]
+pub enum UserAccess {
+    // The user is not restricted to specific applications within the tenant.
+    Tenant,
+    // The user is restricted to specific applications and is a member of this application.
+    Member(ApplicationUser),
+    // The user is restricted to other applications within the tenant.
+    None,
 }
 
 #[derive(Deserialize, Serialize, Copy, Clone, Debug, Eq, PartialEq, AsExpression, FromSqlRow)]
@@ -168,11 +230,21 @@ impl serialize::ToSql<Jsonb, Pg> for IntegrationConfiguration {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct HttpConfiguration {
     pub headers: HashMap<String, String>,
     pub json: bool,
     pub event_endpoint_url: String,
+    #[serde(default)]
+    pub timeout_ms: u32,
+    #[serde(default)]
+    pub signing_secret: String,
+    #[serde(default)]
+    pub ca_cert: String,
+    #[serde(default)]
+    pub tls_cert: String,
+    #[serde(default)]
+    pub tls_key: String,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -187,6 +259,12 @@ pub struct InfluxDbConfiguration {
     pub token: String,
     pub organization: String,
     pub bucket: String,
+    #[serde(default)]
+    pub flush_interval_ms: u32,
+    #[serde(default)]
+    pub tag_variables: Vec<String>,
+    #[serde(default)]
+    pub rx_metadata: bool,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -273,6 +351,7 @@ pub struct Integration {
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
     pub configuration: IntegrationConfiguration,
+    pub filter: IntegrationFilter,
 }
 
 impl Default for Integration {
@@ -285,7 +364,65 @@ impl Default for Integration {
             created_at: now,
             updated_at: now,
             configuration: IntegrationConfiguration::None,
+            filter: IntegrationFilter::default(),
+        }
+    }
+}
+
+// Filter which determines which events are forwarded to the integration. Empty fields match
+// all events.
+#[derive(
+    Default, Debug, Clone, PartialEq, Eq, AsExpression, FromSqlRow, Serialize, Deserialize,
+)]
+#[diesel(sql_type = Jsonb)]
+#[serde(default)]
+pub struct IntegrationFilter {
+    pub events: Vec<String>,
+    pub f_ports: Vec<u32>,
+    pub device_profile_ids: Vec<Uuid>,
+    pub tags: HashMap<String, String>,
+    pub expression: String,
+}
+
+impl IntegrationFilter {
+    fn validate(&self) -> Result<(), Error> {
+        for e in &self.events {
+            if !FILTER_EVENTS.contains(&e.as_str()) {
+                return Err(Error::Validation(format!("Invalid event: {}", e)));
+            }
+        }
+        for f_port in &self.f_ports {
+            if *f_port > 255 {
+                return Err(Error::Validation(format!("Invalid FPort: {}", f_port)));
+            }
         }
+        Ok(())
+    }
+}
+
+const FILTER_EVENTS: [&str; 9] = [
+    "up",
+    "join",
+    "ack",
+    "txack",
+    "log",
+    "status",
+    "location",
+    "integration",
+    "downlink",
+];
+
+impl deserialize::FromSql<Jsonb, Pg> for IntegrationFilter {
+    fn from_sql(value: <Pg as Backend>::RawValue<'_>) -> deserialize::Result<Self> {
+        let value = <serde_json::Value as deserialize::FromSql<Jsonb, Pg>>::from_sql(value)?;
+        Ok(serde_json::from_value(value)?)
+    }
+}
+
+impl serialize::ToSql<Jsonb, Pg> for IntegrationFilter {
+    fn to_sql(&self, out: &mut serialize::Output<'_, '_, Pg>) -> serialize::Result {
+        let value = serde_json::to_value(self)?;
+        <serde_json::Value as serialize::ToSql<Jsonb, Pg>>::to_sql(&value, &mut out.reborrow())
     }
 }
 
@@ -306,6 +443,7 @@ pub async fn create(a: Application) -> Result<Application, Error> {
 pub async fn get(id: &Uuid) -> Result<Application, Error> {
     let a = application::dsl::application
         .find(&id)
+        .filter(application::dsl::deleted_at.is_null())
         .first(&mut get_async_db_conn().await?)
         .await
         .map_err(|e| Error::from_diesel(e, id.to_string()))?;
@@ -349,7 +487,107 @@ pub async fn update_mqtt_cls_cert(id: &Uuid, cert: &[u8]) -> Result<Application,
     Ok(app)
 }
 
+// Deletes the application. When soft-delete is enabled, the application and its devices are
+// marked as deleted and are permanently removed by the purge loop after the retention period.
 pub async fn delete(id: &Uuid) -> Result<(), Error> {
+    let conf = config::get();
+    if conf.soft_delete.retention.is_zero() {
+        return purge(id).await;
+    }
+
+    let mut c = get_async_db_conn().await?;
+    c.build_transaction()
+        .run::<(), Error, _>(|c| {
+            Box::pin(async move {
+                let deleted_at = Utc::now();
+
+                let ra = diesel::update(
+                    application::dsl::application
+                        .find(&id)
+                        .filter(application::dsl::deleted_at.is_null()),
+                )
+                .set(application::deleted_at.eq(deleted_at))
+                .execute(c)
+                .await?;
+                if ra == 0 {
+                    return Err(Error::NotFound(id.to_string()));
+                }
+
+                diesel::update(
+                    device::dsl::device
+                        .filter(device::dsl::application_id.eq(&id))
+                        .filter(device::dsl::deleted_at.is_null()),
+                )
+                .set(device::deleted_at.eq(deleted_at))
+                .execute(c)
+                .await?;
+
+                Ok(())
+            })
+        })
+        .await?;
+
+    info!(
+        application_id = %id,
+        "Application deleted"
+    );
+
+    Ok(())
+}
+
+// Restores the (soft) deleted application, including the devices that were deleted together
+// with the application. This is not allowed when the tenant is deleted.
+pub async fn restore(id: &Uuid) -> Result<(), Error> {
+    let mut c = get_async_db_conn().await?;
+    c.build_transaction()
+        .run::<(), Error, _>(|c| {
+            Box::pin(async move {
+                let (deleted_at, tenant_deleted_at): (
+                    Option<DateTime<Utc>>,
+                    Option<DateTime<Utc>>,
+                ) = application::dsl::application
+                    .inner_join(tenant::table)
+                    .select((application::dsl::deleted_at, tenant::dsl::deleted_at))
+                    .filter(application::dsl::id.eq(&id))
+                    .for_update()
+                    .first(c)
+                    .await
+                    .map_err(|e| Error::from_diesel(e, id.to_string()))?;
+
+                let deleted_at = deleted_at.ok_or_else(|| Error::NotFound(id.to_string()))?;
+                if tenant_deleted_at.is_some() {
+                    return Err(Error::NotAllowed("Tenant is deleted".into()));
+                }
+
+                diesel::update(application::dsl::application.find(&id))
+                    .set(application::deleted_at.eq(None::<DateTime<Utc>>))
+                    .execute(c)
+                    .await?;
+
+                diesel::update(
+                    device::dsl::device
+                        .filter(device::dsl::application_id.eq(&id))
+                        .filter(device::dsl::deleted_at.eq(deleted_at)),
+                )
+                .set(device::deleted_at.eq(None::<DateTime<Utc>>))
+                .execute(c)
+                .await?;
+
+                Ok(())
+            })
+        })
+        .await?;
+
+    info!(
+        application_id = %id,
+        "Application restored"
+    );
+
+    Ok(())
+}
+
+// Permanently deletes the application, including all its devices.
+pub async fn purge(id: &Uuid) -> Result<(), Error> {
     let ra = diesel::delete(application::dsl::application.find(&id))
         .execute(&mut get_async_db_conn().await?)
         .await?;
@@ -359,12 +597,38 @@ pub async fn delete(id: &Uuid) -> Result<(), Error> {
 
     info!(
         application_id = %id,
-        "Application deleted"
+        "Application purged"
     );
 
     Ok(())
 }
 
+// Permanently deletes the applications that were (soft) deleted before the given timestamp.
+// Returns the IDs of the applications that will be purged for the given timestamp, either
+// because the application or because the tenant was deleted before this timestamp.
+pub async fn get_purge_ids(before: DateTime<Utc>) -> Result<Vec<Uuid>, Error> {
+    application::dsl::application
+        .inner_join(tenant::table)
+        .select(application::dsl::id)
+        .filter(
+            application::dsl::deleted_at
+                .lt(before)
+                .or(tenant::dsl::deleted_at.lt(before)),
+        )
+        .load(&mut get_async_db_conn().await?)
+        .await
+        .map_err(|e| Error::from_diesel(e, "".into()))
+}
+
+pub async fn purge_deleted(before: DateTime<Utc>) -> Result<usize, Error> {
+    let ra = diesel::delete(
+        application::dsl::application.filter(application::dsl::deleted_at.lt(before)),
+    )
+    .execute(&mut get_async_db_conn().await?)
+    .await?;
+    Ok(ra)
+}
+
 pub async fn get_count(filters: &Filters) -> Result<i64, Error> {
     let mut q = application::dsl::application
         .select(dsl::count_star())
@@ -378,9 +642,45 @@ pub async fn get_count(filters: &Filters) -> Result<i64, Error> {
         q = q.filter(application::dsl::name.ilike(format!("%{}%", search)));
     }
 
+    if filters.deleted {
+        q = q.filter(application::dsl::deleted_at.is_not_null());
+    } else {
+        q = q.filter(application::dsl::deleted_at.is_null());
+    }
+
+    if let Some(user_id) = &filters.user_id {
+        q = q.filter(
+            dsl::exists(
+                application_user::dsl::application_user.filter(
+                    application_user::dsl::application_id
+                        .eq(application::dsl::id)
+                        .and(application_user::dsl::user_id.eq(user_id)),
+                ),
+            )
+            .or(dsl::not(dsl::exists(
+                application_user::dsl::application_user.filter(
+                    application_user::dsl::tenant_id
+                        .eq(application::dsl::tenant_id)
+                        .and(application_user::dsl::user_id.eq(user_id)),
+                ),
+            ))),
+        );
+    }
+
     Ok(q.first(&mut get_async_db_conn().await?).await?)
 }
 
+// Returns the IDs of all the applications of the given tenant, including the (soft) deleted
+// applications.
+pub async fn get_ids_for_tenant(tenant_id: &Uuid) -> Result<Vec<Uuid>, Error> {
+    application::dsl::application
+        .select(application::dsl::id)
+        .filter(application::dsl::tenant_id.eq(tenant_id))
+        .load(&mut get_async_db_conn().await?)
+        .await
+        .map_err(|e| Error::from_diesel(e, tenant_id.to_string()))
+}
+
 pub async fn list(
     limit: i64,
     offset: i64,
@@ -393,6 +693,7 @@ pub async fn list(
             application::updated_at,
             application::name,
             application::description,
+            application::deleted_at,
         ))
         .into_boxed();
 
@@ -404,6 +705,31 @@ pub async fn list(
         q = q.filter(application::dsl::name.ilike(format!("%{}%", search)));
     }
 
+    if filters.deleted {
+        q = q.filter(application::dsl::deleted_at.is_not_null());
+    } else {
+        q = q.filter(application::dsl::deleted_at.is_null());
+    }
+
+    if let Some(user_id) = &filters.user_id {
+        q = q.filter(
+            dsl::exists(
+                application_user::dsl::application_user.filter(
+                    application_user::dsl::application_id
+                        .eq(application::dsl::id)
+                        .and(application_user::dsl::user_id.eq(user_id)),
+                ),
+            )
+            .or(dsl::not(dsl::exists(
+                application_user::dsl::application_user.filter(
+                    application_user::dsl::tenant_id
+                        .eq(application::dsl::tenant_id)
+                        .and(application_user::dsl::user_id.eq(user_id)),
+                ),
+            ))),
+        );
+    }
+
     let items = q
         .order_by(application::dsl::name)
         .limit(limit)
@@ -413,6 +739,166 @@ pub async fn list(
     Ok(items)
 }
 
+pub async fn add_user(au: ApplicationUser) -> Result<ApplicationUser, Error> {
+    let a = get(&au.application_id).await?;
+    let au = ApplicationUser {
+        tenant_id: a.tenant_id,
+        ..au
+    };
+
+    let au: ApplicationUser = diesel::insert_into(application_user::table)
+        .values(&au)
+        .get_result(&mut get_async_db_conn().await?)
+        .await
+        .map_err(|e| match e {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UniqueViolation,
+                _,
+            ) => Error::AlreadyExists(au.user_id.to_string()),
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::ForeignKeyViolation,
+                _,
+            ) => Error::Validation("user is not a member of the tenant".into()),
+            _ => Error::from_diesel(e, au.user_id.to_string()),
+        })?;
+    info!(
+        application_id = %au.application_id,
+        user_id = %au.user_id,
+        "Application user added"
+    );
+    Ok(au)
+}
+
+pub async fn update_user(au: ApplicationUser) -> Result<ApplicationUser, Error> {
+    let au: ApplicationUser = diesel::update(
+        application_user::dsl::application_user.find((&au.application_id, &au.user_id)),
+    )
+    .set((
+        application_user::updated_at.eq(Utc::now()),
+        application_user::is_admin.eq(&au.is_admin),
+        application_user::is_device_admin.eq(&au.is_device_admin),
+    ))
+    .get_result(&mut get_async_db_conn().await?)
+    .await
+    .map_err(|e| Error::from_diesel(e, au.user_id.to_string()))?;
+    info!(
+        application_id = %au.application_id,
+        user_id = %au.user_id,
+        "Application user updated"
+    );
+    Ok(au)
+}
+
+pub async fn get_user(application_id: &Uuid, user_id: &Uuid) -> Result<ApplicationUser, Error> {
+    let au: ApplicationUser = application_user::dsl::application_user
+        .find((&application_id, &user_id))
+        .first(&mut get_async_db_conn().await?)
+        .await
+        .map_err(|e| Error::from_diesel(e, user_id.to_string()))?;
+    Ok(au)
+}
+
+pub async fn get_user_count(application_id: &Uuid) -> Result<i64, Error> {
+    let count = application_user::dsl::application_user
+        .select(dsl::count_star())
+        .filter(application_user::dsl::application_id.eq(&application_id))
+        .first(&mut get_async_db_conn().await?)
+        .await?;
+    Ok(count)
+}
+
+pub async fn get_users(
+    application_id: &Uuid,
+    limit: i64,
+    offset: i64,
+) -> Result<Vec<ApplicationUserListItem>, Error> {
+    let items = application_user::dsl::application_user
+        .inner_join(user::table)
+        .select((
+            application_user::dsl::application_id,
+            application_user::dsl::user_id,
+            application_user::dsl::created_at,
+            application_user::dsl::updated_at,
+            user::dsl::email,
+            application_user::dsl::is_admin,
+            application_user::dsl::is_device_admin,
+        ))
+        .filter(application_user::dsl::application_id.eq(&application_id))
+        .order_by(user::dsl::email)
+        .limit(limit)
+        .offset(offset)
+        .load(&mut get_async_db_conn().await?)
+        .await?;
+    Ok(items)
+}
+
+pub async fn delete_user(application_id: &Uuid, user_id: &Uuid) -> Result<(), Error> {
+    let ra =
+        diesel::delete(application_user::dsl::application_user.find((&application_id, &user_id)))
+            .execute(&mut get_async_db_conn().await?)
+            .await?;
+    if ra == 0 {
+        return Err(Error::NotFound(user_id.to_string()));
+    }
+    info!(
+        application_id = %application_id,
+        user_id = %user_id,
+        "Application user deleted"
+    );
+    Ok(())
+}
+
+// Returns the access of the given (active) user to the application. Global admin users and
+// tenant admin users are never restricted to specific applications.
+pub async fn get_user_access(application_id: &Uuid, user_id: &Uuid) -> Result<UserAccess, Error> {
+    let mut c = get_async_db_conn().await?;
+
+    let au: Option<ApplicationUser> = application_user::dsl::application_user
+        .inner_join(user::table)
+        .select(application_user::all_columns)
+        .filter(application_user::dsl::application_id.eq(&application_id))
+        .filter(application_user::dsl::user_id.eq(&user_id))
+        .filter(user::dsl::is_active.eq(true))
+        .first(&mut c)
+        .await
+        .optional()?;
+    if let Some(au) = au {
+        return Ok(UserAccess::Member(au));
+    }
+
+    let tenant_id: Option<Uuid> = application::dsl::application
+        .select(application::dsl::tenant_id)
+        .find(&application_id)
+        .first(&mut c)
+        .await
+        .optional()?;
+    let tenant_id = match tenant_id {
+        Some(v) => v,
+        None => return Ok(UserAccess::Tenant),
+    };
+
+    let count: i64 = application_user::dsl::application_user
+        .inner_join(user::table)
+        .inner_join(
+            tenant_user::table.on(tenant_user::dsl::tenant_id
+                .eq(application_user::dsl::tenant_id)
+                .and(tenant_user::dsl::user_id.eq(application_user::dsl::user_id))),
+        )
+        .select(dsl::count_star())
+        .filter(application_user::dsl::tenant_id.eq(&tenant_id))
+        .filter(application_user::dsl::user_id.eq(&user_id))
+        .filter(user::dsl::is_admin.eq(false))
+        .filter(tenant_user::dsl::is_admin.eq(false))
+        .first(&mut c)
+        .await?;
+
+    Ok(if count == 0 {
+        UserAccess::Tenant
+    } else {
+        UserAccess::None
+    })
+}
+
 pub async fn create_integration(i: Integration) -> Result<Integration, Error> {
     let i: Integration = diesel::insert_into(application_integration::table)
         .values(&i)
@@ -474,6 +960,33 @@ pub async fn update_integration(i: Integration) -> Result<Integration, Error> {
     Ok(i)
 }
 
+pub async fn update_integration_filter(
+    application_id: &Uuid,
+    kind: IntegrationKind,
+    filter: &IntegrationFilter,
+) -> Result<Integration, Error> {
+    filter.validate()?;
+
+    let i: Integration = diesel::update(
+        application_integration::dsl::application_integration.filter(
+            application_integration::dsl::application_id
+                .eq(application_id)
+                .and(application_integration::dsl::kind.eq(kind)),
+        ),
+    )
+    .set((
+        application_integration::updated_at.eq(Utc::now()),
+        application_integration::filter.eq(filter),
+    ))
+    .get_result(&mut get_async_db_conn().await?)
+    .await
+    .map_err(|e| Error::from_diesel(e, application_id.to_string()))?;
+
+    info!(application_id = %i.application_id, kind = %i.kind, "Integration filter updated");
+
+    Ok(i)
+}
+
 pub async fn delete_integration(application_id: &Uuid, kind: IntegrationKind) -> Result<(), Error> {
     let ra = diesel::delete(
         application_integration::dsl::application_integration.filter(
@@ -585,6 +1098,8 @@ pub mod test {
                 filters: Filters {
                     tenant_id: None,
                     search: None,
+                    user_id: None,
+                    deleted: false,
                 },
                 apps: vec![&app],
                 count: 1,
@@ -595,6 +1110,8 @@ pub mod test {
                 filters: Filters {
                     tenant_id: None,
                     search: Some("aap".into()),
+                    user_id: None,
+                    deleted: false,
                 },
                 apps: vec![],
                 count: 0,
@@ -605,6 +1122,8 @@ pub mod test {
                 filters: Filters {
                     tenant_id: None,
                     search: Some("app".into()),
+                    user_id: None,
+                    deleted: false,
                 },
                 apps: vec![&app],
                 count: 1,
@@ -615,6 +1134,8 @@ pub mod test {
                 filters: Filters {
                     tenant_id: None,
                     search: Some("app".into()),
+                    user_id: None,
+                    deleted: false,
                 },
                 apps: vec![],
                 count: 1,
@@ -625,6 +1146,8 @@ pub mod test {
                 filters: Filters {
                     tenant_id: Some(app.tenant_id),
                     search: None,
+                    user_id: None,
+                    deleted: false,
                 },
                 apps: vec![&app],
                 count: 1,
@@ -635,6 +1158,8 @@ pub mod test {
                 filters: Filters {
                     tenant_id: Some(Uuid::new_v4()),
                     search: None,
+                    user_id: None,
+                    deleted: false,
                 },
                 apps: vec![],
                 count: 0,
@@ -663,5 +1188,140 @@ pub mod test {
         // delete
         delete(&app.id).await.unwrap();
         assert_eq!(true, delete(&app.id).await.is_err());
+        assert_eq!(true, get(&app.id).await.is_err());
+        assert_eq!(0, get_count(&Filters::default()).await.unwrap());
+
+        // list deleted
+        let filters = Filters {
+            tenant_id: Some(app.tenant_id),
+            deleted: true,
+            ..Default::default()
+        };
+        assert_eq!(1, get_count(&filters).await.unwrap());
+        let items = list(10, 0, &filters).await.unwrap();
+        assert_eq!(app.id, items[0].id);
+        assert!(items[0].deleted_at.is_some());
+
+        // restore
+        restore(&app.id).await.unwrap();
+        assert_eq!(true, restore(&app.id).await.is_err());
+        assert_eq!(app, get(&app.id).await.unwrap());
+
+        // restore is not allowed when the tenant is deleted
+        storage::tenant::delete(&app.tenant_id).await.unwrap();
+        assert!(matches!(restore(&app.id).await, Err(Error::NotAllowed(_))));
+        storage::tenant::restore(&app.tenant_id).await.unwrap();
+        assert_eq!(app, get(&app.id).await.unwrap());
+
+        // purge
+        delete(&app.id).await.unwrap();
+        assert_eq!(1, purge_deleted(Utc::now()).await.unwrap());
+        assert_eq!(true, restore(&app.id).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_application_user() {
+        let _guard = test::prepare().await;
+
+        let app_a = create_application(None).await;
+        let app_b = create_application(Some(app_a.tenant_id)).await;
+
+        let u = storage::user::create(storage::user::User {
+            email: "app@user".into(),
+            is_active: true,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        // user must be a tenant user
+        assert!(add_user(ApplicationUser {
+            application_id: app_a.id,
+            user_id: u.id,
+            ..Default::default()
+        })
+        .await
+        .is_err());
+
+        storage::tenant::add_user(storage::tenant::TenantUser {
+            tenant_id: app_a.tenant_id,
+            user_id: u.id,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        // tenant user without memberships can access all applications
+        assert_eq!(
+            UserAccess::Tenant,
+            get_user_access(&app_b.id, &u.id).await.unwrap()
+        );
+        let filters = Filters {
+            tenant_id: Some(app_a.tenant_id),
+            user_id: Some(u.id),
+            ..Default::default()
+        };
+        assert_eq!(2, get_count(&filters).await.unwrap());
+
+        // add
+        let au = add_user(ApplicationUser {
+            application_id: app_a.id,
+            user_id: u.id,
+            is_device_admin: true,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        assert_eq!(app_a.tenant_id, au.tenant_id);
+
+        // get
+        let au_get = get_user(&app_a.id, &u.id).await.unwrap();
+        assert_eq!(au, au_get);
+
+        // update
+        let au = update_user(ApplicationUser {
+            is_admin: true,
+            ..au
+        })
+        .await
+        .unwrap();
+        assert!(au.is_admin);
+
+        // count and list
+        assert_eq!(1, get_user_count(&app_a.id).await.unwrap());
+        let items = get_users(&app_a.id, 10, 0).await.unwrap();
+        assert_eq!(1, items.len());
+        assert_eq!("app@user", items[0].email);
+
+        // member can only access its applications
+        assert_eq!(
+            UserAccess::Member(au.clone()),
+            get_user_access(&app_a.id, &u.id).await.unwrap()
+        );
+        assert_eq!(
+            UserAccess::None,
+            get_user_access(&app_b.id, &u.id).await.unwrap()
+        );
+        assert_eq!(1, get_count(&filters).await.unwrap());
+        let items = list(10, 0, &filters).await.unwrap();
+        assert_eq!(app_a.id, items[0].id);
+
+        // tenant admin is not restricted
+        storage::tenant::update_user(storage::tenant::TenantUser {
+            tenant_id: app_a.tenant_id,
+            user_id: u.id,
+            is_admin: true,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        assert_eq!(
+            UserAccess::Tenant,
+            get_user_access(&app_b.id, &u.id).await.unwrap()
+        );
+
+        // delete
+        delete_user(&app_a.id, &u.id).await.unwrap();
+        assert!(delete_user(&app_a.id, &u.id).await.is_err());
     }
 }
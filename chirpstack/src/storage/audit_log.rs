@@ -0,0 +1,316 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use diesel::dsl;
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use tracing::info;
+use uuid::Uuid;
+
+use lrwn::EUI64;
+
+use super::error::Error;
+use super::schema::audit_log;
+use super::{fields, get_async_db_conn};
+
+#[derive(Queryable, Insertable, PartialEq, Eq, Debug, Clone)]
+#[diesel(table_name = audit_log)]
+pub struct AuditLog {
+    pub id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub user_id: Option<Uuid>,
+    pub api_key_id: Option<Uuid>,
+    pub service: String,
+    pub method: String,
+    pub status_code: String,
+    pub tenant_id: Option<Uuid>,
+    pub application_id: Option<Uuid>,
+    pub dev_eui: Option<EUI64>,
+    pub metadata: fields::KeyValue,
+    pub changes: serde_json::Value,
+}
+
+impl AuditLog {
+    fn validate(&self) -> Result<(), Error> {
+        // Login attempts using an unknown e-mail address can not be attributed.
+        if self.user_id.is_none() && self.api_key_id.is_none() && self.method != "Login" {
+            return Err(Error::Validation(
+                "user_id or api_key_id must be set".into(),
+            ));
+        }
+        if self.method.is_empty() {
+            return Err(Error::Validation("method is not set".into()));
+        }
+        Ok(())
+    }
+}
+
+impl Default for AuditLog {
+    fn default() -> Self {
+        AuditLog {
+            id: Uuid::new_v4(),
+            created_at: Utc::now(),
+            user_id: None,
+            api_key_id: None,
+            service: "".into(),
+            method: "".into(),
+            status_code: "".into(),
+            tenant_id: None,
+            application_id: None,
+            dev_eui: None,
+            metadata: fields::KeyValue::new(HashMap::new()),
+            changes: serde_json::Value::Object(serde_json::Map::new()),
+        }
+    }
+}
+
+#[derive(Default, Clone)]
+pub struct Filters {
+    pub tenant_id: Option<Uuid>,
+    pub application_id: Option<Uuid>,
+    pub dev_eui: Option<EUI64>,
+    pub user_id: Option<Uuid>,
+    pub api_key_id: Option<Uuid>,
+    pub method: Option<String>,
+    pub start: Option<DateTime<Utc>>,
+    pub end: Option<DateTime<Utc>>,
+}
+
+pub async fn create(al: AuditLog) -> Result<AuditLog, Error> {
+    al.validate()?;
+
+    let al: AuditLog = diesel::insert_into(audit_log::table)
+        .values(&al)
+        .get_result(&mut get_async_db_conn().await?)
+        .await
+        .map_err(|e| Error::from_diesel(e, al.id.to_string()))?;
+    Ok(al)
+}
+
+pub async fn get_count(filters: &Filters) -> Result<i64, Error> {
+    let mut q = audit_log::dsl::audit_log
+        .select(dsl::count_star())
+        .into_boxed();
+
+    if let Some(tenant_id) = &filters.tenant_id {
+        q = q.filter(audit_log::dsl::tenant_id.eq(tenant_id));
+    }
+    if let Some(application_id) = &filters.application_id {
+        q = q.filter(audit_log::dsl::application_id.eq(application_id));
+    }
+    if let Some(dev_eui) = &filters.dev_eui {
+        q = q.filter(audit_log::dsl::dev_eui.eq(dev_eui));
+    }
+    if let Some(user_id) = &filters.user_id {
+        q = q.filter(audit_log::dsl::user_id.eq(user_id));
+    }
+    if let Some(api_key_id) = &filters.api_key_id {
+        q = q.filter(audit_log::dsl::api_key_id.eq(api_key_id));
+    }
+    if let Some(method) = &filters.method {
+        q = q.filter(audit_log::dsl::method.eq(method));
+    }
+    if let Some(start) = &filters.start {
+        q = q.filter(audit_log::dsl::created_at.ge(start));
+    }
+    if let Some(end) = &filters.end {
+        q = q.filter(audit_log::dsl::created_at.lt(end));
+    }
+
+    Ok(q.first(&mut get_async_db_conn().await?).await?)
+}
+
+pub async fn list(limit: i64, offset: i64, filters: &Filters) -> Result<Vec<AuditLog>, Error> {
+    let mut q = audit_log::dsl::audit_log.into_boxed();
+
+    if let Some(tenant_id) = &filters.tenant_id {
+        q = q.filter(audit_log::dsl::tenant_id.eq(tenant_id));
+    }
+    if let Some(application_id) = &filters.application_id {
+        q = q.filter(audit_log::dsl::application_id.eq(application_id));
+    }
+    if let Some(dev_eui) = &filters.dev_eui {
+        q = q.filter(audit_log::dsl::dev_eui.eq(dev_eui));
+    }
+    if let Some(user_id) = &filters.user_id {
+        q = q.filter(audit_log::dsl::user_id.eq(user_id));
+    }
+    if let Some(api_key_id) = &filters.api_key_id {
+        q = q.filter(audit_log::dsl::api_key_id.eq(api_key_id));
+    }
+    if let Some(method) = &filters.method {
+        q = q.filter(audit_log::dsl::method.eq(method));
+    }
+    if let Some(start) = &filters.start {
+        q = q.filter(audit_log::dsl::created_at.ge(start));
+    }
+    if let Some(end) = &filters.end {
+        q = q.filter(audit_log::dsl::created_at.lt(end));
+    }
+
+    let items = q
+        .order_by(audit_log::dsl::created_at.desc())
+        .limit(limit)
+        .offset(offset)
+        .load(&mut get_async_db_conn().await?)
+        .await?;
+    Ok(items)
+}
+
+pub async fn delete_older_than(ts: DateTime<Utc>) -> Result<usize, Error> {
+    let ra = diesel::delete(audit_log::dsl::audit_log.filter(audit_log::dsl::created_at.lt(ts)))
+        .execute(&mut get_async_db_conn().await?)
+        .await?;
+    if ra != 0 {
+        info!(count = ra, "Audit log entries deleted");
+    }
+    Ok(ra)
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use crate::test;
+    use chrono::{Duration, SubsecRound};
+    use std::str::FromStr;
+
+    struct FilterTest<'a> {
+        filters: Filters,
+        items: Vec<&'a AuditLog>,
+        count: usize,
+        limit: i64,
+        offset: i64,
+    }
+
+    #[tokio::test]
+    async fn test_audit_log() {
+        let _guard = test::prepare().await;
+
+        let tenant_id = Uuid::new_v4();
+        let user_id = Uuid::new_v4();
+        let api_key_id = Uuid::new_v4();
+        let dev_eui = EUI64::from_str("0102030405060708").unwrap();
+
+        // validation
+        assert!(create(AuditLog {
+            method: "UpdateTenant".into(),
+            ..Default::default()
+        })
+        .await
+        .is_err());
+
+        let al_old = create(AuditLog {
+            created_at: (Utc::now() - Duration::days(2)).round_subsecs(1),
+            user_id: Some(user_id),
+            service: "api.TenantService".into(),
+            method: "UpdateTenant".into(),
+            status_code: "0".into(),
+            tenant_id: Some(tenant_id),
+            changes: serde_json::json!({"name": {"before": "a", "after": "b"}}),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        let al_dev = create(AuditLog {
+            created_at: Utc::now().round_subsecs(1),
+            api_key_id: Some(api_key_id),
+            service: "api.DeviceService".into(),
+            method: "Delete".into(),
+            status_code: "0".into(),
+            tenant_id: Some(tenant_id),
+            dev_eui: Some(dev_eui),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        let tests = vec![
+            FilterTest {
+                filters: Filters::default(),
+                items: vec![&al_dev, &al_old],
+                count: 2,
+                limit: 10,
+                offset: 0,
+            },
+            FilterTest {
+                filters: Filters {
+                    tenant_id: Some(tenant_id),
+                    ..Default::default()
+                },
+                items: vec![&al_dev],
+                count: 2,
+                limit: 1,
+                offset: 0,
+            },
+            FilterTest {
+                filters: Filters {
+                    dev_eui: Some(dev_eui),
+                    ..Default::default()
+                },
+                items: vec![&al_dev],
+                count: 1,
+                limit: 10,
+                offset: 0,
+            },
+            FilterTest {
+                filters: Filters {
+                    user_id: Some(user_id),
+                    ..Default::default()
+                },
+                items: vec![&al_old],
+                count: 1,
+                limit: 10,
+                offset: 0,
+            },
+            FilterTest {
+                filters: Filters {
+                    api_key_id: Some(api_key_id),
+                    method: Some("UpdateTenant".into()),
+                    ..Default::default()
+                },
+                items: vec![],
+                count: 0,
+                limit: 10,
+                offset: 0,
+            },
+            FilterTest {
+                filters: Filters {
+                    start: Some(Utc::now() - Duration::days(1)),
+                    ..Default::default()
+                },
+                items: vec![&al_dev],
+                count: 1,
+                limit: 10,
+                offset: 0,
+            },
+        ];
+
+        for tst in tests {
+            let count = get_count(&tst.filters).await.unwrap() as usize;
+            assert_eq!(tst.count, count);
+
+            let items = list(tst.limit, tst.offset, &tst.filters).await.unwrap();
+            assert_eq!(
+                tst.items
+                    .iter()
+                    .map(|al| al.id.to_string())
+                    .collect::<Vec<String>>(),
+                items
+                    .iter()
+                    .map(|al| al.id.to_string())
+                    .collect::<Vec<String>>()
+            );
+        }
+
+        // retention
+        assert_eq!(
+            1,
+            delete_older_than(Utc::now() - Duration::days(1))
+                .await
+                .unwrap()
+        );
+        assert_eq!(1, get_count(&Filters::default()).await.unwrap());
+    }
+}
@@ -105,6 +105,7 @@ pub struct Device {
     pub join_eui: EUI64,
     pub secondary_dev_addr: Option<DevAddr>,
     pub device_session: Option<internal::DeviceSession>,
+    pub deleted_at: Option<DateTime<Utc>>,
 }
 
 #[derive(AsChangeset, Debug, Clone, Default)]
@@ -179,6 +180,7 @@ impl Default for Device {
             join_eui: EUI64::default(),
             secondary_dev_addr: None,
             device_session: None,
+            deleted_at: None,
         }
     }
 }
@@ -196,6 +198,7 @@ pub struct DeviceListItem {
     pub margin: Option<i32>,
     pub external_power_source: bool,
     pub battery_level: Option<BigDecimal>,
+    pub deleted_at: Option<DateTime<Utc>>,
 }
 
 #[derive(Default, Clone)]
@@ -203,6 +206,8 @@ pub struct Filters {
     pub application_id: Option<Uuid>,
     pub multicast_group_id: Option<Uuid>,
     pub search: Option<String>,
+    // When set, only the (soft) deleted devices are returned.
+    pub deleted: bool,
 }
 
 #[derive(QueryableByName, PartialEq, Eq, Debug)]
@@ -241,9 +246,11 @@ pub async fn create(d: Device) -> Result<Device, Error> {
                         tenant::dsl::private_gateways_up,
                         tenant::dsl::private_gateways_down,
                         tenant::dsl::tags,
+                        tenant::dsl::deleted_at,
                     ))
                     .inner_join(application::table)
                     .filter(application::dsl::id.eq(&d.application_id))
+                    .filter(application::dsl::deleted_at.is_null())
                     .for_update()
                     .first(c)
                     .await?;
@@ -252,6 +259,7 @@ pub async fn create(d: Device) -> Result<Device, Error> {
                     .select(dsl::count_star())
                     .inner_join(application::table)
                     .filter(application::dsl::tenant_id.eq(&t.id))
+                    .filter(device::dsl::deleted_at.is_null())
                     .first(c)
                     .await?;
 
@@ -261,6 +269,16 @@ pub async fn create(d: Device) -> Result<Device, Error> {
                     ));
                 }
 
+                // Purge the soft-deleted device using the same DevEUI (if any), such that
+                // the DevEUI can be re-used.
+                diesel::delete(
+                    device::dsl::device
+                        .find(&d.dev_eui)
+                        .filter(device::dsl::deleted_at.is_not_null()),
+                )
+                .execute(c)
+                .await?;
+
                 diesel::insert_into(device::table)
                     .values(&d)
                     .get_result(c)
@@ -276,6 +294,7 @@ pub async fn create(d: Device) -> Result<Device, Error> {
 pub async fn get(dev_eui: &EUI64) -> Result<Device, Error> {
     let d = device::dsl::device
         .find(&dev_eui)
+        .filter(device::dsl::deleted_at.is_null())
         .first(&mut get_async_db_conn().await?)
         .await
         .map_err(|e| Error::from_diesel(e, dev_eui.to_string()))?;
@@ -314,6 +333,7 @@ pub async fn get_for_phypayload_and_incr_f_cnt_up(
                             .or(device::dsl::secondary_dev_addr.eq(&dev_addr)),
                     )
                     .filter(device::dsl::is_disabled.eq(false))
+                    .filter(device::dsl::deleted_at.is_null())
                     .for_update()
                     .load(c)
                     .await?;
@@ -449,6 +469,7 @@ pub async fn get_for_phypayload(
                 .or(device::dsl::secondary_dev_addr.eq(&dev_addr)),
         )
         .filter(device::dsl::is_disabled.eq(false))
+        .filter(device::dsl::deleted_at.is_null())
         .load(&mut get_async_db_conn().await?)
         .await?;
 
@@ -506,6 +527,21 @@ pub async fn get_for_phypayload(
     Err(Error::InvalidMIC)
 }
 
+// Return all the devices for which the device-session (or secondary device-session) matches
+// the given DevAddr. Unlike get_for_phypayload, this does not validate the MIC or frame-counter.
+pub async fn get_by_dev_addr(dev_addr: DevAddr) -> Result<Vec<Device>, Error> {
+    let devices: Vec<Device> = device::dsl::device
+        .filter(
+            device::dsl::dev_addr
+                .eq(&dev_addr)
+                .or(device::dsl::secondary_dev_addr.eq(&dev_addr)),
+        )
+        .filter(device::dsl::deleted_at.is_null())
+        .load(&mut get_async_db_conn().await?)
+        .await?;
+    Ok(devices)
+}
+
 pub async fn update(d: Device) -> Result<Device, Error> {
     d.validate()?;
 
@@ -540,17 +576,85 @@ pub async fn partial_update(dev_eui: EUI64, d: &DeviceChangeset) -> Result<Devic
     Ok(d)
 }
 
+// Deletes the device. When soft-delete is enabled, the device is marked as deleted and is
+// permanently removed by the purge loop after the retention period.
 pub async fn delete(dev_eui: &EUI64) -> Result<(), Error> {
+    let conf = config::get();
+    if conf.soft_delete.retention.is_zero() {
+        return purge(dev_eui).await;
+    }
+
+    let ra = diesel::update(
+        device::dsl::device
+            .find(&dev_eui)
+            .filter(device::dsl::deleted_at.is_null()),
+    )
+    .set(device::deleted_at.eq(Utc::now()))
+    .execute(&mut get_async_db_conn().await?)
+    .await?;
+    if ra == 0 {
+        return Err(Error::NotFound(dev_eui.to_string()));
+    }
+    info!(dev_eui = %dev_eui, "Device deleted");
+    Ok(())
+}
+
+// Restores the (soft) deleted device. This is not allowed when the application is deleted.
+pub async fn restore(dev_eui: &EUI64) -> Result<(), Error> {
+    let mut c = get_async_db_conn().await?;
+    c.build_transaction()
+        .run::<(), Error, _>(|c| {
+            Box::pin(async move {
+                let (deleted_at, app_deleted_at): (Option<DateTime<Utc>>, Option<DateTime<Utc>>) =
+                    device::dsl::device
+                        .inner_join(application::table)
+                        .select((device::dsl::deleted_at, application::dsl::deleted_at))
+                        .filter(device::dsl::dev_eui.eq(&dev_eui))
+                        .for_update()
+                        .first(c)
+                        .await
+                        .map_err(|e| Error::from_diesel(e, dev_eui.to_string()))?;
+
+                if deleted_at.is_none() {
+                    return Err(Error::NotFound(dev_eui.to_string()));
+                }
+                if app_deleted_at.is_some() {
+                    return Err(Error::NotAllowed("Application is deleted".into()));
+                }
+
+                diesel::update(device::dsl::device.find(&dev_eui))
+                    .set(device::deleted_at.eq(None::<DateTime<Utc>>))
+                    .execute(c)
+                    .await?;
+
+                Ok(())
+            })
+        })
+        .await?;
+    info!(dev_eui = %dev_eui, "Device restored");
+    Ok(())
+}
+
+// Permanently deletes the device.
+pub async fn purge(dev_eui: &EUI64) -> Result<(), Error> {
     let ra = diesel::delete(device::dsl::device.find(&dev_eui))
         .execute(&mut get_async_db_conn().await?)
         .await?;
     if ra == 0 {
         return Err(Error::NotFound(dev_eui.to_string()));
     }
-    info!(dev_eui = %dev_eui, "Device deleted");
+    info!(dev_eui = %dev_eui, "Device purged");
     Ok(())
 }
 
+// Permanently deletes the devices that were (soft) deleted before the given timestamp.
+pub async fn purge_deleted(before: DateTime<Utc>) -> Result<usize, Error> {
+    let ra = diesel::delete(device::dsl::device.filter(device::dsl::deleted_at.lt(before)))
+        .execute(&mut get_async_db_conn().await?)
+        .await?;
+    Ok(ra)
+}
+
 pub async fn get_count(filters: &Filters) -> Result<i64, Error> {
     let mut q = device::dsl::device
         .select(dsl::count_star())
@@ -570,6 +674,12 @@ pub async fn get_count(filters: &Filters) -> Result<i64, Error> {
         q = q.filter(multicast_group_device::dsl::multicast_group_id.eq(multicast_group_id));
     }
 
+    if filters.deleted {
+        q = q.filter(device::dsl::deleted_at.is_not_null());
+    } else {
+        q = q.filter(device::dsl::deleted_at.is_null());
+    }
+
     Ok(q.first(&mut get_async_db_conn().await?).await?)
 }
 
@@ -593,6 +703,7 @@ pub async fn list(
             device::margin,
             device::external_power_source,
             device::battery_level,
+            device::deleted_at,
         ))
         .distinct()
         .into_boxed();
@@ -609,6 +720,12 @@ pub async fn list(
         q = q.filter(multicast_group_device::dsl::multicast_group_id.eq(multicast_group_id));
     }
 
+    if filters.deleted {
+        q = q.filter(device::dsl::deleted_at.is_not_null());
+    } else {
+        q = q.filter(device::dsl::deleted_at.is_null());
+    }
+
     q.order_by(device::dsl::name)
         .limit(limit)
         .offset(offset)
@@ -628,7 +745,8 @@ pub async fn get_active_inactive(tenant_id: &Option<Uuid>) -> Result<DevicesActi
             inner join device_profile dp
                 on d.device_profile_id = dp.id
             where
-                $1 is null or dp.tenant_id = $1
+                d.deleted_at is null
+                and ($1 is null or dp.tenant_id = $1)
         )
         select
             coalesce(sum(case when last_seen_at is null then 1 end), 0) as never_seen_count,
@@ -652,6 +770,7 @@ pub async fn get_data_rates(tenant_id: &Option<Uuid>) -> Result<Vec<DevicesDataR
         ))
         .group_by(device::dr)
         .filter(device::dsl::dr.is_not_null())
+        .filter(device::dsl::deleted_at.is_null())
         .into_boxed();
 
     if let Some(id) = &tenant_id {
@@ -701,6 +820,7 @@ pub async fn get_with_class_b_c_queue_items(limit: usize) -> Result<Vec<Device>>
                                 d.enabled_class in ('B', 'C')
                                 and (d.scheduler_run_after is null or d.scheduler_run_after < $2)
                                 and d.is_disabled = false
+                                and d.deleted_at is null
                                 and exists (
                                     select
                                         1
@@ -745,9 +865,13 @@ pub async fn get_with_class_b_c_queue_items(limit: usize) -> Result<Vec<Device>>
 // * In case of a re-transmission, the returned frame-counter equals
 //   DeviceSession.FCntUp - 1, as the FCntUp value holds the next expected
 //   frame-counter, not the FCntUp which was last seen.
-fn get_full_f_cnt_up(next_expected_full_fcnt: u32, truncated_f_cnt: u32) -> u32 {
-    // Handle re-transmission.
-    if truncated_f_cnt == (((next_expected_full_fcnt % (1 << 16)) as u16).wrapping_sub(1)) as u32 {
+pub fn get_full_f_cnt_up(next_expected_full_fcnt: u32, truncated_f_cnt: u32) -> u32 {
+    // Handle re-transmission. There is no previous frame when the next expected frame-counter
+    // is 0.
+    if next_expected_full_fcnt > 0
+        && truncated_f_cnt
+            == (((next_expected_full_fcnt % (1 << 16)) as u16).wrapping_sub(1)) as u32
+    {
         return next_expected_full_fcnt - 1;
     }
 
@@ -828,6 +952,7 @@ pub mod test {
                     application_id: None,
                     multicast_group_id: None,
                     search: None,
+                    deleted: false,
                 },
                 devs: vec![&d],
                 count: 1,
@@ -839,6 +964,7 @@ pub mod test {
                     application_id: None,
                     multicast_group_id: None,
                     search: Some("uup".into()),
+                    deleted: false,
                 },
                 devs: vec![],
                 count: 0,
@@ -850,6 +976,7 @@ pub mod test {
                     application_id: None,
                     multicast_group_id: None,
                     search: Some("upd".into()),
+                    deleted: false,
                 },
                 devs: vec![&d],
                 count: 1,
@@ -861,6 +988,7 @@ pub mod test {
                     application_id: Some(d.application_id),
                     multicast_group_id: None,
                     search: None,
+                    deleted: false,
                 },
                 devs: vec![&d],
                 count: 1,
@@ -872,6 +1000,7 @@ pub mod test {
                     application_id: Some(Uuid::new_v4()),
                     multicast_group_id: None,
                     search: None,
+                    deleted: false,
                 },
                 devs: vec![],
                 count: 0,
@@ -900,6 +1029,51 @@ pub mod test {
         // delete
         delete(&d.dev_eui).await.unwrap();
         assert_eq!(true, delete(&d.dev_eui).await.is_err());
+        assert_eq!(true, get(&d.dev_eui).await.is_err());
+
+        // list deleted
+        let filters = Filters {
+            application_id: Some(d.application_id),
+            deleted: true,
+            ..Default::default()
+        };
+        assert_eq!(1, get_count(&filters).await.unwrap());
+        let items = list(10, 0, &filters).await.unwrap();
+        assert_eq!(d.dev_eui, items[0].dev_eui);
+        assert!(items[0].deleted_at.is_some());
+
+        // restore
+        restore(&d.dev_eui).await.unwrap();
+        assert_eq!(true, restore(&d.dev_eui).await.is_err());
+        assert_eq!(d, get(&d.dev_eui).await.unwrap());
+
+        // restore is not allowed when the application is deleted
+        delete(&d.dev_eui).await.unwrap();
+        storage::application::delete(&d.application_id)
+            .await
+            .unwrap();
+        assert!(matches!(
+            restore(&d.dev_eui).await,
+            Err(Error::NotAllowed(_))
+        ));
+
+        // restoring the application does not restore the device, as it was deleted before
+        storage::application::restore(&d.application_id)
+            .await
+            .unwrap();
+        assert_eq!(true, get(&d.dev_eui).await.is_err());
+        restore(&d.dev_eui).await.unwrap();
+
+        // creating a device with the DevEUI of a deleted device replaces the deleted device
+        delete(&d.dev_eui).await.unwrap();
+        create(d.clone()).await.unwrap();
+        assert_eq!(0, get_count(&filters).await.unwrap());
+        assert_eq!(d, get(&d.dev_eui).await.unwrap());
+
+        // purge
+        delete(&d.dev_eui).await.unwrap();
+        assert_eq!(1, purge_deleted(Utc::now()).await.unwrap());
+        assert_eq!(true, restore(&d.dev_eui).await.is_err());
     }
 
     #[tokio::test]
@@ -1015,6 +1189,7 @@ pub mod test {
             ((1 << 16) + 1, 0, (1 << 16)),             // re-transmission of previous frame
             ((1 << 16), (1 << 16) - 1, (1 << 16) - 1), // re-transmission of previous frame
             (u32::MAX, 0, 0),                          // 32bit frame-counter rollover
+            (0, (1 << 16) - 1, (1 << 16) - 1),         // no previous frame
         ];
 
         for (i, tst) in tests.iter().enumerate() {
@@ -74,6 +74,7 @@ pub struct DeviceProfile {
     pub relay_overall_limit_bucket_size: i16,
     pub allow_roaming: bool,
     pub rx1_delay: i16,
+    pub lr_fhss_rx_window: i16,
 }
 
 impl DeviceProfile {
@@ -86,6 +87,12 @@ impl DeviceProfile {
             return Err(Error::Validation("RX1 Delay must be between 0 - 15".into()));
         }
 
+        if self.lr_fhss_rx_window < 0 || self.lr_fhss_rx_window > 2 {
+            return Err(Error::Validation(
+                "LR-FHSS RX window must be between 0 - 2".into(),
+            ));
+        }
+
         Ok(())
     }
 }
@@ -150,6 +157,7 @@ impl Default for DeviceProfile {
             relay_overall_limit_bucket_size: 0,
             allow_roaming: false,
             rx1_delay: 0,
+            lr_fhss_rx_window: 0,
         }
     }
 }
@@ -287,6 +295,7 @@ pub async fn update(dp: DeviceProfile) -> Result<DeviceProfile, Error> {
             device_profile::relay_overall_limit_bucket_size.eq(&dp.relay_overall_limit_bucket_size),
             device_profile::allow_roaming.eq(&dp.allow_roaming),
             device_profile::rx1_delay.eq(&dp.rx1_delay),
+            device_profile::lr_fhss_rx_window.eq(&dp.lr_fhss_rx_window),
         ))
         .get_result(&mut get_async_db_conn().await?)
         .await
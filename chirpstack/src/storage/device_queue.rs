@@ -23,6 +23,7 @@ pub struct DeviceQueueItem {
     pub f_cnt_down: Option<i64>,
     pub timeout_after: Option<DateTime<Utc>>,
     pub is_encrypted: bool,
+    pub correlation_id: String,
 }
 
 impl DeviceQueueItem {
@@ -39,6 +40,12 @@ impl DeviceQueueItem {
             ));
         }
 
+        if self.correlation_id.len() > 100 {
+            return Err(Error::Validation(
+                "Correlation ID must not exceed 100 characters".to_string(),
+            ));
+        }
+
         Ok(())
     }
 }
@@ -58,6 +65,7 @@ impl Default for DeviceQueueItem {
             f_cnt_down: None,
             timeout_after: None,
             is_encrypted: false,
+            correlation_id: "".into(),
         }
     }
 }
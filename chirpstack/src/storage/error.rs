@@ -18,6 +18,12 @@ pub enum Error {
     #[error("Invalid username or password")]
     InvalidUsernameOrPassword,
 
+    #[error("User is temporarily locked because of too many failed login attempts")]
+    UserLocked,
+
+    #[error("Invalid MFA code")]
+    InvalidMfaCode,
+
     #[error("Invalid type (expected: {0})")]
     InvalidPayload(String),
 
@@ -7,12 +7,13 @@ use diesel_async::RunQueryDsl;
 use tracing::info;
 use uuid::Uuid;
 
-use lrwn::EUI64;
+use lrwn::{AES128Key, EUI64};
 
 use super::schema::{gateway, multicast_group_gateway, tenant};
 use super::{error::Error, fields, get_async_db_conn};
+use crate::config;
 
-#[derive(Queryable, Insertable, PartialEq, Debug)]
+#[derive(Queryable, Insertable, PartialEq, Debug, Clone)]
 #[diesel(table_name = gateway)]
 pub struct Gateway {
     pub gateway_id: EUI64,
@@ -29,6 +30,8 @@ pub struct Gateway {
     pub tls_certificate: Option<Vec<u8>>,
     pub tags: fields::KeyValue,
     pub properties: fields::KeyValue,
+    pub fine_timestamp_key: Option<AES128Key>,
+    pub deleted_at: Option<DateTime<Utc>>,
 }
 
 impl Gateway {
@@ -65,6 +68,7 @@ pub struct GatewayListItem {
     pub altitude: f32,
     pub properties: fields::KeyValue,
     pub stats_interval_secs: i32,
+    pub deleted_at: Option<DateTime<Utc>>,
 }
 
 #[derive(Queryable, PartialEq, Debug)]
@@ -76,6 +80,7 @@ pub struct GatewayMeta {
     pub altitude: f32,
     pub is_private_up: bool,
     pub is_private_down: bool,
+    pub fine_timestamp_key: Option<AES128Key>,
 }
 
 #[derive(Default, Clone)]
@@ -83,6 +88,8 @@ pub struct Filters {
     pub tenant_id: Option<Uuid>,
     pub multicast_group_id: Option<Uuid>,
     pub search: Option<String>,
+    // When set, only the (soft) deleted gateways are returned.
+    pub deleted: bool,
 }
 
 #[derive(QueryableByName, PartialEq, Eq, Debug)]
@@ -114,6 +121,8 @@ impl Default for Gateway {
             stats_interval_secs: 30,
             tags: fields::KeyValue::new(HashMap::new()),
             properties: fields::KeyValue::new(HashMap::new()),
+            fine_timestamp_key: None,
+            deleted_at: None,
         }
     }
 }
@@ -128,6 +137,7 @@ pub async fn create(gw: Gateway) -> Result<Gateway, Error> {
                 // use for_update to lock the tenant.
                 let t: super::tenant::Tenant = tenant::dsl::tenant
                     .find(&gw.tenant_id)
+                    .filter(tenant::dsl::deleted_at.is_null())
                     .for_update()
                     .get_result(c)
                     .await
@@ -140,6 +150,7 @@ pub async fn create(gw: Gateway) -> Result<Gateway, Error> {
                 let gw_count: i64 = gateway::dsl::gateway
                     .select(dsl::count_star())
                     .filter(gateway::dsl::tenant_id.eq(&gw.tenant_id))
+                    .filter(gateway::dsl::deleted_at.is_null())
                     .first(c)
                     .await?;
 
@@ -149,6 +160,16 @@ pub async fn create(gw: Gateway) -> Result<Gateway, Error> {
                     ));
                 }
 
+                // Purge the soft-deleted gateway using the same Gateway ID (if any), such that
+                // the Gateway ID can be re-used.
+                diesel::delete(
+                    gateway::dsl::gateway
+                        .find(&gw.gateway_id)
+                        .filter(gateway::dsl::deleted_at.is_not_null()),
+                )
+                .execute(c)
+                .await?;
+
                 diesel::insert_into(gateway::table)
                     .values(&gw)
                     .get_result(c)
@@ -167,6 +188,7 @@ pub async fn create(gw: Gateway) -> Result<Gateway, Error> {
 pub async fn get(gateway_id: &EUI64) -> Result<Gateway, Error> {
     let gw = gateway::dsl::gateway
         .find(&gateway_id)
+        .filter(gateway::dsl::deleted_at.is_null())
         .first(&mut get_async_db_conn().await?)
         .await
         .map_err(|e| Error::from_diesel(e, gateway_id.to_string()))?;
@@ -186,6 +208,7 @@ pub async fn update(gw: Gateway) -> Result<Gateway, Error> {
             gateway::altitude.eq(&gw.altitude),
             gateway::stats_interval_secs.eq(&gw.stats_interval_secs),
             gateway::tags.eq(&gw.tags),
+            gateway::fine_timestamp_key.eq(&gw.fine_timestamp_key),
         ))
         .get_result(&mut get_async_db_conn().await?)
         .await
@@ -198,17 +221,89 @@ pub async fn update(gw: Gateway) -> Result<Gateway, Error> {
 }
 
 pub async fn partial_update(gateway_id: EUI64, gw: &GatewayChangeset) -> Result<Gateway, Error> {
-    let gw = diesel::update(gateway::dsl::gateway.find(&gateway_id))
-        .set(gw)
-        .get_result::<Gateway>(&mut get_async_db_conn().await?)
-        .await
-        .map_err(|e| Error::from_diesel(e, gateway_id.to_string()))?;
+    let gw = diesel::update(
+        gateway::dsl::gateway
+            .find(&gateway_id)
+            .filter(gateway::dsl::deleted_at.is_null()),
+    )
+    .set(gw)
+    .get_result::<Gateway>(&mut get_async_db_conn().await?)
+    .await
+    .map_err(|e| Error::from_diesel(e, gateway_id.to_string()))?;
 
     info!(gateway_id = %gateway_id, "Gateway partially updated");
     Ok(gw)
 }
 
+// Deletes the gateway. When soft-delete is enabled, the gateway is marked as deleted and is
+// permanently removed by the purge loop after the retention period.
 pub async fn delete(gateway_id: &EUI64) -> Result<(), Error> {
+    let conf = config::get();
+    if conf.soft_delete.retention.is_zero() {
+        return purge(gateway_id).await;
+    }
+
+    let ra = diesel::update(
+        gateway::dsl::gateway
+            .find(&gateway_id)
+            .filter(gateway::dsl::deleted_at.is_null()),
+    )
+    .set(gateway::deleted_at.eq(Utc::now()))
+    .execute(&mut get_async_db_conn().await?)
+    .await?;
+    if ra == 0 {
+        return Err(Error::NotFound(gateway_id.to_string()));
+    }
+    info!(
+        gateway_id = %gateway_id,
+        "Gateway deleted"
+    );
+    Ok(())
+}
+
+// Restores the (soft) deleted gateway. This is not allowed when the tenant is deleted.
+pub async fn restore(gateway_id: &EUI64) -> Result<(), Error> {
+    let mut c = get_async_db_conn().await?;
+    c.build_transaction()
+        .run::<(), Error, _>(|c| {
+            Box::pin(async move {
+                let (deleted_at, tenant_deleted_at): (
+                    Option<DateTime<Utc>>,
+                    Option<DateTime<Utc>>,
+                ) = gateway::dsl::gateway
+                    .inner_join(tenant::table)
+                    .select((gateway::dsl::deleted_at, tenant::dsl::deleted_at))
+                    .filter(gateway::dsl::gateway_id.eq(&gateway_id))
+                    .for_update()
+                    .first(c)
+                    .await
+                    .map_err(|e| Error::from_diesel(e, gateway_id.to_string()))?;
+
+                if deleted_at.is_none() {
+                    return Err(Error::NotFound(gateway_id.to_string()));
+                }
+                if tenant_deleted_at.is_some() {
+                    return Err(Error::NotAllowed("Tenant is deleted".into()));
+                }
+
+                diesel::update(gateway::dsl::gateway.find(&gateway_id))
+                    .set(gateway::deleted_at.eq(None::<DateTime<Utc>>))
+                    .execute(c)
+                    .await?;
+
+                Ok(())
+            })
+        })
+        .await?;
+    info!(
+        gateway_id = %gateway_id,
+        "Gateway restored"
+    );
+    Ok(())
+}
+
+// Permanently deletes the gateway.
+pub async fn purge(gateway_id: &EUI64) -> Result<(), Error> {
     let ra = diesel::delete(gateway::dsl::gateway.find(&gateway_id))
         .execute(&mut get_async_db_conn().await?)
         .await?;
@@ -217,11 +312,19 @@ pub async fn delete(gateway_id: &EUI64) -> Result<(), Error> {
     }
     info!(
         gateway_id = %gateway_id,
-        "Gateway deleted"
+        "Gateway purged"
     );
     Ok(())
 }
 
+// Permanently deletes the gateways that were (soft) deleted before the given timestamp.
+pub async fn purge_deleted(before: DateTime<Utc>) -> Result<usize, Error> {
+    let ra = diesel::delete(gateway::dsl::gateway.filter(gateway::dsl::deleted_at.lt(before)))
+        .execute(&mut get_async_db_conn().await?)
+        .await?;
+    Ok(ra)
+}
+
 pub async fn get_count(filters: &Filters) -> Result<i64, Error> {
     let mut q = gateway::dsl::gateway
         .select(dsl::count_star())
@@ -241,6 +344,12 @@ pub async fn get_count(filters: &Filters) -> Result<i64, Error> {
         q = q.filter(gateway::dsl::name.ilike(format!("%{}%", search)));
     }
 
+    if filters.deleted {
+        q = q.filter(gateway::dsl::deleted_at.is_not_null());
+    } else {
+        q = q.filter(gateway::dsl::deleted_at.is_null());
+    }
+
     Ok(q.first(&mut get_async_db_conn().await?).await?)
 }
 
@@ -264,6 +373,7 @@ pub async fn list(
             gateway::altitude,
             gateway::properties,
             gateway::stats_interval_secs,
+            gateway::deleted_at,
         ))
         .distinct()
         .into_boxed();
@@ -276,6 +386,12 @@ pub async fn list(
         q = q.filter(gateway::dsl::name.ilike(format!("%{}%", search)));
     }
 
+    if filters.deleted {
+        q = q.filter(gateway::dsl::deleted_at.is_not_null());
+    } else {
+        q = q.filter(gateway::dsl::deleted_at.is_null());
+    }
+
     if let Some(multicast_group_id) = &filters.multicast_group_id {
         q = q.filter(multicast_group_gateway::dsl::multicast_group_id.eq(multicast_group_id));
     }
@@ -300,8 +416,10 @@ pub async fn get_meta(gateway_id: &EUI64) -> Result<GatewayMeta, Error> {
             gateway::altitude,
             tenant::private_gateways_up,
             tenant::private_gateways_down,
+            gateway::fine_timestamp_key,
         ))
         .filter(gateway::dsl::gateway_id.eq(&gateway_id))
+        .filter(gateway::dsl::deleted_at.is_null())
         .first(&mut get_async_db_conn().await?)
         .await
         .map_err(|e| Error::from_diesel(e, gateway_id.to_string()))?;
@@ -317,7 +435,8 @@ pub async fn get_counts_by_state(tenant_id: &Option<Uuid>) -> Result<GatewayCoun
         from
             gateway
         where
-            $1 is null or tenant_id = $1
+            deleted_at is null
+            and ($1 is null or tenant_id = $1)
     "#).bind::<diesel::sql_types::Nullable<diesel::sql_types::Uuid>, _>(tenant_id).get_result(&mut get_async_db_conn().await?).await?;
     Ok(counts)
 }
@@ -403,6 +522,7 @@ pub mod test {
                     tenant_id: None,
                     multicast_group_id: None,
                     search: None,
+                    deleted: false,
                 },
                 gws: vec![&gw],
                 count: 1,
@@ -414,6 +534,7 @@ pub mod test {
                     tenant_id: None,
                     multicast_group_id: None,
                     search: Some("uup".into()),
+                    deleted: false,
                 },
                 gws: vec![],
                 count: 0,
@@ -425,6 +546,7 @@ pub mod test {
                     tenant_id: None,
                     multicast_group_id: None,
                     search: Some("upd".into()),
+                    deleted: false,
                 },
                 gws: vec![&gw],
                 count: 1,
@@ -436,6 +558,7 @@ pub mod test {
                     tenant_id: Some(gw.tenant_id),
                     multicast_group_id: None,
                     search: None,
+                    deleted: false,
                 },
                 gws: vec![&gw],
                 count: 1,
@@ -447,6 +570,7 @@ pub mod test {
                     tenant_id: Some(Uuid::new_v4()),
                     multicast_group_id: None,
                     search: None,
+                    deleted: false,
                 },
                 gws: vec![],
                 count: 0,
@@ -458,6 +582,7 @@ pub mod test {
                     tenant_id: None,
                     multicast_group_id: Some(mg.id),
                     search: None,
+                    deleted: false,
                 },
                 gws: vec![&gw],
                 count: 1,
@@ -469,6 +594,7 @@ pub mod test {
                     tenant_id: None,
                     multicast_group_id: Some(Uuid::new_v4()),
                     search: None,
+                    deleted: false,
                 },
                 gws: vec![],
                 count: 0,
@@ -497,5 +623,34 @@ pub mod test {
         // delete
         delete(&gw.gateway_id).await.unwrap();
         assert_eq!(true, delete(&gw.gateway_id).await.is_err());
+        assert_eq!(true, get(&gw.gateway_id).await.is_err());
+        assert_eq!(true, get_meta(&gw.gateway_id).await.is_err());
+
+        // list deleted
+        let filters = Filters {
+            tenant_id: Some(gw.tenant_id),
+            deleted: true,
+            ..Default::default()
+        };
+        assert_eq!(1, get_count(&filters).await.unwrap());
+        let items = list(10, 0, &filters).await.unwrap();
+        assert_eq!(gw.gateway_id, items[0].gateway_id);
+        assert!(items[0].deleted_at.is_some());
+
+        // restore
+        restore(&gw.gateway_id).await.unwrap();
+        assert_eq!(true, restore(&gw.gateway_id).await.is_err());
+        assert_eq!(gw, get(&gw.gateway_id).await.unwrap());
+
+        // creating a gateway with the ID of a deleted gateway replaces the deleted gateway
+        delete(&gw.gateway_id).await.unwrap();
+        create(gw.clone()).await.unwrap();
+        assert_eq!(0, get_count(&filters).await.unwrap());
+        assert_eq!(gw, get(&gw.gateway_id).await.unwrap());
+
+        // purge
+        delete(&gw.gateway_id).await.unwrap();
+        assert_eq!(1, purge_deleted(Utc::now()).await.unwrap());
+        assert_eq!(true, restore(&gw.gateway_id).await.is_err());
     }
 }
@@ -16,6 +16,7 @@ pub async fn get_all_device_data(
         .inner_join(tenant::table.on(application::dsl::tenant_id.eq(tenant::dsl::id)))
         .inner_join(device_profile::table)
         .filter(device::dsl::dev_eui.eq(&dev_eui))
+        .filter(device::dsl::deleted_at.is_null())
         .first::<(Device, Application, Tenant, DeviceProfile)>(&mut get_async_db_conn().await?)
         .await
         .map_err(|e| Error::from_diesel(e, dev_eui.to_string()))?;
@@ -0,0 +1,412 @@
+use std::collections::HashMap;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use diesel::dsl;
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use uuid::Uuid;
+
+use super::application::IntegrationKind;
+use super::error::Error;
+use super::schema::integration_outbox;
+use super::{fields, get_async_db_conn};
+use crate::config;
+
+#[derive(Queryable, QueryableByName, Insertable, PartialEq, Eq, Debug, Clone)]
+#[diesel(table_name = integration_outbox)]
+pub struct OutboxItem {
+    pub id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub application_id: Uuid,
+    pub kind: IntegrationKind,
+    pub event: String,
+    pub vars: fields::KeyValue,
+    pub payload: Vec<u8>,
+    pub attempts: i32,
+    pub next_attempt_at: DateTime<Utc>,
+    pub last_error: String,
+    pub dead_at: Option<DateTime<Utc>>,
+}
+
+impl OutboxItem {
+    fn validate(&self) -> Result<(), Error> {
+        if self.event.is_empty() {
+            return Err(Error::Validation("event is not set".into()));
+        }
+        Ok(())
+    }
+}
+
+impl Default for OutboxItem {
+    fn default() -> Self {
+        let now = Utc::now();
+
+        OutboxItem {
+            id: Uuid::new_v4(),
+            created_at: now,
+            application_id: Uuid::nil(),
+            kind: IntegrationKind::Http,
+            event: "".into(),
+            vars: fields::KeyValue::new(HashMap::new()),
+            payload: Vec::new(),
+            attempts: 0,
+            next_attempt_at: now,
+            last_error: "".into(),
+            dead_at: None,
+        }
+    }
+}
+
+#[derive(Default, Clone)]
+pub struct Filters {
+    pub application_id: Option<Uuid>,
+    pub kinds: Vec<IntegrationKind>,
+}
+
+#[derive(QueryableByName, Debug, PartialEq, Eq)]
+pub struct Backlog {
+    #[diesel(sql_type = diesel::sql_types::Text)]
+    pub kind: IntegrationKind,
+    #[diesel(sql_type = diesel::sql_types::BigInt)]
+    pub pending: i64,
+    #[diesel(sql_type = diesel::sql_types::BigInt)]
+    pub dead: i64,
+}
+
+pub async fn create(item: OutboxItem) -> Result<OutboxItem, Error> {
+    item.validate()?;
+
+    let item: OutboxItem = diesel::insert_into(integration_outbox::table)
+        .values(&item)
+        .get_result(&mut get_async_db_conn().await?)
+        .await
+        .map_err(|e| Error::from_diesel(e, item.id.to_string()))?;
+    Ok(item)
+}
+
+pub async fn get(id: &Uuid) -> Result<OutboxItem, Error> {
+    let item = integration_outbox::dsl::integration_outbox
+        .find(&id)
+        .first(&mut get_async_db_conn().await?)
+        .await
+        .map_err(|e| Error::from_diesel(e, id.to_string()))?;
+    Ok(item)
+}
+
+// Returns the items for which the next attempt is due. The returned items are locked for the
+// configured lock_duration, such that other instances will not retry the same items.
+pub async fn get_pending(limit: usize) -> Result<Vec<OutboxItem>> {
+    let mut c = get_async_db_conn().await?;
+    c.build_transaction()
+        .run::<Vec<OutboxItem>, Error, _>(|c| {
+            Box::pin(async {
+                let conf = config::get();
+
+                // See device::get_with_class_b_c_queue_items for why the next_attempt_at is
+                // updated instead of keeping the rows locked until the batch has been processed.
+                diesel::sql_query(
+                    r#"
+                    update
+                        integration_outbox
+                    set
+                        next_attempt_at = $3
+                    where
+                        id in (
+                            select
+                                id
+                            from
+                                integration_outbox
+                            where
+                                dead_at is null
+                                and next_attempt_at <= $2
+                            order by next_attempt_at
+                            limit $1
+                            for update skip locked
+                        )
+                    returning *
+                "#,
+                )
+                .bind::<diesel::sql_types::Integer, _>(limit as i32)
+                .bind::<diesel::sql_types::Timestamptz, _>(Utc::now())
+                .bind::<diesel::sql_types::Timestamptz, _>(
+                    Utc::now() + Duration::from_std(conf.integration.outbox.lock_duration).unwrap(),
+                )
+                .load(c)
+                .await
+                .map_err(|e| Error::from_diesel(e, "".into()))
+            })
+        })
+        .await
+        .context("Get pending integration outbox items transaction")
+}
+
+// Registers a failed delivery attempt. The next attempt is scheduled using an exponential
+// backoff. When the max. number of attempts has been reached, the item is dead-lettered.
+pub async fn set_failed(id: &Uuid, error: &str) -> Result<OutboxItem, Error> {
+    let conf = config::get();
+    let item = get(id).await?;
+
+    let attempts = item.attempts + 1;
+    let backoff = conf
+        .integration
+        .outbox
+        .initial_backoff
+        .saturating_mul(2_u32.saturating_pow(attempts as u32 - 1))
+        .min(conf.integration.outbox.max_backoff);
+    let now = Utc::now();
+
+    let item: OutboxItem = diesel::update(integration_outbox::dsl::integration_outbox.find(&id))
+        .set((
+            integration_outbox::attempts.eq(attempts),
+            integration_outbox::next_attempt_at
+                .eq(now + Duration::from_std(backoff).map_err(anyhow::Error::new)?),
+            integration_outbox::last_error.eq(error),
+            integration_outbox::dead_at.eq(
+                if attempts as u32 >= conf.integration.outbox.max_attempts {
+                    Some(now)
+                } else {
+                    None
+                },
+            ),
+        ))
+        .get_result(&mut get_async_db_conn().await?)
+        .await
+        .map_err(|e| Error::from_diesel(e, id.to_string()))?;
+    Ok(item)
+}
+
+pub async fn delete(id: &Uuid) -> Result<(), Error> {
+    let ra = diesel::delete(integration_outbox::dsl::integration_outbox.find(&id))
+        .execute(&mut get_async_db_conn().await?)
+        .await?;
+    if ra == 0 {
+        return Err(Error::NotFound(id.to_string()));
+    }
+    Ok(())
+}
+
+pub async fn get_dead_count(filters: &Filters) -> Result<i64, Error> {
+    let mut q = integration_outbox::dsl::integration_outbox
+        .select(dsl::count_star())
+        .filter(integration_outbox::dsl::dead_at.is_not_null())
+        .into_boxed();
+
+    if let Some(application_id) = &filters.application_id {
+        q = q.filter(integration_outbox::dsl::application_id.eq(application_id));
+    }
+    if !filters.kinds.is_empty() {
+        q = q.filter(integration_outbox::dsl::kind.eq_any(&filters.kinds));
+    }
+
+    Ok(q.first(&mut get_async_db_conn().await?).await?)
+}
+
+pub async fn list_dead(
+    limit: i64,
+    offset: i64,
+    filters: &Filters,
+) -> Result<Vec<OutboxItem>, Error> {
+    let mut q = integration_outbox::dsl::integration_outbox
+        .filter(integration_outbox::dsl::dead_at.is_not_null())
+        .into_boxed();
+
+    if let Some(application_id) = &filters.application_id {
+        q = q.filter(integration_outbox::dsl::application_id.eq(application_id));
+    }
+    if !filters.kinds.is_empty() {
+        q = q.filter(integration_outbox::dsl::kind.eq_any(&filters.kinds));
+    }
+
+    let items = q
+        .order_by(integration_outbox::dsl::created_at)
+        .limit(limit)
+        .offset(offset)
+        .load(&mut get_async_db_conn().await?)
+        .await?;
+    Ok(items)
+}
+
+// Moves the dead-lettered items matching the filters back to the outbox, such that they will be
+// retried on the next interval. It returns the number of replayed items.
+pub async fn replay_dead(filters: &Filters) -> Result<usize, Error> {
+    let mut q = diesel::update(integration_outbox::dsl::integration_outbox)
+        .filter(integration_outbox::dsl::dead_at.is_not_null())
+        .into_boxed();
+
+    if let Some(application_id) = &filters.application_id {
+        q = q.filter(integration_outbox::dsl::application_id.eq(application_id));
+    }
+    if !filters.kinds.is_empty() {
+        q = q.filter(integration_outbox::dsl::kind.eq_any(&filters.kinds));
+    }
+
+    Ok(q.set((
+        integration_outbox::attempts.eq(0),
+        integration_outbox::next_attempt_at.eq(Utc::now()),
+        integration_outbox::dead_at.eq(None::<DateTime<Utc>>),
+    ))
+    .execute(&mut get_async_db_conn().await?)
+    .await?)
+}
+
+// Deletes the dead-lettered items matching the filters. It returns the number of deleted items.
+pub async fn purge_dead(filters: &Filters) -> Result<usize, Error> {
+    let mut q = diesel::delete(integration_outbox::dsl::integration_outbox)
+        .filter(integration_outbox::dsl::dead_at.is_not_null())
+        .into_boxed();
+
+    if let Some(application_id) = &filters.application_id {
+        q = q.filter(integration_outbox::dsl::application_id.eq(application_id));
+    }
+    if !filters.kinds.is_empty() {
+        q = q.filter(integration_outbox::dsl::kind.eq_any(&filters.kinds));
+    }
+
+    Ok(q.execute(&mut get_async_db_conn().await?).await?)
+}
+
+// Returns the number of pending and dead-lettered items per integration kind.
+pub async fn get_backlog() -> Result<Vec<Backlog>, Error> {
+    let items = diesel::sql_query(
+        r#"
+        select
+            kind,
+            count(*) filter (where dead_at is null) as pending,
+            count(*) filter (where dead_at is not null) as dead
+        from
+            integration_outbox
+        group by
+            kind
+        order by
+            kind
+    "#,
+    )
+    .load(&mut get_async_db_conn().await?)
+    .await?;
+    Ok(items)
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use crate::storage::{application, tenant};
+    use crate::test;
+
+    #[tokio::test]
+    async fn test_integration_outbox() {
+        let _guard = test::prepare().await;
+
+        let mut conf = (*config::get()).clone();
+        conf.integration.outbox.max_attempts = 2;
+        conf.integration.outbox.initial_backoff = std::time::Duration::from_secs(0);
+        config::set(conf);
+
+        let t = tenant::create(tenant::Tenant {
+            name: "test-tenant".into(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        let app = application::create(application::Application {
+            tenant_id: t.id,
+            name: "test-app".into(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        // validation
+        assert!(create(OutboxItem {
+            application_id: app.id,
+            ..Default::default()
+        })
+        .await
+        .is_err());
+
+        let item = create(OutboxItem {
+            application_id: app.id,
+            kind: IntegrationKind::Http,
+            event: "up".into(),
+            payload: vec![1, 2, 3],
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        // pending
+        let items = get_pending(10).await.unwrap();
+        assert_eq!(
+            vec![item.id],
+            items.iter().map(|i| i.id).collect::<Vec<_>>()
+        );
+
+        // locked
+        assert!(get_pending(10).await.unwrap().is_empty());
+
+        // first failure
+        let item = set_failed(&item.id, "connection refused").await.unwrap();
+        assert_eq!(1, item.attempts);
+        assert_eq!("connection refused", item.last_error);
+        assert!(item.dead_at.is_none());
+        assert_eq!(
+            vec![Backlog {
+                kind: IntegrationKind::Http,
+                pending: 1,
+                dead: 0,
+            }],
+            get_backlog().await.unwrap()
+        );
+
+        // second failure, dead-lettered
+        let item = set_failed(&item.id, "connection refused").await.unwrap();
+        assert_eq!(2, item.attempts);
+        assert!(item.dead_at.is_some());
+        assert!(get_pending(10).await.unwrap().is_empty());
+
+        let filters = Filters {
+            application_id: Some(app.id),
+            ..Default::default()
+        };
+        assert_eq!(1, get_dead_count(&filters).await.unwrap());
+        let items = list_dead(10, 0, &filters).await.unwrap();
+        assert_eq!(
+            vec![item.id],
+            items.iter().map(|i| i.id).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            0,
+            get_dead_count(&Filters {
+                application_id: Some(app.id),
+                kinds: vec![IntegrationKind::InfluxDb],
+            })
+            .await
+            .unwrap()
+        );
+
+        // replay
+        assert_eq!(1, replay_dead(&filters).await.unwrap());
+        assert_eq!(0, get_dead_count(&filters).await.unwrap());
+        let items = get_pending(10).await.unwrap();
+        assert_eq!(0, items[0].attempts);
+
+        // purge
+        set_failed(&item.id, "error").await.unwrap();
+        set_failed(&item.id, "error").await.unwrap();
+        assert_eq!(1, purge_dead(&filters).await.unwrap());
+        assert!(get(&item.id).await.is_err());
+        assert!(get_backlog().await.unwrap().is_empty());
+
+        // delete
+        let item = create(OutboxItem {
+            application_id: app.id,
+            event: "join".into(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        delete(&item.id).await.unwrap();
+        assert!(delete(&item.id).await.is_err());
+    }
+}
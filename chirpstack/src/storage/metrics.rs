@@ -74,6 +74,62 @@ fn get_key(name: &str, a: Aggregation, dt: NaiveDateTime) -> String {
     ))
 }
 
+fn get_unique_key(name: &str, a: Aggregation, dt: NaiveDateTime) -> String {
+    redis_key(format!(
+        "metrics:{{{}}}:{}:{}:unique",
+        name,
+        a,
+        dt.format("%Y%m%d%H%M")
+    ))
+}
+
+// Returns the start of the aggregation interval in which the given time falls.
+fn get_timestamp(a: Aggregation, time: &DateTime<Local>) -> Result<NaiveDateTime> {
+    Ok(match a {
+        Aggregation::MINUTE => NaiveDate::from_ymd_opt(time.year(), time.month(), time.day())
+            .ok_or_else(|| anyhow!("Invalid date"))?
+            .and_hms_opt(time.hour(), time.minute(), 0)
+            .ok_or_else(|| anyhow!("Invalid time"))?,
+        Aggregation::HOUR => NaiveDate::from_ymd_opt(time.year(), time.month(), time.day())
+            .ok_or_else(|| anyhow!("Invalid date"))?
+            .and_hms_opt(time.hour(), 0, 0)
+            .ok_or_else(|| anyhow!("Invalid time"))?,
+        Aggregation::DAY => NaiveDate::from_ymd_opt(time.year(), time.month(), time.day())
+            .ok_or_else(|| anyhow!("Invalid date"))?
+            .and_hms_opt(0, 0, 0)
+            .ok_or_else(|| anyhow!("Invalid time"))?,
+        Aggregation::MONTH => NaiveDate::from_ymd_opt(time.year(), time.month(), 1)
+            .ok_or_else(|| anyhow!("Invalid date"))?
+            .and_hms_opt(0, 0, 0)
+            .ok_or_else(|| anyhow!("Invalid time"))?,
+    })
+}
+
+// Returns the start timestamps of the aggregation intervals between start and end.
+fn get_timestamps(
+    a: Aggregation,
+    start: DateTime<Local>,
+    end: DateTime<Local>,
+) -> Result<Vec<NaiveDateTime>> {
+    let mut timestamps: Vec<NaiveDateTime> = Vec::new();
+    let mut ts = get_timestamp(a, &start)?;
+    let end = get_timestamp(a, &end)?;
+
+    while ts.le(&end) {
+        timestamps.push(ts);
+        ts = match a {
+            Aggregation::MINUTE => ts + ChronoDuration::minutes(1),
+            Aggregation::HOUR => ts + ChronoDuration::hours(1),
+            Aggregation::DAY => ts + ChronoDuration::days(1),
+            Aggregation::MONTH => ts
+                .checked_add_months(Months::new(1))
+                .ok_or_else(|| anyhow!("Add month error"))?,
+        };
+    }
+
+    Ok(timestamps)
+}
+
 pub async fn save_state(name: &str, state: &str) -> Result<()> {
     let key = redis_key(format!("metrics:{{{}}}", name));
     let ttl = get_ttl(Aggregation::MONTH);
@@ -100,33 +156,7 @@ pub async fn save(name: &str, record: &Record, aggregations: &[Aggregation]) ->
     for a in aggregations {
         let ttl = get_ttl(*a);
 
-        let ts: NaiveDateTime = match a {
-            Aggregation::MINUTE => {
-                NaiveDate::from_ymd_opt(record.time.year(), record.time.month(), record.time.day())
-                    .ok_or_else(|| anyhow!("Invalid date"))?
-                    .and_hms_opt(record.time.hour(), record.time.minute(), 0)
-                    .ok_or_else(|| anyhow!("Invalid time"))?
-            }
-            Aggregation::HOUR => {
-                NaiveDate::from_ymd_opt(record.time.year(), record.time.month(), record.time.day())
-                    .ok_or_else(|| anyhow!("Invalid date"))?
-                    .and_hms_opt(record.time.hour(), 0, 0)
-                    .ok_or_else(|| anyhow!("Invalid time"))?
-            }
-            Aggregation::DAY => {
-                NaiveDate::from_ymd_opt(record.time.year(), record.time.month(), record.time.day())
-                    .ok_or_else(|| anyhow!("Invalid date"))?
-                    .and_hms_opt(0, 0, 0)
-                    .ok_or_else(|| anyhow!("Invalid time"))?
-            }
-            Aggregation::MONTH => {
-                NaiveDate::from_ymd_opt(record.time.year(), record.time.month(), 1)
-                    .ok_or_else(|| anyhow!("Invalid date"))?
-                    .and_hms_opt(0, 0, 0)
-                    .ok_or_else(|| anyhow!("Invalid time"))?
-            }
-        };
-
+        let ts = get_timestamp(*a, &record.time)?;
         let key = get_key(name, *a, ts);
 
         for (k, v) in &record.metrics {
@@ -165,6 +195,65 @@ pub async fn save(name: &str, record: &Record, aggregations: &[Aggregation]) ->
     Ok(())
 }
 
+// Adds the member to the set of unique members of each aggregation interval. This is used for
+// metrics like the number of active devices, which can not be summed over records.
+pub async fn save_unique(
+    name: &str,
+    time: DateTime<Local>,
+    member: &str,
+    aggregations: &[Aggregation],
+) -> Result<()> {
+    let mut pipe = redis::pipe();
+    pipe.atomic();
+
+    for a in aggregations {
+        let ttl = get_ttl(*a);
+        let key = get_unique_key(name, *a, get_timestamp(*a, &time)?);
+
+        pipe.cmd("SADD").arg(&key).arg(member).ignore();
+        pipe.cmd("PEXPIRE")
+            .arg(&key)
+            .arg(ttl.as_millis() as usize)
+            .ignore();
+    }
+
+    pipe.query_async(&mut get_async_redis_conn().await?).await?;
+
+    Ok(())
+}
+
+// Returns the number of unique members for each aggregation interval between start and end.
+pub async fn get_unique(
+    name: &str,
+    a: Aggregation,
+    start: DateTime<Local>,
+    end: DateTime<Local>,
+) -> Result<Vec<(DateTime<Local>, usize)>> {
+    let timestamps = get_timestamps(a, start, end)?;
+    if timestamps.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut pipe = redis::pipe();
+    for ts in &timestamps {
+        pipe.cmd("SCARD").arg(get_unique_key(name, a, *ts));
+    }
+
+    let res: Vec<usize> = pipe.query_async(&mut get_async_redis_conn().await?).await?;
+    let mut out: Vec<(DateTime<Local>, usize)> = Vec::new();
+
+    for (ts, count) in timestamps.iter().zip(res) {
+        let tz = match ts.and_local_timezone(Local) {
+            chrono::LocalResult::Single(v) => v,
+            _ => continue,
+        };
+
+        out.push((tz, count));
+    }
+
+    Ok(out)
+}
+
 pub async fn get_state(name: &str) -> Result<String> {
     let key = redis_key(format!("metrics:{{{}}}", name));
 
@@ -182,77 +271,8 @@ pub async fn get(
     start: DateTime<Local>,
     end: DateTime<Local>,
 ) -> Result<Vec<Record>> {
-    let mut keys: Vec<String> = Vec::new();
-    let mut timestamps: Vec<NaiveDateTime> = Vec::new();
-
-    match a {
-        Aggregation::MINUTE => {
-            let mut ts = NaiveDate::from_ymd_opt(start.year(), start.month(), start.day())
-                .ok_or_else(|| anyhow!("Invalid date"))?
-                .and_hms_opt(start.hour(), start.minute(), 0)
-                .ok_or_else(|| anyhow!("Invalid time"))?;
-            let end = NaiveDate::from_ymd_opt(end.year(), end.month(), end.day())
-                .ok_or_else(|| anyhow!("Invalid date"))?
-                .and_hms_opt(end.hour(), end.minute(), 0)
-                .ok_or_else(|| anyhow!("Invalid time"))?;
-
-            while ts.le(&end) {
-                timestamps.push(ts);
-                keys.push(get_key(name, a, ts));
-                ts += ChronoDuration::minutes(1);
-            }
-        }
-        Aggregation::HOUR => {
-            let mut ts = NaiveDate::from_ymd_opt(start.year(), start.month(), start.day())
-                .ok_or_else(|| anyhow!("Invalid date"))?
-                .and_hms_opt(start.hour(), 0, 0)
-                .ok_or_else(|| anyhow!("Invalid time"))?;
-            let end = NaiveDate::from_ymd_opt(end.year(), end.month(), end.day())
-                .ok_or_else(|| anyhow!("Invalid date"))?
-                .and_hms_opt(end.hour(), 0, 0)
-                .ok_or_else(|| anyhow!("Invalid time"))?;
-
-            while ts.le(&end) {
-                timestamps.push(ts);
-                keys.push(get_key(name, a, ts));
-                ts += ChronoDuration::hours(1);
-            }
-        }
-        Aggregation::DAY => {
-            let mut ts = NaiveDate::from_ymd_opt(start.year(), start.month(), start.day())
-                .ok_or_else(|| anyhow!("Invalid date"))?
-                .and_hms_opt(0, 0, 0)
-                .ok_or_else(|| anyhow!("Invalid time"))?;
-            let end = NaiveDate::from_ymd_opt(end.year(), end.month(), end.day())
-                .ok_or_else(|| anyhow!("Invalid date"))?
-                .and_hms_opt(0, 0, 0)
-                .ok_or_else(|| anyhow!("Invalid time"))?;
-
-            while ts.le(&end) {
-                timestamps.push(ts);
-                keys.push(get_key(name, a, ts));
-                ts += ChronoDuration::days(1);
-            }
-        }
-        Aggregation::MONTH => {
-            let mut ts = NaiveDate::from_ymd_opt(start.year(), start.month(), 1)
-                .ok_or_else(|| anyhow!("Invalid date"))?
-                .and_hms_opt(0, 0, 0)
-                .ok_or_else(|| anyhow!("Invalid time"))?;
-            let end = NaiveDate::from_ymd_opt(end.year(), end.month(), 1)
-                .ok_or_else(|| anyhow!("Invalid date"))?
-                .and_hms_opt(0, 0, 0)
-                .ok_or_else(|| anyhow!("Invalid time"))?;
-
-            while ts.le(&end) {
-                timestamps.push(ts);
-                keys.push(get_key(name, a, ts));
-                ts = ts
-                    .checked_add_months(Months::new(1))
-                    .ok_or_else(|| anyhow!("Add month error"))?;
-            }
-        }
-    }
+    let timestamps = get_timestamps(a, start, end)?;
+    let keys: Vec<String> = timestamps.iter().map(|ts| get_key(name, a, *ts)).collect();
 
     if keys.is_empty() {
         return Ok(Vec::new());
@@ -482,6 +502,52 @@ pub mod test {
         );
     }
 
+    #[tokio::test]
+    async fn test_unique() {
+        let _guard = test::prepare().await;
+
+        for (min, member) in [(1, "a"), (2, "b"), (3, "a"), (61, "a")] {
+            save_unique(
+                "test",
+                Local.with_ymd_and_hms(2018, 1, 1, 1, 0, 0).unwrap() + ChronoDuration::minutes(min),
+                member,
+                &[Aggregation::HOUR, Aggregation::DAY],
+            )
+            .await
+            .unwrap();
+        }
+
+        let resp = get_unique(
+            "test",
+            Aggregation::HOUR,
+            Local.with_ymd_and_hms(2018, 1, 1, 1, 0, 0).unwrap(),
+            Local.with_ymd_and_hms(2018, 1, 1, 3, 0, 0).unwrap(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            vec![
+                (Local.with_ymd_and_hms(2018, 1, 1, 1, 0, 0).unwrap(), 2),
+                (Local.with_ymd_and_hms(2018, 1, 1, 2, 0, 0).unwrap(), 1),
+                (Local.with_ymd_and_hms(2018, 1, 1, 3, 0, 0).unwrap(), 0),
+            ],
+            resp
+        );
+
+        let resp = get_unique(
+            "test",
+            Aggregation::DAY,
+            Local.with_ymd_and_hms(2018, 1, 1, 0, 0, 0).unwrap(),
+            Local.with_ymd_and_hms(2018, 1, 1, 0, 0, 0).unwrap(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            vec![(Local.with_ymd_and_hms(2018, 1, 1, 0, 0, 0).unwrap(), 2)],
+            resp
+        );
+    }
+
     #[tokio::test]
     async fn test_day_dst_transition() {
         let _guard = test::prepare().await;
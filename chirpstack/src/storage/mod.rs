@@ -20,6 +20,7 @@ use crate::config;
 
 pub mod api_key;
 pub mod application;
+pub mod audit_log;
 pub mod device;
 pub mod device_gateway;
 pub mod device_keys;
@@ -32,14 +33,20 @@ pub mod error;
 pub mod fields;
 pub mod gateway;
 pub mod helpers;
+pub mod integration_outbox;
 pub mod mac_command;
 pub mod metrics;
+pub mod mqtt_client_certificate;
 pub mod multicast;
 pub mod passive_roaming;
 pub mod relay;
 pub mod schema;
 pub mod search;
+pub mod soft_delete;
 pub mod tenant;
+pub mod tenant_archive;
+pub mod tenant_role;
+pub mod tenant_usage;
 pub mod user;
 
 use crate::helpers::tls::get_root_certs;
@@ -0,0 +1,223 @@
+use chrono::{DateTime, Utc};
+use diesel::dsl;
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use tracing::info;
+use uuid::Uuid;
+
+use super::error::Error;
+use super::get_async_db_conn;
+use super::schema::{application, mqtt_client_certificate};
+
+#[derive(Queryable, Insertable, PartialEq, Eq, Debug, Clone)]
+#[diesel(table_name = mqtt_client_certificate)]
+pub struct ClientCertificate {
+    pub serial_number: String,
+    pub created_at: DateTime<Utc>,
+    pub application_id: Uuid,
+    pub expires_at: DateTime<Utc>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+impl ClientCertificate {
+    fn validate(&self) -> Result<(), Error> {
+        if self.serial_number.is_empty() {
+            return Err(Error::Validation("serial_number is not set".into()));
+        }
+        Ok(())
+    }
+}
+
+impl Default for ClientCertificate {
+    fn default() -> Self {
+        let now = Utc::now();
+
+        ClientCertificate {
+            serial_number: "".into(),
+            created_at: now,
+            application_id: Uuid::nil(),
+            expires_at: now,
+            revoked_at: None,
+        }
+    }
+}
+
+pub async fn create(c: ClientCertificate) -> Result<ClientCertificate, Error> {
+    c.validate()?;
+
+    let c: ClientCertificate = diesel::insert_into(mqtt_client_certificate::table)
+        .values(&c)
+        .get_result(&mut get_async_db_conn().await?)
+        .await
+        .map_err(|e| Error::from_diesel(e, c.serial_number.clone()))?;
+
+    info!(application_id = %c.application_id, serial_number = %c.serial_number, "MQTT client certificate created");
+
+    Ok(c)
+}
+
+pub async fn get_count(application_id: &Uuid) -> Result<i64, Error> {
+    Ok(mqtt_client_certificate::dsl::mqtt_client_certificate
+        .select(dsl::count_star())
+        .filter(mqtt_client_certificate::dsl::application_id.eq(application_id))
+        .first(&mut get_async_db_conn().await?)
+        .await?)
+}
+
+pub async fn list(
+    limit: i64,
+    offset: i64,
+    application_id: &Uuid,
+) -> Result<Vec<ClientCertificate>, Error> {
+    let items = mqtt_client_certificate::dsl::mqtt_client_certificate
+        .filter(mqtt_client_certificate::dsl::application_id.eq(application_id))
+        .order_by(mqtt_client_certificate::dsl::created_at.desc())
+        .limit(limit)
+        .offset(offset)
+        .load(&mut get_async_db_conn().await?)
+        .await?;
+    Ok(items)
+}
+
+// Revokes the given certificate. An error is returned when the certificate does not exist, does
+// not belong to the given application or has already been revoked.
+pub async fn revoke(
+    application_id: &Uuid,
+    serial_number: &str,
+) -> Result<ClientCertificate, Error> {
+    let c: ClientCertificate = diesel::update(
+        mqtt_client_certificate::dsl::mqtt_client_certificate
+            .find(serial_number)
+            .filter(mqtt_client_certificate::dsl::application_id.eq(application_id))
+            .filter(mqtt_client_certificate::dsl::revoked_at.is_null()),
+    )
+    .set(mqtt_client_certificate::revoked_at.eq(Some(Utc::now())))
+    .get_result(&mut get_async_db_conn().await?)
+    .await
+    .map_err(|e| Error::from_diesel(e, serial_number.to_string()))?;
+
+    info!(application_id = %c.application_id, serial_number = %c.serial_number, "MQTT client certificate revoked");
+
+    Ok(c)
+}
+
+// Returns the revoked certificates which have not yet expired. Expired certificates do not need
+// to be included in the CRL, as these are rejected anyway.
+pub async fn list_revoked() -> Result<Vec<ClientCertificate>, Error> {
+    let items = mqtt_client_certificate::dsl::mqtt_client_certificate
+        .filter(mqtt_client_certificate::dsl::revoked_at.is_not_null())
+        .filter(mqtt_client_certificate::dsl::expires_at.gt(Utc::now()))
+        .order_by(mqtt_client_certificate::dsl::revoked_at)
+        .load(&mut get_async_db_conn().await?)
+        .await?;
+    Ok(items)
+}
+
+// Returns true when the given certificate belongs to the (not deleted) application, has not been
+// revoked and has not yet expired.
+pub async fn is_valid(application_id: &Uuid, serial_number: &str) -> Result<bool, Error> {
+    let count: i64 = mqtt_client_certificate::dsl::mqtt_client_certificate
+        .inner_join(application::table)
+        .select(dsl::count_star())
+        .filter(mqtt_client_certificate::dsl::serial_number.eq(serial_number))
+        .filter(mqtt_client_certificate::dsl::application_id.eq(application_id))
+        .filter(application::dsl::deleted_at.is_null())
+        .filter(mqtt_client_certificate::dsl::revoked_at.is_null())
+        .filter(mqtt_client_certificate::dsl::expires_at.gt(Utc::now()))
+        .first(&mut get_async_db_conn().await?)
+        .await?;
+    Ok(count > 0)
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use crate::storage::{application, tenant};
+    use crate::test;
+    use chrono::Duration;
+
+    pub async fn get(serial_number: &str) -> Result<ClientCertificate, Error> {
+        let c = mqtt_client_certificate::dsl::mqtt_client_certificate
+            .find(serial_number)
+            .first(&mut get_async_db_conn().await?)
+            .await
+            .map_err(|e| Error::from_diesel(e, serial_number.to_string()))?;
+        Ok(c)
+    }
+
+    #[tokio::test]
+    async fn test_mqtt_client_certificate() {
+        let _guard = test::prepare().await;
+
+        let t = tenant::create(tenant::Tenant {
+            name: "test-tenant".into(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        let app = application::create(application::Application {
+            tenant_id: t.id,
+            name: "test-app".into(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        // validation
+        assert!(create(ClientCertificate {
+            application_id: app.id,
+            ..Default::default()
+        })
+        .await
+        .is_err());
+
+        assert!(!is_valid(&app.id, "01020304").await.unwrap());
+
+        let c = create(ClientCertificate {
+            serial_number: "01020304".into(),
+            application_id: app.id,
+            expires_at: Utc::now() + Duration::days(1),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let expired = create(ClientCertificate {
+            serial_number: "05060708".into(),
+            application_id: app.id,
+            expires_at: Utc::now() - Duration::days(1),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        // get
+        assert_eq!(c, get(&c.serial_number).await.unwrap());
+
+        // list
+        assert_eq!(2, get_count(&app.id).await.unwrap());
+        let items = list(10, 0, &app.id).await.unwrap();
+        assert_eq!(2, items.len());
+        assert!(is_valid(&app.id, &c.serial_number).await.unwrap());
+        assert!(!is_valid(&Uuid::new_v4(), &c.serial_number).await.unwrap());
+        assert!(!is_valid(&app.id, &expired.serial_number).await.unwrap());
+
+        // revoke
+        assert!(revoke(&Uuid::new_v4(), &c.serial_number).await.is_err());
+        let c = revoke(&app.id, &c.serial_number).await.unwrap();
+        assert!(c.revoked_at.is_some());
+        assert!(revoke(&app.id, &c.serial_number).await.is_err());
+        assert!(!is_valid(&app.id, &c.serial_number).await.unwrap());
+        revoke(&app.id, &expired.serial_number).await.unwrap();
+
+        // the expired certificate is not included
+        let items = list_revoked().await.unwrap();
+        assert_eq!(
+            vec![c.serial_number.clone()],
+            items
+                .iter()
+                .map(|c| c.serial_number.clone())
+                .collect::<Vec<_>>()
+        );
+    }
+}
@@ -243,6 +243,7 @@ pub async fn add_device(group_id: &Uuid, dev_eui: &EUI64) -> Result<(), Error> {
             Box::pin(async move {
                 let d: super::device::Device = device::dsl::device
                     .find(&dev_eui)
+                    .filter(device::dsl::deleted_at.is_null())
                     .for_update()
                     .get_result(c)
                     .await
@@ -302,6 +303,7 @@ pub async fn add_gateway(group_id: &Uuid, gateway_id: &EUI64) -> Result<(), Erro
             Box::pin(async move {
                 let gw: super::gateway::Gateway = gateway::dsl::gateway
                     .find(&gateway_id)
+                    .filter(gateway::dsl::deleted_at.is_null())
                     .for_update()
                     .get_result(c)
                     .await
@@ -363,8 +365,10 @@ pub async fn remove_gateway(group_id: &Uuid, gateway_id: &EUI64) -> Result<(), E
 
 pub async fn get_dev_euis(group_id: &Uuid) -> Result<Vec<EUI64>, Error> {
     multicast_group_device::dsl::multicast_group_device
+        .inner_join(device::table)
         .select(multicast_group_device::dev_eui)
         .filter(multicast_group_device::dsl::multicast_group_id.eq(&group_id))
+        .filter(device::dsl::deleted_at.is_null())
         .load(&mut get_async_db_conn().await?)
         .await
         .map_err(|e| Error::from_diesel(e, group_id.to_string()))
@@ -372,8 +376,10 @@ pub async fn get_dev_euis(group_id: &Uuid) -> Result<Vec<EUI64>, Error> {
 
 pub async fn get_gateway_ids(group_id: &Uuid) -> Result<Vec<EUI64>, Error> {
     multicast_group_gateway::dsl::multicast_group_gateway
+        .inner_join(gateway::table)
         .select(multicast_group_gateway::gateway_id)
         .filter(multicast_group_gateway::dsl::multicast_group_id.eq(&group_id))
+        .filter(gateway::dsl::deleted_at.is_null())
         .load(&mut get_async_db_conn().await?)
         .await
         .map_err(|e| Error::from_diesel(e, group_id.to_string()))
@@ -606,8 +612,14 @@ pub async fn get_schedulable_queue_items(limit: usize) -> Result<Vec<MulticastGr
                                     multicast_group_queue_item qi
                                 inner join gateway g
                                     on g.gateway_id = qi.gateway_id
+                                inner join multicast_group mg
+                                    on mg.id = qi.multicast_group_id
+                                inner join application a
+                                    on a.id = mg.application_id
                                 where
                                     qi.scheduler_run_after <= $2
+                                    and g.deleted_at is null
+                                    and a.deleted_at is null
                                     and now() - make_interval(secs => g.stats_interval_secs * 2) <= g.last_seen_at
                                 order by
                                     qi.created_at
@@ -47,6 +47,7 @@ pub async fn get_relay_count(filters: &RelayFilters) -> Result<i64, Error> {
         .select(dsl::count_star())
         .inner_join(device_profile::table)
         .filter(device_profile::dsl::is_relay.eq(true))
+        .filter(device::dsl::deleted_at.is_null())
         .into_boxed();
 
     if let Some(application_id) = &filters.application_id {
@@ -65,6 +66,7 @@ pub async fn list_relays(
         .inner_join(device_profile::table)
         .select((device::dev_eui, device::name))
         .filter(device_profile::dsl::is_relay.eq(true))
+        .filter(device::dsl::deleted_at.is_null())
         .into_boxed();
 
     if let Some(application_id) = &filters.application_id {
@@ -81,7 +83,9 @@ pub async fn list_relays(
 
 pub async fn get_device_count(filters: &DeviceFilters) -> Result<i64, Error> {
     let mut q = relay_device::dsl::relay_device
+        .inner_join(device::table.on(relay_device::dsl::dev_eui.eq(device::dsl::dev_eui)))
         .select(dsl::count_star())
+        .filter(device::dsl::deleted_at.is_null())
         .into_boxed();
 
     if let Some(relay_dev_eui) = &filters.relay_dev_eui {
@@ -112,6 +116,7 @@ pub async fn list_devices(
             device_profile::relay_ed_uplink_limit_bucket_size,
             device_profile::relay_ed_uplink_limit_reload_rate,
         ))
+        .filter(device::dsl::deleted_at.is_null())
         .into_boxed();
 
     if let Some(relay_dev_eui) = &filters.relay_dev_eui {
@@ -134,6 +139,7 @@ pub async fn add_device(relay_dev_eui: EUI64, device_dev_eui: EUI64) -> Result<(
                 // We lock the relay device to avoid race-conditions in the validation.
                 let rd: Device = device::dsl::device
                     .find(&relay_dev_eui)
+                    .filter(device::dsl::deleted_at.is_null())
                     .for_update()
                     .get_result(c)
                     .await
@@ -152,6 +158,7 @@ pub async fn add_device(relay_dev_eui: EUI64, device_dev_eui: EUI64) -> Result<(
                 // Validate that relay and device are under the same application.
                 let d: Device = device::dsl::device
                     .find(&device_dev_eui)
+                    .filter(device::dsl::deleted_at.is_null())
                     .get_result(c)
                     .await
                     .map_err(|e| Error::from_diesel(e, device_dev_eui.to_string()))?;
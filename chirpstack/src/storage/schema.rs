@@ -8,6 +8,14 @@ diesel::table! {
         name -> Varchar,
         is_admin -> Bool,
         tenant_id -> Nullable<Uuid>,
+        application_id -> Nullable<Uuid>,
+        device_profile_id -> Nullable<Uuid>,
+        can_read -> Bool,
+        can_write -> Bool,
+        can_enqueue -> Bool,
+        can_read_keys -> Bool,
+        expires_at -> Nullable<Timestamptz>,
+        last_used_at -> Nullable<Timestamptz>,
     }
 }
 
@@ -22,6 +30,7 @@ diesel::table! {
         description -> Text,
         mqtt_tls_cert -> Nullable<Bytea>,
         tags -> Jsonb,
+        deleted_at -> Nullable<Timestamptz>,
     }
 }
 
@@ -33,6 +42,39 @@ diesel::table! {
         created_at -> Timestamptz,
         updated_at -> Timestamptz,
         configuration -> Jsonb,
+        filter -> Jsonb,
+    }
+}
+
+diesel::table! {
+    application_user (application_id, user_id) {
+        application_id -> Uuid,
+        user_id -> Uuid,
+        tenant_id -> Uuid,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+        is_admin -> Bool,
+        is_device_admin -> Bool,
+    }
+}
+
+diesel::table! {
+    audit_log (id) {
+        id -> Uuid,
+        created_at -> Timestamptz,
+        user_id -> Nullable<Uuid>,
+        api_key_id -> Nullable<Uuid>,
+        #[max_length = 100]
+        service -> Varchar,
+        #[max_length = 100]
+        method -> Varchar,
+        #[max_length = 20]
+        status_code -> Varchar,
+        tenant_id -> Nullable<Uuid>,
+        application_id -> Nullable<Uuid>,
+        dev_eui -> Nullable<Bytea>,
+        metadata -> Jsonb,
+        changes -> Jsonb,
     }
 }
 
@@ -65,6 +107,7 @@ diesel::table! {
         join_eui -> Bytea,
         secondary_dev_addr -> Nullable<Bytea>,
         device_session -> Nullable<Bytea>,
+        deleted_at -> Nullable<Timestamptz>,
     }
 }
 
@@ -144,6 +187,7 @@ diesel::table! {
         relay_overall_limit_bucket_size -> Int2,
         allow_roaming -> Bool,
         rx1_delay -> Int2,
+        lr_fhss_rx_window -> Int2,
     }
 }
 
@@ -203,6 +247,8 @@ diesel::table! {
         f_cnt_down -> Nullable<Int8>,
         timeout_after -> Nullable<Timestamptz>,
         is_encrypted -> Bool,
+        #[max_length = 100]
+        correlation_id -> Varchar,
     }
 }
 
@@ -223,6 +269,37 @@ diesel::table! {
         tls_certificate -> Nullable<Bytea>,
         tags -> Jsonb,
         properties -> Jsonb,
+        fine_timestamp_key -> Nullable<Bytea>,
+        deleted_at -> Nullable<Timestamptz>,
+    }
+}
+
+diesel::table! {
+    integration_outbox (id) {
+        id -> Uuid,
+        created_at -> Timestamptz,
+        application_id -> Uuid,
+        #[max_length = 20]
+        kind -> Varchar,
+        #[max_length = 20]
+        event -> Varchar,
+        vars -> Jsonb,
+        payload -> Bytea,
+        attempts -> Int4,
+        next_attempt_at -> Timestamptz,
+        last_error -> Text,
+        dead_at -> Nullable<Timestamptz>,
+    }
+}
+
+diesel::table! {
+    mqtt_client_certificate (serial_number) {
+        #[max_length = 40]
+        serial_number -> Varchar,
+        created_at -> Timestamptz,
+        application_id -> Uuid,
+        expires_at -> Timestamptz,
+        revoked_at -> Nullable<Timestamptz>,
     }
 }
 
@@ -302,6 +379,42 @@ diesel::table! {
         private_gateways_up -> Bool,
         private_gateways_down -> Bool,
         tags -> Jsonb,
+        deleted_at -> Nullable<Timestamptz>,
+    }
+}
+
+diesel::table! {
+    tenant_role (id) {
+        id -> Uuid,
+        tenant_id -> Uuid,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+        #[max_length = 100]
+        name -> Varchar,
+        description -> Text,
+        can_write_applications -> Bool,
+        can_write_integrations -> Bool,
+        can_write_devices -> Bool,
+        can_enqueue -> Bool,
+        can_write_device_profiles -> Bool,
+        can_write_gateways -> Bool,
+        can_write_multicast_groups -> Bool,
+    }
+}
+
+diesel::table! {
+    tenant_usage (tenant_id, aggregation, time) {
+        tenant_id -> Uuid,
+        #[max_length = 10]
+        aggregation -> Varchar,
+        time -> Timestamptz,
+        uplinks -> Int8,
+        uplink_bytes -> Int8,
+        downlinks -> Int8,
+        downlink_bytes -> Int8,
+        active_devices -> Int8,
+        gateway_airtime_ms -> Int8,
+        roaming_uplinks -> Int8,
     }
 }
 
@@ -314,6 +427,19 @@ diesel::table! {
         is_admin -> Bool,
         is_device_admin -> Bool,
         is_gateway_admin -> Bool,
+        #[max_length = 100]
+        scim_group -> Nullable<Varchar>,
+    }
+}
+
+diesel::table! {
+    tenant_user_role (id) {
+        id -> Uuid,
+        tenant_id -> Uuid,
+        user_id -> Uuid,
+        role_id -> Uuid,
+        application_id -> Nullable<Uuid>,
+        created_at -> Timestamptz,
     }
 }
 
@@ -330,18 +456,50 @@ diesel::table! {
         #[max_length = 200]
         password_hash -> Varchar,
         note -> Text,
+        totp_secret -> Nullable<Bytea>,
+        #[max_length = 100]
+        totp_kek_label -> Varchar,
+        totp_enabled -> Bool,
+        totp_last_counter -> Int8,
+        failed_login_count -> Int4,
+        locked_until -> Nullable<Timestamptz>,
+        sessions_revoked_at -> Nullable<Timestamptz>,
+    }
+}
+
+diesel::table! {
+    user_password_history (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        created_at -> Timestamptz,
+        #[max_length = 200]
+        password_hash -> Varchar,
+    }
+}
+
+diesel::table! {
+    user_recovery_code (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        created_at -> Timestamptz,
+        #[max_length = 64]
+        code_hash -> Varchar,
     }
 }
 
 diesel::joinable!(api_key -> tenant (tenant_id));
 diesel::joinable!(application -> tenant (tenant_id));
 diesel::joinable!(application_integration -> application (application_id));
+diesel::joinable!(application_user -> application (application_id));
+diesel::joinable!(application_user -> user (user_id));
 diesel::joinable!(device -> application (application_id));
 diesel::joinable!(device -> device_profile (device_profile_id));
 diesel::joinable!(device_keys -> device (dev_eui));
 diesel::joinable!(device_profile -> tenant (tenant_id));
 diesel::joinable!(device_queue_item -> device (dev_eui));
 diesel::joinable!(gateway -> tenant (tenant_id));
+diesel::joinable!(integration_outbox -> application (application_id));
+diesel::joinable!(mqtt_client_certificate -> application (application_id));
 diesel::joinable!(multicast_group -> application (application_id));
 diesel::joinable!(multicast_group_device -> device (dev_eui));
 diesel::joinable!(multicast_group_device -> multicast_group (multicast_group_id));
@@ -349,25 +507,41 @@ diesel::joinable!(multicast_group_gateway -> gateway (gateway_id));
 diesel::joinable!(multicast_group_gateway -> multicast_group (multicast_group_id));
 diesel::joinable!(multicast_group_queue_item -> gateway (gateway_id));
 diesel::joinable!(multicast_group_queue_item -> multicast_group (multicast_group_id));
+diesel::joinable!(tenant_role -> tenant (tenant_id));
+diesel::joinable!(tenant_usage -> tenant (tenant_id));
 diesel::joinable!(tenant_user -> tenant (tenant_id));
 diesel::joinable!(tenant_user -> user (user_id));
+diesel::joinable!(tenant_user_role -> application (application_id));
+diesel::joinable!(tenant_user_role -> tenant_role (role_id));
+diesel::joinable!(tenant_user_role -> user (user_id));
+diesel::joinable!(user_password_history -> user (user_id));
+diesel::joinable!(user_recovery_code -> user (user_id));
 
 diesel::allow_tables_to_appear_in_same_query!(
     api_key,
     application,
     application_integration,
+    application_user,
+    audit_log,
     device,
     device_keys,
     device_profile,
     device_profile_template,
     device_queue_item,
     gateway,
+    integration_outbox,
+    mqtt_client_certificate,
     multicast_group,
     multicast_group_device,
     multicast_group_gateway,
     multicast_group_queue_item,
     relay_device,
     tenant,
+    tenant_role,
+    tenant_usage,
     tenant_user,
+    tenant_user_role,
     user,
+    user_password_history,
+    user_recovery_code,
 );
@@ -73,7 +73,9 @@ pub async fn global_search(
                 on u.id = tu.user_id
             where
                 ($3 = true or u.id = $4)
+                    and d.deleted_at is null
                     and (d.name ilike $2 or encode(d.dev_eui, 'hex') ilike $2 or encode(d.dev_addr, 'hex') ilike $2 or ($7 != '{}'::jsonb and d.tags @> $7))
+                    and ($3 = true or tu.is_admin = true or not exists (select 1 from application_user au where au.tenant_id = t.id and au.user_id = $4) or exists (select 1 from application_user au where au.application_id = a.id and au.user_id = $4))
             -- gateway
             union
             select
@@ -97,6 +99,7 @@ pub async fn global_search(
                 on u.id = tu.user_id
             where
                 ($3 = true or u.id = $4)
+                and g.deleted_at is null
                 and (g.name ilike $2 or encode(g.gateway_id, 'hex') ilike $2 or ($7 != '{}'::jsonb and g.tags @> $7))
             -- tenant
             union
@@ -119,6 +122,7 @@ pub async fn global_search(
                 on u.id = tu.user_id
             where
                 ($3 = true or u.id = $4)
+                and t.deleted_at is null
                 and t.name ilike $2
             -- application
             union
@@ -143,7 +147,9 @@ pub async fn global_search(
                 on u.id = tu.user_id
             where
                 ($3 = true or u.id = $4)
+                and a.deleted_at is null
                 and a.name ilike $2
+                and ($3 = true or tu.is_admin = true or not exists (select 1 from application_user au where au.tenant_id = t.id and au.user_id = $4) or exists (select 1 from application_user au where au.application_id = a.id and au.user_id = $4))
             order by
                 score desc
             limit $5
@@ -346,5 +352,37 @@ pub mod test {
             let res = global_search(&u.id, false, k, 10, 0).await.unwrap();
             assert_eq!(*v, res.len(), "query: {}", k);
         }
+
+        // User is member of an other application, the application and its devices are
+        // no longer returned.
+        let a_other = application::create(application::Application {
+            name: "test-app-other".into(),
+            tenant_id: t.id.clone(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        application::add_user(application::ApplicationUser {
+            application_id: a_other.id.clone(),
+            user_id: u.id.clone(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        let queries: HashMap<String, usize> = [
+            ("test".into(), 3),
+            ("app".into(), 1),
+            ("020304".into(), 1),
+            ("device".into(), 0),
+            ("gatew".into(), 1),
+        ]
+        .iter()
+        .cloned()
+        .collect();
+        for (k, v) in &queries {
+            let res = global_search(&u.id, false, k, 10, 0).await.unwrap();
+            assert_eq!(*v, res.len(), "query: {}", k);
+        }
     }
 }
@@ -0,0 +1,61 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use tracing::{error, info};
+
+use super::{application, device, gateway, tenant};
+use crate::config;
+use crate::helpers::errors::PrintFullError;
+use crate::integration;
+
+// Periodically purges the tenants, applications, devices and gateways that were (soft) deleted
+// longer than the configured retention period ago.
+pub async fn purge_loop() {
+    let conf = config::get();
+    if conf.soft_delete.retention.is_zero() {
+        return;
+    }
+
+    let retention = match chrono::Duration::from_std(conf.soft_delete.retention) {
+        Ok(v) => v,
+        Err(e) => {
+            error!(error = %e, "Invalid soft-delete retention");
+            return;
+        }
+    };
+
+    let mut interval = tokio::time::interval(std::time::Duration::from_secs(60 * 60));
+    loop {
+        interval.tick().await;
+
+        if let Err(e) = purge(Utc::now() - retention).await {
+            error!(error = %e.full(), "Soft-delete purge error");
+        }
+    }
+}
+
+// Purges the entities deleted before the given timestamp. The database cascades the delete
+// to the related records (e.g. device keys, queue-items and multicast-group memberships).
+async fn purge(before: DateTime<Utc>) -> Result<()> {
+    let application_ids = application::get_purge_ids(before).await?;
+
+    let tenants = tenant::purge_deleted(before).await?;
+    let applications = application::purge_deleted(before).await?;
+    let devices = device::purge_deleted(before).await?;
+    let gateways = gateway::purge_deleted(before).await?;
+
+    for application_id in &application_ids {
+        integration::invalidate_cache(application_id).await;
+    }
+
+    if tenants + applications + devices + gateways > 0 {
+        info!(
+            tenants = tenants,
+            applications = applications,
+            devices = devices,
+            gateways = gateways,
+            "Soft-deleted entities purged"
+        );
+    }
+
+    Ok(())
+}
@@ -8,8 +8,9 @@ use tracing::info;
 use uuid::Uuid;
 
 use super::error::Error;
-use super::schema::{tenant, tenant_user, user};
+use super::schema::{application, device, gateway, tenant, tenant_user, user};
 use super::{fields, get_async_db_conn};
+use crate::config;
 
 #[derive(Queryable, Insertable, PartialEq, Eq, Debug, Clone)]
 #[diesel(table_name = tenant)]
@@ -25,6 +26,7 @@ pub struct Tenant {
     pub private_gateways_up: bool,
     pub private_gateways_down: bool,
     pub tags: fields::KeyValue,
+    pub deleted_at: Option<DateTime<Utc>>,
 }
 
 impl Tenant {
@@ -52,6 +54,7 @@ impl Default for Tenant {
             private_gateways_up: false,
             private_gateways_down: false,
             tags: fields::KeyValue::new(HashMap::new()),
+            deleted_at: None,
         }
     }
 }
@@ -66,6 +69,8 @@ pub struct TenantUser {
     pub is_admin: bool,
     pub is_device_admin: bool,
     pub is_gateway_admin: bool,
+    // Set when the membership is managed by SCIM, to the display name of the SCIM group.
+    pub scim_group: Option<String>,
 }
 
 impl Default for TenantUser {
@@ -80,6 +85,7 @@ impl Default for TenantUser {
             is_admin: false,
             is_device_admin: false,
             is_gateway_admin: false,
+            scim_group: None,
         }
     }
 }
@@ -94,12 +100,15 @@ pub struct TenantUserListItem {
     pub is_admin: bool,
     pub is_device_admin: bool,
     pub is_gateway_admin: bool,
+    pub scim_group: Option<String>,
 }
 
 #[derive(Default, Clone)]
 pub struct Filters {
     pub user_id: Option<Uuid>,
     pub search: Option<String>,
+    // When set, only the (soft) deleted tenants are returned.
+    pub deleted: bool,
 }
 
 pub async fn create(t: Tenant) -> Result<Tenant, Error> {
@@ -117,6 +126,7 @@ pub async fn create(t: Tenant) -> Result<Tenant, Error> {
 pub async fn get(id: &Uuid) -> Result<Tenant, Error> {
     let t = tenant::dsl::tenant
         .find(&id)
+        .filter(tenant::dsl::deleted_at.is_null())
         .first(&mut get_async_db_conn().await?)
         .await
         .map_err(|e| Error::from_diesel(e, id.to_string()))?;
@@ -145,7 +155,142 @@ pub async fn update(t: Tenant) -> Result<Tenant, Error> {
     Ok(t)
 }
 
+// Deletes the tenant. When soft-delete is enabled, the tenant and its applications, devices and
+// gateways are marked as deleted, using the same timestamp such that these can be restored
+// together. These are permanently removed by the purge loop after the retention period.
 pub async fn delete(id: &Uuid) -> Result<(), Error> {
+    let conf = config::get();
+    if conf.soft_delete.retention.is_zero() {
+        return purge(id).await;
+    }
+
+    let mut c = get_async_db_conn().await?;
+    c.build_transaction()
+        .run::<(), Error, _>(|c| {
+            Box::pin(async move {
+                let deleted_at = Utc::now();
+
+                let ra = diesel::update(
+                    tenant::dsl::tenant
+                        .find(&id)
+                        .filter(tenant::dsl::deleted_at.is_null()),
+                )
+                .set(tenant::deleted_at.eq(deleted_at))
+                .execute(c)
+                .await
+                .map_err(|e| Error::from_diesel(e, id.to_string()))?;
+                if ra == 0 {
+                    return Err(Error::NotFound(id.to_string()));
+                }
+
+                diesel::update(
+                    device::dsl::device
+                        .filter(
+                            device::dsl::application_id.eq_any(
+                                application::dsl::application
+                                    .select(application::dsl::id)
+                                    .filter(application::dsl::tenant_id.eq(&id)),
+                            ),
+                        )
+                        .filter(device::dsl::deleted_at.is_null()),
+                )
+                .set(device::deleted_at.eq(deleted_at))
+                .execute(c)
+                .await?;
+
+                diesel::update(
+                    application::dsl::application
+                        .filter(application::dsl::tenant_id.eq(&id))
+                        .filter(application::dsl::deleted_at.is_null()),
+                )
+                .set(application::deleted_at.eq(deleted_at))
+                .execute(c)
+                .await?;
+
+                diesel::update(
+                    gateway::dsl::gateway
+                        .filter(gateway::dsl::tenant_id.eq(&id))
+                        .filter(gateway::dsl::deleted_at.is_null()),
+                )
+                .set(gateway::deleted_at.eq(deleted_at))
+                .execute(c)
+                .await?;
+
+                Ok(())
+            })
+        })
+        .await?;
+
+    info!(id = %id, "Tenant deleted");
+    Ok(())
+}
+
+// Restores the (soft) deleted tenant, including the applications, devices and gateways that
+// were deleted together with the tenant.
+pub async fn restore(id: &Uuid) -> Result<(), Error> {
+    let mut c = get_async_db_conn().await?;
+    c.build_transaction()
+        .run::<(), Error, _>(|c| {
+            Box::pin(async move {
+                let deleted_at: DateTime<Utc> = tenant::dsl::tenant
+                    .find(&id)
+                    .select(tenant::dsl::deleted_at)
+                    .for_update()
+                    .first::<Option<DateTime<Utc>>>(c)
+                    .await
+                    .map_err(|e| Error::from_diesel(e, id.to_string()))?
+                    .ok_or_else(|| Error::NotFound(id.to_string()))?;
+
+                diesel::update(tenant::dsl::tenant.find(&id))
+                    .set(tenant::deleted_at.eq(None::<DateTime<Utc>>))
+                    .execute(c)
+                    .await?;
+
+                diesel::update(
+                    device::dsl::device
+                        .filter(
+                            device::dsl::application_id.eq_any(
+                                application::dsl::application
+                                    .select(application::dsl::id)
+                                    .filter(application::dsl::tenant_id.eq(&id))
+                                    .filter(application::dsl::deleted_at.eq(deleted_at)),
+                            ),
+                        )
+                        .filter(device::dsl::deleted_at.eq(deleted_at)),
+                )
+                .set(device::deleted_at.eq(None::<DateTime<Utc>>))
+                .execute(c)
+                .await?;
+
+                diesel::update(
+                    application::dsl::application
+                        .filter(application::dsl::tenant_id.eq(&id))
+                        .filter(application::dsl::deleted_at.eq(deleted_at)),
+                )
+                .set(application::deleted_at.eq(None::<DateTime<Utc>>))
+                .execute(c)
+                .await?;
+
+                diesel::update(
+                    gateway::dsl::gateway
+                        .filter(gateway::dsl::tenant_id.eq(&id))
+                        .filter(gateway::dsl::deleted_at.eq(deleted_at)),
+                )
+                .set(gateway::deleted_at.eq(None::<DateTime<Utc>>))
+                .execute(c)
+                .await?;
+
+                Ok(())
+            })
+        })
+        .await?;
+
+    info!(id = %id, "Tenant restored");
+    Ok(())
+}
+
+// Permanently deletes the tenant, including all its applications, devices and gateways.
+pub async fn purge(id: &Uuid) -> Result<(), Error> {
     let ra = diesel::delete(tenant::dsl::tenant.find(&id))
         .execute(&mut get_async_db_conn().await?)
         .await
@@ -154,10 +299,18 @@ pub async fn delete(id: &Uuid) -> Result<(), Error> {
     if ra == 0 {
         return Err(Error::NotFound(id.to_string()));
     }
-    info!(id = %id, "Tenant deleted");
+    info!(id = %id, "Tenant purged");
     Ok(())
 }
 
+// Permanently deletes the tenants that were (soft) deleted before the given timestamp.
+pub async fn purge_deleted(before: DateTime<Utc>) -> Result<usize, Error> {
+    let ra = diesel::delete(tenant::dsl::tenant.filter(tenant::dsl::deleted_at.lt(before)))
+        .execute(&mut get_async_db_conn().await?)
+        .await?;
+    Ok(ra)
+}
+
 pub async fn get_count(filters: &Filters) -> Result<i64, Error> {
     let mut q = tenant::dsl::tenant
         .left_join(tenant_user::table)
@@ -171,6 +324,12 @@ pub async fn get_count(filters: &Filters) -> Result<i64, Error> {
         q = q.filter(tenant::dsl::name.ilike(format!("%{}%", search)));
     }
 
+    if filters.deleted {
+        q = q.filter(tenant::dsl::deleted_at.is_not_null());
+    } else {
+        q = q.filter(tenant::dsl::deleted_at.is_null());
+    }
+
     Ok(
         q.select(dsl::sql::<diesel::sql_types::BigInt>("count(distinct id)"))
             .first(&mut get_async_db_conn().await?)
@@ -196,6 +355,12 @@ pub async fn list(limit: i64, offset: i64, filters: &Filters) -> Result<Vec<Tena
         q = q.filter(tenant::dsl::name.ilike(format!("%{}%", search)));
     }
 
+    if filters.deleted {
+        q = q.filter(tenant::dsl::deleted_at.is_not_null());
+    } else {
+        q = q.filter(tenant::dsl::deleted_at.is_null());
+    }
+
     let items = q.load(&mut get_async_db_conn().await?).await?;
     Ok(items)
 }
@@ -267,6 +432,7 @@ pub async fn get_users(
             tenant_user::dsl::is_admin,
             tenant_user::dsl::is_device_admin,
             tenant_user::dsl::is_gateway_admin,
+            tenant_user::dsl::scim_group,
         ))
         .filter(tenant_user::dsl::tenant_id.eq(&tenant_id))
         .order_by(user::dsl::email)
@@ -308,6 +474,7 @@ pub async fn get_tenant_users_for_user(user_id: &Uuid) -> Result<Vec<TenantUser>
 #[cfg(test)]
 pub mod test {
     use super::*;
+    use crate::storage::application::{self, test::create_application};
     use crate::storage::user::test::create_user;
     use crate::test;
     use chrono::SubsecRound;
@@ -335,6 +502,7 @@ pub mod test {
             private_gateways_up: true,
             private_gateways_down: true,
             tags: fields::KeyValue::new(HashMap::new()),
+            deleted_at: None,
         };
         create(t).await.unwrap()
     }
@@ -378,6 +546,7 @@ pub mod test {
                 filter: Filters {
                     search: None,
                     user_id: None,
+                    deleted: false,
                 },
                 ts: vec![&t],
                 count: 1,
@@ -388,6 +557,7 @@ pub mod test {
                 filter: Filters {
                     search: Some("bt".into()),
                     user_id: None,
+                    deleted: false,
                 },
                 ts: vec![],
                 count: 0,
@@ -398,6 +568,7 @@ pub mod test {
                 filter: Filters {
                     search: Some("t".into()),
                     user_id: None,
+                    deleted: false,
                 },
                 ts: vec![&t],
                 count: 1,
@@ -408,6 +579,7 @@ pub mod test {
                 filter: Filters {
                     search: Some("t".into()),
                     user_id: None,
+                    deleted: false,
                 },
                 ts: vec![],
                 count: 1,
@@ -418,6 +590,7 @@ pub mod test {
                 filter: Filters {
                     search: Some("t".into()),
                     user_id: None,
+                    deleted: false,
                 },
                 ts: vec![],
                 count: 1,
@@ -428,6 +601,7 @@ pub mod test {
                 filter: Filters {
                     user_id: Some(user.id),
                     search: None,
+                    deleted: false,
                 },
                 ts: vec![&t],
                 count: 1,
@@ -453,8 +627,40 @@ pub mod test {
         }
 
         // delete
+        let app = create_application(Some(t.id)).await;
         delete(&t.id).await.unwrap();
         assert_eq!(true, delete(&t.id).await.is_err());
+        assert_eq!(true, get(&t.id).await.is_err());
+        assert_eq!(true, application::get(&app.id).await.is_err());
+
+        // list deleted
+        let filters = Filters {
+            user_id: Some(user.id),
+            deleted: true,
+            ..Default::default()
+        };
+        assert_eq!(1, get_count(&filters).await.unwrap());
+        let items = list(10, 0, &filters).await.unwrap();
+        assert_eq!(t.id, items[0].id);
+        assert!(items[0].deleted_at.is_some());
+
+        // restore
+        restore(&t.id).await.unwrap();
+        assert_eq!(true, restore(&t.id).await.is_err());
+        assert_eq!(t, get(&t.id).await.unwrap());
+        assert_eq!(app, application::get(&app.id).await.unwrap());
+
+        // purge
+        delete(&t.id).await.unwrap();
+        assert_eq!(
+            0,
+            purge_deleted(Utc::now() - chrono::Duration::hours(1))
+                .await
+                .unwrap()
+        );
+        assert_eq!(2, purge_deleted(Utc::now()).await.unwrap());
+        assert_eq!(true, restore(&t.id).await.is_err());
+        assert_eq!(true, application::get(&app.id).await.is_err());
     }
 
     #[tokio::test]
@@ -0,0 +1,1005 @@
+use std::collections::HashMap;
+
+use anyhow::{Context, Result};
+use diesel::prelude::*;
+use diesel::sql_types::Jsonb;
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+use prost::Message;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use super::error::Error;
+use super::schema::{application_user, device, gateway, tenant_user, tenant_user_role, user};
+use super::{application, get_async_db_conn, tenant, tenant_role};
+use crate::backend::keywrap;
+use backend::KeyEnvelope;
+use chirpstack_api::internal;
+use lrwn::{AES128Key, EUI64};
+
+// The tenant archive contains the rows of the tenant related tables, encoded as JSON objects
+// (using the PostgreSQL column names). As these rows are exported and imported using the
+// PostgreSQL JSON functions, the archive covers all columns without the need for maintaining
+// a separate (serde) representation of each storage struct.
+//
+// All keys and the integration configurations (which might contain credentials) are wrapped
+// using the KEK of the given label, which must be configured on both the exporting and the
+// importing instance.
+
+const VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Archive {
+    pub version: u32,
+    pub kek_label: String,
+    pub tenant: Value,
+    pub tenant_users: Vec<TenantUser>,
+    pub tenant_roles: Vec<Value>,
+    pub tenant_user_roles: Vec<TenantUserRole>,
+    pub device_profiles: Vec<Value>,
+    pub applications: Vec<Value>,
+    pub application_users: Vec<ApplicationUser>,
+    pub application_integrations: Vec<Value>,
+    pub devices: Vec<Value>,
+    pub device_keys: Vec<Value>,
+    pub relay_devices: Vec<Value>,
+    pub gateways: Vec<Value>,
+    pub multicast_groups: Vec<Value>,
+    pub multicast_group_devices: Vec<Value>,
+    pub multicast_group_gateways: Vec<Value>,
+}
+
+// Users are not part of the archive, as these are not owned by the tenant. The memberships
+// are exported by email and are only imported for users that exist on the importing instance.
+#[derive(Serialize, Deserialize, Queryable, Debug, Clone, PartialEq, Eq)]
+pub struct TenantUser {
+    pub email: String,
+    pub is_admin: bool,
+    pub is_device_admin: bool,
+    pub is_gateway_admin: bool,
+}
+
+#[derive(Serialize, Deserialize, Queryable, Debug, Clone, PartialEq, Eq)]
+pub struct TenantUserRole {
+    pub email: String,
+    pub role_id: Uuid,
+    pub application_id: Option<Uuid>,
+}
+
+#[derive(Serialize, Deserialize, Queryable, Debug, Clone, PartialEq, Eq)]
+pub struct ApplicationUser {
+    pub application_id: Uuid,
+    pub email: String,
+    pub is_admin: bool,
+    pub is_device_admin: bool,
+}
+
+#[derive(QueryableByName)]
+struct Row {
+    #[diesel(sql_type = Jsonb)]
+    row: Value,
+}
+
+// Exports the tenant. (Soft) deleted applications, devices and gateways are not exported.
+pub async fn export(tenant_id: &Uuid, kek_label: &str) -> Result<Archive> {
+    if kek_label.is_empty() {
+        return Err(anyhow!("KEK label must be set"));
+    }
+
+    let mut tenants = get_rows(
+        "select to_jsonb(t) as row from tenant t where t.id = $1 and t.deleted_at is null",
+        tenant_id,
+    )
+    .await?;
+    let tenant = tenants
+        .pop()
+        .ok_or_else(|| Error::NotFound(tenant_id.to_string()))?;
+
+    let mut c = get_async_db_conn().await?;
+    let tenant_users: Vec<TenantUser> = tenant_user::dsl::tenant_user
+        .inner_join(user::table)
+        .select((
+            user::dsl::email,
+            tenant_user::dsl::is_admin,
+            tenant_user::dsl::is_device_admin,
+            tenant_user::dsl::is_gateway_admin,
+        ))
+        .filter(tenant_user::dsl::tenant_id.eq(&tenant_id))
+        .load(&mut c)
+        .await?;
+    let tenant_user_roles: Vec<TenantUserRole> = tenant_user_role::dsl::tenant_user_role
+        .inner_join(user::table)
+        .select((
+            user::dsl::email,
+            tenant_user_role::dsl::role_id,
+            tenant_user_role::dsl::application_id,
+        ))
+        .filter(tenant_user_role::dsl::tenant_id.eq(&tenant_id))
+        .load(&mut c)
+        .await?;
+    let application_users: Vec<ApplicationUser> = application_user::dsl::application_user
+        .inner_join(user::table)
+        .select((
+            application_user::dsl::application_id,
+            user::dsl::email,
+            application_user::dsl::is_admin,
+            application_user::dsl::is_device_admin,
+        ))
+        .filter(application_user::dsl::tenant_id.eq(&tenant_id))
+        .load(&mut c)
+        .await?;
+
+    let mut a = Archive {
+        version: VERSION,
+        kek_label: kek_label.to_string(),
+        tenant,
+        tenant_users,
+        tenant_roles: get_rows(
+            r#"
+            select to_jsonb(tr) as row
+            from tenant_role tr
+            where tr.tenant_id = $1"#,
+            tenant_id,
+        )
+        .await?,
+        tenant_user_roles: Vec::new(),
+        device_profiles: get_rows(
+            r#"
+            select to_jsonb(dp) as row
+            from device_profile dp
+            where dp.tenant_id = $1"#,
+            tenant_id,
+        )
+        .await?,
+        applications: get_rows(
+            r#"
+            select to_jsonb(a) as row
+            from application a
+            where a.tenant_id = $1 and a.deleted_at is null"#,
+            tenant_id,
+        )
+        .await?,
+        application_users: Vec::new(),
+        application_integrations: get_rows(
+            r#"
+            select to_jsonb(ai) as row
+            from application_integration ai
+            inner join application a on a.id = ai.application_id
+            where a.tenant_id = $1 and a.deleted_at is null"#,
+            tenant_id,
+        )
+        .await?,
+        devices: get_rows(
+            r#"
+            select to_jsonb(d) as row
+            from device d
+            inner join application a on a.id = d.application_id
+            where a.tenant_id = $1 and a.deleted_at is null and d.deleted_at is null"#,
+            tenant_id,
+        )
+        .await?,
+        device_keys: get_rows(
+            r#"
+            select to_jsonb(dk) as row
+            from device_keys dk
+            inner join device d on d.dev_eui = dk.dev_eui
+            inner join application a on a.id = d.application_id
+            where a.tenant_id = $1 and a.deleted_at is null and d.deleted_at is null"#,
+            tenant_id,
+        )
+        .await?,
+        relay_devices: get_rows(
+            r#"
+            select to_jsonb(rd) as row
+            from relay_device rd
+            inner join device r on r.dev_eui = rd.relay_dev_eui
+            inner join application ra on ra.id = r.application_id
+            inner join device d on d.dev_eui = rd.dev_eui
+            inner join application a on a.id = d.application_id
+            where
+                ra.tenant_id = $1 and ra.deleted_at is null and r.deleted_at is null
+                and a.tenant_id = $1 and a.deleted_at is null and d.deleted_at is null"#,
+            tenant_id,
+        )
+        .await?,
+        gateways: get_rows(
+            r#"
+            select to_jsonb(g) as row
+            from gateway g
+            where g.tenant_id = $1 and g.deleted_at is null"#,
+            tenant_id,
+        )
+        .await?,
+        multicast_groups: get_rows(
+            r#"
+            select to_jsonb(mg) as row
+            from multicast_group mg
+            inner join application a on a.id = mg.application_id
+            where a.tenant_id = $1 and a.deleted_at is null"#,
+            tenant_id,
+        )
+        .await?,
+        multicast_group_devices: get_rows(
+            r#"
+            select to_jsonb(mgd) as row
+            from multicast_group_device mgd
+            inner join device d on d.dev_eui = mgd.dev_eui
+            inner join application a on a.id = d.application_id
+            where a.tenant_id = $1 and a.deleted_at is null and d.deleted_at is null"#,
+            tenant_id,
+        )
+        .await?,
+        multicast_group_gateways: get_rows(
+            r#"
+            select to_jsonb(mgg) as row
+            from multicast_group_gateway mgg
+            inner join multicast_group mg on mg.id = mgg.multicast_group_id
+            inner join application a on a.id = mg.application_id
+            inner join gateway g on g.gateway_id = mgg.gateway_id
+            where a.tenant_id = $1 and a.deleted_at is null and g.deleted_at is null"#,
+            tenant_id,
+        )
+        .await?,
+    };
+
+    // Only include the memberships and roles of the exported applications.
+    let application_ids = a
+        .applications
+        .iter()
+        .map(|r| get_uuid(r, "id"))
+        .collect::<Result<Vec<Uuid>>>()?;
+    a.application_users = application_users
+        .into_iter()
+        .filter(|u| application_ids.contains(&u.application_id))
+        .collect();
+    a.tenant_user_roles = tenant_user_roles
+        .into_iter()
+        .filter(|r| match &r.application_id {
+            Some(id) => application_ids.contains(id),
+            None => true,
+        })
+        .collect();
+
+    for r in &mut a.application_integrations {
+        wrap_configuration(r, kek_label).context("Wrap integration configuration")?;
+    }
+
+    for r in &mut a.device_keys {
+        wrap_key(r, "nwk_key", kek_label).context("Wrap nwk_key")?;
+        wrap_key(r, "app_key", kek_label).context("Wrap app_key")?;
+    }
+    for r in &mut a.devices {
+        if let Some(v) = r.get_mut("device_session").filter(|v| !v.is_null()) {
+            let mut ds = internal::DeviceSession::decode(&*bytea_decode(v)?)?;
+            wrap_device_session(&mut ds, kek_label).context("Wrap device-session keys")?;
+            *v = bytea_encode(&ds.encode_to_vec());
+        }
+    }
+    for r in &mut a.multicast_groups {
+        wrap_key(r, "mc_nwk_s_key", kek_label).context("Wrap mc_nwk_s_key")?;
+        wrap_key(r, "mc_app_s_key", kek_label).context("Wrap mc_app_s_key")?;
+    }
+    for r in &mut a.gateways {
+        wrap_key(r, "fine_timestamp_key", kek_label).context("Wrap fine_timestamp_key")?;
+    }
+
+    info!(
+        tenant_id = %tenant_id,
+        applications = a.applications.len(),
+        devices = a.devices.len(),
+        gateways = a.gateways.len(),
+        "Tenant exported"
+    );
+    Ok(a)
+}
+
+// Imports the archive as a new tenant and returns the ID of the new tenant. All tenant,
+// application, device-profile and multicast-group IDs are re-generated. The import fails
+// in case any of the devices or gateways already exists.
+pub async fn import(a: &Archive) -> Result<Uuid> {
+    if a.version != VERSION {
+        return Err(anyhow!("Unsupported archive version: {}", a.version));
+    }
+
+    let dev_euis = a
+        .devices
+        .iter()
+        .map(|r| get_eui64(r, "dev_eui"))
+        .collect::<Result<Vec<EUI64>>>()?;
+    let gateway_ids = a
+        .gateways
+        .iter()
+        .map(|r| get_eui64(r, "gateway_id"))
+        .collect::<Result<Vec<EUI64>>>()?;
+
+    let mut c = get_async_db_conn().await?;
+    let existing: Vec<EUI64> = device::dsl::device
+        .select(device::dsl::dev_eui)
+        .filter(device::dsl::dev_eui.eq_any(&dev_euis))
+        .load(&mut c)
+        .await?;
+    if !existing.is_empty() {
+        return Err(Error::AlreadyExists(format!("Devices: {}", join(&existing))).into());
+    }
+    let existing: Vec<EUI64> = gateway::dsl::gateway
+        .select(gateway::dsl::gateway_id)
+        .filter(gateway::dsl::gateway_id.eq_any(&gateway_ids))
+        .load(&mut c)
+        .await?;
+    if !existing.is_empty() {
+        return Err(Error::AlreadyExists(format!("Gateways: {}", join(&existing))).into());
+    }
+
+    let emails: Vec<&String> = a
+        .tenant_users
+        .iter()
+        .map(|u| &u.email)
+        .chain(a.tenant_user_roles.iter().map(|u| &u.email))
+        .chain(a.application_users.iter().map(|u| &u.email))
+        .collect();
+    let users: HashMap<String, Uuid> = user::dsl::user
+        .select((user::dsl::email, user::dsl::id))
+        .filter(user::dsl::email.eq_any(&emails))
+        .load::<(String, Uuid)>(&mut c)
+        .await?
+        .into_iter()
+        .collect();
+
+    let tenant_id = Uuid::new_v4();
+    let mut ids: HashMap<Uuid, Uuid> = HashMap::new();
+
+    let mut tenant = a.tenant.clone();
+    set_value(&mut tenant, "id", tenant_id)?;
+
+    let mut device_profiles = a.device_profiles.clone();
+    for r in &mut device_profiles {
+        remap_id(r, "id", &mut ids)?;
+        set_value(r, "tenant_id", tenant_id)?;
+    }
+
+    let mut applications = a.applications.clone();
+    for r in &mut applications {
+        remap_id(r, "id", &mut ids)?;
+        set_value(r, "tenant_id", tenant_id)?;
+    }
+
+    let mut application_integrations = a.application_integrations.clone();
+    for r in &mut application_integrations {
+        map_id(r, "application_id", &ids)?;
+        unwrap_configuration(r, &a.kek_label).context("Unwrap integration configuration")?;
+    }
+
+    let mut tenant_roles = a.tenant_roles.clone();
+    for r in &mut tenant_roles {
+        remap_id(r, "id", &mut ids)?;
+        set_value(r, "tenant_id", tenant_id)?;
+    }
+
+    let mut devices = a.devices.clone();
+    for r in &mut devices {
+        map_id(r, "application_id", &ids)?;
+        map_id(r, "device_profile_id", &ids)?;
+
+        if let Some(v) = r.get_mut("device_session").filter(|v| !v.is_null()) {
+            let mut ds = internal::DeviceSession::decode(&*bytea_decode(v)?)?;
+            unwrap_device_session(&mut ds, &a.kek_label).context("Unwrap device-session keys")?;
+            *v = bytea_encode(&ds.encode_to_vec());
+        }
+    }
+
+    let mut device_keys = a.device_keys.clone();
+    for r in &mut device_keys {
+        unwrap_key(r, "nwk_key").context("Unwrap nwk_key")?;
+        unwrap_key(r, "app_key").context("Unwrap app_key")?;
+    }
+
+    let mut gateways = a.gateways.clone();
+    for r in &mut gateways {
+        set_value(r, "tenant_id", tenant_id)?;
+        unwrap_key(r, "fine_timestamp_key").context("Unwrap fine_timestamp_key")?;
+    }
+
+    let mut multicast_groups = a.multicast_groups.clone();
+    for r in &mut multicast_groups {
+        remap_id(r, "id", &mut ids)?;
+        map_id(r, "application_id", &ids)?;
+        unwrap_key(r, "mc_nwk_s_key").context("Unwrap mc_nwk_s_key")?;
+        unwrap_key(r, "mc_app_s_key").context("Unwrap mc_app_s_key")?;
+    }
+
+    let mut multicast_group_devices = a.multicast_group_devices.clone();
+    for r in &mut multicast_group_devices {
+        map_id(r, "multicast_group_id", &ids)?;
+    }
+
+    let mut multicast_group_gateways = a.multicast_group_gateways.clone();
+    for r in &mut multicast_group_gateways {
+        map_id(r, "multicast_group_id", &ids)?;
+    }
+
+    let mut tenant_users: Vec<tenant::TenantUser> = Vec::new();
+    for u in &a.tenant_users {
+        match users.get(&u.email) {
+            Some(user_id) => tenant_users.push(tenant::TenantUser {
+                tenant_id,
+                user_id: *user_id,
+                is_admin: u.is_admin,
+                is_device_admin: u.is_device_admin,
+                is_gateway_admin: u.is_gateway_admin,
+                ..Default::default()
+            }),
+            None => {
+                warn!(email = %u.email, "User does not exist, skipping tenant membership");
+            }
+        }
+    }
+
+    let mut tenant_user_roles: Vec<tenant_role::TenantUserRole> = Vec::new();
+    for r in &a.tenant_user_roles {
+        let role_id = *ids
+            .get(&r.role_id)
+            .ok_or_else(|| anyhow!("Unknown role ID: {}", r.role_id))?;
+        let application_id = match &r.application_id {
+            Some(id) => Some(
+                *ids.get(id)
+                    .ok_or_else(|| anyhow!("Unknown application ID: {}", id))?,
+            ),
+            None => None,
+        };
+
+        match users.get(&r.email) {
+            Some(user_id) => tenant_user_roles.push(tenant_role::TenantUserRole {
+                tenant_id,
+                user_id: *user_id,
+                role_id,
+                application_id,
+                ..Default::default()
+            }),
+            None => {
+                warn!(email = %r.email, "User does not exist, skipping role assignment");
+            }
+        }
+    }
+
+    let mut application_users: Vec<application::ApplicationUser> = Vec::new();
+    for u in &a.application_users {
+        let application_id = *ids
+            .get(&u.application_id)
+            .ok_or_else(|| anyhow!("Unknown application ID: {}", u.application_id))?;
+
+        match users.get(&u.email) {
+            Some(user_id) => application_users.push(application::ApplicationUser {
+                application_id,
+                user_id: *user_id,
+                tenant_id,
+                is_admin: u.is_admin,
+                is_device_admin: u.is_device_admin,
+                ..Default::default()
+            }),
+            None => {
+                warn!(email = %u.email, "User does not exist, skipping application membership");
+            }
+        }
+    }
+
+    c.build_transaction()
+        .run::<(), Error, _>(|c| {
+            Box::pin(async move {
+                insert_row(c, "tenant", &tenant).await?;
+
+                for (table, rows) in [
+                    ("tenant_role", &tenant_roles),
+                    ("device_profile", &device_profiles),
+                    ("application", &applications),
+                    ("application_integration", &application_integrations),
+                    ("device", &devices),
+                    ("device_keys", &device_keys),
+                    ("relay_device", &a.relay_devices),
+                    ("gateway", &gateways),
+                    ("multicast_group", &multicast_groups),
+                    ("multicast_group_device", &multicast_group_devices),
+                    ("multicast_group_gateway", &multicast_group_gateways),
+                ] {
+                    for r in rows {
+                        insert_row(c, table, r).await?;
+                    }
+                }
+
+                for tu in &tenant_users {
+                    diesel::insert_into(tenant_user::table)
+                        .values(tu)
+                        .execute(c)
+                        .await?;
+                }
+
+                for tur in &tenant_user_roles {
+                    diesel::insert_into(tenant_user_role::table)
+                        .values(tur)
+                        .execute(c)
+                        .await?;
+                }
+
+                for au in &application_users {
+                    diesel::insert_into(application_user::table)
+                        .values(au)
+                        .execute(c)
+                        .await?;
+                }
+
+                Ok(())
+            })
+        })
+        .await?;
+
+    info!(tenant_id = %tenant_id, "Tenant imported");
+    Ok(tenant_id)
+}
+
+async fn get_rows(query: &str, tenant_id: &Uuid) -> Result<Vec<Value>, Error> {
+    let rows: Vec<Row> = diesel::sql_query(query)
+        .bind::<diesel::sql_types::Uuid, _>(tenant_id)
+        .load(&mut get_async_db_conn().await?)
+        .await?;
+    Ok(rows.into_iter().map(|r| r.row).collect())
+}
+
+// Inserts the row, columns that are not present in the row are set to null.
+async fn insert_row(c: &mut AsyncPgConnection, table: &str, row: &Value) -> Result<(), Error> {
+    diesel::sql_query(format!(
+        "insert into {0} select * from jsonb_populate_record(null::{0}, $1)",
+        table
+    ))
+    .bind::<Jsonb, _>(row)
+    .execute(c)
+    .await?;
+    Ok(())
+}
+
+fn get_uuid(row: &Value, field: &str) -> Result<Uuid> {
+    let s = row
+        .get(field)
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| anyhow!("{} is missing", field))?;
+    Ok(Uuid::parse_str(s)?)
+}
+
+fn get_eui64(row: &Value, field: &str) -> Result<EUI64> {
+    let v = row
+        .get(field)
+        .ok_or_else(|| anyhow!("{} is missing", field))?;
+    Ok(EUI64::from_slice(&bytea_decode(v)?)?)
+}
+
+fn set_value(row: &mut Value, field: &str, id: Uuid) -> Result<()> {
+    let obj = row
+        .as_object_mut()
+        .ok_or_else(|| anyhow!("Row must be an object"))?;
+    obj.insert(field.to_string(), Value::String(id.to_string()));
+    Ok(())
+}
+
+// Replaces the ID by a new random ID, the mapping is stored in ids.
+fn remap_id(row: &mut Value, field: &str, ids: &mut HashMap<Uuid, Uuid>) -> Result<()> {
+    let id = Uuid::new_v4();
+    ids.insert(get_uuid(row, field)?, id);
+    set_value(row, field, id)
+}
+
+// Replaces the ID by the previously re-generated ID.
+fn map_id(row: &mut Value, field: &str, ids: &HashMap<Uuid, Uuid>) -> Result<()> {
+    let old_id = get_uuid(row, field)?;
+    let id = *ids
+        .get(&old_id)
+        .ok_or_else(|| anyhow!("Unknown {}: {}", field, old_id))?;
+    set_value(row, field, id)
+}
+
+// PostgreSQL encodes bytea values as \x prefixed HEX string.
+fn bytea_decode(v: &Value) -> Result<Vec<u8>> {
+    let s = v
+        .as_str()
+        .and_then(|s| s.strip_prefix("\\x"))
+        .ok_or_else(|| anyhow!("Invalid bytea value"))?;
+    Ok(hex::decode(s)?)
+}
+
+fn bytea_encode(b: &[u8]) -> Value {
+    Value::String(format!("\\x{}", hex::encode(b)))
+}
+
+fn wrap_key(row: &mut Value, field: &str, kek_label: &str) -> Result<()> {
+    if let Some(v) = row.get_mut(field).filter(|v| !v.is_null()) {
+        let key = AES128Key::from_slice(&bytea_decode(v)?)?;
+        *v = serde_json::to_value(keywrap::wrap(kek_label, key)?)?;
+    }
+    Ok(())
+}
+
+fn unwrap_key(row: &mut Value, field: &str) -> Result<()> {
+    if let Some(v) = row.get_mut(field).filter(|v| !v.is_null()) {
+        let ke: KeyEnvelope = serde_json::from_value(v.clone())?;
+        *v = bytea_encode(&keywrap::unwrap(&ke)?.to_bytes());
+    }
+    Ok(())
+}
+
+// The configuration is replaced by the wrapped (JSON encoded) configuration.
+fn wrap_configuration(row: &mut Value, kek_label: &str) -> Result<()> {
+    if let Some(v) = row.get_mut("configuration") {
+        let b = keywrap::wrap_data(kek_label, &serde_json::to_vec(v)?)?;
+        *v = Value::String(hex::encode(b));
+    }
+    Ok(())
+}
+
+fn unwrap_configuration(row: &mut Value, kek_label: &str) -> Result<()> {
+    if let Some(v) = row.get_mut("configuration") {
+        let s = v
+            .as_str()
+            .ok_or_else(|| anyhow!("Configuration is not wrapped"))?;
+        let b = keywrap::unwrap_data(kek_label, &hex::decode(s)?)?;
+        *v = serde_json::from_slice(&b)?;
+    }
+    Ok(())
+}
+
+// The network session keys are replaced by the wrapped keys (using the KEK of the archive).
+// The AppSKey is only wrapped in case it is not yet wrapped.
+fn wrap_device_session(ds: &mut internal::DeviceSession, kek_label: &str) -> Result<()> {
+    for key in [
+        &mut ds.f_nwk_s_int_key,
+        &mut ds.s_nwk_s_int_key,
+        &mut ds.nwk_s_enc_key,
+    ] {
+        if !key.is_empty() {
+            *key = keywrap::wrap(kek_label, AES128Key::from_slice(key.as_slice())?)?.aes_key;
+        }
+    }
+
+    if let Some(app_s_key) = &mut ds.app_s_key {
+        if app_s_key.kek_label.is_empty() && !app_s_key.aes_key.is_empty() {
+            let ke = keywrap::wrap(kek_label, AES128Key::from_slice(&app_s_key.aes_key)?)?;
+            app_s_key.kek_label = ke.kek_label;
+            app_s_key.aes_key = ke.aes_key;
+        }
+    }
+
+    if let Some(ds) = &mut ds.pending_rejoin_device_session {
+        wrap_device_session(ds, kek_label)?;
+    }
+
+    Ok(())
+}
+
+fn unwrap_device_session(ds: &mut internal::DeviceSession, kek_label: &str) -> Result<()> {
+    for key in [
+        &mut ds.f_nwk_s_int_key,
+        &mut ds.s_nwk_s_int_key,
+        &mut ds.nwk_s_enc_key,
+    ] {
+        if !key.is_empty() {
+            *key = keywrap::unwrap(&KeyEnvelope {
+                kek_label: kek_label.to_string(),
+                aes_key: key.clone(),
+            })?
+            .to_vec();
+        }
+    }
+
+    if let Some(app_s_key) = &mut ds.app_s_key {
+        if app_s_key.kek_label == kek_label {
+            let key = keywrap::unwrap(&KeyEnvelope {
+                kek_label: app_s_key.kek_label.clone(),
+                aes_key: app_s_key.aes_key.clone(),
+            })?;
+            app_s_key.kek_label = "".into();
+            app_s_key.aes_key = key.to_vec();
+        }
+    }
+
+    if let Some(ds) = &mut ds.pending_rejoin_device_session {
+        unwrap_device_session(ds, kek_label)?;
+    }
+
+    Ok(())
+}
+
+fn join(ids: &[EUI64]) -> String {
+    ids.iter()
+        .map(|v| v.to_string())
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use crate::config;
+    use crate::storage::{device as device_storage, gateway as gateway_storage};
+    use crate::storage::{device_keys, device_profile, multicast, relay, user as user_storage};
+    use crate::test;
+    use lrwn::DevAddr;
+
+    #[tokio::test]
+    async fn test_export_import() {
+        let _guard = test::prepare().await;
+
+        let mut conf = (*config::get()).clone();
+        conf.keks = vec![config::Kek {
+            label: "kek-1".into(),
+            kek: AES128Key::from_bytes([1, 2, 3, 4, 5, 6, 7, 8, 1, 2, 3, 4, 5, 6, 7, 8]),
+        }];
+        config::set(conf);
+
+        let u = user_storage::create(user_storage::User {
+            email: "archive@example.com".into(),
+            is_active: true,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        let t = tenant::test::create_tenant().await;
+        tenant::add_user(tenant::TenantUser {
+            tenant_id: t.id,
+            user_id: u.id,
+            is_device_admin: true,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        let dp = device_profile::test::create_device_profile(Some(t.id)).await;
+        let app = application::create(application::Application {
+            tenant_id: t.id,
+            name: "test-app".into(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        application::add_user(application::ApplicationUser {
+            application_id: app.id,
+            user_id: u.id,
+            tenant_id: t.id,
+            is_admin: true,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        application::create_integration(application::Integration {
+            application_id: app.id,
+            kind: application::IntegrationKind::Http,
+            configuration: application::IntegrationConfiguration::Http(
+                application::HttpConfiguration {
+                    headers: HashMap::new(),
+                    json: true,
+                    event_endpoint_url: "http://localhost:8080/events".into(),
+                    ..Default::default()
+                },
+            ),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        let ds = internal::DeviceSession {
+            dev_addr: vec![1, 2, 3, 4],
+            f_nwk_s_int_key: vec![1; 16],
+            s_nwk_s_int_key: vec![2; 16],
+            nwk_s_enc_key: vec![3; 16],
+            app_s_key: Some(chirpstack_api::common::KeyEnvelope {
+                kek_label: "".into(),
+                aes_key: vec![4; 16],
+            }),
+            ..Default::default()
+        };
+        let d = device_storage::create(device_storage::Device {
+            dev_eui: EUI64::from_be_bytes([1, 2, 3, 4, 5, 6, 7, 8]),
+            application_id: app.id,
+            device_profile_id: dp.id,
+            name: "test-dev".into(),
+            dev_addr: Some(DevAddr::from_be_bytes([1, 2, 3, 4])),
+            device_session: Some(ds.clone()),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let dk = device_keys::create(device_keys::DeviceKeys {
+            dev_eui: d.dev_eui,
+            nwk_key: AES128Key::from_bytes([5; 16]),
+            app_key: AES128Key::from_bytes([6; 16]),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        let relay_dp = device_profile::create(device_profile::DeviceProfile {
+            id: Uuid::new_v4(),
+            is_relay: true,
+            ..dp.clone()
+        })
+        .await
+        .unwrap();
+        let relay_d = device_storage::create(device_storage::Device {
+            dev_eui: EUI64::from_be_bytes([2, 2, 3, 4, 5, 6, 7, 8]),
+            application_id: app.id,
+            device_profile_id: relay_dp.id,
+            name: "test-relay".into(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        relay::add_device(relay_d.dev_eui, d.dev_eui).await.unwrap();
+
+        let role = tenant_role::test::create_role(t.id, &[tenant_role::Permission::Enqueue]).await;
+        tenant_role::add_user_role(tenant_role::TenantUserRole {
+            tenant_id: t.id,
+            user_id: u.id,
+            role_id: role.id,
+            application_id: Some(app.id),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        let gw = gateway_storage::create(gateway_storage::Gateway {
+            gateway_id: EUI64::from_be_bytes([8, 7, 6, 5, 4, 3, 2, 1]),
+            tenant_id: t.id,
+            name: "test-gw".into(),
+            fine_timestamp_key: Some(AES128Key::from_bytes([7; 16])),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        let mg = multicast::create(multicast::MulticastGroup {
+            application_id: app.id,
+            name: "test-mg".into(),
+            mc_nwk_s_key: AES128Key::from_bytes([8; 16]),
+            mc_app_s_key: AES128Key::from_bytes([9; 16]),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        multicast::add_device(&mg.id, &d.dev_eui).await.unwrap();
+        multicast::add_gateway(&mg.id, &gw.gateway_id)
+            .await
+            .unwrap();
+
+        // export
+        assert!(export(&t.id, "").await.is_err());
+        assert!(export(&t.id, "kek-2").await.is_err());
+        let a = export(&t.id, "kek-1").await.unwrap();
+        assert_eq!(1, a.devices.len());
+        assert_eq!(1, a.gateways.len());
+        assert_eq!(
+            vec![TenantUser {
+                email: "archive@example.com".into(),
+                is_admin: false,
+                is_device_admin: true,
+                is_gateway_admin: false,
+            }],
+            a.tenant_users
+        );
+        assert_eq!("kek-1", a.device_keys[0]["nwk_key"]["KEKLabel"]);
+        assert_eq!("kek-1", a.multicast_groups[0]["mc_app_s_key"]["KEKLabel"]);
+        assert_eq!("kek-1", a.gateways[0]["fine_timestamp_key"]["KEKLabel"]);
+        assert!(a.application_integrations[0]["configuration"].is_string());
+        assert!(!serde_json::to_string(&a)
+            .unwrap()
+            .contains("http://localhost:8080/events"));
+        assert_eq!(1, a.relay_devices.len());
+        assert_eq!(1, a.tenant_roles.len());
+        assert_eq!(
+            vec![TenantUserRole {
+                email: "archive@example.com".into(),
+                role_id: role.id,
+                application_id: Some(app.id),
+            }],
+            a.tenant_user_roles
+        );
+
+        let a: Archive = serde_json::from_str(&serde_json::to_string(&a).unwrap()).unwrap();
+
+        // import conflicts with the existing devices and gateways
+        assert!(import(&a).await.is_err());
+        tenant::purge(&t.id).await.unwrap();
+
+        // import
+        let tenant_id = import(&a).await.unwrap();
+        assert_ne!(t.id, tenant_id);
+        assert_eq!(
+            tenant::Tenant {
+                id: tenant_id,
+                ..t.clone()
+            },
+            tenant::get(&tenant_id).await.unwrap()
+        );
+        tenant::get_user(&tenant_id, &u.id).await.unwrap();
+
+        let d_imported = device_storage::get(&d.dev_eui).await.unwrap();
+        assert_ne!(app.id, d_imported.application_id);
+        assert_ne!(dp.id, d_imported.device_profile_id);
+        assert_eq!(Some(ds), d_imported.device_session);
+        assert_eq!(d.name, d_imported.name);
+
+        let app_imported = application::get(&d_imported.application_id).await.unwrap();
+        assert_eq!(tenant_id, app_imported.tenant_id);
+        assert_eq!(app.name, app_imported.name);
+        assert!(
+            application::get_user(&app_imported.id, &u.id)
+                .await
+                .unwrap()
+                .is_admin
+        );
+        let int_imported =
+            application::get_integration(&app_imported.id, application::IntegrationKind::Http)
+                .await
+                .unwrap();
+        match int_imported.configuration {
+            application::IntegrationConfiguration::Http(conf) => {
+                assert_eq!("http://localhost:8080/events", conf.event_endpoint_url);
+            }
+            _ => panic!("Invalid configuration"),
+        }
+
+        let relay_devices = relay::list_devices(
+            10,
+            0,
+            &relay::DeviceFilters {
+                relay_dev_eui: Some(relay_d.dev_eui),
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(1, relay_devices.len());
+        assert_eq!(d.dev_eui, relay_devices[0].dev_eui);
+
+        let user_roles = tenant_role::get_user_roles(&tenant_id, &u.id)
+            .await
+            .unwrap();
+        assert_eq!(1, user_roles.len());
+        assert_ne!(role.id, user_roles[0].role_id);
+        assert_eq!(Some(app_imported.id), user_roles[0].application_id);
+        let role_imported = tenant_role::get(&user_roles[0].role_id).await.unwrap();
+        assert_eq!(tenant_id, role_imported.tenant_id);
+        assert_eq!(role.permissions(), role_imported.permissions());
+
+        let dp_imported = device_profile::get(&d_imported.device_profile_id)
+            .await
+            .unwrap();
+        assert_eq!(tenant_id, dp_imported.tenant_id);
+
+        let dk_imported = device_keys::get(&d.dev_eui).await.unwrap();
+        assert_eq!(dk.nwk_key, dk_imported.nwk_key);
+        assert_eq!(dk.app_key, dk_imported.app_key);
+
+        let gw_imported = gateway_storage::get(&gw.gateway_id).await.unwrap();
+        assert_eq!(tenant_id, gw_imported.tenant_id);
+        assert_eq!(gw.fine_timestamp_key, gw_imported.fine_timestamp_key);
+
+        let mgs = multicast::list(
+            10,
+            0,
+            &multicast::Filters {
+                application_id: Some(app_imported.id),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(1, mgs.len());
+        let mg_imported = multicast::get(&mgs[0].id).await.unwrap();
+        assert_eq!(mg.mc_nwk_s_key, mg_imported.mc_nwk_s_key);
+        assert_eq!(mg.mc_app_s_key, mg_imported.mc_app_s_key);
+        assert_eq!(
+            vec![d.dev_eui],
+            multicast::get_dev_euis(&mg_imported.id).await.unwrap()
+        );
+        assert_eq!(
+            vec![gw.gateway_id],
+            multicast::get_gateway_ids(&mg_imported.id).await.unwrap()
+        );
+    }
+}
@@ -0,0 +1,605 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use diesel::dsl;
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use tracing::info;
+use uuid::Uuid;
+
+use lrwn::EUI64;
+
+use super::error::Error;
+use super::get_async_db_conn;
+use super::schema::{
+    application, device, device_profile, gateway, multicast_group, tenant_role, tenant_user_role,
+    user,
+};
+
+#[derive(Queryable, Insertable, PartialEq, Eq, Debug, Clone)]
+#[diesel(table_name = tenant_role)]
+pub struct TenantRole {
+    pub id: Uuid,
+    pub tenant_id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub name: String,
+    pub description: String,
+    pub can_write_applications: bool,
+    pub can_write_integrations: bool,
+    pub can_write_devices: bool,
+    pub can_enqueue: bool,
+    pub can_write_device_profiles: bool,
+    pub can_write_gateways: bool,
+    pub can_write_multicast_groups: bool,
+}
+
+// Permissions which can be granted to tenant users through a role. These are in addition to the
+// permissions implied by the is_admin, is_device_admin and is_gateway_admin tenant user flags.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Permission {
+    ApplicationWrite,
+    IntegrationWrite,
+    DeviceWrite,
+    Enqueue,
+    DeviceProfileWrite,
+    GatewayWrite,
+    MulticastGroupWrite,
+}
+
+impl TenantRole {
+    fn validate(&self) -> Result<(), Error> {
+        if self.name.is_empty() {
+            return Err(Error::Validation("name is not set".into()));
+        }
+        Ok(())
+    }
+
+    pub fn permissions(&self) -> Vec<Permission> {
+        [
+            (self.can_write_applications, Permission::ApplicationWrite),
+            (self.can_write_integrations, Permission::IntegrationWrite),
+            (self.can_write_devices, Permission::DeviceWrite),
+            (self.can_enqueue, Permission::Enqueue),
+            (
+                self.can_write_device_profiles,
+                Permission::DeviceProfileWrite,
+            ),
+            (self.can_write_gateways, Permission::GatewayWrite),
+            (
+                self.can_write_multicast_groups,
+                Permission::MulticastGroupWrite,
+            ),
+        ]
+        .into_iter()
+        .filter(|(ok, _)| *ok)
+        .map(|(_, p)| p)
+        .collect()
+    }
+
+    pub fn set_permissions(&mut self, permissions: &[Permission]) {
+        self.can_write_applications = permissions.contains(&Permission::ApplicationWrite);
+        self.can_write_integrations = permissions.contains(&Permission::IntegrationWrite);
+        self.can_write_devices = permissions.contains(&Permission::DeviceWrite);
+        self.can_enqueue = permissions.contains(&Permission::Enqueue);
+        self.can_write_device_profiles = permissions.contains(&Permission::DeviceProfileWrite);
+        self.can_write_gateways = permissions.contains(&Permission::GatewayWrite);
+        self.can_write_multicast_groups = permissions.contains(&Permission::MulticastGroupWrite);
+    }
+}
+
+impl Default for TenantRole {
+    fn default() -> Self {
+        let now = Utc::now();
+
+        TenantRole {
+            id: Uuid::new_v4(),
+            tenant_id: Uuid::nil(),
+            created_at: now,
+            updated_at: now,
+            name: "".into(),
+            description: "".into(),
+            can_write_applications: false,
+            can_write_integrations: false,
+            can_write_devices: false,
+            can_enqueue: false,
+            can_write_device_profiles: false,
+            can_write_gateways: false,
+            can_write_multicast_groups: false,
+        }
+    }
+}
+
+// Assignment of a role to a tenant user. When application_id is set, the permissions of the
+// role only apply to the given application.
+#[derive(Queryable, Insertable, PartialEq, Eq, Debug, Clone)]
+#[diesel(table_name = tenant_user_role)]
+pub struct TenantUserRole {
+    pub id: Uuid,
+    pub tenant_id: Uuid,
+    pub user_id: Uuid,
+    pub role_id: Uuid,
+    pub application_id: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl Default for TenantUserRole {
+    fn default() -> Self {
+        TenantUserRole {
+            id: Uuid::new_v4(),
+            tenant_id: Uuid::nil(),
+            user_id: Uuid::nil(),
+            role_id: Uuid::nil(),
+            application_id: None,
+            created_at: Utc::now(),
+        }
+    }
+}
+
+// The object to which a permission check applies.
+#[derive(Debug, Clone, Copy)]
+pub enum Scope {
+    Tenant(Uuid),
+    Application(Uuid),
+    Device(EUI64),
+    DeviceProfile(Uuid),
+    Gateway(EUI64),
+    MulticastGroup(Uuid),
+}
+
+impl Scope {
+    // Returns the tenant ID and (if applicable) the application ID of the scope. A nil tenant
+    // ID is returned in case the object does not exist.
+    pub async fn get_tenant_and_application_id(&self) -> Result<(Uuid, Option<Uuid>), Error> {
+        let mut c = get_async_db_conn().await?;
+
+        Ok(match *self {
+            Scope::Tenant(id) => (id, None),
+            Scope::Application(id) => application::dsl::application
+                .select((application::dsl::tenant_id, application::dsl::id.nullable()))
+                .find(&id)
+                .first(&mut c)
+                .await
+                .optional()?
+                .unwrap_or((Uuid::nil(), None)),
+            Scope::Device(dev_eui) => device::dsl::device
+                .inner_join(application::table)
+                .select((application::dsl::tenant_id, application::dsl::id.nullable()))
+                .filter(device::dsl::dev_eui.eq(&dev_eui))
+                .first(&mut c)
+                .await
+                .optional()?
+                .unwrap_or((Uuid::nil(), None)),
+            Scope::DeviceProfile(id) => (
+                device_profile::dsl::device_profile
+                    .select(device_profile::dsl::tenant_id)
+                    .find(&id)
+                    .first(&mut c)
+                    .await
+                    .optional()?
+                    .unwrap_or_default(),
+                None,
+            ),
+            Scope::Gateway(gateway_id) => (
+                gateway::dsl::gateway
+                    .select(gateway::dsl::tenant_id)
+                    .find(&gateway_id)
+                    .first(&mut c)
+                    .await
+                    .optional()?
+                    .unwrap_or_default(),
+                None,
+            ),
+            Scope::MulticastGroup(id) => multicast_group::dsl::multicast_group
+                .inner_join(application::table)
+                .select((application::dsl::tenant_id, application::dsl::id.nullable()))
+                .filter(multicast_group::dsl::id.eq(&id))
+                .first(&mut c)
+                .await
+                .optional()?
+                .unwrap_or((Uuid::nil(), None)),
+        })
+    }
+}
+
+pub async fn create(r: TenantRole) -> Result<TenantRole, Error> {
+    r.validate()?;
+
+    let r: TenantRole = diesel::insert_into(tenant_role::table)
+        .values(&r)
+        .get_result(&mut get_async_db_conn().await?)
+        .await
+        .map_err(|e| Error::from_diesel(e, r.id.to_string()))?;
+    info!(id = %r.id, tenant_id = %r.tenant_id, "Tenant role created");
+    Ok(r)
+}
+
+pub async fn get(id: &Uuid) -> Result<TenantRole, Error> {
+    let r = tenant_role::dsl::tenant_role
+        .find(&id)
+        .first(&mut get_async_db_conn().await?)
+        .await
+        .map_err(|e| Error::from_diesel(e, id.to_string()))?;
+    Ok(r)
+}
+
+pub async fn update(r: TenantRole) -> Result<TenantRole, Error> {
+    r.validate()?;
+
+    let r: TenantRole = diesel::update(tenant_role::dsl::tenant_role.find(&r.id))
+        .set((
+            tenant_role::updated_at.eq(Utc::now()),
+            tenant_role::name.eq(&r.name),
+            tenant_role::description.eq(&r.description),
+            tenant_role::can_write_applications.eq(&r.can_write_applications),
+            tenant_role::can_write_integrations.eq(&r.can_write_integrations),
+            tenant_role::can_write_devices.eq(&r.can_write_devices),
+            tenant_role::can_enqueue.eq(&r.can_enqueue),
+            tenant_role::can_write_device_profiles.eq(&r.can_write_device_profiles),
+            tenant_role::can_write_gateways.eq(&r.can_write_gateways),
+            tenant_role::can_write_multicast_groups.eq(&r.can_write_multicast_groups),
+        ))
+        .get_result(&mut get_async_db_conn().await?)
+        .await
+        .map_err(|e| Error::from_diesel(e, r.id.to_string()))?;
+    info!(id = %r.id, "Tenant role updated");
+    Ok(r)
+}
+
+pub async fn delete(id: &Uuid) -> Result<(), Error> {
+    let ra = diesel::delete(tenant_role::dsl::tenant_role.find(&id))
+        .execute(&mut get_async_db_conn().await?)
+        .await?;
+    if ra == 0 {
+        return Err(Error::NotFound(id.to_string()));
+    }
+    info!(id = %id, "Tenant role deleted");
+    Ok(())
+}
+
+pub async fn get_count(tenant_id: &Uuid) -> Result<i64, Error> {
+    Ok(tenant_role::dsl::tenant_role
+        .select(dsl::count_star())
+        .filter(tenant_role::dsl::tenant_id.eq(&tenant_id))
+        .first(&mut get_async_db_conn().await?)
+        .await?)
+}
+
+pub async fn list(limit: i64, offset: i64, tenant_id: &Uuid) -> Result<Vec<TenantRole>, Error> {
+    let items = tenant_role::dsl::tenant_role
+        .filter(tenant_role::dsl::tenant_id.eq(&tenant_id))
+        .order_by(tenant_role::dsl::name)
+        .limit(limit)
+        .offset(offset)
+        .load(&mut get_async_db_conn().await?)
+        .await?;
+    Ok(items)
+}
+
+pub async fn add_user_role(ur: TenantUserRole) -> Result<TenantUserRole, Error> {
+    let r = get(&ur.role_id).await?;
+    if r.tenant_id != ur.tenant_id {
+        return Err(Error::Validation(
+            "role does not belong to the tenant".into(),
+        ));
+    }
+
+    if let Some(application_id) = &ur.application_id {
+        let tenant_id: Uuid = application::dsl::application
+            .select(application::dsl::tenant_id)
+            .find(&application_id)
+            .first(&mut get_async_db_conn().await?)
+            .await
+            .map_err(|e| Error::from_diesel(e, application_id.to_string()))?;
+        if tenant_id != ur.tenant_id {
+            return Err(Error::Validation(
+                "application does not belong to the tenant".into(),
+            ));
+        }
+    }
+
+    let ur: TenantUserRole = diesel::insert_into(tenant_user_role::table)
+        .values(&ur)
+        .get_result(&mut get_async_db_conn().await?)
+        .await
+        .map_err(|e| match e {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UniqueViolation,
+                _,
+            ) => Error::AlreadyExists(ur.role_id.to_string()),
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::ForeignKeyViolation,
+                _,
+            ) => Error::Validation("user is not a member of the tenant".into()),
+            _ => Error::from_diesel(e, ur.id.to_string()),
+        })?;
+    info!(
+        tenant_id = %ur.tenant_id,
+        user_id = %ur.user_id,
+        role_id = %ur.role_id,
+        "Tenant user role added"
+    );
+    Ok(ur)
+}
+
+pub async fn delete_user_role(tenant_id: &Uuid, user_id: &Uuid, id: &Uuid) -> Result<(), Error> {
+    let ra = diesel::delete(
+        tenant_user_role::dsl::tenant_user_role
+            .filter(tenant_user_role::dsl::id.eq(&id))
+            .filter(tenant_user_role::dsl::tenant_id.eq(&tenant_id))
+            .filter(tenant_user_role::dsl::user_id.eq(&user_id)),
+    )
+    .execute(&mut get_async_db_conn().await?)
+    .await?;
+    if ra == 0 {
+        return Err(Error::NotFound(id.to_string()));
+    }
+    info!(
+        tenant_id = %tenant_id,
+        user_id = %user_id,
+        id = %id,
+        "Tenant user role deleted"
+    );
+    Ok(())
+}
+
+pub async fn get_user_roles(
+    tenant_id: &Uuid,
+    user_id: &Uuid,
+) -> Result<Vec<TenantUserRole>, Error> {
+    let items = tenant_user_role::dsl::tenant_user_role
+        .filter(tenant_user_role::dsl::tenant_id.eq(&tenant_id))
+        .filter(tenant_user_role::dsl::user_id.eq(&user_id))
+        .order_by(tenant_user_role::dsl::created_at)
+        .load(&mut get_async_db_conn().await?)
+        .await?;
+    Ok(items)
+}
+
+// Returns true when the (active) user has been granted the given permission through one of
+// its roles within the tenant of the given scope. Roles assigned to a specific application
+// only apply to the scopes belonging to that application.
+pub async fn has_permission(user_id: &Uuid, perm: Permission, scope: Scope) -> Result<bool, Error> {
+    let (tenant_id, application_id) = scope.get_tenant_and_application_id().await?;
+
+    if tenant_id.is_nil() {
+        return Ok(false);
+    }
+
+    let mut q = tenant_user_role::dsl::tenant_user_role
+        .inner_join(tenant_role::table)
+        .inner_join(user::table)
+        .select(dsl::count_star())
+        .filter(tenant_user_role::dsl::user_id.eq(&user_id))
+        .filter(tenant_user_role::dsl::tenant_id.eq(&tenant_id))
+        .filter(user::dsl::is_active.eq(true))
+        .into_boxed();
+
+    q = match application_id {
+        Some(id) => q.filter(
+            tenant_user_role::dsl::application_id
+                .is_null()
+                .or(tenant_user_role::dsl::application_id.eq(id)),
+        ),
+        None => q.filter(tenant_user_role::dsl::application_id.is_null()),
+    };
+
+    q = match perm {
+        Permission::ApplicationWrite => q.filter(tenant_role::dsl::can_write_applications.eq(true)),
+        Permission::IntegrationWrite => q.filter(tenant_role::dsl::can_write_integrations.eq(true)),
+        Permission::DeviceWrite => q.filter(tenant_role::dsl::can_write_devices.eq(true)),
+        Permission::Enqueue => q.filter(tenant_role::dsl::can_enqueue.eq(true)),
+        Permission::DeviceProfileWrite => {
+            q.filter(tenant_role::dsl::can_write_device_profiles.eq(true))
+        }
+        Permission::GatewayWrite => q.filter(tenant_role::dsl::can_write_gateways.eq(true)),
+        Permission::MulticastGroupWrite => {
+            q.filter(tenant_role::dsl::can_write_multicast_groups.eq(true))
+        }
+    };
+
+    let count: i64 = q.first(&mut get_async_db_conn().await?).await?;
+    Ok(count > 0)
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use crate::storage::{application, tenant, user};
+    use crate::test;
+
+    pub async fn create_role(tenant_id: Uuid, permissions: &[Permission]) -> TenantRole {
+        let mut r = TenantRole {
+            tenant_id,
+            name: format!("role {}", Uuid::new_v4()),
+            ..Default::default()
+        };
+        r.set_permissions(permissions);
+        create(r).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_tenant_role() {
+        let _guard = test::prepare().await;
+
+        let t = tenant::test::create_tenant().await;
+        let r = create_role(t.id, &[Permission::DeviceWrite, Permission::Enqueue]).await;
+        assert_eq!(
+            vec![Permission::DeviceWrite, Permission::Enqueue],
+            r.permissions()
+        );
+
+        // get
+        let r_get = get(&r.id).await.unwrap();
+        assert_eq!(r, r_get);
+
+        // update
+        let mut r = r_get;
+        r.name = "operator".into();
+        r.set_permissions(&[Permission::Enqueue]);
+        let r = update(r).await.unwrap();
+        let r_get = get(&r.id).await.unwrap();
+        assert_eq!(r, r_get);
+        assert_eq!(vec![Permission::Enqueue], r_get.permissions());
+
+        // count and list
+        assert_eq!(1, get_count(&t.id).await.unwrap());
+        let items = list(10, 0, &t.id).await.unwrap();
+        assert_eq!(1, items.len());
+        assert_eq!(r.id, items[0].id);
+
+        // delete
+        delete(&r.id).await.unwrap();
+        assert!(delete(&r.id).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_has_permission() {
+        let _guard = test::prepare().await;
+
+        let t = tenant::test::create_tenant().await;
+        let t2 = tenant::test::create_tenant().await;
+        let app_a = application::create(application::Application {
+            tenant_id: t.id,
+            name: "app a".into(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let app_b = application::create(application::Application {
+            tenant_id: t.id,
+            name: "app b".into(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let app_t2 = application::create(application::Application {
+            tenant_id: t2.id,
+            name: "app t2".into(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        let u = user::create(user::User {
+            email: "role@user".into(),
+            is_active: true,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        tenant::add_user(tenant::TenantUser {
+            tenant_id: t.id,
+            user_id: u.id,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        let operator = create_role(t.id, &[Permission::Enqueue]).await;
+        let integrations = create_role(t.id, &[Permission::IntegrationWrite]).await;
+        let role_t2 = create_role(t2.id, &[Permission::Enqueue]).await;
+
+        // no roles assigned
+        assert!(
+            !has_permission(&u.id, Permission::Enqueue, Scope::Application(app_a.id))
+                .await
+                .unwrap()
+        );
+
+        // role of other tenant can not be assigned
+        assert!(add_user_role(TenantUserRole {
+            tenant_id: t.id,
+            user_id: u.id,
+            role_id: role_t2.id,
+            ..Default::default()
+        })
+        .await
+        .is_err());
+
+        // application of other tenant can not be assigned
+        assert!(add_user_role(TenantUserRole {
+            tenant_id: t.id,
+            user_id: u.id,
+            role_id: operator.id,
+            application_id: Some(app_t2.id),
+            ..Default::default()
+        })
+        .await
+        .is_err());
+
+        // tenant-wide role
+        add_user_role(TenantUserRole {
+            tenant_id: t.id,
+            user_id: u.id,
+            role_id: operator.id,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        // application scoped role
+        let ur = add_user_role(TenantUserRole {
+            tenant_id: t.id,
+            user_id: u.id,
+            role_id: integrations.id,
+            application_id: Some(app_a.id),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        // duplicate
+        assert!(add_user_role(TenantUserRole {
+            tenant_id: t.id,
+            user_id: u.id,
+            role_id: integrations.id,
+            application_id: Some(app_a.id),
+            ..Default::default()
+        })
+        .await
+        .is_err());
+
+        assert_eq!(2, get_user_roles(&t.id, &u.id).await.unwrap().len());
+
+        for (perm, scope, expected) in [
+            (Permission::Enqueue, Scope::Application(app_a.id), true),
+            (Permission::Enqueue, Scope::Application(app_b.id), true),
+            (Permission::Enqueue, Scope::Tenant(t.id), true),
+            (Permission::Enqueue, Scope::Application(app_t2.id), false),
+            (Permission::Enqueue, Scope::Tenant(t2.id), false),
+            (
+                Permission::IntegrationWrite,
+                Scope::Application(app_a.id),
+                true,
+            ),
+            (
+                Permission::IntegrationWrite,
+                Scope::Application(app_b.id),
+                false,
+            ),
+            (Permission::IntegrationWrite, Scope::Tenant(t.id), false),
+            (Permission::DeviceWrite, Scope::Application(app_a.id), false),
+        ] {
+            assert_eq!(
+                expected,
+                has_permission(&u.id, perm, scope).await.unwrap(),
+                "{:?}",
+                perm
+            );
+        }
+
+        // remove role
+        delete_user_role(&t.id, &u.id, &ur.id).await.unwrap();
+        assert!(!has_permission(
+            &u.id,
+            Permission::IntegrationWrite,
+            Scope::Application(app_a.id)
+        )
+        .await
+        .unwrap());
+
+        // removing the user from the tenant removes its roles
+        tenant::delete_user(&t.id, &u.id).await.unwrap();
+        assert!(get_user_roles(&t.id, &u.id).await.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,481 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use anyhow::Result;
+use chrono::{DateTime, Local, Utc};
+use diesel::prelude::*;
+use diesel::upsert::excluded;
+use diesel_async::RunQueryDsl;
+use tracing::{error, info};
+use uuid::Uuid;
+
+use lrwn::EUI64;
+
+use super::error::Error;
+use super::schema::{tenant, tenant_usage};
+use super::{get_async_db_conn, metrics};
+use crate::helpers::errors::PrintFullError;
+
+// The tenant usage is counted using the (Redis) metrics storage. As these metrics expire
+// (e.g. the hourly aggregates after two days), the aggregates are periodically persisted
+// into the tenant_usage table, such that they remain available for billing.
+
+// The max. number of intervals that can be requested at once.
+const MAX_INTERVALS: i64 = 1000;
+
+fn get_name(tenant_id: &Uuid) -> String {
+    format!("tenant:{}", tenant_id)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Usage {
+    pub time: DateTime<Local>,
+    pub uplinks: u64,
+    pub uplink_bytes: u64,
+    pub downlinks: u64,
+    pub downlink_bytes: u64,
+    pub active_devices: u64,
+    pub gateway_airtime_ms: u64,
+    pub roaming_uplinks: u64,
+}
+
+#[derive(Queryable, Insertable, Debug, Clone)]
+#[diesel(table_name = tenant_usage)]
+struct UsageRow {
+    tenant_id: Uuid,
+    aggregation: String,
+    time: DateTime<Utc>,
+    uplinks: i64,
+    uplink_bytes: i64,
+    downlinks: i64,
+    downlink_bytes: i64,
+    active_devices: i64,
+    gateway_airtime_ms: i64,
+    roaming_uplinks: i64,
+}
+
+impl UsageRow {
+    fn new(tenant_id: &Uuid, a: metrics::Aggregation, u: &Usage) -> Self {
+        UsageRow {
+            tenant_id: *tenant_id,
+            aggregation: a.to_string(),
+            time: u.time.into(),
+            uplinks: u.uplinks as i64,
+            uplink_bytes: u.uplink_bytes as i64,
+            downlinks: u.downlinks as i64,
+            downlink_bytes: u.downlink_bytes as i64,
+            active_devices: u.active_devices as i64,
+            gateway_airtime_ms: u.gateway_airtime_ms as i64,
+            roaming_uplinks: u.roaming_uplinks as i64,
+        }
+    }
+}
+
+impl Usage {
+    fn is_empty(&self) -> bool {
+        self.uplinks == 0 && self.downlinks == 0 && self.active_devices == 0
+    }
+
+    // Merges the persisted usage into the usage read from the metrics storage. As the persisted
+    // usage is a (possibly older) copy of the same counters, the highest value is used.
+    fn merge(&mut self, r: &UsageRow) {
+        self.uplinks = self.uplinks.max(r.uplinks as u64);
+        self.uplink_bytes = self.uplink_bytes.max(r.uplink_bytes as u64);
+        self.downlinks = self.downlinks.max(r.downlinks as u64);
+        self.downlink_bytes = self.downlink_bytes.max(r.downlink_bytes as u64);
+        self.active_devices = self.active_devices.max(r.active_devices as u64);
+        self.gateway_airtime_ms = self.gateway_airtime_ms.max(r.gateway_airtime_ms as u64);
+        self.roaming_uplinks = self.roaming_uplinks.max(r.roaming_uplinks as u64);
+    }
+}
+
+// Records an uplink received from the given device. The device is also counted as active device.
+pub async fn save_uplink(
+    tenant_id: &Uuid,
+    dev_eui: &EUI64,
+    phy_payload_size: usize,
+    roaming: bool,
+) -> Result<()> {
+    let name = get_name(tenant_id);
+    let record = metrics::Record {
+        time: Local::now(),
+        kind: metrics::Kind::ABSOLUTE,
+        metrics: [
+            ("uplinks".to_string(), 1.0),
+            ("uplink_bytes".to_string(), phy_payload_size as f64),
+            (
+                "roaming_uplinks".to_string(),
+                if roaming { 1.0 } else { 0.0 },
+            ),
+        ]
+        .into_iter()
+        .collect(),
+    };
+
+    metrics::save(
+        &name,
+        &record,
+        &metrics::Aggregation::default_aggregations(),
+    )
+    .await?;
+    metrics::save_unique(
+        &name,
+        record.time,
+        &dev_eui.to_string(),
+        &metrics::Aggregation::default_aggregations(),
+    )
+    .await
+}
+
+// Records a downlink transmitted by a gateway to a device of the tenant.
+pub async fn save_downlink(
+    tenant_id: &Uuid,
+    phy_payload_size: usize,
+    airtime: Duration,
+) -> Result<()> {
+    let record = metrics::Record {
+        time: Local::now(),
+        kind: metrics::Kind::ABSOLUTE,
+        metrics: [
+            ("downlinks".to_string(), 1.0),
+            ("downlink_bytes".to_string(), phy_payload_size as f64),
+            (
+                "gateway_airtime_ms".to_string(),
+                airtime.as_secs_f64() * 1000.0,
+            ),
+        ]
+        .into_iter()
+        .collect(),
+    };
+
+    metrics::save(
+        &get_name(tenant_id),
+        &record,
+        &metrics::Aggregation::default_aggregations(),
+    )
+    .await
+}
+
+// Validates that the given interval does not exceed the max. number of intervals.
+pub fn validate_range(
+    a: metrics::Aggregation,
+    start: DateTime<Local>,
+    end: DateTime<Local>,
+) -> Result<(), Error> {
+    if end < start {
+        return Err(Error::Validation(
+            "The end timestamp must be after the start timestamp".into(),
+        ));
+    }
+
+    let interval = match a {
+        metrics::Aggregation::MINUTE => chrono::Duration::minutes(1),
+        metrics::Aggregation::HOUR => chrono::Duration::hours(1),
+        metrics::Aggregation::DAY => chrono::Duration::days(1),
+        metrics::Aggregation::MONTH => chrono::Duration::days(28),
+    };
+
+    if (end - start).num_seconds() / interval.num_seconds() >= MAX_INTERVALS {
+        return Err(Error::Validation(format!(
+            "The interval can not exceed {} {} aggregates",
+            MAX_INTERVALS, a
+        )));
+    }
+
+    Ok(())
+}
+
+// Returns the usage of the given tenant. This combines the usage from the metrics storage
+// with the usage that was persisted before the metrics expired.
+pub async fn get(
+    tenant_id: &Uuid,
+    a: metrics::Aggregation,
+    start: DateTime<Local>,
+    end: DateTime<Local>,
+) -> Result<Vec<Usage>> {
+    let persisted: HashMap<DateTime<Utc>, UsageRow> = tenant_usage::dsl::tenant_usage
+        .filter(tenant_usage::dsl::tenant_id.eq(tenant_id))
+        .filter(tenant_usage::dsl::aggregation.eq(a.to_string()))
+        .filter(tenant_usage::dsl::time.ge(start - get_interval_offset(a)))
+        .filter(tenant_usage::dsl::time.le(end))
+        .load::<UsageRow>(&mut get_async_db_conn().await?)
+        .await?
+        .into_iter()
+        .map(|r| (r.time, r))
+        .collect();
+
+    let mut usage = get_metrics(tenant_id, a, start, end).await?;
+    for u in &mut usage {
+        if let Some(r) = persisted.get(&u.time.with_timezone(&Utc)) {
+            u.merge(r);
+        }
+    }
+
+    Ok(usage)
+}
+
+// Persists the (non-empty) usage aggregates of the given tenant and interval.
+pub async fn persist(
+    tenant_id: &Uuid,
+    a: metrics::Aggregation,
+    start: DateTime<Local>,
+    end: DateTime<Local>,
+) -> Result<usize> {
+    let rows: Vec<UsageRow> = get_metrics(tenant_id, a, start, end)
+        .await?
+        .iter()
+        .filter(|u| !u.is_empty())
+        .map(|u| UsageRow::new(tenant_id, a, u))
+        .collect();
+
+    if rows.is_empty() {
+        return Ok(0);
+    }
+
+    let ra = diesel::insert_into(tenant_usage::table)
+        .values(&rows)
+        .on_conflict((
+            tenant_usage::dsl::tenant_id,
+            tenant_usage::dsl::aggregation,
+            tenant_usage::dsl::time,
+        ))
+        .do_update()
+        .set((
+            tenant_usage::dsl::uplinks.eq(excluded(tenant_usage::dsl::uplinks)),
+            tenant_usage::dsl::uplink_bytes.eq(excluded(tenant_usage::dsl::uplink_bytes)),
+            tenant_usage::dsl::downlinks.eq(excluded(tenant_usage::dsl::downlinks)),
+            tenant_usage::dsl::downlink_bytes.eq(excluded(tenant_usage::dsl::downlink_bytes)),
+            tenant_usage::dsl::active_devices.eq(excluded(tenant_usage::dsl::active_devices)),
+            tenant_usage::dsl::gateway_airtime_ms
+                .eq(excluded(tenant_usage::dsl::gateway_airtime_ms)),
+            tenant_usage::dsl::roaming_uplinks.eq(excluded(tenant_usage::dsl::roaming_uplinks)),
+        ))
+        .execute(&mut get_async_db_conn().await?)
+        .await?;
+
+    Ok(ra)
+}
+
+// Periodically persists the recent usage aggregates of all tenants, well within the retention
+// of the metrics storage.
+pub async fn persist_loop() {
+    let mut interval = tokio::time::interval(std::time::Duration::from_secs(60 * 60));
+    loop {
+        interval.tick().await;
+
+        if let Err(e) = persist_all().await {
+            error!(error = %e.full(), "Persist tenant usage error");
+        }
+    }
+}
+
+async fn persist_all() -> Result<()> {
+    let tenant_ids: Vec<Uuid> = tenant::dsl::tenant
+        .select(tenant::dsl::id)
+        .load(&mut get_async_db_conn().await?)
+        .await?;
+
+    let now = Local::now();
+    let mut count = 0;
+
+    for tenant_id in &tenant_ids {
+        for (a, start) in [
+            (
+                metrics::Aggregation::HOUR,
+                now - chrono::Duration::hours(25),
+            ),
+            (metrics::Aggregation::DAY, now - chrono::Duration::days(2)),
+            (
+                metrics::Aggregation::MONTH,
+                now - chrono::Duration::days(32),
+            ),
+        ] {
+            count += persist(tenant_id, a, start, now).await?;
+        }
+    }
+
+    info!(
+        tenants = tenant_ids.len(),
+        aggregates = count,
+        "Tenant usage persisted"
+    );
+
+    Ok(())
+}
+
+// Returns the offset to apply to the start timestamp, such that the aggregate containing the
+// start timestamp is included.
+fn get_interval_offset(a: metrics::Aggregation) -> chrono::Duration {
+    match a {
+        metrics::Aggregation::MINUTE => chrono::Duration::minutes(1),
+        metrics::Aggregation::HOUR => chrono::Duration::hours(1),
+        metrics::Aggregation::DAY => chrono::Duration::days(1),
+        metrics::Aggregation::MONTH => chrono::Duration::days(31),
+    }
+}
+
+async fn get_metrics(
+    tenant_id: &Uuid,
+    a: metrics::Aggregation,
+    start: DateTime<Local>,
+    end: DateTime<Local>,
+) -> Result<Vec<Usage>> {
+    let name = get_name(tenant_id);
+    let records = metrics::get(&name, metrics::Kind::ABSOLUTE, a, start, end).await?;
+    let active_devices: HashMap<DateTime<Local>, usize> = metrics::get_unique(&name, a, start, end)
+        .await?
+        .into_iter()
+        .collect();
+
+    Ok(records
+        .into_iter()
+        .map(|r| {
+            let get = |k: &str| r.metrics.get(k).cloned().unwrap_or_default().round() as u64;
+
+            Usage {
+                time: r.time,
+                uplinks: get("uplinks"),
+                uplink_bytes: get("uplink_bytes"),
+                downlinks: get("downlinks"),
+                downlink_bytes: get("downlink_bytes"),
+                active_devices: active_devices.get(&r.time).cloned().unwrap_or_default() as u64,
+                gateway_airtime_ms: get("gateway_airtime_ms"),
+                roaming_uplinks: get("roaming_uplinks"),
+            }
+        })
+        .collect())
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use crate::storage::tenant as tenant_storage;
+    use crate::test;
+    use chrono::Timelike;
+
+    #[tokio::test]
+    async fn test_usage() {
+        let _guard = test::prepare().await;
+
+        let tenant_id = Uuid::new_v4();
+        let dev_eui_1 = EUI64::from_be_bytes([1, 1, 1, 1, 1, 1, 1, 1]);
+        let dev_eui_2 = EUI64::from_be_bytes([2, 2, 2, 2, 2, 2, 2, 2]);
+
+        save_uplink(&tenant_id, &dev_eui_1, 20, false)
+            .await
+            .unwrap();
+        save_uplink(&tenant_id, &dev_eui_1, 30, false)
+            .await
+            .unwrap();
+        save_uplink(&tenant_id, &dev_eui_2, 10, true).await.unwrap();
+        save_downlink(&tenant_id, 15, Duration::from_micros(61_696))
+            .await
+            .unwrap();
+
+        let now = Local::now();
+        let usage = get(&tenant_id, metrics::Aggregation::HOUR, now, now)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            vec![Usage {
+                time: now
+                    .with_minute(0)
+                    .unwrap()
+                    .with_second(0)
+                    .unwrap()
+                    .with_nanosecond(0)
+                    .unwrap(),
+                uplinks: 3,
+                uplink_bytes: 60,
+                downlinks: 1,
+                downlink_bytes: 15,
+                active_devices: 2,
+                gateway_airtime_ms: 62,
+                roaming_uplinks: 1,
+            }],
+            usage
+        );
+
+        // Other tenant.
+        let usage = get(&Uuid::new_v4(), metrics::Aggregation::HOUR, now, now)
+            .await
+            .unwrap();
+        assert_eq!(0, usage[0].uplinks);
+        assert_eq!(0, usage[0].active_devices);
+    }
+
+    #[tokio::test]
+    async fn test_persist() {
+        let _guard = test::prepare().await;
+
+        let t = tenant_storage::create(tenant_storage::Tenant {
+            name: "test-tenant".into(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let dev_eui = EUI64::from_be_bytes([1, 1, 1, 1, 1, 1, 1, 1]);
+
+        save_uplink(&t.id, &dev_eui, 20, false).await.unwrap();
+
+        let now = Local::now();
+        let ra = persist(&t.id, metrics::Aggregation::HOUR, now, now)
+            .await
+            .unwrap();
+        assert_eq!(1, ra);
+
+        // Persisting again updates the same aggregate.
+        save_uplink(&t.id, &dev_eui, 20, false).await.unwrap();
+        let ra = persist(&t.id, metrics::Aggregation::HOUR, now, now)
+            .await
+            .unwrap();
+        assert_eq!(1, ra);
+
+        let rows: Vec<UsageRow> = tenant_usage::dsl::tenant_usage
+            .filter(tenant_usage::dsl::tenant_id.eq(&t.id))
+            .load(&mut get_async_db_conn().await.unwrap())
+            .await
+            .unwrap();
+        assert_eq!(1, rows.len());
+        assert_eq!("HOUR", rows[0].aggregation);
+        assert_eq!(2, rows[0].uplinks);
+        assert_eq!(40, rows[0].uplink_bytes);
+        assert_eq!(1, rows[0].active_devices);
+
+        let usage = get(&t.id, metrics::Aggregation::HOUR, now, now)
+            .await
+            .unwrap();
+        assert_eq!(2, usage[0].uplinks);
+    }
+
+    #[test]
+    fn test_validate_range() {
+        let now = Local::now();
+
+        assert!(validate_range(
+            metrics::Aggregation::HOUR,
+            now - chrono::Duration::hours(24),
+            now
+        )
+        .is_ok());
+        assert!(validate_range(
+            metrics::Aggregation::HOUR,
+            now - chrono::Duration::days(365),
+            now
+        )
+        .is_err());
+        assert!(validate_range(
+            metrics::Aggregation::MONTH,
+            now - chrono::Duration::days(365),
+            now
+        )
+        .is_ok());
+        assert!(validate_range(
+            metrics::Aggregation::DAY,
+            now,
+            now - chrono::Duration::days(1)
+        )
+        .is_err());
+    }
+}
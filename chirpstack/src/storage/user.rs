@@ -1,19 +1,31 @@
 use anyhow::Result;
 use chrono::{DateTime, Utc};
 use diesel::{dsl, prelude::*};
-use diesel_async::RunQueryDsl;
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
 use email_address::EmailAddress;
+use hmac::{Hmac, Mac};
 use pbkdf2::{
     password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
     Algorithm, Pbkdf2,
 };
+use rand::Rng;
 use rand_core::OsRng;
-use tracing::info;
+use sha2::Sha256;
+use tracing::{info, warn};
 use uuid::Uuid;
 
 use super::error::Error;
-use super::get_async_db_conn;
-use super::schema::user;
+use super::schema::{user, user_password_history, user_recovery_code};
+use super::{get_async_db_conn, get_async_redis_conn, redis_key};
+use crate::backend::keywrap;
+use crate::config;
+use crate::helpers::totp;
+
+const RECOVERY_CODE_COUNT: usize = 10;
+
+// The session revocation timestamp is read on every API request. It is cached in Redis for
+// this number of seconds, revoke_sessions updates the cached value.
+const SESSIONS_REVOKED_AT_CACHE_TTL: usize = 60;
 
 #[derive(Queryable, Insertable, PartialEq, Eq, Debug, Clone)]
 #[diesel(table_name = user)]
@@ -28,6 +40,13 @@ pub struct User {
     pub email_verified: bool,
     pub password_hash: String,
     pub note: String,
+    pub totp_secret: Option<Vec<u8>>,
+    pub totp_kek_label: String,
+    pub totp_enabled: bool,
+    pub totp_last_counter: i64,
+    pub failed_login_count: i32,
+    pub locked_until: Option<DateTime<Utc>>,
+    pub sessions_revoked_at: Option<DateTime<Utc>>,
 }
 
 impl Default for User {
@@ -45,10 +64,35 @@ impl Default for User {
             email_verified: false,
             password_hash: "".into(),
             note: "".into(),
+            totp_secret: None,
+            totp_kek_label: "".into(),
+            totp_enabled: false,
+            totp_last_counter: 0,
+            failed_login_count: 0,
+            locked_until: None,
+            sessions_revoked_at: None,
         }
     }
 }
 
+#[derive(Insertable, Debug, Clone)]
+#[diesel(table_name = user_password_history)]
+struct PasswordHistoryItem {
+    id: Uuid,
+    user_id: Uuid,
+    created_at: DateTime<Utc>,
+    password_hash: String,
+}
+
+#[derive(Insertable, Debug, Clone)]
+#[diesel(table_name = user_recovery_code)]
+struct RecoveryCode {
+    id: Uuid,
+    user_id: Uuid,
+    created_at: DateTime<Utc>,
+    code_hash: String,
+}
+
 impl User {
     pub fn validate(&self) -> Result<(), Error> {
         if self.email != "admin" && !EmailAddress::is_valid(&self.email) {
@@ -62,6 +106,22 @@ impl User {
         self.password_hash = hash_password(pw, rounds)?;
         Ok(())
     }
+
+    pub fn is_locked(&self) -> bool {
+        match self.locked_until {
+            Some(v) => v > Utc::now(),
+            None => false,
+        }
+    }
+
+    // Returns the (decrypted) TOTP secret.
+    pub fn get_totp_secret(&self) -> Result<Vec<u8>, Error> {
+        let secret = self
+            .totp_secret
+            .as_ref()
+            .ok_or_else(|| Error::Validation("TOTP enrollment has not been started".into()))?;
+        Ok(keywrap::unwrap_data(&self.totp_kek_label, secret)?)
+    }
 }
 
 pub async fn create(u: User) -> Result<User, Error> {
@@ -119,17 +179,75 @@ pub async fn get_by_email_and_pw(email: &str, pw: &str) -> Result<User, Error> {
         }
     };
 
-    if verify_password(pw, &u.password_hash) {
+    // The password is validated first, such that the lock state of the user is not exposed
+    // to a caller who does not know the password. Failed logins of a locked user are not
+    // registered, as this would extend the lockout.
+    if !verify_password(pw, &u.password_hash) {
+        if !u.is_locked() {
+            register_failed_login(&u.id).await?;
+        }
+        return Err(Error::InvalidUsernameOrPassword);
+    }
+
+    if u.is_locked() {
+        return Err(Error::UserLocked);
+    }
+
+    // When TOTP is enabled, the failed login counter must be reset after validating the
+    // TOTP code, else it would be possible to brute-force the TOTP code.
+    if u.totp_enabled || (u.failed_login_count == 0 && u.locked_until.is_none()) {
         return Ok(u);
     }
+    reset_failed_logins(&u.id).await
+}
+
+// Increments the failed login counter of the user. When the configured max. number of failed
+// logins has been reached, the user is locked for the configured lockout duration.
+pub async fn register_failed_login(id: &Uuid) -> Result<User, Error> {
+    let conf = config::get();
+    let max_failed_logins = conf.user_authentication.internal.max_failed_logins as i32;
+
+    let mut u: User = diesel::update(user::dsl::user.find(&id))
+        .set(user::failed_login_count.eq(user::failed_login_count + 1))
+        .get_result(&mut get_async_db_conn().await?)
+        .await
+        .map_err(|e| Error::from_diesel(e, id.to_string()))?;
 
-    Err(Error::InvalidUsernameOrPassword)
+    if max_failed_logins > 0 && u.failed_login_count >= max_failed_logins {
+        let locked_until = Utc::now()
+            + chrono::Duration::from_std(conf.user_authentication.internal.lockout_duration)
+                .map_err(anyhow::Error::from)?;
+
+        u = diesel::update(user::dsl::user.find(&id))
+            .set((
+                user::failed_login_count.eq(0),
+                user::locked_until.eq(Some(locked_until)),
+            ))
+            .get_result(&mut get_async_db_conn().await?)
+            .await
+            .map_err(|e| Error::from_diesel(e, id.to_string()))?;
+        warn!(user_id = %id, locked_until = %locked_until, "User locked after failed logins");
+    }
+
+    Ok(u)
+}
+
+pub async fn reset_failed_logins(id: &Uuid) -> Result<User, Error> {
+    let u: User = diesel::update(user::dsl::user.find(&id))
+        .set((
+            user::failed_login_count.eq(0),
+            user::locked_until.eq(None::<DateTime<Utc>>),
+        ))
+        .get_result(&mut get_async_db_conn().await?)
+        .await
+        .map_err(|e| Error::from_diesel(e, id.to_string()))?;
+    Ok(u)
 }
 
 pub async fn update(u: User) -> Result<User, Error> {
     u.validate()?;
 
-    let u: User = diesel::update(user::dsl::user.find(&u.id))
+    let mut u: User = diesel::update(user::dsl::user.find(&u.id))
         .set((
             user::updated_at.eq(Utc::now()),
             user::is_admin.eq(&u.is_admin),
@@ -143,19 +261,362 @@ pub async fn update(u: User) -> Result<User, Error> {
         .await
         .map_err(|e| Error::from_diesel(e, u.id.to_string()))?;
     info!(user_id = %u.id, "User updated");
+
+    // Tokens issued before the user was disabled must not become valid again when the user is
+    // re-activated.
+    if !u.is_active {
+        u = revoke_sessions(&u.id).await?;
+    }
+
     Ok(u)
 }
 
+// Sets the password hash. This stores the previous password hash in the password history (if
+// enabled) and resets the failed login counter and lockout.
 pub async fn set_password_hash(id: &Uuid, hash: &str) -> Result<User, Error> {
+    let conf = config::get();
+    let history = conf.user_authentication.internal.password_policy.history as i64;
+    let id = *id;
+    let hash = hash.to_string();
+
+    let mut c = get_async_db_conn().await?;
+    let u: User = c
+        .build_transaction()
+        .run::<User, Error, _>(|c| {
+            Box::pin(async move {
+                let u: User = user::dsl::user
+                    .find(&id)
+                    .for_update()
+                    .first(c)
+                    .await
+                    .map_err(|e| Error::from_diesel(e, id.to_string()))?;
+
+                if history > 0 && !u.password_hash.is_empty() {
+                    diesel::insert_into(user_password_history::table)
+                        .values(&PasswordHistoryItem {
+                            id: Uuid::new_v4(),
+                            user_id: id,
+                            created_at: Utc::now(),
+                            password_hash: u.password_hash.clone(),
+                        })
+                        .execute(c)
+                        .await?;
+                }
+
+                let keep: Vec<Uuid> = user_password_history::dsl::user_password_history
+                    .select(user_password_history::dsl::id)
+                    .filter(user_password_history::dsl::user_id.eq(&id))
+                    .order_by(user_password_history::dsl::created_at.desc())
+                    .limit(history)
+                    .load(c)
+                    .await?;
+                diesel::delete(
+                    user_password_history::dsl::user_password_history
+                        .filter(user_password_history::dsl::user_id.eq(&id))
+                        .filter(user_password_history::dsl::id.ne_all(keep)),
+                )
+                .execute(c)
+                .await?;
+
+                diesel::update(user::dsl::user.find(&id))
+                    .set((
+                        user::password_hash.eq(&hash),
+                        user::failed_login_count.eq(0),
+                        user::locked_until.eq(None::<DateTime<Utc>>),
+                    ))
+                    .get_result(c)
+                    .await
+                    .map_err(|e| Error::from_diesel(e, id.to_string()))
+            })
+        })
+        .await?;
+    info!(id = %id, "Password set");
+    Ok(u)
+}
+
+// Validates the password against the configured password policy.
+pub fn validate_password(pw: &str) -> Result<(), Error> {
+    let conf = config::get();
+    let policy = &conf.user_authentication.internal.password_policy;
+
+    if pw.chars().count() < policy.min_length {
+        return Err(Error::Validation(format!(
+            "password must contain at least {} characters",
+            policy.min_length
+        )));
+    }
+    if policy.require_uppercase && !pw.chars().any(|c| c.is_uppercase()) {
+        return Err(Error::Validation(
+            "password must contain an uppercase character".into(),
+        ));
+    }
+    if policy.require_lowercase && !pw.chars().any(|c| c.is_lowercase()) {
+        return Err(Error::Validation(
+            "password must contain a lowercase character".into(),
+        ));
+    }
+    if policy.require_digit && !pw.chars().any(|c| c.is_ascii_digit()) {
+        return Err(Error::Validation("password must contain a digit".into()));
+    }
+    if policy.require_special && !pw.chars().any(|c| !c.is_alphanumeric()) {
+        return Err(Error::Validation(
+            "password must contain a special character".into(),
+        ));
+    }
+
+    Ok(())
+}
+
+// Validates that the password is not equal to the current password or one of the passwords in
+// the password history.
+pub async fn validate_password_history(id: &Uuid, pw: &str) -> Result<(), Error> {
+    let conf = config::get();
+    let history = conf.user_authentication.internal.password_policy.history as i64;
+    if history == 0 {
+        return Ok(());
+    }
+
+    let u = get(id).await?;
+    let mut hashes: Vec<String> = user_password_history::dsl::user_password_history
+        .select(user_password_history::dsl::password_hash)
+        .filter(user_password_history::dsl::user_id.eq(&id))
+        .order_by(user_password_history::dsl::created_at.desc())
+        .limit(history)
+        .load(&mut get_async_db_conn().await?)
+        .await?;
+    hashes.push(u.password_hash);
+
+    if hashes.iter().any(|h| verify_password(pw, h)) {
+        return Err(Error::Validation("password has been used before".into()));
+    }
+
+    Ok(())
+}
+
+// Generates a new TOTP secret for the user. TOTP is not enabled until the secret has been
+// confirmed using enable_totp. The secret is stored encrypted, using the configured KEK.
+pub async fn enroll_totp(id: &Uuid) -> Result<User, Error> {
+    let u = get(id).await?;
+    if u.totp_enabled {
+        return Err(Error::Validation("TOTP is already enabled".into()));
+    }
+
+    let conf = config::get();
+    let kek_label = &conf.user_authentication.internal.totp_kek_label;
+    if kek_label.is_empty() {
+        return Err(Error::Anyhow(anyhow!("TOTP KEK label is not configured")));
+    }
+    let secret = keywrap::wrap_data(kek_label, &totp::generate_secret())?;
+
     let u: User = diesel::update(user::dsl::user.find(&id))
-        .set(user::password_hash.eq(&hash))
+        .set((
+            user::totp_secret.eq(Some(secret)),
+            user::totp_kek_label.eq(kek_label),
+            user::totp_last_counter.eq(0),
+        ))
         .get_result(&mut get_async_db_conn().await?)
         .await
         .map_err(|e| Error::from_diesel(e, id.to_string()))?;
-    info!(id = %id, "Password set");
+    info!(user_id = %id, "TOTP enrollment started");
     Ok(u)
 }
 
+// Enables TOTP after validating the code against the enrolled secret. This returns the
+// (plaintext) recovery codes, only their hashes are stored.
+pub async fn enable_totp(id: &Uuid, code: &str) -> Result<Vec<String>, Error> {
+    let u = get(id).await?;
+    if u.totp_enabled {
+        return Err(Error::Validation("TOTP is already enabled".into()));
+    }
+    let secret = u.get_totp_secret()?;
+    let counter =
+        totp::verify(&secret, code, Utc::now().timestamp() as u64)?.ok_or(Error::InvalidMfaCode)?;
+
+    let id = *id;
+    let codes = generate_recovery_codes();
+    let codes_copy = codes.clone();
+
+    let mut c = get_async_db_conn().await?;
+    c.build_transaction()
+        .run::<(), Error, _>(|c| {
+            Box::pin(async move {
+                diesel::update(user::dsl::user.find(&id))
+                    .set((
+                        user::totp_enabled.eq(true),
+                        user::totp_last_counter.eq(counter as i64),
+                    ))
+                    .execute(c)
+                    .await?;
+                set_recovery_codes(c, &id, &codes_copy).await
+            })
+        })
+        .await?;
+    info!(user_id = %id, "TOTP enabled");
+    Ok(codes)
+}
+
+pub async fn disable_totp(id: &Uuid) -> Result<User, Error> {
+    let id = *id;
+
+    let mut c = get_async_db_conn().await?;
+    let u: User = c
+        .build_transaction()
+        .run::<User, Error, _>(|c| {
+            Box::pin(async move {
+                diesel::delete(
+                    user_recovery_code::dsl::user_recovery_code
+                        .filter(user_recovery_code::dsl::user_id.eq(&id)),
+                )
+                .execute(c)
+                .await?;
+
+                diesel::update(user::dsl::user.find(&id))
+                    .set((
+                        user::totp_secret.eq(None::<Vec<u8>>),
+                        user::totp_kek_label.eq(""),
+                        user::totp_enabled.eq(false),
+                        user::totp_last_counter.eq(0),
+                    ))
+                    .get_result(c)
+                    .await
+                    .map_err(|e| Error::from_diesel(e, id.to_string()))
+            })
+        })
+        .await?;
+    info!(user_id = %id, "TOTP disabled");
+    Ok(u)
+}
+
+// Validates the TOTP code of the user. A code can only be used once, codes for the same or an
+// older time-step than the last accepted code are rejected.
+pub async fn verify_totp(u: &User, code: &str) -> Result<(), Error> {
+    if !u.totp_enabled {
+        return Err(Error::Validation("TOTP is not enabled".into()));
+    }
+    let secret = u.get_totp_secret()?;
+    let counter = totp::verify(&secret, code, Utc::now().timestamp() as u64)?
+        .ok_or(Error::InvalidMfaCode)? as i64;
+
+    let ra = diesel::update(
+        user::dsl::user
+            .find(&u.id)
+            .filter(user::dsl::totp_last_counter.lt(counter)),
+    )
+    .set(user::totp_last_counter.eq(counter))
+    .execute(&mut get_async_db_conn().await?)
+    .await?;
+    if ra == 0 {
+        return Err(Error::InvalidMfaCode);
+    }
+
+    Ok(())
+}
+
+// Validates and consumes the recovery code.
+pub async fn use_recovery_code(id: &Uuid, code: &str) -> Result<(), Error> {
+    let ra = diesel::delete(
+        user_recovery_code::dsl::user_recovery_code
+            .filter(user_recovery_code::dsl::user_id.eq(&id))
+            .filter(user_recovery_code::dsl::code_hash.eq(hash_recovery_code(id, code))),
+    )
+    .execute(&mut get_async_db_conn().await?)
+    .await?;
+    if ra == 0 {
+        return Err(Error::InvalidMfaCode);
+    }
+
+    info!(user_id = %id, "Recovery code used");
+    Ok(())
+}
+
+// Replaces the recovery codes of the user and returns the new (plaintext) recovery codes.
+pub async fn regenerate_recovery_codes(id: &Uuid) -> Result<Vec<String>, Error> {
+    let u = get(id).await?;
+    if !u.totp_enabled {
+        return Err(Error::Validation("TOTP is not enabled".into()));
+    }
+
+    let id = *id;
+    let codes = generate_recovery_codes();
+    let codes_copy = codes.clone();
+
+    let mut c = get_async_db_conn().await?;
+    c.build_transaction()
+        .run::<(), Error, _>(|c| {
+            Box::pin(async move { set_recovery_codes(c, &id, &codes_copy).await })
+        })
+        .await?;
+    info!(user_id = %id, "Recovery codes regenerated");
+    Ok(codes)
+}
+
+// Revokes all sessions of the user. Tokens issued before this moment are rejected.
+pub async fn revoke_sessions(id: &Uuid) -> Result<User, Error> {
+    let u: User = diesel::update(user::dsl::user.find(&id))
+        .set(user::sessions_revoked_at.eq(Some(Utc::now())))
+        .get_result(&mut get_async_db_conn().await?)
+        .await
+        .map_err(|e| Error::from_diesel(e, id.to_string()))?;
+    set_cached_sessions_revoked_at(id, u.sessions_revoked_at).await?;
+    info!(user_id = %id, "User sessions revoked");
+    Ok(u)
+}
+
+// Returns true when the sessions of the user have been revoked after the token with the given
+// issued-at timestamp has been issued. Tokens without issued-at timestamp are considered
+// revoked once the sessions of the user have been revoked. As the issued-at timestamp has a
+// resolution of seconds, tokens issued within the same second as the revocation are considered
+// revoked as well.
+pub async fn is_session_revoked(id: &Uuid, issued_at: Option<usize>) -> Result<bool, Error> {
+    let revoked_at = get_sessions_revoked_at(id).await?;
+
+    Ok(match (revoked_at, issued_at) {
+        (Some(revoked_at), Some(issued_at)) => (issued_at as i64) <= revoked_at,
+        (Some(_), None) => true,
+        (None, _) => false,
+    })
+}
+
+// Returns the session revocation timestamp (in seconds) of the user, using the cached value
+// when available.
+async fn get_sessions_revoked_at(id: &Uuid) -> Result<Option<i64>, Error> {
+    let key = redis_key(format!("user:{}:sessions_revoked_at", id));
+    let cached: Option<i64> = redis::cmd("GET")
+        .arg(&key)
+        .query_async(&mut get_async_redis_conn().await?)
+        .await?;
+
+    // A cached value of 0 means that the sessions have never been revoked.
+    if let Some(v) = cached {
+        return Ok(if v == 0 { None } else { Some(v) });
+    }
+
+    let revoked_at: Option<DateTime<Utc>> = user::dsl::user
+        .find(&id)
+        .select(user::dsl::sessions_revoked_at)
+        .first(&mut get_async_db_conn().await?)
+        .await
+        .optional()?
+        .flatten();
+
+    set_cached_sessions_revoked_at(id, revoked_at).await?;
+    Ok(revoked_at.map(|v| v.timestamp()))
+}
+
+async fn set_cached_sessions_revoked_at(
+    id: &Uuid,
+    revoked_at: Option<DateTime<Utc>>,
+) -> Result<(), Error> {
+    let key = redis_key(format!("user:{}:sessions_revoked_at", id));
+    redis::cmd("SETEX")
+        .arg(key)
+        .arg(SESSIONS_REVOKED_AT_CACHE_TTL)
+        .arg(revoked_at.map(|v| v.timestamp()).unwrap_or_default())
+        .query_async(&mut get_async_redis_conn().await?)
+        .await?;
+    Ok(())
+}
+
 pub async fn delete(id: &Uuid) -> Result<(), Error> {
     let ra = diesel::delete(user::dsl::user.find(&id))
         .execute(&mut get_async_db_conn().await?)
@@ -187,6 +648,30 @@ pub async fn list(limit: i64, offset: i64) -> Result<Vec<User>, Error> {
     Ok(items)
 }
 
+// Returns the number of (non-admin) users with an external ID, these are the users that can be
+// provisioned through SCIM.
+pub async fn get_external_count() -> Result<i64, Error> {
+    let count = user::dsl::user
+        .select(dsl::count_star())
+        .filter(user::dsl::external_id.is_not_null())
+        .filter(user::dsl::is_admin.eq(false))
+        .first(&mut get_async_db_conn().await?)
+        .await?;
+    Ok(count)
+}
+
+pub async fn list_external(limit: i64, offset: i64) -> Result<Vec<User>, Error> {
+    let items = user::dsl::user
+        .filter(user::dsl::external_id.is_not_null())
+        .filter(user::dsl::is_admin.eq(false))
+        .order_by(user::dsl::email)
+        .limit(limit)
+        .offset(offset)
+        .load(&mut get_async_db_conn().await?)
+        .await?;
+    Ok(items)
+}
+
 // The output format is documented here:
 // https://github.com/P-H-C/phc-string-format/blob/master/phc-sf-spec.md#specification
 fn hash_password(pw: &str, rounds: u32) -> Result<String, Error> {
@@ -219,6 +704,62 @@ fn verify_password(pw: &str, hash: &str) -> bool {
     Pbkdf2.verify_password(pw.as_bytes(), &parsed).is_ok()
 }
 
+async fn set_recovery_codes(
+    c: &mut AsyncPgConnection,
+    id: &Uuid,
+    codes: &[String],
+) -> Result<(), Error> {
+    diesel::delete(
+        user_recovery_code::dsl::user_recovery_code
+            .filter(user_recovery_code::dsl::user_id.eq(&id)),
+    )
+    .execute(c)
+    .await?;
+
+    let now = Utc::now();
+    let items: Vec<RecoveryCode> = codes
+        .iter()
+        .map(|code| RecoveryCode {
+            id: Uuid::new_v4(),
+            user_id: *id,
+            created_at: now,
+            code_hash: hash_recovery_code(id, code),
+        })
+        .collect();
+    diesel::insert_into(user_recovery_code::table)
+        .values(&items)
+        .execute(c)
+        .await?;
+
+    Ok(())
+}
+
+// Recovery codes contain 80 random bits, formatted as 'xxxxx-xxxxx-xxxxx-xxxxx' (hex encoded).
+fn generate_recovery_codes() -> Vec<String> {
+    let mut rng = rand::thread_rng();
+    (0..RECOVERY_CODE_COUNT)
+        .map(|_| {
+            let s = hex::encode(rng.gen::<[u8; 10]>());
+            format!("{}-{}-{}-{}", &s[..5], &s[5..10], &s[10..15], &s[15..])
+        })
+        .collect()
+}
+
+// With 80 random bits, brute-forcing a recovery code from its hash is not feasible, therefore
+// a HMAC-SHA256 is used instead of the (slow) password hashing. The HMAC is keyed by the user ID,
+// such that equal codes of different users result in different hashes and hashes can not be
+// pre-computed for all users at once.
+fn hash_recovery_code(id: &Uuid, code: &str) -> String {
+    let code: String = code
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .collect::<String>()
+        .to_lowercase();
+    let mut m = Hmac::<Sha256>::new_from_slice(id.as_bytes()).unwrap();
+    m.update(code.as_bytes());
+    hex::encode(m.finalize().into_bytes())
+}
+
 #[cfg(test)]
 pub mod test {
     use super::*;
@@ -236,6 +777,15 @@ pub mod test {
         create(user).await.unwrap()
     }
 
+    pub async fn get_recovery_code_count(id: &Uuid) -> Result<i64, Error> {
+        let count = user_recovery_code::dsl::user_recovery_code
+            .select(dsl::count_star())
+            .filter(user_recovery_code::dsl::user_id.eq(&id))
+            .first(&mut get_async_db_conn().await?)
+            .await?;
+        Ok(count)
+    }
+
     #[test]
     fn test_hash_password() {
         assert_eq!(true, hash_password(&"foobar", 1000).is_ok());
@@ -281,4 +831,236 @@ pub mod test {
         delete(&user.id).await.unwrap();
         assert_eq!(true, delete(&user.id).await.is_err());
     }
+
+    #[tokio::test]
+    async fn test_lockout() {
+        let _guard = test::prepare().await;
+
+        let mut conf = (*config::get()).clone();
+        conf.user_authentication.internal.max_failed_logins = 2;
+        config::set(conf);
+
+        let user = create_user().await;
+
+        // first failed login
+        assert!(matches!(
+            get_by_email_and_pw(&"test@example.com", &"bar").await,
+            Err(Error::InvalidUsernameOrPassword)
+        ));
+        assert_eq!(1, get(&user.id).await.unwrap().failed_login_count);
+
+        // successful login resets the counter
+        let user_get = get_by_email_and_pw(&"test@example.com", &"password!")
+            .await
+            .unwrap();
+        assert_eq!(0, user_get.failed_login_count);
+
+        // two failed logins lock the user
+        for _ in 0..2 {
+            assert!(get_by_email_and_pw(&"test@example.com", &"bar")
+                .await
+                .is_err());
+        }
+        assert!(get(&user.id).await.unwrap().is_locked());
+        assert!(matches!(
+            get_by_email_and_pw(&"test@example.com", &"password!").await,
+            Err(Error::UserLocked)
+        ));
+
+        // the lock state is not exposed when using an invalid password
+        assert!(matches!(
+            get_by_email_and_pw(&"test@example.com", &"bar").await,
+            Err(Error::InvalidUsernameOrPassword)
+        ));
+        assert_eq!(0, get(&user.id).await.unwrap().failed_login_count);
+
+        // setting the password unlocks the user
+        let user_get = set_password_hash(&user.id, &hash_password("password!", 1).unwrap())
+            .await
+            .unwrap();
+        assert!(!user_get.is_locked());
+        assert!(get_by_email_and_pw(&"test@example.com", &"password!")
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_password_policy() {
+        let _guard = test::prepare().await;
+
+        let mut conf = (*config::get()).clone();
+        conf.user_authentication.internal.password_policy = config::PasswordPolicy {
+            min_length: 8,
+            require_uppercase: true,
+            require_lowercase: true,
+            require_digit: true,
+            require_special: true,
+            history: 2,
+        };
+        config::set(conf);
+
+        assert!(validate_password("Sh0rt!").is_err());
+        assert!(validate_password("password1!").is_err());
+        assert!(validate_password("PASSWORD1!").is_err());
+        assert!(validate_password("Password!").is_err());
+        assert!(validate_password("Password1").is_err());
+        assert!(validate_password("Password1!").is_ok());
+
+        let user = create_user().await;
+
+        // current password
+        assert!(validate_password_history(&user.id, "password!")
+            .await
+            .is_err());
+
+        for pw in ["Password1!", "Password2!", "Password3!"] {
+            validate_password_history(&user.id, pw).await.unwrap();
+            set_password_hash(&user.id, &hash_password(pw, 1).unwrap())
+                .await
+                .unwrap();
+        }
+
+        // the original password is no longer part of the history
+        assert!(validate_password_history(&user.id, "password!")
+            .await
+            .is_ok());
+        for pw in ["Password1!", "Password2!", "Password3!"] {
+            assert!(validate_password_history(&user.id, pw).await.is_err());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_totp() {
+        let _guard = test::prepare().await;
+        let user = create_user().await;
+
+        // enroll without KEK
+        assert!(enroll_totp(&user.id).await.is_err());
+
+        let mut conf = (*config::get()).clone();
+        conf.keks.push(config::Kek {
+            label: "totp-kek".into(),
+            kek: lrwn::AES128Key::from_bytes([1, 2, 3, 4, 5, 6, 7, 8, 1, 2, 3, 4, 5, 6, 7, 8]),
+        });
+        conf.user_authentication.internal.totp_kek_label = "totp-kek".into();
+        config::set(conf);
+
+        // enable without enrollment
+        assert!(enable_totp(&user.id, "123456").await.is_err());
+
+        let user = enroll_totp(&user.id).await.unwrap();
+        assert!(!user.totp_enabled);
+        assert_eq!("totp-kek", user.totp_kek_label);
+
+        // the stored secret is encrypted
+        let secret = user.get_totp_secret().unwrap();
+        assert_eq!(20, secret.len());
+        assert_ne!(&secret, user.totp_secret.as_ref().unwrap());
+
+        // invalid code
+        assert!(matches!(
+            enable_totp(&user.id, "invalid").await,
+            Err(Error::InvalidMfaCode)
+        ));
+
+        let code = totp::test::generate(&secret, Utc::now().timestamp() as u64);
+        let recovery_codes = enable_totp(&user.id, &code).await.unwrap();
+        assert_eq!(RECOVERY_CODE_COUNT, recovery_codes.len());
+        assert_eq!(
+            RECOVERY_CODE_COUNT as i64,
+            get_recovery_code_count(&user.id).await.unwrap()
+        );
+
+        // the code used for enabling TOTP can not be re-used
+        let user = get(&user.id).await.unwrap();
+        assert!(user.totp_enabled);
+        assert!(matches!(
+            verify_totp(&user, &code).await,
+            Err(Error::InvalidMfaCode)
+        ));
+
+        // recovery codes can be used once
+        use_recovery_code(&user.id, &recovery_codes[0].to_uppercase())
+            .await
+            .unwrap();
+        assert!(use_recovery_code(&user.id, &recovery_codes[0])
+            .await
+            .is_err());
+
+        // regenerate
+        let new_codes = regenerate_recovery_codes(&user.id).await.unwrap();
+        assert!(use_recovery_code(&user.id, &recovery_codes[1])
+            .await
+            .is_err());
+        use_recovery_code(&user.id, &new_codes[0]).await.unwrap();
+
+        // disable
+        let user = disable_totp(&user.id).await.unwrap();
+        assert!(!user.totp_enabled);
+        assert!(user.totp_secret.is_none());
+        assert_eq!("", user.totp_kek_label);
+        assert_eq!(0, get_recovery_code_count(&user.id).await.unwrap());
+    }
+
+    #[test]
+    fn test_recovery_code_hash() {
+        let codes = generate_recovery_codes();
+        assert_eq!(RECOVERY_CODE_COUNT, codes.len());
+        for code in &codes {
+            assert_eq!(23, code.len());
+            assert_eq!(20, code.chars().filter(|c| c.is_ascii_hexdigit()).count());
+        }
+
+        let user_a = Uuid::new_v4();
+        let user_b = Uuid::new_v4();
+
+        // formatting is ignored
+        assert_eq!(
+            hash_recovery_code(&user_a, &codes[0]),
+            hash_recovery_code(&user_a, &codes[0].to_uppercase().replace('-', ""))
+        );
+
+        // the hash depends on the user
+        assert_ne!(
+            hash_recovery_code(&user_a, &codes[0]),
+            hash_recovery_code(&user_b, &codes[0])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_session_revocation() {
+        let _guard = test::prepare().await;
+        let mut user = create_user().await;
+        let issued_at = Utc::now().timestamp() as usize - 10;
+
+        assert!(!is_session_revoked(&user.id, Some(issued_at)).await.unwrap());
+        assert!(!is_session_revoked(&user.id, None).await.unwrap());
+
+        let revoked_at = revoke_sessions(&user.id)
+            .await
+            .unwrap()
+            .sessions_revoked_at
+            .unwrap();
+        assert!(is_session_revoked(&user.id, Some(issued_at)).await.unwrap());
+
+        // tokens issued within the same second are revoked as well
+        assert!(
+            is_session_revoked(&user.id, Some(revoked_at.timestamp() as usize))
+                .await
+                .unwrap()
+        );
+        assert!(is_session_revoked(&user.id, None).await.unwrap());
+        assert!(
+            !is_session_revoked(&user.id, Some(Utc::now().timestamp() as usize + 10))
+                .await
+                .unwrap()
+        );
+
+        // disabling the user revokes the sessions
+        let issued_at = Utc::now().timestamp() as usize + 10;
+        user.is_active = false;
+        user = update(user).await.unwrap();
+        assert!(user.sessions_revoked_at.is_some());
+        assert!(!is_session_revoked(&user.id, Some(issued_at)).await.unwrap());
+    }
 }
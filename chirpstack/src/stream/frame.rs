@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use std::io::Cursor;
 use std::str::FromStr;
 use std::time::Duration;
@@ -15,7 +16,7 @@ use lrwn::EUI64;
 use crate::config;
 use crate::helpers::errors::PrintFullError;
 use crate::storage::{get_async_redis_conn, redis_key};
-use chirpstack_api::{api, stream};
+use chirpstack_api::{api, gw, stream};
 
 pub async fn log_uplink_for_gateways(ufl: &stream::UplinkFrameLog) -> Result<()> {
     let conf = config::get();
@@ -293,6 +294,15 @@ async fn handle_stream(
                     }
                 }
 
+                let mut properties: HashMap<String, String> = [
+                    ("DevAddr".to_string(), pl.dev_addr.clone()),
+                    ("DevEUI".to_string(), pl.dev_eui.clone()),
+                ]
+                .iter()
+                .cloned()
+                .collect();
+                properties.extend(get_lr_fhss_properties(&pl));
+
                 let pl = api::LogItem {
                     id: stream_id.to_string(),
                     time: pl.time.as_ref().map(|t| prost_types::Timestamp {
@@ -306,13 +316,7 @@ async fn handle_stream(
                         "rx_info": pl.rx_info,
                     })
                     .to_string(),
-                    properties: [
-                        ("DevAddr".to_string(), pl.dev_addr),
-                        ("DevEUI".to_string(), pl.dev_eui),
-                    ]
-                    .iter()
-                    .cloned()
-                    .collect(),
+                    properties,
                 };
 
                 channel.send(pl).await?;
@@ -366,3 +370,40 @@ async fn handle_stream(
 
     Ok(())
 }
+
+// Returns the LR-FHSS OCW, grid and hop / header counts in case the uplink was received
+// using LR-FHSS modulation.
+fn get_lr_fhss_properties(pl: &stream::UplinkFrameLog) -> Vec<(String, String)> {
+    let v = match pl
+        .tx_info
+        .as_ref()
+        .and_then(|t| t.modulation.as_ref())
+        .and_then(|m| m.parameters.as_ref())
+    {
+        Some(gw::modulation::Parameters::LrFhss(v)) => v,
+        _ => return Vec::new(),
+    };
+
+    let code_rate: String = v.code_rate().into();
+    let grid_steps = if v.grid_steps != 0 {
+        Some(v.grid_steps)
+    } else {
+        lrwn::region::lr_fhss::get_grid_steps(v.operating_channel_width).ok()
+    };
+
+    let mut out = vec![(
+        "LR-FHSS OCW".to_string(),
+        v.operating_channel_width.to_string(),
+    )];
+    if let Some(grid_steps) = grid_steps {
+        out.push(("LR-FHSS grid steps".to_string(), grid_steps.to_string()));
+    }
+    if let Ok(headers) = lrwn::region::lr_fhss::get_header_count(&code_rate) {
+        out.push(("LR-FHSS headers".to_string(), headers.to_string()));
+    }
+    if let Ok(hops) = lrwn::region::lr_fhss::get_hop_count(&code_rate, pl.phy_payload.len()) {
+        out.push(("LR-FHSS hops".to_string(), hops.to_string()));
+    }
+
+    out
+}
@@ -4318,6 +4318,7 @@ async fn test_lorawan_10_adr() {
             max_rssi: -120,
             tx_power_index: 3,
             gateway_count: 3,
+            phy_payload_size: 12,
         }],
         ..ds.clone()
     };
@@ -5045,6 +5046,7 @@ async fn test_lorawan_10_adr() {
                         max_rssi: 0,
                         tx_power_index: 0,
                         gateway_count: 1,
+                        phy_payload_size: 12,
                     }],
                 ),
             ],
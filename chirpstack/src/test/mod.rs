@@ -40,6 +40,7 @@ pub async fn prepare<'a>() -> std::sync::MutexGuard<'a, ()> {
     conf.postgresql.dsn = env::var("TEST_POSTGRESQL_DSN").unwrap();
     conf.redis.servers = vec![env::var("TEST_REDIS_URL").unwrap()];
     conf.network.enabled_regions = vec!["eu868".to_string()];
+    conf.soft_delete.retention = std::time::Duration::from_secs(60 * 60 * 24 * 30);
     conf.regions = vec![config::Region {
         id: "eu868".to_string(),
         description: "EU868".to_string(),
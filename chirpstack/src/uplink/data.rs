@@ -19,7 +19,7 @@ use crate::storage::{
     device::{self, DeviceClass},
     device_gateway, device_profile, device_queue, fields,
     helpers::get_all_device_data,
-    metrics, tenant,
+    metrics, tenant, tenant_usage,
 };
 use crate::{codec, config, downlink, integration, maccommand, region, stream};
 use chirpstack_api::{common, integration as integration_pb, internal, stream as stream_pb};
@@ -150,6 +150,7 @@ impl Data {
         ctx.update_device().await?;
         ctx.handle_uplink_ack().await?;
         ctx.save_metrics().await?;
+        ctx.save_tenant_usage().await?;
 
         if ctx._is_relay() {
             ctx.handle_forward_uplink_req().await?;
@@ -205,6 +206,7 @@ impl Data {
         ctx.update_device().await?;
         ctx.handle_uplink_ack().await?;
         ctx.save_metrics_relayed().await?;
+        ctx.save_tenant_usage().await?;
         ctx.start_downlink_data_flow_relayed().await?;
 
         Ok(())
@@ -843,6 +845,7 @@ impl Data {
     }
 
     fn append_meta_data_to_uplink_history(&mut self) -> Result<()> {
+        let phy_payload_size = self.uplink_frame_set.phy_payload.to_vec()?.len();
         let ds = self.device.as_mut().unwrap().get_device_session_mut()?;
 
         // ignore re-transmissions we don't know the source of the
@@ -873,6 +876,7 @@ impl Data {
             max_rssi,
             tx_power_index: ds.tx_power_index,
             gateway_count: self.uplink_frame_set.rx_info_set.len() as u32,
+            phy_payload_size: phy_payload_size as u32,
         });
 
         if ds.uplink_adr_history.len() > 20 {
@@ -888,6 +892,7 @@ impl Data {
     fn append_meta_data_to_uplink_history_relayed(&mut self) -> Result<()> {
         trace!("Apping meta-data of relayed uplink to upink history");
 
+        let phy_payload_size = self.uplink_frame_set.phy_payload.to_vec()?.len();
         let ds = self.device.as_mut().unwrap().get_device_session_mut()?;
         let relay_ctx = self.relay_context.as_ref().unwrap();
 
@@ -905,6 +910,7 @@ impl Data {
             max_rssi: relay_ctx.req.metadata.rssi as i32,
             tx_power_index: ds.tx_power_index,
             gateway_count: 1,
+            phy_payload_size: phy_payload_size as u32,
         });
 
         if ds.uplink_adr_history.len() > 20 {
@@ -1175,6 +1181,7 @@ impl Data {
                 queue_item_id: qi.id.to_string(),
                 acknowledged: true,
                 f_cnt_down: qi.f_cnt_down.unwrap_or(0) as u32,
+                correlation_id: qi.correlation_id.clone(),
             },
         )
         .await;
@@ -1267,6 +1274,20 @@ impl Data {
         Ok(())
     }
 
+    async fn save_tenant_usage(&self) -> Result<()> {
+        trace!("Saving tenant usage");
+        let tenant = self.tenant.as_ref().unwrap();
+        let dev = self.device.as_ref().unwrap();
+
+        tenant_usage::save_uplink(
+            &tenant.id,
+            &dev.dev_eui,
+            self.phy_payload.to_vec()?.len(),
+            self._is_roaming(),
+        )
+        .await
+    }
+
     async fn start_downlink_data_flow(&mut self) -> Result<()> {
         trace!("Starting downlink data flow");
 
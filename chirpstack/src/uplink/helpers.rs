@@ -2,13 +2,35 @@
 use std::str::FromStr;
 use std::time::{Duration, SystemTime};
 
+use aes::cipher::generic_array::GenericArray;
+use aes::cipher::{BlockDecrypt, KeyInit};
+use aes::Aes128;
 use anyhow::Result;
 use chrono::{DateTime, Utc};
+use prometheus_client::metrics::counter::Counter;
+use prometheus_client::metrics::family::Family;
+use tracing::warn;
 
+use lrwn::AES128Key;
+
+use crate::config;
 use crate::gpstime::ToDateTime;
+use crate::monitoring::prometheus;
 use crate::region;
 use chirpstack_api::{common, gw};
 
+lazy_static! {
+    static ref LR_FHSS_GRID_MISMATCH_COUNTER: Family<(), Counter> = {
+        let counter = Family::<(), Counter>::default();
+        prometheus::register(
+            "uplink_lr_fhss_grid_mismatch_count",
+            "Number of LR-FHSS uplinks for which the reported grid steps did not match the occupied channel width",
+            counter.clone(),
+        );
+        counter
+    };
+}
+
 pub fn get_uplink_dr(
     region_config_id: &str,
     tx_info: &chirpstack_api::gw::UplinkTxInfo,
@@ -38,6 +60,20 @@ pub fn get_uplink_dr(
             })
         }
         chirpstack_api::gw::modulation::Parameters::LrFhss(v) => {
+            // A mismatch can be caused by a gateway reporting the grid incorrectly. When allowed
+            // by the configuration, the data-rate is resolved using the occupied channel width
+            // and coding-rate only.
+            if let Err(e) =
+                lrwn::region::lr_fhss::validate_grid(v.operating_channel_width, v.grid_steps)
+            {
+                LR_FHSS_GRID_MISMATCH_COUNTER.get_or_create(&()).inc();
+
+                if !config::get().network.lr_fhss_grid_mismatch_allowed {
+                    return Err(e);
+                }
+                warn!(error = %e, "Unexpected LR-FHSS grid");
+            }
+
             lrwn::region::DataRateModulation::LrFhss(lrwn::region::LrFhssDataRate {
                 coding_rate: v.code_rate().into(),
                 occupied_channel_width: v.operating_channel_width,
@@ -133,6 +169,41 @@ pub fn get_time_since_gps_epoch_chrono(rx_info: &[gw::UplinkRxInfo]) -> Option<c
     None
 }
 
+// Decrypts the encrypted fine-timestamp and returns the fine time since GPS epoch. The
+// encrypted fine-timestamp only contains the nanosecond part of the timestamp, the seconds
+// are taken from the (non-fine) time since GPS epoch.
+pub fn decrypt_fine_timestamp(
+    key: &AES128Key,
+    time_since_gps_epoch: &pbjson_types::Duration,
+    ts: &gw::EncryptedFineTimestamp,
+) -> Result<pbjson_types::Duration> {
+    if ts.encrypted_ns.len() != 16 {
+        return Err(anyhow!(
+            "Encrypted fine-timestamp must be exactly 16 bytes, got: {}",
+            ts.encrypted_ns.len()
+        ));
+    }
+
+    let key_bytes = key.to_bytes();
+    let cipher = Aes128::new(GenericArray::from_slice(&key_bytes));
+    let mut b = GenericArray::clone_from_slice(&ts.encrypted_ns);
+    cipher.decrypt_block(&mut b);
+
+    let mut ns_bytes: [u8; 8] = [0; 8];
+    ns_bytes.copy_from_slice(&b[8..]);
+    let nanos = u64::from_be_bytes(ns_bytes);
+    if nanos >= 1_000_000_000 {
+        return Err(anyhow!(
+            "Fine-timestamp nanoseconds must be < 1 second, is the fine-timestamp key correct?"
+        ));
+    }
+
+    Ok(pbjson_types::Duration {
+        seconds: time_since_gps_epoch.seconds,
+        nanos: nanos as i32,
+    })
+}
+
 pub fn get_start_location(rx_info: &[gw::UplinkRxInfo]) -> Option<common::Location> {
     let mut with_loc: Vec<gw::UplinkRxInfo> = rx_info
         .iter()
@@ -190,3 +261,86 @@ pub fn set_uplink_modulation(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use aes::cipher::BlockEncrypt;
+
+    use super::*;
+    use crate::test;
+
+    fn encrypt_ns(key: &AES128Key, nanos: u64) -> Vec<u8> {
+        let key_bytes = key.to_bytes();
+        let cipher = Aes128::new(GenericArray::from_slice(&key_bytes));
+        let mut b = GenericArray::clone_from_slice(&[[0; 8], nanos.to_be_bytes()].concat());
+        cipher.encrypt_block(&mut b);
+        b.to_vec()
+    }
+
+    #[test]
+    fn test_decrypt_fine_timestamp() {
+        let key = AES128Key::from_bytes([1, 2, 3, 4, 5, 6, 7, 8, 1, 2, 3, 4, 5, 6, 7, 8]);
+        let time_since_gps_epoch = pbjson_types::Duration {
+            seconds: 1234,
+            nanos: 500_000_000,
+        };
+
+        let ts = gw::EncryptedFineTimestamp {
+            encrypted_ns: encrypt_ns(&key, 123_456_789),
+            ..Default::default()
+        };
+        assert_eq!(
+            pbjson_types::Duration {
+                seconds: 1234,
+                nanos: 123_456_789,
+            },
+            decrypt_fine_timestamp(&key, &time_since_gps_epoch, &ts).unwrap()
+        );
+
+        // Invalid key.
+        let invalid_key = AES128Key::from_bytes([8; 16]);
+        assert!(decrypt_fine_timestamp(&invalid_key, &time_since_gps_epoch, &ts).is_err());
+
+        // Invalid length.
+        let ts = gw::EncryptedFineTimestamp {
+            encrypted_ns: vec![1, 2, 3],
+            ..Default::default()
+        };
+        assert!(decrypt_fine_timestamp(&key, &time_since_gps_epoch, &ts).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_get_uplink_dr_lr_fhss() {
+        let _guard = test::prepare().await;
+
+        let tx_info = |grid_steps: u32| gw::UplinkTxInfo {
+            frequency: 868100000,
+            modulation: Some(gw::Modulation {
+                parameters: Some(gw::modulation::Parameters::LrFhss(
+                    gw::LrFhssModulationInfo {
+                        operating_channel_width: 137000,
+                        code_rate: gw::CodeRate::Cr26.into(),
+                        grid_steps,
+                        ..Default::default()
+                    },
+                )),
+            }),
+        };
+
+        // Grid not reported.
+        assert_eq!(8, get_uplink_dr("eu868", &tx_info(0)).unwrap());
+
+        // Expected grid.
+        assert_eq!(8, get_uplink_dr("eu868", &tx_info(35)).unwrap());
+
+        // Grid mismatch.
+        assert!(get_uplink_dr("eu868", &tx_info(86)).is_err());
+
+        // Grid mismatch allowed.
+        let mut conf = (*config::get()).clone();
+        conf.network.lr_fhss_grid_mismatch_allowed = true;
+        config::set(conf);
+
+        assert_eq!(8, get_uplink_dr("eu868", &tx_info(86)).unwrap());
+    }
+}
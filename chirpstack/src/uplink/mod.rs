@@ -396,6 +396,34 @@ async fn update_gateway_metadata(ufs: &mut UplinkFrameSet) -> Result<()> {
             });
         }
 
+        // Decrypt the fine-timestamp in case it is encrypted and the gateway has a
+        // fine-timestamp key configured.
+        if let Some(ts) = &rx_info.encrypted_fine_timestamp {
+            match (&gw_meta.fine_timestamp_key, &rx_info.time_since_gps_epoch) {
+                (Some(key), Some(gps_time)) => {
+                    match helpers::decrypt_fine_timestamp(key, gps_time, ts) {
+                        Ok(v) => {
+                            rx_info.fine_time_since_gps_epoch = Some(v);
+                            rx_info.encrypted_fine_timestamp = None;
+                        }
+                        Err(e) => {
+                            warn!(
+                                gateway_id = %gw_id,
+                                error = %e.full(),
+                                "Decrypting fine-timestamp failed"
+                            );
+                        }
+                    }
+                }
+                (None, _) => {
+                    debug!(gateway_id = %gw_id, "Gateway has no fine-timestamp key configured");
+                }
+                (_, None) => {
+                    warn!(gateway_id = %gw_id, "Encrypted fine-timestamp without time since GPS epoch");
+                }
+            }
+        }
+
         ufs.gateway_private_up_map
             .insert(gw_id, gw_meta.is_private_up);
         ufs.gateway_private_down_map
@@ -26,6 +26,7 @@ export function id() {
 //  installationMargin: 10,
 //  minDr: 0,
 //  maxDr: 5,
+//  uplinkDwellTime400ms: false,
 //  skipFCntCheck: false,
 //  deviceVariables: {
 //    "varA": "value1",
@@ -37,7 +38,8 @@ export function id() {
 //      "maxSnr": 7.5,
 //      "maxRssi": -110,
 //      "txPowerIndex": 0,
-//      "gatewayCount": 3
+//      "gatewayCount": 3,
+//      "phyPayloadSize": 24
 //    }
 //  ]
 // }
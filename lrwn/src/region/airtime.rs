@@ -0,0 +1,201 @@
+use std::time::Duration;
+
+use anyhow::Result;
+
+use super::{lr_fhss, DataRateModulation, MaxPayloadSize};
+
+/// Max. time on air when the 400ms dwell-time limitation applies.
+pub const DWELL_TIME_400MS: Duration = Duration::from_millis(400);
+
+// LoRaWAN uses a preamble of 8 symbols for LoRa and 5 bytes for FSK.
+const LORA_PREAMBLE_SYMBOLS: u64 = 8;
+const FSK_PREAMBLE_BYTES: u64 = 5;
+
+// MHDR + FHDR (without FOpts) + FPort + MIC.
+const PHY_OVERHEAD_N: usize = 13;
+// MHDR + MIC.
+const PHY_OVERHEAD_M: usize = 5;
+
+/// Returns the time on air of a PHYPayload with the given size. The payload CRC is only
+/// enabled for uplink transmissions.
+pub fn get_time_on_air(
+    modulation: &DataRateModulation,
+    phy_payload_size: usize,
+    uplink: bool,
+) -> Result<Duration> {
+    match modulation {
+        DataRateModulation::Lora(dr) => {
+            let cr = get_lora_coding_rate(&dr.coding_rate)?;
+            let sf = dr.spreading_factor as i64;
+            let bw = dr.bandwidth as u64;
+            if bw == 0 {
+                return Err(anyhow!("Invalid bandwidth"));
+            }
+
+            // Symbol time in nanoseconds.
+            let t_sym = (1_000_000_000u64 << sf) / bw;
+            // Low data-rate optimization is mandated when the symbol time exceeds 16ms.
+            let de: i64 = if t_sym >= 16_000_000 { 1 } else { 0 };
+            let crc: i64 = if uplink { 1 } else { 0 };
+
+            let num = 8 * phy_payload_size as i64 - 4 * sf + 28 + 16 * crc;
+            let den = 4 * (sf - 2 * de);
+            let n_payload = 8 + if num > 0 {
+                ((num + den - 1) / den) * (cr as i64 + 4)
+            } else {
+                0
+            };
+
+            // The preamble is followed by 4.25 symbols of sync-word.
+            let t_preamble = (4 * LORA_PREAMBLE_SYMBOLS + 17) * t_sym / 4;
+
+            Ok(Duration::from_nanos(t_preamble + n_payload as u64 * t_sym))
+        }
+        DataRateModulation::Fsk(dr) => {
+            if dr.bitrate == 0 {
+                return Err(anyhow!("Invalid bitrate"));
+            }
+
+            // Preamble + sync-word (3 bytes) + length (1 byte) + payload + CRC (2 bytes).
+            let bytes = FSK_PREAMBLE_BYTES + 3 + 1 + phy_payload_size as u64 + 2;
+            Ok(Duration::from_nanos(
+                bytes * 8 * 1_000_000_000 / dr.bitrate as u64,
+            ))
+        }
+        DataRateModulation::LrFhss(dr) => {
+            let headers = lr_fhss::get_header_count(&dr.coding_rate)? as u64;
+            let fragments = lr_fhss::get_fragment_count(&dr.coding_rate, phy_payload_size)? as u64;
+
+            Ok(Duration::from_micros(
+                headers * lr_fhss::HEADER_DURATION_US + fragments * lr_fhss::FRAGMENT_DURATION_US,
+            ))
+        }
+    }
+}
+
+/// Returns the max. PHYPayload size which can be transmitted within the given time on air.
+pub fn get_max_phy_payload_size(
+    modulation: &DataRateModulation,
+    max_time_on_air: Duration,
+    uplink: bool,
+) -> Result<usize> {
+    let mut out = 0;
+    for size in 1..=255 {
+        if get_time_on_air(modulation, size, uplink)? > max_time_on_air {
+            break;
+        }
+        out = size;
+    }
+
+    Ok(out)
+}
+
+/// Returns the given max. payload-size, limited to what can be transmitted within the 400ms
+/// dwell-time.
+pub fn apply_dwell_time_400ms(
+    max_pl_size: MaxPayloadSize,
+    modulation: &DataRateModulation,
+    uplink: bool,
+) -> Result<MaxPayloadSize> {
+    let max_phy_size = get_max_phy_payload_size(modulation, DWELL_TIME_400MS, uplink)?;
+
+    Ok(MaxPayloadSize {
+        m: max_pl_size
+            .m
+            .min(max_phy_size.saturating_sub(PHY_OVERHEAD_M)),
+        n: max_pl_size
+            .n
+            .min(max_phy_size.saturating_sub(PHY_OVERHEAD_N)),
+    })
+}
+
+fn get_lora_coding_rate(cr: &str) -> Result<u8> {
+    Ok(match cr {
+        "4/5" => 1,
+        "4/6" => 2,
+        "4/7" => 3,
+        "4/8" | "4/8LI" => 4,
+        _ => return Err(anyhow!("Unknown coding-rate: {}", cr)),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::region::{self, CommonName, LoraDataRate, MacVersion, Revision};
+
+    fn lora(sf: u8, bw: u32) -> DataRateModulation {
+        DataRateModulation::Lora(LoraDataRate {
+            spreading_factor: sf,
+            bandwidth: bw,
+            coding_rate: "4/5".into(),
+        })
+    }
+
+    #[test]
+    fn test_get_time_on_air() {
+        struct Test {
+            modulation: DataRateModulation,
+            size: usize,
+            uplink: bool,
+            expected_ms: u128,
+        }
+
+        let tests = vec![
+            Test {
+                modulation: lora(12, 125000),
+                size: 13,
+                uplink: true,
+                expected_ms: 1155,
+            },
+            Test {
+                modulation: lora(7, 125000),
+                size: 13,
+                uplink: true,
+                expected_ms: 46,
+            },
+            Test {
+                modulation: lora(10, 125000),
+                size: 24,
+                uplink: true,
+                expected_ms: 370,
+            },
+            Test {
+                modulation: lora(10, 125000),
+                size: 25,
+                uplink: true,
+                expected_ms: 411,
+            },
+        ];
+
+        for tst in &tests {
+            let toa = get_time_on_air(&tst.modulation, tst.size, tst.uplink).unwrap();
+            assert_eq!(tst.expected_ms, toa.as_millis());
+        }
+    }
+
+    #[test]
+    fn test_apply_dwell_time_400ms() {
+        // The AS923 dwell-time max. payload-sizes must fit within the dwell-time.
+        let r = region::get(CommonName::AS923, false, true);
+
+        for (dr, data_rate) in r.get_data_rates() {
+            if !data_rate.uplink {
+                continue;
+            }
+
+            let pl = r
+                .get_max_payload_size(MacVersion::Latest, Revision::Latest, dr)
+                .unwrap();
+            let pl_dwell = apply_dwell_time_400ms(pl.clone(), &data_rate.modulation, true).unwrap();
+            assert_eq!(pl.m, pl_dwell.m, "dr: {}", dr);
+            assert_eq!(pl.n, pl_dwell.n, "dr: {}", dr);
+        }
+
+        // Without dwell-time tables, SF10 is limited to 11 bytes.
+        let pl = apply_dwell_time_400ms(MaxPayloadSize { m: 59, n: 51 }, &lora(10, 125000), true)
+            .unwrap();
+        assert_eq!(19, pl.m);
+        assert_eq!(11, pl.n);
+    }
+}
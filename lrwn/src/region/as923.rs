@@ -844,6 +844,10 @@ impl Region for Configuration {
         Ok(923400000 - self.freq_offset)
     }
 
+    fn get_beacon_frequency(&self, _beacon_time: Duration) -> Result<u32> {
+        Ok(923400000 - self.freq_offset)
+    }
+
     fn get_downlink_tx_power_eirp(&self, _frequency: u32) -> isize {
         16
     }
@@ -856,6 +860,7 @@ impl Region for Configuration {
             rx2_delay: Duration::from_secs(2),
             join_accept_delay1: Duration::from_secs(5),
             join_accept_delay2: Duration::from_secs(6),
+            beacon_dr: 3,
         }
     }
 
@@ -863,6 +868,22 @@ impl Region for Configuration {
         true
     }
 
+    fn get_default_max_uplink_eirp(&self) -> f32 {
+        16.0
+    }
+
+    fn get_dwell_time_400ms(&self) -> bool {
+        self.dwell_time_400ms
+    }
+
+    fn supports_user_channels(&self) -> bool {
+        self.base.supports_user_channels()
+    }
+
+    fn get_data_rates(&self) -> Vec<(u8, DataRate)> {
+        self.base.get_data_rates()
+    }
+
     fn get_rx1_data_rate_index(&self, uplink_dr: u8, rx1_dr_offset: usize) -> Result<u8> {
         if uplink_dr > 7 {
             return Err(anyhow!("Invalid uplink data-rate: {}", uplink_dr));
@@ -10,11 +10,13 @@ use crate::{CFList, ChMask, DevAddr, Redundancy};
 
 pub struct Configuration {
     base: RegionBaseConfig,
+    dwell_time_400ms: bool,
 }
 
 impl Configuration {
     pub fn new(repeater_compatible: bool, dwell_time_400ms: bool) -> Self {
         let mut c = Configuration {
+            dwell_time_400ms,
             base: RegionBaseConfig {
                 supports_user_channels: false,
                 cf_list_min_dr: 0,
@@ -992,6 +994,12 @@ impl Region for Configuration {
         Ok(self.base.downlink_channels[down_channel].frequency)
     }
 
+    fn get_beacon_frequency(&self, beacon_time: Duration) -> Result<u32> {
+        // The beacon channel is defined by floor(beacon_time / beacon_period) % 8.
+        let down_channel = ((beacon_time.as_secs() / 128) % 8) as usize;
+        Ok(self.base.downlink_channels[down_channel].frequency)
+    }
+
     fn get_downlink_tx_power_eirp(&self, _frequency: u32) -> isize {
         30
     }
@@ -1004,6 +1012,7 @@ impl Region for Configuration {
             rx2_delay: Duration::from_secs(2),
             join_accept_delay1: Duration::from_secs(5),
             join_accept_delay2: Duration::from_secs(6),
+            beacon_dr: 8,
         }
     }
 
@@ -1011,6 +1020,22 @@ impl Region for Configuration {
         !(mac_version == MacVersion::LORAWAN_1_0_1 || mac_version == MacVersion::LORAWAN_1_0_2)
     }
 
+    fn get_default_max_uplink_eirp(&self) -> f32 {
+        30.0
+    }
+
+    fn get_dwell_time_400ms(&self) -> bool {
+        self.dwell_time_400ms
+    }
+
+    fn supports_user_channels(&self) -> bool {
+        self.base.supports_user_channels()
+    }
+
+    fn get_data_rates(&self) -> Vec<(u8, DataRate)> {
+        self.base.get_data_rates()
+    }
+
     fn get_data_rate_index(&self, uplink: bool, modulation: &DataRateModulation) -> Result<u8> {
         self.base.get_data_rate_index(uplink, modulation)
     }
@@ -387,6 +387,8 @@ impl Configuration {
                     (3, vec![3, 2, 1, 0, 0, 0]),
                     (4, vec![4, 3, 2, 1, 0, 0]),
                     (5, vec![5, 4, 3, 2, 1, 0]),
+                    (6, vec![6, 5, 4, 3, 2, 1]),
+                    (7, vec![7, 6, 5, 4, 3, 2]),
                 ]
                 .iter()
                 .cloned()
@@ -462,6 +464,17 @@ impl Region for Configuration {
         Ok(beacon_freqs[down_channel])
     }
 
+    fn get_beacon_frequency(&self, beacon_time: Duration) -> Result<u32> {
+        // The beacon channel is defined by floor(beacon_time / beacon_period) % 8.
+        let down_channel = ((beacon_time.as_secs() / 128) % 8) as usize;
+
+        let beacon_freqs: Vec<u32> = vec![
+            508300000, 508500000, 508700000, 508900000, 509100000, 509300000, 509500000, 509700000,
+        ];
+
+        Ok(beacon_freqs[down_channel])
+    }
+
     fn get_downlink_tx_power_eirp(&self, _frequency: u32) -> isize {
         19
     }
@@ -474,6 +487,7 @@ impl Region for Configuration {
             rx2_delay: Duration::from_secs(2),
             join_accept_delay1: Duration::from_secs(5),
             join_accept_delay2: Duration::from_secs(6),
+            beacon_dr: 2,
         }
     }
 
@@ -481,6 +495,22 @@ impl Region for Configuration {
         false
     }
 
+    fn get_default_max_uplink_eirp(&self) -> f32 {
+        19.15
+    }
+
+    fn get_dwell_time_400ms(&self) -> bool {
+        false
+    }
+
+    fn supports_user_channels(&self) -> bool {
+        self.base.supports_user_channels()
+    }
+
+    fn get_data_rates(&self) -> Vec<(u8, DataRate)> {
+        self.base.get_data_rates()
+    }
+
     fn get_data_rate_index(&self, uplink: bool, modulation: &DataRateModulation) -> Result<u8> {
         self.base.get_data_rate_index(uplink, modulation)
     }
@@ -602,6 +632,16 @@ mod tests {
         assert_eq!(509100000, freq);
     }
 
+    #[test]
+    fn test_get_rx1_data_rate_index() {
+        let c = config_full();
+
+        assert_eq!(6, c.get_rx1_data_rate_index(6, 0).unwrap());
+        assert_eq!(1, c.get_rx1_data_rate_index(6, 5).unwrap());
+        assert_eq!(7, c.get_rx1_data_rate_index(7, 0).unwrap());
+        assert_eq!(2, c.get_rx1_data_rate_index(7, 5).unwrap());
+    }
+
     #[test]
     fn test_uplink_channels() {
         let c = config_full();
@@ -484,6 +484,10 @@ impl Region for Configuration {
         Ok(434665000)
     }
 
+    fn get_beacon_frequency(&self, _beacon_time: Duration) -> Result<u32> {
+        Ok(434665000)
+    }
+
     fn get_downlink_tx_power_eirp(&self, _frequency: u32) -> isize {
         12
     }
@@ -496,6 +500,7 @@ impl Region for Configuration {
             rx2_delay: Duration::from_secs(2),
             join_accept_delay1: Duration::from_secs(5),
             join_accept_delay2: Duration::from_secs(6),
+            beacon_dr: 3,
         }
     }
 
@@ -503,6 +508,22 @@ impl Region for Configuration {
         false
     }
 
+    fn get_default_max_uplink_eirp(&self) -> f32 {
+        12.15
+    }
+
+    fn get_dwell_time_400ms(&self) -> bool {
+        false
+    }
+
+    fn supports_user_channels(&self) -> bool {
+        self.base.supports_user_channels()
+    }
+
+    fn get_data_rates(&self) -> Vec<(u8, DataRate)> {
+        self.base.get_data_rates()
+    }
+
     fn get_data_rate_index(&self, uplink: bool, modulation: &DataRateModulation) -> Result<u8> {
         self.base.get_data_rate_index(uplink, modulation)
     }
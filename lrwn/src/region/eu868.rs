@@ -601,6 +601,10 @@ impl Region for Configuration {
         Ok(869525000)
     }
 
+    fn get_beacon_frequency(&self, _beacon_time: Duration) -> Result<u32> {
+        Ok(869525000)
+    }
+
     fn get_downlink_tx_power_eirp(&self, freq: u32) -> isize {
         if (863000000..869200000).contains(&freq) {
             16
@@ -619,6 +623,7 @@ impl Region for Configuration {
             rx2_delay: Duration::from_secs(2),
             join_accept_delay1: Duration::from_secs(5),
             join_accept_delay2: Duration::from_secs(6),
+            beacon_dr: 3,
         }
     }
 
@@ -626,6 +631,22 @@ impl Region for Configuration {
         false
     }
 
+    fn get_default_max_uplink_eirp(&self) -> f32 {
+        16.0
+    }
+
+    fn get_dwell_time_400ms(&self) -> bool {
+        false
+    }
+
+    fn supports_user_channels(&self) -> bool {
+        self.base.supports_user_channels()
+    }
+
+    fn get_data_rates(&self) -> Vec<(u8, DataRate)> {
+        self.base.get_data_rates()
+    }
+
     fn get_data_rate_index(&self, uplink: bool, modulation: &DataRateModulation) -> Result<u8> {
         self.base.get_data_rate_index(uplink, modulation)
     }
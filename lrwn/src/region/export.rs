@@ -0,0 +1,322 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use serde::Serialize;
+
+use super::{
+    get, lr_fhss, Channel, CommonName, DataRate, DataRateModulation, MacVersion, Region, Revision,
+};
+
+const COMMON_NAMES: [CommonName; 14] = [
+    CommonName::EU868,
+    CommonName::US915,
+    CommonName::CN779,
+    CommonName::EU433,
+    CommonName::AU915,
+    CommonName::CN470,
+    CommonName::AS923,
+    CommonName::AS923_2,
+    CommonName::AS923_3,
+    CommonName::AS923_4,
+    CommonName::KR920,
+    CommonName::IN865,
+    CommonName::RU864,
+    CommonName::ISM2400,
+];
+
+const REVISIONS: [Revision; 7] = [
+    Revision::A,
+    Revision::B,
+    Revision::RP002_1_0_0,
+    Revision::RP002_1_0_1,
+    Revision::RP002_1_0_2,
+    Revision::RP002_1_0_3,
+    Revision::RP002_1_0_4,
+];
+
+// MAC versions for which the max. payload-sizes are exported. A MAC version without its own
+// max. payload-size table falls back to the table of the latest MAC version.
+const MAC_VERSIONS: [MacVersion; 7] = [
+    MacVersion::Latest,
+    MacVersion::LORAWAN_1_0_0,
+    MacVersion::LORAWAN_1_0_1,
+    MacVersion::LORAWAN_1_0_2,
+    MacVersion::LORAWAN_1_0_3,
+    MacVersion::LORAWAN_1_0_4,
+    MacVersion::LORAWAN_1_1_0,
+];
+
+/// Regional parameters of a single region configuration, for a single revision.
+#[derive(Serialize)]
+pub struct RegionParameters {
+    pub common_name: CommonName,
+    pub revision: String,
+    pub repeater_compatible: bool,
+    pub dwell_time_400ms: bool,
+    pub supports_user_channels: bool,
+    pub default_max_uplink_eirp: f32,
+    pub data_rates: Vec<DataRateParameters>,
+    pub rx1_data_rate_table: Vec<Rx1DataRateParameters>,
+    pub tx_power_offsets: Vec<isize>,
+    pub uplink_channels: Vec<Channel>,
+    pub downlink_channels: Vec<Channel>,
+    pub defaults: DefaultParameters,
+    pub mac_versions: Vec<MacVersionParameters>,
+}
+
+#[derive(Serialize)]
+pub struct DataRateParameters {
+    pub index: u8,
+    #[serde(flatten)]
+    pub data_rate: DataRate,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lr_fhss_grid_steps: Option<u32>,
+}
+
+#[derive(Serialize)]
+pub struct Rx1DataRateParameters {
+    pub uplink_dr: u8,
+    pub rx1_dr_per_offset: Vec<u8>,
+}
+
+#[derive(Serialize)]
+pub struct DefaultParameters {
+    pub rx1_delay_secs: u64,
+    pub rx2_delay_secs: u64,
+    pub join_accept_delay1_secs: u64,
+    pub join_accept_delay2_secs: u64,
+    pub rx2_frequency: u32,
+    pub rx2_dr: u8,
+    pub beacon_dr: u8,
+    pub beacon_frequency: u32,
+}
+
+#[derive(Serialize)]
+pub struct MacVersionParameters {
+    pub mac_version: String,
+    pub implements_tx_param_setup: bool,
+    pub max_payload_size_per_dr: Vec<MaxPayloadSizeParameters>,
+}
+
+#[derive(Serialize)]
+pub struct MaxPayloadSizeParameters {
+    pub dr: u8,
+    pub m: usize,
+    pub n: usize,
+}
+
+impl RegionParameters {
+    /// Collects the regional parameters of the given region configuration for the given
+    /// revision.
+    pub fn from_region(
+        common_name: CommonName,
+        revision: Revision,
+        repeater_compatible: bool,
+        region: &dyn Region,
+    ) -> Result<Self> {
+        let data_rates = region.get_data_rates();
+        let defaults = region.get_defaults();
+
+        let mut rx1_data_rate_table = Vec::new();
+        for (dr, data_rate) in &data_rates {
+            if !data_rate.uplink {
+                continue;
+            }
+
+            let mut rx1_dr_per_offset = Vec::new();
+            while let Ok(rx1_dr) = region.get_rx1_data_rate_index(*dr, rx1_dr_per_offset.len()) {
+                rx1_dr_per_offset.push(rx1_dr);
+            }
+
+            rx1_data_rate_table.push(Rx1DataRateParameters {
+                uplink_dr: *dr,
+                rx1_dr_per_offset,
+            });
+        }
+
+        let mut tx_power_offsets = Vec::new();
+        while let Ok(offset) = region.get_tx_power_offset(tx_power_offsets.len()) {
+            tx_power_offsets.push(offset);
+        }
+
+        let mut uplink_channels = Vec::new();
+        for i in region.get_uplink_channel_indices() {
+            uplink_channels.push(region.get_uplink_channel(i)?);
+        }
+
+        let mut downlink_channels = Vec::new();
+        while let Ok(c) = region.get_downlink_channel(downlink_channels.len()) {
+            downlink_channels.push(c);
+        }
+
+        let mut mac_versions = Vec::new();
+        for mac_version in MAC_VERSIONS {
+            let mut max_payload_size_per_dr = Vec::new();
+            for (dr, data_rate) in &data_rates {
+                if !data_rate.uplink && !data_rate.downlink {
+                    continue;
+                }
+
+                if let Ok(pl) = region.get_max_payload_size(mac_version, revision, *dr) {
+                    max_payload_size_per_dr.push(MaxPayloadSizeParameters {
+                        dr: *dr,
+                        m: pl.m,
+                        n: pl.n,
+                    });
+                }
+            }
+
+            mac_versions.push(MacVersionParameters {
+                mac_version: match mac_version {
+                    MacVersion::Latest => "latest".to_string(),
+                    _ => mac_version.to_string(),
+                },
+                implements_tx_param_setup: region.implements_tx_param_setup(mac_version),
+                max_payload_size_per_dr,
+            });
+        }
+
+        Ok(RegionParameters {
+            common_name,
+            revision: revision.to_string(),
+            repeater_compatible,
+            dwell_time_400ms: region.get_dwell_time_400ms(),
+            supports_user_channels: region.supports_user_channels(),
+            default_max_uplink_eirp: region.get_default_max_uplink_eirp(),
+            data_rates: data_rates
+                .into_iter()
+                .map(|(index, data_rate)| {
+                    let lr_fhss_grid_steps = match &data_rate.modulation {
+                        DataRateModulation::LrFhss(v) => {
+                            lr_fhss::get_grid_steps(v.occupied_channel_width).ok()
+                        }
+                        _ => None,
+                    };
+
+                    DataRateParameters {
+                        index,
+                        data_rate,
+                        lr_fhss_grid_steps,
+                    }
+                })
+                .collect(),
+            rx1_data_rate_table,
+            tx_power_offsets,
+            uplink_channels,
+            downlink_channels,
+            defaults: DefaultParameters {
+                rx1_delay_secs: defaults.rx1_delay.as_secs(),
+                rx2_delay_secs: defaults.rx2_delay.as_secs(),
+                join_accept_delay1_secs: defaults.join_accept_delay1.as_secs(),
+                join_accept_delay2_secs: defaults.join_accept_delay2.as_secs(),
+                rx2_frequency: defaults.rx2_frequency,
+                rx2_dr: defaults.rx2_dr,
+                beacon_dr: defaults.beacon_dr,
+                beacon_frequency: region.get_beacon_frequency(Duration::ZERO)?,
+            },
+            mac_versions,
+        })
+    }
+}
+
+/// Returns the regional parameters of all regions, for all revisions. For regions that have
+/// the 400ms dwell-time limitation, both the variant with and without dwell-time are returned.
+pub fn export_all() -> Result<Vec<RegionParameters>> {
+    let mut out = Vec::new();
+
+    for common_name in COMMON_NAMES {
+        for dwell_time_400ms in [false, true] {
+            let region = get(common_name, false, dwell_time_400ms);
+            if region.get_dwell_time_400ms() != dwell_time_400ms {
+                continue;
+            }
+
+            for revision in REVISIONS {
+                out.push(RegionParameters::from_region(
+                    common_name,
+                    revision,
+                    false,
+                    region.as_ref(),
+                )?);
+            }
+        }
+    }
+
+    Ok(out)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_export_all() {
+        let params = export_all().unwrap();
+
+        // AS923 (4 variants) and AU915 have a dwell-time variant.
+        assert_eq!((COMMON_NAMES.len() + 5) * REVISIONS.len(), params.len());
+    }
+
+    #[test]
+    fn test_consistency() {
+        for p in export_all().unwrap() {
+            let name = format!("{} {}", p.common_name, p.revision);
+            let uplink_drs: Vec<u8> = p
+                .data_rates
+                .iter()
+                .filter(|dr| dr.data_rate.uplink)
+                .map(|dr| dr.index)
+                .collect();
+            let dr_defined = |dr: u8| p.data_rates.iter().any(|d| d.index == dr);
+
+            assert!(!p.uplink_channels.is_empty(), "{}", name);
+            assert!(!p.downlink_channels.is_empty(), "{}", name);
+            assert!(!p.tx_power_offsets.is_empty(), "{}", name);
+            assert!(dr_defined(p.defaults.rx2_dr), "{}", name);
+            assert!(dr_defined(p.defaults.beacon_dr), "{}", name);
+
+            for dr in &p.data_rates {
+                if let DataRateModulation::LrFhss(_) = dr.data_rate.modulation {
+                    assert!(dr.lr_fhss_grid_steps.is_some(), "{} {}", name, dr.index);
+                    assert!(!dr.data_rate.downlink, "{} {}", name, dr.index);
+                }
+            }
+
+            for c in p.uplink_channels.iter().chain(p.downlink_channels.iter()) {
+                assert!(dr_defined(c.min_dr), "{} {}", name, c.frequency);
+                assert!(dr_defined(c.max_dr), "{} {}", name, c.frequency);
+            }
+
+            for rx1 in &p.rx1_data_rate_table {
+                assert!(!rx1.rx1_dr_per_offset.is_empty(), "{}", name);
+
+                // Only the offsets 0 - 5 are common to all regions.
+                for dr in rx1.rx1_dr_per_offset.iter().take(6) {
+                    assert!(dr_defined(*dr), "{} {}", name, dr);
+                }
+            }
+
+            // Older revisions and mac-versions do not define the max. payload-size for all
+            // data-rates (e.g. LR-FHSS), so this is only validated against the latest ones.
+            if p.revision != Revision::RP002_1_0_4.to_string() {
+                continue;
+            }
+
+            for mv in &p.mac_versions {
+                if mv.mac_version != "latest" {
+                    continue;
+                }
+
+                for dr in &uplink_drs {
+                    assert!(
+                        mv.max_payload_size_per_dr.iter().any(|pl| pl.dr == *dr),
+                        "{} {} {}",
+                        name,
+                        mv.mac_version,
+                        dr
+                    );
+                }
+            }
+        }
+    }
+}
@@ -301,7 +301,7 @@ impl Configuration {
                     (4, vec![4, 3, 2, 1, 0, 0, 5, 5]),
                     (5, vec![5, 4, 3, 2, 1, 0, 5, 5]),
                     // 6
-                    (7, vec![7, 6, 5, 4, 3, 2, 7, 7]),
+                    (7, vec![7, 5, 5, 4, 3, 2, 7, 7]),
                 ]
                 .iter()
                 .cloned()
@@ -390,6 +390,10 @@ impl Region for Configuration {
         Ok(866550000)
     }
 
+    fn get_beacon_frequency(&self, _beacon_time: Duration) -> Result<u32> {
+        Ok(866550000)
+    }
+
     fn get_downlink_tx_power_eirp(&self, _frequency: u32) -> isize {
         30
     }
@@ -402,6 +406,7 @@ impl Region for Configuration {
             rx2_delay: Duration::from_secs(2),
             join_accept_delay1: Duration::from_secs(5),
             join_accept_delay2: Duration::from_secs(6),
+            beacon_dr: 4,
         }
     }
 
@@ -409,6 +414,22 @@ impl Region for Configuration {
         false
     }
 
+    fn get_default_max_uplink_eirp(&self) -> f32 {
+        30.0
+    }
+
+    fn get_dwell_time_400ms(&self) -> bool {
+        false
+    }
+
+    fn supports_user_channels(&self) -> bool {
+        self.base.supports_user_channels()
+    }
+
+    fn get_data_rates(&self) -> Vec<(u8, DataRate)> {
+        self.base.get_data_rates()
+    }
+
     fn get_data_rate_index(&self, uplink: bool, modulation: &DataRateModulation) -> Result<u8> {
         self.base.get_data_rate_index(uplink, modulation)
     }
@@ -529,6 +550,17 @@ mod test {
         assert_eq!(866550000, freq);
     }
 
+    #[test]
+    fn test_get_rx1_data_rate_index() {
+        let c = config();
+
+        // DR6 is RFU, an offset from DR7 skips it.
+        assert_eq!(7, c.get_rx1_data_rate_index(7, 0).unwrap());
+        assert_eq!(5, c.get_rx1_data_rate_index(7, 1).unwrap());
+        assert_eq!(5, c.get_rx1_data_rate_index(7, 2).unwrap());
+        assert_eq!(7, c.get_rx1_data_rate_index(7, 6).unwrap());
+    }
+
     #[test]
     fn get_rx1_channel_index_for_uplink_channel_index() {
         let c = config();
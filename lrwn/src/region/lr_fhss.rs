@@ -0,0 +1,109 @@
+use anyhow::Result;
+
+// Duration of a single header (and its hop) in microseconds.
+pub const HEADER_DURATION_US: u64 = 233_472;
+
+// Duration of a single payload fragment (and its hop) in microseconds.
+pub const FRAGMENT_DURATION_US: u64 = 102_400;
+
+// Hopping grid step (Hz) for the narrow (EU868) and wide (US915 / AU915) grid.
+const GRID_STEP_NARROW: f64 = 3906.25;
+const GRID_STEP_WIDE: f64 = 25390.625;
+
+/// Returns the hopping grid step (Hz) for the given occupied channel width (Hz).
+pub fn get_grid_step(occupied_channel_width: u32) -> Result<f64> {
+    Ok(match occupied_channel_width {
+        137000 | 336000 => GRID_STEP_NARROW,
+        1523000 => GRID_STEP_WIDE,
+        _ => {
+            return Err(anyhow!(
+                "Unknown LR-FHSS occupied channel width: {}",
+                occupied_channel_width
+            ))
+        }
+    })
+}
+
+/// Returns the number of grid steps within the given occupied channel width (Hz).
+pub fn get_grid_steps(occupied_channel_width: u32) -> Result<u32> {
+    let grid_step = get_grid_step(occupied_channel_width)?;
+    Ok((occupied_channel_width as f64 / grid_step).round() as u32)
+}
+
+/// Validates the occupied channel width and number of grid steps as reported by the gateway.
+/// A grid_steps value of 0 means that the gateway did not report the grid.
+pub fn validate_grid(occupied_channel_width: u32, grid_steps: u32) -> Result<()> {
+    let expected = get_grid_steps(occupied_channel_width)?;
+    if grid_steps != 0 && grid_steps != expected {
+        return Err(anyhow!(
+            "LR-FHSS grid steps {} do not match the expected grid steps {} for OCW {}",
+            grid_steps,
+            expected,
+            occupied_channel_width
+        ));
+    }
+
+    Ok(())
+}
+
+/// Returns the number of header replicas for the given coding-rate.
+pub fn get_header_count(coding_rate: &str) -> Result<usize> {
+    Ok(get_coding_rate(coding_rate)?.0)
+}
+
+/// Returns the number of payload fragments for the given coding-rate and PHYPayload size.
+/// Each fragment is transmitted using a separate hop.
+pub fn get_fragment_count(coding_rate: &str, phy_payload_size: usize) -> Result<usize> {
+    let (_, cr_num, cr_den) = get_coding_rate(coding_rate)?;
+
+    // Payload + CRC (2 bytes) + 6 tail bits, each fragment carries 48 coded bits.
+    let coded_bits = ((phy_payload_size + 2) * 8 + 6) * cr_den / cr_num;
+    Ok(coded_bits.div_ceil(48))
+}
+
+/// Returns the total number of hops (headers + fragments).
+pub fn get_hop_count(coding_rate: &str, phy_payload_size: usize) -> Result<usize> {
+    Ok(get_header_count(coding_rate)? + get_fragment_count(coding_rate, phy_payload_size)?)
+}
+
+// Returns the header count and the coding-rate as numerator / denominator.
+fn get_coding_rate(coding_rate: &str) -> Result<(usize, usize, usize)> {
+    Ok(match coding_rate {
+        "2/6" | "1/3" => (3, 1, 3),
+        "4/6" | "2/3" => (2, 2, 3),
+        "4/8" | "1/2" => (2, 1, 2),
+        "5/6" => (2, 5, 6),
+        _ => return Err(anyhow!("Unknown LR-FHSS coding-rate: {}", coding_rate)),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_grid_steps() {
+        assert_eq!(35, get_grid_steps(137000).unwrap());
+        assert_eq!(86, get_grid_steps(336000).unwrap());
+        assert_eq!(60, get_grid_steps(1523000).unwrap());
+        assert!(get_grid_steps(125000).is_err());
+
+        assert!(validate_grid(137000, 0).is_ok());
+        assert!(validate_grid(137000, 35).is_ok());
+        assert!(validate_grid(137000, 86).is_err());
+    }
+
+    #[test]
+    fn test_hop_count() {
+        assert_eq!(3, get_header_count("2/6").unwrap());
+        assert_eq!(2, get_header_count("4/6").unwrap());
+
+        // 10 bytes + CRC + tail bits = 102 bits, at CR 1/3 = 306 coded bits.
+        assert_eq!(7, get_fragment_count("2/6", 10).unwrap());
+        assert_eq!(10, get_hop_count("2/6", 10).unwrap());
+
+        // At CR 2/3 = 153 coded bits.
+        assert_eq!(4, get_fragment_count("4/6", 10).unwrap());
+        assert_eq!(6, get_hop_count("4/6", 10).unwrap());
+    }
+}
@@ -17,15 +17,19 @@ use crate::{
     CFList, CFListChannelMasks, CFListChannels, ChMask, DevAddr, LinkADRReqPayload, Redundancy,
 };
 
+pub mod airtime;
 pub mod as923;
 pub mod au915;
 pub mod cn470;
 pub mod cn779;
 pub mod eu433;
 pub mod eu868;
+#[cfg(feature = "serde")]
+pub mod export;
 pub mod in865;
 pub mod ism2400;
 pub mod kr920;
+pub mod lr_fhss;
 pub mod ru864;
 pub mod us915;
 
@@ -285,6 +289,7 @@ where
 }
 
 #[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
 pub struct DataRate {
     pub uplink: bool,
     pub downlink: bool,
@@ -292,6 +297,8 @@ pub struct DataRate {
 }
 
 #[derive(Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum DataRateModulation {
     Lora(LoraDataRate),
     Fsk(FskDataRate),
@@ -299,6 +306,7 @@ pub enum DataRateModulation {
 }
 
 #[derive(Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
 pub struct LoraDataRate {
     pub spreading_factor: u8,
     pub bandwidth: u32,
@@ -306,11 +314,13 @@ pub struct LoraDataRate {
 }
 
 #[derive(Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
 pub struct FskDataRate {
     pub bitrate: u32,
 }
 
 #[derive(Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
 pub struct LrFhssDataRate {
     pub coding_rate: String,
     pub occupied_channel_width: u32,
@@ -323,9 +333,11 @@ pub struct Defaults {
     pub rx2_delay: Duration,
     pub join_accept_delay1: Duration,
     pub join_accept_delay2: Duration,
+    pub beacon_dr: u8,
 }
 
 #[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
 pub struct MaxPayloadSize {
     /// The maximum MACPayload size length.
     pub m: usize,
@@ -334,6 +346,7 @@ pub struct MaxPayloadSize {
 }
 
 #[derive(Clone, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
 pub struct Channel {
     pub frequency: u32,
     pub min_dr: u8,
@@ -457,6 +470,21 @@ pub trait Region {
 
     /// Returns if the device supports the TxParamSetup mac-command.
     fn implements_tx_param_setup(&self, mac_version: MacVersion) -> bool;
+
+    /// Returns the default max. uplink EIRP (dBm) for the band.
+    fn get_default_max_uplink_eirp(&self) -> f32;
+
+    /// Returns if the configuration has the 400ms dwell-time limitation applied.
+    fn get_dwell_time_400ms(&self) -> bool;
+
+    /// Returns if the band supports user-defined (extra) channels.
+    fn supports_user_channels(&self) -> bool;
+
+    /// Returns all the data-rates defined for the band, sorted by index.
+    fn get_data_rates(&self) -> Vec<(u8, DataRate)>;
+
+    /// Returns the Class-B beacon frequency for the given beacon time (time since GPS epoch).
+    fn get_beacon_frequency(&self, beacon_time: Duration) -> Result<u32>;
 }
 
 struct RegionBaseConfig {
@@ -472,6 +500,20 @@ struct RegionBaseConfig {
 }
 
 impl RegionBaseConfig {
+    fn supports_user_channels(&self) -> bool {
+        self.supports_user_channels
+    }
+
+    fn get_data_rates(&self) -> Vec<(u8, DataRate)> {
+        let mut out: Vec<(u8, DataRate)> = self
+            .data_rates
+            .iter()
+            .map(|(k, v)| (*k, v.clone()))
+            .collect();
+        out.sort_by_key(|(k, _)| *k);
+        out
+    }
+
     fn get_data_rate_index(&self, uplink: bool, modulation: &DataRateModulation) -> Result<u8> {
         for (i, dr) in &self.data_rates {
             if uplink != dr.uplink && uplink == dr.downlink {